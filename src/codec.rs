@@ -12,20 +12,27 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use crate::errors::Result;
+use crate::errors::{Error, Result};
+use crate::OpConfig;
+use tremor_pipeline::ConfigImpl;
 use tremor_script::Value;
+pub(crate) mod avro;
 pub(crate) mod binary;
 pub(crate) mod binflux;
+pub(crate) mod bson;
+pub(crate) mod cbor;
+pub(crate) mod csv;
 pub(crate) mod influx;
 pub(crate) mod json;
 pub(crate) mod msgpack;
 pub(crate) mod null;
+pub(crate) mod protobuf;
 pub(crate) mod statsd;
 pub(crate) mod string;
 pub(crate) mod syslog;
 pub(crate) mod yaml;
 
-const MIME_TYPES: [&str; 8] = [
+const MIME_TYPES: [&str; 12] = [
     "application/json",
     "application/yaml",
     "text/plain",
@@ -34,11 +41,18 @@ const MIME_TYPES: [&str; 8] = [
     "application/x-msgpack",
     "application/vnd.msgpack",
     "application/octet-stream",
+    "text/csv",
+    "text/tab-separated-values",
+    "application/cbor",
+    "application/bson",
 ];
 
 mod prelude {
     pub use super::Codec;
+    pub(crate) use super::{parse_config, parse_required_config};
     pub use crate::errors::*;
+    pub use crate::OpConfig;
+    pub use tremor_pipeline::ConfigImpl;
     pub use tremor_script::prelude::*;
     pub use tremor_script::{Object, Value};
 }
@@ -93,15 +107,55 @@ pub trait Codec: Send + Sync {
     fn boxed_clone(&self) -> Box<dyn Codec>;
 }
 
+/// Codec configuration as found in onramp and offramp configs,
+/// either just the name of a codec or its name and a codec specific config
+///
+/// e.g.:
+///       codec: json
+///
+/// or
+///       codec:
+///         name: json
+///         config:
+///           pretty: true
+pub type Config = crate::config::NameWithConfig;
+
 /// Codec lookup function
 ///
 /// # Errors
 ///  * if the codec doesn't exist
 pub fn lookup(name: &str) -> Result<Box<dyn Codec>> {
+    lookup_with_config(name, None)
+}
+
+/// Looks up the codec described by `config`
+///
+/// # Errors
+///  * if the codec doesn't exist or its config is invalid
+pub fn resolve(config: &Config) -> Result<Box<dyn Codec>> {
+    lookup_with_config(config.name(), config.config())
+}
+
+/// Codec lookup function, passing the codec specific `config` to the codec
+///
+/// # Errors
+///  * if the codec doesn't exist or its config is invalid
+pub fn lookup_with_config(name: &str, config: Option<&OpConfig>) -> Result<Box<dyn Codec>> {
     match name {
-        "json" => Ok(Box::new(json::Json::<json::Unsorted>::default())),
-        "json-sorted" => Ok(Box::new(json::Json::<json::Sorted>::default())),
-        "msgpack" => Ok(Box::new(msgpack::MsgPack {})),
+        "json" => Ok(Box::new(json::Json::<json::Unsorted>::from_config(config)?)),
+        "json-sorted" => Ok(Box::new(json::Json::<json::Sorted>::from_config(config)?)),
+        "syslog" => Ok(Box::new(syslog::Syslog::from_config(config)?)),
+        "csv" => Ok(Box::new(csv::Csv::from_config("csv", config)?)),
+        "tsv" => Ok(Box::new(csv::Csv::from_config("tsv", config)?)),
+        "protobuf" => Ok(Box::new(protobuf::Protobuf::from_config(config)?)),
+        "avro" => Ok(Box::new(avro::Avro::from_config(config)?)),
+        "msgpack" => Ok(Box::new(msgpack::MsgPack::from_config(config)?)),
+        "influx" | "binflux" | "null" | "string" | "statsd" | "yaml" | "binary" | "cbor"
+        | "bson"
+            if config.is_some() =>
+        {
+            Err(format!("Codec '{}' does not take a config.", name).into())
+        }
         "influx" => Ok(Box::new(influx::Influx {})),
         "binflux" => Ok(Box::new(binflux::BInflux {})),
         "null" => Ok(Box::new(null::Null {})),
@@ -109,11 +163,33 @@ pub fn lookup(name: &str) -> Result<Box<dyn Codec>> {
         "statsd" => Ok(Box::new(statsd::StatsD {})),
         "yaml" => Ok(Box::new(yaml::Yaml {})),
         "binary" => Ok(Box::new(binary::Binary {})),
-        "syslog" => Ok(Box::new(syslog::Syslog::utcnow())),
+        "cbor" => Ok(Box::new(cbor::Cbor {})),
+        "bson" => Ok(Box::new(bson::Bson {})),
         _ => Err(format!("Codec '{}' not found.", name).into()),
     }
 }
 
+/// parses the config of the codec `name`, using its defaults if no config was given
+fn parse_config<C>(name: &str, config: Option<&OpConfig>) -> Result<C>
+where
+    C: ConfigImpl + Default + for<'de> serde::Deserialize<'de>,
+{
+    config.map_or_else(
+        || Ok(C::default()),
+        |config| parse_required_config(name, Some(config)),
+    )
+}
+
+/// parses the config of the codec `name` for codecs that can't do without one
+fn parse_required_config<C>(name: &str, config: Option<&OpConfig>) -> Result<C>
+where
+    C: ConfigImpl + for<'de> serde::Deserialize<'de>,
+{
+    let config =
+        config.ok_or_else(|| Error::from(format!("Codec '{}' requires a config.", name)))?;
+    crate::config::parse_config("codec", name, config)
+}
+
 /// Map from Mime types to codecs for all builtin codecs mappable to Mime types
 /// these are all safe mappings
 /// if you have a specific codec to be used for a more unspecific mime type
@@ -137,9 +213,13 @@ pub fn by_mime_type(mime: &str) -> Result<Box<dyn Codec>> {
         "application/yaml" => Ok(Box::new(yaml::Yaml {})),
         "text/plain" | "text/html" => Ok(Box::new(string::String {})),
         "application/msgpack" | "application/x-msgpack" | "application/vnd.msgpack" => {
-            Ok(Box::new(msgpack::MsgPack {}))
+            Ok(Box::new(msgpack::MsgPack::default()))
         }
         "application/octet-stream" => Ok(Box::new(binary::Binary {})),
+        "text/csv" => Ok(Box::new(csv::Csv::from_config("csv", None)?)),
+        "text/tab-separated-values" => Ok(Box::new(csv::Csv::from_config("tsv", None)?)),
+        "application/cbor" => Ok(Box::new(cbor::Cbor {})),
+        "application/bson" => Ok(Box::new(bson::Bson {})),
         _ => Err(format!("No codec found for mime type '{}'", mime).into()),
     }
 }
@@ -159,12 +239,48 @@ mod test {
         assert!(super::lookup("statsd").is_ok());
         assert!(super::lookup("yaml").is_ok());
         assert!(super::lookup("syslog").is_ok());
+        assert!(super::lookup("csv").is_ok());
+        assert!(super::lookup("tsv").is_ok());
+        assert!(super::lookup("cbor").is_ok());
+        assert!(super::lookup("bson").is_ok());
+        assert_eq!(
+            super::lookup("protobuf").err().unwrap().to_string(),
+            "Codec 'protobuf' requires a config."
+        );
+        assert!(super::lookup("avro").is_err());
         assert_eq!(
             super::lookup("snot").err().unwrap().to_string(),
             "Codec 'snot' not found."
         )
     }
 
+    #[test]
+    fn lookup_with_config() {
+        let config: super::Config =
+            serde_yaml::from_str("{name: json, config: {pretty: true}}").unwrap();
+        assert_eq!("json", config.name());
+        assert!(super::resolve(&config).is_ok());
+
+        let config: super::Config = serde_yaml::from_str("syslog").unwrap();
+        assert_eq!(super::Config::from("syslog"), config);
+        assert!(super::resolve(&config).is_ok());
+
+        let config: super::Config =
+            serde_yaml::from_str("{name: json, config: {snot: true}}").unwrap();
+        assert!(super::resolve(&config)
+            .err()
+            .unwrap()
+            .to_string()
+            .starts_with("Invalid config for codec 'json': "));
+
+        let config: super::Config =
+            serde_yaml::from_str("{name: yaml, config: {pretty: true}}").unwrap();
+        assert_eq!(
+            super::resolve(&config).err().unwrap().to_string(),
+            "Codec 'yaml' does not take a config."
+        );
+    }
+
     #[test]
     fn builtin_codec_map() {
         let map = super::builtin_codec_map();
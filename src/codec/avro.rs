@@ -0,0 +1,451 @@
+// Copyright 2020-2021, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Apache Avro codec using the confluent wire format.
+//!
+//! Every message starts with a magic byte `0` followed by the big endian
+//! id of the writer schema, which is looked up in a schema registry.
+//!
+//! Schemas are cached and shared by all streams decoding with the same codec.
+//! Schemas that can't be fetched are not looked up again before a backoff,
+//! doubling with every failure, expired, messages using them fail right away.
+
+use super::prelude::*;
+use async_std::{future, task};
+use avro_rs::types::Value as AvroValue;
+use avro_rs::Schema;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+const MAGIC_BYTE: u8 = 0;
+const HEADER_LEN: usize = 5;
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// url of a confluent compatible schema registry
+    #[serde(default = "Default::default")]
+    pub registry: Option<String>,
+    /// directory holding `<schema id>.avsc` files, used if no `registry` is set
+    #[serde(default = "Default::default")]
+    pub schema_dir: Option<String>,
+    /// id of the schema used to encode events
+    #[serde(default = "Default::default")]
+    pub schema_id: Option<u32>,
+    /// subject whose latest schema is used to encode events, requires a `registry`
+    #[serde(default = "Default::default")]
+    pub subject: Option<String>,
+    /// ids of schemas fetched from the `registry` at deploy time, so decoding
+    /// doesn't have to wait for them, all schemas in `schema_dir` are loaded right away
+    #[serde(default = "Default::default")]
+    pub prefetch: Vec<u32>,
+    /// timeout for requests to the `registry` in milliseconds
+    #[serde(default = "default_timeout")]
+    pub timeout: u64,
+}
+
+fn default_timeout() -> u64 {
+    1000
+}
+
+impl ConfigImpl for Config {}
+
+#[derive(Deserialize)]
+struct RegistrySchema {
+    #[serde(default = "Default::default")]
+    id: Option<u32>,
+    schema: String,
+}
+
+#[derive(Clone, Debug)]
+enum Registry {
+    Url(String),
+    Dir(String),
+}
+
+impl Registry {
+    fn schema(&self, id: u32, timeout: Duration) -> Result<Schema> {
+        let schema = match self {
+            Self::Url(url) => fetch(&format!("{}/schemas/ids/{}", url, id), timeout)?.schema,
+            Self::Dir(dir) => std::fs::read_to_string(format!("{}/{}.avsc", dir, id))?,
+        };
+        Ok(Schema::parse_str(&schema)?)
+    }
+
+    /// all schemas that can be found without asking a registry
+    fn local_schemas(&self) -> Result<Vec<u32>> {
+        let mut ids = Vec::new();
+        if let Self::Dir(dir) = self {
+            for entry in std::fs::read_dir(dir)? {
+                let path = entry?.path();
+                if path.extension().map_or(false, |ext| ext == "avsc") {
+                    if let Some(id) = path
+                        .file_stem()
+                        .and_then(|stem| stem.to_str())
+                        .and_then(|stem| stem.parse().ok())
+                    {
+                        ids.push(id);
+                    }
+                }
+            }
+        }
+        Ok(ids)
+    }
+
+    fn latest(&self, subject: &str, timeout: Duration) -> Result<(u32, Schema)> {
+        match self {
+            Self::Url(url) => {
+                let latest = fetch(
+                    &format!("{}/subjects/{}/versions/latest", url, subject),
+                    timeout,
+                )?;
+                let id = latest.id.ok_or_else(|| {
+                    Error::from(format!("No schema id found for subject '{}'.", subject))
+                })?;
+                Ok((id, Schema::parse_str(&latest.schema)?))
+            }
+            Self::Dir(_) => Err(
+                "Invalid config for codec 'avro': a `subject` can only be used with a `registry`."
+                    .into(),
+            ),
+        }
+    }
+}
+
+fn fetch(url: &str, timeout: Duration) -> Result<RegistrySchema> {
+    let request = async {
+        let mut response = surf::get(url)
+            .header("Accept", "application/vnd.schemaregistry.v1+json")
+            .await?;
+        if !response.status().is_success() {
+            return Err(surf::Error::from_str(
+                response.status(),
+                format!("unexpected status {}", response.status()),
+            ));
+        }
+        response.body_bytes().await
+    };
+    let mut body = task::block_on(future::timeout(timeout, request))
+        .map_err(|_| format!("Timed out fetching avro schema from {}", url))?
+        .map_err(|e| Error::from(format!("Failed to fetch avro schema from {}: {}", url, e)))?;
+    Ok(simd_json::from_slice(&mut body)?)
+}
+
+/// a schema that could not be fetched
+struct Failure {
+    error: String,
+    retry_at: Instant,
+    backoff: Duration,
+}
+
+/// writer schemas seen while decoding and the ones that could not be fetched
+#[derive(Default)]
+struct Schemas {
+    known: HashMap<u32, Arc<Schema>>,
+    failed: HashMap<u32, Failure>,
+}
+
+#[derive(Clone)]
+pub struct Avro {
+    registry: Registry,
+    timeout: Duration,
+    schemas: Arc<Mutex<Schemas>>,
+    /// schema used for encoding and its id
+    encoding: Option<(u32, Arc<Schema>)>,
+}
+
+impl Avro {
+    pub fn from_config(config: Option<&OpConfig>) -> Result<Self> {
+        let config: Config = parse_required_config("avro", config)?;
+        let registry = match (config.registry, config.schema_dir) {
+            (Some(url), _) => Registry::Url(url.trim_end_matches('/').to_string()),
+            (None, Some(dir)) => Registry::Dir(dir),
+            (None, None) => return Err(
+                "Invalid config for codec 'avro': either `registry` or `schema_dir` is required."
+                    .into(),
+            ),
+        };
+        let timeout = Duration::from_millis(config.timeout);
+        // the encoding schema is resolved right away so misconfiguration surfaces at deploy time
+        let encoding = match (config.schema_id, config.subject) {
+            (Some(id), _) => Some((id, Arc::new(registry.schema(id, timeout)?))),
+            (None, Some(subject)) => {
+                let (id, schema) = registry.latest(&subject, timeout)?;
+                Some((id, Arc::new(schema)))
+            }
+            (None, None) => None,
+        };
+        let mut schemas = Schemas::default();
+        if let Some((id, schema)) = &encoding {
+            schemas.known.insert(*id, schema.clone());
+        }
+        let mut prefetch = config.prefetch;
+        prefetch.append(&mut registry.local_schemas()?);
+        for id in prefetch {
+            if !schemas.known.contains_key(&id) {
+                schemas
+                    .known
+                    .insert(id, Arc::new(registry.schema(id, timeout)?));
+            }
+        }
+        Ok(Self {
+            registry,
+            timeout,
+            schemas: Arc::new(Mutex::new(schemas)),
+            encoding,
+        })
+    }
+
+    fn schema(&self, id: u32) -> Result<Arc<Schema>> {
+        let mut schemas = self
+            .schemas
+            .lock()
+            .map_err(|_| Error::from("Avro schema cache is poisoned."))?;
+        if let Some(schema) = schemas.known.get(&id) {
+            return Ok(schema.clone());
+        }
+        let now = Instant::now();
+        let backoff = match schemas.failed.get(&id) {
+            Some(failure) if now < failure.retry_at => {
+                return Err(format!("Avro schema {} is unavailable: {}", id, failure.error).into());
+            }
+            Some(failure) => (failure.backoff * 2).min(MAX_BACKOFF),
+            None => MIN_BACKOFF,
+        };
+        match self.registry.schema(id, self.timeout) {
+            Ok(schema) => {
+                let schema = Arc::new(schema);
+                schemas.failed.remove(&id);
+                schemas.known.insert(id, schema.clone());
+                Ok(schema)
+            }
+            Err(e) => {
+                schemas.failed.insert(
+                    id,
+                    Failure {
+                        error: e.to_string(),
+                        retry_at: now + backoff,
+                        backoff,
+                    },
+                );
+                Err(e)
+            }
+        }
+    }
+}
+
+fn to_value(value: AvroValue) -> Result<Value<'static>> {
+    Ok(match value {
+        AvroValue::Null => Value::null(),
+        AvroValue::Boolean(b) => Value::from(b),
+        AvroValue::Int(i) | AvroValue::Date(i) | AvroValue::TimeMillis(i) => Value::from(i),
+        AvroValue::Long(i)
+        | AvroValue::TimeMicros(i)
+        | AvroValue::TimestampMillis(i)
+        | AvroValue::TimestampMicros(i) => Value::from(i),
+        AvroValue::Float(f) => Value::from(f64::from(f)),
+        AvroValue::Double(f) => Value::from(f),
+        AvroValue::Bytes(b) | AvroValue::Fixed(_, b) => Value::Bytes(b.into()),
+        AvroValue::String(s) | AvroValue::Enum(_, s) => Value::from(s),
+        AvroValue::Uuid(u) => Value::from(u.to_string()),
+        AvroValue::Union(v) => to_value(*v)?,
+        AvroValue::Array(a) => {
+            Value::from(a.into_iter().map(to_value).collect::<Result<Vec<_>>>()?)
+        }
+        AvroValue::Map(m) => to_record(m.len(), m)?,
+        AvroValue::Record(r) => to_record(r.len(), r)?,
+        other => return Err(format!("Unsupported avro value: {:?}", other).into()),
+    })
+}
+
+fn to_record<I>(len: usize, fields: I) -> Result<Value<'static>>
+where
+    I: IntoIterator<Item = (String, AvroValue)>,
+{
+    let mut record = Object::with_capacity(len);
+    for (k, v) in fields {
+        record.insert(k.into(), to_value(v)?);
+    }
+    Ok(Value::from(record))
+}
+
+/// converts a value into an avro value, it still has to be resolved against a schema
+fn to_avro(value: &Value) -> Result<AvroValue> {
+    if value.is_null() {
+        Ok(AvroValue::Null)
+    } else if let Some(b) = value.as_bool() {
+        Ok(AvroValue::Boolean(b))
+    } else if let Some(i) = value.as_i64() {
+        Ok(AvroValue::Long(i))
+    } else if let Some(f) = value.as_f64() {
+        Ok(AvroValue::Double(f))
+    } else if let Some(s) = value.as_str() {
+        Ok(AvroValue::String(s.to_string()))
+    } else if let Some(b) = value.as_bytes() {
+        Ok(AvroValue::Bytes(b.to_vec()))
+    } else if let Some(a) = value.as_array() {
+        Ok(AvroValue::Array(
+            a.iter().map(to_avro).collect::<Result<Vec<_>>>()?,
+        ))
+    } else if let Some(o) = value.as_object() {
+        let mut map = HashMap::with_capacity(o.len());
+        for (k, v) in o.iter() {
+            map.insert(k.to_string(), to_avro(v)?);
+        }
+        Ok(AvroValue::Map(map))
+    } else {
+        Err(format!("Value {} can not be encoded as avro.", value.encode()).into())
+    }
+}
+
+impl Codec for Avro {
+    #[cfg(not(tarpaulin_include))]
+    fn name(&self) -> &str {
+        "avro"
+    }
+
+    #[cfg(not(tarpaulin_include))]
+    fn mime_types(&self) -> Vec<&str> {
+        vec!["application/vnd.kafka.avro.v2+json", "avro/binary"]
+    }
+
+    fn decode<'input>(
+        &mut self,
+        data: &'input mut [u8],
+        _ingest_ns: u64,
+    ) -> Result<Option<Value<'input>>> {
+        if data.len() < HEADER_LEN || data[0] != MAGIC_BYTE {
+            return Err("Invalid avro data: missing magic byte and schema id.".into());
+        }
+        let id = u32::from_be_bytes([data[1], data[2], data[3], data[4]]);
+        let schema = self.schema(id)?;
+        let mut datum = &data[HEADER_LEN..];
+        let value = avro_rs::from_avro_datum(&schema, &mut datum, None)?;
+        to_value(value).map(Some)
+    }
+
+    fn encode(&self, data: &Value) -> Result<Vec<u8>> {
+        let (id, schema) = self.encoding.as_ref().ok_or_else(|| {
+            Error::from("Codec 'avro' requires a `schema_id` or `subject` to encode events.")
+        })?;
+        let value = to_avro(data)?.resolve(schema)?;
+        let mut res = Vec::with_capacity(128);
+        res.push(MAGIC_BYTE);
+        res.extend_from_slice(&id.to_be_bytes());
+        res.append(&mut avro_rs::to_avro_datum(schema, value)?);
+        Ok(res)
+    }
+
+    #[cfg(not(tarpaulin_include))]
+    fn boxed_clone(&self) -> Box<dyn Codec> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tremor_value::literal;
+
+    const SCHEMA: &str = r#"{
+        "type": "record",
+        "name": "Badger",
+        "fields": [
+            {"name": "name", "type": "string"},
+            {"name": "count", "type": "int"},
+            {"name": "tag", "type": ["null", "string"]},
+            {"name": "kind", "type": {"type": "enum", "name": "Kind", "symbols": ["SNOT", "BADGER"]}}
+        ]
+    }"#;
+
+    fn codec(dir: &tempfile::TempDir, config: &str) -> Result<Avro> {
+        std::fs::write(dir.path().join("42.avsc"), SCHEMA)?;
+        let config: OpConfig = serde_yaml::from_str(&format!(
+            "{{schema_dir: '{}', {}}}",
+            dir.path().display(),
+            config
+        ))?;
+        Avro::from_config(Some(&config))
+    }
+
+    #[test]
+    fn roundtrip() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let mut codec = codec(&dir, "schema_id: 42")?;
+        let event = literal!({"name": "snot", "count": 3, "tag": null, "kind": "BADGER"});
+        let mut data = codec.encode(&event)?;
+        assert_eq!(&[0, 0, 0, 0, 42], &data[..HEADER_LEN]);
+        assert_eq!(Some(event), codec.decode(&mut data, 0)?);
+
+        let event = literal!({"name": "badger", "count": 7, "tag": "snot", "kind": "SNOT"});
+        let mut data = codec.encode(&event)?;
+        assert_eq!(Some(event), codec.decode(&mut data, 0)?);
+        Ok(())
+    }
+
+    #[test]
+    fn decode_only() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let encoder = codec(&dir, "schema_id: 42")?;
+        let mut data =
+            encoder.encode(&literal!({"name": "snot", "count": 1, "tag": null, "kind": "SNOT"}))?;
+        // the writer schema is looked up by the id in the message
+        let mut decoder = codec(&dir, "subject: null")?;
+        assert!(decoder.decode(&mut data, 0)?.is_some());
+        assert!(decoder.encode(&literal!({})).is_err());
+
+        let mut data = vec![1, 0, 0, 0, 42];
+        assert!(decoder.decode(&mut data, 0).is_err());
+        let mut data = vec![0, 0, 0, 0, 23, 0];
+        assert!(decoder.decode(&mut data, 0).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn failed_schemas_backoff() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let encoder = codec(&dir, "schema_id: 42")?;
+        let mut data =
+            encoder.encode(&literal!({"name": "snot", "count": 1, "tag": null, "kind": "SNOT"}))?;
+        let mut decoder = codec(&dir, "subject: null")?;
+        // the schema dir is loaded right away
+        std::fs::remove_file(dir.path().join("42.avsc"))?;
+        assert!(decoder.decode(&mut data, 0)?.is_some());
+
+        // schema 23 doesn't exist, it isn't looked up again until its backoff expired
+        let mut data = vec![0, 0, 0, 0, 23, 0];
+        assert!(decoder.decode(&mut data, 0).is_err());
+        std::fs::write(dir.path().join("23.avsc"), SCHEMA)?;
+        let err = decoder.decode(&mut data, 0).err().unwrap().to_string();
+        assert!(err.starts_with("Avro schema 23 is unavailable: "));
+        // the failure is shared with other streams
+        let mut other = decoder.clone();
+        assert!(other.decode(&mut data, 0).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn bad_config() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        assert!(codec(&dir, "schema_id: 23").is_err());
+        assert!(codec(&dir, "subject: snot").is_err());
+        assert!(Avro::from_config(None).is_err());
+        let config: OpConfig = serde_yaml::from_str("{schema_id: 42}")?;
+        assert!(Avro::from_config(Some(&config)).is_err());
+        Ok(())
+    }
+}
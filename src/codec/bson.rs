@@ -0,0 +1,160 @@
+// Copyright 2020-2021, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! BSON codec, events are encoded as documents so they need to be records.
+//!
+//! BSON specific types without a tremor counterpart are decoded in the
+//! spirit of MongoDB extended JSON, e.g. object ids become `{"$oid": "..."}`.
+
+use super::prelude::*;
+use bson::{Bson as BsonValue, Document};
+
+#[derive(Clone)]
+pub struct Bson {}
+
+fn to_value(bson: BsonValue) -> Value<'static> {
+    match bson {
+        BsonValue::Null | BsonValue::Undefined => Value::null(),
+        BsonValue::Boolean(b) => Value::from(b),
+        BsonValue::Int32(i) => Value::from(i),
+        BsonValue::Int64(i) => Value::from(i),
+        BsonValue::Double(f) => Value::from(f),
+        BsonValue::String(s) | BsonValue::Symbol(s) | BsonValue::JavaScriptCode(s) => {
+            Value::from(s)
+        }
+        BsonValue::Binary(b) => Value::Bytes(b.bytes.into()),
+        BsonValue::Array(a) => Value::from(a.into_iter().map(to_value).collect::<Vec<_>>()),
+        BsonValue::Document(d) => to_record(d),
+        BsonValue::ObjectId(oid) => extended("$oid", Value::from(oid.to_hex())),
+        BsonValue::DateTime(dt) => extended("$date", Value::from(dt.timestamp_millis())),
+        other => Value::from(other.to_string()),
+    }
+}
+
+fn extended(key: &'static str, value: Value<'static>) -> Value<'static> {
+    let mut record = Object::with_capacity(1);
+    record.insert(key.into(), value);
+    Value::from(record)
+}
+
+fn to_record(doc: Document) -> Value<'static> {
+    let mut record = Object::with_capacity(doc.len());
+    for (k, v) in doc {
+        record.insert(k.into(), to_value(v));
+    }
+    Value::from(record)
+}
+
+fn to_bson(value: &Value) -> Result<BsonValue> {
+    if value.is_null() {
+        Ok(BsonValue::Null)
+    } else if let Some(b) = value.as_bool() {
+        Ok(BsonValue::Boolean(b))
+    } else if let Some(i) = value.as_i64() {
+        Ok(BsonValue::Int64(i))
+    } else if let Some(f) = value.as_f64() {
+        Ok(BsonValue::Double(f))
+    } else if let Some(s) = value.as_str() {
+        Ok(BsonValue::String(s.to_string()))
+    } else if let Some(b) = value.as_bytes() {
+        Ok(BsonValue::Binary(bson::Binary {
+            subtype: bson::spec::BinarySubtype::Generic,
+            bytes: b.to_vec(),
+        }))
+    } else if let Some(a) = value.as_array() {
+        Ok(BsonValue::Array(
+            a.iter().map(to_bson).collect::<Result<Vec<_>>>()?,
+        ))
+    } else if let Some(o) = value.as_object() {
+        let mut doc = Document::new();
+        for (k, v) in o.iter() {
+            doc.insert(k.to_string(), to_bson(v)?);
+        }
+        Ok(BsonValue::Document(doc))
+    } else {
+        Err(format!("Value {} can not be encoded as BSON.", value.encode()).into())
+    }
+}
+
+impl Codec for Bson {
+    #[cfg(not(tarpaulin_include))]
+    fn name(&self) -> &str {
+        "bson"
+    }
+
+    #[cfg(not(tarpaulin_include))]
+    fn mime_types(&self) -> Vec<&str> {
+        vec!["application/bson"]
+    }
+
+    fn decode<'input>(
+        &mut self,
+        data: &'input mut [u8],
+        _ingest_ns: u64,
+    ) -> Result<Option<Value<'input>>> {
+        let mut reader: &[u8] = data;
+        let doc = Document::from_reader(&mut reader)?;
+        Ok(Some(to_record(doc)))
+    }
+
+    fn encode(&self, data: &Value) -> Result<Vec<u8>> {
+        if let BsonValue::Document(doc) = to_bson(data)? {
+            let mut res = Vec::with_capacity(128);
+            doc.to_writer(&mut res)?;
+            Ok(res)
+        } else {
+            Err("BSON can only encode records.".into())
+        }
+    }
+
+    #[cfg(not(tarpaulin_include))]
+    fn boxed_clone(&self) -> Box<dyn Codec> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tremor_value::literal;
+
+    #[test]
+    fn test_bson_codec() -> Result<()> {
+        let mut seed = literal!({ "snot": "badger", "n": [-1, 3.5, null, true], "o": {"a": []} });
+        seed.insert("bytes", Value::Bytes(vec![0_u8, 1, 2].into()))?;
+
+        let mut codec = Bson {};
+        let mut as_raw = codec.encode(&seed)?;
+        let decoded = codec.decode(as_raw.as_mut_slice(), 0)?;
+        assert_eq!(Some(seed), decoded);
+
+        assert!(codec.encode(&literal!([1, 2])).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_bson_object_id() -> Result<()> {
+        let mut doc = Document::new();
+        doc.insert("_id", bson::oid::ObjectId::with_bytes([1; 12]));
+        let mut data = Vec::new();
+        doc.to_writer(&mut data)?;
+
+        let mut codec = Bson {};
+        assert_eq!(
+            Some(literal!({"_id": {"$oid": "010101010101010101010101"}})),
+            codec.decode(&mut data, 0)?
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,84 @@
+// Copyright 2020-2021, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::prelude::*;
+
+#[derive(Clone)]
+pub struct Cbor {}
+
+impl Codec for Cbor {
+    #[cfg(not(tarpaulin_include))]
+    fn name(&self) -> &str {
+        "cbor"
+    }
+
+    #[cfg(not(tarpaulin_include))]
+    fn mime_types(&self) -> Vec<&str> {
+        vec!["application/cbor"]
+    }
+
+    fn decode<'input>(
+        &mut self,
+        data: &'input mut [u8],
+        _ingest_ns: u64,
+    ) -> Result<Option<Value<'input>>> {
+        serde_cbor::from_slice::<Value>(data)
+            .map(Some)
+            .map_err(|e| e.into())
+    }
+
+    fn encode(&self, data: &Value) -> Result<Vec<u8>> {
+        Ok(serde_cbor::to_vec(&data)?)
+    }
+
+    #[cfg(not(tarpaulin_include))]
+    fn boxed_clone(&self) -> Box<dyn Codec> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tremor_value::literal;
+
+    #[test]
+    fn test_cbor_codec() -> Result<()> {
+        let mut seed = literal!({ "snot": "badger", "n": [1, -2, 3.5, null, true] });
+        seed.insert("bytes", Value::Bytes(vec![0_u8, 1, 2].into()))?;
+
+        let mut codec = Cbor {};
+        let mut as_raw = codec.encode(&seed)?;
+        let decoded = codec.decode(as_raw.as_mut_slice(), 0)?;
+        assert_eq!(Some(seed), decoded);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_cbor_decode() -> Result<()> {
+        let mut codec = Cbor {};
+        // {"a": h'0102'}
+        let mut data = vec![0xa1, 0x61, 0x61, 0x42, 0x01, 0x02];
+        let decoded = codec.decode(&mut data, 0)?;
+        assert_eq!(
+            Some(&[1_u8, 2][..]),
+            decoded
+                .as_ref()
+                .and_then(|v| v.get("a"))
+                .and_then(Value::as_bytes)
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,277 @@
+// Copyright 2020-2021, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Delimited text codec, decoding a single line into a record or an array.
+//!
+//! Meant to be combined with the `lines` preprocessor and postprocessor,
+//! as every decoded buffer is expected to hold a single line.
+
+use super::prelude::*;
+use std::collections::BTreeSet;
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// field separator, `,` for `csv` and a tab for `tsv`
+    #[serde(default = "Default::default")]
+    pub separator: Option<char>,
+    /// quote character, `"` by default
+    #[serde(default = "Default::default")]
+    pub quote: Option<char>,
+    /// column names, lines are decoded into arrays if no headers are known
+    #[serde(default = "Default::default")]
+    pub headers: Option<Vec<String>>,
+    /// take the column names from the first decoded line of every stream,
+    /// if `headers` are configured as well the first line is skipped
+    #[serde(default = "Default::default")]
+    pub header_line: bool,
+}
+
+impl ConfigImpl for Config {}
+
+#[derive(Clone, Debug)]
+pub struct Csv {
+    name: &'static str,
+    separator: u8,
+    quote: u8,
+    headers: Option<Vec<String>>,
+    expect_header_line: bool,
+}
+
+impl Csv {
+    pub fn from_config(name: &'static str, config: Option<&OpConfig>) -> Result<Self> {
+        let config: Config = parse_config(name, config)?;
+        let default_separator = if name == "tsv" { '\t' } else { ',' };
+        Ok(Self {
+            name,
+            separator: as_byte(
+                name,
+                "separator",
+                config.separator.unwrap_or(default_separator),
+            )?,
+            quote: as_byte(name, "quote", config.quote.unwrap_or('"'))?,
+            headers: config.headers,
+            expect_header_line: config.header_line,
+        })
+    }
+
+    fn reader<'data>(&self, data: &'data [u8]) -> csv::Reader<&'data [u8]> {
+        csv::ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .delimiter(self.separator)
+            .quote(self.quote)
+            .from_reader(data)
+    }
+
+    fn write_line<'cells, I>(&self, cells: I) -> Result<Vec<u8>>
+    where
+        I: IntoIterator<Item = &'cells str>,
+    {
+        let mut writer = csv::WriterBuilder::new()
+            .has_headers(false)
+            .delimiter(self.separator)
+            .quote(self.quote)
+            .terminator(csv::Terminator::Any(b'\n'))
+            .from_writer(Vec::with_capacity(128));
+        writer.write_record(cells)?;
+        let mut line = writer
+            .into_inner()
+            .map_err(|e| Error::from(format!("Failed to encode {}: {}", self.name, e)))?;
+        // the line terminator is added by the `lines` postprocessor
+        line.pop();
+        Ok(line)
+    }
+}
+
+fn as_byte(name: &str, field: &str, c: char) -> Result<u8> {
+    if c.is_ascii() {
+        Ok(c as u8)
+    } else {
+        Err(format!(
+            "Invalid config for codec '{}': {} must be an ascii character.",
+            name, field
+        )
+        .into())
+    }
+}
+
+/// Turns a value into the content of a single cell
+fn cell(value: Option<&Value>) -> String {
+    match value {
+        None => String::new(),
+        Some(v) if v.is_null() => String::new(),
+        Some(v) => v.as_str().map_or_else(|| v.encode(), ToString::to_string),
+    }
+}
+
+impl Codec for Csv {
+    #[cfg(not(tarpaulin_include))]
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    #[cfg(not(tarpaulin_include))]
+    fn mime_types(&self) -> Vec<&str> {
+        if self.name == "tsv" {
+            vec!["text/tab-separated-values"]
+        } else {
+            vec!["text/csv"]
+        }
+    }
+
+    fn decode<'input>(
+        &mut self,
+        data: &'input mut [u8],
+        _ingest_ns: u64,
+    ) -> Result<Option<Value<'input>>> {
+        let mut records = self.reader(data).into_records();
+        let record = match records.next() {
+            Some(record) => record?,
+            None => return Ok(None),
+        };
+        if records.next().is_some() {
+            return Err(format!("Codec '{}' expects a single line per event.", self.name).into());
+        }
+        if self.expect_header_line {
+            self.expect_header_line = false;
+            if self.headers.is_none() {
+                self.headers = Some(record.iter().map(ToString::to_string).collect());
+            }
+            return Ok(None);
+        }
+        if let Some(headers) = &self.headers {
+            let mut obj = Object::with_capacity(headers.len());
+            for (i, column) in headers.iter().enumerate() {
+                let v = record
+                    .get(i)
+                    .map_or_else(Value::null, |c| Value::from(c.to_string()));
+                obj.insert(column.clone().into(), v);
+            }
+            Ok(Some(Value::from(obj)))
+        } else {
+            Ok(Some(Value::from(
+                record
+                    .iter()
+                    .map(|c| Value::from(c.to_string()))
+                    .collect::<Vec<_>>(),
+            )))
+        }
+    }
+
+    fn encode(&self, data: &Value) -> Result<Vec<u8>> {
+        if let Some(a) = data.as_array() {
+            let cells: Vec<String> = a.iter().map(|v| cell(Some(v))).collect();
+            self.write_line(cells.iter().map(String::as_str))
+        } else if let Some(o) = data.as_object() {
+            // without headers columns are ordered by name so all lines line up
+            let cells: Vec<String> = if let Some(headers) = &self.headers {
+                headers.iter().map(|h| cell(o.get(h.as_str()))).collect()
+            } else {
+                let columns: BTreeSet<&str> = o.keys().map(|k| &**k).collect();
+                columns.into_iter().map(|k| cell(o.get(k))).collect()
+            };
+            self.write_line(cells.iter().map(String::as_str))
+        } else {
+            Err(format!("Codec '{}' can only encode records and arrays.", self.name).into())
+        }
+    }
+
+    #[cfg(not(tarpaulin_include))]
+    fn boxed_clone(&self) -> Box<dyn Codec> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tremor_value::literal;
+
+    fn csv(config: &str) -> Result<Csv> {
+        let config: OpConfig = serde_yaml::from_str(config)?;
+        Csv::from_config("csv", Some(&config))
+    }
+
+    #[test]
+    fn decode_array() -> Result<()> {
+        let mut codec = Csv::from_config("csv", None)?;
+        let mut data = br#"snot,"badger, badger",42"#.to_vec();
+        assert_eq!(
+            Some(literal!(["snot", "badger, badger", "42"])),
+            codec.decode(&mut data, 0)?
+        );
+        let mut data = b"".to_vec();
+        assert_eq!(None, codec.decode(&mut data, 0)?);
+        let mut data = b"snot\nbadger".to_vec();
+        assert!(codec.decode(&mut data, 0).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn decode_with_headers() -> Result<()> {
+        let mut codec = csv("{headers: [a, b, c]}")?;
+        let mut data = b"1,2".to_vec();
+        assert_eq!(
+            Some(literal!({"a": "1", "b": "2", "c": null})),
+            codec.decode(&mut data, 0)?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn decode_with_header_line() -> Result<()> {
+        let mut codec = csv("{header_line: true, separator: ';'}")?;
+        let mut header = b"name;count".to_vec();
+        assert_eq!(None, codec.decode(&mut header, 0)?);
+        let mut data = b"snot;3".to_vec();
+        assert_eq!(
+            Some(literal!({"name": "snot", "count": "3"})),
+            codec.decode(&mut data, 0)?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn encode() -> Result<()> {
+        let codec = Csv::from_config("csv", None)?;
+        let data = literal!({"b": "badger, badger", "a": 1, "c": null, "d": [1]});
+        assert_eq!(br#"1,"badger, badger",,[1]"#.to_vec(), codec.encode(&data)?);
+        assert_eq!(b"1,snot".to_vec(), codec.encode(&literal!([1, "snot"]))?);
+        assert!(codec.encode(&literal!("snot")).is_err());
+
+        let codec = csv("{headers: [c, a]}")?;
+        assert_eq!(b",1".to_vec(), codec.encode(&literal!({"a": 1, "b": 2}))?);
+        Ok(())
+    }
+
+    #[test]
+    fn tsv() -> Result<()> {
+        let mut codec = Csv::from_config("tsv", None)?;
+        let mut data = b"snot\tbadger".to_vec();
+        let decoded = codec.decode(&mut data, 0)?;
+        assert_eq!(Some(literal!(["snot", "badger"])), decoded);
+        assert_eq!(
+            b"snot\tbadger".to_vec(),
+            codec.encode(&literal!(["snot", "badger"]))?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn bad_config() {
+        assert!(csv("{separator: 'ä'}").is_err());
+        assert!(csv("{snot: badger}").is_err());
+    }
+}
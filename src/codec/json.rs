@@ -36,15 +36,44 @@ impl Sorting for Sorted {
     const SORTED: bool = true;
 }
 
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// pretty print encoded json
+    #[serde(default = "Default::default")]
+    pub pretty: bool,
+}
+
+impl ConfigImpl for Config {}
+
 pub struct Json<S: Sorting> {
     _phantom: PhantomData<S>,
     input_buffer: AlignedBuf,
     string_buffer: Vec<u8>,
+    config: Config,
+}
+
+impl<S: Sorting> Json<S> {
+    pub fn from_config(config: Option<&OpConfig>) -> Result<Self> {
+        let config: Config = parse_config(if S::SORTED { "json-sorted" } else { "json" }, config)?;
+        if S::SORTED && config.pretty {
+            return Err(
+                "Invalid config for codec 'json-sorted': pretty printing is not supported.".into(),
+            );
+        }
+        Ok(Self {
+            config,
+            ..Self::default()
+        })
+    }
 }
 
 impl<S: Sorting> Clone for Json<S> {
     fn clone(&self) -> Self {
-        Self::default()
+        Self {
+            config: self.config.clone(),
+            ..Self::default()
+        }
     }
 }
 
@@ -54,6 +83,7 @@ impl<S: Sorting> Default for Json<S> {
             _phantom: PhantomData::default(),
             input_buffer: AlignedBuf::with_capacity(1024),
             string_buffer: Vec::with_capacity(1024),
+            config: Config::default(),
         }
     }
 }
@@ -102,7 +132,11 @@ impl<S: Sorting> Codec for Json<S> {
         }
     }
     fn encode_into(&self, data: &Value, dst: &mut Vec<u8>) -> Result<()> {
-        data.write(dst)?;
+        if self.config.pretty {
+            data.write_pp(dst)?;
+        } else {
+            data.write(dst)?;
+        }
         Ok(())
     }
 
@@ -152,6 +186,23 @@ mod test {
 
         Ok(())
     }
+    #[test]
+    fn test_json_codec_pretty() -> Result<()> {
+        let config = serde_yaml::from_str("pretty: true")?;
+        let mut codec = Json::<Unsorted>::from_config(Some(&config))?;
+        let seed = literal!({ "snot": "badger" });
+
+        let mut as_raw = codec.encode(&seed)?;
+        assert_eq!(
+            "{\n  \"snot\": \"badger\"\n}",
+            std::str::from_utf8(&as_raw)?
+        );
+        assert_eq!(Some(seed), codec.decode(as_raw.as_mut_slice(), 0)?);
+
+        assert!(Json::<Sorted>::from_config(Some(&config)).is_err());
+        Ok(())
+    }
+
     #[test]
     fn test_json_codec_sorted() -> Result<()> {
         let seed = literal!({ "snot": "badger" });
@@ -15,8 +15,28 @@
 use super::prelude::*;
 use rmp_serde as rmps;
 
-#[derive(Clone)]
-pub struct MsgPack {}
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// encode structs as maps keyed by their field names instead of arrays
+    #[serde(default = "Default::default")]
+    pub struct_map: bool,
+}
+
+impl ConfigImpl for Config {}
+
+#[derive(Clone, Default)]
+pub struct MsgPack {
+    config: Config,
+}
+
+impl MsgPack {
+    pub fn from_config(config: Option<&OpConfig>) -> Result<Self> {
+        Ok(Self {
+            config: parse_config("msgpack", config)?,
+        })
+    }
+}
 
 impl Codec for MsgPack {
     #[cfg(not(tarpaulin_include))]
@@ -44,7 +64,11 @@ impl Codec for MsgPack {
     }
 
     fn encode(&self, data: &Value) -> Result<Vec<u8>> {
-        Ok(rmps::to_vec(&data)?)
+        if self.config.struct_map {
+            Ok(rmps::to_vec_named(&data)?)
+        } else {
+            Ok(rmps::to_vec(&data)?)
+        }
     }
 
     #[cfg(not(tarpaulin_include))]
@@ -62,7 +86,7 @@ mod test {
     fn test_msgpack_codec() -> Result<()> {
         let seed = literal!({ "snot": "badger" });
 
-        let mut codec = MsgPack {};
+        let mut codec = MsgPack::default();
         let mut as_raw = codec.encode(&seed)?;
         let as_json = codec.decode(as_raw.as_mut_slice(), 0);
 
@@ -70,4 +94,19 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn test_msgpack_codec_struct_map() -> Result<()> {
+        let seed = literal!({ "snot": ["badger", {"a": true}] });
+
+        let config = serde_yaml::from_str("struct_map: true")?;
+        let mut codec = MsgPack::from_config(Some(&config))?;
+        let mut as_raw = codec.encode(&seed)?;
+        let decoded = codec.decode(as_raw.as_mut_slice(), 0)?;
+        assert_eq!(Some(seed), decoded);
+
+        let config = serde_yaml::from_str("struct_as_map: true")?;
+        assert!(MsgPack::from_config(Some(&config)).is_err());
+        Ok(())
+    }
 }
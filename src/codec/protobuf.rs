@@ -0,0 +1,855 @@
+// Copyright 2020-2021, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Protocol buffers codec for arbitrary messages described by a descriptor set.
+//!
+//! Messages are decoded into records keyed by field name, repeated fields
+//! into arrays and map fields into records. Fields not present on the wire
+//! are not added to the decoded record.
+
+use super::prelude::*;
+use bytes::Buf;
+use prost::encoding::{decode_key, decode_varint, encode_key, encode_varint, WireType};
+use prost::Message;
+use prost_types::field_descriptor_proto::{Label, Type};
+use prost_types::{DescriptorProto, EnumDescriptorProto, FileDescriptorSet};
+use std::collections::HashMap;
+use std::process::Command;
+use std::sync::Arc;
+
+/// nesting depth of messages we decode at most, recursive message types
+/// would otherwise let crafted input exhaust the stack
+const MAX_DEPTH: usize = 100;
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// fully qualified name of the message type, e.g. `acme.events.Click`
+    pub message: String,
+    /// path to a descriptor set as written by
+    /// `protoc --include_imports --descriptor_set_out=<file>`
+    #[serde(default = "Default::default")]
+    pub descriptor_set: Option<String>,
+    /// `.proto` files to compile into a descriptor set using `protoc`
+    /// (or the binary set in the `PROTOC` environment variable)
+    #[serde(default = "Default::default")]
+    pub proto_files: Vec<String>,
+    /// include paths used when compiling `proto_files`
+    #[serde(default = "Default::default")]
+    pub include_paths: Vec<String>,
+}
+
+impl ConfigImpl for Config {}
+
+#[derive(Debug)]
+struct Field {
+    name: String,
+    number: u32,
+    kind: Type,
+    repeated: bool,
+    packed: bool,
+    type_name: String,
+}
+
+impl Field {
+    fn wire_type(&self) -> WireType {
+        match self.kind {
+            Type::Double | Type::Fixed64 | Type::Sfixed64 => WireType::SixtyFourBit,
+            Type::Float | Type::Fixed32 | Type::Sfixed32 => WireType::ThirtyTwoBit,
+            Type::String | Type::Bytes | Type::Message => WireType::LengthDelimited,
+            Type::Group => WireType::StartGroup,
+            _ => WireType::Varint,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct MessageDescriptor {
+    /// fields ordered by their number
+    fields: Vec<Field>,
+    map_entry: bool,
+}
+
+impl MessageDescriptor {
+    fn by_number(&self, number: u32) -> Option<&Field> {
+        self.fields
+            .binary_search_by_key(&number, |f| f.number)
+            .ok()
+            .and_then(|i| self.fields.get(i))
+    }
+    fn by_name(&self, name: &str) -> Option<&Field> {
+        self.fields.iter().find(|f| f.name == name)
+    }
+}
+
+/// Messages and enums of a descriptor set by their fully qualified name
+#[derive(Debug, Default)]
+struct Descriptors {
+    messages: HashMap<String, MessageDescriptor>,
+    enums: HashMap<String, Vec<(i32, String)>>,
+}
+
+impl Descriptors {
+    fn from_set(set: &FileDescriptorSet) -> Self {
+        let mut descriptors = Self::default();
+        for file in &set.file {
+            let proto3 = file.syntax() == "proto3";
+            let prefix = if file.package().is_empty() {
+                String::new()
+            } else {
+                format!(".{}", file.package())
+            };
+            for e in &file.enum_type {
+                descriptors.add_enum(&prefix, e);
+            }
+            for m in &file.message_type {
+                descriptors.add_message(&prefix, m, proto3);
+            }
+        }
+        descriptors
+    }
+
+    fn add_enum(&mut self, prefix: &str, e: &EnumDescriptorProto) {
+        let values = e
+            .value
+            .iter()
+            .map(|v| (v.number(), v.name().to_string()))
+            .collect();
+        self.enums
+            .insert(format!("{}.{}", prefix, e.name()), values);
+    }
+
+    #[allow(clippy::cast_sign_loss)]
+    fn add_message(&mut self, prefix: &str, m: &DescriptorProto, proto3: bool) {
+        let name = format!("{}.{}", prefix, m.name());
+        for e in &m.enum_type {
+            self.add_enum(&name, e);
+        }
+        for nested in &m.nested_type {
+            self.add_message(&name, nested, proto3);
+        }
+        let mut fields: Vec<Field> = m
+            .field
+            .iter()
+            .map(|f| {
+                let kind = f.r#type();
+                let repeated = f.label() == Label::Repeated;
+                // only scalar numeric fields can be packed, proto3 packs them by default
+                let packable = !matches!(
+                    kind,
+                    Type::String | Type::Bytes | Type::Message | Type::Group
+                );
+                let packed = repeated
+                    && packable
+                    && f.options.as_ref().and_then(|o| o.packed).unwrap_or(proto3);
+                Field {
+                    name: f.name().to_string(),
+                    number: f.number() as u32,
+                    kind,
+                    repeated,
+                    packed,
+                    type_name: f.type_name().to_string(),
+                }
+            })
+            .collect();
+        fields.sort_by_key(|f| f.number);
+        let map_entry = m
+            .options
+            .as_ref()
+            .and_then(|o| o.map_entry)
+            .unwrap_or_default();
+        self.messages
+            .insert(name, MessageDescriptor { fields, map_entry });
+    }
+
+    fn message(&self, name: &str) -> Result<&MessageDescriptor> {
+        self.messages.get(name).ok_or_else(|| {
+            format!("Message '{}' not found in the protobuf descriptors.", name).into()
+        })
+    }
+
+    fn enum_name(&self, name: &str, number: i32) -> Option<&str> {
+        self.enums
+            .get(name)?
+            .iter()
+            .find(|(n, _)| *n == number)
+            .map(|(_, name)| name.as_str())
+    }
+
+    fn enum_number(&self, name: &str, value: &Value) -> Result<i32> {
+        if let Some(n) = value.as_i32() {
+            return Ok(n);
+        }
+        value
+            .as_str()
+            .and_then(|s| self.enums.get(name)?.iter().find(|(_, n)| n == s))
+            .map(|(n, _)| *n)
+            .ok_or_else(|| format!("Invalid value {} for enum '{}'.", value.encode(), name).into())
+    }
+}
+
+#[derive(Clone)]
+pub struct Protobuf {
+    message: String,
+    descriptors: Arc<Descriptors>,
+}
+
+impl Protobuf {
+    pub fn from_config(config: Option<&OpConfig>) -> Result<Self> {
+        let config: Config = parse_required_config("protobuf", config)?;
+        let data = if let Some(descriptor_set) = &config.descriptor_set {
+            std::fs::read(descriptor_set)?
+        } else if config.proto_files.is_empty() {
+            return Err("Invalid config for codec 'protobuf': either `descriptor_set` or `proto_files` is required.".into());
+        } else {
+            compile(&config.proto_files, &config.include_paths)?
+        };
+        let set = FileDescriptorSet::decode(data.as_slice())?;
+        Self::from_descriptor_set(&set, &config.message)
+    }
+
+    fn from_descriptor_set(set: &FileDescriptorSet, message: &str) -> Result<Self> {
+        let descriptors = Descriptors::from_set(set);
+        // descriptors reference types by their fully qualified name with a leading `.`
+        let message = if message.starts_with('.') {
+            message.to_string()
+        } else {
+            format!(".{}", message)
+        };
+        descriptors.message(&message)?;
+        Ok(Self {
+            message,
+            descriptors: Arc::new(descriptors),
+        })
+    }
+
+    fn decode_message<'input>(
+        &self,
+        message: &MessageDescriptor,
+        mut buf: &'input [u8],
+        depth: usize,
+    ) -> Result<Value<'input>> {
+        if depth > MAX_DEPTH {
+            return Err(format!(
+                "Messages nested deeper than {} levels are not supported.",
+                MAX_DEPTH
+            )
+            .into());
+        }
+        let mut record = Object::with_capacity(message.fields.len());
+        while buf.has_remaining() {
+            let (number, wire_type) = decode_key(&mut buf)?;
+            let field = if let Some(field) = message.by_number(number) {
+                field
+            } else {
+                skip(wire_type, &mut buf)?;
+                continue;
+            };
+            if field.packed && wire_type == WireType::LengthDelimited {
+                let mut packed = take(&mut buf)?;
+                while packed.has_remaining() {
+                    let value = self.decode_field(field, field.wire_type(), &mut packed, depth)?;
+                    insert(&mut record, field, value);
+                }
+            } else {
+                let value = self.decode_field(field, wire_type, &mut buf, depth)?;
+                if let Some(entry) = self.map_entry(field) {
+                    let key = value.get("key").map_or_else(String::new, |k| {
+                        k.as_str().map_or_else(|| k.encode(), ToString::to_string)
+                    });
+                    let value = value.get("value").cloned().unwrap_or_default();
+                    if let Some(Value::Object(map)) = record.get_mut(field.name.as_str()) {
+                        map.insert(key.into(), value);
+                    } else {
+                        let mut map = Object::with_capacity(entry.fields.len());
+                        map.insert(key.into(), value);
+                        record.insert(field.name.clone().into(), Value::from(map));
+                    }
+                } else {
+                    insert(&mut record, field, value);
+                }
+            }
+        }
+        Ok(Value::from(record))
+    }
+
+    #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+    fn decode_field<'input>(
+        &self,
+        field: &Field,
+        wire_type: WireType,
+        buf: &mut &'input [u8],
+        depth: usize,
+    ) -> Result<Value<'input>> {
+        Ok(match (field.kind, wire_type) {
+            (Type::String, WireType::LengthDelimited) => {
+                Value::from(std::str::from_utf8(take(buf)?)?)
+            }
+            (Type::Bytes, WireType::LengthDelimited) => Value::Bytes(take(buf)?.into()),
+            (Type::Message, WireType::LengthDelimited) => {
+                let message = self.descriptors.message(&field.type_name)?;
+                self.decode_message(message, take(buf)?, depth + 1)?
+            }
+            (_, WireType::Varint) => {
+                let v = decode_varint(buf)?;
+                match field.kind {
+                    Type::Int32 => Value::from(v as i32),
+                    Type::Int64 => Value::from(v as i64),
+                    Type::Uint32 => Value::from(v as u32),
+                    Type::Uint64 => Value::from(v),
+                    Type::Sint32 => {
+                        let v = v as u32;
+                        Value::from(((v >> 1) as i32) ^ -((v & 1) as i32))
+                    }
+                    Type::Sint64 => Value::from(((v >> 1) as i64) ^ -((v & 1) as i64)),
+                    Type::Bool => Value::from(v != 0),
+                    Type::Enum => self
+                        .descriptors
+                        .enum_name(&field.type_name, v as i32)
+                        .map_or_else(|| Value::from(v as i32), |n| Value::from(n.to_string())),
+                    _ => return Err(invalid_wire_type(field, wire_type)),
+                }
+            }
+            (_, WireType::SixtyFourBit) if buf.remaining() >= 8 => match field.kind {
+                Type::Fixed64 => Value::from(buf.get_u64_le()),
+                Type::Sfixed64 => Value::from(buf.get_i64_le()),
+                Type::Double => Value::from(buf.get_f64_le()),
+                _ => return Err(invalid_wire_type(field, wire_type)),
+            },
+            (_, WireType::ThirtyTwoBit) if buf.remaining() >= 4 => match field.kind {
+                Type::Fixed32 => Value::from(buf.get_u32_le()),
+                Type::Sfixed32 => Value::from(buf.get_i32_le()),
+                Type::Float => Value::from(f64::from(buf.get_f32_le())),
+                _ => return Err(invalid_wire_type(field, wire_type)),
+            },
+            _ => return Err(invalid_wire_type(field, wire_type)),
+        })
+    }
+
+    fn encode_message(
+        &self,
+        name: &str,
+        message: &MessageDescriptor,
+        data: &Value,
+        buf: &mut Vec<u8>,
+    ) -> Result<()> {
+        let record = data
+            .as_object()
+            .ok_or_else(|| Error::from(format!("Expected a record for message '{}'.", name)))?;
+        if let Some(unknown) = record.keys().find(|k| message.by_name(k).is_none()) {
+            return Err(format!("Unknown field '{}' in message '{}'.", unknown, name).into());
+        }
+        for field in &message.fields {
+            let value = match record.get(field.name.as_str()) {
+                Some(value) if !value.is_null() => value,
+                _ => continue,
+            };
+            if let Some(entry) = self.map_entry(field) {
+                let map = value
+                    .as_object()
+                    .ok_or_else(|| expected("a record", field))?;
+                for (k, v) in map {
+                    let key_field = entry.by_number(1).ok_or_else(|| expected("a key", field))?;
+                    let mut entry_record = Object::with_capacity(2);
+                    entry_record.insert("key".into(), map_key(key_field, k)?);
+                    entry_record.insert("value".into(), v.clone());
+                    let mut nested = Vec::new();
+                    self.encode_message(
+                        &field.type_name,
+                        entry,
+                        &Value::from(entry_record),
+                        &mut nested,
+                    )?;
+                    encode_key(field.number, WireType::LengthDelimited, buf);
+                    encode_varint(nested.len() as u64, buf);
+                    buf.extend_from_slice(&nested);
+                }
+            } else if field.repeated {
+                let values = value
+                    .as_array()
+                    .ok_or_else(|| expected("an array", field))?;
+                if field.packed {
+                    let mut packed = Vec::new();
+                    for v in values {
+                        self.encode_value(field, v, &mut packed)?;
+                    }
+                    encode_key(field.number, WireType::LengthDelimited, buf);
+                    encode_varint(packed.len() as u64, buf);
+                    buf.extend_from_slice(&packed);
+                } else {
+                    for v in values {
+                        encode_key(field.number, field.wire_type(), buf);
+                        self.encode_value(field, v, buf)?;
+                    }
+                }
+            } else {
+                encode_key(field.number, field.wire_type(), buf);
+                self.encode_value(field, value, buf)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// encodes a single value of `field` without its key
+    #[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)]
+    fn encode_value(&self, field: &Field, value: &Value, buf: &mut Vec<u8>) -> Result<()> {
+        match field.kind {
+            Type::Int32 => {
+                let v = value.as_i32().ok_or_else(|| expected("an i32", field))?;
+                encode_varint(i64::from(v) as u64, buf);
+            }
+            Type::Int64 => {
+                let v = value.as_i64().ok_or_else(|| expected("an i64", field))?;
+                encode_varint(v as u64, buf);
+            }
+            Type::Uint32 => {
+                let v = value.as_u32().ok_or_else(|| expected("an u32", field))?;
+                encode_varint(u64::from(v), buf);
+            }
+            Type::Uint64 => {
+                let v = value.as_u64().ok_or_else(|| expected("an u64", field))?;
+                encode_varint(v, buf);
+            }
+            Type::Sint32 => {
+                let v = value.as_i32().ok_or_else(|| expected("an i32", field))?;
+                encode_varint(u64::from(((v << 1) ^ (v >> 31)) as u32), buf);
+            }
+            Type::Sint64 => {
+                let v = value.as_i64().ok_or_else(|| expected("an i64", field))?;
+                encode_varint(((v << 1) ^ (v >> 63)) as u64, buf);
+            }
+            Type::Bool => {
+                let v = value.as_bool().ok_or_else(|| expected("a bool", field))?;
+                encode_varint(u64::from(v), buf);
+            }
+            Type::Enum => {
+                let v = self.descriptors.enum_number(&field.type_name, value)?;
+                encode_varint(i64::from(v) as u64, buf);
+            }
+            Type::Fixed64 => {
+                let v = value.as_u64().ok_or_else(|| expected("an u64", field))?;
+                buf.extend_from_slice(&v.to_le_bytes());
+            }
+            Type::Sfixed64 => {
+                let v = value.as_i64().ok_or_else(|| expected("an i64", field))?;
+                buf.extend_from_slice(&v.to_le_bytes());
+            }
+            Type::Double => {
+                let v = value.cast_f64().ok_or_else(|| expected("a float", field))?;
+                buf.extend_from_slice(&v.to_le_bytes());
+            }
+            Type::Fixed32 => {
+                let v = value.as_u32().ok_or_else(|| expected("an u32", field))?;
+                buf.extend_from_slice(&v.to_le_bytes());
+            }
+            Type::Sfixed32 => {
+                let v = value.as_i32().ok_or_else(|| expected("an i32", field))?;
+                buf.extend_from_slice(&v.to_le_bytes());
+            }
+            Type::Float => {
+                let v = value.cast_f64().ok_or_else(|| expected("a float", field))?;
+                buf.extend_from_slice(&(v as f32).to_le_bytes());
+            }
+            Type::String => {
+                let v = value.as_str().ok_or_else(|| expected("a string", field))?;
+                encode_varint(v.len() as u64, buf);
+                buf.extend_from_slice(v.as_bytes());
+            }
+            Type::Bytes => {
+                let v = value
+                    .as_bytes()
+                    .or_else(|| value.as_str().map(str::as_bytes))
+                    .ok_or_else(|| expected("bytes", field))?;
+                encode_varint(v.len() as u64, buf);
+                buf.extend_from_slice(v);
+            }
+            Type::Message => {
+                let message = self.descriptors.message(&field.type_name)?;
+                let mut nested = Vec::new();
+                self.encode_message(&field.type_name, message, value, &mut nested)?;
+                encode_varint(nested.len() as u64, buf);
+                buf.extend_from_slice(&nested);
+            }
+            Type::Group => {
+                return Err(format!("Field '{}': groups are not supported.", field.name).into())
+            }
+        }
+        Ok(())
+    }
+
+    fn map_entry(&self, field: &Field) -> Option<&MessageDescriptor> {
+        if field.kind == Type::Message && field.repeated {
+            self.descriptors
+                .messages
+                .get(&field.type_name)
+                .filter(|m| m.map_entry)
+        } else {
+            None
+        }
+    }
+}
+
+/// compiles `.proto` files into a serialized descriptor set
+fn compile(proto_files: &[String], include_paths: &[String]) -> Result<Vec<u8>> {
+    let out = tempfile::NamedTempFile::new()?;
+    let protoc = std::env::var("PROTOC").unwrap_or_else(|_| "protoc".to_string());
+    let output = Command::new(&protoc)
+        .arg("--include_imports")
+        .arg(format!("--descriptor_set_out={}", out.path().display()))
+        .args(include_paths.iter().map(|p| format!("--proto_path={}", p)))
+        .args(proto_files)
+        .output()
+        .map_err(|e| Error::from(format!("Failed to run '{}': {}", protoc, e)))?;
+    if !output.status.success() {
+        return Err(format!(
+            "Failed to compile protobuf files: {}",
+            String::from_utf8_lossy(&output.stderr)
+        )
+        .into());
+    }
+    Ok(std::fs::read(out.path())?)
+}
+
+fn insert<'input>(record: &mut Object<'input>, field: &Field, value: Value<'input>) {
+    if field.repeated {
+        if let Some(Value::Array(values)) = record.get_mut(field.name.as_str()) {
+            values.push(value);
+            return;
+        }
+        record.insert(field.name.clone().into(), Value::from(vec![value]));
+    } else {
+        record.insert(field.name.clone().into(), value);
+    }
+}
+
+/// takes a length delimited chunk from the buffer
+#[allow(clippy::cast_possible_truncation)]
+fn take<'input>(buf: &mut &'input [u8]) -> Result<&'input [u8]> {
+    let len = decode_varint(buf)? as usize;
+    if buf.len() < len {
+        return Err("Invalid protobuf data: length exceeds the remaining data.".into());
+    }
+    let (chunk, rest) = buf.split_at(len);
+    *buf = rest;
+    Ok(chunk)
+}
+
+fn skip(wire_type: WireType, buf: &mut &[u8]) -> Result<()> {
+    let len = match wire_type {
+        WireType::Varint => {
+            decode_varint(buf)?;
+            0
+        }
+        WireType::SixtyFourBit => 8,
+        WireType::ThirtyTwoBit => 4,
+        WireType::LengthDelimited => {
+            take(buf)?;
+            0
+        }
+        WireType::StartGroup | WireType::EndGroup => {
+            return Err("Invalid protobuf data: groups are not supported.".into())
+        }
+    };
+    if buf.remaining() < len {
+        return Err("Invalid protobuf data: unexpected end of data.".into());
+    }
+    buf.advance(len);
+    Ok(())
+}
+
+/// map keys are strings in records, turn them back into values of the key type
+fn map_key<'key>(key_field: &Field, key: &'key str) -> Result<Value<'key>> {
+    Ok(match key_field.kind {
+        Type::String => Value::from(key),
+        Type::Bool => Value::from(
+            key.parse::<bool>()
+                .map_err(|e| Error::from(format!("Invalid map key '{}': {}", key, e)))?,
+        ),
+        Type::Uint32 | Type::Uint64 | Type::Fixed32 | Type::Fixed64 => {
+            Value::from(key.parse::<u64>()?)
+        }
+        _ => Value::from(key.parse::<i64>()?),
+    })
+}
+
+fn expected(what: &str, field: &Field) -> Error {
+    format!("Expected {} for field '{}'.", what, field.name).into()
+}
+
+fn invalid_wire_type(field: &Field, wire_type: WireType) -> Error {
+    format!(
+        "Invalid protobuf data: unexpected wire type {:?} for field '{}'.",
+        wire_type, field.name
+    )
+    .into()
+}
+
+impl Codec for Protobuf {
+    #[cfg(not(tarpaulin_include))]
+    fn name(&self) -> &str {
+        "protobuf"
+    }
+
+    #[cfg(not(tarpaulin_include))]
+    fn mime_types(&self) -> Vec<&str> {
+        vec!["application/x-protobuf", "application/protobuf"]
+    }
+
+    fn decode<'input>(
+        &mut self,
+        data: &'input mut [u8],
+        _ingest_ns: u64,
+    ) -> Result<Option<Value<'input>>> {
+        let message = self.descriptors.message(&self.message)?;
+        self.decode_message(message, data, 0).map(Some)
+    }
+
+    fn encode(&self, data: &Value) -> Result<Vec<u8>> {
+        let message = self.descriptors.message(&self.message)?;
+        let mut buf = Vec::with_capacity(128);
+        self.encode_message(&self.message, message, data, &mut buf)?;
+        Ok(buf)
+    }
+
+    #[cfg(not(tarpaulin_include))]
+    fn boxed_clone(&self) -> Box<dyn Codec> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use prost_types::{
+        DescriptorProto, EnumValueDescriptorProto, FieldDescriptorProto, FileDescriptorProto,
+        MessageOptions,
+    };
+    use tremor_value::literal;
+
+    fn field(
+        name: &str,
+        number: i32,
+        label: Label,
+        kind: Type,
+        type_name: &str,
+    ) -> FieldDescriptorProto {
+        FieldDescriptorProto {
+            name: Some(name.to_string()),
+            number: Some(number),
+            label: Some(label as i32),
+            r#type: Some(kind as i32),
+            type_name: if type_name.is_empty() {
+                None
+            } else {
+                Some(type_name.to_string())
+            },
+            ..FieldDescriptorProto::default()
+        }
+    }
+
+    fn descriptor_set() -> FileDescriptorSet {
+        let point = DescriptorProto {
+            name: Some("Point".to_string()),
+            field: vec![
+                field("x", 1, Label::Optional, Type::Sint32, ""),
+                field("y", 2, Label::Optional, Type::Double, ""),
+            ],
+            ..DescriptorProto::default()
+        };
+        let labels_entry = DescriptorProto {
+            name: Some("LabelsEntry".to_string()),
+            field: vec![
+                field("key", 1, Label::Optional, Type::String, ""),
+                field("value", 2, Label::Optional, Type::Uint64, ""),
+            ],
+            options: Some(MessageOptions {
+                map_entry: Some(true),
+                ..MessageOptions::default()
+            }),
+            ..DescriptorProto::default()
+        };
+        let kind = EnumDescriptorProto {
+            name: Some("Kind".to_string()),
+            value: vec![
+                EnumValueDescriptorProto {
+                    name: Some("CLICK".to_string()),
+                    number: Some(0),
+                    ..EnumValueDescriptorProto::default()
+                },
+                EnumValueDescriptorProto {
+                    name: Some("VIEW".to_string()),
+                    number: Some(1),
+                    ..EnumValueDescriptorProto::default()
+                },
+            ],
+            ..EnumDescriptorProto::default()
+        };
+        let event = DescriptorProto {
+            name: Some("Event".to_string()),
+            field: vec![
+                field("id", 1, Label::Optional, Type::Int32, ""),
+                field("name", 2, Label::Optional, Type::String, ""),
+                field("kind", 3, Label::Optional, Type::Enum, ".snot.Event.Kind"),
+                field("points", 4, Label::Repeated, Type::Message, ".snot.Point"),
+                field("scores", 5, Label::Repeated, Type::Uint32, ""),
+                field(
+                    "labels",
+                    6,
+                    Label::Repeated,
+                    Type::Message,
+                    ".snot.Event.LabelsEntry",
+                ),
+                field("payload", 7, Label::Optional, Type::Bytes, ""),
+            ],
+            nested_type: vec![labels_entry],
+            enum_type: vec![kind],
+            ..DescriptorProto::default()
+        };
+        FileDescriptorSet {
+            file: vec![FileDescriptorProto {
+                name: Some("snot.proto".to_string()),
+                package: Some("snot".to_string()),
+                syntax: Some("proto3".to_string()),
+                message_type: vec![point, event],
+                ..FileDescriptorProto::default()
+            }],
+        }
+    }
+
+    #[test]
+    fn wire_format() -> Result<()> {
+        let mut codec = Protobuf::from_descriptor_set(&descriptor_set(), "snot.Event")?;
+        // the example from the protobuf encoding docs
+        assert_eq!(
+            vec![0x08, 0x96, 0x01],
+            codec.encode(&literal!({"id": 150}))?
+        );
+        // packed repeated fields
+        assert_eq!(
+            vec![0x2a, 0x03, 0x03, 0x8e, 0x02],
+            codec.encode(&literal!({"scores": [3, 270]}))?
+        );
+        let mut data = vec![0x08, 0x96, 0x01, 0x2a, 0x03, 0x03, 0x8e, 0x02];
+        assert_eq!(
+            literal!({"id": 150, "scores": [3, 270]}),
+            codec.decode(&mut data, 0)?.unwrap_or_default()
+        );
+        // unknown fields are skipped
+        let mut data = vec![0x50, 0x01, 0x08, 0x01];
+        assert_eq!(
+            literal!({"id": 1}),
+            codec.decode(&mut data, 0)?.unwrap_or_default()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn roundtrip() -> Result<()> {
+        let mut codec = Protobuf::from_descriptor_set(&descriptor_set(), ".snot.Event")?;
+        let event = literal!({
+            "id": -1,
+            "name": "snot",
+            "kind": "VIEW",
+            "points": [{"x": -3, "y": 1.5}, {"x": 42}],
+            "labels": {"badger": 7},
+        });
+        let mut data = codec.encode(&event)?;
+        assert_eq!(Some(event), codec.decode(&mut data, 0)?);
+
+        let mut data = codec.encode(&literal!({"payload": "snot"}))?;
+        assert_eq!(
+            Some(Value::Bytes(b"snot".to_vec().into())),
+            codec
+                .decode(&mut data, 0)?
+                .and_then(|v| v.get("payload").cloned())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn errors() -> Result<()> {
+        let codec = Protobuf::from_descriptor_set(&descriptor_set(), "snot.Event")?;
+        assert_eq!(
+            "Unknown field 'snot' in message '.snot.Event'.",
+            codec
+                .encode(&literal!({"snot": 1}))
+                .err()
+                .unwrap()
+                .to_string()
+        );
+        assert_eq!(
+            "Expected an i32 for field 'id'.",
+            codec
+                .encode(&literal!({"id": "snot"}))
+                .err()
+                .unwrap()
+                .to_string()
+        );
+        assert_eq!(
+            "Invalid value \"SNOT\" for enum '.snot.Event.Kind'.",
+            codec
+                .encode(&literal!({"kind": "SNOT"}))
+                .err()
+                .unwrap()
+                .to_string()
+        );
+        assert!(Protobuf::from_descriptor_set(&descriptor_set(), "snot.Badger").is_err());
+        assert!(Protobuf::from_config(None).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn max_depth() -> Result<()> {
+        let tree = DescriptorProto {
+            name: Some("Tree".to_string()),
+            field: vec![field(
+                "child",
+                1,
+                Label::Optional,
+                Type::Message,
+                ".snot.Tree",
+            )],
+            ..DescriptorProto::default()
+        };
+        let set = FileDescriptorSet {
+            file: vec![FileDescriptorProto {
+                name: Some("tree.proto".to_string()),
+                package: Some("snot".to_string()),
+                syntax: Some("proto3".to_string()),
+                message_type: vec![tree],
+                ..FileDescriptorProto::default()
+            }],
+        };
+        let mut codec = Protobuf::from_descriptor_set(&set, "snot.Tree")?;
+        let nested = |depth: usize| {
+            let mut data = Vec::new();
+            for _ in 0..depth {
+                let mut outer = Vec::with_capacity(data.len() + 4);
+                encode_key(1, WireType::LengthDelimited, &mut outer);
+                encode_varint(data.len() as u64, &mut outer);
+                outer.append(&mut data);
+                data = outer;
+            }
+            data
+        };
+        let mut data = nested(MAX_DEPTH);
+        assert!(codec.decode(&mut data, 0)?.is_some());
+        let mut data = nested(10_000);
+        assert_eq!(
+            "Messages nested deeper than 100 levels are not supported.",
+            codec.decode(&mut data, 0).err().unwrap().to_string()
+        );
+        Ok(())
+    }
+}
@@ -13,7 +13,7 @@
 // limitations under the License.
 
 use super::prelude::*;
-use chrono::{DateTime, Datelike, Offset, TimeZone, Utc};
+use chrono::{DateTime, Datelike, FixedOffset, Offset, TimeZone, Utc};
 use syslog_loose::{IncompleteDate, ProcId, Protocol, SyslogFacility, SyslogSeverity};
 use tremor_value::Value;
 
@@ -31,20 +31,91 @@ impl Now for UtcNow {
     }
 }
 
+/// protocol used to encode events that don't specify one
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum Mode {
+    #[serde(rename = "RFC5424")]
+    Rfc5424,
+    #[serde(rename = "RFC3164")]
+    Rfc3164,
+}
+
+impl Default for Mode {
+    fn default() -> Self {
+        Self::Rfc5424
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// protocol to use for events without a `protocol` field
+    #[serde(default = "Default::default")]
+    pub mode: Mode,
+    /// fixed offset to UTC, as `+HH:MM` or `-HH:MM`, for timestamps that don't carry one,
+    /// like the ones in RFC3164 messages. Defaults to UTC.
+    #[serde(default = "Default::default")]
+    pub timezone: Option<String>,
+}
+
+impl ConfigImpl for Config {}
+
 #[derive(Clone)]
 pub struct Syslog<N>
 where
     N: Now,
 {
     now: N,
+    mode: Mode,
+    timezone: FixedOffset,
 }
 
 impl Syslog<UtcNow> {
     /// construct a Syslog codec
     /// that adds the current time in UTC during encoding if none was provided in the event payload
     pub fn utcnow() -> Self {
-        Self { now: UtcNow {} }
+        Self {
+            now: UtcNow {},
+            mode: Mode::default(),
+            timezone: Utc.fix(),
+        }
+    }
+
+    /// construct a Syslog codec from its config
+    pub fn from_config(config: Option<&OpConfig>) -> Result<Self> {
+        let config: Config = parse_config("syslog", config)?;
+        let timezone = config
+            .timezone
+            .as_deref()
+            .map_or_else(|| Ok(Utc.fix()), parse_timezone)?;
+        Ok(Self {
+            mode: config.mode,
+            timezone,
+            ..Self::utcnow()
+        })
+    }
+}
+
+/// parse a fixed offset to UTC given as `+HH:MM` or `-HH:MM`
+fn parse_timezone(timezone: &str) -> Result<FixedOffset> {
+    let invalid = || {
+        Error::from(format!(
+            "Invalid timezone '{}', expected an offset like '+02:00'.",
+            timezone
+        ))
+    };
+    let (sign, offset) = match timezone.as_bytes().first() {
+        Some(b'+') => (1, &timezone[1..]),
+        Some(b'-') => (-1, &timezone[1..]),
+        _ => return Err(invalid()),
+    };
+    let (hours, minutes) = offset.split_once(':').ok_or_else(invalid)?;
+    let hours: i32 = hours.parse().map_err(|_| invalid())?;
+    let minutes: i32 = minutes.parse().map_err(|_| invalid())?;
+    if minutes >= 60 {
+        return Err(invalid());
     }
+    FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60)).ok_or_else(invalid)
 }
 
 impl<N> Syslog<N>
@@ -105,7 +176,8 @@ where
         };
         let datetime = data
             .get_i64("timestamp")
-            .map_or_else(|| self.now.now(), |t| Utc.timestamp_nanos(t));
+            .map_or_else(|| self.now.now(), |t| Utc.timestamp_nanos(t))
+            .with_timezone(&self.timezone);
         result.push(format!("<{}>{}", pri, datetime.format("%b %e %H:%M:%S")));
 
         result.push(
@@ -199,11 +271,8 @@ where
         _ingest_ns: u64,
     ) -> Result<Option<Value<'input>>> {
         let line: &str = std::str::from_utf8(data)?;
-        let parsed = syslog_loose::parse_message_with_year_tz(
-            line,
-            resolve_year,
-            Some(chrono::offset::Utc.fix()),
-        );
+        let parsed =
+            syslog_loose::parse_message_with_year_tz(line, resolve_year, Some(self.timezone));
 
         let mut decoded = Value::object_with_capacity(11);
         if let Some(hostname) = parsed.hostname {
@@ -277,7 +346,10 @@ where
             (Some(&_), _) => {
                 return Err(ErrorKind::InvalidSyslogData("invalid protocol type").into())
             }
-            (None, None) => Protocol::RFC5424(1_u32),
+            (None, None) => match self.mode {
+                Mode::Rfc5424 => Protocol::RFC5424(1_u32),
+                Mode::Rfc3164 => Protocol::RFC3164,
+            },
         };
         let result = match protocol {
             Protocol::RFC3164 => self.encode_rfc3164(data)?,
@@ -371,7 +443,11 @@ mod test {
     }
 
     fn test_codec() -> Syslog<TestNow> {
-        Syslog { now: TestNow {} }
+        Syslog {
+            now: TestNow {},
+            mode: Mode::default(),
+            timezone: Utc.fix(),
+        }
     }
 
     #[test]
@@ -457,6 +533,30 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn encode_rfc3164_mode_with_timezone() -> Result<()> {
+        let config = serde_yaml::from_str("{mode: RFC3164, timezone: \"+02:00\"}")?;
+        let configured = Syslog::from_config(Some(&config))?;
+        let codec = Syslog {
+            now: TestNow {},
+            mode: configured.mode,
+            timezone: configured.timezone,
+        };
+        let msg = literal!({
+            "severity": "notice",
+            "facility": "local4",
+            "msg": "test message",
+            "timestamp": 0_u64
+        });
+        let encoded = codec.encode(&msg)?;
+        let expected = "<165>Jan  1 02:00:00 - : test message";
+        assert_eq!(std::str::from_utf8(&encoded).unwrap(), expected);
+
+        let config = serde_yaml::from_str("timezone: CEST")?;
+        assert!(Syslog::from_config(Some(&config)).is_err());
+        Ok(())
+    }
+
     #[test]
     fn test_incorrect_sd() -> Result<()> {
         let mut msg =
@@ -12,8 +12,11 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use crate::errors::{Error, Result};
 use crate::url::TremorUrl;
+use crate::{codec, postprocessor, preprocessor, OpConfig};
 use hashbrown::HashMap;
+use tremor_pipeline::ConfigImpl;
 
 pub(crate) type Id = String;
 pub(crate) type OnRampVec = Vec<OnRamp>;
@@ -22,6 +25,58 @@ pub(crate) type BindingVec = Vec<Binding>;
 pub(crate) type BindingMap = HashMap<TremorUrl, Vec<TremorUrl>>;
 pub(crate) type MappingMap = HashMap<TremorUrl, HashMap<String, String>>;
 
+/// Something configured either by just its name, using its defaults,
+/// or by its name and a specific config, as used for codecs, preprocessors
+/// and postprocessors
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum NameWithConfig {
+    /// just the name, using the default config
+    Name(String),
+    /// name and a custom config
+    Full {
+        /// name
+        name: String,
+        /// specific config
+        #[serde(default = "Default::default", skip_serializing_if = "Option::is_none")]
+        config: Option<OpConfig>,
+    },
+}
+
+impl NameWithConfig {
+    /// the configured name
+    #[must_use]
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Name(name) | Self::Full { name, .. } => name,
+        }
+    }
+
+    /// the specific config, if any
+    #[must_use]
+    pub fn config(&self) -> Option<&OpConfig> {
+        match self {
+            Self::Name(_) => None,
+            Self::Full { config, .. } => config.as_ref(),
+        }
+    }
+}
+
+impl From<&str> for NameWithConfig {
+    fn from(name: &str) -> Self {
+        Self::Name(name.to_string())
+    }
+}
+
+/// parses the `config` of the `kind` (codec, preprocessor, ...) named `name`
+pub(crate) fn parse_config<C>(kind: &str, name: &str, config: &OpConfig) -> Result<C>
+where
+    C: ConfigImpl + for<'de> serde::Deserialize<'de>,
+{
+    C::new(config)
+        .map_err(|e| Error::from(format!("Invalid config for {} '{}': {}", kind, name, e)))
+}
+
 /// A full tremor config
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(deny_unknown_fields)]
@@ -52,8 +107,9 @@ pub struct OnRamp {
     pub(crate) is_linked: bool,
     #[serde(default = "Default::default")]
     pub(crate) err_required: bool,
+    /// codec to use, either its name or a record with its `name` and `config`
     #[serde(default = "Default::default", skip_serializing_if = "Option::is_none")]
-    pub(crate) codec: Option<String>,
+    pub(crate) codec: Option<codec::Config>,
     /// mapping from mime-type to codec used to handle requests/responses
     /// with this mime-type
     ///
@@ -61,15 +117,21 @@ pub struct OnRamp {
     ///       codec_map:
     ///         "application/json": "json"
     ///         "text/plain": "string"
+    ///         "application/yaml":
+    ///           name: json
+    ///           config:
+    ///             pretty: true
     ///
     /// A default builtin codec mapping is defined
     /// for msgpack, json, yaml and plaintext codecs with the common mime-types
     #[serde(default = "Default::default", skip_serializing_if = "Option::is_none")]
-    pub(crate) codec_map: Option<halfbrown::HashMap<String, String>>,
+    pub(crate) codec_map: Option<halfbrown::HashMap<String, codec::Config>>,
+    /// preprocessors, either their names or records with their `name` and `config`
     #[serde(default = "Default::default", skip_serializing_if = "Option::is_none")]
-    pub(crate) preprocessors: Option<Vec<String>>,
+    pub(crate) preprocessors: Option<Vec<preprocessor::Config>>,
+    /// postprocessors, either their names or records with their `name` and `config`
     #[serde(default = "Default::default", skip_serializing_if = "Option::is_none")]
-    pub(crate) postprocessors: Option<Vec<String>>,
+    pub(crate) postprocessors: Option<Vec<postprocessor::Config>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub(crate) metrics_interval_s: Option<u64>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -90,8 +152,9 @@ pub struct OffRamp {
     #[serde(rename = "linked", default = "Default::default")]
     // TODO validate that this is turned on only for supported offramps (rest, ws)
     pub(crate) is_linked: bool,
+    /// codec to use, either its name or a record with its `name` and `config`
     #[serde(default = "Default::default", skip_serializing_if = "Option::is_none")]
-    pub(crate) codec: Option<String>,
+    pub(crate) codec: Option<codec::Config>,
     /// mapping from mime-type to codec used to handle requests/responses
     /// with this mime-type
     ///
@@ -99,17 +162,26 @@ pub struct OffRamp {
     ///       codec_map:
     ///         "application/json": "json"
     ///         "text/plain": "string"
+    ///         "application/yaml":
+    ///           name: json
+    ///           config:
+    ///             pretty: true
     ///
     /// A default builtin codec mapping is defined
     /// for msgpack, json, yaml and plaintext codecs with the common mime-types
     #[serde(default = "Default::default", skip_serializing_if = "Option::is_none")]
-    pub(crate) codec_map: Option<halfbrown::HashMap<String, String>>,
+    pub(crate) codec_map: Option<halfbrown::HashMap<String, codec::Config>>,
+    /// preprocessors, either their names or records with their `name` and `config`
     #[serde(default = "Default::default", skip_serializing_if = "Option::is_none")]
-    pub(crate) preprocessors: Option<Vec<String>>,
+    pub(crate) preprocessors: Option<Vec<preprocessor::Config>>,
+    /// postprocessors, either their names or records with their `name` and `config`
     #[serde(default = "Default::default", skip_serializing_if = "Option::is_none")]
-    pub(crate) postprocessors: Option<Vec<String>>,
+    pub(crate) postprocessors: Option<Vec<postprocessor::Config>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub(crate) metrics_interval_s: Option<u64>,
+    /// policy for retrying events the offramp failed to deliver
+    #[serde(default = "Default::default", skip_serializing_if = "Option::is_none")]
+    pub(crate) retry: Option<crate::offramp::retry::Config>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub(crate) config: tremor_pipeline::ConfigMap,
 }
@@ -0,0 +1,290 @@
+// Copyright 2020-2021, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Dead-letter queue.
+//!
+//! Data an onramp fails to preprocess or decode is sent to its `err` port as a
+//! dead letter carrying the raw data, the codec and the preprocessors it still
+//! has to pass. Events a pipeline operator fails to handle are sent to its
+//! `err` port as a dead letter carrying the original event, the pipeline and
+//! the operator. Dead letters can be routed to any offramp, the `dlq` offramp
+//! keeps them in a sled database in its `dir`. From there they can be listed
+//! and replayed into the onramp or pipeline they came from.
+
+use crate::errors::{ErrorKind, Result};
+use crate::utils::open_sled;
+use tremor_script::prelude::*;
+
+/// Data an onramp failed to preprocess or decode, or an event a pipeline
+/// failed to handle
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DeadLetter {
+    /// id of the dead letter in its queue
+    pub id: u64,
+    /// the onramp instance the data came from, for onramp stages
+    #[serde(default = "Default::default")]
+    pub onramp: Option<String>,
+    /// the stage that failed, `preprocessor`, `codec` or `pipeline`
+    pub stage: String,
+    /// the codec the data is decoded with, for onramp stages
+    #[serde(default = "Default::default")]
+    pub codec: Option<String>,
+    /// the preprocessors the raw data still has to pass
+    #[serde(default = "Default::default")]
+    pub preprocessors: Vec<String>,
+    /// the error
+    pub error: String,
+    /// the base64 encoded raw data, if it was kept
+    #[serde(default = "Default::default")]
+    pub raw: Option<String>,
+    /// the pipeline instance the event failed in, for the `pipeline` stage
+    #[serde(default = "Default::default")]
+    pub pipeline: Option<String>,
+    /// the operator the event failed in, for the `pipeline` stage
+    #[serde(default = "Default::default")]
+    pub operator: Option<String>,
+    /// the JSON encoded original event, for the `pipeline` stage
+    #[serde(default = "Default::default")]
+    pub event: Option<String>,
+    /// ingest time of the dead letter
+    pub ingest_ns: u64,
+}
+
+impl DeadLetter {
+    /// Reads a dead letter from an error record of an onramp or a pipeline
+    ///
+    /// # Errors
+    ///  * if the record is no error record of an onramp or a pipeline
+    pub(crate) fn from_record(id: u64, record: &Value, ingest_ns: u64) -> Result<Self> {
+        let field = |name: &str| {
+            record
+                .get_str(name)
+                .map(ToString::to_string)
+                .ok_or_else(|| {
+                    ErrorKind::InvalidDeadLetter(format!("missing or invalid `{}`", name))
+                })
+        };
+        if !record.contains_key("source_id") {
+            let event = record.get("event").ok_or_else(|| {
+                ErrorKind::InvalidDeadLetter("missing `source_id` or `event`".to_string())
+            })?;
+            return Ok(Self {
+                id,
+                onramp: None,
+                stage: "pipeline".to_string(),
+                codec: None,
+                preprocessors: vec![],
+                error: field("error")?,
+                raw: None,
+                pipeline: Some(field("pipeline")?),
+                operator: Some(field("operator")?),
+                event: Some(event.encode()),
+                ingest_ns,
+            });
+        }
+        Ok(Self {
+            id,
+            onramp: Some(field("source_id")?),
+            stage: field("stage")?,
+            codec: Some(field("codec")?),
+            preprocessors: record
+                .get_array("preprocessors")
+                .map(|pps| {
+                    pps.iter()
+                        .filter_map(ValueAccess::as_str)
+                        .map(ToString::to_string)
+                        .collect()
+                })
+                .unwrap_or_default(),
+            error: field("error")?,
+            raw: record.get_bytes("raw").map(base64::encode),
+            pipeline: None,
+            operator: None,
+            event: None,
+            ingest_ns,
+        })
+    }
+
+    /// The original event of a dead letter of a pipeline
+    ///
+    /// # Errors
+    ///  * if the dead letter has no valid event
+    pub(crate) fn event(&self) -> Result<Value<'static>> {
+        if let Some(event) = &self.event {
+            let mut data = event.as_bytes().to_vec();
+            Ok(tremor_value::parse_to_value(&mut data)?.into_static())
+        } else {
+            Err(
+                ErrorKind::InvalidDeadLetter(format!("dead letter {} keeps no event", self.id))
+                    .into(),
+            )
+        }
+    }
+
+    /// The raw data of the dead letter
+    ///
+    /// # Errors
+    ///  * if the raw data wasn't kept
+    pub(crate) fn raw(&self) -> Result<Vec<u8>> {
+        if let Some(raw) = &self.raw {
+            Ok(base64::decode(raw)?)
+        } else {
+            Err(ErrorKind::InvalidDeadLetter(format!(
+                "the raw data of dead letter {} wasn't kept",
+                self.id
+            ))
+            .into())
+        }
+    }
+}
+
+/// A dead-letter queue on disk
+#[derive(Clone, Debug)]
+pub(crate) struct Queue {
+    dir: String,
+    db: sled::Db,
+}
+
+impl Queue {
+    /// Opens the queue in the directory `dir`, creating it if needed
+    ///
+    /// # Errors
+    ///  * if the queue can't be opened
+    pub(crate) fn open(dir: &str) -> Result<Self> {
+        Ok(Self {
+            dir: dir.to_string(),
+            db: open_sled(dir)?,
+        })
+    }
+
+    /// Adds the error record of an onramp or a pipeline to the queue
+    ///
+    /// # Errors
+    ///  * if the record is invalid or can't be written
+    pub(crate) async fn push(&self, record: &Value<'_>, ingest_ns: u64) -> Result<u64> {
+        let id = self.db.generate_id()?;
+        let letter = DeadLetter::from_record(id, record, ingest_ns)?;
+        self.db
+            .insert(id.to_be_bytes(), simd_json::to_vec(&letter)?)?;
+        self.db.flush_async().await?;
+        Ok(id)
+    }
+
+    /// All dead letters in the queue, oldest first
+    ///
+    /// # Errors
+    ///  * if the queue can't be read
+    pub(crate) fn list(&self) -> Result<Vec<DeadLetter>> {
+        let mut letters = Vec::with_capacity(self.db.len());
+        for entry in self.db.iter() {
+            let (_, value) = entry?;
+            letters.push(simd_json::from_slice(&mut value.to_vec())?);
+        }
+        Ok(letters)
+    }
+
+    /// The dead letter with the id `id`
+    ///
+    /// # Errors
+    ///  * if the dead letter doesn't exist or can't be read
+    pub(crate) fn get(&self, id: u64) -> Result<DeadLetter> {
+        if let Some(value) = self.db.get(id.to_be_bytes())? {
+            Ok(simd_json::from_slice(&mut value.to_vec())?)
+        } else {
+            Err(ErrorKind::DeadLetterNotFound(self.dir.clone(), id).into())
+        }
+    }
+
+    /// Removes the dead letter with the id `id`
+    ///
+    /// # Errors
+    ///  * if the dead letter doesn't exist or can't be removed
+    pub(crate) async fn remove(&self, id: u64) -> Result<DeadLetter> {
+        let letter = self.get(id)?;
+        self.db.remove(id.to_be_bytes())?;
+        self.db.flush_async().await?;
+        Ok(letter)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[async_std::test]
+    async fn push_list_remove() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let queue = Queue::open(&dir.path().to_string_lossy())?;
+        assert!(queue.list()?.is_empty());
+
+        let record = literal!({
+            "error": "oh no!",
+            "event_id": 1,
+            "source_id": "tremor://localhost/onramp/in/01/out",
+            "stage": "codec",
+            "codec": "json",
+            "preprocessors": [],
+            "raw": Value::Bytes(b"{\"snot\":".to_vec().into())
+        });
+        let id = queue.push(&record, 42).await?;
+        assert!(queue.push(&literal!({"snot": "badger"}), 42).await.is_err());
+
+        let letters = queue.list()?;
+        assert_eq!(1, letters.len());
+        let letter = &letters[0];
+        assert_eq!(id, letter.id);
+        assert_eq!(
+            Some("tremor://localhost/onramp/in/01/out"),
+            letter.onramp.as_deref()
+        );
+        assert_eq!("codec", letter.stage);
+        assert_eq!(Some("json"), letter.codec.as_deref());
+        assert_eq!(42, letter.ingest_ns);
+        assert_eq!(b"{\"snot\":".to_vec(), letter.raw()?);
+
+        assert_eq!(letter, &queue.remove(id).await?);
+        assert!(queue.list()?.is_empty());
+        assert!(queue.get(id).is_err());
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn pipeline_errors() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let queue = Queue::open(&dir.path().to_string_lossy())?;
+
+        let record = literal!({
+            "error": "oh no!",
+            "event": {"snot": "badger"},
+            "pipeline": "tremor://localhost/pipeline/main/01",
+            "operator": "script"
+        });
+        let id = queue.push(&record, 42).await?;
+        // the pipeline and operator are required
+        let record = literal!({"error": "oh no!", "event": {"snot": "badger"}});
+        assert!(queue.push(&record, 42).await.is_err());
+
+        let letter = queue.get(id)?;
+        assert_eq!("pipeline", letter.stage);
+        assert_eq!(None, letter.onramp);
+        assert_eq!(
+            Some("tremor://localhost/pipeline/main/01"),
+            letter.pipeline.as_deref()
+        );
+        assert_eq!(Some("script"), letter.operator.as_deref());
+        assert_eq!(literal!({"snot": "badger"}), letter.event()?);
+        assert!(letter.raw().is_err());
+        Ok(())
+    }
+}
@@ -167,6 +167,13 @@ error_chain! {
         TonicStatusError(tonic::Status);
         RustlsError(rustls::TLSError);
         Hex(hex::FromHexError);
+        AvroError(avro_rs::Error);
+        BsonDecodeError(bson::de::Error);
+        BsonEncodeError(bson::ser::Error);
+        CborError(serde_cbor::Error);
+        CsvError(csv::Error);
+        ProtobufDecodeError(prost::DecodeError);
+        RedisError(redis::RedisError);
     }
 
     errors {
@@ -206,6 +213,41 @@ error_chain! {
                 display("Failed to bind non existand {}.", key)
         }
 
+        PipelineNotPaused(key: String) {
+            description("The pipeline is not paused")
+                display("The pipeline {} is not paused.", key)
+        }
+
+        PipelineReloadFailed(key: String, reason: String) {
+            description("The pipeline could not be reloaded")
+                display("Failed to reload pipeline {}: {}.", key, reason)
+        }
+
+        InvalidTap(url: String, reason: String) {
+            description("The tap is invalid")
+                display("Cannot tap {}: {}.", url, reason)
+        }
+
+        InvalidDeployment(reason: String) {
+            description("The deployment is invalid")
+                display("Invalid deployment: {}.", reason)
+        }
+
+        DeploymentConflict(url: String) {
+            description("The deployment conflicts with the running deployment")
+                display("The deployment of {} differs from the running one.", url)
+        }
+
+        DeadLetterNotFound(queue: String, id: u64) {
+            description("The dead letter was not found")
+                display("The dead letter {} was not found in {}.", id, queue)
+        }
+
+        InvalidDeadLetter(reason: String) {
+            description("The dead letter is invalid")
+                display("Invalid dead letter: {}.", reason)
+        }
+
         // TODO: Old errors, verify if needed
         ClonedError(t: String) {
             description("This is a cloned error we need to get rod of this")
@@ -293,6 +335,10 @@ error_chain! {
             description("KV error")
                 display("{}", s)
         }
+        RedisCommandError(s: String) {
+            description("Redis command error")
+                display("{}", s)
+        }
         TLSError(s: String) {
             description("TLS error")
                 display("{}", s)
@@ -51,6 +51,8 @@ pub(crate) mod async_sink;
 pub mod codec;
 /// Tremor runtime configuration
 pub mod config;
+/// Dead-letter queue
+pub mod dlq;
 /// Tremor runtime errors
 pub mod errors;
 /// Tremor function library
@@ -73,8 +75,12 @@ pub mod registry;
 pub mod repository;
 pub(crate) mod sink;
 pub(crate) mod source;
+/// Persistent deployment store
+pub mod store;
 /// Tremor runtime system
 pub mod system;
+/// Live taps on running artefacts
+pub mod tap;
 /// Tremor URI
 pub mod url;
 /// Utility functions
@@ -192,7 +198,7 @@ pub async fn load_query_file(world: &World, file_name: &str) -> Result<usize> {
 
     let id = TremorUrl::parse(&format!("/pipeline/{}", id))?;
     info!("Loading {} from file {}.", id, file_name);
-    world.repo.publish_pipeline(&id, false, query).await?;
+    world.publish_pipeline(&id, query).await?;
 
     Ok(1)
 }
@@ -211,24 +217,22 @@ pub async fn load_cfg_file(world: &World, file_name: &str) -> Result<usize> {
     for o in config.offramps {
         let id = TremorUrl::parse(&format!("/offramp/{}", o.id))?;
         info!("Loading {} from file.", id);
-        world.repo.publish_offramp(&id, false, o).await?;
+        world.publish_offramp(&id, o).await?;
         count += 1;
     }
 
     for o in config.onramps {
         let id = TremorUrl::parse(&format!("/onramp/{}", o.id))?;
         info!("Loading {} from file.", id);
-        world.repo.publish_onramp(&id, false, o).await?;
+        world.publish_onramp(&id, o).await?;
         count += 1;
     }
     for binding in config.bindings {
         let id = TremorUrl::parse(&format!("/binding/{}", binding.id))?;
         info!("Loading {} from file.", id);
         world
-            .repo
             .publish_binding(
                 &id,
-                false,
                 BindingArtefact {
                     binding,
                     mapping: None,
@@ -16,9 +16,14 @@ use crate::pipeline;
 use crate::url::TremorUrl;
 use beef::Cow;
 use halfbrown::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
 use tremor_pipeline::Event;
 use tremor_script::prelude::*;
 
+/// Prometheus exposition of the runtime metrics
+pub mod prometheus;
+
 /// Metrics instance name
 pub static mut INSTANCE: &str = "tremor";
 
@@ -29,10 +34,35 @@ pub(crate) struct Ramp {
     err: u64,
 }
 
+/// Counters of a ramp exported via the prometheus registry
+#[derive(Debug)]
+struct ExportedRamp {
+    r#in: Arc<AtomicU64>,
+    out: Arc<AtomicU64>,
+    err: Arc<AtomicU64>,
+}
+
+impl ExportedRamp {
+    fn new(artefact_url: &TremorUrl) -> Self {
+        let name = prometheus::ramp_events_name(artefact_url);
+        let counter = |port: &str| {
+            let mut labels = prometheus::url_labels(artefact_url);
+            labels.push(("port", port.to_string()));
+            prometheus::REGISTRY.counter(name, "Number of events per ramp port", labels)
+        };
+        Self {
+            r#in: counter("in"),
+            out: counter("out"),
+            err: counter("error"),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub(crate) struct RampReporter {
     artefact_url: TremorUrl,
     metrics: Ramp,
+    exported: ExportedRamp,
     metrics_pipeline: Option<(TremorUrl, pipeline::Addr)>,
     flush_interval: Option<u64>, // as nano-seconds
     last_flush_ns: u64,
@@ -41,6 +71,7 @@ pub(crate) struct RampReporter {
 impl RampReporter {
     pub(crate) fn new(artefact_url: TremorUrl, flush_interval_s: Option<u64>) -> Self {
         Self {
+            exported: ExportedRamp::new(&artefact_url),
             artefact_url,
             metrics: Ramp {
                 r#in: 0,
@@ -59,14 +90,17 @@ impl RampReporter {
 
     pub(crate) fn increment_in(&mut self) {
         self.metrics.r#in += 1;
+        self.exported.r#in.fetch_add(1, Ordering::Relaxed);
     }
 
     pub(crate) fn increment_out(&mut self) {
         self.metrics.out += 1;
+        self.exported.out.fetch_add(1, Ordering::Relaxed);
     }
 
     pub(crate) fn increment_err(&mut self) {
         self.metrics.err += 1;
+        self.exported.err.fetch_add(1, Ordering::Relaxed);
     }
 
     pub(crate) fn periodic_flush(&mut self, timestamp: u64) -> Option<u64> {
@@ -118,6 +152,12 @@ impl RampReporter {
     }
 }
 
+impl Drop for RampReporter {
+    fn drop(&mut self) {
+        prometheus::REGISTRY.remove(&prometheus::url_labels(&self.artefact_url));
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -146,4 +186,17 @@ mod test {
         assert_eq!(r.periodic_flush(1_000_000_001), None);
         assert_eq!(r.periodic_flush(2_000_000_000), Some(2_000_000_000));
     }
+
+    #[test]
+    fn exported() {
+        let url = TremorUrl::parse("/offramp/exported/00").unwrap();
+        let mut r = RampReporter::new(url, None);
+        r.increment_out();
+        r.increment_out();
+        let out =
+            "tremor_offramp_events_total{kind=\"offramp\",artefact=\"exported\",servant=\"00\",port=\"out\"} 2\n";
+        assert!(prometheus::render().contains(out));
+        drop(r);
+        assert!(!prometheus::render().contains(out));
+    }
 }
@@ -0,0 +1,306 @@
+// Copyright 2020-2021, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Registry of runtime metrics, rendered in the prometheus text exposition format.
+//!
+//! Ramps and pipelines register their series here on startup and remove them
+//! again once they are stopped. Series are labeled with the `kind` of resource
+//! and the `artefact` and `servant` ids of the `TremorUrl` they belong to, so
+//! an onramp and a pipeline sharing ids never remove each others series.
+
+use crate::url::{ResourceType, TremorUrl};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, PoisonError, RwLock};
+
+/// Label names and values of a single series
+pub(crate) type Labels = Vec<(&'static str, String)>;
+
+/// Upper bounds of the latency histogram buckets in nanoseconds
+const BUCKETS_NS: [u64; 11] = [
+    10_000,
+    50_000,
+    100_000,
+    500_000,
+    1_000_000,
+    5_000_000,
+    10_000_000,
+    50_000_000,
+    100_000_000,
+    500_000_000,
+    1_000_000_000,
+];
+
+lazy_static! {
+    /// The registry exposed via the `/metrics` api endpoint
+    pub(crate) static ref REGISTRY: Registry = Registry::default();
+}
+
+/// Renders all metrics of the runtime in the prometheus text exposition format
+#[must_use]
+pub fn render() -> String {
+    REGISTRY.render()
+}
+
+/// Histogram of latencies, rendered in seconds
+#[derive(Debug, Default)]
+pub(crate) struct Histogram {
+    buckets: [AtomicU64; BUCKETS_NS.len()],
+    count: AtomicU64,
+    sum_ns: AtomicU64,
+}
+
+impl Histogram {
+    pub(crate) fn observe_ns(&self, ns: u64) {
+        if let Some(i) = BUCKETS_NS.iter().position(|b| ns <= *b) {
+            self.buckets[i].fetch_add(1, Ordering::Relaxed);
+        }
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum_ns.fetch_add(ns, Ordering::Relaxed);
+    }
+}
+
+#[derive(Debug)]
+enum Series {
+    Counter(Arc<AtomicU64>),
+    Histogram(Arc<Histogram>),
+}
+
+#[derive(Debug)]
+struct Family {
+    help: &'static str,
+    series: BTreeMap<Labels, Series>,
+}
+
+/// A set of metric families
+#[derive(Debug, Default)]
+pub(crate) struct Registry {
+    families: RwLock<BTreeMap<&'static str, Family>>,
+}
+
+impl Registry {
+    fn series<F>(&self, name: &'static str, help: &'static str, labels: Labels, new: F) -> Series
+    where
+        F: FnOnce() -> Series,
+    {
+        let mut families = self
+            .families
+            .write()
+            .unwrap_or_else(PoisonError::into_inner);
+        let family = families.entry(name).or_insert_with(|| Family {
+            help,
+            series: BTreeMap::new(),
+        });
+        match family.series.entry(labels).or_insert_with(new) {
+            Series::Counter(c) => Series::Counter(c.clone()),
+            Series::Histogram(h) => Series::Histogram(h.clone()),
+        }
+    }
+
+    /// Returns the counter `name` with the given `labels`, registering it if needed
+    pub(crate) fn counter(
+        &self,
+        name: &'static str,
+        help: &'static str,
+        labels: Labels,
+    ) -> Arc<AtomicU64> {
+        match self.series(name, help, labels, || Series::Counter(Arc::default())) {
+            Series::Counter(c) => c,
+            // a family never mixes counters and histograms, so hand out a detached counter
+            Series::Histogram(_) => Arc::default(),
+        }
+    }
+
+    /// Returns the histogram `name` with the given `labels`, registering it if needed
+    pub(crate) fn histogram(
+        &self,
+        name: &'static str,
+        help: &'static str,
+        labels: Labels,
+    ) -> Arc<Histogram> {
+        match self.series(name, help, labels, || Series::Histogram(Arc::default())) {
+            Series::Histogram(h) => h,
+            Series::Counter(_) => Arc::default(),
+        }
+    }
+
+    /// Removes all series carrying all of the given `labels`
+    pub(crate) fn remove(&self, labels: &[(&'static str, String)]) {
+        let mut families = self
+            .families
+            .write()
+            .unwrap_or_else(PoisonError::into_inner);
+        for family in families.values_mut() {
+            family
+                .series
+                .retain(|series_labels, _| !labels.iter().all(|l| series_labels.contains(l)));
+        }
+        families.retain(|_, family| !family.series.is_empty());
+    }
+
+    /// Renders all series in the prometheus text exposition format
+    #[allow(clippy::cast_precision_loss)]
+    pub(crate) fn render(&self) -> String {
+        let families = self.families.read().unwrap_or_else(PoisonError::into_inner);
+        let mut out = String::new();
+        // writing to a string can't fail
+        for (name, family) in &*families {
+            let kind = match family.series.values().next() {
+                Some(Series::Histogram(_)) => "histogram",
+                _ => "counter",
+            };
+            let _ = writeln!(out, "# HELP {} {}", name, family.help);
+            let _ = writeln!(out, "# TYPE {} {}", name, kind);
+            for (labels, series) in &family.series {
+                match series {
+                    Series::Counter(c) => {
+                        let _ = writeln!(
+                            out,
+                            "{}{} {}",
+                            name,
+                            render_labels(labels, None),
+                            c.load(Ordering::Relaxed)
+                        );
+                    }
+                    Series::Histogram(h) => {
+                        let mut cumulative = 0;
+                        for (bucket, bound) in h.buckets.iter().zip(&BUCKETS_NS) {
+                            cumulative += bucket.load(Ordering::Relaxed);
+                            let le = (*bound as f64 / 1_000_000_000.0).to_string();
+                            let _ = writeln!(
+                                out,
+                                "{}_bucket{} {}",
+                                name,
+                                render_labels(labels, Some(&le)),
+                                cumulative
+                            );
+                        }
+                        let count = h.count.load(Ordering::Relaxed);
+                        let sum = h.sum_ns.load(Ordering::Relaxed) as f64 / 1_000_000_000.0;
+                        let labels_str = render_labels(labels, None);
+                        let _ = writeln!(
+                            out,
+                            "{}_bucket{} {}",
+                            name,
+                            render_labels(labels, Some("+Inf")),
+                            count
+                        );
+                        let _ = writeln!(out, "{}_sum{} {}", name, labels_str, sum);
+                        let _ = writeln!(out, "{}_count{} {}", name, labels_str, count);
+                    }
+                }
+            }
+        }
+        out
+    }
+}
+
+fn render_labels(labels: &[(&'static str, String)], le: Option<&str>) -> String {
+    let mut rendered: Vec<String> = labels
+        .iter()
+        .map(|(k, v)| format!("{}=\"{}\"", k, escape(v)))
+        .collect();
+    if let Some(le) = le {
+        rendered.push(format!("le=\"{}\"", le));
+    }
+    if rendered.is_empty() {
+        String::new()
+    } else {
+        format!("{{{}}}", rendered.join(","))
+    }
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Labels identifying the kind of resource, artefact and servant of `url`
+pub(crate) fn url_labels(url: &TremorUrl) -> Labels {
+    vec![
+        (
+            "kind",
+            url.resource_type()
+                .map(|kind| kind.to_string())
+                .unwrap_or_default(),
+        ),
+        ("artefact", url.artefact().unwrap_or_default().to_string()),
+        ("servant", url.instance().unwrap_or_default().to_string()),
+    ]
+}
+
+/// Name of the event counter for the ramp identified by `url`
+pub(crate) fn ramp_events_name(url: &TremorUrl) -> &'static str {
+    match url.resource_type() {
+        Some(ResourceType::Offramp) => "tremor_offramp_events_total",
+        _ => "tremor_onramp_events_total",
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::errors::Result;
+
+    #[test]
+    fn counters() -> Result<()> {
+        let registry = Registry::default();
+        let url = TremorUrl::parse("/offramp/snot/01")?;
+        let mut labels = url_labels(&url);
+        labels.push(("port", "out".to_string()));
+        let c = registry.counter(ramp_events_name(&url), "events per port", labels.clone());
+        c.fetch_add(2, Ordering::Relaxed);
+        registry
+            .counter(ramp_events_name(&url), "events per port", labels)
+            .fetch_add(1, Ordering::Relaxed);
+        assert_eq!(
+            "# HELP tremor_offramp_events_total events per port\n\
+             # TYPE tremor_offramp_events_total counter\n\
+             tremor_offramp_events_total{kind=\"offramp\",artefact=\"snot\",servant=\"01\",port=\"out\"} 3\n",
+            registry.render()
+        );
+        // a pipeline with the same ids keeps its series
+        let pipeline = TremorUrl::parse("/pipeline/snot/01")?;
+        registry
+            .histogram("latency", "latency", url_labels(&pipeline))
+            .observe_ns(1);
+        registry.remove(&url_labels(&url));
+        assert!(!registry.render().contains("offramp"));
+        assert!(registry
+            .render()
+            .contains("latency_count{kind=\"pipeline\""));
+        registry.remove(&url_labels(&pipeline));
+        assert_eq!("", registry.render());
+        Ok(())
+    }
+
+    #[test]
+    fn histogram() {
+        let registry = Registry::default();
+        let h = registry.histogram("latency", "latency", vec![("pipeline", "a\"b".into())]);
+        h.observe_ns(20_000);
+        h.observe_ns(2_000_000_000);
+        let rendered = registry.render();
+        assert!(rendered.contains("# TYPE latency histogram\n"));
+        assert!(rendered.contains("latency_bucket{pipeline=\"a\\\"b\",le=\"0.00001\"} 0\n"));
+        assert!(rendered.contains("latency_bucket{pipeline=\"a\\\"b\",le=\"0.00005\"} 1\n"));
+        assert!(rendered.contains("latency_bucket{pipeline=\"a\\\"b\",le=\"1\"} 1\n"));
+        assert!(rendered.contains("latency_bucket{pipeline=\"a\\\"b\",le=\"+Inf\"} 2\n"));
+        assert!(rendered.contains("latency_sum{pipeline=\"a\\\"b\"} 2.00002\n"));
+        assert!(rendered.contains("latency_count{pipeline=\"a\\\"b\"} 2\n"));
+    }
+}
@@ -19,12 +19,14 @@ use crate::permge::PriorityMerge;
 use crate::pipeline;
 use crate::registry::ServantId;
 use crate::sink::{
-    self, amqp, blackhole, cb, debug, dns, elastic, exit, file, gcs, gpub, handle_response, kafka,
-    kv, nats, newrelic, otel, postgres, rest, stderr, stdout, tcp, udp, ws,
+    self, amqp, blackhole, cb, debug, dlq, dns, elastic, exit, file, gcs, gpub, handle_response,
+    kafka, kv, mqtt, nats, newrelic, otel, postgres, redis, rest, stderr, stdout, tcp, udp, ws,
 };
 use crate::source::Processors;
+use crate::tap::{self, Tap};
 use crate::url::ports::{IN, METRICS};
 use crate::url::TremorUrl;
+use crate::{postprocessor, preprocessor};
 use crate::{Event, OpConfig};
 use async_channel::{self, bounded, unbounded};
 use async_std::stream::StreamExt; // for .next() on PriorityMerge
@@ -32,11 +34,14 @@ use async_std::task::{self, JoinHandle};
 use beef::Cow;
 use halfbrown::HashMap;
 use pipeline::ConnectTarget;
-use std::borrow::{Borrow, BorrowMut};
+use retry::{Failure, Pending, Retries};
+use std::borrow::Borrow;
 use std::fmt;
 use tremor_common::ids::OfframpIdGen;
 use tremor_common::time::nanotime;
 
+pub(crate) mod retry;
+
 #[derive(Debug)]
 pub enum Msg {
     Event {
@@ -54,6 +59,7 @@ pub enum Msg {
         id: TremorUrl,
         tx: async_channel::Sender<bool>,
     },
+    Tap(Tap),
     Terminate,
 }
 
@@ -112,16 +118,19 @@ pub fn lookup(name: &str, config: &Option<OpConfig>) -> Result<Box<dyn Offramp>>
         "blackhole" => blackhole::Blackhole::from_config(config),
         "cb" => cb::Cb::from_config(config),
         "debug" => debug::Debug::from_config(config),
+        "dlq" => dlq::Dlq::from_config(config),
         "dns" => dns::Dns::from_config(config),
         "elastic" => elastic::Elastic::from_config(config),
         "exit" => exit::Exit::from_config(config),
         "file" => file::File::from_config(config),
         "kafka" => kafka::Kafka::from_config(config),
         "kv" => kv::Kv::from_config(config),
+        "mqtt" => mqtt::Mqtt::from_config(config),
         "nats" => nats::Nats::from_config(config),
         "newrelic" => newrelic::NewRelic::from_config(config),
         "otel" => otel::OpenTelemetry::from_config(config),
         "postgres" => postgres::Postgres::from_config(config),
+        "redis" => redis::Redis::from_config(config),
         "rest" => rest::Rest::from_config(config),
         "stderr" => stderr::StdErr::from_config(config),
         "stdout" => stdout::StdOut::from_config(config),
@@ -139,10 +148,11 @@ pub(crate) struct Create {
     pub offramp: Box<dyn Offramp>,
     pub codec: Box<dyn Codec>,
     pub codec_map: halfbrown::HashMap<String, Box<dyn Codec>>,
-    pub preprocessors: Vec<String>,
-    pub postprocessors: Vec<String>,
+    pub preprocessors: Vec<preprocessor::Config>,
+    pub postprocessors: Vec<postprocessor::Config>,
     pub metrics_reporter: RampReporter,
     pub is_linked: bool,
+    pub retry: Option<retry::Config>,
 }
 
 #[cfg(not(tarpaulin_include))]
@@ -184,6 +194,79 @@ async fn send_to_pipelines(
 pub(crate) enum OfframpMsg {
     Msg(Msg),
     Reply(sink::Reply),
+    Retry(Pending),
+}
+
+/// Hands an event to the offramp, returns the insight to send upstream
+#[allow(clippy::too_many_arguments)]
+async fn deliver(
+    offramp_url: &TremorUrl,
+    offramp: &mut dyn Offramp,
+    codec: &mut dyn Codec,
+    codec_map: &HashMap<String, Box<dyn Codec>>,
+    metrics_reporter: &mut RampReporter,
+    retries: &mut Option<Retries>,
+    pending: Pending,
+) -> Option<Event> {
+    let Pending {
+        input,
+        event,
+        attempt,
+    } = pending;
+    let ingest_ns = event.ingest_ns;
+    let transactional = event.transactional;
+    let ids = event.id.clone();
+    // with retries the event is kept until it is delivered
+    let pending = retries.as_ref().map(|_| Pending {
+        input: input.clone(),
+        event: event.clone(),
+        attempt,
+    });
+
+    let res = offramp.on_event(codec, codec_map, &input, event).await;
+    if let Err(err) = &res {
+        error!("[Offramp::{}] On Event error: {}", offramp_url, err);
+        metrics_reporter.increment_err();
+    } else {
+        metrics_reporter.increment_out();
+    }
+    if let (Some(retries), Some(pending)) = (retries.as_mut(), pending) {
+        match res {
+            Err(err) => {
+                // only fail upstream once the event is not retried anymore
+                if let Some(pending) = retries.retry(pending, &Failure::Error(&err)) {
+                    warn!(
+                        "[Offramp::{}] Giving up on event after {} attempts",
+                        offramp_url, pending.attempt
+                    );
+                    if transactional {
+                        return Some(Event::cb_fail(ingest_ns, ids));
+                    }
+                }
+            }
+            Ok(()) if offramp.auto_ack() => {
+                if transactional {
+                    return Some(Event::cb_ack(ingest_ns, ids));
+                }
+            }
+            // the sink acks or fails the event itself
+            Ok(()) if transactional => return retries.track(pending, nanotime()),
+            Ok(()) => (),
+        }
+        None
+    } else {
+        let fail = res.is_err();
+        // always send a fail, if on_event errored and the event is transactional
+        // assuming if a sink fails, it didnt already sent a fail insight via reply_channel
+        // even if it did, double fails or double acks should not lead to trouble
+        // this will prevent fail insights being swallowed here
+        // sinks need to take care of sending acks themselves. Deal with it.
+        if (fail || offramp.auto_ack()) && transactional {
+            Some(Event::ack_or_fail(!fail, ingest_ns, ids))
+        } else {
+            None
+        }
+    }
 }
 
 impl Manager {
@@ -204,6 +287,7 @@ impl Manager {
             mut metrics_reporter,
             is_linked,
             id,
+            retry,
         }: Create,
         offramp_uid: u64,
     ) -> Result<()> {
@@ -228,8 +312,12 @@ impl Manager {
             error!("Failed to create offramp {}: {}", id, e);
             return Err(e);
         }
+        let (retry_tx, retry_rx) = unbounded::<Pending>();
+        let mut retries = retry.map(|config| Retries::new(config, retry_tx));
         // merge channels and prioritize contraflow/insight events
-        let m_rx = msg_rx.map(OfframpMsg::Msg);
+        let m_rx = msg_rx
+            .map(OfframpMsg::Msg)
+            .merge(retry_rx.map(OfframpMsg::Retry));
         let c_rx = cf_rx.map(OfframpMsg::Reply);
         let mut to_and_from_offramp_rx = PriorityMerge::new(c_rx, m_rx);
 
@@ -242,6 +330,7 @@ impl Manager {
             // for linked offramp output (port to pipeline(s) mapping)
             let mut dest_pipelines: HashMap<Cow<'static, str>, Vec<(TremorUrl, pipeline::Addr)>> =
                 HashMap::new();
+            let mut taps: Vec<Tap> = Vec::new();
 
             info!("[Offramp::{}] started", offramp_url);
 
@@ -250,37 +339,41 @@ impl Manager {
                     OfframpMsg::Msg(m) => {
                         match m {
                             Msg::Signal(signal) => {
+                                if let Some(insight) =
+                                    retries.as_mut().and_then(|r| r.expire(nanotime()))
+                                {
+                                    send_to_pipelines(&offramp_url, &mut pipelines, insight).await;
+                                }
                                 if let Some(insight) = offramp.on_signal(signal).await {
                                     send_to_pipelines(&offramp_url, &mut pipelines, insight).await;
                                 }
                             }
                             Msg::Event { event, input } => {
-                                let ingest_ns = event.ingest_ns;
-                                let transactional = event.transactional;
-                                let ids = event.id.clone();
-
-                                metrics_reporter.periodic_flush(ingest_ns);
+                                if !taps.is_empty() {
+                                    if tap::offer(&mut taps, None, &input, &event) {
+                                        tap::detach(&mut taps);
+                                    }
+                                }
+                                metrics_reporter.periodic_flush(event.ingest_ns);
                                 metrics_reporter.increment_in();
 
-                                let c: &mut dyn Codec = codec.borrow_mut();
-                                let fail = if let Err(err) =
-                                    offramp.on_event(c, &codec_map, input.borrow(), event).await
-                                {
-                                    error!("[Offramp::{}] On Event error: {}", offramp_url, err);
-                                    metrics_reporter.increment_err();
-                                    true
-                                } else {
-                                    metrics_reporter.increment_out();
-                                    false
+                                let pending = Pending {
+                                    input,
+                                    event,
+                                    attempt: 1,
                                 };
-                                // always send a fail, if on_event errored and the event is transactional
-                                // assuming if a sink fails, it didnt already sent a fail insight via reply_channel
-                                // even if it did, double fails or double acks should not lead to trouble
-                                // this will prevent fail insights being swallowed here
-                                // sinks need to take care of sending acks themselves. Deal with it.
-                                if (fail || offramp.auto_ack()) && transactional {
-                                    let e = Event::ack_or_fail(!fail, ingest_ns, ids);
-                                    send_to_pipelines(&offramp_url, &mut pipelines, e).await;
+                                if let Some(insight) = deliver(
+                                    &offramp_url,
+                                    offramp.as_mut(),
+                                    codec.as_mut(),
+                                    &codec_map,
+                                    &mut metrics_reporter,
+                                    &mut retries,
+                                    pending,
+                                )
+                                .await
+                                {
+                                    send_to_pipelines(&offramp_url, &mut pipelines, insight).await;
                                 }
                             }
                             Msg::Connect { port, id, addr } => {
@@ -331,6 +424,11 @@ impl Manager {
                                     if let Err(e) = addr
                                         .send_mgmt(pipeline::MgmtMsg::ConnectInput {
                                             input_url: offramp_url.clone(),
+                                            port: id
+                                                .instance_port()
+                                                .unwrap_or("in")
+                                                .to_string()
+                                                .into(),
                                             target: ConnectTarget::Offramp(offramp_addr.clone()),
                                             transactional: false, // TODO: Linked Offramps do not support insights yet
                                         })
@@ -400,6 +498,11 @@ impl Manager {
                                     break;
                                 }
                             }
+                            Msg::Tap(tap) => {
+                                info!("[Offramp::{}] Attaching tap to {}", offramp_url, tap.url());
+                                tap::detach(&mut taps);
+                                taps.push(tap);
+                            }
                             Msg::Terminate => {
                                 info!("[Offramp::{}] Terminating...", offramp_url);
                                 offramp.terminate().await;
@@ -408,9 +511,40 @@ impl Manager {
                         }
                     }
                     OfframpMsg::Reply(sink::Reply::Insight(event)) => {
-                        send_to_pipelines(&offramp_url, &mut pipelines, event).await
+                        let insight = if let Some(retries) = retries.as_mut() {
+                            retries.on_insight(event)
+                        } else {
+                            Some(event)
+                        };
+                        if let Some(insight) = insight {
+                            send_to_pipelines(&offramp_url, &mut pipelines, insight).await
+                        }
+                    }
+                    OfframpMsg::Retry(pending) => {
+                        debug!(
+                            "[Offramp::{}] Retrying event, attempt {}",
+                            offramp_url, pending.attempt
+                        );
+                        if let Some(insight) = deliver(
+                            &offramp_url,
+                            offramp.as_mut(),
+                            codec.as_mut(),
+                            &codec_map,
+                            &mut metrics_reporter,
+                            &mut retries,
+                            pending,
+                        )
+                        .await
+                        {
+                            send_to_pipelines(&offramp_url, &mut pipelines, insight).await;
+                        }
                     }
                     OfframpMsg::Reply(sink::Reply::Response(port, event)) => {
+                        if !taps.is_empty() {
+                            if tap::offer(&mut taps, None, &port, &event) {
+                                tap::detach(&mut taps);
+                            }
+                        }
                         if let Some(pipelines) = dest_pipelines.get_mut(&port) {
                             if let Err(e) = handle_response(event, pipelines.iter()).await {
                                 error!("[Offramp::{}] Response error: {}", offramp_url, e)
@@ -597,6 +731,7 @@ mod tests {
                 metrics_reporter: ramp_reporter,
                 offramp: Box::new(offramp),
                 is_linked: true,
+                retry: None,
             }),
         );
         sender.send(create).await?;
@@ -0,0 +1,497 @@
+// Copyright 2020-2021, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Retries of events an offramp failed to deliver.
+//!
+//! With a `retry` policy configured, an offramp keeps each event until it is
+//! delivered. If the sink fails it, either by returning an error or by sending
+//! a `fail` insight, the event is handed to the sink again after an
+//! exponentially growing, jittered backoff. Only once all attempts are used up
+//! the failure is passed on upstream. Events the sink doesn't ack or fail
+//! within `ack_timeout_ms`, or that exceed the `max_pending` events waiting for
+//! an insight, are failed upstream without a retry, oldest first.
+
+use crate::errors::{Error, ErrorKind, Result};
+use async_channel::Sender;
+use async_std::task;
+use beef::Cow;
+use hashbrown::HashMap;
+use rand::Rng;
+use std::collections::VecDeque;
+use std::time::Duration;
+use tremor_pipeline::{CbAction, Event, EventId};
+
+/// Retry policy of an offramp
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// maximum number of attempts to deliver an event, including the first one
+    #[serde(default = "Config::default_max_attempts")]
+    pub max_attempts: u32,
+    /// delay before the first retry in milliseconds
+    #[serde(default = "Config::default_backoff_ms")]
+    pub backoff_ms: u64,
+    /// factor the delay grows by with every retry
+    #[serde(default = "Config::default_multiplier")]
+    pub multiplier: f64,
+    /// upper bound of the delay in milliseconds
+    #[serde(default = "Config::default_max_backoff_ms")]
+    pub max_backoff_ms: u64,
+    /// fraction of the delay that is randomized, between 0 and 1
+    #[serde(default = "Config::default_jitter")]
+    pub jitter: f64,
+    /// the failures that are retried
+    #[serde(default = "Config::default_retry_on")]
+    pub retry_on: Vec<ErrorClass>,
+    /// time the sink gets to ack or fail an event in milliseconds
+    #[serde(default = "Config::default_ack_timeout_ms")]
+    pub ack_timeout_ms: u64,
+    /// maximum number of events waiting for the sink to ack or fail them
+    #[serde(default = "Config::default_max_pending")]
+    pub max_pending: usize,
+}
+
+/// Classes of failures of a sink
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorClass {
+    /// `fail` insights sent by the sink
+    Fail,
+    /// any error returned by the sink
+    Error,
+    /// io errors returned by the sink
+    Io,
+    /// timeouts returned by the sink
+    Timeout,
+}
+
+/// A failure to deliver an event
+pub(crate) enum Failure<'e> {
+    /// the sink sent a `fail` insight
+    Insight,
+    /// the sink returned an error
+    Error(&'e Error),
+}
+
+impl ErrorClass {
+    fn matches(self, failure: &Failure) -> bool {
+        match (self, failure) {
+            (Self::Fail, Failure::Insight) | (Self::Error, Failure::Error(_)) => true,
+            (Self::Io, Failure::Error(e)) => matches!(e.0, ErrorKind::Io(_)),
+            (Self::Timeout, Failure::Error(e)) => {
+                matches!(&e.0, ErrorKind::Io(e) if e.kind() == std::io::ErrorKind::TimedOut)
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Config {
+    fn default_max_attempts() -> u32 {
+        3
+    }
+
+    fn default_backoff_ms() -> u64 {
+        100
+    }
+
+    fn default_multiplier() -> f64 {
+        2.0
+    }
+
+    fn default_max_backoff_ms() -> u64 {
+        30_000
+    }
+
+    fn default_jitter() -> f64 {
+        0.1
+    }
+
+    fn default_retry_on() -> Vec<ErrorClass> {
+        vec![ErrorClass::Fail, ErrorClass::Error]
+    }
+
+    fn default_ack_timeout_ms() -> u64 {
+        60_000
+    }
+
+    fn default_max_pending() -> usize {
+        10_000
+    }
+
+    /// Checks the policy is sound
+    ///
+    /// # Errors
+    ///  * if any of the settings is out of range
+    pub(crate) fn validate(&self) -> Result<()> {
+        if self.max_attempts == 0 {
+            Err("retry `max_attempts` needs to be at least 1".into())
+        } else if self.multiplier.is_nan() || self.multiplier < 1.0 {
+            Err("retry `multiplier` needs to be at least 1.0".into())
+        } else if !(0.0..=1.0).contains(&self.jitter) {
+            Err("retry `jitter` needs to be between 0.0 and 1.0".into())
+        } else if self.max_pending == 0 {
+            Err("retry `max_pending` needs to be at least 1".into())
+        } else {
+            Ok(())
+        }
+    }
+
+    fn is_retryable(&self, failure: &Failure) -> bool {
+        self.retry_on.iter().any(|class| class.matches(failure))
+    }
+
+    /// Delay before retrying an event whose `attempt` failed
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_precision_loss,
+        clippy::cast_sign_loss,
+        clippy::cast_possible_wrap
+    )]
+    fn backoff(&self, attempt: u32) -> Duration {
+        let exp = attempt.saturating_sub(1).min(i32::MAX as u32) as i32;
+        let delay =
+            (self.backoff_ms as f64 * self.multiplier.powi(exp)).min(self.max_backoff_ms as f64);
+        let jitter = if self.jitter > 0.0 {
+            rand::thread_rng().gen_range(-self.jitter..=self.jitter)
+        } else {
+            0.0
+        };
+        Duration::from_millis((delay * (1.0 + jitter)).max(0.0) as u64)
+    }
+}
+
+/// An event on its way through an offramp
+#[derive(Debug)]
+pub(crate) struct Pending {
+    pub(crate) input: Cow<'static, str>,
+    pub(crate) event: Event,
+    /// the attempt the event is on, starting at 1
+    pub(crate) attempt: u32,
+}
+
+/// Key of a pending event: its source, stream and event id
+type Key = (u64, u64, u64);
+
+fn key(id: &EventId) -> Key {
+    (id.source_id(), id.stream_id(), id.event_id())
+}
+
+/// Events of an offramp that failed or wait for their ack or fail
+pub(crate) struct Retries {
+    config: Config,
+    /// events that wait for their ack or fail insight, with their deadline
+    pending: HashMap<Key, (u64, Pending)>,
+    /// deadlines of the pending events, oldest first. Entries of events that
+    /// were settled since are skipped once they come up.
+    deadlines: VecDeque<(u64, Key)>,
+    tx: Sender<Pending>,
+}
+
+impl Retries {
+    /// Creates retries sending events to retry to `tx` once they are due
+    pub(crate) fn new(config: Config, tx: Sender<Pending>) -> Self {
+        Self {
+            config,
+            pending: HashMap::new(),
+            deadlines: VecDeque::new(),
+            tx,
+        }
+    }
+
+    /// Keeps an event the sink has to ack or fail, returns a fail insight for
+    /// the events that expired or exceed `max_pending`, if any
+    pub(crate) fn track(&mut self, pending: Pending, now: u64) -> Option<Event> {
+        let key = key(&pending.event.id);
+        let deadline = now.saturating_add(self.config.ack_timeout_ms.saturating_mul(1_000_000));
+        self.pending.insert(key, (deadline, pending));
+        self.deadlines.push_back((deadline, key));
+        // drop the entries of settled events before they pile up
+        if self.deadlines.len() > 2 * self.pending.len().max(1024) {
+            let pending = &self.pending;
+            self.deadlines.retain(
+                |(deadline, key)| matches!(pending.get(key), Some((d, _)) if d == deadline),
+            );
+        }
+        self.expire(now)
+    }
+
+    /// Fails the events the sink didn't ack or fail in time and the oldest
+    /// ones exceeding `max_pending`, returns the fail insight to pass on
+    /// upstream, if any
+    pub(crate) fn expire(&mut self, now: u64) -> Option<Event> {
+        let mut expired: Option<EventId> = None;
+        while let Some((deadline, key)) = self.deadlines.front().copied() {
+            if deadline > now && self.pending.len() <= self.config.max_pending {
+                break;
+            }
+            self.deadlines.pop_front();
+            match self.pending.get(&key) {
+                Some((d, _)) if *d == deadline => (),
+                // settled or tracked again since
+                _ => continue,
+            }
+            if let Some((_, p)) = self.pending.remove(&key) {
+                warn!(
+                    "Failing event {} the sink didn't ack or fail in time",
+                    p.event.id
+                );
+                if let Some(id) = expired.as_mut() {
+                    id.track(&p.event.id);
+                } else {
+                    expired = Some(p.event.id);
+                }
+            }
+        }
+        expired.map(|id| Event::cb_fail(now, id))
+    }
+
+    /// Removes the tracked events `id` refers to
+    fn settle(&mut self, id: &EventId) -> Vec<Pending> {
+        // sinks usually settle the very event they were handed
+        let exact = key(id);
+        if matches!(self.pending.get(&exact), Some((_, p)) if &p.event.id == id) {
+            return self
+                .pending
+                .remove(&exact)
+                .map(|(_, p)| p)
+                .into_iter()
+                .collect();
+        }
+        let keys: Vec<Key> = self
+            .pending
+            .iter()
+            .filter(|(_, (_, p))| id.is_tracking(&p.event.id))
+            .map(|(key, _)| *key)
+            .collect();
+        keys.iter()
+            .filter_map(|key| self.pending.remove(key))
+            .map(|(_, p)| p)
+            .collect()
+    }
+
+    /// Schedules the retry of a failed event, hands the event back if it
+    /// isn't retried
+    pub(crate) fn retry(&self, pending: Pending, failure: &Failure) -> Option<Pending> {
+        if pending.attempt >= self.config.max_attempts || !self.config.is_retryable(failure) {
+            return Some(pending);
+        }
+        let delay = self.config.backoff(pending.attempt);
+        let tx = self.tx.clone();
+        let next = Pending {
+            attempt: pending.attempt + 1,
+            ..pending
+        };
+        task::spawn(async move {
+            task::sleep(delay).await;
+            if let Err(e) = tx.send(next).await {
+                warn!("Dropping retry of an event for a stopped offramp: {}", e);
+            }
+        });
+        None
+    }
+
+    /// Settles the tracked events an insight of the sink refers to, returns
+    /// the insight to pass on upstream, if any
+    pub(crate) fn on_insight(&mut self, insight: Event) -> Option<Event> {
+        match insight.cb {
+            CbAction::Ack => {
+                self.settle(&insight.id);
+                Some(insight)
+            }
+            CbAction::Fail => {
+                let failed = self.settle(&insight.id);
+                if failed.is_empty() {
+                    // not one of ours
+                    return Some(insight);
+                }
+                let mut exhausted: Option<EventId> = None;
+                for p in failed {
+                    if let Some(p) = self.retry(p, &Failure::Insight) {
+                        if let Some(id) = exhausted.as_mut() {
+                            id.track(&p.event.id);
+                        } else {
+                            exhausted = Some(p.event.id);
+                        }
+                    }
+                }
+                exhausted.map(|id| Event::cb_fail(insight.ingest_ns, id))
+            }
+            _ => Some(insight),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use async_channel::unbounded;
+
+    fn config(max_attempts: u32) -> Config {
+        Config {
+            max_attempts,
+            backoff_ms: 1,
+            multiplier: 2.0,
+            max_backoff_ms: 3,
+            jitter: 0.0,
+            retry_on: vec![ErrorClass::Fail, ErrorClass::Io],
+            ack_timeout_ms: 1,
+            max_pending: 2,
+        }
+    }
+
+    fn pending(id: u64) -> Pending {
+        Pending {
+            input: "in".into(),
+            event: Event {
+                id: (0, 0, id).into(),
+                transactional: true,
+                ..Event::default()
+            },
+            attempt: 1,
+        }
+    }
+
+    #[test]
+    fn parse_config() -> Result<()> {
+        let config: Config = serde_yaml::from_str("retry_on: [io, timeout]")?;
+        assert_eq!(3, config.max_attempts);
+        assert_eq!(vec![ErrorClass::Io, ErrorClass::Timeout], config.retry_on);
+        config.validate()?;
+        assert!(Config {
+            jitter: 1.5,
+            ..config.clone()
+        }
+        .validate()
+        .is_err());
+        assert!(Config {
+            max_attempts: 0,
+            ..config
+        }
+        .validate()
+        .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn backoff() {
+        let config = config(5);
+        assert_eq!(Duration::from_millis(1), config.backoff(1));
+        assert_eq!(Duration::from_millis(2), config.backoff(2));
+        // capped at `max_backoff_ms`
+        assert_eq!(Duration::from_millis(3), config.backoff(3));
+        assert_eq!(Duration::from_millis(3), config.backoff(u32::MAX));
+
+        let config = Config {
+            backoff_ms: 1000,
+            max_backoff_ms: 1000,
+            jitter: 0.5,
+            ..config
+        };
+        let delay = config.backoff(1);
+        assert!(delay >= Duration::from_millis(500));
+        assert!(delay <= Duration::from_millis(1500));
+    }
+
+    #[test]
+    fn error_classes() {
+        let io = Error::from(std::io::Error::new(std::io::ErrorKind::TimedOut, "snot"));
+        let other = Error::from("badger");
+        assert!(ErrorClass::Fail.matches(&Failure::Insight));
+        assert!(!ErrorClass::Fail.matches(&Failure::Error(&io)));
+        assert!(ErrorClass::Error.matches(&Failure::Error(&other)));
+        assert!(ErrorClass::Io.matches(&Failure::Error(&io)));
+        assert!(!ErrorClass::Io.matches(&Failure::Error(&other)));
+        assert!(ErrorClass::Timeout.matches(&Failure::Error(&io)));
+    }
+
+    #[async_std::test]
+    async fn retry_until_exhausted() -> Result<()> {
+        let (tx, rx) = unbounded();
+        let mut retries = Retries::new(config(2), tx);
+
+        // errors that aren't retryable are handed back right away
+        assert!(retries
+            .retry(pending(1), &Failure::Error(&Error::from("snot")))
+            .is_some());
+
+        // a fail insight schedules a retry
+        assert!(retries.track(pending(1), 0).is_none());
+        assert!(retries.track(pending(2), 0).is_none());
+        assert!(retries
+            .on_insight(Event::cb_fail(0, (0, 0, 1).into()))
+            .is_none());
+        let retry = rx.recv().await?;
+        assert_eq!(2, retry.attempt);
+        assert_eq!(EventId::from((0, 0, 1)), retry.event.id);
+
+        // the second fail is passed on
+        assert!(retries.track(retry, 0).is_none());
+        let insight = retries.on_insight(Event::cb_fail(0, (0, 0, 1).into()));
+        assert_eq!(
+            Some(CbAction::Fail),
+            insight.as_ref().map(|insight| insight.cb)
+        );
+
+        // acks settle tracked events and are passed on
+        let ack = retries.on_insight(Event::cb_ack(0, (0, 0, 2).into()));
+        assert_eq!(Some(CbAction::Ack), ack.map(|insight| insight.cb));
+        assert!(retries.pending.is_empty());
+
+        // insights for untracked events are passed on
+        assert!(retries
+            .on_insight(Event::cb_fail(0, (0, 0, 3).into()))
+            .is_some());
+
+        // batched insights settle all the events they track
+        let mut batch = EventId::from((0, 0, 4));
+        batch.track_id(0, 0, 5);
+        assert!(retries.track(pending(4), 0).is_none());
+        assert!(retries.track(pending(5), 0).is_none());
+        assert!(retries.on_insight(Event::cb_ack(0, batch)).is_some());
+        assert!(retries.pending.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn expire() {
+        let (tx, _rx) = unbounded();
+        let mut retries = Retries::new(config(2), tx);
+        assert!(retries.track(pending(1), 0).is_none());
+        assert!(retries.track(pending(2), 500_000).is_none());
+        assert!(retries.expire(999_999).is_none());
+
+        // events the sink didn't settle in time are failed
+        let fail = retries.expire(1_000_000);
+        assert_eq!(
+            Some(CbAction::Fail),
+            fail.as_ref().map(|insight| insight.cb)
+        );
+        assert!(fail.map_or(false, |insight| insight.id.is_tracking(&(0, 0, 1).into())));
+        assert_eq!(1, retries.pending.len());
+
+        // settled events don't expire
+        assert!(retries
+            .on_insight(Event::cb_ack(0, (0, 0, 2).into()))
+            .is_some());
+        assert!(retries.expire(u64::MAX).is_none());
+
+        // the oldest events exceeding `max_pending` are failed
+        assert!(retries.track(pending(3), 0).is_none());
+        assert!(retries.track(pending(4), 0).is_none());
+        let fail = retries.track(pending(5), 0);
+        assert!(fail.map_or(false, |insight| insight.id == EventId::from((0, 0, 3))));
+        assert_eq!(2, retries.pending.len());
+    }
+}
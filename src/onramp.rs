@@ -11,16 +11,20 @@
 // WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
 // See the License for the specific language governing permissions and
 // limitations under the License.
+use crate::codec;
+use crate::dlq::DeadLetter;
 use crate::errors::Result;
 use crate::metrics::RampReporter;
 use crate::pipeline;
 use crate::repository::ServantId;
 use crate::source::prelude::*;
 use crate::source::{
-    amqp, blaster, cb, crononome, discord, file, gsub, kafka, metronome, nats, otel, postgres,
-    rest, sse, stdin, tcp, udp, ws,
+    amqp, blaster, cb, crononome, dir, discord, file, gsub, kafka, metronome, mqtt, nats, otel,
+    postgres, redis, rest, sse, stdin, tail, tcp, udp, ws,
 };
+use crate::tap::Tap;
 use crate::url::TremorUrl;
+use crate::{postprocessor, preprocessor};
 use async_std::task::{self, JoinHandle};
 use serde_yaml::Value;
 use std::fmt;
@@ -43,14 +47,17 @@ pub enum Msg {
     Cb(CbAction, EventId),
     // TODO pick good naming here: LinkedEvent / Response / Result?
     Response(tremor_pipeline::Event),
+    Tap(Tap),
+    /// Replays a dead letter of this onramp
+    Replay(DeadLetter, async_channel::Sender<Result<()>>),
 }
 
 pub type Addr = async_channel::Sender<Msg>;
 
 pub(crate) struct OnrampConfig<'cfg> {
     pub onramp_uid: u64,
-    pub codec: &'cfg str,
-    pub codec_map: halfbrown::HashMap<String, String>,
+    pub codec: &'cfg codec::Config,
+    pub codec_map: halfbrown::HashMap<String, codec::Config>,
     pub processors: Processors<'cfg>,
     pub metrics_reporter: RampReporter,
     pub is_linked: bool,
@@ -81,12 +88,16 @@ pub(crate) fn lookup(
         "stdin" => stdin::Stdin::from_config(id, config),
         "udp" => udp::Udp::from_config(id, config),
         "tcp" => tcp::Tcp::from_config(id, config),
+        "tail" => tail::Tail::from_config(id, config),
         "rest" => rest::Rest::from_config(id, config),
         "sse" => sse::Sse::from_config(id, config),
         "ws" => ws::Ws::from_config(id, config),
+        "dir" => dir::Dir::from_config(id, config),
         "discord" => discord::Discord::from_config(id, config),
         "otel" => otel::OpenTelemetry::from_config(id, config),
         "nats" => nats::Nats::from_config(id, config),
+        "mqtt" => mqtt::Mqtt::from_config(id, config),
+        "redis" => redis::Redis::from_config(id, config),
         "gsub" => gsub::GoogleCloudPubSub::from_config(id, config),
         _ => Err(format!("[onramp:{}] Onramp type {} not known", id, name).into()),
     }
@@ -95,10 +106,10 @@ pub(crate) fn lookup(
 pub(crate) struct Create {
     pub id: ServantId,
     pub stream: Box<dyn Onramp>,
-    pub codec: String,
-    pub codec_map: halfbrown::HashMap<String, String>,
-    pub preprocessors: Vec<String>,
-    pub postprocessors: Vec<String>,
+    pub codec: codec::Config,
+    pub codec_map: halfbrown::HashMap<String, codec::Config>,
+    pub preprocessors: Vec<preprocessor::Config>,
+    pub postprocessors: Vec<postprocessor::Config>,
     pub metrics_reporter: RampReporter,
     pub is_linked: bool,
     pub err_required: bool,
@@ -11,24 +11,35 @@
 // WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
 // See the License for the specific language governing permissions and
 // limitations under the License.
-use crate::errors::{Error, Result};
+use crate::errors::{Error, ErrorKind, Result};
+use crate::metrics::prometheus::{self, Histogram, Labels};
 use crate::permge::{PriorityMerge, M};
 use crate::registry::ServantId;
 use crate::repository::PipelineArtefact;
+use crate::tap::{self, Tap};
 use crate::url::TremorUrl;
 use crate::{offramp, onramp};
 use async_channel::{bounded, unbounded};
 use async_std::stream::StreamExt;
 use async_std::task::{self, JoinHandle};
 use beef::Cow;
+use std::collections::VecDeque;
 use std::fmt;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
 use std::time::Duration;
 use tremor_common::ids::OperatorIdGen;
 use tremor_common::time::nanotime;
 use tremor_pipeline::errors::ErrorKind as PipelineErrorKind;
-use tremor_pipeline::{CbAction, Event, ExecutableGraph, SignalKind};
+use tremor_pipeline::{CbAction, Event, EventId, ExecutableGraph, Sampler, SignalKind};
+use tremor_script::Value;
+
+mod checkpoint;
+use checkpoint::Checkpoints;
 
 const TICK_MS: u64 = 100;
+/// maximum number of events a paused pipeline holds back, any further events are failed
+const MAX_HELD: usize = 1_000;
 pub(crate) type Sender = async_channel::Sender<ManagerMsg>;
 type Inputs = halfbrown::HashMap<TremorUrl, (bool, Input)>;
 type Dests = halfbrown::HashMap<Cow<'static, str>, Vec<(TremorUrl, Dest)>>;
@@ -82,6 +93,62 @@ impl Addr {
     pub(crate) async fn send_mgmt(&self, msg: MgmtMsg) -> Result<()> {
         Ok(self.mgmt_addr.send(msg).await?)
     }
+
+    /// Attaches a tap to the pipeline
+    pub(crate) async fn tap(&self, tap: Tap) -> Result<()> {
+        let (tx, rx) = async_channel::bounded(1);
+        self.send_mgmt(MgmtMsg::Tap(tap, tx)).await?;
+        rx.recv().await?
+    }
+
+    /// Pauses, resumes or steps the pipeline
+    pub(crate) async fn control(&self, control: Control) -> Result<ControlReply> {
+        let (tx, rx) = async_channel::bounded(1);
+        self.send_mgmt(MgmtMsg::Control(control, tx)).await?;
+        rx.recv().await?
+    }
+}
+
+/// Control requests for a running pipeline
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Control {
+    /// stop processing events and close the circuit breakers of all inputs
+    Pause,
+    /// process all held back events and open the circuit breakers again
+    Resume,
+    /// process the next held back event of a paused pipeline
+    Step,
+}
+
+/// An event emitted by a pipeline while stepping through it
+#[derive(Debug, Serialize)]
+pub struct StepOutput {
+    /// output port of the pipeline
+    pub port: String,
+    /// event payload
+    pub data: Value<'static>,
+    /// event metadata
+    pub meta: Value<'static>,
+}
+
+/// State of a pipeline after a control request
+#[derive(Debug, Serialize)]
+pub struct ControlReply {
+    /// if the pipeline is paused
+    pub paused: bool,
+    /// number of events held back while the pipeline is paused
+    pub pending: usize,
+    /// if an event was stepped through the pipeline
+    pub stepped: bool,
+    /// events emitted by the stepped event
+    pub outputs: Vec<StepOutput>,
+}
+
+/// Result of reloading a pipeline
+#[derive(Debug, Serialize)]
+pub struct ReloadReply {
+    /// nodes whose operators were carried over with all their internal state
+    pub migrated: Vec<String>,
 }
 
 #[cfg(not(tarpaulin_include))]
@@ -104,9 +171,10 @@ pub(crate) enum ConnectTarget {
 
 #[derive(Debug)]
 pub(crate) enum MgmtMsg {
-    /// input can only ever be connected to the `in` port, so no need to include it here
     ConnectInput {
         input_url: TremorUrl,
+        /// the input port of the pipeline it is connected to
+        port: Cow<'static, str>,
         target: ConnectTarget,
         /// should we send insights to this input
         transactional: bool,
@@ -118,6 +186,14 @@ pub(crate) enum MgmtMsg {
     },
     DisconnectOutput(Cow<'static, str>, TremorUrl),
     DisconnectInput(TremorUrl),
+    Control(Control, async_channel::Sender<Result<ControlReply>>),
+    /// attaches a tap to an output port of the pipeline or one of its operators
+    Tap(Tap, async_channel::Sender<Result<()>>),
+    /// replaces the graph of the pipeline, taking over the state of the current one
+    Reload(
+        Box<ExecutableGraph>,
+        async_channel::Sender<Result<ReloadReply>>,
+    ),
     // only for testing
     Echo(async_channel::Sender<()>),
 }
@@ -202,9 +278,15 @@ pub struct Create {
     pub id: ServantId,
 }
 
+pub struct Reload {
+    pub config: PipelineArtefact,
+    pub addr: Addr,
+}
+
 pub(crate) enum ManagerMsg {
     Stop,
     Create(async_channel::Sender<Result<Addr>>, Box<Create>),
+    Reload(async_channel::Sender<Result<ReloadReply>>, Box<Reload>),
 }
 
 #[derive(Default, Debug)]
@@ -230,6 +312,34 @@ async fn send_events(eventset: &mut Eventset, dests: &mut Dests) -> Result<()> {
     Ok(())
 }
 
+/// Offers the events emitted on tapped operator ports and on the outputs of the pipeline to the taps
+fn offer_taps(pipeline: &mut ExecutableGraph, taps: &mut Vec<Tap>, eventset: &Eventset) {
+    let mut detached = false;
+    if !pipeline.tapped.is_empty() {
+        // the graph already sampled these events for the taps on operator nodes
+        let on_nodes: Vec<&Tap> = taps.iter().filter(|t| t.node().is_some()).collect();
+        for (tap, event) in pipeline.tapped.drain(..) {
+            if let Some(tap) = on_nodes.get(tap) {
+                detached |= tap.is_detached();
+                tap.send(&event);
+            }
+        }
+    }
+    for (port, event) in eventset {
+        detached |= tap::offer(taps, None, port, event);
+    }
+    if detached && tap::detach(taps) {
+        pipeline.taps = node_taps(taps);
+    }
+}
+
+/// Taps on operator nodes, in the order of `taps`, the graph samples events for
+fn node_taps(taps: &[Tap]) -> Vec<(String, String, Sampler)> {
+    taps.iter()
+        .filter_map(|t| Some((t.node()?.to_string(), t.port().to_string(), t.sampler())))
+        .collect()
+}
+
 #[inline]
 async fn send_signal(own_id: &TremorUrl, signal: Event, dests: &mut Dests) -> Result<()> {
     let mut offramps = dests.values_mut().flatten();
@@ -247,14 +357,48 @@ async fn send_signal(own_id: &TremorUrl, signal: Event, dests: &mut Dests) -> Re
     Ok(())
 }
 
+/// forward the signals emitted by operators of the pipeline to whatever is connected
+/// to the output they reached
+#[inline]
+async fn send_signals(
+    own_id: &TremorUrl,
+    pipeline: &mut ExecutableGraph,
+    dests: &mut Dests,
+) -> Result<()> {
+    for (output, signal) in pipeline.signals.drain(..) {
+        if let Some(dest) = dests.get_mut(&output) {
+            for (id, offramp) in dest.iter_mut() {
+                if id != own_id {
+                    offramp.send_signal(signal.clone()).await?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
 #[inline]
 async fn handle_insight(
     skip_to: Option<usize>,
     insight: Event,
     pipeline: &mut ExecutableGraph,
     inputs: &Inputs,
+    checkpoints: &mut Option<Checkpoints>,
 ) {
     let insight = pipeline.contraflow(skip_to, insight);
+    // acks are held back until the acked events are part of a checkpoint
+    let insight = match checkpoints {
+        Some(c) => c.hold(insight),
+        None => Some(insight),
+    };
+    if let Some(insight) = insight {
+        send_insight(&pipeline.id, insight, inputs).await;
+    }
+}
+
+/// Sends an insight to all inputs of the pipeline
+#[inline]
+async fn send_insight(pipeline_id: &str, insight: Event, inputs: &Inputs) {
     if insight.cb != CbAction::None {
         let mut input_iter = inputs.iter();
         let first = input_iter.next();
@@ -281,7 +425,7 @@ async fn handle_insight(
                 } {
                     error!(
                         "[Pipeline::{}] failed to send insight to input: {} {}",
-                        &pipeline.id, e, url
+                        pipeline_id, e, url
                     );
                 }
             }
@@ -308,7 +452,7 @@ async fn handle_insight(
                 } {
                     error!(
                         "[Pipeline::{}] failed to send insight to input: {} {}",
-                        &pipeline.id, e, &url
+                        pipeline_id, e, &url
                     );
                 }
             }
@@ -317,13 +461,70 @@ async fn handle_insight(
 }
 
 #[inline]
-async fn handle_insights(pipeline: &mut ExecutableGraph, onramps: &Inputs) {
+async fn handle_insights(
+    pipeline: &mut ExecutableGraph,
+    onramps: &Inputs,
+    checkpoints: &mut Option<Checkpoints>,
+) {
     if !pipeline.insights.is_empty() {
         let mut insights = Vec::with_capacity(pipeline.insights.len());
         std::mem::swap(&mut insights, &mut pipeline.insights);
         for (skip_to, insight) in insights.drain(..) {
-            handle_insight(Some(skip_to), insight, pipeline, onramps).await
+            handle_insight(Some(skip_to), insight, pipeline, onramps, checkpoints).await
+        }
+    }
+}
+
+/// Checkpoints the pipeline once a checkpoint is due at `ns` and releases the
+/// acks held back until then. A failing checkpoint disables checkpointing,
+/// so acks aren't held back forever.
+async fn checkpoint(
+    pid: &TremorUrl,
+    pipeline: &ExecutableGraph,
+    inputs: &Inputs,
+    checkpoints: &mut Option<Checkpoints>,
+    ns: u64,
+) {
+    let res = match checkpoints {
+        Some(c) if c.is_due(ns) => c.save(pipeline, ns).await,
+        _ => return,
+    };
+    let acks = match res {
+        Ok(acks) => acks,
+        Err(e) => {
+            error!(
+                "[Pipeline::{}] Checkpointing failed, disabling it: {}",
+                pid, e
+            );
+            checkpoints
+                .take()
+                .map(Checkpoints::into_acks)
+                .unwrap_or_default()
+        }
+    };
+    for ack in acks {
+        send_insight(&pipeline.id, ack, inputs).await;
+    }
+}
+
+/// Opens the checkpoints of the pipeline instance `pid`, if the pipeline is
+/// checkpointed, and restores the last checkpoint into it
+fn restore_checkpoint(
+    pid: &TremorUrl,
+    pipeline: &mut ExecutableGraph,
+) -> Result<Option<Checkpoints>> {
+    if let Some(settings) = &pipeline.checkpoint {
+        let checkpoints = Checkpoints::open(pid, settings)?;
+        if let Some(state) = checkpoints.load()? {
+            let restored = pipeline.restore(&state)?;
+            info!(
+                "[Pipeline::{}] Restored checkpoint, restored operators: {:?}",
+                pid, restored
+            );
         }
+        Ok(Some(checkpoints))
+    } else {
+        Ok(None)
     }
 }
 
@@ -340,9 +541,16 @@ async fn tick(tick_tx: async_channel::Sender<Msg>) {
     }
 }
 
-async fn handle_cf_msg(msg: CfMsg, pipeline: &mut ExecutableGraph, inputs: &Inputs) -> Result<()> {
+async fn handle_cf_msg(
+    msg: CfMsg,
+    pipeline: &mut ExecutableGraph,
+    inputs: &Inputs,
+    checkpoints: &mut Option<Checkpoints>,
+) -> Result<()> {
     match msg {
-        CfMsg::Insight(insight) => handle_insight(None, insight, pipeline, inputs).await,
+        CfMsg::Insight(insight) => {
+            handle_insight(None, insight, pipeline, inputs, checkpoints).await
+        }
     }
     Ok(())
 }
@@ -354,6 +562,189 @@ fn maybe_send(r: Result<()>) {
     }
 }
 
+/// Exports the per port event counts of all nodes of the pipeline to the prometheus registry
+fn export_port_metrics(pipeline: &ExecutableGraph, labels: &[(&'static str, String)]) {
+    pipeline.visit_port_metrics(|node, direction, port, count| {
+        let mut labels: Labels = labels.to_vec();
+        labels.push(("node", node.to_string()));
+        labels.push(("direction", direction.to_string()));
+        labels.push(("port", port.to_string()));
+        prometheus::REGISTRY
+            .counter(
+                "tremor_pipeline_events_total",
+                "Number of events per pipeline node port",
+                labels,
+            )
+            .store(count, Ordering::Relaxed);
+    });
+}
+
+/// Latency histograms of a pipeline and its operators
+struct Latencies {
+    labels: Labels,
+    pipeline: Arc<Histogram>,
+    operators: halfbrown::HashMap<String, Arc<Histogram>>,
+}
+
+impl Latencies {
+    fn new(labels: Labels) -> Self {
+        let pipeline = prometheus::REGISTRY.histogram(
+            "tremor_pipeline_latency_seconds",
+            "Time spent processing an event in a pipeline",
+            labels.clone(),
+        );
+        Self {
+            labels,
+            pipeline,
+            operators: halfbrown::HashMap::new(),
+        }
+    }
+
+    /// records the time the pipeline spent on an event and the time each of its operators spent on it
+    fn observe(&mut self, pipeline: &mut ExecutableGraph, ns: u64) {
+        self.pipeline.observe_ns(ns);
+        let Self {
+            labels, operators, ..
+        } = self;
+        pipeline.drain_latencies(|node, ns| {
+            if let Some(histogram) = operators.get(node) {
+                histogram.observe_ns(ns);
+            } else {
+                let mut labels = labels.clone();
+                labels.push(("node", node.to_string()));
+                let histogram = prometheus::REGISTRY.histogram(
+                    "tremor_operator_latency_seconds",
+                    "Time spent processing an event in a pipeline operator",
+                    labels,
+                );
+                histogram.observe_ns(ns);
+                operators.insert(node.to_string(), histogram);
+            }
+        });
+    }
+}
+
+/// State of a paused pipeline
+#[derive(Default)]
+struct Paused {
+    /// events and signals received while paused, in order of arrival
+    held: VecDeque<Msg>,
+}
+
+impl Paused {
+    fn pending(&self) -> usize {
+        self.held
+            .iter()
+            .filter(|msg| matches!(msg, Msg::Event { .. }))
+            .count()
+    }
+}
+
+/// Sends a circuit breaker action to all inputs of the pipeline
+async fn send_cb(pid: &TremorUrl, cb: CbAction, inputs: &Inputs) {
+    for (url, (_, input)) in inputs {
+        if let Err(e) = match input {
+            Input::Onramp(addr) => addr
+                .send(onramp::Msg::Cb(cb, EventId::default()))
+                .await
+                .map_err(Error::from),
+            Input::Pipeline(addr) => {
+                addr.send_insight(Event {
+                    ingest_ns: nanotime(),
+                    cb,
+                    ..Event::default()
+                })
+                .await
+            }
+            // we never send contraflow to linked offramps, see `handle_insight`
+            Input::LinkedOfframp(_addr) => Ok(()),
+        } {
+            error!(
+                "[Pipeline::{}] failed to send {:?} to input: {} {}",
+                pid, cb, e, url
+            );
+        }
+    }
+}
+
+/// Formats an error of the pipeline, locating script errors in the pipeline's source
+fn format_error(pipeline: &ExecutableGraph, e: tremor_pipeline::errors::Error) -> String {
+    if let PipelineErrorKind::Script(script_kind) = e.0 {
+        let script_error = tremor_script::errors::Error(script_kind, e.1);
+        // possibly a hygienic error
+        pipeline
+            .source
+            .as_ref()
+            .and_then(|s| script_error.locate_in_source(s))
+            .map_or_else(
+                || format!(" {:?}", script_error),
+                |located| format!("\n{}", located),
+            ) // add a newline to have the error nicely formatted in the log
+    } else {
+        format!(" {}", e)
+    }
+}
+
+/// Enqueues an event into the pipeline, logging errors, returns `true` on success
+fn process_event(
+    pipeline: &mut ExecutableGraph,
+    input: &str,
+    event: Event,
+    eventset: &mut Eventset,
+    latencies: &mut Latencies,
+) -> bool {
+    let start = nanotime();
+    let res = pipeline.enqueue(input, event, eventset);
+    latencies.observe(pipeline, nanotime().saturating_sub(start));
+    if let Err(e) = res {
+        error!("Error handling event:{}", format_error(pipeline, e));
+        false
+    } else {
+        true
+    }
+}
+
+/// Enqueues a signal into the pipeline, logging errors, returns `true` on success
+fn process_signal(
+    pid: &TremorUrl,
+    pipeline: &mut ExecutableGraph,
+    signal: Event,
+    eventset: &mut Eventset,
+) -> bool {
+    if let Err(e) = pipeline.enqueue_signal(signal, eventset) {
+        error!(
+            "[Pipeline::{}] Error handling signal:{}",
+            pid,
+            format_error(pipeline, e)
+        );
+        false
+    } else {
+        true
+    }
+}
+
+/// Runs a control signal through the pipeline, so operators can react to it
+fn enqueue_control_signal(
+    pid: &TremorUrl,
+    kind: SignalKind,
+    pipeline: &mut ExecutableGraph,
+    eventset: &mut Eventset,
+) {
+    let signal = Event {
+        ingest_ns: nanotime(),
+        kind: Some(kind),
+        ..Event::default()
+    };
+    if let Err(e) = pipeline.enqueue_signal(signal, eventset) {
+        error!(
+            "[Pipeline::{}] Error handling {:?} signal:{}",
+            pid,
+            kind,
+            format_error(pipeline, e)
+        );
+    }
+}
+
 #[allow(dead_code)]
 async fn echo(addr: &Addr) -> Result<()> {
     let (tx, rx) = async_channel::bounded(1);
@@ -370,6 +761,7 @@ async fn pipeline_task(
     rx: async_channel::Receiver<Msg>,
     cf_rx: async_channel::Receiver<CfMsg>,
     mgmt_rx: async_channel::Receiver<MgmtMsg>,
+    mut checkpoints: Option<Checkpoints>,
 ) -> Result<()> {
     let mut pid = id.clone();
     pid.trim_to_instance();
@@ -377,8 +769,21 @@ async fn pipeline_task(
 
     let mut dests: Dests = halfbrown::HashMap::new();
     let mut inputs: Inputs = halfbrown::HashMap::new();
+    // the input ports of the pipeline each input is connected to
+    let mut input_ports: halfbrown::HashMap<TremorUrl, Vec<Cow<'static, str>>> =
+        halfbrown::HashMap::new();
     let mut eventset: Eventset = Vec::new();
 
+    let labels = prometheus::url_labels(&pid);
+    let mut latencies = Latencies::new(labels.clone());
+    pipeline.track_latencies = true;
+
+    // `Some` while the pipeline is paused
+    let mut paused: Option<Paused> = None;
+    // the circuit breaker was closed from downstream
+    let mut downstream_closed = false;
+    let mut taps: Vec<Tap> = Vec::new();
+
     info!("[Pipeline:{}] starting task.", id);
 
     let ff = rx.map(M::F);
@@ -390,62 +795,68 @@ async fn pipeline_task(
     while let Some(msg) = s.next().await {
         match msg {
             M::C(msg) => {
-                handle_cf_msg(msg, &mut pipeline, &inputs).await?;
+                if let CfMsg::Insight(insight) = &msg {
+                    if insight.cb.is_cb() {
+                        downstream_closed = insight.cb == CbAction::Close;
+                        // while paused we keep the circuit breakers of our inputs closed
+                        // and only remember the state downstream wants them to be in
+                        if paused.is_some() {
+                            continue;
+                        }
+                    }
+                }
+                handle_cf_msg(msg, &mut pipeline, &inputs, &mut checkpoints).await?;
             }
             M::F(Msg::Event { input, event }) => {
-                match pipeline.enqueue(&input, event, &mut eventset) {
-                    Ok(()) => {
-                        handle_insights(&mut pipeline, &inputs).await;
-                        maybe_send(send_events(&mut eventset, &mut dests).await);
-                    }
-                    Err(e) => {
-                        let err_str = if let PipelineErrorKind::Script(script_kind) = e.0 {
-                            let script_error = tremor_script::errors::Error(script_kind, e.1);
-                            // possibly a hygienic error
-                            pipeline
-                                .source
-                                .as_ref()
-                                .and_then(|s| script_error.locate_in_source(s))
-                                .map_or_else(
-                                    || format!(" {:?}", script_error),
-                                    |located| format!("\n{}", located),
-                                ) // add a newline to have the error nicely formatted in the log
-                        } else {
-                            format!(" {}", e)
-                        };
-                        error!("Error handling event:{}", err_str);
+                if let Some(p) = &mut paused {
+                    // inputs might not honour the closed circuit breaker,
+                    // so we fail what we can't hold instead of growing without bounds
+                    if p.held.len() < MAX_HELD {
+                        p.held.push_back(Msg::Event { input, event });
+                    } else {
+                        send_insight(&pipeline.id, event.to_fail(), &inputs).await;
                     }
+                } else if process_event(&mut pipeline, &input, event, &mut eventset, &mut latencies)
+                {
+                    handle_insights(&mut pipeline, &inputs, &mut checkpoints).await;
+                    offer_taps(&mut pipeline, &mut taps, &eventset);
+                    maybe_send(send_events(&mut eventset, &mut dests).await);
+                    maybe_send(send_signals(&id, &mut pipeline, &mut dests).await);
                 }
             }
             M::F(Msg::Signal(signal)) => {
-                if let Err(e) = pipeline.enqueue_signal(signal.clone(), &mut eventset) {
-                    let err_str = if let PipelineErrorKind::Script(script_kind) = e.0 {
-                        let script_error = tremor_script::errors::Error(script_kind, e.1);
-                        // possibly a hygienic error
-                        pipeline
-                            .source
-                            .as_ref()
-                            .and_then(|s| script_error.locate_in_source(s))
-                            .map_or_else(
-                                || format!(" {:?}", script_error),
-                                |located| format!("\n{}", located),
-                            ) // add a newline to have the error nicely formatted in the log
-                    } else {
-                        format!(" {:?}", e)
-                    };
-                    error!("[Pipeline::{}] Error handling signal:{}", pid, err_str);
-                } else {
+                if signal.kind == Some(SignalKind::Tick) {
+                    export_port_metrics(&pipeline, &labels);
+                    if tap::detach(&mut taps) {
+                        pipeline.taps = node_taps(&taps);
+                    }
+                    checkpoint(&pid, &pipeline, &inputs, &mut checkpoints, signal.ingest_ns).await;
+                }
+                if let Some(p) = &mut paused {
+                    // operators don't see time pass while paused, ticks are dropped
+                    // and all other signals are delivered in order once resumed
+                    if signal.kind != Some(SignalKind::Tick) {
+                        p.held.push_back(Msg::Signal(signal));
+                    }
+                } else if process_signal(&pid, &mut pipeline, signal.clone(), &mut eventset) {
                     maybe_send(send_signal(&id, signal, &mut dests).await);
-                    handle_insights(&mut pipeline, &inputs).await;
+                    handle_insights(&mut pipeline, &inputs, &mut checkpoints).await;
+                    offer_taps(&mut pipeline, &mut taps, &eventset);
                     maybe_send(send_events(&mut eventset, &mut dests).await);
+                    maybe_send(send_signals(&id, &mut pipeline, &mut dests).await);
                 }
             }
             M::M(MgmtMsg::ConnectInput {
                 input_url,
+                port,
                 target,
                 transactional,
             }) => {
-                info!("[Pipeline::{}] Connecting {} to 'in'", pid, input_url);
+                info!("[Pipeline::{}] Connecting {} to '{}'", pid, input_url, port);
+                let ports = input_ports.entry(input_url.clone()).or_default();
+                if !ports.contains(&port) {
+                    ports.push(port);
+                }
                 inputs.insert(input_url, (transactional, target.into()));
             }
             M::M(MgmtMsg::ConnectOutput {
@@ -470,6 +881,11 @@ async fn pipeline_task(
                         if let Err(e) = pipe
                             .send_mgmt(MgmtMsg::ConnectInput {
                                 input_url: pid.clone(),
+                                port: output_url
+                                    .instance_port()
+                                    .unwrap_or("in")
+                                    .to_string()
+                                    .into(),
                                 target: ConnectTarget::Pipeline(Box::new(addr.clone())),
                                 transactional: true,
                             })
@@ -517,8 +933,236 @@ async fn pipeline_task(
             }
             M::M(MgmtMsg::DisconnectInput(input_url)) => {
                 info!("[Pipeline::{}] Disconnecting {} from 'in'", pid, &input_url);
+                input_ports.remove(&input_url);
                 inputs.remove(&input_url);
             }
+            M::M(MgmtMsg::Control(control, sender)) => {
+                let mut stepped = false;
+                let mut outputs = Vec::new();
+                let reply = match (control, paused.is_some()) {
+                    (Control::Pause, false) => {
+                        info!("[Pipeline::{}] Pausing", pid);
+                        paused = Some(Paused::default());
+                        enqueue_control_signal(
+                            &pid,
+                            SignalKind::Pause,
+                            &mut pipeline,
+                            &mut eventset,
+                        );
+                        handle_insights(&mut pipeline, &inputs, &mut checkpoints).await;
+                        offer_taps(&mut pipeline, &mut taps, &eventset);
+                        maybe_send(send_events(&mut eventset, &mut dests).await);
+                        maybe_send(send_signals(&id, &mut pipeline, &mut dests).await);
+                        send_cb(&pid, CbAction::Close, &inputs).await;
+                        Ok(())
+                    }
+                    (Control::Resume, true) => {
+                        info!("[Pipeline::{}] Resuming", pid);
+                        let Paused { held } = paused.take().unwrap_or_default();
+                        enqueue_control_signal(
+                            &pid,
+                            SignalKind::Resume,
+                            &mut pipeline,
+                            &mut eventset,
+                        );
+                        handle_insights(&mut pipeline, &inputs, &mut checkpoints).await;
+                        offer_taps(&mut pipeline, &mut taps, &eventset);
+                        maybe_send(send_events(&mut eventset, &mut dests).await);
+                        maybe_send(send_signals(&id, &mut pipeline, &mut dests).await);
+                        for msg in held {
+                            let processed = match msg {
+                                Msg::Event { input, event } => process_event(
+                                    &mut pipeline,
+                                    &input,
+                                    event,
+                                    &mut eventset,
+                                    &mut latencies,
+                                ),
+                                Msg::Signal(signal) => {
+                                    let processed = process_signal(
+                                        &pid,
+                                        &mut pipeline,
+                                        signal.clone(),
+                                        &mut eventset,
+                                    );
+                                    if processed {
+                                        maybe_send(send_signal(&id, signal, &mut dests).await);
+                                    }
+                                    processed
+                                }
+                            };
+                            if processed {
+                                handle_insights(&mut pipeline, &inputs, &mut checkpoints).await;
+                                offer_taps(&mut pipeline, &mut taps, &eventset);
+                                maybe_send(send_events(&mut eventset, &mut dests).await);
+                                maybe_send(send_signals(&id, &mut pipeline, &mut dests).await);
+                            }
+                        }
+                        if !downstream_closed {
+                            send_cb(&pid, CbAction::Open, &inputs).await;
+                        }
+                        Ok(())
+                    }
+                    (Control::Step, true) => {
+                        // signals held back in front of the next event are delivered first
+                        let mut next = None;
+                        while let Some(msg) = paused.as_mut().and_then(|p| p.held.pop_front()) {
+                            match msg {
+                                Msg::Event { input, event } => {
+                                    next = Some((input, event));
+                                    break;
+                                }
+                                Msg::Signal(signal) => {
+                                    if process_signal(
+                                        &pid,
+                                        &mut pipeline,
+                                        signal.clone(),
+                                        &mut eventset,
+                                    ) {
+                                        maybe_send(send_signal(&id, signal, &mut dests).await);
+                                        handle_insights(&mut pipeline, &inputs, &mut checkpoints)
+                                            .await;
+                                        offer_taps(&mut pipeline, &mut taps, &eventset);
+                                        maybe_send(send_events(&mut eventset, &mut dests).await);
+                                        maybe_send(
+                                            send_signals(&id, &mut pipeline, &mut dests).await,
+                                        );
+                                    }
+                                }
+                            }
+                        }
+                        if let Some((input, event)) = next {
+                            stepped = true;
+                            enqueue_control_signal(
+                                &pid,
+                                SignalKind::Step,
+                                &mut pipeline,
+                                &mut eventset,
+                            );
+                            // the step signal's output isn't part of the step's outputs
+                            handle_insights(&mut pipeline, &inputs, &mut checkpoints).await;
+                            offer_taps(&mut pipeline, &mut taps, &eventset);
+                            maybe_send(send_events(&mut eventset, &mut dests).await);
+                            maybe_send(send_signals(&id, &mut pipeline, &mut dests).await);
+                            if process_event(
+                                &mut pipeline,
+                                &input,
+                                event,
+                                &mut eventset,
+                                &mut latencies,
+                            ) {
+                                outputs = eventset
+                                    .iter()
+                                    .map(|(port, event)| {
+                                        let (data, meta) = event.data.parts();
+                                        StepOutput {
+                                            port: port.to_string(),
+                                            data: data.clone_static(),
+                                            meta: meta.clone_static(),
+                                        }
+                                    })
+                                    .collect();
+                                handle_insights(&mut pipeline, &inputs, &mut checkpoints).await;
+                                offer_taps(&mut pipeline, &mut taps, &eventset);
+                                maybe_send(send_events(&mut eventset, &mut dests).await);
+                                maybe_send(send_signals(&id, &mut pipeline, &mut dests).await);
+                            }
+                        }
+                        Ok(())
+                    }
+                    (Control::Step, false) => {
+                        Err(ErrorKind::PipelineNotPaused(pid.to_string()).into())
+                    }
+                    // pausing a paused or resuming a running pipeline is a noop
+                    (Control::Pause, true) | (Control::Resume, false) => Ok(()),
+                };
+                let reply = reply.map(|()| ControlReply {
+                    paused: paused.is_some(),
+                    pending: paused.as_ref().map_or(0, Paused::pending),
+                    stepped,
+                    outputs,
+                });
+                if let Err(e) = sender.send(reply).await {
+                    error!(
+                        "[Pipeline::{}] Error responding to control message: {}",
+                        pid, e
+                    );
+                }
+            }
+            M::M(MgmtMsg::Tap(tap, sender)) => {
+                let res = match tap.node() {
+                    Some(node) if !pipeline.has_node(node) => Err(ErrorKind::InvalidTap(
+                        tap.url().to_string(),
+                        format!("unknown operator node {}", node),
+                    )
+                    .into()),
+                    _ => {
+                        info!("[Pipeline::{}] Attaching tap to {}", pid, tap.url());
+                        taps.push(tap);
+                        pipeline.taps = node_taps(&taps);
+                        Ok(())
+                    }
+                };
+                if let Err(e) = sender.send(res).await {
+                    error!("[Pipeline::{}] Error responding to tap message: {}", pid, e);
+                }
+            }
+            M::M(MgmtMsg::Reload(mut graph, sender)) => {
+                // every connected output and input needs to exist in the new graph,
+                // otherwise we keep running the current one
+                let missing_input = input_ports
+                    .iter()
+                    .flat_map(|(url, ports)| ports.iter().map(move |port| (url, port)))
+                    .find(|(_, port)| !graph.has_input(port));
+                let res = if let Some(port) = dests.keys().find(|p| !graph.has_output(p)) {
+                    Err(ErrorKind::PipelineReloadFailed(
+                        pid.to_string(),
+                        format!("the connected output {} is missing", port),
+                    )
+                    .into())
+                } else if let Some((url, port)) = missing_input {
+                    Err(ErrorKind::PipelineReloadFailed(
+                        pid.to_string(),
+                        format!("the input {} of {} is missing", port, url),
+                    )
+                    .into())
+                } else {
+                    graph.id = pipeline.id.clone();
+                    graph.track_latencies = pipeline.track_latencies;
+                    let migrated = graph.migrate_state(&mut pipeline);
+                    pipeline = *graph;
+                    info!(
+                        "[Pipeline::{}] Reloaded, migrated operators: {:?}",
+                        pid, migrated
+                    );
+                    // pick up changed checkpoint settings, held back acks
+                    // are released by the final checkpoint of the old settings
+                    if checkpoints.as_ref().map(Checkpoints::settings)
+                        != pipeline.checkpoint.as_ref()
+                    {
+                        checkpoint(&pid, &pipeline, &inputs, &mut checkpoints, u64::MAX).await;
+                        checkpoints = match pipeline
+                            .checkpoint
+                            .as_ref()
+                            .map(|settings| Checkpoints::open(&pid, settings))
+                            .transpose()
+                        {
+                            Ok(checkpoints) => checkpoints,
+                            Err(e) => {
+                                error!("[Pipeline::{}] Failed to open checkpoints: {}", pid, e);
+                                None
+                            }
+                        };
+                    }
+                    Ok(ReloadReply { migrated })
+                };
+                if let Err(e) = sender.send(res).await {
+                    error!(
+                        "[Pipeline::{}] Error responding to reload message: {}",
+                        pid, e
+                    );
+                }
+            }
             M::M(MgmtMsg::Echo(sender)) => {
                 if let Err(e) = sender.send(()).await {
                     error!(
@@ -530,6 +1174,9 @@ async fn pipeline_task(
         }
     }
 
+    // a last checkpoint so a restart doesn't need to replay anything
+    checkpoint(&pid, &pipeline, &inputs, &mut checkpoints, u64::MAX).await;
+    prometheus::REGISTRY.remove(&labels);
     info!("[Pipeline:{}] stopping task.", id);
     Ok(())
 }
@@ -554,6 +1201,26 @@ impl Manager {
                     Ok(ManagerMsg::Create(r, create)) => {
                         r.send(self.start_pipeline(*create)).await?
                     }
+                    Ok(ManagerMsg::Reload(r, reload)) => {
+                        // the new graph is compiled here as operator ids are unique
+                        // across all pipelines, a failing compilation leaves the
+                        // running pipeline untouched
+                        match reload.config.to_pipe(&mut self.operator_id_gen) {
+                            Ok(graph) => {
+                                let msg = MgmtMsg::Reload(Box::new(graph), r);
+                                if let Err(e) = reload.addr.send_mgmt(msg).await {
+                                    error!("Failed to reload pipeline {}: {}", reload.addr.id, e);
+                                }
+                            }
+                            Err(e) => {
+                                let e = ErrorKind::PipelineReloadFailed(
+                                    reload.addr.id.to_string(),
+                                    e.to_string(),
+                                );
+                                r.send(Err(e.into())).await?
+                            }
+                        }
+                    }
                     Err(e) => {
                         info!("Stopping Pipeline manager... {}", e);
                         break;
@@ -568,9 +1235,12 @@ impl Manager {
 
     fn start_pipeline(&mut self, req: Create) -> Result<Addr> {
         let config = req.config;
-        let pipeline = config.to_pipe(&mut self.operator_id_gen)?;
+        let mut pipeline = config.to_pipe(&mut self.operator_id_gen)?;
 
         let id = req.id.clone();
+        let mut pid = id.clone();
+        pid.trim_to_instance();
+        let checkpoints = restore_checkpoint(&pid, &mut pipeline)?;
 
         let (tx, rx) = bounded::<Msg>(self.qsize);
         // We use a unbounded channel for counterflow, while an unbounded channel seems dangerous
@@ -604,6 +1274,7 @@ impl Manager {
                 rx,
                 cf_rx,
                 mgmt_rx,
+                checkpoints,
             ))?;
         Ok(addr)
     }
@@ -677,6 +1348,7 @@ mod tests {
         let onramp_url = TremorUrl::parse("/onramp/fake_onramp/instance/out")?;
         addr.send_mgmt(MgmtMsg::ConnectInput {
             input_url: onramp_url.clone(),
+            port: "in".into(),
             target: ConnectTarget::Onramp(onramp_tx.clone()), // clone avoids the channel to be closed on disconnect below
             transactional: true,
         })
@@ -687,6 +1359,7 @@ mod tests {
         let onramp2_url = TremorUrl::parse("/onramp/fake_onramp2/instance/out")?;
         addr.send_mgmt(MgmtMsg::ConnectInput {
             input_url: onramp2_url.clone(),
+            port: "in".into(),
             target: ConnectTarget::Onramp(onramp2_tx.clone()),
             transactional: false,
         })
@@ -882,4 +1555,235 @@ mod tests {
         handle.cancel().await;
         Ok(())
     }
+
+    #[async_std::test]
+    async fn test_pipeline_pause_resume_step() -> Result<()> {
+        let module_path = ModulePath { mounts: vec![] };
+        let query = r#"
+            select event
+            from in
+            into out;
+        "#;
+        let aggr_reg: tremor_script::registry::Aggr = tremor_script::aggr_registry();
+        let q = Query::parse(
+            &module_path,
+            "test_pipeline_pause.trickle",
+            query,
+            vec![],
+            &*FN_REGISTRY.lock()?,
+            &aggr_reg,
+        )?;
+        let config = tremor_pipeline::query::Query(q);
+        let id = TremorUrl::parse("/pipeline/test_pipeline_pause/instance")?;
+        let manager = Manager::new(12);
+        let (handle, sender) = manager.start();
+        let (tx, rx) = async_channel::bounded(1);
+        let create = Create { config, id };
+        sender
+            .send(ManagerMsg::Create(tx, Box::new(create)))
+            .await?;
+        let addr = rx.recv().await??;
+
+        let (onramp_tx, onramp_rx) = async_channel::unbounded();
+        addr.send_mgmt(MgmtMsg::ConnectInput {
+            input_url: TremorUrl::parse("/onramp/fake_onramp/instance/out")?,
+            port: "in".into(),
+            target: ConnectTarget::Onramp(onramp_tx.clone()),
+            transactional: false,
+        })
+        .await?;
+        let (offramp_tx, offramp_rx) = async_channel::unbounded();
+        addr.send_mgmt(MgmtMsg::ConnectOutput {
+            port: OUT,
+            output_url: TremorUrl::parse("/offramp/fake_offramp/instance/in")?,
+            target: ConnectTarget::Offramp(offramp_tx.clone()),
+        })
+        .await?;
+        manager_fence(&addr).await?;
+
+        // stepping requires a paused pipeline
+        assert!(addr.control(Control::Step).await.is_err());
+
+        let reply = addr.control(Control::Pause).await?;
+        assert!(reply.paused);
+        match timeout(POSITIVE_RECV_TIMEOUT, onramp_rx.recv()).await {
+            Ok(Ok(onramp::Msg::Cb(CbAction::Close, _))) => {}
+            other => assert!(false, "Expected a cb close, got: {:?}", other),
+        };
+
+        for i in 0..2_u64 {
+            addr.send(Msg::Event {
+                event: Event {
+                    data: literal!({ "snot": i }).into(),
+                    ..Event::default()
+                },
+                input: "in".into(),
+            })
+            .await?;
+        }
+        // events are held back while paused
+        match timeout(NEGATIVE_RECV_TIMEOUT, offramp_rx.recv()).await {
+            Ok(Ok(m @ offramp::Msg::Event { .. })) => {
+                assert!(false, "Didnt expect to receive something, got: {:?}", m)
+            }
+            Ok(Err(e)) => return Err(e.into()),
+            _ => {}
+        };
+
+        let reply = addr.control(Control::Step).await?;
+        assert!(reply.paused);
+        assert!(reply.stepped);
+        assert_eq!(1, reply.pending);
+        assert_eq!(1, reply.outputs.len());
+        assert_eq!("out", reply.outputs[0].port);
+        assert_eq!(literal!({ "snot": 0 }), reply.outputs[0].data);
+        let event = wait_for_event(&offramp_rx, None).await?;
+        assert_eq!(&literal!({ "snot": 0 }), event.data.suffix().value());
+
+        let reply = addr.control(Control::Resume).await?;
+        assert!(!reply.paused);
+        assert_eq!(0, reply.pending);
+        let event = wait_for_event(&offramp_rx, None).await?;
+        assert_eq!(&literal!({ "snot": 1 }), event.data.suffix().value());
+        match timeout(POSITIVE_RECV_TIMEOUT, onramp_rx.recv()).await {
+            Ok(Ok(onramp::Msg::Cb(CbAction::Open, _))) => {}
+            other => assert!(false, "Expected a cb open, got: {:?}", other),
+        };
+
+        // stopping the manager
+        sender.send(ManagerMsg::Stop).await?;
+        handle.cancel().await;
+        Ok(())
+    }
+    #[async_std::test]
+    async fn test_pipeline_pause_downstream_closed() -> Result<()> {
+        let module_path = ModulePath { mounts: vec![] };
+        let query = r#"
+            select event
+            from in
+            into out;
+        "#;
+        let aggr_reg: tremor_script::registry::Aggr = tremor_script::aggr_registry();
+        let q = Query::parse(
+            &module_path,
+            "test_pipeline_pause_downstream_closed.trickle",
+            query,
+            vec![],
+            &*FN_REGISTRY.lock()?,
+            &aggr_reg,
+        )?;
+        let config = tremor_pipeline::query::Query(q);
+        let id = TremorUrl::parse("/pipeline/test_pipeline_pause_downstream_closed/instance")?;
+        let manager = Manager::new(12);
+        let (handle, sender) = manager.start();
+        let (tx, rx) = async_channel::bounded(1);
+        let create = Create { config, id };
+        sender
+            .send(ManagerMsg::Create(tx, Box::new(create)))
+            .await?;
+        let addr = rx.recv().await??;
+
+        let (onramp_tx, onramp_rx) = async_channel::unbounded();
+        addr.send_mgmt(MgmtMsg::ConnectInput {
+            input_url: TremorUrl::parse("/onramp/fake_onramp/instance/out")?,
+            port: "in".into(),
+            target: ConnectTarget::Onramp(onramp_tx.clone()),
+            transactional: false,
+        })
+        .await?;
+        manager_fence(&addr).await?;
+
+        // downstream closes the circuit breaker before the pipeline is paused
+        addr.send_insight(Event::cb_trigger(nanotime())).await?;
+        match timeout(POSITIVE_RECV_TIMEOUT, onramp_rx.recv()).await {
+            Ok(Ok(onramp::Msg::Cb(CbAction::Close, _))) => {}
+            other => assert!(false, "Expected a cb close, got: {:?}", other),
+        };
+        addr.control(Control::Pause).await?;
+        match timeout(POSITIVE_RECV_TIMEOUT, onramp_rx.recv()).await {
+            Ok(Ok(onramp::Msg::Cb(CbAction::Close, _))) => {}
+            other => assert!(false, "Expected a cb close, got: {:?}", other),
+        };
+        // resuming must not open it again
+        addr.control(Control::Resume).await?;
+        if let Ok(Ok(m)) = timeout(NEGATIVE_RECV_TIMEOUT, onramp_rx.recv()).await {
+            assert!(false, "Didnt expect to receive something, got: {:?}", m)
+        }
+
+        // stopping the manager
+        sender.send(ManagerMsg::Stop).await?;
+        handle.cancel().await;
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn test_pipeline_tap() -> Result<()> {
+        let module_path = ModulePath { mounts: vec![] };
+        let query = r#"
+            select event
+            from in
+            into out;
+        "#;
+        let aggr_reg: tremor_script::registry::Aggr = tremor_script::aggr_registry();
+        let q = Query::parse(
+            &module_path,
+            "test_pipeline_tap.trickle",
+            query,
+            vec![],
+            &*FN_REGISTRY.lock()?,
+            &aggr_reg,
+        )?;
+        let config = tremor_pipeline::query::Query(q);
+        let id = TremorUrl::parse("/pipeline/test_pipeline_tap/instance")?;
+        let manager = Manager::new(12);
+        let (handle, sender) = manager.start();
+        let (tx, rx) = async_channel::bounded(1);
+        let create = Create { config, id };
+        sender
+            .send(ManagerMsg::Create(tx, Box::new(create)))
+            .await?;
+        let addr = rx.recv().await??;
+
+        let (offramp_tx, offramp_rx) = async_channel::unbounded();
+        addr.send_mgmt(MgmtMsg::ConnectOutput {
+            port: OUT,
+            output_url: TremorUrl::parse("/offramp/fake_offramp/instance/in")?,
+            target: ConnectTarget::Offramp(offramp_tx.clone()),
+        })
+        .await?;
+
+        let url = TremorUrl::parse("/pipeline/test_pipeline_tap/instance/out")?;
+        let (tap, _) = Tap::new(url.clone(), Some("snot".to_string()), 1.0)?;
+        assert!(addr.tap(tap).await.is_err());
+        let (tap, tap_rx) = Tap::new(url, None, 0.5)?;
+        addr.tap(tap).await?;
+
+        for i in 0..4_u64 {
+            addr.send(Msg::Event {
+                event: Event {
+                    data: literal!({ "snot": i }).into(),
+                    ..Event::default()
+                },
+                input: "in".into(),
+            })
+            .await?;
+            // taps don't affect the connected outputs
+            let event = wait_for_event(&offramp_rx, None).await?;
+            assert_eq!(&literal!({ "snot": i }), event.data.suffix().value());
+        }
+        for i in &[1_u64, 3] {
+            let tapped = tap_rx.recv().await?;
+            assert_eq!(
+                "tremor://localhost/pipeline/test_pipeline_tap/instance/out",
+                tapped.url
+            );
+            assert_eq!(literal!({ "snot": *i }), tapped.data);
+        }
+        assert!(tap_rx.try_recv().is_err());
+
+        // stopping the manager
+        sender.send(ManagerMsg::Stop).await?;
+        handle.cancel().await;
+        Ok(())
+    }
 }
@@ -0,0 +1,225 @@
+// Copyright 2020-2021, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Checkpoints of the operator state of pipelines.
+//!
+//! The state of a pipeline instance is captured every `checkpoint_interval_s`
+//! and written to a sled database in `checkpoint_dir`, keyed by the instance url.
+//! Acks flowing back to the inputs of the pipeline are held back until the state
+//! reflecting the acked events has been written, so onramps only consider events
+//! done once their effect can't be lost anymore. After a restart the state is
+//! restored and onramps replay everything that hasn't been acked.
+
+use crate::errors::Result;
+use crate::url::TremorUrl;
+use crate::utils::open_sled;
+use tremor_pipeline::{CbAction, CheckpointSettings, Event, EventId, ExecutableGraph};
+use tremor_script::prelude::*;
+
+/// Checkpoints of a single pipeline instance
+pub(crate) struct Checkpoints {
+    key: String,
+    db: sled::Db,
+    settings: CheckpointSettings,
+    next: u64,
+    /// acks held back until the next checkpoint
+    acks: Vec<Event>,
+    /// the events covered by previous checkpoints that were acked already
+    acked: Option<EventId>,
+}
+
+impl Checkpoints {
+    /// Opens the checkpoints of the pipeline instance `pid`
+    ///
+    /// # Errors
+    ///  * if the checkpoint store can't be opened
+    pub(crate) fn open(pid: &TremorUrl, settings: &CheckpointSettings) -> Result<Self> {
+        Ok(Self {
+            key: pid.to_string(),
+            db: open_sled(&settings.dir)?,
+            settings: settings.clone(),
+            next: 0,
+            acks: Vec::new(),
+            acked: None,
+        })
+    }
+
+    /// The settings the checkpoints were opened with
+    pub(crate) fn settings(&self) -> &CheckpointSettings {
+        &self.settings
+    }
+
+    /// Loads the last checkpoint of the pipeline instance, if there is one
+    ///
+    /// # Errors
+    ///  * if the checkpoint can't be read
+    pub(crate) fn load(&self) -> Result<Option<Value<'static>>> {
+        if let Some(bytes) = self.db.get(self.key.as_bytes())? {
+            let mut bytes = bytes.to_vec();
+            Ok(Some(
+                tremor_value::parse_to_value(&mut bytes)?.into_static(),
+            ))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Holds back acks until the next checkpoint, returns all other insights
+    pub(crate) fn hold(&mut self, insight: Event) -> Option<Event> {
+        if insight.cb == CbAction::Ack {
+            self.acks.push(insight);
+            None
+        } else {
+            Some(insight)
+        }
+    }
+
+    /// If the next checkpoint is due at `ns`
+    pub(crate) fn is_due(&self, ns: u64) -> bool {
+        ns >= self.next
+    }
+
+    /// Writes a checkpoint of `pipeline` and returns the acks that can be
+    /// released now, including an ack for the events incorporated into the
+    /// state of its operators that weren't acked by a previous checkpoint
+    ///
+    /// # Errors
+    ///  * if the state can't be captured or written
+    pub(crate) async fn save(&mut self, pipeline: &ExecutableGraph, ns: u64) -> Result<Vec<Event>> {
+        let (state, covered) = pipeline.checkpoint()?;
+        self.db
+            .insert(self.key.as_bytes(), state.encode().into_bytes())?;
+        self.db.flush_async().await?;
+        self.next = ns.saturating_add(self.settings.interval);
+        let mut acks = std::mem::take(&mut self.acks);
+        if let Some(covered) = covered {
+            let unacked = self
+                .acked
+                .as_ref()
+                .map_or_else(|| Some(covered.clone()), |acked| covered.without(acked));
+            if let Some(id) = unacked {
+                acks.push(Event::cb_ack(ns, id));
+            }
+            match &mut self.acked {
+                Some(acked) => acked.track(&covered),
+                None => self.acked = Some(covered),
+            }
+        }
+        Ok(acks)
+    }
+
+    /// Stops checkpointing, returns the acks held back so far
+    pub(crate) fn into_acks(self) -> Vec<Event> {
+        self.acks
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tremor_common::ids::OperatorIdGen;
+    use tremor_pipeline::FN_REGISTRY;
+    use tremor_script::{path::ModulePath, query::Query};
+
+    fn graph(dir: &str) -> Result<ExecutableGraph> {
+        let query = format!(
+            r#"
+#!config checkpoint_dir = "{}"
+define tumbling window two
+with
+  size = 2
+end;
+select {{ "count": aggr::stats::count() }} from in[two] into out;
+"#,
+            dir
+        );
+        let aggr_reg = tremor_script::aggr_registry();
+        let q = Query::parse(
+            &ModulePath { mounts: vec![] },
+            "checkpoint.trickle",
+            &query,
+            vec![],
+            &*FN_REGISTRY.lock()?,
+            &aggr_reg,
+        )?;
+        Ok(tremor_pipeline::query::Query(q).to_pipe(&mut OperatorIdGen::new())?)
+    }
+
+    #[async_std::test]
+    async fn save_and_load() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let dir = dir.path().to_string_lossy().to_string();
+        let pid = TremorUrl::parse("/pipeline/main/01")?;
+        let mut pipeline = graph(&dir)?;
+        let settings = pipeline
+            .checkpoint
+            .clone()
+            .unwrap_or_else(|| CheckpointSettings {
+                dir: dir.clone(),
+                interval: 0,
+            });
+        assert_eq!(10_000_000_000, settings.interval);
+        let mut checkpoints = Checkpoints::open(&pid, &settings)?;
+        assert!(checkpoints.load()?.is_none());
+
+        let mut eventset = Vec::new();
+        pipeline.enqueue(
+            "in",
+            Event {
+                id: (1, 1, 1).into(),
+                ..Event::default()
+            },
+            &mut eventset,
+        )?;
+        assert!(eventset.is_empty());
+
+        // acks are held back until the next checkpoint
+        assert!(checkpoints
+            .hold(Event::cb_ack(0, (1, 1, 0).into()))
+            .is_none());
+        assert!(checkpoints
+            .hold(Event::cb_fail(0, (1, 1, 0).into()))
+            .is_some());
+        assert!(checkpoints.is_due(0));
+        let acks = checkpoints.save(&pipeline, 0).await?;
+        assert!(!checkpoints.is_due(1));
+        // the held ack and one for the event in the window
+        assert_eq!(2, acks.len());
+        assert!(acks.iter().all(|e| e.cb == CbAction::Ack));
+        assert!(acks[1].id.is_tracking(&(1, 1, 1).into()));
+
+        // events still held by the window are only acked once
+        assert!(checkpoints.save(&pipeline, 0).await?.is_empty());
+
+        // a new pipeline picks up the window where the old one left off
+        let mut pipeline = graph(&dir)?;
+        let checkpoints = Checkpoints::open(&pid, &settings)?;
+        let state = checkpoints.load()?.unwrap_or_default();
+        assert_eq!(1, pipeline.restore(&state)?.len());
+        pipeline.enqueue(
+            "in",
+            Event {
+                id: (1, 1, 2).into(),
+                ..Event::default()
+            },
+            &mut eventset,
+        )?;
+        assert_eq!(1, eventset.len());
+        assert_eq!(
+            Some(2),
+            eventset[0].1.data.suffix().value().get_u64("count")
+        );
+        Ok(())
+    }
+}
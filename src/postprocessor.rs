@@ -16,7 +16,9 @@ mod gelf;
 pub(crate) use gelf::Gelf;
 
 use crate::errors::{Error, Result};
-use byteorder::{BigEndian, WriteBytesExt};
+use crate::preprocessor::{Endian, LengthPrefixConfig};
+use crate::OpConfig;
+use byteorder::{BigEndian, LittleEndian, WriteBytesExt};
 use std::default::Default;
 use tremor_common::time::nanotime;
 /// Set of Postprocessors
@@ -24,6 +26,18 @@ pub type Postprocessors = Vec<Box<dyn Postprocessor>>;
 use std::io::Write;
 use std::mem;
 use std::str;
+use tremor_pipeline::ConfigImpl;
+
+/// Postprocessor configuration, either just the name of a postprocessor
+/// or its name and a postprocessor specific config
+///
+/// e.g.:
+///       postprocessors:
+///         - name: zstd
+///           config:
+///             level: 19
+///         - lines
+pub type Config = crate::config::NameWithConfig;
 
 /// Postprocessor trait
 pub trait Postprocessor: Send {
@@ -60,13 +74,82 @@ pub fn lookup(name: &str) -> Result<Box<dyn Postprocessor>> {
     }
 }
 
-/// Given the slice of postprocessor names: Lookup each of them and return them as `Postprocessors`
+/// Lookup a postprocessor via its unique id, passing it its specific `config`
 ///
 /// # Errors
 ///
-///   * If any postprocessor is not known.
-pub fn make_postprocessors(postprocessors: &[String]) -> Result<Postprocessors> {
-    postprocessors.iter().map(|n| lookup(&n)).collect()
+///   * Errors if the postprocessor is not known or its config is invalid
+pub fn lookup_with_config(name: &str, config: Option<&OpConfig>) -> Result<Box<dyn Postprocessor>> {
+    match (name, config) {
+        (_, None) => lookup(name),
+        ("lines", Some(config)) => Ok(Box::new(Lines::from_config(config)?)),
+        ("length-prefixed", Some(config)) => Ok(Box::new(LengthPrefix {
+            config: LengthPrefixConfig::parse("postprocessor", config)?,
+        })),
+        ("gzip", Some(config)) => Ok(Box::new(Gzip {
+            config: parse_level("gzip", config, 0..=9)?,
+        })),
+        ("zlib", Some(config)) => Ok(Box::new(Zlib {
+            config: parse_level("zlib", config, 0..=9)?,
+        })),
+        ("xz2", Some(config)) => Ok(Box::new(Xz2 {
+            config: parse_level("xz2", config, 0..=9)?,
+        })),
+        ("lz4", Some(config)) => Ok(Box::new(Lz4 {
+            config: parse_level("lz4", config, 0..=16)?,
+        })),
+        ("zstd", Some(config)) => Ok(Box::new(Zstd {
+            config: parse_level("zstd", config, -7..=22)?,
+        })),
+        (_, Some(_)) => {
+            lookup(name)?;
+            Err(format!("Postprocessor '{}' does not take a config.", name).into())
+        }
+    }
+}
+
+/// Given the slice of postprocessor configs: Lookup each of them and return them as `Postprocessors`
+///
+/// # Errors
+///
+///   * If any postprocessor is not known or its config is invalid.
+pub fn make_postprocessors(postprocessors: &[Config]) -> Result<Postprocessors> {
+    postprocessors
+        .iter()
+        .map(|c| lookup_with_config(c.name(), c.config()))
+        .collect()
+}
+
+/// Config of the compressing postprocessors
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct CompressionConfig<L> {
+    /// compression level
+    pub(crate) level: L,
+}
+
+impl<L> ConfigImpl for CompressionConfig<L> {}
+
+fn parse_level<L>(
+    name: &str,
+    config: &OpConfig,
+    range: std::ops::RangeInclusive<L>,
+) -> Result<CompressionConfig<L>>
+where
+    L: PartialOrd + std::fmt::Display + for<'de> serde::Deserialize<'de>,
+{
+    let config: CompressionConfig<L> = crate::config::parse_config("postprocessor", name, config)?;
+    if range.contains(&config.level) {
+        Ok(config)
+    } else {
+        Err(format!(
+            "Invalid config for postprocessor '{}': level must be between {} and {}.",
+            name,
+            range.start(),
+            range.end()
+        )
+        .into())
+    }
 }
 
 /// canonical way to process encoded data passed from a `Codec`
@@ -99,8 +182,48 @@ pub fn postprocess(
     Ok(data)
 }
 
-#[derive(Default)]
-pub(crate) struct Lines {}
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct LinesConfig {
+    /// line separator, a single ascii character
+    #[serde(default = "LinesConfig::default_separator")]
+    pub(crate) separator: char,
+}
+
+impl ConfigImpl for LinesConfig {}
+
+impl LinesConfig {
+    fn default_separator() -> char {
+        '\n'
+    }
+}
+
+pub(crate) struct Lines {
+    separator: u8,
+}
+
+impl Default for Lines {
+    fn default() -> Self {
+        Self { separator: b'\n' }
+    }
+}
+
+impl Lines {
+    fn from_config(config: &OpConfig) -> Result<Self> {
+        let config: LinesConfig = crate::config::parse_config("postprocessor", "lines", config)?;
+        if config.separator.is_ascii() {
+            Ok(Self {
+                separator: config.separator as u8,
+            })
+        } else {
+            Err(
+                "Invalid config for postprocessor 'lines': separator must be an ascii character."
+                    .into(),
+            )
+        }
+    }
+}
+
 impl Postprocessor for Lines {
     #[cfg(not(tarpaulin_include))]
     fn name(&self) -> &str {
@@ -111,7 +234,7 @@ impl Postprocessor for Lines {
         // padding capacity with 1 to account for the new line char we will be pushing
         let mut framed: Vec<u8> = Vec::with_capacity(data.len() + 1);
         framed.extend_from_slice(data);
-        framed.push(b'\n');
+        framed.push(self.separator);
         Ok(vec![framed])
     }
 }
@@ -129,8 +252,18 @@ impl Postprocessor for Base64 {
     }
 }
 
-#[derive(Default)]
-pub(crate) struct Gzip {}
+pub(crate) struct Gzip {
+    config: CompressionConfig<u32>,
+}
+
+impl Default for Gzip {
+    fn default() -> Self {
+        Self {
+            config: CompressionConfig { level: 6 },
+        }
+    }
+}
+
 impl Postprocessor for Gzip {
     #[cfg(not(tarpaulin_include))]
     fn name(&self) -> &str {
@@ -138,16 +271,25 @@ impl Postprocessor for Gzip {
     }
 
     fn process(&mut self, _ingres_ns: u64, _egress_ns: u64, data: &[u8]) -> Result<Vec<Vec<u8>>> {
-        use libflate::gzip::Encoder;
-
-        let mut encoder = Encoder::new(Vec::new())?;
+        use flate2::{write::GzEncoder as Encoder, Compression};
+        let mut encoder = Encoder::new(Vec::new(), Compression::new(self.config.level));
         encoder.write_all(&data)?;
-        Ok(vec![encoder.finish().into_result()?])
+        Ok(vec![encoder.finish()?])
+    }
+}
+
+pub(crate) struct Zlib {
+    config: CompressionConfig<u32>,
+}
+
+impl Default for Zlib {
+    fn default() -> Self {
+        Self {
+            config: CompressionConfig { level: 6 },
+        }
     }
 }
 
-#[derive(Default)]
-pub(crate) struct Zlib {}
 impl Postprocessor for Zlib {
     #[cfg(not(tarpaulin_include))]
     fn name(&self) -> &str {
@@ -155,15 +297,25 @@ impl Postprocessor for Zlib {
     }
 
     fn process(&mut self, _ingres_ns: u64, _egress_ns: u64, data: &[u8]) -> Result<Vec<Vec<u8>>> {
-        use libflate::zlib::Encoder;
-        let mut encoder = Encoder::new(Vec::new())?;
+        use flate2::{write::ZlibEncoder as Encoder, Compression};
+        let mut encoder = Encoder::new(Vec::new(), Compression::new(self.config.level));
         encoder.write_all(&data)?;
-        Ok(vec![encoder.finish().into_result()?])
+        Ok(vec![encoder.finish()?])
+    }
+}
+
+pub(crate) struct Xz2 {
+    config: CompressionConfig<u32>,
+}
+
+impl Default for Xz2 {
+    fn default() -> Self {
+        Self {
+            config: CompressionConfig { level: 9 },
+        }
     }
 }
 
-#[derive(Default)]
-pub(crate) struct Xz2 {}
 impl Postprocessor for Xz2 {
     #[cfg(not(tarpaulin_include))]
     fn name(&self) -> &str {
@@ -172,7 +324,7 @@ impl Postprocessor for Xz2 {
 
     fn process(&mut self, _ingres_ns: u64, _egress_ns: u64, data: &[u8]) -> Result<Vec<Vec<u8>>> {
         use xz2::write::XzEncoder as Encoder;
-        let mut encoder = Encoder::new(Vec::new(), 9);
+        let mut encoder = Encoder::new(Vec::new(), self.config.level);
         encoder.write_all(&data)?;
         Ok(vec![encoder.finish()?])
     }
@@ -197,8 +349,18 @@ impl Postprocessor for Snappy {
     }
 }
 
-#[derive(Default)]
-pub(crate) struct Lz4 {}
+pub(crate) struct Lz4 {
+    config: CompressionConfig<u32>,
+}
+
+impl Default for Lz4 {
+    fn default() -> Self {
+        Self {
+            config: CompressionConfig { level: 4 },
+        }
+    }
+}
+
 impl Postprocessor for Lz4 {
     #[cfg(not(tarpaulin_include))]
     fn name(&self) -> &str {
@@ -208,7 +370,9 @@ impl Postprocessor for Lz4 {
     fn process(&mut self, _ingres_ns: u64, _egress_ns: u64, data: &[u8]) -> Result<Vec<Vec<u8>>> {
         use lz4::EncoderBuilder;
         let buffer = Vec::<u8>::new();
-        let mut encoder = EncoderBuilder::new().level(4).build(buffer)?;
+        let mut encoder = EncoderBuilder::new()
+            .level(self.config.level)
+            .build(buffer)?;
         encoder.write_all(&data)?;
         Ok(vec![encoder.finish().0])
     }
@@ -231,7 +395,9 @@ impl Postprocessor for AttachIngresTs {
 }
 
 #[derive(Clone, Default)]
-pub(crate) struct LengthPrefix {}
+pub(crate) struct LengthPrefix {
+    config: LengthPrefixConfig,
+}
 impl Postprocessor for LengthPrefix {
     #[cfg(not(tarpaulin_include))]
     fn name(&self) -> &str {
@@ -239,8 +405,21 @@ impl Postprocessor for LengthPrefix {
     }
 
     fn process(&mut self, _ingres_ns: u64, _egress_ns: u64, data: &[u8]) -> Result<Vec<Vec<u8>>> {
-        let mut res = Vec::with_capacity(data.len() + 8);
-        res.write_u64::<BigEndian>(data.len() as u64)?;
+        let prefix_len = self.config.length;
+        let len = data.len() as u64;
+        // the length has to fit into the prefix
+        if prefix_len < 8 && len >> (prefix_len * 8) > 0 {
+            return Err(format!(
+                "Data of length {} exceeds the {} byte length prefix",
+                len, prefix_len
+            )
+            .into());
+        }
+        let mut res = Vec::with_capacity(data.len() + prefix_len);
+        match self.config.endian {
+            Endian::Big => res.write_uint::<BigEndian>(len, prefix_len)?,
+            Endian::Little => res.write_uint::<LittleEndian>(len, prefix_len)?,
+        }
         res.write_all(&data)?;
         Ok(vec![res])
     }
@@ -265,8 +444,20 @@ impl Postprocessor for TextualLength {
     }
 }
 
-#[derive(Clone, Default, Debug)]
-pub(crate) struct Zstd {}
+#[derive(Clone, Debug)]
+pub(crate) struct Zstd {
+    config: CompressionConfig<i32>,
+}
+
+impl Default for Zstd {
+    fn default() -> Self {
+        // Value of 0 indicates default level for encode.
+        Self {
+            config: CompressionConfig { level: 0 },
+        }
+    }
+}
+
 impl Postprocessor for Zstd {
     #[cfg(not(tarpaulin_include))]
     fn name(&self) -> &str {
@@ -274,8 +465,7 @@ impl Postprocessor for Zstd {
     }
 
     fn process(&mut self, _ingres_ns: u64, _egress_ns: u64, data: &[u8]) -> Result<Vec<Vec<u8>>> {
-        // Value of 0 indicates default level for encode.
-        let compressed = zstd::encode_all(data, 0)?;
+        let compressed = zstd::encode_all(data, self.config.level)?;
         Ok(vec![compressed])
     }
 }
@@ -286,7 +476,7 @@ mod test {
 
     #[test]
     fn line() {
-        let mut line = Lines {};
+        let mut line = Lines::default();
         let data: [u8; 0] = [];
         assert_eq!(Ok(vec![vec![b'\n']]), line.process(0, 0, &data));
         assert_eq!(
@@ -314,4 +504,63 @@ mod test {
         let encoded = post.process(42, 23, &data).unwrap().pop().unwrap();
         assert_eq!("3 \u{1}\u{2}\u{3}", str::from_utf8(&encoded).unwrap());
     }
+
+    #[test]
+    fn lookup_with_config() -> Result<()> {
+        let config: OpConfig = serde_yaml::from_str("{separator: '|'}")?;
+        let mut line = super::lookup_with_config("lines", Some(&config))?;
+        assert_eq!(vec![b"snot|".to_vec()], line.process(0, 0, b"snot")?);
+        let config: OpConfig = serde_yaml::from_str("{}")?;
+        let mut line = super::lookup_with_config("lines", Some(&config))?;
+        assert_eq!(vec![b"snot\n".to_vec()], line.process(0, 0, b"snot")?);
+
+        let config: OpConfig = serde_yaml::from_str("{length: 2, endian: little}")?;
+        let mut prefix = super::lookup_with_config("length-prefixed", Some(&config))?;
+        assert_eq!(vec![vec![3, 0, 1, 2, 3]], prefix.process(0, 0, &[1, 2, 3])?);
+        assert!(prefix.process(0, 0, &[0; 65536]).is_err());
+
+        let config: OpConfig = serde_yaml::from_str("{level: 19}")?;
+        assert!(super::lookup_with_config("zstd", Some(&config)).is_ok());
+        assert_eq!(
+            "Invalid config for postprocessor 'xz2': level must be between 0 and 9.",
+            super::lookup_with_config("xz2", Some(&config))
+                .err()
+                .unwrap()
+                .to_string()
+        );
+        assert_eq!(
+            "Invalid config for postprocessor 'gzip': level must be between 0 and 9.",
+            super::lookup_with_config("gzip", Some(&config))
+                .err()
+                .unwrap()
+                .to_string()
+        );
+        assert_eq!(
+            "Postprocessor 'base64' does not take a config.",
+            super::lookup_with_config("base64", Some(&config))
+                .err()
+                .unwrap()
+                .to_string()
+        );
+
+        // every level yields data the preprocessors can decompress
+        for name in &["gzip", "zlib"] {
+            for level in &[0, 1, 9] {
+                let config: OpConfig = serde_yaml::from_str(&format!("{{level: {}}}", level))?;
+                let mut post = super::lookup_with_config(name, Some(&config))?;
+                let mut pre = crate::preprocessor::lookup(name)?;
+                let compressed = post
+                    .process(0, 0, b"snot badger")?
+                    .pop()
+                    .unwrap_or_default();
+                let mut ingest_ns = 0;
+                assert_eq!(
+                    vec![b"snot badger".to_vec()],
+                    pre.process(&mut ingest_ns, &compressed)?
+                );
+            }
+        }
+        assert!(super::lookup_with_config("snot", Some(&config)).is_err());
+        Ok(())
+    }
 }
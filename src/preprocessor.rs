@@ -15,21 +15,36 @@
 mod gelf;
 pub(crate) use gelf::Gelf;
 pub(crate) mod lines;
+pub(crate) mod multiline;
 
 use crate::errors::{Error, Result};
 use crate::url::TremorUrl;
-use byteorder::{BigEndian, ByteOrder, ReadBytesExt};
+use crate::OpConfig;
+use byteorder::{BigEndian, ByteOrder, LittleEndian, ReadBytesExt};
 use bytes::buf::Buf;
 use bytes::BytesMut;
 use std::str;
 
 use std::io::{self, Read};
+use tremor_pipeline::ConfigImpl;
 
 //pub type Lines = lines::Lines;
 
 /// A set of preprocessors
 pub type Preprocessors = Vec<Box<dyn Preprocessor>>;
 
+/// Preprocessor configuration, either just the name of a preprocessor
+/// or its name and a preprocessor specific config
+///
+/// e.g.:
+///       preprocessors:
+///         - gzip
+///         - name: lines
+///           config:
+///             separator: "|"
+///             max_length: 16384
+pub type Config = crate::config::NameWithConfig;
+
 /// Preprocessor trait
 pub trait Preprocessor: Sync + Send {
     /// Canonical name for this preprocessor
@@ -40,6 +55,22 @@ pub trait Preprocessor: Sync + Send {
     ///
     /// * Errors if the data can not processed
     fn process(&mut self, ingest_ns: &mut u64, data: &[u8]) -> Result<Vec<Vec<u8>>>;
+    /// flushes all data held back, called at the end of a stream
+    ///
+    /// # Errors
+    ///
+    /// * Errors if the held back data can not processed
+    fn finish(&mut self, _ingest_ns: u64) -> Result<Vec<Vec<u8>>> {
+        Ok(vec![])
+    }
+    /// flushes data held back for too long, called periodically
+    ///
+    /// # Errors
+    ///
+    /// * Errors if the held back data can not processed
+    fn on_tick(&mut self, _ingest_ns: u64) -> Result<Vec<Vec<u8>>> {
+        Ok(vec![])
+    }
 }
 
 /// Lookup a preprocessor implementation via its unique id
@@ -70,17 +101,39 @@ pub fn lookup(name: &str) -> Result<Box<dyn Preprocessor>> {
         "length-prefixed" => Ok(Box::new(LengthPrefix::default())),
         "textual-length-prefix" => Ok(Box::new(TextualLength::default())),
         "zstd" => Ok(Box::new(Zstd::default())),
+        "multiline" => Ok(Box::new(Multiline::default())),
         _ => Err(format!("Preprocessor '{}' not found.", name).into()),
     }
 }
 
-/// Given the slice of preprocessor names: Look them up and return them as `Preprocessors`.
+/// Lookup a preprocessor implementation via its unique id, passing it its specific `config`
 ///
 /// # Errors
 ///
-///   * If the preprocessor is not known.
-pub fn make_preprocessors(preprocessors: &[String]) -> Result<Preprocessors> {
-    preprocessors.iter().map(|n| lookup(&n)).collect()
+///   * Errors if the preprocessor is not known or its config is invalid
+pub fn lookup_with_config(name: &str, config: Option<&OpConfig>) -> Result<Box<dyn Preprocessor>> {
+    match (name, config) {
+        (_, None) => lookup(name),
+        ("lines", Some(config)) => Ok(Box::new(Lines::from_config(config)?)),
+        ("length-prefixed", Some(config)) => Ok(Box::new(LengthPrefix::from_config(config)?)),
+        ("multiline", Some(config)) => Ok(Box::new(Multiline::from_config(config)?)),
+        (_, Some(_)) => {
+            lookup(name)?;
+            Err(format!("Preprocessor '{}' does not take a config.", name).into())
+        }
+    }
+}
+
+/// Given the slice of preprocessor configs: Look them up and return them as `Preprocessors`.
+///
+/// # Errors
+///
+///   * If the preprocessor is not known or its config is invalid.
+pub fn make_preprocessors(preprocessors: &[Config]) -> Result<Preprocessors> {
+    preprocessors
+        .iter()
+        .map(|c| lookup_with_config(c.name(), c.config()))
+        .collect()
 }
 
 /// Canonical way to preprocess data before it is fed to a codec for decoding.
@@ -115,6 +168,48 @@ pub fn preprocess(
     Ok(data)
 }
 
+/// Flushes the data held back by `preprocessors`, all of it at the end of a stream,
+/// otherwise only data held back for too long.
+///
+/// Data flushed by a preprocessor still passes all the preprocessors after it.
+///
+/// # Errors
+///
+///   * If a preprocessor failed
+pub fn flush(
+    preprocessors: &mut [Box<dyn Preprocessor>],
+    ingest_ns: &mut u64,
+    end_of_stream: bool,
+    instance_id: &TremorUrl,
+) -> Result<Vec<Vec<u8>>> {
+    let mut data: Vec<Vec<u8>> = Vec::new();
+    for (i, pp) in preprocessors.iter_mut().enumerate() {
+        let mut flushed = Vec::new();
+        let res = data
+            .iter()
+            .try_for_each(|d| {
+                flushed.append(&mut pp.process(ingest_ns, d)?);
+                Ok(())
+            })
+            .and_then(|()| {
+                if end_of_stream {
+                    pp.finish(*ingest_ns)
+                } else {
+                    pp.on_tick(*ingest_ns)
+                }
+            });
+        match res {
+            Ok(mut r) => flushed.append(&mut r),
+            Err(e) => {
+                error!("[{}] Preprocessor [{}] error {}", instance_id, i, e);
+                return Err(e);
+            }
+        }
+        data = flushed;
+    }
+    Ok(data)
+}
+
 trait SliceTrim {
     fn trim(&self) -> &Self;
 }
@@ -139,6 +234,7 @@ impl SliceTrim for [u8] {
 }
 
 pub(crate) use lines::Lines;
+pub(crate) use multiline::Multiline;
 
 #[derive(Default, Debug, Clone)]
 pub(crate) struct FilterEmpty {}
@@ -343,11 +439,80 @@ impl Preprocessor for Decompress {
         Ok(vec![r])
     }
 }
+/// Byte order of a length prefix
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Endian {
+    #[serde(rename = "big")]
+    Big,
+    #[serde(rename = "little")]
+    Little,
+}
+
+impl Default for Endian {
+    fn default() -> Self {
+        Self::Big
+    }
+}
+
+/// Config of the `length-prefixed` pre- and postprocessors
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct LengthPrefixConfig {
+    /// size of the length prefix in bytes
+    #[serde(default = "LengthPrefixConfig::default_length")]
+    pub(crate) length: usize,
+    /// byte order of the length prefix
+    #[serde(default = "Default::default")]
+    pub(crate) endian: Endian,
+}
+
+impl ConfigImpl for LengthPrefixConfig {}
+
+impl Default for LengthPrefixConfig {
+    fn default() -> Self {
+        Self {
+            length: Self::default_length(),
+            endian: Endian::default(),
+        }
+    }
+}
+
+impl LengthPrefixConfig {
+    fn default_length() -> usize {
+        8
+    }
+
+    /// parses and validates the config of the length prefix processor `kind`
+    pub(crate) fn parse(kind: &str, config: &OpConfig) -> Result<Self> {
+        let config: Self = crate::config::parse_config(kind, "length-prefixed", config)?;
+        if matches!(config.length, 1 | 2 | 4 | 8) {
+            Ok(config)
+        } else {
+            Err(format!(
+                "Invalid config for {} 'length-prefixed': length must be 1, 2, 4 or 8 bytes.",
+                kind
+            )
+            .into())
+        }
+    }
+}
+
 #[derive(Clone, Default, Debug)]
 pub(crate) struct LengthPrefix {
     len: Option<usize>,
     buffer: BytesMut,
+    config: LengthPrefixConfig,
 }
+
+impl LengthPrefix {
+    pub(crate) fn from_config(config: &OpConfig) -> Result<Self> {
+        Ok(Self {
+            config: LengthPrefixConfig::parse("preprocessor", config)?,
+            ..Self::default()
+        })
+    }
+}
+
 impl Preprocessor for LengthPrefix {
     #[cfg(not(tarpaulin_include))]
     fn name(&self) -> &str {
@@ -370,9 +535,14 @@ impl Preprocessor for LengthPrefix {
                     break;
                 }
             }
-            if self.buffer.len() > 8 {
-                self.len = Some(BigEndian::read_u64(&self.buffer) as usize);
-                self.buffer.advance(8);
+            let prefix_len = self.config.length;
+            if self.buffer.len() >= prefix_len {
+                let len = match self.config.endian {
+                    Endian::Big => BigEndian::read_uint(&self.buffer, prefix_len),
+                    Endian::Little => LittleEndian::read_uint(&self.buffer, prefix_len),
+                };
+                self.len = Some(len as usize);
+                self.buffer.advance(prefix_len);
             } else {
                 break;
             }
@@ -601,7 +771,30 @@ mod test {
         Ok(())
     }
 
-    const LOOKUP_TABLE: [&str; 17] = [
+    #[test]
+    fn length_prefix_with_config() -> Result<()> {
+        let mut it = 0;
+        let config: OpConfig = serde_yaml::from_str("{length: 2, endian: little}")?;
+        let mut pps = vec![pre::lookup_with_config("length-prefixed", Some(&config))?];
+        let mut post_p = post::lookup_with_config("length-prefixed", Some(&config))?;
+
+        let data = vec![0, 1, 2, 3, 4, 5, 6, 7, 8, 9];
+        let wire = post_p.process(0, 0, &data)?;
+        assert_eq!(&[10, 0], &wire[0][..2]);
+        let (start, end) = wire[0].split_at(1);
+        let id = TremorUrl::parse("/onramp/snot/00").unwrap();
+        let recv = preprocess(pps.as_mut_slice(), &mut it, start.to_vec(), &id)?;
+        assert!(recv.is_empty());
+        let recv = preprocess(pps.as_mut_slice(), &mut it, end.to_vec(), &id)?;
+        assert_eq!(recv[0], data);
+
+        let config: OpConfig = serde_yaml::from_str("{length: 3}")?;
+        assert!(pre::lookup_with_config("length-prefixed", Some(&config)).is_err());
+        assert!(pre::lookup_with_config("base64", Some(&config)).is_err());
+        Ok(())
+    }
+
+    const LOOKUP_TABLE: [&str; 18] = [
         "lines",
         "lines-null",
         "lines-pipe",
@@ -619,6 +812,7 @@ mod test {
         "length-prefixed",
         "textual-length-prefix",
         "zstd",
+        "multiline",
     ];
 
     #[test]
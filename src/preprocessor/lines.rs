@@ -14,6 +14,36 @@
 
 use super::Preprocessor;
 use crate::errors::Result;
+use crate::OpConfig;
+use tremor_pipeline::ConfigImpl;
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Config {
+    /// line separator, a single ascii character
+    #[serde(default = "Config::default_separator")]
+    pub(crate) separator: char,
+    /// maximum length of a line in bytes, `0` for no limit
+    #[serde(default = "Config::default_max_length")]
+    pub(crate) max_length: usize,
+    /// buffer line fragments spanning multiple chunks of data
+    #[serde(default = "Config::default_buffered")]
+    pub(crate) buffered: bool,
+}
+
+impl ConfigImpl for Config {}
+
+impl Config {
+    fn default_separator() -> char {
+        '\n'
+    }
+    fn default_max_length() -> usize {
+        1_048_576
+    }
+    fn default_buffered() -> bool {
+        true
+    }
+}
 
 #[derive(Clone)]
 pub struct Lines {
@@ -30,8 +60,7 @@ impl Default for Lines {
 }
 
 impl Lines {
-    // TODO have the params here as a config struct
-    // also break lines on string (eg: \r\n)
+    // TODO break lines on string (eg: \r\n)
     pub fn new(separator: char, max_length: usize, is_buffered: bool) -> Self {
         Self {
             separator: separator as u8,
@@ -43,6 +72,27 @@ impl Lines {
         }
     }
 
+    pub(crate) fn from_config(config: &OpConfig) -> Result<Self> {
+        let config: Config = crate::config::parse_config("preprocessor", "lines", config)?;
+        if !config.separator.is_ascii() {
+            Err(
+                "Invalid config for preprocessor 'lines': separator must be an ascii character."
+                    .into(),
+            )
+        } else if config.buffered && config.max_length == 0 {
+            Err(
+                "Invalid config for preprocessor 'lines': buffered lines require a max_length."
+                    .into(),
+            )
+        } else {
+            Ok(Self::new(
+                config.separator,
+                config.max_length,
+                config.buffered,
+            ))
+        }
+    }
+
     fn is_valid_line(&self, v: &[u8]) -> bool {
         //return true if is there is no limit on max length of the data fragment
         if self.max_length == 0 {
@@ -462,4 +512,28 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn from_config() -> Result<()> {
+        let config: OpConfig = serde_yaml::from_str("{separator: '|', max_length: 5}")?;
+        let mut pp = Lines::from_config(&config)?;
+        let mut i = 0_u64;
+        let mut r = pp.process(&mut i, b"snot|badger|012")?;
+        // lines exceeding the max length are dropped
+        assert_eq!(r.pop().unwrap(), b"snot");
+        assert!(r.is_empty());
+        assert_eq!(pp.process(&mut i, b"34|")?, vec![b"01234".to_vec()]);
+
+        let config: OpConfig = serde_yaml::from_str("{buffered: false, max_length: 0}")?;
+        let mut pp = Lines::from_config(&config)?;
+        assert_eq!(pp.process(&mut i, b"snot\nbadger")?.len(), 2);
+
+        let config: OpConfig = serde_yaml::from_str("{max_length: 0}")?;
+        assert!(Lines::from_config(&config).is_err());
+        let config: OpConfig = serde_yaml::from_str("{separator: 'ä'}")?;
+        assert!(Lines::from_config(&config).is_err());
+        let config: OpConfig = serde_yaml::from_str("{snot: badger}")?;
+        assert!(Lines::from_config(&config).is_err());
+        Ok(())
+    }
 }
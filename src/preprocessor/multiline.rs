@@ -0,0 +1,283 @@
+// Copyright 2020-2021, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Joins continuation lines into a single event, e.g. stack traces.
+//!
+//! Expects a single line per chunk of data, so it is meant to be placed
+//! after the `lines` preprocessor.
+
+use super::Preprocessor;
+use crate::errors::{Error, Result};
+use crate::OpConfig;
+use regex::bytes::Regex;
+use tremor_pipeline::ConfigImpl;
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Config {
+    /// pattern matching the first line of an event,
+    /// all lines not matching it are continuation lines
+    #[serde(default = "Default::default")]
+    pub(crate) start: Option<String>,
+    /// pattern matching continuation lines
+    #[serde(default = "Default::default")]
+    pub(crate) continuation: Option<String>,
+    /// separator put between joined lines
+    #[serde(default = "Config::default_separator")]
+    pub(crate) separator: String,
+    /// maximum number of lines joined into a single event
+    #[serde(default = "Config::default_max_lines")]
+    pub(crate) max_lines: usize,
+    /// time in milliseconds after which a pending event is flushed,
+    /// checked when the next line arrives and periodically by the onramp
+    #[serde(default = "Config::default_timeout_ms")]
+    pub(crate) timeout_ms: u64,
+}
+
+impl ConfigImpl for Config {}
+
+impl Config {
+    fn default_separator() -> String {
+        "\n".to_string()
+    }
+    fn default_max_lines() -> usize {
+        500
+    }
+    fn default_timeout_ms() -> u64 {
+        1000
+    }
+}
+
+pub(crate) struct Multiline {
+    start: Option<Regex>,
+    continuation: Option<Regex>,
+    separator: Vec<u8>,
+    max_lines: usize,
+    timeout_ns: u64,
+    buffer: Vec<u8>,
+    lines: usize,
+    first_ns: u64,
+}
+
+// By default indented lines, as found in java stack traces or python tracebacks,
+// are joined with the preceding line
+impl Default for Multiline {
+    fn default() -> Self {
+        Self {
+            start: None,
+            continuation: Regex::new(r"^[ \t]").ok(),
+            separator: b"\n".to_vec(),
+            max_lines: Config::default_max_lines(),
+            timeout_ns: Config::default_timeout_ms() * 1_000_000,
+            buffer: Vec::new(),
+            lines: 0,
+            first_ns: 0,
+        }
+    }
+}
+
+fn pattern(field: &str, pattern: Option<&String>) -> Result<Option<Regex>> {
+    pattern
+        .map(|p| {
+            Regex::new(p).map_err(|e| {
+                Error::from(format!(
+                    "Invalid config for preprocessor 'multiline': invalid {} pattern: {}",
+                    field, e
+                ))
+            })
+        })
+        .transpose()
+}
+
+impl Multiline {
+    pub(crate) fn from_config(config: &OpConfig) -> Result<Self> {
+        let config: Config = crate::config::parse_config("preprocessor", "multiline", config)?;
+        if config.start.is_none() && config.continuation.is_none() {
+            return Err(
+                "Invalid config for preprocessor 'multiline': start or continuation required."
+                    .into(),
+            );
+        }
+        if config.max_lines == 0 {
+            return Err(
+                "Invalid config for preprocessor 'multiline': max_lines must be greater than 0."
+                    .into(),
+            );
+        }
+        Ok(Self {
+            start: pattern("start", config.start.as_ref())?,
+            continuation: pattern("continuation", config.continuation.as_ref())?,
+            separator: config.separator.into_bytes(),
+            max_lines: config.max_lines,
+            timeout_ns: config.timeout_ms.saturating_mul(1_000_000),
+            ..Self::default()
+        })
+    }
+
+    fn is_continuation(&self, line: &[u8]) -> bool {
+        self.start.as_ref().map_or(true, |s| !s.is_match(line))
+            && self
+                .continuation
+                .as_ref()
+                .map_or(true, |c| c.is_match(line))
+    }
+
+    fn flush(&mut self, res: &mut Vec<Vec<u8>>) {
+        if self.lines > 0 {
+            res.push(std::mem::take(&mut self.buffer));
+            self.lines = 0;
+        }
+    }
+
+    fn flush_expired(&mut self, ingest_ns: u64, res: &mut Vec<Vec<u8>>) {
+        if self.lines > 0 && ingest_ns.saturating_sub(self.first_ns) > self.timeout_ns {
+            self.flush(res);
+        }
+    }
+}
+
+impl Preprocessor for Multiline {
+    #[cfg(not(tarpaulin_include))]
+    fn name(&self) -> &str {
+        "multiline"
+    }
+
+    fn process(&mut self, ingest_ns: &mut u64, data: &[u8]) -> Result<Vec<Vec<u8>>> {
+        let mut res = Vec::new();
+        self.flush_expired(*ingest_ns, &mut res);
+        if self.lines > 0 && self.is_continuation(data) {
+            self.buffer.extend_from_slice(&self.separator);
+            self.buffer.extend_from_slice(data);
+            self.lines += 1;
+        } else {
+            self.flush(&mut res);
+            self.buffer.extend_from_slice(data);
+            self.lines = 1;
+            self.first_ns = *ingest_ns;
+        }
+        if self.lines >= self.max_lines {
+            self.flush(&mut res);
+        }
+        Ok(res)
+    }
+
+    fn finish(&mut self, _ingest_ns: u64) -> Result<Vec<Vec<u8>>> {
+        let mut res = Vec::new();
+        self.flush(&mut res);
+        Ok(res)
+    }
+
+    fn on_tick(&mut self, ingest_ns: u64) -> Result<Vec<Vec<u8>>> {
+        let mut res = Vec::new();
+        self.flush_expired(ingest_ns, &mut res);
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn multiline(config: &str) -> Result<Multiline> {
+        let config: OpConfig = serde_yaml::from_str(config)?;
+        Multiline::from_config(&config)
+    }
+
+    #[test]
+    fn stack_trace() -> Result<()> {
+        let mut pp = Multiline::default();
+        let mut ingest_ns = 0;
+        assert!(pp
+            .process(&mut ingest_ns, b"Exception in thread main")?
+            .is_empty());
+        assert!(pp
+            .process(&mut ingest_ns, b"\tat snot.badger()")?
+            .is_empty());
+        assert!(pp.process(&mut ingest_ns, b"\tat snot.main()")?.is_empty());
+        assert_eq!(
+            vec![b"Exception in thread main\n\tat snot.badger()\n\tat snot.main()".to_vec()],
+            pp.process(&mut ingest_ns, b"next")?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn start_pattern() -> Result<()> {
+        let mut pp = multiline(r#"{start: '^\[', separator: " "}"#)?;
+        let mut ingest_ns = 0;
+        assert!(pp.process(&mut ingest_ns, b"[1] snot")?.is_empty());
+        assert!(pp.process(&mut ingest_ns, b"badger")?.is_empty());
+        assert_eq!(
+            vec![b"[1] snot badger".to_vec()],
+            pp.process(&mut ingest_ns, b"[2] snot")?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn max_lines() -> Result<()> {
+        let mut pp = multiline("{continuation: '^ ', max_lines: 2}")?;
+        let mut ingest_ns = 0;
+        assert!(pp.process(&mut ingest_ns, b"a")?.is_empty());
+        assert_eq!(vec![b"a\n b".to_vec()], pp.process(&mut ingest_ns, b" b")?);
+        assert!(pp.process(&mut ingest_ns, b" c")?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn timeout() -> Result<()> {
+        let mut pp = multiline("{continuation: '^ ', timeout_ms: 10}")?;
+        let mut ingest_ns = 0;
+        assert!(pp.process(&mut ingest_ns, b"a")?.is_empty());
+        ingest_ns = 20_000_000;
+        assert_eq!(vec![b"a".to_vec()], pp.process(&mut ingest_ns, b" b")?);
+        assert_eq!(vec![b" b".to_vec()], pp.process(&mut ingest_ns, b"c")?);
+        Ok(())
+    }
+
+    #[test]
+    fn end_of_stream() -> Result<()> {
+        let mut pp = Multiline::default();
+        let mut ingest_ns = 0;
+        assert!(pp.process(&mut ingest_ns, b"Traceback")?.is_empty());
+        assert!(pp.process(&mut ingest_ns, b"  snot.py")?.is_empty());
+        // the final event isn't followed by another line
+        assert_eq!(
+            vec![b"Traceback\n  snot.py".to_vec()],
+            pp.finish(ingest_ns)?
+        );
+        assert!(pp.finish(ingest_ns)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn tick() -> Result<()> {
+        let mut pp = multiline("{continuation: '^ ', timeout_ms: 10}")?;
+        let mut ingest_ns = 0;
+        assert!(pp.process(&mut ingest_ns, b"a")?.is_empty());
+        assert!(pp.process(&mut ingest_ns, b" b")?.is_empty());
+        assert!(pp.on_tick(5_000_000)?.is_empty());
+        assert_eq!(vec![b"a\n b".to_vec()], pp.on_tick(20_000_000)?);
+        assert!(pp.on_tick(40_000_000)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn bad_config() {
+        assert!(multiline("{separator: ' '}").is_err());
+        assert!(multiline("{start: '('}").is_err());
+        assert!(multiline("{start: '^a', max_lines: 0}").is_err());
+        assert!(multiline("{snot: badger}").is_err());
+    }
+}
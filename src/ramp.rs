@@ -22,6 +22,7 @@ use std::path::Path;
 use tremor_pipeline::ConfigImpl;
 use tremor_script::prelude::*;
 
+pub mod mqtt;
 pub mod postgres;
 
 pub trait Kv {
@@ -0,0 +1,370 @@
+// Copyright 2020-2021, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Connections to MQTT brokers shared by the MQTT onramp and offramp.
+//!
+//! MQTT 3.1.1 is spoken by `rumqttc`, MQTT 5 by the client in `v5`. `Client`
+//! and `EventLoop` wrap both so the ramps don't care which one is used.
+
+pub mod v5;
+
+use crate::errors::{Error, Result};
+use async_std::fs;
+use async_tls::TlsConnector;
+use rumqttc::{
+    AsyncClient, Event as MqttEvent, Key, MqttOptions, Outgoing, Packet, TlsConfiguration,
+    Transport,
+};
+use rustls::internal::pemfile::{certs, rsa_private_keys};
+use rustls::ClientConfig;
+use std::io::Cursor;
+use std::sync::Arc;
+use std::time::Duration;
+
+pub use rumqttc::QoS;
+
+const KEEP_ALIVE: Duration = Duration::from_secs(60);
+
+// MQTT protocol version spoken with the broker
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Protocol {
+    // MQTT 3.1.1
+    V3,
+    // MQTT 5
+    V5,
+}
+
+impl Default for Protocol {
+    fn default() -> Self {
+        Self::V3
+    }
+}
+
+// TLS settings for connecting to the broker
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct TlsConfig {
+    // CA certificate (PEM) used to verify the broker
+    pub ca_file: String,
+    // client certificate (PEM) for mutual TLS
+    #[serde(default = "Default::default")]
+    pub cert_file: Option<String>,
+    // RSA private key (PEM) of the client certificate
+    #[serde(default = "Default::default")]
+    pub key_file: Option<String>,
+}
+
+// options to use when opening a new connection
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct ConnectOptions {
+    // broker host
+    #[serde(default = "default_host")]
+    pub host: String,
+    // broker port
+    #[serde(default = "default_port")]
+    pub port: u16,
+    // protocol version, `v3` (3.1.1) or `v5`
+    #[serde(default = "Default::default")]
+    pub protocol: Protocol,
+    // client id, generated from the hostname if not set
+    #[serde(default = "Default::default")]
+    pub client_id: Option<String>,
+    #[serde(default = "Default::default")]
+    pub username: Option<String>,
+    #[serde(default = "Default::default")]
+    pub password: Option<String>,
+    // start with a fresh session instead of resuming the one stored on the broker
+    #[serde(default = "default_clean_session")]
+    pub clean_session: bool,
+    #[serde(default = "Default::default")]
+    pub tls: Option<TlsConfig>,
+}
+
+fn default_host() -> String {
+    "localhost".to_string()
+}
+
+fn default_port() -> u16 {
+    1883
+}
+
+fn default_clean_session() -> bool {
+    true
+}
+
+pub(crate) fn default_qos() -> u8 {
+    1
+}
+
+/// Turns a numeric QoS level into its MQTT representation
+pub(crate) fn qos(level: u8) -> Result<QoS> {
+    match level {
+        0 => Ok(QoS::AtMostOnce),
+        1 => Ok(QoS::AtLeastOnce),
+        2 => Ok(QoS::ExactlyOnce),
+        other => Err(format!("Invalid MQTT QoS level: {}", other).into()),
+    }
+}
+
+fn client_error<E: std::fmt::Display>(e: E) -> Error {
+    Error::from(format!("MQTT client error: {}", e))
+}
+
+async fn tls_connector(tls: &TlsConfig) -> Result<TlsConnector> {
+    let mut config = ClientConfig::new();
+    let mut ca = Cursor::new(fs::read(&tls.ca_file).await?);
+    config
+        .root_store
+        .add_pem_file(&mut ca)
+        .map_err(|_e| Error::from(format!("Invalid certificate in {}", tls.ca_file)))?;
+    if let Some((cert_file, key_file)) = tls.cert_file.as_ref().zip(tls.key_file.as_ref()) {
+        let mut cert = Cursor::new(fs::read(cert_file).await?);
+        let cert_chain = certs(&mut cert)
+            .map_err(|_e| Error::from(format!("Invalid certificate in {}", cert_file)))?;
+        let mut key = Cursor::new(fs::read(key_file).await?);
+        let key = rsa_private_keys(&mut key)
+            .ok()
+            .and_then(|keys| keys.into_iter().next())
+            .ok_or_else(|| Error::from(format!("No RSA private key in {}", key_file)))?;
+        config
+            .set_single_client_cert(cert_chain, key)
+            .map_err(client_error)?;
+    }
+    Ok(TlsConnector::from(Arc::new(config)))
+}
+
+impl ConnectOptions {
+    /// Creates a client and the event loop driving its connection, which is
+    /// opened by the first poll. With `manual_acks` received messages are only
+    /// acknowledged via `Client::try_ack`.
+    pub async fn connect(
+        &self,
+        default_client_id: &str,
+        manual_acks: bool,
+    ) -> Result<(Client, EventLoop)> {
+        let client_id = self
+            .client_id
+            .clone()
+            .unwrap_or_else(|| default_client_id.to_string());
+        let credentials = self.username.clone().zip(self.password.clone());
+        match self.protocol {
+            Protocol::V3 => {
+                let mut options = MqttOptions::new(client_id, self.host.clone(), self.port);
+                options.set_clean_session(self.clean_session);
+                options.set_manual_acks(manual_acks);
+                if let Some((username, password)) = credentials {
+                    options.set_credentials(username, password);
+                }
+                if let Some(tls) = &self.tls {
+                    let ca = fs::read(&tls.ca_file).await?;
+                    let client_auth = if let Some((cert_file, key_file)) =
+                        tls.cert_file.as_ref().zip(tls.key_file.as_ref())
+                    {
+                        Some((
+                            fs::read(cert_file).await?,
+                            Key::RSA(fs::read(key_file).await?),
+                        ))
+                    } else {
+                        None
+                    };
+                    options.set_transport(Transport::Tls(TlsConfiguration::Simple {
+                        ca,
+                        alpn: None,
+                        client_auth,
+                    }));
+                }
+                let (client, eventloop) = AsyncClient::new(options, crate::QSIZE);
+                Ok((Client::V3(client), EventLoop::V3(Box::new(eventloop))))
+            }
+            Protocol::V5 => {
+                let tls = if let Some(tls) = &self.tls {
+                    Some(tls_connector(tls).await?)
+                } else {
+                    None
+                };
+                let options = v5::Options {
+                    host: self.host.clone(),
+                    port: self.port,
+                    client_id,
+                    clean_start: self.clean_session,
+                    credentials,
+                    tls,
+                    keep_alive: KEEP_ALIVE,
+                    manual_acks,
+                };
+                let (client, eventloop) = v5::Client::new(options, crate::QSIZE);
+                Ok((Client::V5(client), EventLoop::V5(eventloop)))
+            }
+        }
+    }
+}
+
+/// A message received from the broker
+#[derive(Clone, Debug, PartialEq)]
+pub struct Message {
+    pub topic: String,
+    pub qos: QoS,
+    pub retain: bool,
+    pub dup: bool,
+    /// needed to acknowledge the message, 0 for QoS 0
+    pub pkid: u16,
+    pub payload: Vec<u8>,
+    /// publish properties, only sent with MQTT 5
+    pub properties: Option<v5::Properties>,
+}
+
+impl From<rumqttc::Publish> for Message {
+    fn from(publish: rumqttc::Publish) -> Self {
+        Self {
+            topic: publish.topic,
+            qos: publish.qos,
+            retain: publish.retain,
+            dup: publish.dup,
+            pkid: publish.pkid,
+            payload: publish.payload.to_vec(),
+            properties: None,
+        }
+    }
+}
+
+/// What happened on the connection
+#[derive(Debug)]
+pub enum Notification {
+    /// the broker accepted the connection
+    Connected {
+        session_present: bool,
+    },
+    /// a message was received
+    Message(Message),
+    /// a publish was sent with the given packet id, 0 for QoS 0
+    Sent(u16),
+    /// the broker acknowledged the publish (`PUBACK` for QoS 1, `PUBCOMP` for
+    /// QoS 2)
+    Acknowledged(u16),
+    /// the broker refused the publish with an error reason code (MQTT 5 only)
+    Rejected(u16),
+    Other,
+}
+
+#[derive(Clone)]
+pub enum Client {
+    V3(AsyncClient),
+    V5(v5::Client),
+}
+
+impl Client {
+    /// Hands a message to the event loop, `properties` are only sent with MQTT 5
+    pub async fn publish(
+        &self,
+        topic: String,
+        qos: QoS,
+        retain: bool,
+        payload: Vec<u8>,
+        properties: v5::Properties,
+    ) -> Result<()> {
+        match self {
+            Self::V3(client) => client
+                .publish(topic, qos, retain, payload)
+                .await
+                .map_err(client_error),
+            Self::V5(client) => {
+                client
+                    .publish(topic, qos, retain, payload, properties)
+                    .await
+            }
+        }
+    }
+
+    pub fn try_subscribe(&self, filter: &str, qos: QoS) -> Result<()> {
+        match self {
+            Self::V3(client) => client.try_subscribe(filter, qos).map_err(client_error),
+            Self::V5(client) => client.try_subscribe(filter, qos),
+        }
+    }
+
+    /// Acknowledges a message received with manual acks
+    pub fn try_ack(&self, message: &Message) -> Result<()> {
+        match self {
+            Self::V3(client) => {
+                let mut publish = rumqttc::Publish::new(&message.topic, message.qos, Vec::new());
+                publish.pkid = message.pkid;
+                client.try_ack(&publish).map_err(client_error)
+            }
+            Self::V5(client) => client.try_ack(message),
+        }
+    }
+
+    pub async fn disconnect(&self) -> Result<()> {
+        match self {
+            Self::V3(client) => client.disconnect().await.map_err(client_error),
+            Self::V5(client) => client.disconnect().await,
+        }
+    }
+}
+
+pub enum EventLoop {
+    V3(Box<rumqttc::EventLoop>),
+    V5(v5::EventLoop),
+}
+
+impl EventLoop {
+    /// Drives the connection until something happens, connecting again when
+    /// polled after an error
+    pub async fn poll(&mut self) -> Result<Notification> {
+        match self {
+            Self::V3(eventloop) => Ok(match eventloop.poll().await.map_err(client_error)? {
+                MqttEvent::Incoming(Packet::ConnAck(ack)) => Notification::Connected {
+                    session_present: ack.session_present,
+                },
+                MqttEvent::Incoming(Packet::Publish(publish)) => {
+                    Notification::Message(publish.into())
+                }
+                MqttEvent::Incoming(Packet::PubAck(ack)) => Notification::Acknowledged(ack.pkid),
+                MqttEvent::Incoming(Packet::PubComp(comp)) => Notification::Acknowledged(comp.pkid),
+                MqttEvent::Outgoing(Outgoing::Publish(pkid)) => Notification::Sent(pkid),
+                _ => Notification::Other,
+            }),
+            Self::V5(eventloop) => eventloop.poll().await,
+        }
+    }
+
+    /// If publishes the broker didn't acknowledge are sent again after a
+    /// reconnect, only `rumqttc` does
+    pub fn resends(&self) -> bool {
+        matches!(self, Self::V3(_))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn protocol() -> Result<()> {
+        let options: ConnectOptions = serde_yaml::from_str("{}")?;
+        assert_eq!(Protocol::V3, options.protocol);
+        let options: ConnectOptions = serde_yaml::from_str("protocol: v5")?;
+        assert_eq!(Protocol::V5, options.protocol);
+        assert!(serde_yaml::from_str::<ConnectOptions>("protocol: v4").is_err());
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn missing_ca_file() {
+        let options: ConnectOptions =
+            serde_yaml::from_str("{protocol: v5, tls: {ca_file: /does/not/exist.pem}}")
+                .expect("valid yaml");
+        assert!(options.connect("tremor-test", false).await.is_err());
+    }
+}
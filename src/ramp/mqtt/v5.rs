@@ -0,0 +1,1017 @@
+// Copyright 2020-2021, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A MQTT 5 client covering what the MQTT ramps need: connecting, subscribing,
+//! publishing and acknowledging with QoS 0, 1 and 2, and publish properties.
+//!
+//! `rumqttc` only speaks MQTT 3.1.1 in the versions our toolchain builds, so
+//! the packets are encoded here. Like with `rumqttc` the client hands requests
+//! to an event loop that drives the connection and reconnects when polled
+//! after an error. Publishes the broker didn't acknowledge before the
+//! connection dropped are not sent again, their events are failed instead.
+
+use super::{Message, Notification, QoS};
+use crate::errors::{Error, Result};
+use async_channel::{bounded, Receiver, Sender};
+use async_std::future::timeout;
+use async_std::io::{ReadExt, WriteExt};
+use async_std::net::TcpStream;
+use async_tls::client::TlsStream;
+use async_tls::TlsConnector;
+use bytes::{Buf, BufMut, BytesMut};
+use futures::future::{select, Either};
+use futures::pin_mut;
+use std::collections::HashSet;
+use std::convert::TryFrom;
+use std::time::{Duration, Instant};
+use tremor_script::prelude::*;
+
+/// largest packet we accept from the broker
+const MAX_PACKET: usize = 256 * 1024 * 1024;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Properties of a publish
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Properties {
+    pub payload_format_indicator: Option<u8>,
+    pub message_expiry_interval: Option<u32>,
+    pub content_type: Option<String>,
+    pub response_topic: Option<String>,
+    pub correlation_data: Option<Vec<u8>>,
+    pub user_properties: Vec<(String, String)>,
+    /// set by the broker on publishes matching subscriptions with identifiers
+    pub subscription_identifiers: Vec<u32>,
+}
+
+impl Properties {
+    /// The properties as `$mqtt.properties` metadata, user properties are a
+    /// list of `[name, value]` pairs as names may repeat
+    pub fn to_value(&self) -> Value<'static> {
+        let mut properties = Object::with_capacity(7);
+        if let Some(indicator) = self.payload_format_indicator {
+            properties.insert("payload_format_indicator".into(), indicator.into());
+        }
+        if let Some(interval) = self.message_expiry_interval {
+            properties.insert("message_expiry_interval".into(), interval.into());
+        }
+        if let Some(content_type) = &self.content_type {
+            properties.insert("content_type".into(), content_type.clone().into());
+        }
+        if let Some(topic) = &self.response_topic {
+            properties.insert("response_topic".into(), topic.clone().into());
+        }
+        if let Some(data) = &self.correlation_data {
+            properties.insert("correlation_data".into(), Value::Bytes(data.clone().into()));
+        }
+        if !self.user_properties.is_empty() {
+            let pairs: Vec<Value<'static>> = self
+                .user_properties
+                .iter()
+                .map(|(name, value)| Value::from(vec![name.clone(), value.clone()]))
+                .collect();
+            properties.insert("user_properties".into(), pairs.into());
+        }
+        if !self.subscription_identifiers.is_empty() {
+            properties.insert(
+                "subscription_identifiers".into(),
+                self.subscription_identifiers.clone().into(),
+            );
+        }
+        Value::from(properties)
+    }
+
+    /// Reads the properties to publish with from `$mqtt.properties`
+    ///
+    /// # Errors
+    ///  * if a property has the wrong type
+    pub fn from_value(v: &Value) -> Result<Self> {
+        let invalid = |name: &str| Error::from(format!("Invalid MQTT property `{}`", name));
+        let mut properties = Self::default();
+        if let Some(indicator) = v.get("payload_format_indicator") {
+            properties.payload_format_indicator = Some(
+                indicator
+                    .as_u8()
+                    .ok_or_else(|| invalid("payload_format_indicator"))?,
+            );
+        }
+        if let Some(interval) = v.get("message_expiry_interval") {
+            properties.message_expiry_interval = Some(
+                interval
+                    .as_u32()
+                    .ok_or_else(|| invalid("message_expiry_interval"))?,
+            );
+        }
+        if let Some(content_type) = v.get("content_type") {
+            properties.content_type = Some(
+                content_type
+                    .as_str()
+                    .ok_or_else(|| invalid("content_type"))?
+                    .to_string(),
+            );
+        }
+        if let Some(topic) = v.get("response_topic") {
+            properties.response_topic = Some(
+                topic
+                    .as_str()
+                    .ok_or_else(|| invalid("response_topic"))?
+                    .to_string(),
+            );
+        }
+        if let Some(data) = v.get("correlation_data") {
+            properties.correlation_data = Some(match data {
+                Value::Bytes(bytes) => bytes.to_vec(),
+                other => other
+                    .as_str()
+                    .ok_or_else(|| invalid("correlation_data"))?
+                    .as_bytes()
+                    .to_vec(),
+            });
+        }
+        if let Some(pairs) = v.get("user_properties") {
+            for pair in pairs.as_array().ok_or_else(|| invalid("user_properties"))? {
+                let (name, value) = pair
+                    .as_array()
+                    .and_then(|p| Some((p.get(0)?.as_str()?, p.get(1)?.as_str()?)))
+                    .ok_or_else(|| invalid("user_properties"))?;
+                properties
+                    .user_properties
+                    .push((name.to_string(), value.to_string()));
+            }
+        }
+        Ok(properties)
+    }
+}
+
+/// A publish packet
+#[derive(Clone, Debug, PartialEq)]
+pub struct Publish {
+    pub dup: bool,
+    pub qos: QoS,
+    pub retain: bool,
+    pub topic: String,
+    /// 0 for QoS 0
+    pub pkid: u16,
+    pub properties: Properties,
+    pub payload: Vec<u8>,
+}
+
+impl From<Publish> for Message {
+    fn from(publish: Publish) -> Self {
+        Self {
+            topic: publish.topic,
+            qos: publish.qos,
+            retain: publish.retain,
+            dup: publish.dup,
+            pkid: publish.pkid,
+            payload: publish.payload,
+            properties: Some(publish.properties),
+        }
+    }
+}
+
+/// The packets we receive
+#[derive(Debug, PartialEq)]
+enum Packet {
+    ConnAck { session_present: bool, reason: u8 },
+    Publish(Publish),
+    PubAck { pkid: u16, reason: u8 },
+    PubRec { pkid: u16, reason: u8 },
+    PubRel { pkid: u16 },
+    PubComp { pkid: u16 },
+    SubAck { pkid: u16, reasons: Vec<u8> },
+    PingResp,
+    Disconnect { reason: u8 },
+}
+
+/// Connection settings
+#[derive(Clone)]
+pub struct Options {
+    pub host: String,
+    pub port: u16,
+    pub client_id: String,
+    pub clean_start: bool,
+    pub credentials: Option<(String, String)>,
+    pub tls: Option<TlsConnector>,
+    pub keep_alive: Duration,
+    /// if received messages are only acknowledged by `Client::try_ack`
+    pub manual_acks: bool,
+}
+
+enum Request {
+    Publish(Publish),
+    Subscribe(String, QoS),
+    Ack(QoS, u16),
+    Disconnect,
+}
+
+/// Hands requests to the event loop
+#[derive(Clone)]
+pub struct Client {
+    tx: Sender<Request>,
+}
+
+impl Client {
+    pub fn new(options: Options, cap: usize) -> (Self, EventLoop) {
+        let (tx, rx) = bounded(cap);
+        let eventloop = EventLoop {
+            options,
+            requests: rx,
+            connection: None,
+            inflight: HashSet::new(),
+            last_pkid: 0,
+        };
+        (Self { tx }, eventloop)
+    }
+
+    pub async fn publish(
+        &self,
+        topic: String,
+        qos: QoS,
+        retain: bool,
+        payload: Vec<u8>,
+        properties: Properties,
+    ) -> Result<()> {
+        let publish = Publish {
+            dup: false,
+            qos,
+            retain,
+            topic,
+            pkid: 0,
+            properties,
+            payload,
+        };
+        Ok(self.tx.send(Request::Publish(publish)).await?)
+    }
+
+    pub fn try_subscribe(&self, filter: &str, qos: QoS) -> Result<()> {
+        Ok(self
+            .tx
+            .try_send(Request::Subscribe(filter.to_string(), qos))?)
+    }
+
+    pub fn try_ack(&self, message: &Message) -> Result<()> {
+        if message.qos == QoS::AtMostOnce {
+            return Ok(());
+        }
+        Ok(self.tx.try_send(Request::Ack(message.qos, message.pkid))?)
+    }
+
+    pub async fn disconnect(&self) -> Result<()> {
+        Ok(self.tx.send(Request::Disconnect).await?)
+    }
+}
+
+enum Stream {
+    Tcp(TcpStream),
+    Tls(Box<TlsStream<TcpStream>>),
+}
+
+impl Stream {
+    async fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            Self::Tcp(stream) => stream.read(buf).await,
+            Self::Tls(stream) => stream.read(buf).await,
+        }
+    }
+
+    async fn write_all(&mut self, buf: &[u8]) -> std::io::Result<()> {
+        match self {
+            Self::Tcp(stream) => {
+                stream.write_all(buf).await?;
+                stream.flush().await
+            }
+            Self::Tls(stream) => {
+                stream.write_all(buf).await?;
+                stream.flush().await
+            }
+        }
+    }
+}
+
+struct Connection {
+    stream: Stream,
+    buffer: BytesMut,
+    last_write: Instant,
+}
+
+/// What the event loop waits for
+enum Input {
+    Read(usize),
+    Request(Option<Request>),
+    Idle,
+}
+
+impl Connection {
+    async fn open(options: &Options) -> Result<(Self, bool)> {
+        let tcp = TcpStream::connect((options.host.as_str(), options.port)).await?;
+        let stream = if let Some(tls) = &options.tls {
+            Stream::Tls(Box::new(tls.connect(&options.host, tcp).await?))
+        } else {
+            Stream::Tcp(tcp)
+        };
+        let mut connection = Self {
+            stream,
+            buffer: BytesMut::new(),
+            last_write: Instant::now(),
+        };
+        connection.write(&encode_connect(options)?).await?;
+        match timeout(CONNECT_TIMEOUT, connection.next_packet()).await {
+            Ok(Ok(Packet::ConnAck {
+                session_present,
+                reason,
+            })) => {
+                if reason < 0x80 {
+                    Ok((connection, session_present))
+                } else {
+                    Err(format!(
+                        "MQTT broker refused the connection, reason code {:#04x}",
+                        reason
+                    )
+                    .into())
+                }
+            }
+            Ok(Ok(packet)) => Err(format!("Expected CONNACK, got {:?}", packet).into()),
+            Ok(Err(e)) => Err(e),
+            Err(_) => Err("MQTT broker didn't answer the connect in time".into()),
+        }
+    }
+
+    async fn write(&mut self, packet: &[u8]) -> Result<()> {
+        self.stream.write_all(packet).await?;
+        self.last_write = Instant::now();
+        Ok(())
+    }
+
+    async fn next_packet(&mut self) -> Result<Packet> {
+        loop {
+            if let Some(packet) = decode(&mut self.buffer)? {
+                return Ok(packet);
+            }
+            let mut chunk = [0_u8; 4096];
+            let read = self.stream.read(&mut chunk).await?;
+            if read == 0 {
+                return Err("MQTT broker closed the connection".into());
+            }
+            self.buffer.extend_from_slice(&chunk[..read]);
+        }
+    }
+}
+
+/// Drives the connection
+pub struct EventLoop {
+    options: Options,
+    requests: Receiver<Request>,
+    connection: Option<Connection>,
+    /// packet ids of our publishes the broker didn't acknowledge yet
+    inflight: HashSet<u16>,
+    last_pkid: u16,
+}
+
+impl EventLoop {
+    /// Connects if needed and returns the next notification, the connection
+    /// is dropped on errors and opened again by the next poll
+    pub async fn poll(&mut self) -> Result<Notification> {
+        let res = self.next().await;
+        if res.is_err() {
+            self.connection = None;
+            self.inflight.clear();
+        }
+        res
+    }
+
+    async fn next(&mut self) -> Result<Notification> {
+        let Self {
+            options,
+            requests,
+            connection,
+            inflight,
+            last_pkid,
+        } = self;
+        let connection = if let Some(connection) = connection {
+            connection
+        } else {
+            let (opened, session_present) = Connection::open(options).await?;
+            *connection = Some(opened);
+            return Ok(Notification::Connected { session_present });
+        };
+        // pings keep the connection alive if nothing else was sent for half
+        // of the keep alive interval
+        let ping_interval = options.keep_alive / 2;
+        loop {
+            if let Some(packet) = decode(&mut connection.buffer)? {
+                return incoming(connection, options.manual_acks, inflight, packet).await;
+            }
+            let idle = connection.last_write.elapsed();
+            if idle >= ping_interval {
+                connection.write(&[0xC0, 0x00]).await?;
+                continue;
+            }
+            let mut chunk = [0_u8; 4096];
+            let input = {
+                let read = connection.stream.read(&mut chunk);
+                let request = requests.recv();
+                pin_mut!(read, request);
+                match timeout(ping_interval - idle, select(read, request)).await {
+                    Ok(Either::Left((read, _))) => Input::Read(read?),
+                    Ok(Either::Right((request, _))) => Input::Request(request.ok()),
+                    Err(_) => Input::Idle,
+                }
+            };
+            match input {
+                Input::Read(0) => return Err("MQTT broker closed the connection".into()),
+                Input::Read(read) => connection.buffer.extend_from_slice(&chunk[..read]),
+                Input::Request(Some(request)) => {
+                    return outgoing(connection, inflight, last_pkid, request).await;
+                }
+                Input::Request(None) => return Err("MQTT client was dropped".into()),
+                Input::Idle => (),
+            }
+        }
+    }
+}
+
+async fn incoming(
+    connection: &mut Connection,
+    manual_acks: bool,
+    inflight: &mut HashSet<u16>,
+    packet: Packet,
+) -> Result<Notification> {
+    match packet {
+        Packet::Publish(publish) => {
+            if !manual_acks && publish.qos != QoS::AtMostOnce {
+                connection
+                    .write(&encode_ack(publish.qos, publish.pkid))
+                    .await?;
+            }
+            Ok(Notification::Message(publish.into()))
+        }
+        Packet::PubAck { pkid, reason } => {
+            inflight.remove(&pkid);
+            if reason < 0x80 {
+                Ok(Notification::Acknowledged(pkid))
+            } else {
+                Ok(Notification::Rejected(pkid))
+            }
+        }
+        Packet::PubRec { pkid, reason } => {
+            if reason < 0x80 {
+                connection.write(&encode_pkid(0x62, pkid)).await?;
+                Ok(Notification::Other)
+            } else {
+                inflight.remove(&pkid);
+                Ok(Notification::Rejected(pkid))
+            }
+        }
+        Packet::PubRel { pkid } => {
+            connection.write(&encode_pkid(0x70, pkid)).await?;
+            Ok(Notification::Other)
+        }
+        Packet::PubComp { pkid } => {
+            inflight.remove(&pkid);
+            Ok(Notification::Acknowledged(pkid))
+        }
+        Packet::SubAck { pkid, reasons } => {
+            if reasons.iter().any(|reason| *reason >= 0x80) {
+                Err(format!(
+                    "MQTT broker refused subscription {}, reason codes {:?}",
+                    pkid, reasons
+                )
+                .into())
+            } else {
+                Ok(Notification::Other)
+            }
+        }
+        Packet::PingResp => Ok(Notification::Other),
+        Packet::Disconnect { reason } => {
+            Err(format!("MQTT broker disconnected, reason code {:#04x}", reason).into())
+        }
+        Packet::ConnAck { .. } => Err("Unexpected CONNACK from the MQTT broker".into()),
+    }
+}
+
+async fn outgoing(
+    connection: &mut Connection,
+    inflight: &mut HashSet<u16>,
+    last_pkid: &mut u16,
+    request: Request,
+) -> Result<Notification> {
+    match request {
+        Request::Publish(mut publish) => {
+            if publish.qos != QoS::AtMostOnce {
+                publish.pkid = next_pkid(last_pkid, inflight);
+                inflight.insert(publish.pkid);
+            }
+            connection.write(&encode_publish(&publish)?).await?;
+            Ok(Notification::Sent(publish.pkid))
+        }
+        Request::Subscribe(filter, qos) => {
+            let pkid = next_pkid(last_pkid, inflight);
+            connection
+                .write(&encode_subscribe(pkid, &filter, qos)?)
+                .await?;
+            Ok(Notification::Other)
+        }
+        Request::Ack(qos, pkid) => {
+            connection.write(&encode_ack(qos, pkid)).await?;
+            Ok(Notification::Other)
+        }
+        Request::Disconnect => {
+            connection.write(&[0xE0, 0x00]).await?;
+            Err("Disconnected from the MQTT broker".into())
+        }
+    }
+}
+
+/// The next free packet id, 0 is reserved for QoS 0
+fn next_pkid(last: &mut u16, inflight: &HashSet<u16>) -> u16 {
+    loop {
+        *last = last.checked_add(1).unwrap_or(1);
+        if !inflight.contains(last) {
+            return *last;
+        }
+    }
+}
+
+fn qos_level(qos: QoS) -> u8 {
+    match qos {
+        QoS::AtMostOnce => 0,
+        QoS::AtLeastOnce => 1,
+        QoS::ExactlyOnce => 2,
+    }
+}
+
+fn len_u16(len: usize) -> Result<u16> {
+    u16::try_from(len).map_err(|_| Error::from("MQTT string or binary data too long"))
+}
+
+fn put_remaining_length(buf: &mut BytesMut, mut len: usize) {
+    loop {
+        // ALLOW: `len % 128` fits into a byte
+        #[allow(clippy::cast_possible_truncation)]
+        let mut byte = (len % 128) as u8;
+        len /= 128;
+        if len > 0 {
+            byte |= 0x80;
+        }
+        buf.put_u8(byte);
+        if len == 0 {
+            break;
+        }
+    }
+}
+
+fn put_str(buf: &mut BytesMut, s: &str) -> Result<()> {
+    put_bytes(buf, s.as_bytes())
+}
+
+fn put_bytes(buf: &mut BytesMut, data: &[u8]) -> Result<()> {
+    buf.put_u16(len_u16(data.len())?);
+    buf.put_slice(data);
+    Ok(())
+}
+
+/// Prepends the fixed header to the rest of a packet
+fn packet(header: u8, rest: &[u8]) -> Vec<u8> {
+    let mut buf = BytesMut::with_capacity(rest.len() + 5);
+    buf.put_u8(header);
+    put_remaining_length(&mut buf, rest.len());
+    buf.put_slice(rest);
+    buf.to_vec()
+}
+
+fn encode_connect(options: &Options) -> Result<Vec<u8>> {
+    let mut rest = BytesMut::new();
+    put_str(&mut rest, "MQTT")?;
+    rest.put_u8(5);
+    let mut flags = 0;
+    if options.clean_start {
+        flags |= 0x02;
+    }
+    if options.credentials.is_some() {
+        flags |= 0xC0;
+    }
+    rest.put_u8(flags);
+    rest.put_u16(u16::try_from(options.keep_alive.as_secs()).unwrap_or(u16::MAX));
+    // no connect properties
+    rest.put_u8(0);
+    put_str(&mut rest, &options.client_id)?;
+    if let Some((username, password)) = &options.credentials {
+        put_str(&mut rest, username)?;
+        put_str(&mut rest, password)?;
+    }
+    Ok(packet(0x10, &rest))
+}
+
+fn encode_properties(properties: &Properties) -> Result<BytesMut> {
+    let mut buf = BytesMut::new();
+    if let Some(indicator) = properties.payload_format_indicator {
+        buf.put_u8(0x01);
+        buf.put_u8(indicator);
+    }
+    if let Some(interval) = properties.message_expiry_interval {
+        buf.put_u8(0x02);
+        buf.put_u32(interval);
+    }
+    if let Some(content_type) = &properties.content_type {
+        buf.put_u8(0x03);
+        put_str(&mut buf, content_type)?;
+    }
+    if let Some(topic) = &properties.response_topic {
+        buf.put_u8(0x08);
+        put_str(&mut buf, topic)?;
+    }
+    if let Some(data) = &properties.correlation_data {
+        buf.put_u8(0x09);
+        put_bytes(&mut buf, data)?;
+    }
+    for (name, value) in &properties.user_properties {
+        buf.put_u8(0x26);
+        put_str(&mut buf, name)?;
+        put_str(&mut buf, value)?;
+    }
+    Ok(buf)
+}
+
+fn encode_publish(publish: &Publish) -> Result<Vec<u8>> {
+    let mut rest = BytesMut::with_capacity(publish.topic.len() + publish.payload.len() + 16);
+    put_str(&mut rest, &publish.topic)?;
+    if publish.qos != QoS::AtMostOnce {
+        rest.put_u16(publish.pkid);
+    }
+    let properties = encode_properties(&publish.properties)?;
+    put_remaining_length(&mut rest, properties.len());
+    rest.put_slice(&properties);
+    rest.put_slice(&publish.payload);
+    let mut header = 0x30 | (qos_level(publish.qos) << 1);
+    if publish.dup {
+        header |= 0x08;
+    }
+    if publish.retain {
+        header |= 0x01;
+    }
+    Ok(packet(header, &rest))
+}
+
+fn encode_subscribe(pkid: u16, filter: &str, qos: QoS) -> Result<Vec<u8>> {
+    let mut rest = BytesMut::new();
+    rest.put_u16(pkid);
+    // no subscribe properties
+    rest.put_u8(0);
+    put_str(&mut rest, filter)?;
+    rest.put_u8(qos_level(qos));
+    Ok(packet(0x82, &rest))
+}
+
+/// A packet carrying just a packet id, the reason code defaults to success
+fn encode_pkid(header: u8, pkid: u16) -> Vec<u8> {
+    let [high, low] = pkid.to_be_bytes();
+    vec![header, 0x02, high, low]
+}
+
+/// Acknowledges a received publish with `PUBACK` for QoS 1 or `PUBREC` for QoS 2
+fn encode_ack(qos: QoS, pkid: u16) -> Vec<u8> {
+    if qos == QoS::ExactlyOnce {
+        encode_pkid(0x50, pkid)
+    } else {
+        encode_pkid(0x40, pkid)
+    }
+}
+
+fn malformed(what: &str) -> Error {
+    Error::from(format!("Malformed MQTT packet: {}", what))
+}
+
+fn get_u8(buf: &mut BytesMut) -> Result<u8> {
+    if buf.has_remaining() {
+        Ok(buf.get_u8())
+    } else {
+        Err(malformed("truncated"))
+    }
+}
+
+fn get_u16(buf: &mut BytesMut) -> Result<u16> {
+    if buf.remaining() >= 2 {
+        Ok(buf.get_u16())
+    } else {
+        Err(malformed("truncated"))
+    }
+}
+
+fn get_u32(buf: &mut BytesMut) -> Result<u32> {
+    if buf.remaining() >= 4 {
+        Ok(buf.get_u32())
+    } else {
+        Err(malformed("truncated"))
+    }
+}
+
+fn get_bytes(buf: &mut BytesMut) -> Result<Vec<u8>> {
+    let len = usize::from(get_u16(buf)?);
+    if buf.remaining() >= len {
+        Ok(buf.split_to(len).to_vec())
+    } else {
+        Err(malformed("truncated"))
+    }
+}
+
+fn get_str(buf: &mut BytesMut) -> Result<String> {
+    String::from_utf8(get_bytes(buf)?).map_err(|_| malformed("invalid UTF-8"))
+}
+
+/// Reads a variable byte integer, `None` if `buf` doesn't hold all of it yet
+fn get_var_int(buf: &[u8]) -> Result<Option<(usize, usize)>> {
+    let mut value = 0;
+    for (i, byte) in buf.iter().enumerate().take(4) {
+        value += usize::from(byte & 0x7F) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Ok(Some((value, i + 1)));
+        }
+    }
+    if buf.len() >= 4 {
+        Err(malformed("variable byte integer too long"))
+    } else {
+        Ok(None)
+    }
+}
+
+fn get_properties(buf: &mut BytesMut) -> Result<BytesMut> {
+    let (len, len_bytes) = get_var_int(buf)?.ok_or_else(|| malformed("truncated"))?;
+    buf.advance(len_bytes);
+    if buf.remaining() >= len {
+        Ok(buf.split_to(len))
+    } else {
+        Err(malformed("truncated"))
+    }
+}
+
+fn decode_publish_properties(mut buf: BytesMut) -> Result<Properties> {
+    let mut properties = Properties::default();
+    while buf.has_remaining() {
+        match get_u8(&mut buf)? {
+            0x01 => properties.payload_format_indicator = Some(get_u8(&mut buf)?),
+            0x02 => properties.message_expiry_interval = Some(get_u32(&mut buf)?),
+            0x03 => properties.content_type = Some(get_str(&mut buf)?),
+            0x08 => properties.response_topic = Some(get_str(&mut buf)?),
+            0x09 => properties.correlation_data = Some(get_bytes(&mut buf)?),
+            0x0B => {
+                let (id, len) = get_var_int(&buf)?.ok_or_else(|| malformed("truncated"))?;
+                buf.advance(len);
+                properties
+                    .subscription_identifiers
+                    .push(u32::try_from(id).map_err(|_| malformed("subscription identifier"))?);
+            }
+            // topic aliases are never used as we don't allow them on connect
+            0x23 => {
+                get_u16(&mut buf)?;
+            }
+            0x26 => {
+                let name = get_str(&mut buf)?;
+                let value = get_str(&mut buf)?;
+                properties.user_properties.push((name, value));
+            }
+            other => {
+                return Err(malformed(&format!(
+                    "unknown publish property {:#04x}",
+                    other
+                )));
+            }
+        }
+    }
+    Ok(properties)
+}
+
+/// Packet id and reason code of an acknowledgement, the reason code may be
+/// left out for success
+fn decode_ack(buf: &mut BytesMut) -> Result<(u16, u8)> {
+    let pkid = get_u16(buf)?;
+    let reason = if buf.has_remaining() { get_u8(buf)? } else { 0 };
+    Ok((pkid, reason))
+}
+
+/// Takes the next complete packet off `buf`, `None` if it isn't complete yet
+fn decode(buf: &mut BytesMut) -> Result<Option<Packet>> {
+    if buf.len() < 2 {
+        return Ok(None);
+    }
+    let (len, len_bytes) = if let Some(remaining) = get_var_int(&buf[1..])? {
+        remaining
+    } else {
+        return Ok(None);
+    };
+    if len > MAX_PACKET {
+        return Err(malformed("packet too large"));
+    }
+    if buf.len() < 1 + len_bytes + len {
+        return Ok(None);
+    }
+    let header = buf.get_u8();
+    buf.advance(len_bytes);
+    let mut rest = buf.split_to(len);
+    let packet = match header >> 4 {
+        2 => {
+            let session_present = get_u8(&mut rest)? & 0x01 == 0x01;
+            let reason = get_u8(&mut rest)?;
+            Packet::ConnAck {
+                session_present,
+                reason,
+            }
+        }
+        3 => {
+            let qos = match (header >> 1) & 0x03 {
+                0 => QoS::AtMostOnce,
+                1 => QoS::AtLeastOnce,
+                2 => QoS::ExactlyOnce,
+                _ => return Err(malformed("invalid QoS")),
+            };
+            let topic = get_str(&mut rest)?;
+            let pkid = if qos == QoS::AtMostOnce {
+                0
+            } else {
+                get_u16(&mut rest)?
+            };
+            let properties = decode_publish_properties(get_properties(&mut rest)?)?;
+            Packet::Publish(Publish {
+                dup: header & 0x08 == 0x08,
+                qos,
+                retain: header & 0x01 == 0x01,
+                topic,
+                pkid,
+                properties,
+                payload: rest.to_vec(),
+            })
+        }
+        4 => {
+            let (pkid, reason) = decode_ack(&mut rest)?;
+            Packet::PubAck { pkid, reason }
+        }
+        5 => {
+            let (pkid, reason) = decode_ack(&mut rest)?;
+            Packet::PubRec { pkid, reason }
+        }
+        6 => Packet::PubRel {
+            pkid: decode_ack(&mut rest)?.0,
+        },
+        7 => Packet::PubComp {
+            pkid: decode_ack(&mut rest)?.0,
+        },
+        9 => {
+            let pkid = get_u16(&mut rest)?;
+            get_properties(&mut rest)?;
+            Packet::SubAck {
+                pkid,
+                reasons: rest.to_vec(),
+            }
+        }
+        13 => Packet::PingResp,
+        14 => Packet::Disconnect {
+            reason: if rest.has_remaining() {
+                get_u8(&mut rest)?
+            } else {
+                0
+            },
+        },
+        other => return Err(malformed(&format!("unexpected packet type {}", other))),
+    };
+    Ok(Some(packet))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn publish(qos: QoS, properties: Properties) -> Publish {
+        Publish {
+            dup: false,
+            qos,
+            retain: true,
+            topic: "snot/badger".to_string(),
+            pkid: if qos == QoS::AtMostOnce { 0 } else { 42 },
+            properties,
+            payload: b"snot".to_vec(),
+        }
+    }
+
+    fn properties() -> Properties {
+        Properties {
+            payload_format_indicator: Some(1),
+            message_expiry_interval: Some(60),
+            content_type: Some("application/json".to_string()),
+            response_topic: Some("snot/reply".to_string()),
+            correlation_data: Some(b"badger".to_vec()),
+            user_properties: vec![
+                ("tenant".to_string(), "snot".to_string()),
+                ("tenant".to_string(), "badger".to_string()),
+            ],
+            subscription_identifiers: vec![],
+        }
+    }
+
+    #[test]
+    fn publish_round_trip() -> Result<()> {
+        for qos in &[QoS::AtMostOnce, QoS::AtLeastOnce, QoS::ExactlyOnce] {
+            for properties in vec![Properties::default(), properties()] {
+                let sent = publish(*qos, properties);
+                let mut buf = BytesMut::from(encode_publish(&sent)?.as_slice());
+                assert_eq!(Some(Packet::Publish(sent)), decode(&mut buf)?);
+                assert!(buf.is_empty());
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn partial_packets() -> Result<()> {
+        let encoded = encode_publish(&publish(QoS::AtLeastOnce, properties()))?;
+        let mut buf = BytesMut::new();
+        for byte in &encoded[..encoded.len() - 1] {
+            buf.put_u8(*byte);
+            assert_eq!(None, decode(&mut buf)?);
+        }
+        buf.put_u8(encoded[encoded.len() - 1]);
+        assert!(matches!(decode(&mut buf)?, Some(Packet::Publish(_))));
+
+        // two packets at once, an ack without a reason code means success
+        let mut buf = BytesMut::from(&[0x40, 0x02, 0x00, 0x01, 0x50, 0x03, 0x00, 0x02, 0x87][..]);
+        assert_eq!(
+            Some(Packet::PubAck { pkid: 1, reason: 0 }),
+            decode(&mut buf)?
+        );
+        assert_eq!(
+            Some(Packet::PubRec {
+                pkid: 2,
+                reason: 0x87
+            }),
+            decode(&mut buf)?
+        );
+        assert_eq!(None, decode(&mut buf)?);
+        Ok(())
+    }
+
+    #[test]
+    fn remaining_length() -> Result<()> {
+        for len in &[0, 127, 128, 16_383, 16_384, 2_097_151, 2_097_152] {
+            let mut buf = BytesMut::new();
+            put_remaining_length(&mut buf, *len);
+            assert_eq!(Some((*len, buf.len())), get_var_int(&buf)?);
+        }
+        assert!(get_var_int(&[0xFF, 0xFF, 0xFF, 0xFF]).is_err());
+        assert_eq!(None, get_var_int(&[0xFF, 0xFF])?);
+        Ok(())
+    }
+
+    #[test]
+    fn connect_and_subscribe() -> Result<()> {
+        let options = Options {
+            host: "localhost".to_string(),
+            port: 1883,
+            client_id: "tremor".to_string(),
+            clean_start: true,
+            credentials: Some(("snot".to_string(), "badger".to_string())),
+            tls: None,
+            keep_alive: Duration::from_secs(60),
+            manual_acks: true,
+        };
+        let connect = encode_connect(&options)?;
+        assert_eq!(
+            &[0x10, 33, 0, 4, b'M', b'Q', b'T', b'T', 5, 0xC2, 0, 60, 0][..],
+            &connect[..13]
+        );
+        let subscribe = encode_subscribe(7, "snot/#", QoS::AtLeastOnce)?;
+        assert_eq!(
+            vec![0x82, 12, 0, 7, 0, 0, 6, b's', b'n', b'o', b't', b'/', b'#', 1],
+            subscribe
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn properties_as_values() -> Result<()> {
+        let properties = properties();
+        let value = properties.to_value();
+        assert_eq!(Some("application/json"), value.get_str("content_type"));
+        assert_eq!(properties, Properties::from_value(&value)?);
+        assert!(Properties::from_value(&literal!({"user_properties": ["snot"]})).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn pkids_skip_inflight() {
+        let mut last = u16::MAX - 1;
+        let mut inflight = HashSet::new();
+        inflight.insert(1);
+        assert_eq!(u16::MAX, next_pkid(&mut last, &inflight));
+        // 0 is reserved and 1 still inflight
+        assert_eq!(2, next_pkid(&mut last, &inflight));
+    }
+}
@@ -116,6 +116,21 @@ impl<A: Artefact> Repository<A> {
         }
     }
 
+    /// Replaces a published artefact, keeping its instances
+    pub fn update(&mut self, mut id: ArtefactId, artefact: A) -> Result<&A> {
+        id.trim_to_artefact();
+        match self.map.get_mut(&id) {
+            Some(w) if w.system => {
+                Err(ErrorKind::UnpublishFailedSystemArtefact(id.to_string()).into())
+            }
+            Some(w) => {
+                w.artefact = artefact;
+                Ok(&w.artefact)
+            }
+            None => Err(ErrorKind::ArtefactNotFound(id.to_string()).into()),
+        }
+    }
+
     /// Binds an artefact to a given servant
     pub fn bind(&mut self, mut id: ArtefactId, mut sid: ServantId) -> Result<&A> {
         id.trim_to_artefact();
@@ -151,6 +166,7 @@ pub(crate) enum Msg<A: Artefact> {
     ),
     PublishArtefact(async_channel::Sender<Result<A>>, ArtefactId, bool, A),
     UnpublishArtefact(async_channel::Sender<Result<A>>, ArtefactId),
+    UpdateArtefact(async_channel::Sender<Result<A>>, ArtefactId, A),
     RegisterInstance(async_channel::Sender<Result<A>>, ArtefactId, ServantId),
     UnregisterInstance(async_channel::Sender<Result<A>>, ArtefactId, ServantId),
 }
@@ -179,6 +195,13 @@ impl<A: Artefact + Send + Sync + 'static> Repository<A> {
                         r.send(A::artefact_id(&id).and_then(|id| self.unpublish(id)))
                             .await?
                     }
+                    Msg::UpdateArtefact(r, id, a) => {
+                        r.send(
+                            A::artefact_id(&id)
+                                .and_then(|id| self.update(id, a).map(std::clone::Clone::clone)),
+                        )
+                        .await?
+                    }
                     Msg::RegisterInstance(r, a_id, s_id) => {
                         r.send(
                             A::artefact_id(&a_id)
@@ -306,6 +329,22 @@ impl Repositories {
         rx.recv().await?
     }
 
+    /// Replaces a published pipeline, keeping its instances
+    ///
+    /// # Errors
+    ///  * if the pipeline isn't published or is a system pipeline
+    pub async fn update_pipeline(
+        &self,
+        id: &TremorUrl,
+        artefact: PipelineArtefact,
+    ) -> Result<PipelineArtefact> {
+        let (tx, rx) = bounded(1);
+        self.pipeline
+            .send(Msg::UpdateArtefact(tx, id.clone(), artefact))
+            .await?;
+        rx.recv().await?
+    }
+
     /// Bind a pipeline
     ///
     /// # Errors
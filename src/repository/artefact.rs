@@ -20,7 +20,7 @@ use crate::pipeline;
 use crate::registry::ServantId;
 use crate::system::{self, World};
 use crate::url::{ResourceType, TremorUrl};
-use crate::{codec, pipeline::ConnectTarget};
+use crate::{codec, pipeline::ConnectTarget, postprocessor, preprocessor};
 use beef::Cow;
 use hashbrown::HashMap;
 use std::collections::HashSet;
@@ -196,7 +196,7 @@ impl Artefact for OfframpArtefact {
         // lookup codecs already here
         // this will bail out early if something is mistyped or so
         let codec = if let Some(codec) = &self.codec {
-            codec::lookup(&codec)?
+            codec::resolve(codec)?
         } else {
             codec::lookup(offramp.default_codec())?
         };
@@ -204,7 +204,7 @@ impl Artefact for OfframpArtefact {
         // override the builtin map
         if let Some(codec_map) = &self.codec_map {
             for (k, v) in codec_map {
-                resolved_codec_map.insert(k.to_string(), codec::lookup(v.as_str())?);
+                resolved_codec_map.insert(k.to_string(), codec::resolve(v)?);
             }
         }
 
@@ -219,6 +219,12 @@ impl Artefact for OfframpArtefact {
         } else {
             vec![]
         };
+        // validate the processor configs early
+        preprocessor::make_preprocessors(&preprocessors)?;
+        postprocessor::make_postprocessors(&postprocessors)?;
+        if let Some(retry) = &self.retry {
+            retry.validate()?;
+        }
         let metrics_reporter = RampReporter::new(servant_id.clone(), self.metrics_interval_s);
 
         let (tx, rx) = bounded(1);
@@ -236,6 +242,7 @@ impl Artefact for OfframpArtefact {
                     postprocessors,
                     metrics_reporter,
                     is_linked: self.is_linked,
+                    retry: self.retry.clone(),
                 }),
             ))
             .await?;
@@ -326,13 +333,19 @@ impl Artefact for OnrampArtefact {
     async fn spawn(&self, world: &World, servant_id: ServantId) -> Result<Self::SpawnResult> {
         let stream = onramp::lookup(&self.binding_type, &servant_id, &self.config)?;
         let codec = self.codec.as_ref().map_or_else(
-            || stream.default_codec().to_string(),
+            || codec::Config::from(stream.default_codec()),
             std::clone::Clone::clone,
         );
         let codec_map = self
             .codec_map
             .clone()
             .unwrap_or_else(|| halfbrown::HashMap::with_capacity(0));
+        // lookup codecs already here
+        // this will bail out early if something is mistyped or misconfigured
+        codec::resolve(&codec)?;
+        for v in codec_map.values() {
+            codec::resolve(v)?;
+        }
         let preprocessors = if let Some(preprocessors) = &self.preprocessors {
             preprocessors.clone()
         } else {
@@ -343,6 +356,9 @@ impl Artefact for OnrampArtefact {
         } else {
             vec![]
         };
+        // validate the processor configs early
+        preprocessor::make_preprocessors(&preprocessors)?;
+        postprocessor::make_postprocessors(&postprocessors)?;
 
         let metrics_reporter = RampReporter::new(servant_id.clone(), self.metrics_interval_s);
         let (tx, rx) = bounded(1);
@@ -23,6 +23,7 @@ pub(crate) mod amqp;
 pub(crate) mod blackhole;
 pub(crate) mod cb;
 pub(crate) mod debug;
+pub(crate) mod dlq;
 pub(crate) mod dns;
 pub(crate) mod elastic;
 pub(crate) mod exit;
@@ -31,11 +32,13 @@ pub(crate) mod gcs;
 pub(crate) mod gpub;
 pub(crate) mod kafka;
 pub(crate) mod kv;
+pub(crate) mod mqtt;
 pub(crate) mod nats;
 pub(crate) mod newrelic;
 pub(crate) mod otel;
 pub(crate) mod postgres;
 pub(crate) mod prelude;
+pub(crate) mod redis;
 pub(crate) mod rest;
 pub(crate) mod stderr;
 pub(crate) mod stdout;
@@ -127,6 +130,8 @@ where
     sink_url: Option<TremorUrl>,
     sink: T,
     pipelines: HashMap<TremorUrl, pipeline::Addr>,
+    /// insights go through the offramp, so it can retry failed events
+    reply_tx: Option<Sender<Reply>>,
     // for linked offramps
     dest_pipelines: HashMap<Cow<'static, str>, Vec<(TremorUrl, pipeline::Addr)>>,
 }
@@ -140,6 +145,7 @@ where
             sink_url: None,
             sink,
             pipelines: HashMap::new(),
+            reply_tx: None,
             dest_pipelines: HashMap::new(),
         }
     }
@@ -151,6 +157,15 @@ where
     fn has_dest_pipelines(&self) -> bool {
         self.dest_pipelines.values().any(|xs| !xs.is_empty())
     }
+
+    async fn send_insight(&mut self, insight: Event) -> Result<()> {
+        if let Some(tx) = &self.reply_tx {
+            tx.send(Reply::Insight(insight)).await?;
+            Ok(())
+        } else {
+            handle_insight(insight, self.pipelines.values()).await
+        }
+    }
 }
 
 #[async_trait::async_trait]
@@ -173,6 +188,7 @@ where
         reply_channel: Sender<Reply>,
     ) -> Result<()> {
         self.sink_url = Some(offramp_url.clone());
+        self.reply_tx = Some(reply_channel.clone());
         self.sink
             .init(
                 offramp_uid, // we treat offramp_uid and sink_uid as the same thing
@@ -196,7 +212,7 @@ where
         if let Some(mut replies) = self.sink.on_event(input, codec, codec_map, event).await? {
             for reply in replies.drain(..) {
                 match reply {
-                    Reply::Insight(e) => handle_insight(e, self.pipelines.values()).await?,
+                    Reply::Insight(e) => self.send_insight(e).await?,
                     Reply::Response(port, event) => {
                         if let Some(pipelines) = self.dest_pipelines.get_mut(&port) {
                             handle_response(event, pipelines.iter()).await?
@@ -249,7 +265,7 @@ where
         for reply in replies {
             match reply {
                 Reply::Insight(e) => {
-                    if let Err(e) = handle_insight(e, self.pipelines.values()).await {
+                    if let Err(e) = self.send_insight(e).await {
                         if let Some(sink_url) = &self.sink_url {
                             error!("[Sink::{}] Error handling insight in sink: {}", sink_url, e);
                         }
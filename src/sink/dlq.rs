@@ -0,0 +1,98 @@
+// Copyright 2020-2021, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+#![cfg(not(tarpaulin_include))]
+
+//! # Dead-letter queue offramp
+//!
+//! Keeps the error records of onramps and pipelines in a dead-letter queue on
+//! disk, from where they can be listed and replayed through the API.
+//!
+//! ## Configuration
+//!
+//! See [Config](struct.Config.html) for details.
+
+use crate::dlq::Queue;
+use crate::sink::prelude::*;
+use halfbrown::HashMap;
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Config {
+    /// directory of the queue
+    pub dir: String,
+}
+
+impl ConfigImpl for Config {}
+
+pub struct Dlq {
+    queue: Queue,
+}
+
+impl offramp::Impl for Dlq {
+    fn from_config(config: &Option<OpConfig>) -> Result<Box<dyn Offramp>> {
+        if let Some(config) = config {
+            let config = Config::new(config)?;
+            Ok(SinkManager::new_box(Self {
+                queue: Queue::open(&config.dir)?,
+            }))
+        } else {
+            Err("[DLQ Offramp] Offramp requires a config".into())
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Sink for Dlq {
+    async fn on_event(
+        &mut self,
+        _input: &str,
+        _codec: &mut dyn Codec,
+        _codec_map: &HashMap<String, Box<dyn Codec>>,
+        event: Event,
+    ) -> ResultVec {
+        for value in event.value_iter() {
+            self.queue.push(value, event.ingest_ns).await?;
+        }
+        Ok(None)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn init(
+        &mut self,
+        _sink_uid: u64,
+        _sink_url: &TremorUrl,
+        _codec: &dyn Codec,
+        _codec_map: &HashMap<String, Box<dyn Codec>>,
+        _processors: Processors<'_>,
+        _is_linked: bool,
+        _reply_channel: Sender<sink::Reply>,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    fn default_codec(&self) -> &str {
+        "json"
+    }
+
+    async fn on_signal(&mut self, _signal: Event) -> ResultVec {
+        Ok(None)
+    }
+
+    fn is_active(&self) -> bool {
+        true
+    }
+
+    fn auto_ack(&self) -> bool {
+        true
+    }
+}
@@ -16,6 +16,20 @@
 //!
 //! Writes events to a file, one event per line
 //!
+//! The filename can be a template, `{event.tenant}` and `{$tenant}` are
+//! replaced by fields of the event or its metadata, `{%Y-%m-%d}` by its ingest
+//! time formatted with a chrono `strftime` format string. So events can be
+//! written to many files at once, of which the `max_open_files` most recently
+//! written are kept open. Files are always appended to.
+//!
+//! With `rotate_bytes` or `rotate_interval_s` set, a file is rotated once it
+//! grows too big or too old, by renaming it to `<file>.<timestamp>`. The age of
+//! a file that was closed in between counts from its reopening. Rotated files
+//! are compressed chunk by chunk with the `compression` postprocessor, every
+//! chunk becoming a frame of its own, and only the last `retain` of them are
+//! kept. Rotation errors are logged and don't fail the written events, a file
+//! that couldn't be renamed is rotated with the next event or tick.
+//!
 //! ## Configuration
 //!
 //! See [Config](struct.Config.html) for details.
@@ -24,34 +38,380 @@
 
 use crate::sink::prelude::*;
 use async_std::fs::File as FSFile;
+use async_std::fs::OpenOptions;
 use async_std::io::prelude::*;
+use async_std::path::{Path, PathBuf};
+use async_std::task;
+use chrono::format::{Item, StrftimeItems};
+use chrono::{TimeZone, Utc};
 use halfbrown::HashMap;
-use tremor_common::asy::file as cfile;
+use std::time::{Duration, Instant};
+
+/// Postprocessors whose concatenated frames decompress as one file
+const COMPRESSIONS: [&str; 5] = ["gzip", "zstd", "xz2", "snappy", "lz4"];
+
+/// Size of the chunks rotated files are compressed in
+const COMPRESSION_CHUNK: usize = 1024 * 1024;
 
 /// An offramp that write a given file
 pub struct File {
-    file: Option<FSFile>,
+    file: Template,
+    files: HashMap<PathBuf, Segment>,
     postprocessors: Postprocessors,
+    compression: Option<Box<dyn Postprocessor>>,
     config: Config,
 }
 
 #[derive(Deserialize)]
 pub struct Config {
-    /// Filename to write to
+    /// Filename to write to, can contain `{event.field}`, `{$field}` and
+    /// `{<strftime format>}` placeholders
     pub file: String,
+    /// Size in bytes after which a file is rotated
+    #[serde(default = "Default::default")]
+    pub rotate_bytes: Option<u64>,
+    /// Age in seconds after which a file is rotated
+    #[serde(default = "Default::default")]
+    pub rotate_interval_s: Option<u64>,
+    /// Number of rotated files to keep per file, all are kept if not set
+    #[serde(default = "Default::default")]
+    pub retain: Option<usize>,
+    /// Postprocessor to compress rotated files with, `gzip`, `zstd`, `xz2`,
+    /// `snappy` or `lz4`
+    #[serde(default = "Default::default")]
+    pub compression: Option<postprocessor::Config>,
+    /// Number of files kept open, the least recently written one is closed
+    /// to open another
+    #[serde(default = "Config::default_max_open_files")]
+    pub max_open_files: usize,
 }
 
 impl ConfigImpl for Config {}
 
+impl Config {
+    fn default_max_open_files() -> usize {
+        64
+    }
+}
+
+/// A part of a filename template
+#[derive(Debug, PartialEq)]
+enum Part {
+    Text(String),
+    Event(Vec<String>),
+    Meta(Vec<String>),
+    Time(String),
+}
+
+/// A filename with placeholders
+#[derive(Debug, PartialEq)]
+struct Template(Vec<Part>);
+
+impl Template {
+    fn parse(template: &str) -> Result<Self> {
+        let segments = |path: &str| -> Vec<String> {
+            path.split('.')
+                .filter(|s| !s.is_empty())
+                .map(ToString::to_string)
+                .collect()
+        };
+        let mut parts = Vec::new();
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            let end = rest[start..]
+                .find('}')
+                .map(|end| start + end)
+                .ok_or_else(|| format!("Unterminated placeholder in file `{}`", template))?;
+            if start > 0 {
+                parts.push(Part::Text(rest[..start].to_string()));
+            }
+            let placeholder = &rest[start + 1..end];
+            if placeholder == "event" {
+                parts.push(Part::Event(vec![]));
+            } else if let Some(path) = placeholder.strip_prefix("event.") {
+                parts.push(Part::Event(segments(path)));
+            } else if let Some(path) = placeholder.strip_prefix('$') {
+                parts.push(Part::Meta(segments(path)));
+            } else if placeholder.contains('%')
+                && StrftimeItems::new(placeholder).all(|i| i != Item::Error)
+            {
+                parts.push(Part::Time(placeholder.to_string()));
+            } else {
+                return Err(format!(
+                    "Invalid placeholder `{{{}}}` in file `{}`",
+                    placeholder, template
+                )
+                .into());
+            }
+            rest = &rest[end + 1..];
+        }
+        if !rest.is_empty() {
+            parts.push(Part::Text(rest.to_string()));
+        }
+        Ok(Self(parts))
+    }
+
+    /// Renders the filename for an event, fields are sanitized so they can't
+    /// escape the directory
+    #[allow(clippy::cast_possible_wrap)]
+    fn render(&self, value: &Value, meta: &Value, ingest_ns: u64) -> PathBuf {
+        let mut file = String::new();
+        for part in &self.0 {
+            let (mut v, path) = match part {
+                Part::Text(text) => {
+                    file.push_str(text);
+                    continue;
+                }
+                Part::Time(format) => {
+                    let time = Utc.timestamp_nanos(ingest_ns as i64);
+                    file.push_str(&time.format(format).to_string());
+                    continue;
+                }
+                Part::Event(path) => (value, path),
+                Part::Meta(path) => (meta, path),
+            };
+            for segment in path {
+                v = v.get(segment.as_str()).unwrap_or(&NULL);
+            }
+            let field = v.as_str().map_or_else(|| v.encode(), ToString::to_string);
+            let field: String = field
+                .chars()
+                .map(|c| {
+                    if c.is_alphanumeric() || c == '-' || c == '_' || c == '.' {
+                        c
+                    } else {
+                        '_'
+                    }
+                })
+                .collect();
+            if field.is_empty() || field.chars().all(|c| c == '.') {
+                file.push('_');
+            } else {
+                file.push_str(&field);
+            }
+        }
+        PathBuf::from(file)
+    }
+}
+
+/// A file being written
+struct Segment {
+    file: FSFile,
+    bytes: u64,
+    opened: Instant,
+    written: Instant,
+}
+
+impl File {
+    async fn segment(&mut self, path: &Path) -> Result<&mut Segment> {
+        if !self.files.contains_key(path) {
+            if let Some(dir) = path.parent() {
+                if !dir.as_os_str().is_empty() {
+                    async_std::fs::create_dir_all(dir).await?;
+                }
+            }
+            if self.files.len() >= self.config.max_open_files {
+                self.close_least_recent().await?;
+            }
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .await?;
+            let bytes = file.metadata().await?.len();
+            self.files.insert(
+                path.to_path_buf(),
+                Segment {
+                    file,
+                    bytes,
+                    opened: Instant::now(),
+                    written: Instant::now(),
+                },
+            );
+        }
+        let segment = self
+            .files
+            .get_mut(path)
+            .ok_or_else(|| Error::from("file vanished"))?;
+        segment.written = Instant::now();
+        Ok(segment)
+    }
+
+    /// Closes the least recently written file
+    async fn close_least_recent(&mut self) -> Result<()> {
+        let least_recent = self
+            .files
+            .iter()
+            .min_by_key(|(_, segment)| segment.written)
+            .map(|(path, _)| path.clone());
+        if let Some(mut segment) = least_recent.and_then(|path| self.files.remove(&path)) {
+            segment.file.flush().await?;
+        }
+        Ok(())
+    }
+
+    fn is_due(&self, segment: &Segment) -> bool {
+        segment.bytes > 0
+            && (self
+                .config
+                .rotate_bytes
+                .map_or(false, |max| segment.bytes >= max)
+                || self.config.rotate_interval_s.map_or(false, |s| {
+                    segment.opened.elapsed() >= Duration::from_secs(s)
+                }))
+    }
+
+    /// Renames the file at `path` and closes it, compresses it and removes
+    /// rotated files beyond `retain`. The file stays open and due if it
+    /// couldn't be renamed.
+    async fn rotate(&mut self, path: &Path) -> Result<()> {
+        if let Some(segment) = self.files.get_mut(path) {
+            segment.file.flush().await?;
+        }
+        let mut rotated = path.as_os_str().to_owned();
+        rotated.push(format!(".{}", nanotime()));
+        let mut rotated = PathBuf::from(rotated);
+        async_std::fs::rename(path, &rotated).await?;
+        self.files.remove(path);
+        if let Some(mut compression) = self.compression.take() {
+            let mut target = rotated.as_os_str().to_owned();
+            target.push(format!(".{}", extension(compression.name())));
+            let target = PathBuf::from(target);
+            let (source, dest) = (rotated.clone(), target.clone());
+            let (compression, compressed) = task::spawn_blocking(move || {
+                let compressed = compress(compression.as_mut(), source.as_ref(), dest.as_ref());
+                (compression, compressed)
+            })
+            .await;
+            self.compression = Some(compression);
+            compressed?;
+            async_std::fs::remove_file(&rotated).await?;
+            rotated = target;
+        }
+        debug!(
+            "[Sink::File] Rotated {} to {}",
+            path.display(),
+            rotated.display()
+        );
+        if let Some(retain) = self.config.retain {
+            remove_old(path, retain).await?;
+        }
+        Ok(())
+    }
+
+    /// Rotates the files that are due, failed rotations are logged and tried
+    /// again with the next event or tick
+    async fn rotate_due(&mut self) {
+        let due: Vec<PathBuf> = self
+            .files
+            .iter()
+            .filter(|(_, segment)| self.is_due(segment))
+            .map(|(path, _)| path.clone())
+            .collect();
+        for path in due {
+            if let Err(e) = self.rotate(&path).await {
+                error!("[Sink::File] Failed to rotate {}: {}", path.display(), e);
+            }
+        }
+    }
+}
+
+/// Compresses `source` into `target` chunk by chunk, blocking
+fn compress(
+    compression: &mut dyn Postprocessor,
+    source: &std::path::Path,
+    target: &std::path::Path,
+) -> Result<()> {
+    use std::io::{Read, Write};
+    let mut input = std::fs::File::open(source)?;
+    let mut output = std::io::BufWriter::new(std::fs::File::create(target)?);
+    let mut chunk = Vec::new();
+    loop {
+        chunk.clear();
+        (&mut input)
+            .take(COMPRESSION_CHUNK as u64)
+            .read_to_end(&mut chunk)?;
+        if chunk.is_empty() {
+            break;
+        }
+        let now = nanotime();
+        for compressed in compression.process(now, now, &chunk)? {
+            output.write_all(&compressed)?;
+        }
+    }
+    output.flush()?;
+    Ok(())
+}
+
+/// The file extension of files compressed by a postprocessor
+fn extension(postprocessor: &str) -> &str {
+    match postprocessor {
+        "gzip" => "gz",
+        "zstd" => "zst",
+        "xz2" => "xz",
+        "snappy" => "sz",
+        other => other,
+    }
+}
+
+/// Removes all but the `retain` newest rotated files of `path`
+async fn remove_old(path: &Path, retain: usize) -> Result<()> {
+    let name = path
+        .file_name()
+        .map(|name| format!("{}.", name.to_string_lossy()))
+        .unwrap_or_default();
+    let dir = path
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or_else(|| Path::new("."));
+    let mut rotated = Vec::new();
+    let mut entries = async_std::fs::read_dir(dir).await?;
+    while let Some(entry) = entries.next().await {
+        let entry = entry?;
+        let file_name = entry.file_name().to_string_lossy().to_string();
+        if let Some(suffix) = file_name.strip_prefix(&name) {
+            let stamp = suffix.split('.').next().unwrap_or_default();
+            if let Ok(stamp) = stamp.parse::<u64>() {
+                rotated.push((stamp, dir.join(&file_name)));
+            }
+        }
+    }
+    rotated.sort();
+    let remove = rotated.len().saturating_sub(retain);
+    for (_, old) in rotated.into_iter().take(remove) {
+        async_std::fs::remove_file(&old).await?;
+    }
+    Ok(())
+}
+
 impl offramp::Impl for File {
     fn from_config(config: &Option<OpConfig>) -> Result<Box<dyn Offramp>> {
         if let Some(config) = config {
             let config: Config = Config::new(config)?;
+            if config.max_open_files == 0 {
+                return Err("File offramp `max_open_files` needs to be at least 1".into());
+            }
+            if let Some(c) = &config.compression {
+                if !COMPRESSIONS.contains(&c.name()) {
+                    return Err(format!(
+                        "File offramp `compression` needs to be one of {}",
+                        COMPRESSIONS.join(", ")
+                    )
+                    .into());
+                }
+            }
+            let compression = config
+                .compression
+                .as_ref()
+                .map(|c| postprocessor::lookup_with_config(c.name(), c.config()))
+                .transpose()?;
 
             Ok(SinkManager::new_box(Self {
-                file: None,
+                file: Template::parse(&config.file)?,
+                files: HashMap::new(),
                 config,
                 postprocessors: vec![],
+                compression,
             }))
         } else {
             Err("Blackhole offramp requires a config".into())
@@ -62,8 +422,8 @@ impl offramp::Impl for File {
 #[async_trait::async_trait]
 impl Sink for File {
     async fn terminate(&mut self) {
-        if let Some(file) = &mut self.file {
-            if let Err(e) = file.flush().await {
+        for segment in self.files.values_mut() {
+            if let Err(e) = segment.file.flush().await {
                 error!("Failed to flush file: {}", e);
             }
         }
@@ -76,17 +436,28 @@ impl Sink for File {
         _codec_map: &HashMap<String, Box<dyn Codec>>,
         mut event: Event,
     ) -> ResultVec {
-        if let Some(file) = &mut self.file {
-            for value in event.value_iter() {
-                let raw = codec.encode(value)?;
-                let packets = postprocess(&mut self.postprocessors, event.ingest_ns, raw)?;
-                for packet in packets {
-                    file.write_all(&packet).await?;
-                    file.write_all(b"\n").await?;
-                }
+        let mut written = Vec::new();
+        for (value, meta) in event.value_meta_iter() {
+            let path = self.file.render(value, meta, event.ingest_ns);
+            let raw = codec.encode(value)?;
+            let packets = postprocess(&mut self.postprocessors, event.ingest_ns, raw)?;
+            let segment = self.segment(&path).await?;
+            for packet in packets {
+                segment.file.write_all(&packet).await?;
+                segment.file.write_all(b"\n").await?;
+                segment.bytes += packet.len() as u64 + 1;
+            }
+            if !written.contains(&path) {
+                written.push(path);
+            }
+        }
+        for path in &written {
+            if let Some(segment) = self.files.get_mut(path) {
+                segment.file.flush().await?;
             }
-            file.flush().await?
         }
+        // the payloads are written, so a failed rotation doesn't fail the event
+        self.rotate_due().await;
         Ok(Some(vec![sink::Reply::Insight(event.insight_ack())]))
     }
     fn default_codec(&self) -> &str {
@@ -104,11 +475,17 @@ impl Sink for File {
         _reply_channel: Sender<sink::Reply>,
     ) -> Result<()> {
         self.postprocessors = make_postprocessors(processors.post)?;
-        let file = cfile::create(&self.config.file).await?;
-        self.file = Some(file);
+        // a plain filename is created right away, as it always was
+        if self.file.0.iter().all(|p| matches!(p, Part::Text(_))) {
+            let path = self.file.render(&Value::null(), &Value::null(), 0);
+            self.segment(&path).await?;
+        }
         Ok(())
     }
     async fn on_signal(&mut self, _signal: Event) -> ResultVec {
+        if self.config.rotate_interval_s.is_some() || self.config.rotate_bytes.is_some() {
+            self.rotate_due().await;
+        }
         Ok(None)
     }
     fn is_active(&self) -> bool {
@@ -118,3 +495,203 @@ impl Sink for File {
         true
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn template() -> Result<()> {
+        let template = Template::parse("/var/log/{$tenant}/{event.app.name}-{%Y-%m-%d}.log")?;
+        assert_eq!(
+            PathBuf::from("/var/log/snot/badger-1970-01-02.log"),
+            template.render(
+                &literal!({"app": {"name": "badger"}}),
+                &literal!({"tenant": "snot"}),
+                86_400_000_000_000
+            )
+        );
+        // fields can't escape the directory
+        assert_eq!(
+            PathBuf::from("/var/log/_/_.._etc-1970-01-01.log"),
+            template.render(
+                &literal!({"app": {"name": "/../etc"}}),
+                &literal!({"tenant": ".."}),
+                0
+            )
+        );
+        assert_eq!(
+            PathBuf::from("/var/log/null/42-1970-01-01.log"),
+            template.render(&literal!({"app": {"name": 42}}), &Value::object(), 0)
+        );
+        assert!(Template::parse("/var/log/{$tenant").is_err());
+        assert!(Template::parse("/var/log/{%Q}").is_err());
+        assert!(Template::parse("/var/log/{tenant}").is_err());
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn rotate_and_retain() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let config: OpConfig = serde_yaml::from_str(&format!(
+            r#"
+file: "{}/{{$tenant}}/out.log"
+rotate_bytes: 10
+retain: 2
+compression: gzip
+"#,
+            dir.path().display()
+        ))?;
+        let config = Config::new(&config)?;
+        let mut sink = File {
+            file: Template::parse(&config.file)?,
+            files: HashMap::new(),
+            compression: Some(postprocessor::lookup("gzip")?),
+            postprocessors: vec![],
+            config,
+        };
+        let mut codec = crate::codec::lookup("json")?;
+        for i in 0..4_u64 {
+            let event = Event {
+                data: (literal!({ "snot": i }), literal!({"tenant": "badger"})).into(),
+                ..Event::default()
+            };
+            sink.on_event("in", codec.as_mut(), &HashMap::new(), event)
+                .await?;
+        }
+        let mut files: Vec<String> = std::fs::read_dir(dir.path().join("badger"))?
+            .filter_map(std::result::Result::ok)
+            .map(|e| e.file_name().to_string_lossy().to_string())
+            .collect();
+        files.sort();
+        // every event filled a file, the oldest two were removed
+        assert_eq!(2, files.len());
+        assert!(files
+            .iter()
+            .all(|f| f.starts_with("out.log.") && f.ends_with(".gz")));
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn retry_failed_rotation() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("out.log");
+        let config: OpConfig = serde_yaml::from_str(&format!(
+            "{{file: \"{}\", rotate_bytes: 10}}",
+            path.display()
+        ))?;
+        let config = Config::new(&config)?;
+        let mut sink = File {
+            file: Template::parse(&config.file)?,
+            files: HashMap::new(),
+            compression: None,
+            postprocessors: vec![],
+            config,
+        };
+        // the open file can't be renamed once it is gone
+        sink.segment(&path).await?;
+        std::fs::remove_file(&path)?;
+        let mut codec = crate::codec::lookup("json")?;
+        let event = Event {
+            data: (literal!({"snot": "badger"}), Value::object()).into(),
+            ..Event::default()
+        };
+        let replies = sink
+            .on_event("in", codec.as_mut(), &HashMap::new(), event)
+            .await?;
+        // the payload was written, so the event is acked anyway
+        assert!(matches!(
+            replies.as_deref(),
+            Some([sink::Reply::Insight(Event {
+                cb: CbAction::Ack,
+                ..
+            })])
+        ));
+        assert_eq!(1, sink.files.len());
+
+        // the next tick rotates the file again
+        std::fs::write(&path, "{\"snot\":\"badger\"}\n")?;
+        sink.on_signal(Event::default()).await?;
+        assert!(sink.files.is_empty());
+        let files: Vec<String> = std::fs::read_dir(dir.path())?
+            .filter_map(std::result::Result::ok)
+            .map(|e| e.file_name().to_string_lossy().to_string())
+            .collect();
+        assert_eq!(1, files.len());
+        assert!(files[0].starts_with("out.log."));
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn append_and_close_least_recent() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let config: OpConfig = serde_yaml::from_str(&format!(
+            r#"
+file: "{}/{{$tenant}}.log"
+max_open_files: 1
+"#,
+            dir.path().display()
+        ))?;
+        let config = Config::new(&config)?;
+        let mut sink = File {
+            file: Template::parse(&config.file)?,
+            files: HashMap::new(),
+            compression: None,
+            postprocessors: vec![],
+            config,
+        };
+        std::fs::write(dir.path().join("snot.log"), "0\n")?;
+        let mut codec = crate::codec::lookup("json")?;
+        for (i, tenant) in ["snot", "badger", "snot"].iter().enumerate() {
+            let event = Event {
+                data: (Value::from(i + 1), literal!({ "tenant": *tenant })).into(),
+                ..Event::default()
+            };
+            sink.on_event("in", codec.as_mut(), &HashMap::new(), event)
+                .await?;
+            assert_eq!(1, sink.files.len());
+        }
+        // files are appended to, also after they were closed
+        assert_eq!(
+            "0\n1\n3\n",
+            std::fs::read_to_string(dir.path().join("snot.log"))?
+        );
+        assert_eq!(
+            "2\n",
+            std::fs::read_to_string(dir.path().join("badger.log"))?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn compress_in_chunks() -> Result<()> {
+        use std::io::Read;
+        let dir = tempfile::tempdir()?;
+        let source = dir.path().join("out.log");
+        let target = dir.path().join("out.log.gz");
+        let data: Vec<u8> = b"snot badger\n"
+            .iter()
+            .cycle()
+            .take(COMPRESSION_CHUNK * 2 + 42)
+            .copied()
+            .collect();
+        std::fs::write(&source, &data)?;
+        let mut gzip = postprocessor::lookup("gzip")?;
+        compress(gzip.as_mut(), &source, &target)?;
+        // the chunks decompress as one file
+        let mut decompressed = Vec::new();
+        libflate::gzip::MultiDecoder::new(std::fs::File::open(&target)?)?
+            .read_to_end(&mut decompressed)?;
+        assert_eq!(data, decompressed);
+        Ok(())
+    }
+
+    #[test]
+    fn bad_config() -> Result<()> {
+        let config: OpConfig = serde_yaml::from_str("{file: out.log, compression: base64}")?;
+        assert!(<File as offramp::Impl>::from_config(&Some(config)).is_err());
+        let config: OpConfig = serde_yaml::from_str("{file: out.log, max_open_files: 0}")?;
+        assert!(<File as offramp::Impl>::from_config(&Some(config)).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,626 @@
+// Copyright 2020-2021, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+#![cfg(not(tarpaulin_include))]
+
+//! # MQTT Offramp
+//!
+//! Publishes events to a MQTT broker, speaking MQTT 3.1.1 or, with
+//! `protocol: v5`, MQTT 5.
+//!
+//! The topic, QoS level and retain flag are taken from the config and can be
+//! overwritten per event via `$mqtt.topic`, `$mqtt.qos` and `$mqtt.retain`.
+//! With MQTT 5 the publish properties are taken from `$mqtt.properties`
+//! (`payload_format_indicator`, `message_expiry_interval`, `content_type`,
+//! `response_topic`, `correlation_data` and `user_properties` as a list of
+//! `[name, value]` pairs).
+//!
+//! Transactional events are acked once the broker acknowledged all of their
+//! payloads (`PUBACK` for QoS 1, `PUBCOMP` for QoS 2) and failed if one of them
+//! couldn't be published, was refused by the broker or the connection dropped
+//! before.
+
+use crate::ramp::mqtt::{
+    default_qos, qos, v5, Client, ConnectOptions, EventLoop, Notification, QoS,
+};
+use crate::sink::prelude::*;
+use async_channel::{bounded, Receiver};
+use halfbrown::HashMap;
+use std::collections::{HashSet, VecDeque};
+use std::convert::TryFrom;
+use std::time::Duration;
+use tremor_pipeline::{EventId, OpMeta};
+
+#[derive(Deserialize)]
+pub struct Config {
+    #[serde(flatten)]
+    pub connection: ConnectOptions,
+    // topic to publish to
+    pub topic: String,
+    // QoS level to publish with
+    #[serde(default = "default_qos")]
+    pub qos: u8,
+    // if messages should be retained by the broker
+    #[serde(default = "Default::default")]
+    pub retain: bool,
+}
+
+impl ConfigImpl for Config {}
+
+/// An event whose payloads are published
+struct Pending {
+    id: EventId,
+    ingest_ns: u64,
+    op_meta: OpMeta,
+    transactional: bool,
+}
+
+/// What the sink tells the event loop, in the order it hands publishes to the
+/// client
+enum Track {
+    /// event `seq` has `publishes` payloads, sent once the first of them was
+    /// accepted by the client
+    Event {
+        seq: u64,
+        pending: Pending,
+        publishes: usize,
+    },
+    /// the client accepted the next publish of event `seq`
+    Published(u64),
+    /// `unpublished` payloads of event `seq` couldn't be handed to the client
+    Failed { seq: u64, unpublished: usize },
+}
+
+/// An event with publishes the broker didn't acknowledge yet
+struct Tracked {
+    pending: Pending,
+    unsettled: usize,
+    failed: bool,
+}
+
+/// Matches the publishes leaving the event loop and the acknowledgements of
+/// the broker to the events they belong to.
+///
+/// Publishes leave the event loop in the order the client accepted them, so
+/// each outgoing publish belongs to the next event the sink reported as
+/// published and is tracked by the packet id it got assigned until the broker
+/// acknowledges it.
+#[derive(Default)]
+struct Tracker {
+    events: HashMap<u64, Tracked>,
+    /// events of publishes the client accepted that didn't leave the event
+    /// loop yet
+    published: VecDeque<u64>,
+    /// events of publishes waiting for the broker, by packet id
+    inflight: HashMap<u16, u64>,
+    /// packet ids of publishes the event loop re-sends after a reconnect,
+    /// their events were already failed
+    resent: HashSet<u16>,
+}
+
+impl Tracker {
+    fn track(&mut self, track: Track) -> Option<Event> {
+        match track {
+            Track::Event {
+                seq,
+                pending,
+                publishes,
+            } => {
+                self.events.insert(
+                    seq,
+                    Tracked {
+                        pending,
+                        unsettled: publishes,
+                        failed: false,
+                    },
+                );
+                None
+            }
+            Track::Published(seq) => {
+                self.published.push_back(seq);
+                None
+            }
+            Track::Failed { seq, unpublished } => self.settle(seq, unpublished, false),
+        }
+    }
+
+    /// Settles `publishes` publishes of event `seq`, returns the insight for
+    /// the event once all of them are settled
+    fn settle(&mut self, seq: u64, publishes: usize, ok: bool) -> Option<Event> {
+        let tracked = self.events.get_mut(&seq)?;
+        tracked.unsettled = tracked.unsettled.saturating_sub(publishes);
+        tracked.failed |= !ok;
+        if tracked.unsettled > 0 {
+            return None;
+        }
+        let Tracked {
+            pending, failed, ..
+        } = self.events.remove(&seq)?;
+        if !pending.transactional {
+            return None;
+        }
+        let mut insight = if failed {
+            Event::cb_fail(pending.ingest_ns, pending.id)
+        } else {
+            Event::cb_ack(pending.ingest_ns, pending.id)
+        };
+        insight.op_meta = pending.op_meta;
+        Some(insight)
+    }
+
+    /// If a publish leaving the event loop re-sends one whose event was
+    /// already failed. Re-sends precede new publishes after a reconnect, so the
+    /// first new publish ends them and its packet id may be reused from then on.
+    fn resends(&mut self, pkid: u16) -> bool {
+        if self.resent.remove(&pkid) {
+            true
+        } else {
+            self.resent.clear();
+            false
+        }
+    }
+
+    /// Assigns a publish leaving the event loop to the next published event
+    fn outgoing(&mut self, pkid: u16) -> Option<Event> {
+        let seq = self.published.pop_front()?;
+        if pkid == 0 {
+            // QoS 0, nothing to wait for
+            self.settle(seq, 1, true)
+        } else {
+            self.inflight.insert(pkid, seq);
+            None
+        }
+    }
+
+    /// Settles a publish the broker acknowledged, or refused if not `ok`
+    fn acknowledged(&mut self, pkid: u16, ok: bool) -> Option<Event> {
+        let seq = self.inflight.remove(&pkid)?;
+        self.settle(seq, 1, ok)
+    }
+
+    /// Fails the events of publishes the broker didn't acknowledge, `resends`
+    /// if the event loop sends them again after reconnecting
+    fn disconnected(&mut self, resends: bool) -> Vec<Event> {
+        let inflight: Vec<(u16, u64)> = self.inflight.drain().collect();
+        let mut insights = Vec::new();
+        for (pkid, seq) in inflight {
+            if resends {
+                self.resent.insert(pkid);
+            }
+            insights.extend(self.settle(seq, 1, false));
+        }
+        insights
+    }
+}
+
+pub struct Mqtt {
+    sink_url: TremorUrl,
+    config: Config,
+    postprocessors: Postprocessors,
+    client: Option<Client>,
+    track_tx: Option<Sender<Track>>,
+    merged_meta: OpMeta,
+    /// sequence number of the next event
+    seq: u64,
+}
+
+impl offramp::Impl for Mqtt {
+    fn from_config(config: &Option<OpConfig>) -> Result<Box<dyn Offramp>> {
+        if let Some(config) = config {
+            let config: Config = Config::new(config)?;
+            qos(config.qos)?;
+            Ok(SinkManager::new_box(Self {
+                sink_url: TremorUrl::from_offramp_id("mqtt")?,
+                config,
+                postprocessors: vec![],
+                client: None,
+                track_tx: None,
+                merged_meta: OpMeta::default(),
+                seq: 0,
+            }))
+        } else {
+            Err("MQTT offramp requires a configuration.".into())
+        }
+    }
+}
+
+async fn send_insight(reply_tx: &Sender<Reply>, sink_url: &TremorUrl, insight: Event) {
+    if let Err(e) = reply_tx.send(sink::Reply::Insight(insight)).await {
+        error!(
+            "[Sink::{}] Error sending insight via reply channel: {}",
+            sink_url, e
+        );
+    }
+}
+
+/// Drives the connection and passes on the insights for the events of the
+/// publishes the broker acknowledged, or that were lost
+async fn run_eventloop(
+    mut eventloop: EventLoop,
+    track_rx: Receiver<Track>,
+    reply_tx: Sender<Reply>,
+    sink_url: TremorUrl,
+) {
+    let mut tracker = Tracker::default();
+    let mut connected = true;
+    loop {
+        while let Ok(track) = track_rx.try_recv() {
+            if let Some(insight) = tracker.track(track) {
+                send_insight(&reply_tx, &sink_url, insight).await;
+            }
+        }
+        let insight = match eventloop.poll().await {
+            Ok(Notification::Sent(pkid)) => {
+                if tracker.resends(pkid) {
+                    continue;
+                }
+                // the sink reports a publish right after the client accepted it
+                while tracker.published.is_empty() {
+                    if let Ok(track) = track_rx.recv().await {
+                        if let Some(insight) = tracker.track(track) {
+                            send_insight(&reply_tx, &sink_url, insight).await;
+                        }
+                    } else {
+                        return;
+                    }
+                }
+                tracker.outgoing(pkid)
+            }
+            Ok(Notification::Acknowledged(pkid)) => tracker.acknowledged(pkid, true),
+            Ok(Notification::Rejected(pkid)) => {
+                warn!("[Sink::{}] MQTT broker refused publish {}", sink_url, pkid);
+                tracker.acknowledged(pkid, false)
+            }
+            Ok(Notification::Connected { .. }) => {
+                if connected {
+                    None
+                } else {
+                    connected = true;
+                    Some(Event::cb_restore(nanotime()))
+                }
+            }
+            Ok(_) => None,
+            Err(e) => {
+                if track_rx.is_closed() {
+                    break;
+                }
+                error!("[Sink::{}] MQTT connection error: {}", sink_url, e);
+                for insight in tracker.disconnected(eventloop.resends()) {
+                    send_insight(&reply_tx, &sink_url, insight).await;
+                }
+                if connected {
+                    connected = false;
+                    send_insight(&reply_tx, &sink_url, Event::cb_trigger(nanotime())).await;
+                }
+                task::sleep(Duration::from_secs(1)).await;
+                None
+            }
+        };
+        if let Some(insight) = insight {
+            send_insight(&reply_tx, &sink_url, insight).await;
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Sink for Mqtt {
+    async fn on_event(
+        &mut self,
+        _input: &str,
+        codec: &mut dyn Codec,
+        _codec_map: &HashMap<String, Box<dyn Codec>>,
+        event: Event,
+    ) -> ResultVec {
+        let (client, track_tx) = match (&self.client, &self.track_tx) {
+            (Some(client), Some(track_tx)) => (client, track_tx),
+            _ => return Err("MQTT offramp is not connected".into()),
+        };
+        let ingest_ns = event.ingest_ns;
+        self.merged_meta.merge(event.op_meta.clone());
+        let mut publishes = Vec::new();
+        for (value, meta) in event.value_meta_iter() {
+            let encoded = codec.encode(value)?;
+            let processed = postprocess(self.postprocessors.as_mut_slice(), ingest_ns, encoded)?;
+            let mqtt_meta = meta.get("mqtt");
+            let topic = mqtt_meta
+                .and_then(|m| m.get_str("topic"))
+                .unwrap_or_else(|| self.config.topic.as_str());
+            let level = if let Some(level) = mqtt_meta.and_then(|m| m.get_u64("qos")) {
+                qos(u8::try_from(level)?)?
+            } else {
+                qos(self.config.qos)?
+            };
+            let retain = mqtt_meta
+                .and_then(|m| m.get_bool("retain"))
+                .unwrap_or(self.config.retain);
+            let properties = if let Some(properties) = mqtt_meta.and_then(|m| m.get("properties")) {
+                v5::Properties::from_value(properties)?
+            } else {
+                v5::Properties::default()
+            };
+            for payload in processed {
+                publishes.push((
+                    topic.to_string(),
+                    level,
+                    retain,
+                    payload,
+                    properties.clone(),
+                ));
+            }
+        }
+        let seq = self.seq;
+        self.seq += 1;
+        let total = publishes.len();
+        for (i, (topic, level, retain, payload, properties)) in publishes.into_iter().enumerate() {
+            if let Err(e) = client
+                .publish(topic, level, retain, payload, properties)
+                .await
+            {
+                error!("[Sink::{}] failed to publish message: {}", self.sink_url, e);
+                if i == 0 {
+                    if event.transactional {
+                        return Ok(Some(vec![sink::Reply::Insight(event.to_fail())]));
+                    }
+                    return Ok(None);
+                }
+                let failed = Track::Failed {
+                    seq,
+                    unpublished: total - i,
+                };
+                if track_tx.send(failed).await.is_err() {
+                    return Err("MQTT event loop stopped".into());
+                }
+                return Ok(None);
+            }
+            if i == 0 {
+                let pending = Pending {
+                    id: event.id.clone(),
+                    ingest_ns,
+                    op_meta: self.merged_meta.clone(),
+                    transactional: event.transactional,
+                };
+                let tracked = Track::Event {
+                    seq,
+                    pending,
+                    publishes: total,
+                };
+                if track_tx.send(tracked).await.is_err() {
+                    return Err("MQTT event loop stopped".into());
+                }
+            }
+            if track_tx.send(Track::Published(seq)).await.is_err() {
+                return Err("MQTT event loop stopped".into());
+            }
+        }
+        if total == 0 && event.transactional {
+            return Ok(Some(vec![sink::Reply::Insight(event.insight_ack())]));
+        }
+        Ok(None)
+    }
+
+    async fn on_signal(&mut self, _signal: Event) -> ResultVec {
+        Ok(None)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn init(
+        &mut self,
+        sink_uid: u64,
+        sink_url: &TremorUrl,
+        _codec: &dyn Codec,
+        _codec_map: &HashMap<String, Box<dyn Codec>>,
+        processors: Processors<'_>,
+        _is_linked: bool,
+        reply_channel: Sender<Reply>,
+    ) -> Result<()> {
+        self.postprocessors = make_postprocessors(processors.post)?;
+        self.sink_url = sink_url.clone();
+        let (client, eventloop) = self
+            .config
+            .connection
+            .connect(&format!("tremor-{}-{}", hostname(), sink_uid), false)
+            .await?;
+        let (track_tx, track_rx) = bounded(crate::QSIZE);
+        task::spawn(run_eventloop(
+            eventloop,
+            track_rx,
+            reply_channel,
+            self.sink_url.clone(),
+        ));
+        self.client = Some(client);
+        self.track_tx = Some(track_tx);
+        Ok(())
+    }
+
+    fn is_active(&self) -> bool {
+        true
+    }
+
+    fn auto_ack(&self) -> bool {
+        false
+    }
+
+    fn default_codec(&self) -> &str {
+        "json"
+    }
+
+    async fn terminate(&mut self) {
+        self.track_tx = None;
+        if let Some(client) = self.client.take() {
+            if client.disconnect().await.is_err() {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::offramp::Impl;
+    use crate::ramp::mqtt::{Protocol, TlsConfig};
+
+    #[test]
+    fn config() -> Result<()> {
+        let config: Config = serde_yaml::from_str("topic: tremor/out")?;
+        assert_eq!("localhost", config.connection.host);
+        assert_eq!(1883, config.connection.port);
+        assert!(config.connection.clean_session);
+        assert_eq!(None, config.connection.tls);
+        assert_eq!(Protocol::V3, config.connection.protocol);
+        assert_eq!(1, config.qos);
+        assert!(!config.retain);
+
+        let config: Config = serde_yaml::from_str(
+            r#"
+host: broker
+port: 8883
+protocol: v5
+topic: tremor/out
+qos: 2
+tls:
+  ca_file: ca.pem
+"#,
+        )?;
+        assert_eq!("broker", config.connection.host);
+        assert_eq!(8883, config.connection.port);
+        assert_eq!(Protocol::V5, config.connection.protocol);
+        assert_eq!(
+            Some(TlsConfig {
+                ca_file: "ca.pem".to_string(),
+                cert_file: None,
+                key_file: None
+            }),
+            config.connection.tls
+        );
+        assert_eq!(QoS::ExactlyOnce, qos(config.qos)?);
+        Ok(())
+    }
+
+    #[test]
+    fn bad_qos() {
+        assert!(qos(3).is_err());
+        let config = serde_yaml::from_str("topic: tremor/out\nqos: 3").expect("valid yaml");
+        assert!(Mqtt::from_config(&Some(config)).is_err());
+    }
+
+    fn pending(id: u64) -> Pending {
+        Pending {
+            id: EventId::from((0, 0, id)),
+            ingest_ns: 0,
+            op_meta: OpMeta::default(),
+            transactional: true,
+        }
+    }
+
+    fn cb(insight: Option<Event>) -> Option<CbAction> {
+        insight.map(|insight| insight.cb)
+    }
+
+    #[test]
+    fn ack_once_all_payloads_settled() {
+        let mut tracker = Tracker::default();
+        assert!(tracker
+            .track(Track::Event {
+                seq: 0,
+                pending: pending(0),
+                publishes: 2,
+            })
+            .is_none());
+        assert!(tracker.track(Track::Published(0)).is_none());
+        assert!(tracker.track(Track::Published(0)).is_none());
+        assert!(tracker.outgoing(1).is_none());
+        assert!(tracker.outgoing(2).is_none());
+        assert!(tracker.acknowledged(1, true).is_none());
+        assert_eq!(Some(CbAction::Ack), cb(tracker.acknowledged(2, true)));
+        assert!(tracker.events.is_empty());
+
+        // QoS 0 publishes are settled once they are sent
+        tracker.track(Track::Event {
+            seq: 1,
+            pending: pending(1),
+            publishes: 1,
+        });
+        tracker.track(Track::Published(1));
+        assert_eq!(Some(CbAction::Ack), cb(tracker.outgoing(0)));
+    }
+
+    #[test]
+    fn fail_partially_published() {
+        let mut tracker = Tracker::default();
+        tracker.track(Track::Event {
+            seq: 0,
+            pending: pending(0),
+            publishes: 3,
+        });
+        tracker.track(Track::Published(0));
+        assert!(tracker
+            .track(Track::Failed {
+                seq: 0,
+                unpublished: 2
+            })
+            .is_none());
+        assert!(tracker.outgoing(1).is_none());
+        assert_eq!(Some(CbAction::Fail), cb(tracker.acknowledged(1, true)));
+    }
+
+    #[test]
+    fn fail_on_disconnect() {
+        let mut tracker = Tracker::default();
+        for seq in 0..2 {
+            tracker.track(Track::Event {
+                seq,
+                pending: pending(seq),
+                publishes: 1,
+            });
+            tracker.track(Track::Published(seq));
+        }
+        assert!(tracker.outgoing(1).is_none());
+        let insights = tracker.disconnected(true);
+        assert_eq!(
+            vec![CbAction::Fail],
+            insights.iter().map(|i| i.cb).collect::<Vec<_>>()
+        );
+        // the re-sent publish is skipped, the accepted one is sent after it
+        assert!(tracker.resends(1));
+        assert!(!tracker.resends(1));
+        assert!(tracker.outgoing(1).is_none());
+        assert_eq!(Some(CbAction::Ack), cb(tracker.acknowledged(1, true)));
+    }
+
+    #[test]
+    fn fail_refused() {
+        let mut tracker = Tracker::default();
+        for seq in 0..2 {
+            tracker.track(Track::Event {
+                seq,
+                pending: pending(seq),
+                publishes: 1,
+            });
+            tracker.track(Track::Published(seq));
+        }
+        assert!(tracker.outgoing(1).is_none());
+        assert_eq!(Some(CbAction::Fail), cb(tracker.acknowledged(1, false)));
+        // MQTT 5 publishes aren't re-sent, their packet ids are free again
+        assert!(tracker.outgoing(2).is_none());
+        assert_eq!(1, tracker.disconnected(false).len());
+        assert!(!tracker.resends(2));
+    }
+
+    #[test]
+    fn reuse_resent_pkid() {
+        let mut tracker = Tracker::default();
+        tracker.resent.insert(1);
+        // a new publish ends the re-sends, packet id 1 was never re-sent
+        assert!(!tracker.resends(2));
+        assert!(!tracker.resends(1));
+    }
+}
@@ -16,9 +16,11 @@ pub(crate) use crate::codec::Codec;
 pub(crate) use crate::errors::*;
 pub(crate) use crate::offramp::{self, Offramp};
 pub(crate) use crate::postprocessor::{
-    make_postprocessors, postprocess, Postprocessor, Postprocessors,
+    self, make_postprocessors, postprocess, Postprocessor, Postprocessors,
+};
+pub(crate) use crate::preprocessor::{
+    self, make_preprocessors, preprocess, Preprocessor, Preprocessors,
 };
-pub(crate) use crate::preprocessor::{make_preprocessors, preprocess, Preprocessor, Preprocessors};
 pub(crate) use crate::sink::{self, Reply, ResultVec, Sink, SinkManager};
 pub(crate) use crate::source::Processors;
 pub(crate) use crate::url::ports::{ERR, OUT};
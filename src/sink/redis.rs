@@ -0,0 +1,434 @@
+// Copyright 2020-2021, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#![cfg(not(tarpaulin_include))]
+use crate::sink::{prelude::*, Reply};
+use crate::source::prelude::*;
+use async_channel::Sender;
+use halfbrown::HashMap;
+use redis::aio::MultiplexedConnection;
+use redis::{Client, Value as RedisValue};
+use serde::Deserialize;
+use std::boxed::Box;
+use tremor_pipeline::EventIdGenerator;
+use tremor_value::literal;
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Config {
+    // redis url, e.g. `redis://localhost:6379/0`
+    #[serde(default = "default_url")]
+    url: String,
+}
+
+fn default_url() -> String {
+    "redis://localhost:6379".to_string()
+}
+
+pub struct Redis {
+    sink_url: TremorUrl,
+    event_origin_uri: EventOriginUri,
+    idgen: EventIdGenerator,
+    reply_tx: Sender<Reply>,
+    client: Client,
+    con: Option<MultiplexedConnection>,
+}
+
+/// Strings and bytes are sent as they are, everything else is encoded with the codec
+fn encode(v: &Value, codec: &dyn Codec) -> Result<Vec<u8>> {
+    if let Some(bytes) = v.as_bytes() {
+        Ok(bytes.to_vec())
+    } else {
+        codec.encode(v)
+    }
+}
+
+fn decode(v: RedisValue) -> Value<'static> {
+    match v {
+        RedisValue::Nil => Value::null(),
+        RedisValue::Int(i) => Value::from(i),
+        RedisValue::Data(data) => String::from_utf8(data)
+            .map_or_else(|e| Value::Bytes(e.into_bytes().into()), Value::from),
+        RedisValue::Bulk(values) => Value::from(values.into_iter().map(decode).collect::<Vec<_>>()),
+        RedisValue::Status(s) => Value::from(s),
+        RedisValue::Okay => Value::from("OK"),
+    }
+}
+
+fn fields<'v>(v: &'v Value<'v>) -> std::result::Result<Vec<(&'v str, &'v Value<'v>)>, String> {
+    let fields = v
+        .get_object("fields")
+        .ok_or_else(|| "Missing or invalid `fields` field".to_string())?;
+    if fields.is_empty() {
+        return Err("`fields` must not be empty".to_string());
+    }
+    Ok(fields.iter().map(|(k, v)| (k.as_ref(), v)).collect())
+}
+
+impl Redis {
+    async fn execute(&mut self, cmd: Command<'_>, codec: &dyn Codec) -> Result<Value<'static>> {
+        let con = self
+            .con
+            .as_mut()
+            .ok_or_else(|| Error::from("Not connected to redis"))?;
+        let c = match cmd {
+            Command::Xadd {
+                key,
+                fields,
+                maxlen,
+            } => {
+                let mut c = redis::cmd("XADD");
+                c.arg(key);
+                if let Some(maxlen) = maxlen {
+                    c.arg("MAXLEN").arg("~").arg(maxlen);
+                }
+                c.arg("*");
+                for (field, value) in fields {
+                    c.arg(field).arg(encode(value, codec)?);
+                }
+                c
+            }
+            Command::Publish { channel, message } => {
+                let mut c = redis::cmd("PUBLISH");
+                c.arg(channel).arg(encode(message, codec)?);
+                c
+            }
+            Command::Set { key, value, ttl_ms } => {
+                let mut c = redis::cmd("SET");
+                c.arg(key).arg(encode(value, codec)?);
+                if let Some(ttl_ms) = ttl_ms {
+                    c.arg("PX").arg(ttl_ms);
+                }
+                c
+            }
+            Command::Hset { key, fields } => {
+                let mut c = redis::cmd("HSET");
+                c.arg(key);
+                for (field, value) in fields {
+                    c.arg(field).arg(encode(value, codec)?);
+                }
+                c
+            }
+        };
+        let reply: RedisValue = c.query_async(con).await?;
+        Ok(literal!({ "ok": decode(reply) }))
+    }
+}
+
+impl offramp::Impl for Redis {
+    fn from_config(config: &Option<OpConfig>) -> Result<Box<dyn Offramp>> {
+        if let Some(config) = config {
+            let config: Config = serde_yaml::from_value(config.clone())?;
+
+            let client = Client::open(config.url.as_str())?;
+            let url = url::Url::parse(&config.url)?;
+            let event_origin_uri = EventOriginUri {
+                uid: 0,
+                scheme: "tremor-redis".to_string(),
+                host: url.host_str().unwrap_or("localhost").to_string(),
+                port: url.port(),
+                path: url
+                    .path_segments()
+                    .map(|s| {
+                        s.filter(|s| !s.is_empty())
+                            .map(ToString::to_string)
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+            };
+            // dummy
+            let (dummy_tx, _) = async_channel::bounded(1);
+
+            Ok(SinkManager::new_box(Redis {
+                sink_url: TremorUrl::from_offramp_id("redis")?, // dummy value
+                idgen: EventIdGenerator::new(0),
+                reply_tx: dummy_tx, // dummy, will be replaced in init
+                event_origin_uri,
+                client,
+                con: None,
+            }))
+        } else {
+            Err("[Redis Offramp] Offramp requires a config".into())
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Command<'v> {
+    /// ```json
+    /// {"xadd": {"key": "the-stream", "fields": {"data": "the-value"}, "maxlen": 1000}}
+    /// ```
+    Xadd {
+        key: Vec<u8>,
+        fields: Vec<(&'v str, &'v Value<'v>)>,
+        maxlen: Option<u64>,
+    },
+    /// ```json
+    /// {"publish": {"channel": "the-channel", "message": "the-value"}}
+    /// ```
+    Publish {
+        channel: Vec<u8>,
+        message: &'v Value<'v>,
+    },
+    /// ```json
+    /// {"set": {"key": "the-key", "value": "the-value", "ttl_ms": 60000}}
+    /// ```
+    Set {
+        key: Vec<u8>,
+        value: &'v Value<'v>,
+        ttl_ms: Option<u64>,
+    },
+    /// ```json
+    /// {"hset": {"key": "the-key", "fields": {"field": "the-value"}}}
+    /// ```
+    Hset {
+        key: Vec<u8>,
+        fields: Vec<(&'v str, &'v Value<'v>)>,
+    },
+}
+
+impl<'v> Command<'v> {
+    fn op_name(&self) -> &'static str {
+        match self {
+            Command::Xadd { .. } => "xadd",
+            Command::Publish { .. } => "publish",
+            Command::Set { .. } => "set",
+            Command::Hset { .. } => "hset",
+        }
+    }
+
+    fn key(&self) -> Vec<u8> {
+        match self {
+            Command::Xadd { key, .. } | Command::Set { key, .. } | Command::Hset { key, .. } => {
+                key.clone()
+            }
+            Command::Publish { channel, .. } => channel.clone(),
+        }
+    }
+
+    fn parse(v: &'v Value<'v>) -> std::result::Result<Self, String> {
+        if let Some(x) = v.get("xadd") {
+            let key = x
+                .get_bytes("key")
+                .ok_or_else(|| "Missing or invalid `key` field".to_string())?;
+            Ok(Command::Xadd {
+                key: key.to_vec(),
+                fields: fields(x)?,
+                maxlen: x.get_u64("maxlen"),
+            })
+        } else if let Some(p) = v.get("publish") {
+            let channel = p
+                .get_bytes("channel")
+                .ok_or_else(|| "Missing or invalid `channel` field".to_string())?;
+            let message = p
+                .get("message")
+                .ok_or_else(|| "Missing `message` field".to_string())?;
+            Ok(Command::Publish {
+                channel: channel.to_vec(),
+                message,
+            })
+        } else if let Some(s) = v.get("set") {
+            let key = s
+                .get_bytes("key")
+                .ok_or_else(|| "Missing or invalid `key` field".to_string())?;
+            let value = s
+                .get("value")
+                .ok_or_else(|| "Missing `value` field".to_string())?;
+            Ok(Command::Set {
+                key: key.to_vec(),
+                value,
+                ttl_ms: s.get_u64("ttl_ms"),
+            })
+        } else if let Some(h) = v.get("hset") {
+            let key = h
+                .get_bytes("key")
+                .ok_or_else(|| "Missing or invalid `key` field".to_string())?;
+            Ok(Command::Hset {
+                key: key.to_vec(),
+                fields: fields(h)?,
+            })
+        } else {
+            Err(format!("Invalid Redis command: {}", v))
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Sink for Redis {
+    async fn on_event(
+        &mut self,
+        _input: &str,
+        codec: &mut dyn Codec,
+        _codec_map: &HashMap<String, Box<dyn Codec>>,
+        event: Event,
+    ) -> ResultVec {
+        let mut r = Vec::with_capacity(10);
+        let ingest_ns = tremor_common::time::nanotime();
+
+        let mut first_error = None;
+        // note: we always try to execute all commands / handle all errors.
+        //       we might want to exit early in some cases though
+        for (v, m) in event.value_meta_iter() {
+            let correlation = m.get("correlation");
+            let executed = match Command::parse(v) {
+                Ok(cmd) => {
+                    let name = cmd.op_name();
+                    let key = cmd.key();
+                    self.execute(cmd, codec)
+                        .await
+                        .map(|res| (name, res))
+                        .map_err(|e| (Some(name), Some(key), e))
+                }
+                Err(e) => Err((None, None, ErrorKind::RedisCommandError(e).into())),
+            };
+            match executed {
+                Ok((op, data)) => {
+                    let mut id = self.idgen.next_id();
+                    id.track(&event.id);
+
+                    let mut meta = Value::object_with_capacity(2);
+                    meta.try_insert("redis", literal!({ "op": op }));
+                    if let Some(correlation) = correlation {
+                        meta.try_insert("correlation", correlation.clone_static());
+                    }
+                    let e = Event {
+                        id,
+                        ingest_ns,
+                        data: (data, meta).into(),
+                        origin_uri: Some(self.event_origin_uri.clone()),
+                        ..Event::default()
+                    };
+                    r.push(Reply::Response(OUT, e))
+                }
+                Err((op, key, e)) => {
+                    // send ERR response and log err
+                    let mut id = self.idgen.next_id();
+                    id.track(&event.id);
+                    let data = literal!({
+                        "key": key.map_or_else(Value::null, |v| Value::Bytes(v.into())),
+                        "error": e.to_string(),
+                    });
+                    let mut meta = Value::object_with_capacity(3);
+                    meta.try_insert("redis", literal!({ "op": op }));
+                    meta.try_insert("error", e.to_string());
+                    if let Some(correlation) = correlation {
+                        meta.try_insert("correlation", correlation.clone_static());
+                    }
+                    let err_event = Event {
+                        id,
+                        ingest_ns,
+                        data: (data, meta).into(),
+                        origin_uri: Some(self.event_origin_uri.clone()),
+                        ..Event::default()
+                    };
+                    r.push(Reply::Response(ERR, err_event));
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+        if let Some(e) = first_error {
+            // send away all response events asynchronously before
+            for reply in r {
+                if let Err(e) = self.reply_tx.send(reply).await {
+                    error!("[Sink::{}] Error sending error reply: {}", self.sink_url, e);
+                }
+            }
+            // trigger CB fail
+            Err(e)
+        } else {
+            Ok(Some(r))
+        }
+    }
+
+    async fn on_signal(&mut self, _signal: Event) -> ResultVec {
+        Ok(None)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn init(
+        &mut self,
+        sink_uid: u64,
+        sink_url: &TremorUrl,
+        _codec: &dyn Codec,
+        _codec_map: &HashMap<String, Box<dyn Codec>>,
+        _processors: Processors<'_>,
+        _is_linked: bool,
+        reply_channel: Sender<sink::Reply>,
+    ) -> Result<()> {
+        self.event_origin_uri.uid = sink_uid;
+        self.sink_url = sink_url.clone();
+        self.idgen.set_source(sink_uid);
+        self.reply_tx = reply_channel;
+        self.con = Some(self.client.get_multiplexed_async_connection().await?);
+        Ok(())
+    }
+
+    fn is_active(&self) -> bool {
+        true
+    }
+
+    fn auto_ack(&self) -> bool {
+        true
+    }
+
+    fn default_codec(&self) -> &str {
+        "json"
+    }
+
+    async fn terminate(&mut self) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() -> Result<()> {
+        let v = literal!({"set": {"key": "k", "value": {"a": 1}, "ttl_ms": 100}});
+        let cmd = Command::parse(&v)?;
+        assert_eq!("set", cmd.op_name());
+        assert_eq!(b"k".to_vec(), cmd.key());
+        assert!(matches!(
+            cmd,
+            Command::Set {
+                ttl_ms: Some(100),
+                ..
+            }
+        ));
+
+        let v = literal!({"xadd": {"key": "s", "fields": {"data": "snot"}}});
+        let cmd = Command::parse(&v)?;
+        assert!(matches!(cmd, Command::Xadd { ref fields, maxlen: None, .. } if fields.len() == 1));
+
+        let v = literal!({"publish": {"channel": "c", "message": "badger"}});
+        assert_eq!("publish", Command::parse(&v)?.op_name());
+
+        assert!(Command::parse(&literal!({"hset": {"key": "h", "fields": {}}})).is_err());
+        assert!(Command::parse(&literal!({"set": {"key": "k"}})).is_err());
+        assert!(Command::parse(&literal!({"get": {"key": "k"}})).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn decode_reply() {
+        assert_eq!(Value::from("OK"), decode(RedisValue::Okay));
+        assert_eq!(Value::from(3), decode(RedisValue::Int(3)));
+        assert_eq!(
+            Value::from(vec![Value::from("snot"), Value::null()]),
+            decode(RedisValue::Bulk(vec![
+                RedisValue::Data(b"snot".to_vec()),
+                RedisValue::Nil
+            ]))
+        );
+    }
+}
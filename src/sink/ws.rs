@@ -70,8 +70,8 @@ pub struct Ws {
     sink_url: TremorUrl,
     event_origin_uri: EventOriginUri,
     config: Config,
-    preprocessors: Vec<String>,
-    postprocessors: Vec<String>,
+    preprocessors: Vec<preprocessor::Config>,
+    postprocessors: Vec<postprocessor::Config>,
     shared_codec: Box<dyn Codec>,
     connection_lifecycle_tx: Sender<WsConnectionMsg>,
     connection_lifecycle_rx: Receiver<WsConnectionMsg>,
@@ -717,7 +717,7 @@ mod test {
     fn message_to_event_ok() -> Result<()> {
         let sink_url = TremorUrl::parse("/offramp/ws/instance")?;
         let origin_uri = EventOriginUri::default();
-        let mut preprocessors = make_preprocessors(&["lines".to_string()])?;
+        let mut preprocessors = make_preprocessors(&["lines".into()])?;
         let mut ingest_ns = 42_u64;
         let ids = EventId::default();
         let mut codec: Box<dyn Codec> = Box::new(codec::string::String {});
@@ -747,7 +747,7 @@ mod test {
     fn event_to_message_ok() -> Result<()> {
         let mut codec: Box<dyn Codec> =
             Box::new(codec::json::Json::<codec::json::Unsorted>::default());
-        let mut postprocessors = make_postprocessors(&["lines".to_string()])?;
+        let mut postprocessors = make_postprocessors(&["lines".into()])?;
         let mut data = Value::object_with_capacity(2);
         data.insert("snot", "badger")?;
         data.insert("empty", Value::object())?;
@@ -780,8 +780,8 @@ mod test {
             sink_url: url.clone(),
             event_origin_uri: EventOriginUri::default(),
             config: config.clone(),
-            preprocessors: vec!["lines".to_string()],
-            postprocessors: vec!["lines".to_string()],
+            preprocessors: vec!["lines".into()],
+            postprocessors: vec!["lines".into()],
             shared_codec: codec.boxed_clone(),
             connection_lifecycle_rx: conn_rx,
             connection_lifecycle_tx: conn_tx,
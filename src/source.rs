@@ -12,11 +12,14 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use crate::errors::Error;
+use crate::dlq::DeadLetter;
+use crate::errors::{Error, ErrorKind};
 use crate::metrics::RampReporter;
 use crate::onramp;
 use crate::pipeline;
-use crate::preprocessor::{make_preprocessors, preprocess, Preprocessors};
+use crate::postprocessor;
+use crate::preprocessor::{self, make_preprocessors, preprocess, Preprocessors};
+use crate::tap::{self, Tap};
 use crate::url::ports::{ERR, METRICS, OUT};
 use crate::url::TremorUrl;
 use crate::{
@@ -37,22 +40,29 @@ use tremor_script::prelude::*;
 
 use self::prelude::OnrampConfig;
 
+/// interval in which preprocessors get to flush data they held back for too long
+const PP_TICK_NS: u64 = 100_000_000;
+
 pub(crate) mod amqp;
 pub(crate) mod blaster;
 pub(crate) mod cb;
 pub(crate) mod crononome;
+pub(crate) mod dir;
 pub(crate) mod discord;
 pub(crate) mod file;
 pub(crate) mod gsub;
 pub(crate) mod kafka;
 pub(crate) mod metronome;
+pub(crate) mod mqtt;
 pub(crate) mod nats;
 pub(crate) mod otel;
 pub(crate) mod postgres;
 pub(crate) mod prelude;
+pub(crate) mod redis;
 pub(crate) mod rest;
 pub(crate) mod sse;
 pub(crate) mod stdin;
+pub(crate) mod tail;
 pub(crate) mod tcp;
 pub(crate) mod udp;
 pub(crate) mod ws;
@@ -63,9 +73,9 @@ struct StaticValue(Value<'static>);
 /// Set of pre and postprocessors
 pub struct Processors<'processor> {
     /// preprocessors
-    pub pre: &'processor [String],
+    pub pre: &'processor [preprocessor::Config],
     /// postprocessors
-    pub post: &'processor [String],
+    pub post: &'processor [postprocessor::Config],
 }
 
 #[derive(Debug)]
@@ -162,15 +172,48 @@ pub(crate) trait Source {
     }
 }
 
-fn make_error(source_id: String, e: &Error, original_id: u64) -> tremor_script::EventPayload {
-    error!("[Source::{}] Error decoding event data: {}", source_id, e);
+/// Data that failed to preprocess or decode
+struct Undecodable {
+    error: Error,
+    /// the stage that failed
+    stage: &'static str,
+    /// the codec the data is decoded with
+    codec: String,
+    /// the preprocessors `raw` still has to pass
+    preprocessors: Vec<String>,
+    /// the raw data, only kept while the `err` port is connected
+    raw: Option<Vec<u8>>,
+}
+
+type Decoded = std::result::Result<EventPayload, Undecodable>;
+
+/// The codec data is decoded with
+enum Decoder {
+    /// the codec of a stream, keeping its state between decodes
+    Stream(usize),
+    /// a codec of its own
+    Codec(Box<dyn Codec>),
+}
+
+fn make_error(source_id: String, e: Undecodable, original_id: u64) -> tremor_script::EventPayload {
+    error!(
+        "[Source::{}] Error decoding event data: {}",
+        source_id, e.error
+    );
     let mut meta = Object::with_capacity(1);
-    meta.insert_nocheck("error".into(), e.to_string().into());
+    meta.insert_nocheck("error".into(), e.error.to_string().into());
 
-    let mut data = Object::with_capacity(3);
-    data.insert_nocheck("error".into(), e.to_string().into());
+    let mut data = Object::with_capacity(7);
+    data.insert_nocheck("error".into(), e.error.to_string().into());
     data.insert_nocheck("event_id".into(), original_id.into());
     data.insert_nocheck("source_id".into(), source_id.into());
+    data.insert_nocheck("stage".into(), e.stage.into());
+    data.insert_nocheck("codec".into(), e.codec.into());
+    data.insert_nocheck("preprocessors".into(), e.preprocessors.into());
+    data.insert_nocheck(
+        "raw".into(),
+        e.raw.map(|raw| Value::Bytes(raw.into())).into(),
+    );
     (Value::from(data), Value::from(meta)).into()
 }
 
@@ -182,15 +225,22 @@ where
     source: T,
     rx: Receiver<onramp::Msg>,
     tx: Sender<onramp::Msg>,
-    pp_template: Vec<String>,
+    pp_template: Vec<preprocessor::Config>,
     preprocessors: BTreeMap<usize, Preprocessors>,
+    /// the configured codec, every stream decodes with its own instance of it
     codec: Box<dyn Codec>,
+    /// codecs of the streams, codecs like csv keep state between the lines of a stream
+    codecs: BTreeMap<usize, Box<dyn Codec>>,
+    /// origins of the streams, for data flushed by their preprocessors
+    origins: BTreeMap<usize, EventOriginUri>,
+    next_pp_tick: u64,
     codec_map: HashMap<String, Box<dyn Codec>>,
     metrics_reporter: RampReporter,
     triggered: bool,
     pipelines_out: Vec<(TremorUrl, pipeline::Addr)>,
     pipelines_err: Vec<(TremorUrl, pipeline::Addr)>,
     err_required: bool,
+    taps: Vec<Tap>,
     id: u64,
     is_transactional: bool,
     /// Unique Id for the source
@@ -230,46 +280,201 @@ where
         codec_override: Option<String>,
         data: Vec<u8>,
         meta: Option<StaticValue>, // See: https://github.com/rust-lang/rust/issues/63033
-    ) -> Vec<Result<EventPayload>> {
-        let mut results = vec![];
+    ) -> Vec<Decoded> {
+        // the raw data is only of use to dead letters on the err port
+        let raw = if self.pipelines_err.is_empty() {
+            None
+        } else {
+            Some(data.clone())
+        };
         match self.handle_pp(stream, ingest_ns, data) {
-            Ok(data) => {
-                let meta_value = meta.map_or_else(Value::object, |m| m.0);
-                for d in data {
-                    let line_value = EventPayload::try_new::<Option<Error>, _>(d, |mut_data| {
-                        let codec_map = &mut self.codec_map;
-                        let codec = codec_override
-                            .as_ref()
-                            .and_then(|codec_name| codec_map.get_mut(codec_name))
-                            .unwrap_or(&mut self.codec);
-                        let decoded = codec.decode(mut_data, *ingest_ns);
-                        match decoded {
-                            Ok(None) => Err(None),
-                            Err(e) => Err(Some(e)),
-                            Ok(Some(decoded)) => {
-                                Ok(ValueAndMeta::from_parts(decoded, meta_value.clone()))
-                            }
-                        }
-                    });
-                    match line_value {
-                        Ok(decoded) => results.push(Ok(decoded)),
-                        Err(None) => (),
-                        Err(Some(e)) => {
-                            // TODO: add error context (with error handling update)
-                            results.push(Err(e));
-                        }
-                    }
-                }
+            Ok(data) => self.decode(
+                Decoder::Stream(stream),
+                *ingest_ns,
+                codec_override,
+                data,
+                meta,
+            ),
+            Err(error) => {
+                // record preprocessor failures too
+                let codec = codec_override
+                    .as_ref()
+                    .and_then(|codec_name| self.codec_map.get(codec_name))
+                    .unwrap_or(&self.codec)
+                    .name()
+                    .to_string();
+                vec![Err(Undecodable {
+                    error,
+                    stage: "preprocessor",
+                    codec,
+                    preprocessors: self
+                        .pp_template
+                        .iter()
+                        .map(|pp| pp.name().to_string())
+                        .collect(),
+                    raw,
+                })]
             }
+        }
+    }
+
+    /// sends out data the preprocessors of `stream` held back, all of it at the end of the stream,
+    /// otherwise only what was held back for too long
+    async fn flush_preprocessors(&mut self, stream: usize, end_of_stream: bool) {
+        let mut ingest_ns = nanotime();
+        let flushed = if let Some(preprocessors) = self.preprocessors.get_mut(&stream) {
+            preprocessor::flush(
+                preprocessors.as_mut_slice(),
+                &mut ingest_ns,
+                end_of_stream,
+                &self.source_id,
+            )
+        } else {
+            return;
+        };
+        let data = match flushed {
+            Ok(data) => data,
             Err(e) => {
-                // record preprocessor failures too
-                // TODO: add error context (with error handling update)
-                results.push(Err(e));
+                warn!(
+                    "[Source::{}] Failed to flush preprocessors of stream {}: {}",
+                    self.source_id, stream, e
+                );
+                self.metrics_reporter.increment_err();
+                return;
+            }
+        };
+        if data.is_empty() {
+            return;
+        }
+        let origin_uri = self
+            .origins
+            .get(&stream)
+            .cloned()
+            .unwrap_or_else(|| EventOriginUri {
+                uid: self.uid,
+                ..EventOriginUri::default()
+            });
+        let results = self.decode(Decoder::Stream(stream), ingest_ns, None, data, None);
+        let original_id = self.id;
+        if self
+            .route_result(results, original_id, ingest_ns, origin_uri)
+            .await
+        {
+            self.source.fail(original_id);
+        }
+    }
+
+    /// decodes `data` with the codec given by `decoder`, unless a codec of the
+    /// codec map is selected by `codec_override`
+    fn decode(
+        &mut self,
+        mut decoder: Decoder,
+        ingest_ns: u64,
+        codec_override: Option<String>,
+        data: Vec<Vec<u8>>,
+        meta: Option<StaticValue>,
+    ) -> Vec<Decoded> {
+        let keep_raw = !self.pipelines_err.is_empty();
+        let meta_value = meta.map_or_else(Value::object, |m| m.0);
+        let mut results = vec![];
+        for d in data {
+            let raw = if keep_raw { Some(d.clone()) } else { None };
+            let line_value = EventPayload::try_new::<Option<(Error, String)>, _>(d, |mut_data| {
+                let Self {
+                    codec: template,
+                    codecs,
+                    codec_map,
+                    ..
+                } = &mut *self;
+                let overridden = codec_override
+                    .as_ref()
+                    .and_then(|codec_name| codec_map.get_mut(codec_name));
+                let codec = match (overridden, &mut decoder) {
+                    (Some(codec), _) => codec,
+                    (None, Decoder::Stream(stream)) => codecs
+                        .entry(*stream)
+                        .or_insert_with(|| template.boxed_clone()),
+                    (None, Decoder::Codec(codec)) => codec,
+                };
+                let decoded = codec.decode(mut_data, ingest_ns);
+                match decoded {
+                    Ok(None) => Err(None),
+                    Err(e) => Err(Some((e, codec.name().to_string()))),
+                    Ok(Some(decoded)) => Ok(ValueAndMeta::from_parts(decoded, meta_value.clone())),
+                }
+            });
+            match line_value {
+                Ok(decoded) => results.push(Ok(decoded)),
+                Err(None) => (),
+                Err(Some((error, codec))) => results.push(Err(Undecodable {
+                    error,
+                    stage: "codec",
+                    codec,
+                    preprocessors: vec![],
+                    raw,
+                })),
             }
         }
         results
     }
 
+    /// Runs a dead letter of this onramp through the preprocessors it still
+    /// has to pass and its codec again. Replays are no part of any running
+    /// stream, so the raw data of letters that failed in the preprocessor
+    /// stage passes fresh preprocessors, without the state that stateful ones
+    /// like `multiline` had built up for the stream it failed in.
+    async fn replay(&mut self, letter: DeadLetter) -> Result<()> {
+        if self.pipelines_out.is_empty() {
+            return Err(ErrorKind::InvalidDeadLetter(format!(
+                "{} has no pipelines connected to replay into",
+                self.source_id
+            ))
+            .into());
+        }
+        let data = letter.raw()?;
+        let mut ingest_ns = nanotime();
+        let data = if letter.preprocessors.is_empty() {
+            vec![data]
+        } else {
+            // fresh preprocessors, as replays are no part of any running stream
+            let mut preprocessors = make_preprocessors(&self.pp_template)?;
+            preprocess(
+                preprocessors.as_mut_slice(),
+                &mut ingest_ns,
+                data,
+                &self.source_id,
+            )?
+        };
+        // a fresh codec as well, decoding the letter must not depend on the state of any stream.
+        // The letter names its codec, the codec map is keyed by mime type though, so codecs
+        // of the map are found by their name to keep their config
+        let name = letter.codec.as_deref().unwrap_or_else(|| self.codec.name());
+        let codec = if name == self.codec.name() {
+            self.codec.boxed_clone()
+        } else if let Some(codec) = self.codec_map.values().find(|c| c.name() == name) {
+            codec.boxed_clone()
+        } else {
+            codec::lookup(name)?
+        };
+        let results = self.decode(Decoder::Codec(codec), ingest_ns, None, data, None);
+        let origin_uri = EventOriginUri {
+            uid: self.uid,
+            scheme: "tremor-dlq".to_string(),
+            host: crate::utils::hostname(),
+            port: None,
+            path: vec![letter.id.to_string()],
+        };
+        let original_id = self.id;
+        if self
+            .route_result(results, original_id, ingest_ns, origin_uri)
+            .await
+        {
+            Err(format!("failed to replay dead letter {}", letter.id).into())
+        } else {
+            Ok(())
+        }
+    }
+
     fn needs_pipeline_msg(&self) -> bool {
         self.pipelines_out.is_empty()
             || self.triggered
@@ -312,6 +517,7 @@ where
                             };
                             let msg = pipeline::MgmtMsg::ConnectInput {
                                 input_url: self.source_id.clone(),
+                                port: p.0.instance_port().unwrap_or("in").to_string().into(),
                                 target: ConnectTarget::Onramp(self.tx.clone()),
                                 transactional: self.is_transactional,
                             };
@@ -370,6 +576,26 @@ where
                 }
                 onramp::Msg::Cb(CbAction::None, _ids) => {}
 
+                onramp::Msg::Tap(tap) => {
+                    info!(
+                        "[Source::{}] Attaching tap to {}",
+                        self.source_id,
+                        tap.url()
+                    );
+                    tap::detach(&mut self.taps);
+                    self.taps.push(tap);
+                }
+                onramp::Msg::Replay(letter, tx) => {
+                    let id = letter.id;
+                    let res = self.replay(letter).await;
+                    if let Err(e) = &res {
+                        warn!(
+                            "[Source::{}] Failed to replay dead letter {}: {}",
+                            self.source_id, id, e
+                        );
+                    }
+                    tx.send(res).await?;
+                }
                 onramp::Msg::Response(event) => {
                     if let Err(e) = self
                         .source
@@ -412,6 +638,11 @@ where
         } else {
             return false;
         };
+        if !self.taps.is_empty() {
+            if tap::offer(&mut self.taps, None, &port, &event) {
+                tap::detach(&mut self.taps);
+            }
+        }
         if let Some((last, pipelines)) = pipelines.split_last_mut() {
             if let Some(t) = self.metrics_reporter.periodic_flush(ingest_ns) {
                 self.metrics_reporter.send(self.source.metrics(t))
@@ -479,15 +710,15 @@ where
         // N is the maximum number of counterflow events a single event can trigger.
         // N is normally < 1.
         let (tx, rx) = unbounded();
-        let codec = codec::lookup(&config.codec)?;
+        let codec = codec::resolve(config.codec)?;
         let mut resolved_codec_map = codec::builtin_codec_map();
         // override the builtin map
         for (k, v) in config.codec_map {
-            resolved_codec_map.insert(k, codec::lookup(&v)?);
+            resolved_codec_map.insert(k, codec::resolve(&v)?);
         }
         let pp_template = config.processors.pre.to_vec();
         let mut preprocessors = BTreeMap::new();
-        preprocessors.insert(0, make_preprocessors(&&pp_template)?);
+        preprocessors.insert(0, make_preprocessors(&pp_template)?);
 
         source.init().await?;
         let is_transactional = source.is_transactional();
@@ -501,6 +732,9 @@ where
                 preprocessors,
                 //postprocessors,
                 codec,
+                codecs: BTreeMap::new(),
+                origins: BTreeMap::new(),
+                next_pp_tick: 0,
                 codec_map: resolved_codec_map,
                 metrics_reporter: config.metrics_reporter,
                 triggered: false,
@@ -510,6 +744,7 @@ where
                 uid: config.onramp_uid,
                 is_transactional,
                 err_required: config.err_required,
+                taps: Vec::new(),
             },
             tx,
         ))
@@ -524,7 +759,7 @@ where
 
     async fn route_result(
         &mut self,
-        results: Vec<Result<tremor_script::EventPayload>>,
+        results: Vec<Decoded>,
         original_id: u64,
         ingest_ns: u64,
         origin_uri: EventOriginUri,
@@ -532,7 +767,7 @@ where
         let mut error = false;
         for result in results {
             let (port, data) = result.map_or_else(
-                |e| (ERR, make_error(self.source_id.to_string(), &e, original_id)),
+                |e| (ERR, make_error(self.source_id.to_string(), e, original_id)),
                 |data| (OUT, data),
             );
             error |= self
@@ -552,13 +787,25 @@ where
             let pipelines_out_empty = self.pipelines_out.is_empty();
 
             if !self.triggered && !pipelines_out_empty {
+                let now = nanotime();
+                if now >= self.next_pp_tick {
+                    self.next_pp_tick = now + PP_TICK_NS;
+                    let streams: Vec<usize> = self.preprocessors.keys().copied().collect();
+                    for stream in streams {
+                        self.flush_preprocessors(stream, false).await;
+                    }
+                }
                 match self.source.pull_event(self.id).await {
                     Ok(SourceReply::StartStream(id)) => {
                         self.preprocessors
                             .insert(id, make_preprocessors(&self.pp_template)?);
+                        self.codecs.insert(id, self.codec.boxed_clone());
                     }
                     Ok(SourceReply::EndStream(id)) => {
+                        self.flush_preprocessors(id, true).await;
                         self.preprocessors.remove(&id);
+                        self.codecs.remove(&id);
+                        self.origins.remove(&id);
                     }
                     Ok(SourceReply::Structured { origin_uri, data }) => {
                         let ingest_ns = nanotime();
@@ -575,6 +822,9 @@ where
                             origin_uri.maybe_set_uid(self.uid);
                             let mut ingest_ns = nanotime();
 
+                            self.origins
+                                .entry(stream)
+                                .or_insert_with(|| origin_uri.clone());
                             let original_id = self.id;
                             let results = self
                                 .make_event_data(
@@ -609,6 +859,9 @@ where
                         origin_uri.maybe_set_uid(self.uid);
                         let mut ingest_ns = nanotime();
 
+                        self.origins
+                            .entry(stream)
+                            .or_insert_with(|| origin_uri.clone());
                         let original_id = self.id;
                         let results = self
                             .make_event_data(
@@ -651,6 +904,7 @@ where
 #[cfg(test)]
 mod tests {
     use super::*;
+    use tremor_value::literal;
 
     #[derive(Debug)]
     struct FakeSource {
@@ -686,7 +940,7 @@ mod tests {
         };
         let o_config = OnrampConfig {
             onramp_uid: 1,
-            codec: "string",
+            codec: &codec::Config::from("string"),
             codec_map: HashMap::new(),
             processors: Processors::default(),
             metrics_reporter: RampReporter::new(onramp_url.clone(), None),
@@ -752,35 +1006,276 @@ mod tests {
         Ok(())
     }
 
+    #[derive(Debug)]
+    struct StreamsSource {
+        url: TremorUrl,
+        replies: Vec<SourceReply>,
+    }
+
+    #[async_trait::async_trait]
+    impl Source for StreamsSource {
+        async fn pull_event(&mut self, _id: u64) -> Result<SourceReply> {
+            if self.replies.is_empty() {
+                Ok(SourceReply::Empty(10))
+            } else {
+                Ok(self.replies.remove(0))
+            }
+        }
+
+        async fn init(&mut self) -> Result<SourceState> {
+            Ok(SourceState::Connected)
+        }
+
+        fn id(&self) -> &TremorUrl {
+            &self.url
+        }
+    }
+
+    #[async_std::test]
+    async fn codec_state_per_stream() -> Result<()> {
+        let onramp_url = TremorUrl::from_onramp_id("streams")?;
+        let line = |stream: usize, data: &str| SourceReply::Data {
+            origin_uri: EventOriginUri::default(),
+            codec_override: None,
+            stream,
+            data: data.as_bytes().to_vec(),
+            meta: None,
+        };
+        let s = StreamsSource {
+            url: onramp_url.clone(),
+            replies: vec![
+                SourceReply::StartStream(1),
+                line(1, "a,b"),
+                line(1, "1,2"),
+                SourceReply::EndStream(1),
+                SourceReply::StartStream(2),
+                line(2, "c,d"),
+                line(2, "3,4"),
+                SourceReply::EndStream(2),
+            ],
+        };
+        let codec: codec::Config =
+            serde_yaml::from_str("{name: csv, config: {header_line: true}}")?;
+        let o_config = OnrampConfig {
+            onramp_uid: 1,
+            codec: &codec,
+            codec_map: HashMap::new(),
+            processors: Processors::default(),
+            metrics_reporter: RampReporter::new(onramp_url.clone(), None),
+            is_linked: false,
+            err_required: false,
+        };
+        let (sm, sender) = SourceManager::new(s, o_config).await?;
+        let handle = task::spawn(sm.run());
+
+        let pipeline_url = TremorUrl::parse("/pipeline/bla/01/in")?;
+        let (tx1, rx1) = async_channel::unbounded();
+        let (tx2, _rx2) = async_channel::unbounded();
+        let (tx3, rx3) = async_channel::unbounded();
+        let addr = pipeline::Addr::new(tx1, tx2, tx3, pipeline_url.clone());
+        sender
+            .send(onramp::Msg::Connect(OUT, vec![(pipeline_url, addr)]))
+            .await?;
+        rx3.recv().await?;
+        sender
+            .send(onramp::Msg::Cb(CbAction::Open, EventId::default()))
+            .await?;
+
+        // every stream starts with its own header line
+        let mut records = Vec::new();
+        while records.len() < 2 {
+            if let pipeline::Msg::Event { event, .. } = rx1.recv().await? {
+                records.push(event.data.suffix().value().clone_static());
+            }
+        }
+        assert_eq!(
+            vec![
+                literal!({"a": "1", "b": "2"}),
+                literal!({"c": "3", "d": "4"})
+            ],
+            records
+        );
+        handle.cancel().await;
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn flush_preprocessors_on_end_of_stream() -> Result<()> {
+        let onramp_url = TremorUrl::from_onramp_id("streams")?;
+        let line = |data: &str| SourceReply::Data {
+            origin_uri: EventOriginUri::default(),
+            codec_override: None,
+            stream: 1,
+            data: data.as_bytes().to_vec(),
+            meta: None,
+        };
+        let s = StreamsSource {
+            url: onramp_url.clone(),
+            replies: vec![
+                SourceReply::StartStream(1),
+                line("Traceback"),
+                line("  snot.py"),
+                SourceReply::EndStream(1),
+            ],
+        };
+        let pre = vec![preprocessor::Config::from("multiline")];
+        let o_config = OnrampConfig {
+            onramp_uid: 1,
+            codec: &codec::Config::from("string"),
+            codec_map: HashMap::new(),
+            processors: Processors {
+                pre: &pre,
+                post: &[],
+            },
+            metrics_reporter: RampReporter::new(onramp_url.clone(), None),
+            is_linked: false,
+            err_required: false,
+        };
+        let (sm, sender) = SourceManager::new(s, o_config).await?;
+        let handle = task::spawn(sm.run());
+
+        let pipeline_url = TremorUrl::parse("/pipeline/bla/01/in")?;
+        let (tx1, rx1) = async_channel::unbounded();
+        let (tx2, _rx2) = async_channel::unbounded();
+        let (tx3, rx3) = async_channel::unbounded();
+        let addr = pipeline::Addr::new(tx1, tx2, tx3, pipeline_url.clone());
+        sender
+            .send(onramp::Msg::Connect(OUT, vec![(pipeline_url, addr)]))
+            .await?;
+        rx3.recv().await?;
+        sender
+            .send(onramp::Msg::Cb(CbAction::Open, EventId::default()))
+            .await?;
+
+        // the final event isn't followed by another line
+        let event = loop {
+            if let pipeline::Msg::Event { event, .. } = rx1.recv().await? {
+                break event;
+            }
+        };
+        assert_eq!(
+            &Value::from("Traceback\n  snot.py"),
+            event.data.suffix().value()
+        );
+        handle.cancel().await;
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn replay_with_codec_of_codec_map() -> Result<()> {
+        let onramp_url = TremorUrl::from_onramp_id("streams")?;
+        let s = StreamsSource {
+            url: onramp_url.clone(),
+            replies: vec![],
+        };
+        let mut codec_map = HashMap::new();
+        codec_map.insert("application/json".to_string(), codec::Config::from("json"));
+        let o_config = OnrampConfig {
+            onramp_uid: 1,
+            codec: &codec::Config::from("string"),
+            codec_map,
+            processors: Processors::default(),
+            metrics_reporter: RampReporter::new(onramp_url.clone(), None),
+            is_linked: false,
+            err_required: false,
+        };
+        let (sm, sender) = SourceManager::new(s, o_config).await?;
+        let handle = task::spawn(sm.run());
+
+        let pipeline_url = TremorUrl::parse("/pipeline/bla/01/in")?;
+        let (tx1, rx1) = async_channel::unbounded();
+        let (tx2, _rx2) = async_channel::unbounded();
+        let (tx3, rx3) = async_channel::unbounded();
+        let addr = pipeline::Addr::new(tx1, tx2, tx3, pipeline_url.clone());
+        sender
+            .send(onramp::Msg::Connect(OUT, vec![(pipeline_url, addr)]))
+            .await?;
+        rx3.recv().await?;
+
+        // the letter names the codec, the codec map is keyed by mime type
+        let letter = DeadLetter {
+            id: 1,
+            onramp: Some(onramp_url.to_string()),
+            stage: "codec".to_string(),
+            codec: Some("json".to_string()),
+            preprocessors: vec![],
+            error: "snot".to_string(),
+            raw: Some(base64::encode(r#"{"snot": "badger"}"#)),
+            pipeline: None,
+            operator: None,
+            event: None,
+            ingest_ns: 0,
+        };
+        let (tx, rx) = async_channel::bounded(1);
+        sender.send(onramp::Msg::Replay(letter, tx)).await?;
+        rx.recv().await??;
+        let event = loop {
+            if let pipeline::Msg::Event { event, .. } = rx1.recv().await? {
+                break event;
+            }
+        };
+        assert_eq!(&literal!({"snot": "badger"}), event.data.suffix().value());
+        handle.cancel().await;
+        Ok(())
+    }
+
     #[test]
     fn make_error() {
         let source_id = "snot".to_string();
         let e = Error::from("oh no!");
         let original_id = 5;
-        let error_result = super::make_error(source_id.clone(), &e, original_id);
+        let error_result = super::make_error(
+            source_id.clone(),
+            Undecodable {
+                error: Error::from("oh no!"),
+                stage: "codec",
+                codec: "json".to_string(),
+                preprocessors: vec![],
+                raw: None,
+            },
+            original_id,
+        );
         let mut expec_meta = Object::with_capacity(1);
         expec_meta.insert_nocheck("error".into(), e.to_string().into());
 
-        let mut expec_data = Object::with_capacity(3);
+        let mut expec_data = Object::with_capacity(7);
         expec_data.insert_nocheck("error".into(), e.to_string().into());
         expec_data.insert_nocheck("event_id".into(), original_id.into());
         expec_data.insert_nocheck("source_id".into(), source_id.into());
+        expec_data.insert_nocheck("stage".into(), "codec".into());
+        expec_data.insert_nocheck("codec".into(), "json".into());
+        expec_data.insert_nocheck("preprocessors".into(), Value::array());
+        expec_data.insert_nocheck("raw".into(), Value::null());
 
         assert_eq!(error_result.suffix().value(), &Value::from(expec_data));
         assert_eq!(error_result.suffix().meta(), &Value::from(expec_meta));
 
-        // testing with a second set of inputs
+        // testing with a second set of inputs, keeping the raw data
         let source_id = "tremor-source-testing".to_string();
         let e = Error::from("error oh no!!!");
         let original_id = 7;
-        let error_result = super::make_error(source_id.clone(), &e, original_id);
+        let error_result = super::make_error(
+            source_id.clone(),
+            Undecodable {
+                error: Error::from("error oh no!!!"),
+                stage: "preprocessor",
+                codec: "string".to_string(),
+                preprocessors: vec!["base64".to_string(), "lines".to_string()],
+                raw: Some(b"snot".to_vec()),
+            },
+            original_id,
+        );
         let mut expec_meta = Object::with_capacity(1);
         expec_meta.insert_nocheck("error".into(), e.to_string().into());
 
-        let mut expec_data = Object::with_capacity(3);
+        let mut expec_data = Object::with_capacity(7);
         expec_data.insert_nocheck("error".into(), e.to_string().into());
         expec_data.insert_nocheck("event_id".into(), original_id.into());
         expec_data.insert_nocheck("source_id".into(), source_id.into());
+        expec_data.insert_nocheck("stage".into(), "preprocessor".into());
+        expec_data.insert_nocheck("codec".into(), "string".into());
+        expec_data.insert_nocheck("preprocessors".into(), Value::from(vec!["base64", "lines"]));
+        expec_data.insert_nocheck("raw".into(), Value::Bytes(b"snot".to_vec().into()));
 
         assert_eq!(error_result.suffix().value(), &Value::from(expec_data));
         assert_eq!(error_result.suffix().meta(), &Value::from(expec_meta));
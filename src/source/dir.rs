@@ -0,0 +1,615 @@
+// Copyright 2020-2021, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! # Directory onramp
+//!
+//! Watches a spool directory for files dropped into it and ingests each of
+//! them as a stream of its own, so stateful preprocessors like `lines` or
+//! `gzip` start fresh for every file.
+//!
+//! Once all events of a file were acknowledged it is moved to `done_dir`, or
+//! deleted if there is none. A file with failed events is left in place and
+//! ingested again. Acknowledgements cover all events up to the acknowledged
+//! one. Files that weren't done on shutdown are ingested again on restart.
+//!
+//! ## Configuration
+//!
+//! See [Config](struct.Config.html) for details.
+
+use crate::preprocessor;
+use crate::source::prelude::*;
+use async_std::fs::File as FSFile;
+use async_std::io::ReadExt;
+use async_std::path::{Path, PathBuf};
+use std::collections::{HashSet, VecDeque};
+use std::time::{Duration, Instant, SystemTime};
+use tremor_common::asy::file;
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Config {
+    /// directory to watch
+    pub dir: String,
+    /// glob pattern of the filenames to ingest
+    #[serde(default = "Config::default_pattern")]
+    pub pattern: String,
+    /// directory to move done files to, they are deleted if not set
+    #[serde(default = "Default::default")]
+    pub done_dir: Option<String>,
+    /// minimum age in milliseconds since a file was last modified, so files
+    /// that are still being written aren't picked up
+    #[serde(default = "Config::default_min_age_ms")]
+    pub min_age_ms: u64,
+    /// size of the chunks files are read in, files are read as a whole with
+    /// `0`. Chunks need a framing preprocessor like `lines` and can't be
+    /// decompressed.
+    #[serde(default = "Default::default")]
+    pub chunk_size: usize,
+    /// interval in milliseconds to look for new files
+    #[serde(default = "Config::default_poll_interval_ms")]
+    pub poll_interval_ms: u64,
+}
+
+impl Config {
+    fn default_pattern() -> String {
+        "*".to_string()
+    }
+
+    fn default_min_age_ms() -> u64 {
+        1000
+    }
+
+    fn default_poll_interval_ms() -> u64 {
+        1000
+    }
+}
+
+impl ConfigImpl for Config {}
+
+/// preprocessors framing the events of a file read in chunks
+const FRAMING: [&str; 7] = [
+    "lines",
+    "lines-null",
+    "lines-pipe",
+    "lines-no-buffer",
+    "lines-cr-no-buffer",
+    "length-prefixed",
+    "textual-length-prefix",
+];
+
+/// preprocessors that need the whole file
+const WHOLE_FILE: [&str; 7] = ["gzip", "zlib", "xz2", "snappy", "lz4", "zstd", "decompress"];
+
+/// Checks that files read in chunks get their events framed
+fn check_chunks(chunk_size: usize, pre: &[preprocessor::Config]) -> Result<()> {
+    if chunk_size == 0 {
+        Ok(())
+    } else if pre.iter().any(|p| WHOLE_FILE.contains(&p.name())) {
+        Err("The dir onramp can't read files in chunks if they are decompressed, set `chunk_size` to 0".into())
+    } else if pre.iter().any(|p| FRAMING.contains(&p.name())) {
+        Ok(())
+    } else {
+        Err(
+            "The dir onramp needs a framing preprocessor like `lines` to read files in chunks"
+                .into(),
+        )
+    }
+}
+
+pub struct Dir {
+    pub config: Config,
+    onramp_id: TremorUrl,
+}
+
+impl onramp::Impl for Dir {
+    fn from_config(id: &TremorUrl, config: &Option<YamlValue>) -> Result<Box<dyn Onramp>> {
+        if let Some(config) = config {
+            let config: Config = Config::new(config)?;
+            Ok(Box::new(Self {
+                config,
+                onramp_id: id.clone(),
+            }))
+        } else {
+            Err("Missing config for dir onramp".into())
+        }
+    }
+}
+
+/// The file being read
+struct Reading {
+    path: PathBuf,
+    stream: usize,
+    file: Option<FSFile>,
+    /// the id of its first event
+    first: u64,
+    failed: bool,
+    /// all of it was read and its stream ended, events the preprocessors
+    /// flush at the end of the stream still belong to it
+    ended: bool,
+}
+
+/// A file whose events are on their way through the pipelines
+struct InFlight {
+    path: PathBuf,
+    /// the ids of its events, `first..end`
+    first: u64,
+    end: u64,
+    failed: bool,
+}
+
+struct Int {
+    config: Config,
+    onramp_id: TremorUrl,
+    uid: u64,
+    pattern: glob::Pattern,
+    /// files that were queued, read or are in flight
+    seen: HashSet<PathBuf>,
+    queue: VecDeque<PathBuf>,
+    reading: Option<Reading>,
+    in_flight: VecDeque<InFlight>,
+    /// files whose events were all acknowledged or failed
+    settled: Vec<InFlight>,
+    /// all events up to this one were acknowledged or failed
+    handled: Option<u64>,
+    next_stream: usize,
+    next_scan: Instant,
+}
+
+impl std::fmt::Debug for Int {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Dir")
+    }
+}
+
+impl Int {
+    fn from_config(uid: u64, onramp_id: TremorUrl, config: Config) -> Result<Self> {
+        Ok(Self {
+            pattern: glob::Pattern::new(&config.pattern)?,
+            config,
+            onramp_id,
+            uid,
+            seen: HashSet::new(),
+            queue: VecDeque::new(),
+            reading: None,
+            in_flight: VecDeque::new(),
+            settled: Vec::new(),
+            handled: None,
+            // stream 0 is the default stream of the onramp
+            next_stream: 1,
+            next_scan: Instant::now(),
+        })
+    }
+
+    /// Queues new files, oldest first
+    async fn scan(&mut self) -> Result<()> {
+        let min_age = Duration::from_millis(self.config.min_age_ms);
+        let mut found = Vec::new();
+        let mut entries = async_std::fs::read_dir(&self.config.dir).await?;
+        while let Some(entry) = entries.next().await {
+            let entry = entry?;
+            let path = entry.path();
+            let name = entry.file_name();
+            if self.seen.contains(&path) || !self.pattern.matches(&name.to_string_lossy()) {
+                continue;
+            }
+            let meta = entry.metadata().await?;
+            let modified = meta.modified()?;
+            let age = SystemTime::now()
+                .duration_since(modified)
+                .unwrap_or_default();
+            if meta.is_file() && age >= min_age {
+                found.push((modified, path));
+            }
+        }
+        found.sort();
+        for (_, path) in found {
+            self.seen.insert(path.clone());
+            self.queue.push_back(path);
+        }
+        Ok(())
+    }
+
+    fn handle(&mut self, id: u64) {
+        self.handled = Some(self.handled.map_or(id, |handled| handled.max(id)));
+        self.settle();
+    }
+
+    /// Moves files whose events were all handled to the settled list
+    fn settle(&mut self) {
+        while let Some(f) = self.in_flight.front() {
+            let done = f.first == f.end || self.handled.map_or(false, |id| id + 1 >= f.end);
+            if !done {
+                break;
+            }
+            if let Some(f) = self.in_flight.pop_front() {
+                self.settled.push(f);
+            }
+        }
+    }
+
+    /// Moves or deletes files that are done, and makes failed ones available
+    /// for a retry
+    async fn finish(&mut self) {
+        for f in std::mem::take(&mut self.settled) {
+            let done = if f.failed {
+                warn!(
+                    "[Source::{}] Events of {} failed, ingesting it again",
+                    self.onramp_id,
+                    f.path.display()
+                );
+                Ok(())
+            } else if let Some(done_dir) = &self.config.done_dir {
+                move_to(&f.path, Path::new(done_dir)).await
+            } else {
+                async_std::fs::remove_file(&f.path)
+                    .await
+                    .map_err(Error::from)
+            };
+            if let Err(e) = done {
+                error!(
+                    "[Source::{}] Failed to finish {}: {}",
+                    self.onramp_id,
+                    f.path.display(),
+                    e
+                );
+            }
+            self.seen.remove(&f.path);
+        }
+    }
+
+    async fn next_chunk(&mut self) -> Result<Option<Vec<u8>>> {
+        let chunk_size = self.config.chunk_size;
+        if let Some(reading) = &mut self.reading {
+            if let Some(f) = &mut reading.file {
+                let mut data = Vec::new();
+                if chunk_size == 0 {
+                    f.read_to_end(&mut data).await?;
+                } else {
+                    f.take(chunk_size as u64).read_to_end(&mut data).await?;
+                }
+                if !data.is_empty() {
+                    if chunk_size == 0 {
+                        reading.file = None;
+                    }
+                    return Ok(Some(data));
+                }
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Moves a file into `dir`, copying it if it is on another filesystem
+async fn move_to(path: &Path, dir: &Path) -> Result<()> {
+    async_std::fs::create_dir_all(dir).await?;
+    let target = dir.join(path.file_name().unwrap_or_default());
+    if async_std::fs::rename(path, &target).await.is_err() {
+        async_std::fs::copy(path, &target).await?;
+        async_std::fs::remove_file(path).await?;
+    }
+    Ok(())
+}
+
+#[async_trait::async_trait()]
+impl Source for Int {
+    fn id(&self) -> &TremorUrl {
+        &self.onramp_id
+    }
+
+    fn is_transactional(&self) -> bool {
+        true
+    }
+
+    async fn pull_event(&mut self, id: u64) -> Result<SourceReply> {
+        if self.reading.as_ref().map_or(false, |r| r.ended) {
+            if let Some(reading) = self.reading.take() {
+                // all events of the file were sent, including the ones flushed
+                // at the end of its stream, the next one gets `id`
+                self.in_flight.push_back(InFlight {
+                    path: reading.path,
+                    first: reading.first,
+                    end: id,
+                    failed: reading.failed,
+                });
+                self.settle();
+            }
+        }
+        self.finish().await;
+
+        if self.reading.is_some() {
+            match self.next_chunk().await {
+                Ok(Some(data)) => {
+                    if let Some(reading) = &self.reading {
+                        let path = reading.path.to_string_lossy().to_string();
+                        return Ok(SourceReply::Data {
+                            origin_uri: EventOriginUri {
+                                uid: self.uid,
+                                scheme: "tremor-dir".to_string(),
+                                host: hostname(),
+                                port: None,
+                                path: vec![path.clone()],
+                            },
+                            data,
+                            meta: Some(literal!({ "path": path })),
+                            codec_override: None,
+                            stream: reading.stream,
+                        });
+                    }
+                }
+                Ok(None) => {}
+                Err(e) => error!("[Source::{}] Failed to read: {}", self.onramp_id, e),
+            }
+            if let Some(reading) = &mut self.reading {
+                reading.ended = true;
+                return Ok(SourceReply::EndStream(reading.stream));
+            }
+        }
+
+        if self.queue.is_empty() && Instant::now() >= self.next_scan {
+            if let Err(e) = self.scan().await {
+                error!(
+                    "[Source::{}] Failed to scan {}: {}",
+                    self.onramp_id, self.config.dir, e
+                );
+            }
+            self.next_scan = Instant::now() + Duration::from_millis(self.config.poll_interval_ms);
+        }
+        while let Some(path) = self.queue.pop_front() {
+            match file::open(&path).await {
+                Ok(f) => {
+                    debug!("[Source::{}] Ingesting {}", self.onramp_id, path.display());
+                    let stream = self.next_stream;
+                    self.next_stream += 1;
+                    self.reading = Some(Reading {
+                        path,
+                        stream,
+                        file: Some(f),
+                        first: id,
+                        failed: false,
+                        ended: false,
+                    });
+                    return Ok(SourceReply::StartStream(stream));
+                }
+                Err(e) => {
+                    warn!("[Source::{}] {}", self.onramp_id, e);
+                    self.seen.remove(&path);
+                }
+            }
+        }
+        Ok(SourceReply::Empty(self.config.poll_interval_ms.min(100)))
+    }
+
+    async fn init(&mut self) -> Result<SourceState> {
+        Ok(SourceState::Connected)
+    }
+
+    fn ack(&mut self, id: u64) {
+        trace!("[Source::{}] Ack {}", self.onramp_id, id);
+        self.handle(id);
+    }
+
+    fn fail(&mut self, id: u64) {
+        trace!("[Source::{}] Fail {}", self.onramp_id, id);
+        if let Some(f) = self
+            .in_flight
+            .iter_mut()
+            .find(|f| f.first <= id && id < f.end)
+        {
+            f.failed = true;
+        } else if let Some(reading) = self.reading.as_mut().filter(|r| r.first <= id) {
+            reading.failed = true;
+        }
+        self.handle(id);
+    }
+}
+
+#[async_trait::async_trait]
+impl Onramp for Dir {
+    async fn start(&mut self, config: OnrampConfig<'_>) -> Result<onramp::Addr> {
+        check_chunks(self.config.chunk_size, config.processors.pre)?;
+        let source = Int::from_config(
+            config.onramp_uid,
+            self.onramp_id.clone(),
+            self.config.clone(),
+        )?;
+        SourceManager::start(source, config).await
+    }
+
+    fn default_codec(&self) -> &str {
+        "json"
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn config(dir: &Path) -> Config {
+        Config {
+            dir: dir.to_string_lossy().to_string(),
+            pattern: "*.json".to_string(),
+            done_dir: Some(dir.join("done").to_string_lossy().to_string()),
+            min_age_ms: 0,
+            chunk_size: 0,
+            poll_interval_ms: 0,
+        }
+    }
+
+    #[async_std::test]
+    async fn ingest_and_move() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let dir: PathBuf = tmp.path().to_path_buf().into();
+        async_std::fs::write(dir.join("snot.json"), "1\n2\n").await?;
+        async_std::fs::write(dir.join("badger.txt"), "3\n").await?;
+        let url = TremorUrl::parse("/onramp/dir/01")?;
+        let mut source = Int::from_config(0, url, config(&dir))?;
+
+        assert!(matches!(
+            source.pull_event(0).await?,
+            SourceReply::StartStream(1)
+        ));
+        match source.pull_event(0).await? {
+            SourceReply::Data { data, stream, .. } => {
+                assert_eq!(1, stream);
+                assert_eq!(b"1\n2\n".to_vec(), data);
+            }
+            _ => panic!("expected data"),
+        }
+        // the data became events 0 and 1
+        assert!(matches!(
+            source.pull_event(2).await?,
+            SourceReply::EndStream(1)
+        ));
+        assert!(matches!(source.pull_event(2).await?, SourceReply::Empty(_)));
+
+        // a failed event leaves the file in place once all events were handled
+        source.fail(0);
+        source.pull_event(2).await?;
+        assert!(dir.join("snot.json").exists().await);
+        source.ack(1);
+
+        // it is ingested again and moved once acknowledged
+        assert!(matches!(
+            source.pull_event(2).await?,
+            SourceReply::StartStream(2)
+        ));
+        assert!(dir.join("snot.json").exists().await);
+        source.pull_event(2).await?;
+        source.pull_event(4).await?;
+        source.ack(3);
+        source.pull_event(4).await?;
+        assert!(!dir.join("snot.json").exists().await);
+        assert!(dir.join("done").join("snot.json").exists().await);
+        assert!(dir.join("badger.txt").exists().await);
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn read_in_chunks() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let dir: PathBuf = tmp.path().to_path_buf().into();
+        async_std::fs::write(dir.join("snot.json"), "1\n2\n3\n").await?;
+        let url = TremorUrl::parse("/onramp/dir/02")?;
+        let mut source = Int::from_config(
+            0,
+            url,
+            Config {
+                chunk_size: 4,
+                ..config(&dir)
+            },
+        )?;
+
+        assert!(matches!(
+            source.pull_event(0).await?,
+            SourceReply::StartStream(1)
+        ));
+        let mut chunks = Vec::new();
+        while let SourceReply::Data { data, .. } = source.pull_event(0).await? {
+            chunks.push(data);
+        }
+        assert_eq!(vec![b"1\n2\n".to_vec(), b"3\n".to_vec()], chunks);
+        Ok(())
+    }
+
+    #[test]
+    fn chunks_need_framing() -> Result<()> {
+        let config: Config = serde_yaml::from_str("dir: /tmp")?;
+        assert_eq!(0, config.chunk_size);
+        check_chunks(0, &[preprocessor::Config::from("gzip")])?;
+        check_chunks(4, &[preprocessor::Config::from("lines")])?;
+        assert!(check_chunks(4, &[]).is_err());
+        assert!(check_chunks(
+            4,
+            &[
+                preprocessor::Config::from("gzip"),
+                preprocessor::Config::from("lines")
+            ]
+        )
+        .is_err());
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn flushed_events_belong_to_the_file() -> Result<()> {
+        use crate::metrics::RampReporter;
+        use crate::pipeline;
+        use crate::url::ports::OUT;
+        use crate::{codec, source::Processors};
+        use tremor_pipeline::EventId;
+
+        let tmp = tempfile::tempdir()?;
+        let dir: PathBuf = tmp.path().to_path_buf().into();
+        async_std::fs::write(dir.join("snot.json"), "Traceback\n  snot.py\n").await?;
+        let url = TremorUrl::parse("/onramp/dir/03")?;
+        let source = Int::from_config(0, url.clone(), config(&dir))?;
+        // multiline holds the lines back until the end of the stream
+        let pre = vec![
+            preprocessor::Config::from("lines"),
+            preprocessor::Config::from("multiline"),
+        ];
+        let o_config = OnrampConfig {
+            onramp_uid: 1,
+            codec: &codec::Config::from("string"),
+            codec_map: halfbrown::HashMap::new(),
+            processors: Processors {
+                pre: &pre,
+                post: &[],
+            },
+            metrics_reporter: RampReporter::new(url.clone(), None),
+            is_linked: false,
+            err_required: false,
+        };
+        let (sm, sender) = SourceManager::new(source, o_config).await?;
+        let handle = task::spawn(sm.run());
+
+        let pipeline_url = TremorUrl::parse("/pipeline/bla/01/in")?;
+        let (tx1, rx1) = async_channel::unbounded();
+        let (tx2, _rx2) = async_channel::unbounded();
+        let (tx3, rx3) = async_channel::unbounded();
+        let addr = pipeline::Addr::new(tx1, tx2, tx3, pipeline_url.clone());
+        sender
+            .send(onramp::Msg::Connect(OUT, vec![(pipeline_url, addr)]))
+            .await?;
+        rx3.recv().await?;
+        sender
+            .send(onramp::Msg::Cb(CbAction::Open, EventId::default()))
+            .await?;
+
+        let event = loop {
+            if let pipeline::Msg::Event { event, .. } = rx1.recv().await? {
+                break event;
+            }
+        };
+        assert_eq!(
+            &Value::from("Traceback\n  snot.py"),
+            event.data.suffix().value()
+        );
+        // the flushed event wasn't acknowledged yet
+        task::sleep(Duration::from_millis(100)).await;
+        assert!(dir.join("snot.json").exists().await);
+
+        sender
+            .send(onramp::Msg::Cb(CbAction::Ack, event.id))
+            .await?;
+        let done = dir.join("done").join("snot.json");
+        let mut waited = 0;
+        while !done.exists().await && waited < 50 {
+            task::sleep(Duration::from_millis(10)).await;
+            waited += 1;
+        }
+        assert!(done.exists().await);
+        assert!(!dir.join("snot.json").exists().await);
+        handle.cancel().await;
+        Ok(())
+    }
+}
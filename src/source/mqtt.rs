@@ -0,0 +1,469 @@
+// Copyright 2020-2021, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+#![cfg(not(tarpaulin_include))]
+
+//! # MQTT Onramp
+//!
+//! Subscribes to topics (including `+` and `#` wildcards) on a MQTT broker,
+//! speaking MQTT 3.1.1 or, with `protocol: v5`, MQTT 5.
+//!
+//! With a QoS level above 0 messages are only acknowledged to the broker once
+//! the resulting events got acked, failed messages are replayed. Topic, QoS
+//! level, retain and duplicate flags are exposed as `$mqtt` metadata, with
+//! MQTT 5 the publish properties as `$mqtt.properties`.
+
+use crate::ramp::mqtt::{
+    default_qos, qos, Client, ConnectOptions, EventLoop, Message, Notification, QoS,
+};
+use crate::source::prelude::*;
+use async_channel::{Sender, TryRecvError};
+use std::collections::{BTreeMap, VecDeque};
+use std::time::Duration;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Config {
+    #[serde(flatten)]
+    pub connection: ConnectOptions,
+    // topic filters to subscribe to
+    pub topics: Vec<String>,
+    // QoS level to subscribe with
+    #[serde(default = "default_qos")]
+    pub qos: u8,
+}
+
+impl ConfigImpl for Config {}
+
+pub struct Mqtt {
+    pub config: Config,
+    onramp_id: TremorUrl,
+}
+
+impl onramp::Impl for Mqtt {
+    fn from_config(id: &TremorUrl, config: &Option<YamlValue>) -> Result<Box<dyn Onramp>> {
+        if let Some(config) = config {
+            let config: Config = Config::new(config)?;
+            qos(config.qos)?;
+            if config.topics.is_empty() {
+                return Err("MQTT onramp requires at least one topic".into());
+            }
+            Ok(Box::new(Self {
+                config,
+                onramp_id: id.clone(),
+            }))
+        } else {
+            Err("Missing config for mqtt onramp".into())
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Onramp for Mqtt {
+    async fn start(&mut self, config: OnrampConfig<'_>) -> Result<onramp::Addr> {
+        let source = Int::from_config(config.onramp_uid, self.onramp_id.clone(), &self.config)?;
+        SourceManager::start(source, config).await
+    }
+
+    fn default_codec(&self) -> &str {
+        "json"
+    }
+}
+
+pub struct Int {
+    uid: u64,
+    onramp_id: TremorUrl,
+    config: Config,
+    qos: QoS,
+    client: Option<Client>,
+    rx: Option<Receiver<Message>>,
+    // messages waiting for their event to be acked, by event id
+    pending: BTreeMap<u64, Message>,
+    // failed messages to be sent again
+    retry: VecDeque<Message>,
+    origin_uri: EventOriginUri,
+}
+
+impl std::fmt::Debug for Int {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "MQTT")
+    }
+}
+
+impl Int {
+    fn from_config(uid: u64, onramp_id: TremorUrl, config: &Config) -> Result<Self> {
+        let config = config.clone();
+        let origin_uri = EventOriginUri {
+            uid,
+            scheme: "tremor-mqtt".to_string(),
+            host: config.connection.host.clone(),
+            port: Some(config.connection.port),
+            path: vec![],
+        };
+        Ok(Self {
+            uid,
+            onramp_id,
+            qos: qos(config.qos)?,
+            config,
+            client: None,
+            rx: None,
+            pending: BTreeMap::new(),
+            retry: VecDeque::new(),
+            origin_uri,
+        })
+    }
+
+    fn ack_message(&self, message: &Message) {
+        if let Some(client) = &self.client {
+            if let Err(e) = client.try_ack(message) {
+                error!("[Source::{}] failed to ack message: {}", self.onramp_id, e);
+            }
+        }
+    }
+}
+
+/// Drives the connection, (re-)subscribes whenever the broker holds no session
+/// for us and forwards received messages to the source.
+async fn run_eventloop(
+    mut eventloop: EventLoop,
+    client: Client,
+    topics: Vec<String>,
+    qos: QoS,
+    tx: Sender<Message>,
+    onramp_id: TremorUrl,
+) {
+    loop {
+        match eventloop.poll().await {
+            Ok(Notification::Message(message)) => {
+                if tx.send(message).await.is_err() {
+                    break;
+                }
+            }
+            Ok(Notification::Connected { session_present }) => {
+                if !session_present {
+                    for topic in &topics {
+                        if let Err(e) = client.try_subscribe(topic.as_str(), qos) {
+                            error!(
+                                "[Source::{}] failed to subscribe to {}: {}",
+                                onramp_id, topic, e
+                            );
+                        }
+                    }
+                }
+            }
+            Ok(_) => (),
+            Err(e) => {
+                if tx.is_closed() {
+                    break;
+                }
+                warn!("[Source::{}] MQTT connection error: {}", onramp_id, e);
+                task::sleep(Duration::from_secs(1)).await;
+            }
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Source for Int {
+    async fn pull_event(&mut self, id: u64) -> Result<SourceReply> {
+        let message = if let Some(message) = self.retry.pop_front() {
+            message
+        } else if let Some(rx) = &self.rx {
+            match rx.try_recv() {
+                Ok(message) => message,
+                Err(TryRecvError::Empty) => return Ok(SourceReply::Empty(10)),
+                Err(TryRecvError::Closed) => {
+                    return Ok(SourceReply::StateChange(SourceState::Disconnected))
+                }
+            }
+        } else {
+            return Ok(SourceReply::StateChange(SourceState::Disconnected));
+        };
+        let mut origin_uri = self.origin_uri.clone();
+        origin_uri.path = message.topic.split('/').map(ToString::to_string).collect();
+        let mut mqtt_meta = literal!({
+            "topic": message.topic.clone(),
+            "qos": message.qos as u8,
+            "retain": message.retain,
+            "dup": message.dup,
+        });
+        if let Some(properties) = &message.properties {
+            mqtt_meta.insert("properties", properties.to_value())?;
+        }
+        let meta = literal!({ "mqtt": mqtt_meta });
+        let data = message.payload.clone();
+        if message.qos != QoS::AtMostOnce {
+            self.pending.insert(id, message);
+        }
+        Ok(SourceReply::Data {
+            origin_uri,
+            data,
+            meta: Some(meta),
+            codec_override: None,
+            stream: 0,
+        })
+    }
+
+    async fn on_empty_event(&mut self, id: u64, _stream: usize) -> Result<()> {
+        if let Some(message) = self.pending.remove(&id) {
+            self.ack_message(&message);
+        }
+        Ok(())
+    }
+
+    fn id(&self) -> &TremorUrl {
+        &self.onramp_id
+    }
+
+    async fn init(&mut self) -> Result<SourceState> {
+        let (client, eventloop) = self
+            .config
+            .connection
+            .connect(
+                &format!("tremor-{}-{}", hostname(), self.uid),
+                self.is_transactional(),
+            )
+            .await?;
+        let (tx, rx) = bounded(crate::QSIZE);
+        task::spawn(run_eventloop(
+            eventloop,
+            client.clone(),
+            self.config.topics.clone(),
+            self.qos,
+            tx,
+            self.onramp_id.clone(),
+        ));
+        self.client = Some(client);
+        self.rx = Some(rx);
+        Ok(SourceState::Connected)
+    }
+
+    async fn terminate(&mut self) {
+        self.rx = None;
+        if let Some(client) = self.client.take() {
+            if client.disconnect().await.is_err() {}
+        }
+    }
+
+    fn ack(&mut self, id: u64) {
+        // acks are cumulative, everything up to `id` is done
+        let rest = self.pending.split_off(&id.saturating_add(1));
+        let done = std::mem::replace(&mut self.pending, rest);
+        for message in done.values() {
+            self.ack_message(message);
+        }
+    }
+
+    fn fail(&mut self, id: u64) {
+        if let Some(message) = self.pending.remove(&id) {
+            self.retry.push_back(message);
+        }
+    }
+
+    fn is_transactional(&self) -> bool {
+        self.qos != QoS::AtMostOnce
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::onramp::Impl;
+    use crate::ramp::mqtt::{v5, Protocol};
+    use tremor_common::time::nanotime;
+
+    fn config(topic: &str) -> Config {
+        Config {
+            connection: serde_yaml::from_str("{}").expect("valid yaml"),
+            topics: vec![topic.to_string()],
+            qos: 1,
+        }
+    }
+
+    fn properties() -> v5::Properties {
+        v5::Properties {
+            user_properties: vec![("snot".to_string(), "badger".to_string())],
+            ..v5::Properties::default()
+        }
+    }
+
+    fn message(payload: &str, properties: Option<v5::Properties>) -> Message {
+        Message {
+            topic: "snot/badger".to_string(),
+            qos: QoS::AtLeastOnce,
+            retain: false,
+            dup: false,
+            pkid: 1,
+            payload: payload.as_bytes().to_vec(),
+            properties,
+        }
+    }
+
+    fn user_properties<'v>(meta: &'v Option<Value<'v>>) -> Option<&'v Value<'v>> {
+        meta.as_ref()
+            .and_then(|m| m.get("mqtt"))
+            .and_then(|m| m.get("properties"))
+            .and_then(|p| p.get("user_properties"))
+    }
+
+    #[test]
+    fn bad_config() -> Result<()> {
+        let url = TremorUrl::parse("/onramp/mqtt/01")?;
+        let config = serde_yaml::from_str("topics: []")?;
+        assert!(Mqtt::from_config(&url, &Some(config)).is_err());
+        let config = serde_yaml::from_str("{topics: [snot], qos: 3}")?;
+        assert!(Mqtt::from_config(&url, &Some(config)).is_err());
+        let config = serde_yaml::from_str("{topics: [snot], protocol: v4}")?;
+        assert!(Mqtt::from_config(&url, &Some(config)).is_err());
+        let config = serde_yaml::from_str("topics: [snot/#]")?;
+        assert!(Mqtt::from_config(&url, &Some(config)).is_ok());
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn ack_and_retry() -> Result<()> {
+        let url = TremorUrl::parse("/onramp/mqtt/02")?;
+        let mut source = Int::from_config(0, url, &config("snot/#"))?;
+        let (tx, rx) = bounded(crate::QSIZE);
+        source.rx = Some(rx);
+        tx.send(message("1", Some(properties()))).await?;
+        tx.send(message("2", None)).await?;
+
+        match source.pull_event(0).await? {
+            SourceReply::Data {
+                data,
+                meta,
+                origin_uri,
+                ..
+            } => {
+                assert_eq!(b"1".to_vec(), data);
+                assert_eq!(vec!["snot", "badger"], origin_uri.path);
+                assert_eq!(
+                    Some("snot/badger"),
+                    meta.as_ref()
+                        .and_then(|m| m.get("mqtt"))
+                        .and_then(|m| m.get_str("topic"))
+                );
+                assert_eq!(
+                    Some(&literal!([["snot", "badger"]])),
+                    user_properties(&meta)
+                );
+            }
+            _ => panic!("expected data"),
+        }
+        match source.pull_event(1).await? {
+            SourceReply::Data { meta, .. } => {
+                // MQTT 3.1.1 messages have no properties
+                assert!(meta
+                    .as_ref()
+                    .and_then(|m| m.get("mqtt"))
+                    .and_then(|m| m.get("properties"))
+                    .is_none());
+            }
+            _ => panic!("expected data"),
+        }
+        assert_eq!(2, source.pending.len());
+
+        // failed messages are replayed before new ones
+        source.fail(0);
+        match source.pull_event(2).await? {
+            SourceReply::Data { data, .. } => assert_eq!(b"1".to_vec(), data),
+            _ => panic!("expected data"),
+        }
+        // acks cover all messages up to the acknowledged one
+        source.ack(2);
+        assert!(source.pending.is_empty());
+        assert!(matches!(source.pull_event(3).await?, SourceReply::Empty(_)));
+        Ok(())
+    }
+
+    /// Publishes a retained message, so it reaches the source however late it
+    /// subscribes, and receives it. Needs a broker on `localhost:1883` and is
+    /// skipped without one.
+    async fn round_trip(protocol: Protocol) -> Result<Option<Value<'static>>> {
+        if async_std::net::TcpStream::connect(("localhost", 1883))
+            .await
+            .is_err()
+        {
+            println!("No MQTT broker on localhost:1883, skipping");
+            return Ok(None);
+        }
+        let topic = format!("tremor/test/{}", nanotime());
+        let mut config = config(&topic);
+        config.connection.protocol = protocol;
+        let url = TremorUrl::parse("/onramp/mqtt/03")?;
+        let mut source = Int::from_config(0, url, &config)?;
+        source.init().await?;
+
+        let (client, mut eventloop) = config
+            .connection
+            .connect(&format!("tremor-test-{}", nanotime()), false)
+            .await?;
+        task::spawn(async move { while eventloop.poll().await.is_ok() {} });
+        client
+            .publish(
+                topic.clone(),
+                QoS::AtLeastOnce,
+                true,
+                b"snot".to_vec(),
+                properties(),
+            )
+            .await?;
+
+        let deadline = std::time::Instant::now() + Duration::from_secs(10);
+        let (data, meta) = loop {
+            match source.pull_event(0).await? {
+                SourceReply::Data { data, meta, .. } => break (data, meta),
+                _ if std::time::Instant::now() < deadline => {
+                    task::sleep(Duration::from_millis(10)).await;
+                }
+                _ => panic!("no message received"),
+            }
+        };
+        assert_eq!(b"snot".to_vec(), data);
+        source.ack(0);
+        assert!(source.pending.is_empty());
+
+        // clear the retained message
+        client
+            .publish(
+                topic,
+                QoS::AtLeastOnce,
+                true,
+                Vec::new(),
+                v5::Properties::default(),
+            )
+            .await?;
+        source.terminate().await;
+        client.disconnect().await?;
+        Ok(meta)
+    }
+
+    #[async_std::test]
+    async fn broker_round_trip() -> Result<()> {
+        if let Some(meta) = round_trip(Protocol::V3).await? {
+            assert_eq!(None, user_properties(&Some(meta)));
+        }
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn broker_round_trip_v5() -> Result<()> {
+        if let Some(meta) = round_trip(Protocol::V5).await? {
+            assert_eq!(
+                Some(&literal!([["snot", "badger"]])),
+                user_properties(&Some(meta))
+            );
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,648 @@
+// Copyright 2020-2021, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+#![cfg(not(tarpaulin_include))]
+
+//! # Redis Onramp
+//!
+//! Reads entries from redis streams as a member of a consumer group and
+//! messages from pub/sub channels and patterns.
+//!
+//! Stream entries are only acknowledged with `XACK` once their event got
+//! acked, failed entries are replayed. On start the entries still pending for
+//! this consumer are read before new ones. After a reconnect they are read
+//! again too, skipping the ones that were already delivered to the onramp.
+
+use crate::source::prelude::*;
+use async_channel::{unbounded, Sender, TryRecvError};
+use redis::streams::{StreamId, StreamKey, StreamReadReply};
+use redis::{Client, Value as RedisValue};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::time::Duration;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct StreamsConfig {
+    // streams to read from
+    pub keys: Vec<String>,
+    // consumer group, created if it does not exist
+    pub group: String,
+    // consumer name within the group, generated from the hostname if not set
+    #[serde(default = "Default::default")]
+    pub consumer: Option<String>,
+    // entry field holding the event data
+    #[serde(default = "default_field")]
+    pub field: String,
+    // maximum number of entries per read
+    #[serde(default = "default_count")]
+    pub count: usize,
+    // how long a read waits for new entries
+    #[serde(default = "default_block_ms")]
+    pub block_ms: u64,
+    // id the group starts at when it is created, `$` for new entries only, `0` for all
+    #[serde(default = "default_start_id")]
+    pub start_id: String,
+}
+
+fn default_field() -> String {
+    "data".to_string()
+}
+
+fn default_count() -> usize {
+    100
+}
+
+fn default_block_ms() -> u64 {
+    100
+}
+
+fn default_start_id() -> String {
+    "$".to_string()
+}
+
+fn default_url() -> String {
+    "redis://localhost:6379".to_string()
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Config {
+    // redis url, e.g. `redis://localhost:6379/0`
+    #[serde(default = "default_url")]
+    pub url: String,
+    #[serde(default = "Default::default")]
+    pub streams: Option<StreamsConfig>,
+    // pub/sub channels to subscribe to
+    #[serde(default = "Default::default")]
+    pub channels: Vec<String>,
+    // pub/sub channel patterns to subscribe to
+    #[serde(default = "Default::default")]
+    pub patterns: Vec<String>,
+}
+
+impl ConfigImpl for Config {}
+
+pub struct Redis {
+    pub config: Config,
+    onramp_id: TremorUrl,
+}
+
+impl onramp::Impl for Redis {
+    fn from_config(id: &TremorUrl, config: &Option<YamlValue>) -> Result<Box<dyn Onramp>> {
+        if let Some(config) = config {
+            let config: Config = Config::new(config)?;
+            if config.streams.is_none() && config.channels.is_empty() && config.patterns.is_empty()
+            {
+                return Err(
+                    "Redis onramp requires `streams`, `channels` or `patterns` to read from".into(),
+                );
+            }
+            Ok(Box::new(Self {
+                config,
+                onramp_id: id.clone(),
+            }))
+        } else {
+            Err("Missing config for redis onramp".into())
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Onramp for Redis {
+    async fn start(&mut self, config: OnrampConfig<'_>) -> Result<onramp::Addr> {
+        let source = Int::from_config(config.onramp_uid, self.onramp_id.clone(), &self.config)?;
+        SourceManager::start(source, config).await
+    }
+
+    fn default_codec(&self) -> &str {
+        "json"
+    }
+}
+
+#[derive(Debug)]
+enum Message {
+    Entry {
+        stream: String,
+        id: String,
+        fields: HashMap<String, RedisValue>,
+    },
+    Published {
+        channel: String,
+        pattern: Option<String>,
+        payload: Vec<u8>,
+    },
+}
+
+pub struct Int {
+    uid: u64,
+    onramp_id: TremorUrl,
+    config: Config,
+    client: Client,
+    rx: Option<Receiver<Message>>,
+    // (stream, entry id) pairs to acknowledge
+    ack_tx: Option<Sender<(String, String)>>,
+    // stream entries waiting for their event to be acked, by event id
+    pending: BTreeMap<u64, Message>,
+    // failed stream entries to be sent again
+    retry: VecDeque<Message>,
+    origin_uri: EventOriginUri,
+}
+
+impl std::fmt::Debug for Int {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Redis")
+    }
+}
+
+impl Int {
+    fn from_config(uid: u64, onramp_id: TremorUrl, config: &Config) -> Result<Self> {
+        let config = config.clone();
+        let client = Client::open(config.url.as_str())?;
+        let url = url::Url::parse(&config.url)?;
+        let origin_uri = EventOriginUri {
+            uid,
+            scheme: "tremor-redis".to_string(),
+            host: url.host_str().unwrap_or("localhost").to_string(),
+            port: url.port(),
+            path: vec![],
+        };
+        Ok(Self {
+            uid,
+            onramp_id,
+            config,
+            client,
+            rx: None,
+            ack_tx: None,
+            pending: BTreeMap::new(),
+            retry: VecDeque::new(),
+            origin_uri,
+        })
+    }
+
+    fn ack_entry(&self, msg: &Message) {
+        if let (Some(ack_tx), Message::Entry { stream, id, .. }) = (&self.ack_tx, msg) {
+            if ack_tx.try_send((stream.clone(), id.clone())).is_err() {
+                error!(
+                    "[Source::{}] failed to ack stream entry {} {}",
+                    self.onramp_id, stream, id
+                );
+            }
+        }
+    }
+}
+
+fn entry_field(v: &RedisValue) -> Value<'static> {
+    match redis::from_redis_value::<String>(v) {
+        Ok(s) => Value::from(s),
+        Err(_) => Value::null(),
+    }
+}
+
+/// Stream entries read by the onramp, kept across reconnects
+#[derive(Default)]
+struct Delivered {
+    /// (stream, entry id) pairs delivered to the onramp and not acknowledged
+    entries: HashSet<(String, String)>,
+    /// (stream, entry id) pairs to acknowledge with `XACK`
+    acks: VecDeque<(String, String)>,
+}
+
+/// Reads the streams until the source goes away, acknowledging handled
+/// entries in between reads.
+async fn read_streams(
+    client: &Client,
+    config: &StreamsConfig,
+    consumer: &str,
+    tx: &Sender<Message>,
+    ack_rx: &Receiver<(String, String)>,
+    delivered: &mut Delivered,
+) -> Result<()> {
+    let mut con = client.get_async_connection().await?;
+    for key in &config.keys {
+        let created: redis::RedisResult<()> = redis::cmd("XGROUP")
+            .arg("CREATE")
+            .arg(key)
+            .arg(&config.group)
+            .arg(&config.start_id)
+            .arg("MKSTREAM")
+            .query_async(&mut con)
+            .await;
+        if let Err(e) = created {
+            if e.code() != Some("BUSYGROUP") {
+                return Err(e.into());
+            }
+        }
+    }
+    // start with the entries that were delivered to us but never acknowledged,
+    // a stream switches to new entries (`>`) once its backlog is drained
+    let mut cursors: Vec<String> = config.keys.iter().map(|_| "0".to_string()).collect();
+    loop {
+        while let Ok(ack) = ack_rx.try_recv() {
+            delivered.acks.push_back(ack);
+        }
+        // acks are only dropped once `XACK` succeeded, so they survive reconnects
+        while let Some((stream, id)) = delivered.acks.front() {
+            let _: u64 = redis::cmd("XACK")
+                .arg(stream)
+                .arg(&config.group)
+                .arg(id)
+                .query_async(&mut con)
+                .await?;
+            if let Some(ack) = delivered.acks.pop_front() {
+                delivered.entries.remove(&ack);
+            }
+        }
+        if tx.is_closed() {
+            return Ok(());
+        }
+        let reply: Option<StreamReadReply> = redis::cmd("XREADGROUP")
+            .arg("GROUP")
+            .arg(&config.group)
+            .arg(consumer)
+            .arg("COUNT")
+            .arg(config.count)
+            .arg("BLOCK")
+            .arg(config.block_ms)
+            .arg("STREAMS")
+            .arg(&config.keys)
+            .arg(&cursors)
+            .query_async(&mut con)
+            .await?;
+        let mut read: HashMap<String, Vec<StreamId>> = reply
+            .map(|r| r.keys)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|StreamKey { key, ids }| (key, ids))
+            .collect();
+        for (key, cursor) in config.keys.iter().zip(cursors.iter_mut()) {
+            let ids = read.remove(key).unwrap_or_default();
+            if cursor != ">" {
+                *cursor = ids.last().map_or_else(|| ">".to_string(), |e| e.id.clone());
+            }
+            for StreamId { id, map } in ids {
+                // pending entries the onramp still holds are read again after a reconnect
+                if !delivered.entries.insert((key.clone(), id.clone())) {
+                    continue;
+                }
+                let msg = Message::Entry {
+                    stream: key.clone(),
+                    id,
+                    fields: map,
+                };
+                if tx.send(msg).await.is_err() {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+/// Forwards pub/sub messages until the source goes away.
+async fn subscribe(client: &Client, config: &Config, tx: &Sender<Message>) -> Result<()> {
+    let mut pubsub = client.get_async_connection().await?.into_pubsub();
+    for channel in &config.channels {
+        pubsub.subscribe(channel).await?;
+    }
+    for pattern in &config.patterns {
+        pubsub.psubscribe(pattern).await?;
+    }
+    let mut messages = pubsub.on_message();
+    while let Some(msg) = messages.next().await {
+        let pattern = if msg.from_pattern() {
+            msg.get_pattern().ok()
+        } else {
+            None
+        };
+        let msg = Message::Published {
+            channel: msg.get_channel_name().to_string(),
+            pattern,
+            payload: msg.get_payload_bytes().to_vec(),
+        };
+        if tx.send(msg).await.is_err() {
+            return Ok(());
+        }
+    }
+    Err("Redis pub/sub connection closed".into())
+}
+
+#[async_trait::async_trait]
+impl Source for Int {
+    async fn pull_event(&mut self, id: u64) -> Result<SourceReply> {
+        let msg = if let Some(msg) = self.retry.pop_front() {
+            msg
+        } else if let Some(rx) = &self.rx {
+            match rx.try_recv() {
+                Ok(msg) => msg,
+                Err(TryRecvError::Empty) => return Ok(SourceReply::Empty(10)),
+                Err(TryRecvError::Closed) => {
+                    return Ok(SourceReply::StateChange(SourceState::Disconnected))
+                }
+            }
+        } else {
+            return Ok(SourceReply::StateChange(SourceState::Disconnected));
+        };
+        let mut origin_uri = self.origin_uri.clone();
+        let (data, meta) = match &msg {
+            Message::Entry { stream, id, fields } => {
+                let field = self
+                    .config
+                    .streams
+                    .as_ref()
+                    .map_or("data", |s| s.field.as_str());
+                let data = fields
+                    .get(field)
+                    .map(redis::from_redis_value::<Vec<u8>>)
+                    .transpose()?
+                    .unwrap_or_default();
+                let mut other = Value::object_with_capacity(fields.len());
+                for (k, v) in fields.iter().filter(|(k, _)| k.as_str() != field) {
+                    other.try_insert(k.clone(), entry_field(v));
+                }
+                origin_uri.path = vec![stream.clone()];
+                let meta = literal!({
+                    "redis": {
+                        "stream": stream.clone(),
+                        "id": id.clone(),
+                        "fields": other
+                    }
+                });
+                (data, meta)
+            }
+            Message::Published {
+                channel,
+                pattern,
+                payload,
+            } => {
+                origin_uri.path = vec![channel.clone()];
+                let meta = literal!({
+                    "redis": {
+                        "channel": channel.clone(),
+                        "pattern": pattern.clone()
+                    }
+                });
+                (payload.clone(), meta)
+            }
+        };
+        if matches!(msg, Message::Entry { .. }) {
+            self.pending.insert(id, msg);
+        }
+        Ok(SourceReply::Data {
+            origin_uri,
+            data,
+            meta: Some(meta),
+            codec_override: None,
+            stream: 0,
+        })
+    }
+
+    async fn on_empty_event(&mut self, id: u64, _stream: usize) -> Result<()> {
+        if let Some(msg) = self.pending.remove(&id) {
+            self.ack_entry(&msg);
+        }
+        Ok(())
+    }
+
+    fn id(&self) -> &TremorUrl {
+        &self.onramp_id
+    }
+
+    async fn init(&mut self) -> Result<SourceState> {
+        let (tx, rx) = bounded(crate::QSIZE);
+        if let Some(streams) = &self.config.streams {
+            let (ack_tx, ack_rx) = unbounded();
+            let client = self.client.clone();
+            let streams = streams.clone();
+            let consumer = streams
+                .consumer
+                .clone()
+                .unwrap_or_else(|| format!("tremor-{}-{}", hostname(), self.uid));
+            let tx = tx.clone();
+            let onramp_id = self.onramp_id.clone();
+            task::spawn(async move {
+                let mut delivered = Delivered::default();
+                while let Err(e) =
+                    read_streams(&client, &streams, &consumer, &tx, &ack_rx, &mut delivered).await
+                {
+                    if tx.is_closed() {
+                        break;
+                    }
+                    warn!("[Source::{}] Redis stream error: {}", onramp_id, e);
+                    task::sleep(Duration::from_secs(1)).await;
+                }
+            });
+            self.ack_tx = Some(ack_tx);
+        }
+        if !self.config.channels.is_empty() || !self.config.patterns.is_empty() {
+            let client = self.client.clone();
+            let config = self.config.clone();
+            let tx = tx.clone();
+            let onramp_id = self.onramp_id.clone();
+            task::spawn(async move {
+                while let Err(e) = subscribe(&client, &config, &tx).await {
+                    if tx.is_closed() {
+                        break;
+                    }
+                    warn!("[Source::{}] Redis pub/sub error: {}", onramp_id, e);
+                    task::sleep(Duration::from_secs(1)).await;
+                }
+            });
+        }
+        self.rx = Some(rx);
+        Ok(SourceState::Connected)
+    }
+
+    async fn terminate(&mut self) {
+        self.rx = None;
+        self.ack_tx = None;
+    }
+
+    fn ack(&mut self, id: u64) {
+        // acks are cumulative, everything up to `id` is done
+        let rest = self.pending.split_off(&id.saturating_add(1));
+        let done = std::mem::replace(&mut self.pending, rest);
+        for msg in done.values() {
+            self.ack_entry(msg);
+        }
+    }
+
+    fn fail(&mut self, id: u64) {
+        if let Some(msg) = self.pending.remove(&id) {
+            self.retry.push_back(msg);
+        }
+    }
+
+    fn is_transactional(&self) -> bool {
+        self.config.streams.is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::onramp::Impl;
+    use async_std::future::timeout;
+    use tremor_common::time::nanotime;
+
+    fn streams(key: &str) -> StreamsConfig {
+        StreamsConfig {
+            keys: vec![key.to_string()],
+            group: "tremor".to_string(),
+            consumer: None,
+            field: default_field(),
+            count: default_count(),
+            block_ms: 10,
+            start_id: "0".to_string(),
+        }
+    }
+
+    fn entry(id: &str, data: &str) -> Message {
+        let mut fields = HashMap::new();
+        fields.insert(
+            "data".to_string(),
+            RedisValue::Data(data.as_bytes().to_vec()),
+        );
+        fields.insert("snot".to_string(), RedisValue::Data(b"badger".to_vec()));
+        Message::Entry {
+            stream: "in".to_string(),
+            id: id.to_string(),
+            fields,
+        }
+    }
+
+    /// A redis server on `localhost:6379`, tests needing one are skipped
+    /// without it
+    async fn redis() -> Option<Client> {
+        let client = Client::open(default_url()).ok()?;
+        client.get_async_connection().await.ok()?;
+        Some(client)
+    }
+
+    #[test]
+    fn bad_config() -> Result<()> {
+        let url = TremorUrl::parse("/onramp/redis/01")?;
+        let config = serde_yaml::from_str("url: redis://localhost")?;
+        assert!(Redis::from_config(&url, &Some(config)).is_err());
+        let config = serde_yaml::from_str("channels: [snot]")?;
+        assert!(Redis::from_config(&url, &Some(config)).is_ok());
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn ack_and_retry() -> Result<()> {
+        let url = TremorUrl::parse("/onramp/redis/02")?;
+        let config = Config {
+            url: default_url(),
+            streams: Some(streams("in")),
+            channels: vec![],
+            patterns: vec![],
+        };
+        let mut source = Int::from_config(0, url, &config)?;
+        let (tx, rx) = bounded(crate::QSIZE);
+        let (ack_tx, ack_rx) = unbounded();
+        source.rx = Some(rx);
+        source.ack_tx = Some(ack_tx);
+        tx.send(entry("1-0", "1")).await?;
+        tx.send(entry("2-0", "2")).await?;
+
+        match source.pull_event(0).await? {
+            SourceReply::Data { data, meta, .. } => {
+                assert_eq!(b"1".to_vec(), data);
+                let redis = meta.as_ref().and_then(|m| m.get("redis"));
+                assert_eq!(Some("1-0"), redis.and_then(|r| r.get_str("id")));
+                assert_eq!(
+                    Some("badger"),
+                    redis
+                        .and_then(|r| r.get("fields"))
+                        .and_then(|f| f.get_str("snot"))
+                );
+            }
+            _ => panic!("expected data"),
+        }
+        assert!(matches!(
+            source.pull_event(1).await?,
+            SourceReply::Data { .. }
+        ));
+
+        // failed entries are replayed before new ones
+        source.fail(0);
+        match source.pull_event(2).await? {
+            SourceReply::Data { data, .. } => assert_eq!(b"1".to_vec(), data),
+            _ => panic!("expected data"),
+        }
+        // acks cover all entries up to the acknowledged one
+        source.ack(2);
+        assert!(source.pending.is_empty());
+        let mut acked = vec![ack_rx.try_recv()?, ack_rx.try_recv()?];
+        acked.sort();
+        assert_eq!(
+            vec![
+                ("in".to_string(), "1-0".to_string()),
+                ("in".to_string(), "2-0".to_string())
+            ],
+            acked
+        );
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn reconnect_skips_delivered_entries() -> Result<()> {
+        let client = if let Some(client) = redis().await {
+            client
+        } else {
+            println!("No redis server on localhost:6379, skipping");
+            return Ok(());
+        };
+        let key = format!("tremor-test-{}", nanotime());
+        let mut con = client.get_async_connection().await?;
+        for data in &["1", "2"] {
+            let _: String = redis::cmd("XADD")
+                .arg(&key)
+                .arg("*")
+                .arg("data")
+                .arg(*data)
+                .query_async(&mut con)
+                .await?;
+        }
+        let config = streams(&key);
+        let (ack_tx, ack_rx) = unbounded();
+        let mut delivered = Delivered::default();
+
+        let (tx, rx) = bounded(crate::QSIZE);
+        let read = read_streams(&client, &config, "c", &tx, &ack_rx, &mut delivered);
+        assert!(timeout(Duration::from_millis(500), read).await.is_err());
+        assert_eq!(2, rx.len());
+        let first = rx.try_recv()?;
+
+        // the entries are pending for the consumer and read again after a
+        // reconnect, but were delivered already
+        let (tx, rx) = bounded(crate::QSIZE);
+        let read = read_streams(&client, &config, "c", &tx, &ack_rx, &mut delivered);
+        assert!(timeout(Duration::from_millis(500), read).await.is_err());
+        assert!(rx.is_empty());
+
+        // acknowledged entries leave the pending entries list
+        if let Message::Entry { stream, id, .. } = first {
+            ack_tx.send((stream, id)).await?;
+        }
+        let read = read_streams(&client, &config, "c", &tx, &ack_rx, &mut delivered);
+        assert!(timeout(Duration::from_millis(500), read).await.is_err());
+        assert_eq!(1, delivered.entries.len());
+        let pending: (u64, RedisValue, RedisValue, RedisValue) = redis::cmd("XPENDING")
+            .arg(&key)
+            .arg("tremor")
+            .query_async(&mut con)
+            .await?;
+        assert_eq!(1, pending.0);
+
+        let _: u64 = redis::cmd("DEL").arg(&key).query_async(&mut con).await?;
+        Ok(())
+    }
+}
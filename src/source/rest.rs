@@ -16,7 +16,7 @@
 // TODO add tests
 
 use crate::codec::Codec;
-use crate::postprocessor::{make_postprocessors, postprocess, Postprocessors};
+use crate::postprocessor::{self, make_postprocessors, postprocess, Postprocessors};
 use crate::source::prelude::*;
 use async_channel::{unbounded, Sender, TryRecvError};
 use halfbrown::HashMap;
@@ -97,7 +97,7 @@ impl Int {
         uid: u64,
         onramp_id: TremorUrl,
         config: &Config,
-        post_processors: &[String],
+        post_processors: &[postprocessor::Config],
         is_linked: bool,
     ) -> Result<Self> {
         let config = config.clone();
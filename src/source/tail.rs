@@ -0,0 +1,805 @@
+// Copyright 2020-2021, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! # Tailing file onramp
+//!
+//! Follows all files matching a set of glob patterns line by line, like
+//! `tail -F`. Files are tracked by their inode, so a file that is renamed by
+//! logrotate is read to its end while the new file at its path is picked up
+//! from the start. A file shrinking below the read position was truncated and
+//! is read again from the start.
+//!
+//! The offset of every file is only advanced once all lines up to it were
+//! acknowledged, and kept in a sled database in `checkpoint_dir`, so a
+//! restarted onramp neither loses nor duplicates lines. Failed lines are read
+//! again, up to `max_retries` times before they are skipped. Lines that
+//! produce no event while preprocessors are configured are held back by them,
+//! like the continuation lines `multiline` joins, and only settled with the
+//! event that carries them.
+//!
+//! Every file is read as a stream of its own, so stateful preprocessors and
+//! codecs never mix the lines of different files.
+//!
+//! ## Configuration
+//!
+//! See [Config](struct.Config.html) for details.
+
+use crate::source::prelude::*;
+use crate::utils::open_sled;
+use async_std::fs::File as FSFile;
+use async_std::io::{BufReader, SeekFrom};
+use async_std::path::{Path, PathBuf};
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::time::{Duration, Instant};
+use tremor_common::asy::file;
+
+/// Where to start reading files there is no checkpoint for
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum StartAt {
+    /// read the whole file
+    Beginning,
+    /// only read lines appended from now on
+    End,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Config {
+    /// glob patterns of the files to follow
+    pub paths: Vec<String>,
+    /// directory to keep the offsets of the files in, without it every start
+    /// begins from scratch
+    #[serde(default = "Default::default")]
+    pub checkpoint_dir: Option<String>,
+    /// where to start reading files present on start without a checkpoint,
+    /// files showing up later are always read from the beginning
+    #[serde(default = "Config::default_start_at")]
+    pub start_at: StartAt,
+    /// interval in milliseconds to look for new, rotated and truncated files
+    #[serde(default = "Config::default_poll_interval_ms")]
+    pub poll_interval_ms: u64,
+    /// times a failed line is read again before it is skipped
+    #[serde(default = "Config::default_max_retries")]
+    pub max_retries: u32,
+}
+
+impl Config {
+    fn default_start_at() -> StartAt {
+        StartAt::Beginning
+    }
+
+    fn default_poll_interval_ms() -> u64 {
+        500
+    }
+
+    fn default_max_retries() -> u32 {
+        3
+    }
+}
+
+impl ConfigImpl for Config {}
+
+pub struct Tail {
+    pub config: Config,
+    onramp_id: TremorUrl,
+}
+
+impl onramp::Impl for Tail {
+    fn from_config(id: &TremorUrl, config: &Option<YamlValue>) -> Result<Box<dyn Onramp>> {
+        if let Some(config) = config {
+            let config: Config = Config::new(config)?;
+            Ok(Box::new(Self {
+                config,
+                onramp_id: id.clone(),
+            }))
+        } else {
+            Err("Missing config for tail onramp".into())
+        }
+    }
+}
+
+/// Identity of a file that survives renames
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct FileId {
+    dev: u64,
+    ino: u64,
+}
+
+impl FileId {
+    #[cfg(unix)]
+    fn of(_path: &Path, meta: &std::fs::Metadata) -> Self {
+        use std::os::unix::fs::MetadataExt;
+        Self {
+            dev: meta.dev(),
+            ino: meta.ino(),
+        }
+    }
+
+    /// without inodes a file is identified by its path
+    #[cfg(not(unix))]
+    fn of(path: &Path, _meta: &std::fs::Metadata) -> Self {
+        use std::hash::{Hash, Hasher};
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        path.hash(&mut hasher);
+        Self {
+            dev: 0,
+            ino: hasher.finish(),
+        }
+    }
+
+    fn key(self) -> [u8; 16] {
+        let mut key = [0; 16];
+        key[..8].copy_from_slice(&self.dev.to_be_bytes());
+        key[8..].copy_from_slice(&self.ino.to_be_bytes());
+        key
+    }
+}
+
+/// A file being followed
+struct Tailed {
+    id: FileId,
+    path: PathBuf,
+    stream: usize,
+    /// if the start of the stream was announced
+    started: bool,
+    reader: BufReader<FSFile>,
+    /// offset after the last complete line read
+    pos: u64,
+    /// a line that is still being written
+    partial: Vec<u8>,
+    /// offset to read from again, set by failed lines
+    rewind: Option<u64>,
+    /// start of the line that failed last and how often it failed
+    failures: Option<(u64, u32)>,
+    /// end of the last acknowledged line
+    done: u64,
+    /// the offset in the checkpoint
+    committed: u64,
+    /// if the file was matched by the last scan
+    matched: bool,
+}
+
+impl Tailed {
+    async fn open(id: FileId, path: PathBuf, offset: u64, stream: usize) -> Result<Self> {
+        let mut reader = BufReader::new(file::open(&path).await?);
+        reader.seek(SeekFrom::Start(offset)).await?;
+        Ok(Self {
+            id,
+            path,
+            stream,
+            started: false,
+            reader,
+            pos: offset,
+            partial: Vec::new(),
+            rewind: None,
+            failures: None,
+            done: offset,
+            committed: offset,
+            matched: true,
+        })
+    }
+
+    async fn seek(&mut self, offset: u64) -> Result<()> {
+        self.reader.seek(SeekFrom::Start(offset)).await?;
+        self.pos = offset;
+        self.partial.clear();
+        Ok(())
+    }
+
+    /// Reads the next complete line and its start offset
+    async fn next_line(&mut self) -> Result<Option<(Vec<u8>, u64)>> {
+        if let Some(offset) = self.rewind.take() {
+            self.seek(offset).await?;
+        }
+        self.reader.read_until(b'\n', &mut self.partial).await?;
+        if self.partial.last() == Some(&b'\n') {
+            let mut line = std::mem::take(&mut self.partial);
+            let start = self.pos;
+            self.pos += line.len() as u64;
+            line.pop();
+            if line.last() == Some(&b'\r') {
+                line.pop();
+            }
+            Ok(Some((line, start)))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+/// A line on its way through the pipelines
+struct Pending {
+    file: FileId,
+    start: u64,
+    end: u64,
+    /// the line produced no event, a preprocessor holds it back
+    held: bool,
+}
+
+struct Int {
+    config: Config,
+    onramp_id: TremorUrl,
+    uid: u64,
+    checkpoints: Option<sled::Tree>,
+    files: Vec<Tailed>,
+    /// the file to read from next
+    next: usize,
+    /// stream of the next file, stream 0 is the default stream of the onramp
+    next_stream: usize,
+    pending: BTreeMap<u64, Pending>,
+    /// if preprocessors are configured, which may hold lines back
+    preprocessed: bool,
+    next_scan: Instant,
+    first_scan: bool,
+}
+
+impl std::fmt::Debug for Int {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Tail")
+    }
+}
+
+impl Int {
+    fn from_config(uid: u64, onramp_id: TremorUrl, config: Config) -> Result<Self> {
+        for pattern in &config.paths {
+            glob::Pattern::new(pattern)?;
+        }
+        let checkpoints = if let Some(dir) = &config.checkpoint_dir {
+            Some(open_sled(dir)?.open_tree(onramp_id.to_string())?)
+        } else {
+            None
+        };
+        Ok(Self {
+            config,
+            onramp_id,
+            uid,
+            checkpoints,
+            files: Vec::new(),
+            next: 0,
+            next_stream: 1,
+            pending: BTreeMap::new(),
+            preprocessed: false,
+            next_scan: Instant::now(),
+            first_scan: true,
+        })
+    }
+
+    fn checkpoint(&self, id: FileId) -> Result<Option<u64>> {
+        if let Some(checkpoints) = &self.checkpoints {
+            Ok(checkpoints
+                .get(id.key())?
+                .and_then(|offset| <[u8; 8]>::try_from(offset.as_ref()).ok())
+                .map(u64::from_be_bytes))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn store(checkpoints: &Option<sled::Tree>, onramp_id: &TremorUrl, id: FileId, offset: u64) {
+        if let Some(checkpoints) = checkpoints {
+            if let Err(e) = checkpoints.insert(id.key(), &offset.to_be_bytes()) {
+                error!("[Source::{}] failed to store offset: {}", onramp_id, e);
+            }
+        }
+    }
+
+    /// Picks up new, rotated and truncated files and drops the ones that
+    /// were removed and fully read
+    async fn scan(&mut self) -> Result<()> {
+        for f in &mut self.files {
+            f.matched = false;
+        }
+        let mut paths = Vec::new();
+        for pattern in &self.config.paths {
+            for path in glob::glob(pattern)? {
+                match path {
+                    Ok(path) => paths.push(PathBuf::from(path)),
+                    Err(e) => warn!("[Source::{}] {}", self.onramp_id, e),
+                }
+            }
+        }
+        for path in paths {
+            let meta = match async_std::fs::metadata(&path).await {
+                Ok(meta) if meta.is_file() => meta,
+                Ok(_) => continue,
+                Err(e) => {
+                    warn!("[Source::{}] {}: {}", self.onramp_id, path.display(), e);
+                    continue;
+                }
+            };
+            let id = FileId::of(&path, &meta);
+            if let Some(f) = self.files.iter_mut().find(|f| f.id == id) {
+                f.matched = true;
+                f.path = path;
+                if meta.len() < f.pos {
+                    info!(
+                        "[Source::{}] {} was truncated, reading it from the start",
+                        self.onramp_id,
+                        f.path.display()
+                    );
+                    f.rewind = Some(0);
+                    f.done = 0;
+                    f.committed = 0;
+                    self.pending.retain(|_, p| p.file != id);
+                    Self::store(&self.checkpoints, &self.onramp_id, id, 0);
+                }
+                continue;
+            }
+            let offset = match self.checkpoint(id)? {
+                // an offset past the end is left over from a truncated file,
+                // or a removed one whose inode was reused
+                Some(offset) if offset <= meta.len() => offset,
+                Some(_) => 0,
+                None if self.first_scan && self.config.start_at == StartAt::End => meta.len(),
+                None => 0,
+            };
+            info!(
+                "[Source::{}] Following {} from offset {}",
+                self.onramp_id,
+                path.display(),
+                offset
+            );
+            match Tailed::open(id, path.clone(), offset, self.next_stream).await {
+                Ok(f) => {
+                    self.files.push(f);
+                    self.next_stream += 1;
+                }
+                Err(e) => warn!("[Source::{}] {}: {}", self.onramp_id, path.display(), e),
+            }
+        }
+        self.first_scan = false;
+        Ok(())
+    }
+
+    /// Drops a file that isn't matched anymore once it was read and acked
+    /// completely, returns its stream if it was dropped
+    fn forget(&mut self, idx: usize) -> Option<usize> {
+        let f = &self.files[idx];
+        if f.matched || self.pending.values().any(|p| p.file == f.id) {
+            return None;
+        }
+        debug!(
+            "[Source::{}] Done with {}",
+            self.onramp_id,
+            f.path.display()
+        );
+        if let Some(checkpoints) = &self.checkpoints {
+            if let Err(e) = checkpoints.remove(f.id.key()) {
+                error!(
+                    "[Source::{}] failed to remove offset: {}",
+                    self.onramp_id, e
+                );
+            }
+        }
+        Some(self.files.remove(idx).stream)
+    }
+
+    /// Settles lines that were acknowledged or produced no events and moves
+    /// the checkpoints of their files up to the first line still pending
+    fn settle(&mut self, ids: Vec<u64>) {
+        let mut touched = Vec::new();
+        for id in ids {
+            if let Some(p) = self.pending.remove(&id) {
+                if let Some(f) = self.files.iter_mut().find(|f| f.id == p.file) {
+                    f.done = f.done.max(p.end);
+                    if matches!(f.failures, Some((start, _)) if start < f.done) {
+                        f.failures = None;
+                    }
+                }
+                if !touched.contains(&p.file) {
+                    touched.push(p.file);
+                }
+            }
+        }
+        for file in touched {
+            let first_pending = self
+                .pending
+                .values()
+                .filter(|p| p.file == file)
+                .map(|p| p.start)
+                .min();
+            if let Some(f) = self.files.iter_mut().find(|f| f.id == file) {
+                let offset = first_pending.map_or(f.done, |start| start.min(f.done));
+                if offset > f.committed {
+                    f.committed = offset;
+                    Self::store(&self.checkpoints, &self.onramp_id, file, offset);
+                }
+            }
+        }
+    }
+}
+
+#[async_trait::async_trait()]
+impl Source for Int {
+    fn id(&self) -> &TremorUrl {
+        &self.onramp_id
+    }
+
+    fn is_transactional(&self) -> bool {
+        true
+    }
+
+    async fn pull_event(&mut self, id: u64) -> Result<SourceReply> {
+        if Instant::now() >= self.next_scan {
+            self.scan().await?;
+            self.next_scan = Instant::now() + Duration::from_millis(self.config.poll_interval_ms);
+        }
+        for _ in 0..self.files.len() {
+            if self.files.is_empty() {
+                break;
+            }
+            let idx = self.next % self.files.len();
+            self.next = idx + 1;
+            let f = &mut self.files[idx];
+            if !f.started {
+                f.started = true;
+                return Ok(SourceReply::StartStream(f.stream));
+            }
+            match f.next_line().await {
+                Ok(Some((line, start))) => {
+                    let end = f.pos;
+                    let path = f.path.to_string_lossy().to_string();
+                    self.pending.insert(
+                        id,
+                        Pending {
+                            file: f.id,
+                            start,
+                            end,
+                            held: false,
+                        },
+                    );
+                    return Ok(SourceReply::Data {
+                        origin_uri: EventOriginUri {
+                            uid: self.uid,
+                            scheme: "tremor-tail".to_string(),
+                            host: hostname(),
+                            port: None,
+                            path: vec![path.clone()],
+                        },
+                        data: line,
+                        meta: Some(literal!({
+                            "path": path,
+                            "offset": start,
+                        })),
+                        codec_override: None,
+                        stream: f.stream,
+                    });
+                }
+                Ok(None) => {
+                    if let Some(stream) = self.forget(idx) {
+                        return Ok(SourceReply::EndStream(stream));
+                    }
+                }
+                Err(e) => {
+                    warn!(
+                        "[Source::{}] Failed to read {}: {}",
+                        self.onramp_id,
+                        f.path.display(),
+                        e
+                    );
+                    f.matched = false;
+                    if let Some(stream) = self.forget(idx) {
+                        return Ok(SourceReply::EndStream(stream));
+                    }
+                }
+            }
+        }
+        Ok(SourceReply::Empty(self.config.poll_interval_ms.min(100)))
+    }
+
+    async fn on_empty_event(&mut self, id: u64, _stream: usize) -> Result<()> {
+        if self.preprocessed {
+            // acks cover it once the event carrying it is acknowledged
+            if let Some(p) = self.pending.get_mut(&id) {
+                p.held = true;
+            }
+        } else {
+            self.settle(vec![id]);
+        }
+        Ok(())
+    }
+
+    async fn init(&mut self) -> Result<SourceState> {
+        Ok(SourceState::Connected)
+    }
+
+    async fn terminate(&mut self) {
+        if let Some(checkpoints) = &self.checkpoints {
+            if let Err(e) = checkpoints.flush_async().await {
+                error!(
+                    "[Source::{}] failed to flush offsets: {}",
+                    self.onramp_id, e
+                );
+            }
+        }
+    }
+
+    fn ack(&mut self, id: u64) {
+        trace!("[Source::{}] Ack {}", self.onramp_id, id);
+        // acks cover all events up to the acknowledged one
+        let ids = self.pending.range(..=id).map(|(id, _)| *id).collect();
+        self.settle(ids);
+    }
+
+    fn fail(&mut self, id: u64) {
+        trace!("[Source::{}] Fail {}", self.onramp_id, id);
+        if let Some(failed) = self.pending.remove(&id) {
+            // the lines held back right before the failed one are part of its event
+            let mut ids: Vec<u64> = self
+                .pending
+                .range(..id)
+                .rev()
+                .filter(|(_, p)| p.file == failed.file)
+                .take_while(|(_, p)| p.held)
+                .map(|(id, _)| *id)
+                .collect();
+            let from = ids.last().copied().unwrap_or(id);
+            let start = self.pending.get(&from).map_or(failed.start, |p| p.start);
+            let max_retries = self.config.max_retries;
+            if let Some(f) = self.files.iter_mut().find(|f| f.id == failed.file) {
+                let failures = match f.failures {
+                    Some((failed_at, failures)) if failed_at == start => failures + 1,
+                    _ => 1,
+                };
+                if failures > max_retries {
+                    error!(
+                        "[Source::{}] Skipping the line at offset {} of {} after {} retries",
+                        self.onramp_id,
+                        start,
+                        f.path.display(),
+                        max_retries
+                    );
+                    f.failures = None;
+                    // settle it like an acknowledged line
+                    self.pending.insert(id, failed);
+                    ids.push(id);
+                    self.settle(ids);
+                    return;
+                }
+                f.failures = Some((start, failures));
+                // read the file again from the failed line on
+                f.rewind = Some(start);
+            }
+            self.pending
+                .retain(|later, p| *later < from || p.file != failed.file);
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Onramp for Tail {
+    async fn start(&mut self, config: OnrampConfig<'_>) -> Result<onramp::Addr> {
+        let mut source = Int::from_config(
+            config.onramp_uid,
+            self.onramp_id.clone(),
+            self.config.clone(),
+        )?;
+        source.preprocessed = !config.processors.pre.is_empty();
+        SourceManager::start(source, config).await
+    }
+
+    fn default_codec(&self) -> &str {
+        "string"
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use async_std::fs::OpenOptions;
+
+    fn config(dir: &Path) -> Config {
+        Config {
+            paths: vec![format!("{}/*.log", dir.display())],
+            checkpoint_dir: Some(dir.join("checkpoints").to_string_lossy().to_string()),
+            start_at: StartAt::Beginning,
+            poll_interval_ms: 0,
+            max_retries: 1,
+        }
+    }
+
+    async fn append(path: &Path, data: &str) -> Result<()> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .await?;
+        file.write_all(data.as_bytes()).await?;
+        file.flush().await?;
+        Ok(())
+    }
+
+    async fn lines(source: &mut Int, id: &mut u64) -> Result<Vec<(u64, String)>> {
+        let mut lines = Vec::new();
+        loop {
+            match source.pull_event(*id).await? {
+                SourceReply::Data { data, .. } => {
+                    lines.push((*id, String::from_utf8_lossy(&data).to_string()));
+                    *id += 1;
+                }
+                SourceReply::StartStream(_) | SourceReply::EndStream(_) => (),
+                _ => return Ok(lines),
+            }
+        }
+    }
+
+    fn texts(lines: &[(u64, String)]) -> Vec<&str> {
+        lines.iter().map(|(_, l)| l.as_str()).collect()
+    }
+
+    #[async_std::test]
+    async fn follow_ack_and_resume() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let dir: PathBuf = tmp.path().to_path_buf().into();
+        let log = dir.join("app.log");
+        let url = TremorUrl::parse("/onramp/tail/01")?;
+        append(&log, "snot\nbadger\npart").await?;
+
+        let mut id = 0;
+        let mut source = Int::from_config(0, url.clone(), config(&dir))?;
+        let read = lines(&mut source, &mut id).await?;
+        assert_eq!(vec!["snot", "badger"], texts(&read));
+
+        // the partial line is completed
+        append(&log, "ial\r\n").await?;
+        let read = lines(&mut source, &mut id).await?;
+        assert_eq!(vec!["partial"], texts(&read));
+
+        // failing badger reads it again
+        source.ack(0);
+        source.fail(1);
+        let read = lines(&mut source, &mut id).await?;
+        assert_eq!(vec!["badger", "partial"], texts(&read));
+        source.ack(3);
+        source.terminate().await;
+        drop(source);
+
+        // a restart resumes after the acknowledged lines
+        append(&log, "tremor\n").await?;
+        let mut source = Int::from_config(0, url, config(&dir))?;
+        let read = lines(&mut source, &mut id).await?;
+        assert_eq!(vec!["partial", "tremor"], texts(&read));
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn rotate_and_truncate() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let dir: PathBuf = tmp.path().to_path_buf().into();
+        let log = dir.join("app.log");
+        let url = TremorUrl::parse("/onramp/tail/02")?;
+        append(&log, "snot\n").await?;
+
+        let mut id = 0;
+        let mut source = Int::from_config(0, url, config(&dir))?;
+        assert_eq!(vec!["snot"], texts(&lines(&mut source, &mut id).await?));
+
+        // rotation: the old file is read to its end, the new one from its start
+        append(&log, "badger\n").await?;
+        async_std::fs::rename(&log, dir.join("app.1")).await?;
+        append(&log, "tremor\n").await?;
+        let mut read = texts(&lines(&mut source, &mut id).await?)
+            .into_iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        read.sort();
+        assert_eq!(vec!["badger", "tremor"], read);
+
+        // truncation: the file is read from its start again
+        async_std::fs::write(&log, "a\n").await?;
+        assert_eq!(vec!["a"], texts(&lines(&mut source, &mut id).await?));
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn stream_per_file() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let dir: PathBuf = tmp.path().to_path_buf().into();
+        let log = dir.join("app.log");
+        let url = TremorUrl::parse("/onramp/tail/03")?;
+        append(&log, "snot\n").await?;
+
+        let mut source = Int::from_config(0, url, config(&dir))?;
+        assert!(matches!(
+            source.pull_event(0).await?,
+            SourceReply::StartStream(1)
+        ));
+        assert!(matches!(
+            source.pull_event(0).await?,
+            SourceReply::Data { stream: 1, .. }
+        ));
+        source.ack(0);
+
+        // the new file starts a stream of its own, the rotated one ends its
+        // stream once it is read
+        async_std::fs::rename(&log, dir.join("app.1")).await?;
+        append(&log, "badger\n").await?;
+        let mut replies = Vec::new();
+        for id in 1..4 {
+            match source.pull_event(id).await? {
+                SourceReply::StartStream(stream) => replies.push(format!("start {}", stream)),
+                SourceReply::EndStream(stream) => replies.push(format!("end {}", stream)),
+                SourceReply::Data { stream, .. } => replies.push(format!("data {}", stream)),
+                _ => (),
+            }
+        }
+        assert_eq!(vec!["start 2", "end 1", "data 2"], replies);
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn skip_failing_line() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let dir: PathBuf = tmp.path().to_path_buf().into();
+        let log = dir.join("app.log");
+        let url = TremorUrl::parse("/onramp/tail/04")?;
+        append(&log, "snot\nbadger\n").await?;
+
+        let mut id = 0;
+        let mut source = Int::from_config(0, url, config(&dir))?;
+        assert_eq!(
+            vec!["snot", "badger"],
+            texts(&lines(&mut source, &mut id).await?)
+        );
+        // snot is read again once
+        source.fail(0);
+        assert_eq!(
+            vec!["snot", "badger"],
+            texts(&lines(&mut source, &mut id).await?)
+        );
+        // and skipped when it fails again
+        source.fail(2);
+        assert!(lines(&mut source, &mut id).await?.is_empty());
+        source.ack(3);
+        assert!(source.pending.is_empty());
+        let f = &source.files[0];
+        assert_eq!(f.pos, f.committed);
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn hold_lines_for_preprocessors() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let dir: PathBuf = tmp.path().to_path_buf().into();
+        let log = dir.join("app.log");
+        let url = TremorUrl::parse("/onramp/tail/05")?;
+        append(&log, "Traceback\n  snot.py\nbadger\n").await?;
+
+        let mut id = 0;
+        let mut source = Int::from_config(0, url, config(&dir))?;
+        source.preprocessed = true;
+        let read = lines(&mut source, &mut id).await?;
+        assert_eq!(vec!["Traceback", "  snot.py", "badger"], texts(&read));
+        // multiline holds the trace back until badger starts the next event
+        source.on_empty_event(0, 1).await?;
+        source.on_empty_event(1, 1).await?;
+        assert_eq!(0, source.files[0].committed);
+
+        // failing the event reads the held lines again
+        source.fail(2);
+        let read = lines(&mut source, &mut id).await?;
+        assert_eq!(vec!["Traceback", "  snot.py", "badger"], texts(&read));
+        source.on_empty_event(3, 1).await?;
+        source.on_empty_event(4, 1).await?;
+        assert_eq!(0, source.files[0].committed);
+
+        // acknowledging it settles them
+        source.ack(5);
+        assert!(source.pending.is_empty());
+        let f = &source.files[0];
+        assert_eq!(f.pos, f.committed);
+        Ok(())
+    }
+}
@@ -13,7 +13,7 @@
 // limitations under the License.
 #![cfg(not(tarpaulin_include))]
 
-use crate::postprocessor::{make_postprocessors, postprocess, Postprocessors};
+use crate::postprocessor::{self, make_postprocessors, postprocess, Postprocessors};
 use crate::{codec::Codec, source::prelude::*};
 use async_channel::{Sender, TryRecvError};
 use async_std::net::{TcpListener, TcpStream};
@@ -75,7 +75,7 @@ pub struct Int {
     config: Config,
     is_linked: bool,
     listener: Option<Receiver<WsSourceReply>>,
-    post_processors: Vec<String>,
+    post_processors: Vec<postprocessor::Config>,
     // mapping of event id to stream id
     messages: BTreeMap<u64, usize>,
     // mapping of stream id to the stream sender
@@ -93,7 +93,7 @@ impl Int {
     fn from_config(
         uid: u64,
         onramp_id: TremorUrl,
-        post_processors: &[String],
+        post_processors: &[postprocessor::Config],
         config: &Config,
         is_linked: bool,
     ) -> Self {
@@ -128,7 +128,7 @@ async fn handle_connection(
     tx: Sender<WsSourceReply>,
     raw_stream: TcpStream,
     origin_uri: EventOriginUri,
-    processors: Vec<String>,
+    processors: Vec<postprocessor::Config>,
     stream: usize,
     link: bool,
 ) -> Result<()> {
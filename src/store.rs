@@ -0,0 +1,260 @@
+// Copyright 2020-2021, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Persistent deployment store.
+//!
+//! The store keeps the artefacts published to a world and the mappings of
+//! its linked bindings in a sled database, so a restarted world can be
+//! brought back into the same state. Entries are keyed by their kind and id,
+//! each publish, unpublish, link and unlink is recorded as it happens.
+
+use crate::config::{Binding, OffRamp, OnRamp};
+use crate::errors::{ErrorKind, Result};
+use crate::url::TremorUrl;
+use hashbrown::HashMap;
+use std::path::Path;
+
+pub(crate) const ONRAMP: &str = "onramp";
+pub(crate) const OFFRAMP: &str = "offramp";
+pub(crate) const PIPELINE: &str = "pipeline";
+pub(crate) const BINDING: &str = "binding";
+pub(crate) const MAPPING: &str = "mapping";
+
+/// The artefacts published to a world and the mappings of its linked bindings
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Deployment {
+    #[serde(default = "Default::default")]
+    pub(crate) onramp: Vec<OnRamp>,
+    #[serde(default = "Default::default")]
+    pub(crate) offramp: Vec<OffRamp>,
+    /// trickle source of the pipelines by their id
+    #[serde(default = "Default::default")]
+    pub(crate) pipeline: HashMap<String, String>,
+    #[serde(default = "Default::default")]
+    pub(crate) binding: Vec<Binding>,
+    #[serde(default = "Default::default")]
+    pub(crate) mapping: HashMap<TremorUrl, HashMap<String, String>>,
+}
+
+impl Deployment {
+    /// Number of artefacts and mappings in the deployment
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.onramp.len()
+            + self.offramp.len()
+            + self.pipeline.len()
+            + self.binding.len()
+            + self.mapping.len()
+    }
+
+    /// If the deployment is empty
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn entries(&self) -> Result<HashMap<String, Vec<u8>>> {
+        let mut entries = HashMap::with_capacity(self.len());
+        for o in &self.onramp {
+            entries.insert(key(ONRAMP, &o.id), serde_yaml::to_vec(o)?);
+        }
+        for o in &self.offramp {
+            entries.insert(key(OFFRAMP, &o.id), serde_yaml::to_vec(o)?);
+        }
+        for (id, source) in &self.pipeline {
+            entries.insert(key(PIPELINE, id), source.as_bytes().to_vec());
+        }
+        for b in &self.binding {
+            entries.insert(key(BINDING, &b.id), serde_yaml::to_vec(b)?);
+        }
+        for (url, mapping) in &self.mapping {
+            entries.insert(key(MAPPING, &url.to_string()), serde_yaml::to_vec(mapping)?);
+        }
+        Ok(entries)
+    }
+}
+
+fn key(kind: &str, id: &str) -> String {
+    format!("{}/{}", kind, id)
+}
+
+/// On-disk store of a deployment
+#[derive(Clone, Debug)]
+pub(crate) struct Store {
+    db: sled::Db,
+}
+
+impl Store {
+    /// Opens the store in the directory `path`, creating it if needed
+    ///
+    /// # Errors
+    ///  * if the store can't be opened
+    pub(crate) fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Ok(Self {
+            db: sled::open(path)?,
+        })
+    }
+
+    /// Loads the stored deployment
+    ///
+    /// # Errors
+    ///  * if the store can't be read or contains invalid entries
+    pub(crate) fn load(&self) -> Result<Deployment> {
+        let mut deployment = Deployment::default();
+        for entry in self.db.iter() {
+            let (key, value) = entry?;
+            let key = std::str::from_utf8(&key)?;
+            let invalid = |e: &dyn std::fmt::Display| {
+                ErrorKind::InvalidDeployment(format!("stored entry {} is invalid: {}", key, e))
+            };
+            match key.split_once('/') {
+                Some((ONRAMP, _)) => deployment
+                    .onramp
+                    .push(serde_yaml::from_slice(&value).map_err(|e| invalid(&e))?),
+                Some((OFFRAMP, _)) => deployment
+                    .offramp
+                    .push(serde_yaml::from_slice(&value).map_err(|e| invalid(&e))?),
+                Some((PIPELINE, id)) => {
+                    let source = String::from_utf8(value.to_vec()).map_err(|e| invalid(&e))?;
+                    deployment.pipeline.insert(id.to_string(), source);
+                }
+                Some((BINDING, _)) => deployment
+                    .binding
+                    .push(serde_yaml::from_slice(&value).map_err(|e| invalid(&e))?),
+                Some((MAPPING, url)) => {
+                    let url = TremorUrl::parse(url).map_err(|e| invalid(&e))?;
+                    let mapping = serde_yaml::from_slice(&value).map_err(|e| invalid(&e))?;
+                    deployment.mapping.insert(url, mapping);
+                }
+                _ => return Err(invalid(&"unknown kind").into()),
+            }
+        }
+        Ok(deployment)
+    }
+
+    /// Records the entry `id` of `kind`, replacing a stored one
+    ///
+    /// # Errors
+    ///  * if the entry can't be stored
+    pub(crate) async fn insert(&self, kind: &str, id: &str, value: Vec<u8>) -> Result<()> {
+        self.db.insert(key(kind, id).as_bytes(), value)?;
+        self.db.flush_async().await?;
+        Ok(())
+    }
+
+    /// Removes the entry `id` of `kind`
+    ///
+    /// # Errors
+    ///  * if the entry can't be removed
+    pub(crate) async fn remove(&self, kind: &str, id: &str) -> Result<()> {
+        self.db.remove(key(kind, id).as_bytes())?;
+        self.db.flush_async().await?;
+        Ok(())
+    }
+
+    /// Replaces the stored deployment, only entries that changed are written
+    ///
+    /// # Errors
+    ///  * if the deployment can't be stored
+    pub(crate) async fn save(&self, deployment: &Deployment) -> Result<()> {
+        let mut entries = deployment.entries()?;
+        let mut batch = sled::Batch::default();
+        for entry in self.db.iter() {
+            let (key, value) = entry?;
+            let id = String::from_utf8_lossy(&key).into_owned();
+            match entries.get(&id) {
+                Some(v) if v.as_slice() == value.as_ref() => {
+                    entries.remove(&id);
+                }
+                Some(_) => (),
+                None => batch.remove(key),
+            }
+        }
+        for (key, value) in entries {
+            batch.insert(key.as_bytes(), value);
+        }
+        self.db.apply_batch(batch)?;
+        self.db.flush_async().await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn deployment() -> Result<Deployment> {
+        Ok(serde_yaml::from_str(
+            r#"
+onramp:
+  - id: in
+    type: metronome
+    config:
+      interval: 1000
+offramp:
+  - id: out
+    type: stdout
+pipeline:
+  main: "select event from in into out;"
+binding:
+  - id: default
+    links:
+      '/onramp/in/{instance}/out': [ '/pipeline/main/{instance}/in' ]
+      '/pipeline/main/{instance}/out': [ '/offramp/out/{instance}/in' ]
+mapping:
+  /binding/default/01:
+    instance: "01"
+"#,
+        )?)
+    }
+
+    #[async_std::test]
+    async fn save_and_load() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let mut deployment = deployment()?;
+        {
+            let store = Store::open(dir.path())?;
+            assert!(store.load()?.is_empty());
+            store.save(&deployment).await?;
+        }
+        let store = Store::open(dir.path())?;
+        let loaded = store.load()?;
+        assert_eq!(deployment.len(), loaded.len());
+        assert_eq!(deployment.entries()?, loaded.entries()?);
+
+        // removed artefacts are removed from the store
+        deployment.mapping.clear();
+        deployment.offramp.clear();
+        store.save(&deployment).await?;
+        let loaded = store.load()?;
+        assert_eq!(3, loaded.len());
+        assert_eq!(deployment.entries()?, loaded.entries()?);
+
+        // single entries are recorded and removed
+        store
+            .insert(
+                PIPELINE,
+                "other",
+                b"select event from in into out;".to_vec(),
+            )
+            .await?;
+        store.remove(BINDING, "default").await?;
+        let loaded = store.load()?;
+        assert_eq!(3, loaded.len());
+        assert!(loaded.binding.is_empty());
+        assert!(loaded.pipeline.contains_key("other"));
+        Ok(())
+    }
+}
@@ -13,21 +13,26 @@
 // limitations under the License.
 
 use crate::config::{BindingVec, Config, MappingMap, OffRampVec, OnRampVec};
+use crate::dlq::{self, DeadLetter};
 use crate::errors::{Error, ErrorKind, Result};
 use crate::lifecycle::{ActivationState, ActivatorLifecycleFsm};
 use crate::registry::{Registries, ServantId};
 use crate::repository::{
     Artefact, BindingArtefact, OfframpArtefact, OnrampArtefact, PipelineArtefact, Repositories,
 };
-use crate::url::ports::METRICS;
-use crate::url::TremorUrl;
+use crate::store::{self, Deployment, Store};
+use crate::tap::{Tap, TapEvent};
+use crate::url::ports::{ERR, IN, METRICS, OUT};
+use crate::url::{ResourceType, TremorUrl};
 use async_channel::bounded;
 use async_std::io::prelude::*;
 use async_std::path::Path;
 use async_std::task::{self, JoinHandle};
-use hashbrown::HashMap;
+use hashbrown::{HashMap, HashSet};
 use tremor_common::asy::file;
 use tremor_common::time::nanotime;
+use tremor_pipeline::{Event, EventOriginUri};
+use tremor_script::{Object, Value};
 
 pub(crate) use crate::offramp;
 pub(crate) use crate::onramp;
@@ -62,6 +67,10 @@ pub(crate) enum ManagerMsg {
         async_channel::Sender<Result<pipeline::Addr>>,
         pipeline::Create,
     ),
+    ReloadPipeline(
+        async_channel::Sender<Result<pipeline::ReloadReply>>,
+        pipeline::Reload,
+    ),
     CreateOnramp(
         async_channel::Sender<Result<onramp::Addr>>,
         Box<onramp::Create>,
@@ -97,6 +106,11 @@ impl Manager {
                             .send(pipeline::ManagerMsg::Create(r, Box::new(c)))
                             .await?
                     }
+                    ManagerMsg::ReloadPipeline(r, c) => {
+                        self.pipeline
+                            .send(pipeline::ManagerMsg::Reload(r, Box::new(c)))
+                            .await?
+                    }
                     ManagerMsg::CreateOnramp(r, c) => {
                         self.onramp.send(onramp::ManagerMsg::Create(r, c)).await?
                     }
@@ -130,6 +144,7 @@ pub struct World {
     /// Registry
     pub reg: Registries,
     storage_directory: Option<String>,
+    store: Option<Store>,
 }
 
 impl World {
@@ -227,6 +242,129 @@ impl World {
         }
     }
 
+    /// Pauses a pipeline instance, holding back its events and closing the
+    /// circuit breakers of its inputs until it is resumed
+    ///
+    /// # Errors
+    ///  * if the id isn't a running pipeline instance
+    pub async fn pause_pipeline(&self, id: &TremorUrl) -> Result<pipeline::ControlReply> {
+        self.control_pipeline(id, pipeline::Control::Pause).await
+    }
+
+    /// Resumes a paused pipeline instance, processing all held back events
+    ///
+    /// # Errors
+    ///  * if the id isn't a running pipeline instance
+    pub async fn resume_pipeline(&self, id: &TremorUrl) -> Result<pipeline::ControlReply> {
+        self.control_pipeline(id, pipeline::Control::Resume).await
+    }
+
+    /// Steps a single held back event through a paused pipeline instance
+    ///
+    /// # Errors
+    ///  * if the id isn't a running pipeline instance or the pipeline isn't paused
+    pub async fn step_pipeline(&self, id: &TremorUrl) -> Result<pipeline::ControlReply> {
+        self.control_pipeline(id, pipeline::Control::Step).await
+    }
+
+    /// Replaces the query of a running pipeline instance in place, keeping its
+    /// links. The state of the operators is carried over by node id, see
+    /// `ExecutableGraph::migrate_state`. If the new query can't be deployed the
+    /// instance keeps running the current one. Once reloaded the artefact in the
+    /// repository is replaced as well, so new instances and exported deployments
+    /// run the new query.
+    ///
+    /// # Errors
+    ///  * if the id isn't a running pipeline instance or the new query can't be deployed
+    pub async fn reload_pipeline(
+        &self,
+        id: &TremorUrl,
+        config: PipelineArtefact,
+    ) -> Result<pipeline::ReloadReply> {
+        if let Some(addr) = self.reg.find_pipeline(id).await? {
+            let (tx, rx) = bounded(1);
+            self.system
+                .send(ManagerMsg::ReloadPipeline(
+                    tx,
+                    pipeline::Reload {
+                        config: config.clone(),
+                        addr,
+                    },
+                ))
+                .await?;
+            let reply = rx.recv().await??;
+            let source = config.source().as_bytes().to_vec();
+            self.repo.update_pipeline(id, config).await?;
+            if let Some(artefact) = id.artefact() {
+                self.record(store::PIPELINE, artefact, source).await?;
+            }
+            Ok(reply)
+        } else {
+            Err(ErrorKind::ArtefactNotFound(id.to_string()).into())
+        }
+    }
+
+    /// Attaches a live tap to the port of a running onramp, pipeline or offramp,
+    /// sampling `rate` of the events flowing through it. For pipelines `node`
+    /// selects an operator whose output port is tapped instead of an output
+    /// of the pipeline itself.
+    ///
+    /// The tap is detached once the returned receiver is dropped.
+    ///
+    /// # Errors
+    ///  * if the url isn't the port of a running instance or the rate isn't in `(0, 1]`
+    pub async fn tap(
+        &self,
+        url: &TremorUrl,
+        node: Option<&str>,
+        rate: f64,
+    ) -> Result<async_channel::Receiver<TapEvent>> {
+        let (tap, rx) = Tap::new(url.clone(), node.map(ToString::to_string), rate)?;
+        let mut instance = url.clone();
+        instance.trim_to_instance();
+        let not_found = || Error::from(ErrorKind::ArtefactNotFound(instance.to_string()));
+        match (url.resource_type(), node) {
+            (Some(ResourceType::Pipeline), _) => {
+                let addr = self.reg.find_pipeline(&instance).await?;
+                addr.ok_or_else(not_found)?.tap(tap).await?;
+            }
+            (Some(ResourceType::Onramp), None)
+                if tap.port() == OUT.as_ref() || tap.port() == ERR.as_ref() =>
+            {
+                let addr = self.reg.find_onramp(&instance).await?;
+                addr.ok_or_else(not_found)?
+                    .send(onramp::Msg::Tap(tap))
+                    .await?;
+            }
+            (Some(ResourceType::Offramp), None) => {
+                let addr = self.reg.find_offramp(&instance).await?;
+                addr.ok_or_else(not_found)?
+                    .send(offramp::Msg::Tap(tap))
+                    .await?;
+            }
+            _ => {
+                return Err(ErrorKind::InvalidTap(
+                    url.to_string(),
+                    "only onramp outputs, pipelines and offramps can be tapped".into(),
+                )
+                .into())
+            }
+        }
+        Ok(rx)
+    }
+
+    async fn control_pipeline(
+        &self,
+        id: &TremorUrl,
+        control: pipeline::Control,
+    ) -> Result<pipeline::ControlReply> {
+        if let Some(addr) = self.reg.find_pipeline(id).await? {
+            addr.control(control).await
+        } else {
+            Err(ErrorKind::ArtefactNotFound(id.to_string()).into())
+        }
+    }
+
     /// Stop the runtime
     ///
     /// # Errors
@@ -559,10 +697,13 @@ impl World {
         >,
     ) -> Result<<BindingArtefact as Artefact>::LinkResult> {
         if let Some(binding_a) = self.repo.find_binding(id).await? {
+            let mapping = serde_yaml::to_vec(&mappings)?;
             let r = binding_a.artefact.link(self, id, mappings).await?;
             if self.reg.find_binding(id).await?.is_none() {
                 self.bind_binding_a(id, &r).await?;
             };
+            self.record(store::MAPPING, &id.to_string(), mapping)
+                .await?;
             Ok(r)
         } else {
             Err(ErrorKind::ArtefactNotFound(id.to_string()).into())
@@ -593,6 +734,452 @@ impl World {
         Ok(config)
     }
 
+    /// Exports the deployment of the world: all artefacts published to it,
+    /// except for system artefacts, and the mappings of its linked bindings
+    ///
+    /// # Errors
+    ///  * if the deployment can't be exported
+    pub async fn export_deployment(&self) -> Result<Deployment> {
+        let config = self.to_config().await?;
+        let mut pipeline = HashMap::new();
+        for id in self.repo.list_pipelines().await? {
+            match (self.repo.find_pipeline(&id).await?, id.artefact()) {
+                (Some(wrapper), Some(artefact)) if !wrapper.system => {
+                    pipeline.insert(artefact.to_string(), wrapper.artefact.source().to_string());
+                }
+                _ => (),
+            }
+        }
+        Ok(Deployment {
+            onramp: config.onramp,
+            offramp: config.offramp,
+            pipeline,
+            binding: config.binding,
+            mapping: config.mapping,
+        })
+    }
+
+    /// Imports a deployment, publishing its artefacts and linking its bindings.
+    /// Artefacts and mappings that are already deployed unchanged are skipped.
+    /// The whole deployment is checked before anything is deployed: pipelines
+    /// need to compile, bindings may only link published artefacts and it may
+    /// not change artefacts or mappings that are already deployed. If deploying
+    /// fails part way, everything deployed by the import is rolled back.
+    ///
+    /// Returns the number of artefacts and mappings deployed
+    ///
+    /// # Errors
+    ///  * if the deployment is inconsistent, conflicts with the running one or can't be deployed
+    #[allow(clippy::too_many_lines)]
+    pub async fn import_deployment(&self, deployment: Deployment) -> Result<usize> {
+        let mut published = HashSet::new();
+        let mut define = |url: &TremorUrl| {
+            if published.insert(url.clone()) {
+                Ok(())
+            } else {
+                Err(Error::from(ErrorKind::InvalidDeployment(format!(
+                    "{} is defined twice",
+                    url
+                ))))
+            }
+        };
+
+        let mut onramps = Vec::new();
+        for o in deployment.onramp {
+            let url = TremorUrl::from_onramp_id(&o.id)?;
+            define(&url)?;
+            match self.repo.find_onramp(&url).await? {
+                Some(deployed) if unchanged(&deployed.artefact, &o)? => (),
+                Some(_) => return Err(ErrorKind::DeploymentConflict(url.to_string()).into()),
+                None => onramps.push((url, o)),
+            }
+        }
+        let mut offramps = Vec::new();
+        for o in deployment.offramp {
+            let url = TremorUrl::from_offramp_id(&o.id)?;
+            define(&url)?;
+            match self.repo.find_offramp(&url).await? {
+                Some(deployed) if unchanged(&deployed.artefact, &o)? => (),
+                Some(_) => return Err(ErrorKind::DeploymentConflict(url.to_string()).into()),
+                None => offramps.push((url, o)),
+            }
+        }
+        let aggr_reg = tremor_script::registry::aggr();
+        let module_path = tremor_script::path::load();
+        let mut pipelines = Vec::new();
+        for (id, source) in deployment.pipeline {
+            let url = TremorUrl::parse(&format!("/pipeline/{}", id))?;
+            define(&url)?;
+            match self.repo.find_pipeline(&url).await? {
+                Some(deployed) if deployed.artefact.source() == source => (),
+                Some(_) => return Err(ErrorKind::DeploymentConflict(url.to_string()).into()),
+                None => {
+                    let query = tremor_pipeline::query::Query::parse(
+                        &module_path,
+                        &source,
+                        &id,
+                        Vec::new(),
+                        &*tremor_pipeline::FN_REGISTRY.lock()?,
+                        &aggr_reg,
+                    )
+                    .map_err(|e| {
+                        ErrorKind::InvalidDeployment(format!("{} is invalid: {}", url, e.error))
+                    })?;
+                    pipelines.push((url, query));
+                }
+            }
+        }
+        let mut bindings = Vec::new();
+        for binding in deployment.binding {
+            let url = TremorUrl::parse(&format!("/binding/{}", binding.id))?;
+            define(&url)?;
+            match self.repo.find_binding(&url).await? {
+                Some(deployed) if unchanged(&deployed.artefact.binding, &binding)? => (),
+                Some(_) => return Err(ErrorKind::DeploymentConflict(url.to_string()).into()),
+                None => bindings.push((url, binding)),
+            }
+        }
+
+        for (url, binding) in &bindings {
+            for link in binding
+                .links
+                .iter()
+                .flat_map(|(from, to)| to.iter().chain(Some(from)))
+            {
+                let mut artefact = link.clone();
+                artefact.trim_to_artefact();
+                if !published.contains(&artefact) && !self.is_published(&artefact).await? {
+                    return Err(ErrorKind::InvalidDeployment(format!(
+                        "{} links {}, which is not published",
+                        url, link
+                    ))
+                    .into());
+                }
+            }
+        }
+        let mut mappings = Vec::new();
+        for (url, mapping) in deployment.mapping {
+            let mut artefact = url.clone();
+            artefact.trim_to_artefact();
+            if !published.contains(&artefact) && !self.is_published(&artefact).await? {
+                return Err(ErrorKind::InvalidDeployment(format!(
+                    "{} is mapped, but not published",
+                    artefact
+                ))
+                .into());
+            }
+            let deployed = self.reg.find_binding(&url).await?;
+            match deployed.map(|b| b.mapping.and_then(|mut m| m.remove(&url))) {
+                Some(Some(deployed)) if deployed == mapping => (),
+                Some(_) => return Err(ErrorKind::DeploymentConflict(url.to_string()).into()),
+                None => mappings.push((url, mapping)),
+            }
+        }
+
+        let count =
+            onramps.len() + offramps.len() + pipelines.len() + bindings.len() + mappings.len();
+        let mut deployed = Vec::with_capacity(count);
+        let result = self
+            .deploy(
+                onramps,
+                offramps,
+                pipelines,
+                bindings,
+                mappings,
+                &mut deployed,
+            )
+            .await;
+        if let Err(e) = result {
+            error!(
+                "Failed to import the deployment, rolling back {} artefacts and mappings: {}",
+                deployed.len(),
+                e
+            );
+            self.roll_back(deployed).await;
+            return Err(e);
+        }
+        Ok(count)
+    }
+
+    /// Deploys the checked parts of an imported deployment, pushing everything
+    /// deployed to `deployed` so it can be rolled back
+    async fn deploy(
+        &self,
+        onramps: Vec<(TremorUrl, OnrampArtefact)>,
+        offramps: Vec<(TremorUrl, OfframpArtefact)>,
+        pipelines: Vec<(TremorUrl, PipelineArtefact)>,
+        bindings: Vec<(TremorUrl, crate::config::Binding)>,
+        mappings: Vec<(TremorUrl, HashMap<String, String>)>,
+        deployed: &mut Vec<(TremorUrl, bool)>,
+    ) -> Result<()> {
+        for (url, o) in onramps {
+            self.publish_onramp(&url, o).await?;
+            deployed.push((url, false));
+        }
+        for (url, o) in offramps {
+            self.publish_offramp(&url, o).await?;
+            deployed.push((url, false));
+        }
+        for (url, query) in pipelines {
+            self.publish_pipeline(&url, query).await?;
+            deployed.push((url, false));
+        }
+        for (url, binding) in bindings {
+            let artefact = BindingArtefact {
+                binding,
+                mapping: None,
+            };
+            self.publish_binding(&url, artefact).await?;
+            deployed.push((url, false));
+        }
+        for (url, mapping) in mappings {
+            self.link_binding(&url, mapping).await?;
+            deployed.push((url, true));
+        }
+        Ok(())
+    }
+
+    /// Rolls back a partially deployed import in reverse order, `deployed`
+    /// holds the published artefacts and the linked mappings (flagged `true`)
+    async fn roll_back(&self, deployed: Vec<(TremorUrl, bool)>) {
+        for (url, linked) in deployed.into_iter().rev() {
+            let result = if linked {
+                self.unlink_binding(&url, HashMap::new()).await.map(|_| ())
+            } else {
+                match url.resource_type() {
+                    Some(ResourceType::Onramp) => self.unpublish_onramp(&url).await.map(|_| ()),
+                    Some(ResourceType::Offramp) => self.unpublish_offramp(&url).await.map(|_| ()),
+                    Some(ResourceType::Pipeline) => self.unpublish_pipeline(&url).await.map(|_| ()),
+                    Some(ResourceType::Binding) => self.unpublish_binding(&url).await.map(|_| ()),
+                    None => Ok(()),
+                }
+            };
+            if let Err(e) = result {
+                error!("Failed to roll back {}: {}", url, e);
+            }
+        }
+    }
+
+    /// Removes the artefacts and mappings that are already deployed from a
+    /// deployment, the deployed ones take precedence
+    async fn without_deployed(&self, mut deployment: Deployment) -> Result<Deployment> {
+        let mut kept = Vec::with_capacity(deployment.onramp.len());
+        for o in deployment.onramp {
+            if self.is_deployed(&TremorUrl::from_onramp_id(&o.id)?).await? {
+                continue;
+            }
+            kept.push(o);
+        }
+        deployment.onramp = kept;
+        let mut kept = Vec::with_capacity(deployment.offramp.len());
+        for o in deployment.offramp {
+            if self
+                .is_deployed(&TremorUrl::from_offramp_id(&o.id)?)
+                .await?
+            {
+                continue;
+            }
+            kept.push(o);
+        }
+        deployment.offramp = kept;
+        let mut kept = HashMap::with_capacity(deployment.pipeline.len());
+        for (id, source) in deployment.pipeline {
+            if self
+                .is_deployed(&TremorUrl::parse(&format!("/pipeline/{}", id))?)
+                .await?
+            {
+                continue;
+            }
+            kept.insert(id, source);
+        }
+        deployment.pipeline = kept;
+        let mut kept = Vec::with_capacity(deployment.binding.len());
+        for b in deployment.binding {
+            if self
+                .is_deployed(&TremorUrl::parse(&format!("/binding/{}", b.id))?)
+                .await?
+            {
+                continue;
+            }
+            kept.push(b);
+        }
+        deployment.binding = kept;
+        let mut kept = HashMap::with_capacity(deployment.mapping.len());
+        for (url, mapping) in deployment.mapping {
+            if self.reg.find_binding(&url).await?.is_some() {
+                info!("{} is already linked, ignoring the stored mapping", url);
+                continue;
+            }
+            kept.insert(url, mapping);
+        }
+        deployment.mapping = kept;
+        Ok(deployment)
+    }
+
+    async fn is_deployed(&self, id: &TremorUrl) -> Result<bool> {
+        let deployed = self.is_published(id).await?;
+        if deployed {
+            info!("{} is already published, ignoring the stored one", id);
+        }
+        Ok(deployed)
+    }
+
+    async fn is_published(&self, id: &TremorUrl) -> Result<bool> {
+        Ok(match id.resource_type() {
+            Some(ResourceType::Onramp) => self.repo.find_onramp(id).await?.is_some(),
+            Some(ResourceType::Offramp) => self.repo.find_offramp(id).await?.is_some(),
+            Some(ResourceType::Pipeline) => self.repo.find_pipeline(id).await?.is_some(),
+            Some(ResourceType::Binding) => self.repo.find_binding(id).await?.is_some(),
+            None => false,
+        })
+    }
+
+    /// Opens the persistent deployment store in the directory `path` and
+    /// imports the deployment stored in it. Artefacts and mappings that are
+    /// already deployed, e.g. loaded from files, take precedence over stored
+    /// ones with the same id. The store is then brought in line with the
+    /// world and from then on every publish, unpublish, link and unlink is
+    /// recorded in it, so the deployment survives restarts.
+    ///
+    /// Returns the number of artefacts and mappings deployed from the store
+    ///
+    /// # Errors
+    ///  * if the store can't be opened or the stored deployment can't be imported
+    pub async fn open_store(&mut self, path: &str) -> Result<usize> {
+        let store = Store::open(path)?;
+        let deployment = self.without_deployed(store.load()?).await?;
+        info!(
+            "Restoring {} artefacts and mappings from the deployment store {}",
+            deployment.len(),
+            path
+        );
+        let count = self.import_deployment(deployment).await?;
+        store.save(&self.export_deployment().await?).await?;
+        self.store = Some(store);
+        Ok(count)
+    }
+
+    /// Records the entry `id` of `kind` in the deployment store if one is open
+    async fn record(&self, kind: &str, id: &str, value: Vec<u8>) -> Result<()> {
+        if let Some(store) = &self.store {
+            store.insert(kind, id, value).await?;
+        }
+        Ok(())
+    }
+
+    /// Removes the entry `id` of `kind` from the deployment store if one is open
+    async fn forget(&self, kind: &str, id: &str) -> Result<()> {
+        if let Some(store) = &self.store {
+            store.remove(kind, id).await?;
+        }
+        Ok(())
+    }
+
+    /// Publishes an onramp and records it in the deployment store
+    ///
+    /// # Errors
+    ///  * if the onramp can't be published or recorded
+    pub async fn publish_onramp(
+        &self,
+        id: &TremorUrl,
+        artefact: OnrampArtefact,
+    ) -> Result<OnrampArtefact> {
+        let value = serde_yaml::to_vec(&artefact)?;
+        let result = self.repo.publish_onramp(id, false, artefact).await?;
+        self.record(store::ONRAMP, &result.id, value).await?;
+        Ok(result)
+    }
+
+    /// Unpublishes an onramp and removes it from the deployment store
+    ///
+    /// # Errors
+    ///  * if the onramp can't be unpublished
+    pub async fn unpublish_onramp(&self, id: &TremorUrl) -> Result<OnrampArtefact> {
+        let result = self.repo.unpublish_onramp(id).await?;
+        self.forget(store::ONRAMP, &result.id).await?;
+        Ok(result)
+    }
+
+    /// Publishes an offramp and records it in the deployment store
+    ///
+    /// # Errors
+    ///  * if the offramp can't be published or recorded
+    pub async fn publish_offramp(
+        &self,
+        id: &TremorUrl,
+        artefact: OfframpArtefact,
+    ) -> Result<OfframpArtefact> {
+        let value = serde_yaml::to_vec(&artefact)?;
+        let result = self.repo.publish_offramp(id, false, artefact).await?;
+        self.record(store::OFFRAMP, &result.id, value).await?;
+        Ok(result)
+    }
+
+    /// Unpublishes an offramp and removes it from the deployment store
+    ///
+    /// # Errors
+    ///  * if the offramp can't be unpublished
+    pub async fn unpublish_offramp(&self, id: &TremorUrl) -> Result<OfframpArtefact> {
+        let result = self.repo.unpublish_offramp(id).await?;
+        self.forget(store::OFFRAMP, &result.id).await?;
+        Ok(result)
+    }
+
+    /// Publishes a pipeline and records it in the deployment store
+    ///
+    /// # Errors
+    ///  * if the pipeline can't be published or recorded
+    pub async fn publish_pipeline(
+        &self,
+        id: &TremorUrl,
+        artefact: PipelineArtefact,
+    ) -> Result<PipelineArtefact> {
+        let result = self.repo.publish_pipeline(id, false, artefact).await?;
+        if let Some(artefact) = id.artefact() {
+            let source = result.source().as_bytes().to_vec();
+            self.record(store::PIPELINE, artefact, source).await?;
+        }
+        Ok(result)
+    }
+
+    /// Unpublishes a pipeline and removes it from the deployment store
+    ///
+    /// # Errors
+    ///  * if the pipeline can't be unpublished
+    pub async fn unpublish_pipeline(&self, id: &TremorUrl) -> Result<PipelineArtefact> {
+        let result = self.repo.unpublish_pipeline(id).await?;
+        if let Some(artefact) = id.artefact() {
+            self.forget(store::PIPELINE, artefact).await?;
+        }
+        Ok(result)
+    }
+
+    /// Publishes a binding and records it in the deployment store
+    ///
+    /// # Errors
+    ///  * if the binding can't be published or recorded
+    pub async fn publish_binding(
+        &self,
+        id: &TremorUrl,
+        artefact: BindingArtefact,
+    ) -> Result<BindingArtefact> {
+        let value = serde_yaml::to_vec(&artefact.binding)?;
+        let result = self.repo.publish_binding(id, false, artefact).await?;
+        self.record(store::BINDING, &result.binding.id, value)
+            .await?;
+        Ok(result)
+    }
+
+    /// Unpublishes a binding and removes it from the deployment store
+    ///
+    /// # Errors
+    ///  * if the binding can't be unpublished
+    pub async fn unpublish_binding(&self, id: &TremorUrl) -> Result<BindingArtefact> {
+        let result = self.repo.unpublish_binding(id).await?;
+        self.forget(store::BINDING, &result.binding.id).await?;
+        Ok(result)
+    }
+
     /// Saves the current config
     ///
     /// # Errors
@@ -620,6 +1207,97 @@ impl World {
         }
     }
 
+    /// The dead-letter queue kept by the `dlq` offramp `id`
+    async fn dead_letter_queue(&self, id: &TremorUrl) -> Result<dlq::Queue> {
+        let offramp = self
+            .repo
+            .find_offramp(id)
+            .await?
+            .ok_or_else(|| ErrorKind::ArtefactNotFound(id.to_string()))?
+            .artefact;
+        if offramp.binding_type != "dlq" {
+            return Err(
+                ErrorKind::InvalidDeadLetter(format!("{} is no dead-letter queue", id)).into(),
+            );
+        }
+        let config: crate::sink::dlq::Config = offramp
+            .config
+            .as_ref()
+            .map(|c| serde_yaml::from_value(c.clone()))
+            .transpose()?
+            .ok_or_else(|| Error::from(format!("{} has no config", id)))?;
+        dlq::Queue::open(&config.dir)
+    }
+
+    /// Lists the dead letters kept by the `dlq` offramp `id`
+    ///
+    /// # Errors
+    ///  * if the id isn't a `dlq` offramp or its queue can't be read
+    pub async fn list_dead_letters(&self, id: &TremorUrl) -> Result<Vec<DeadLetter>> {
+        self.dead_letter_queue(id).await?.list()
+    }
+
+    /// Removes the dead letter `letter` kept by the `dlq` offramp `id`
+    ///
+    /// # Errors
+    ///  * if the id isn't a `dlq` offramp or the dead letter can't be removed
+    pub async fn remove_dead_letter(&self, id: &TremorUrl, letter: u64) -> Result<DeadLetter> {
+        self.dead_letter_queue(id).await?.remove(letter).await
+    }
+
+    /// Replays the dead letter `letter` kept by the `dlq` offramp `id` into
+    /// the onramp or pipeline instance it came from and removes it from the
+    /// queue
+    ///
+    /// # Errors
+    ///  * if the id isn't a `dlq` offramp, the onramp or pipeline isn't
+    ///    running or the dead letter can't be replayed
+    pub async fn replay_dead_letter(&self, id: &TremorUrl, letter: u64) -> Result<DeadLetter> {
+        let queue = self.dead_letter_queue(id).await?;
+        let letter = queue.get(letter)?;
+        let letter_id = letter.id;
+        if let Some(pipeline) = &letter.pipeline {
+            // the original event enters the pipeline again
+            let mut pipeline_id = TremorUrl::parse(pipeline)?;
+            pipeline_id.trim_to_instance();
+            let addr = self
+                .reg
+                .find_pipeline(&pipeline_id)
+                .await?
+                .ok_or_else(|| ErrorKind::ArtefactNotFound(pipeline_id.to_string()))?;
+            let event = Event {
+                data: (letter.event()?, Value::from(Object::with_capacity(0))).into(),
+                ingest_ns: nanotime(),
+                origin_uri: Some(EventOriginUri {
+                    scheme: "tremor-dlq".to_string(),
+                    host: crate::utils::hostname(),
+                    path: vec![letter_id.to_string()],
+                    ..EventOriginUri::default()
+                }),
+                ..Event::default()
+            };
+            addr.send(pipeline::Msg::Event { input: IN, event }).await?;
+        } else {
+            let onramp = letter.onramp.as_deref().ok_or_else(|| {
+                ErrorKind::InvalidDeadLetter(format!(
+                    "dead letter {} names no onramp or pipeline",
+                    letter_id
+                ))
+            })?;
+            let mut onramp_id = TremorUrl::parse(onramp)?;
+            onramp_id.trim_to_instance();
+            let onramp = self
+                .reg
+                .find_onramp(&onramp_id)
+                .await?
+                .ok_or_else(|| ErrorKind::ArtefactNotFound(onramp_id.to_string()))?;
+            let (tx, rx) = bounded(1);
+            onramp.send(onramp::Msg::Replay(letter, tx)).await?;
+            rx.recv().await??;
+        }
+        queue.remove(letter_id).await
+    }
+
     /// Unlinks a binding
     ///
     /// # Errors
@@ -636,6 +1314,7 @@ impl World {
             if binding.unlink(self, id, mappings).await? {
                 self.unbind_binding_a(id, &binding).await?;
             }
+            self.forget(store::MAPPING, &id.to_string()).await?;
             return Ok(binding);
         }
 
@@ -672,6 +1351,7 @@ impl World {
             repo,
             reg,
             storage_directory,
+            store: None,
         };
 
         world.register_system().await?;
@@ -762,3 +1442,8 @@ type: stderr
         rx.recv().await?
     }
 }
+
+/// Checks if an artefact is the same as the deployed one
+fn unchanged<A: serde::Serialize>(deployed: &A, artefact: &A) -> Result<bool> {
+    Ok(serde_yaml::to_value(deployed)? == serde_yaml::to_value(artefact)?)
+}
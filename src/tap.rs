@@ -0,0 +1,213 @@
+// Copyright 2020-2021, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Live taps on the ports of running onramps, pipelines and offramps.
+//!
+//! A tap is not part of the bindings of the world: it never receives
+//! contraflow nor exerts backpressure. Sampled events are dropped if the
+//! receiving end can't keep up, and a tap is detached once its receiving
+//! end is dropped.
+
+use crate::errors::{ErrorKind, Result};
+use crate::url::TremorUrl;
+use async_channel::{Receiver, Sender, TrySendError};
+use tremor_pipeline::{Event, Sampler};
+use tremor_script::Value;
+
+/// Number of sampled events buffered for a tap before events are dropped
+pub(crate) const TAP_QSIZE: usize = 64;
+
+/// An event sampled from a tapped port
+#[derive(Debug, Clone, Serialize)]
+pub struct TapEvent {
+    /// url of the tapped port
+    pub url: String,
+    /// operator node of a tapped pipeline
+    pub node: Option<String>,
+    /// event id
+    pub id: String,
+    /// ingest time of the event in nanoseconds
+    pub ingest_ns: u64,
+    /// event payload
+    pub data: Value<'static>,
+    /// event metadata
+    pub meta: Value<'static>,
+}
+
+/// The sending end of a tap on a single port
+#[derive(Debug, Clone)]
+pub(crate) struct Tap {
+    url: TremorUrl,
+    node: Option<String>,
+    sampler: Sampler,
+    tx: Sender<TapEvent>,
+}
+
+impl Tap {
+    /// Creates a tap on the port of `url`, sampling `rate` of all events
+    ///
+    /// # Errors
+    ///  * if `url` has no port or `rate` is not in `(0, 1]`
+    pub(crate) fn new(
+        url: TremorUrl,
+        node: Option<String>,
+        rate: f64,
+    ) -> Result<(Self, Receiver<TapEvent>)> {
+        if url.instance_port().is_none() {
+            return Err(ErrorKind::InvalidTap(url.to_string(), "no port given".into()).into());
+        }
+        if rate.is_nan() || rate <= 0.0 || rate > 1.0 {
+            return Err(ErrorKind::InvalidTap(
+                url.to_string(),
+                format!("sample rate {} is not in (0, 1]", rate),
+            )
+            .into());
+        }
+        let (tx, rx) = async_channel::bounded(TAP_QSIZE);
+        let tap = Self {
+            url,
+            node,
+            sampler: Sampler::new(rate),
+            tx,
+        };
+        Ok((tap, rx))
+    }
+
+    /// The url of the tapped port
+    pub(crate) fn url(&self) -> &TremorUrl {
+        &self.url
+    }
+
+    /// The tapped port
+    pub(crate) fn port(&self) -> &str {
+        self.url.instance_port().unwrap_or_default()
+    }
+
+    /// The tapped operator node of a pipeline
+    pub(crate) fn node(&self) -> Option<&str> {
+        self.node.as_deref()
+    }
+
+    /// If the receiving end of the tap is gone
+    pub(crate) fn is_detached(&self) -> bool {
+        self.tx.is_closed()
+    }
+
+    /// A fresh sampler with the sample rate of the tap, for operator
+    /// nodes sampling before events are cloned for the tap
+    pub(crate) fn sampler(&self) -> Sampler {
+        Sampler::new(self.sampler.rate())
+    }
+
+    /// Offers an event seen on the tapped port to the tap
+    pub(crate) fn offer(&mut self, event: &Event) {
+        if self.sampler.sample() {
+            self.send(event);
+        }
+    }
+
+    /// Sends an already sampled event to the tap
+    pub(crate) fn send(&self, event: &Event) {
+        let (data, meta) = event.data.parts();
+        let tap_event = TapEvent {
+            url: self.url.to_string(),
+            node: self.node.clone(),
+            id: event.id.to_string(),
+            ingest_ns: event.ingest_ns,
+            data: data.clone_static(),
+            meta: meta.clone_static(),
+        };
+        match self.tx.try_send(tap_event) {
+            Ok(()) | Err(TrySendError::Closed(_)) => (),
+            Err(TrySendError::Full(_)) => {
+                debug!("[Tap::{}] receiver is lagging, dropping event", self.url);
+            }
+        }
+    }
+}
+
+/// Offers an event seen on `port` of `node` to all matching taps,
+/// returns `true` if any of them is detached and needs to be removed
+pub(crate) fn offer(taps: &mut [Tap], node: Option<&str>, port: &str, event: &Event) -> bool {
+    let mut detached = false;
+    for tap in taps
+        .iter_mut()
+        .filter(|t| t.node() == node && t.port() == port)
+    {
+        if tap.is_detached() {
+            detached = true;
+        } else {
+            tap.offer(event);
+        }
+    }
+    detached
+}
+
+/// Removes all detached taps, returns `true` if any were removed
+pub(crate) fn detach(taps: &mut Vec<Tap>) -> bool {
+    let len = taps.len();
+    taps.retain(|t| !t.is_detached());
+    len != taps.len()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tremor_value::literal;
+
+    #[test]
+    fn sampling() -> Result<()> {
+        let url = TremorUrl::parse("/onramp/snot/01/out")?;
+        let (mut tap, rx) = Tap::new(url, None, 0.25)?;
+        assert_eq!("out", tap.port());
+        for i in 0..8_u64 {
+            let event = Event {
+                ingest_ns: i,
+                data: (literal!({ "i": i }), literal!({})).into(),
+                ..Event::default()
+            };
+            tap.offer(&event);
+        }
+        let sampled: Vec<u64> = std::iter::from_fn(|| rx.try_recv().ok())
+            .map(|e| e.ingest_ns)
+            .collect();
+        assert_eq!(vec![3, 7], sampled);
+        Ok(())
+    }
+
+    #[test]
+    fn detaching() -> Result<()> {
+        let url = TremorUrl::parse("/offramp/snot/01/in")?;
+        let (tap, rx) = Tap::new(url.clone(), None, 1.0)?;
+        let (other, _other_rx) = Tap::new(url, None, 1.0)?;
+        let mut taps = vec![tap, other];
+        assert!(!detach(&mut taps));
+        assert!(!offer(&mut taps, None, "in", &Event::default()));
+        drop(rx);
+        assert!(offer(&mut taps, None, "in", &Event::default()));
+        assert!(detach(&mut taps));
+        assert_eq!(1, taps.len());
+        Ok(())
+    }
+
+    #[test]
+    fn bad_taps() -> Result<()> {
+        assert!(Tap::new(TremorUrl::parse("/onramp/snot/01")?, None, 1.0).is_err());
+        let url = TremorUrl::parse("/onramp/snot/01/out")?;
+        assert!(Tap::new(url.clone(), None, 0.0).is_err());
+        assert!(Tap::new(url.clone(), None, 1.5).is_err());
+        assert!(Tap::new(url, None, f64::NAN).is_err());
+        Ok(())
+    }
+}
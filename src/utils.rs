@@ -11,7 +11,10 @@
 // WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
 // See the License for the specific language governing permissions and
 // limitations under the License.
-use crate::errors::{Error, ErrorKind};
+use crate::errors::{Error, ErrorKind, Result};
+use halfbrown::HashMap;
+use std::sync::Mutex;
+
 /// Fetches a hostname with `tremor-host.local` being the default
 #[must_use]
 #[cfg(not(tarpaulin_include))]
@@ -25,3 +28,26 @@ pub fn hostname() -> String {
         })
         .unwrap_or_else(|_| "tremor_host.local".to_string())
 }
+
+lazy_static! {
+    /// sled locks its directory, so everything using
+    /// the same directory shares a db
+    static ref SLED_DBS: Mutex<HashMap<String, sled::Db>> = Mutex::new(HashMap::new());
+}
+
+/// Opens the sled database in `dir`, or shares it if it is open already
+///
+/// # Errors
+///  * if the database can't be opened
+pub(crate) fn open_sled(dir: &str) -> Result<sled::Db> {
+    let mut dbs = SLED_DBS
+        .lock()
+        .map_err(|_| Error::from("sled registry poisoned"))?;
+    if let Some(db) = dbs.get(dir) {
+        Ok(db.clone())
+    } else {
+        let db = sled::open(dir)?;
+        dbs.insert(dir.to_string(), db.clone());
+        Ok(db)
+    }
+}
@@ -120,6 +120,9 @@ test_cases!(
     window_by_two_scripted,
     window_by_two,
     window_size_tilted,
+    window_sliding_size,
+    window_session_gap,
+    window_event_time,
     // Preprocessor + modules
     pp_win,
     pp_script,
@@ -143,6 +143,9 @@ test_cases!(
     pp_embed_unrecognized_token5,
     // INSERT
     window_both_settings,
+    window_sliding_bad_hop,
+    window_session_no_gap,
+    window_event_time_no_script,
     window_group_by_event_in_target,
     window_event_in_target,
     aggr_arity,
@@ -201,4 +201,5 @@ test_cases!(
     heredoc_interpolation,
     heredoc_usefn_interpolation,
     heredoc_regression,
+    session_ident,
 );
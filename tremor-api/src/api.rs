@@ -20,10 +20,14 @@ use tremor_runtime::system::World;
 use tremor_runtime::url::TremorUrl;
 
 pub mod binding;
+pub mod dead_letter;
+pub mod deployment;
+pub mod metrics;
 pub mod offramp;
 pub mod onramp;
 pub mod pipeline;
 pub mod prelude;
+pub mod tap;
 pub mod version;
 
 pub type Request = tide::Request<State>;
@@ -38,11 +38,10 @@ pub async fn publish_artefact(req: Request) -> Result<Response> {
     let (req, binding): (_, tremor_runtime::config::Binding) = decode(req).await?;
     let url = build_url(&["binding", &binding.id])?;
 
-    let repo = &req.state().world.repo;
-    let result = repo
+    let world = &req.state().world;
+    let result = world
         .publish_binding(
             &url,
-            false,
             BindingArtefact {
                 binding,
                 mapping: None,
@@ -56,8 +55,8 @@ pub async fn publish_artefact(req: Request) -> Result<Response> {
 pub async fn unpublish_artefact(req: Request) -> Result<Response> {
     let id = req.param("aid").unwrap_or_default();
     let url = build_url(&["binding", id])?;
-    let repo = &req.state().world.repo;
-    let result = repo.unpublish_binding(&url).await?;
+    let world = &req.state().world;
+    let result = world.unpublish_binding(&url).await?;
     reply(req, result.binding, true, StatusCode::Ok).await
 }
 
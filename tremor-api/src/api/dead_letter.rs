@@ -0,0 +1,50 @@
+// Copyright 2020-2021, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::api::prelude::*;
+
+pub async fn list(req: Request) -> Result<Response> {
+    let url = queue_url(&req)?;
+    let result = req.state().world.list_dead_letters(&url).await?;
+    reply(req, result, false, StatusCode::Ok).await
+}
+
+pub async fn remove(req: Request) -> Result<Response> {
+    let url = queue_url(&req)?;
+    let id = letter_id(&req)?;
+    let result = req.state().world.remove_dead_letter(&url, id).await?;
+    reply(req, result, false, StatusCode::Ok).await
+}
+
+pub async fn replay(req: Request) -> Result<Response> {
+    let url = queue_url(&req)?;
+    let id = letter_id(&req)?;
+    let result = req.state().world.replay_dead_letter(&url, id).await?;
+    reply(req, result, false, StatusCode::Ok).await
+}
+
+fn queue_url(req: &Request) -> Result<TremorUrl> {
+    let id = req.param("aid").unwrap_or_default();
+    build_url(&["offramp", id])
+}
+
+fn letter_id(req: &Request) -> Result<u64> {
+    let id = req.param("id").unwrap_or_default();
+    id.parse().map_err(|e| {
+        Error::new(
+            StatusCode::BadRequest,
+            format!("Invalid dead letter id {}: {}", id, e),
+        )
+    })
+}
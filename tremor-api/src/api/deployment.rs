@@ -0,0 +1,29 @@
+// Copyright 2020-2021, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::api::prelude::*;
+use tremor_runtime::store::Deployment;
+
+pub async fn export(req: Request) -> Result<Response> {
+    let result = req.state().world.export_deployment().await?;
+    reply(req, result, false, StatusCode::Ok).await
+}
+
+pub async fn import(req: Request) -> Result<Response> {
+    let (req, deployment): (_, Deployment) = decode(req).await?;
+    let world = &req.state().world;
+    world.import_deployment(deployment).await?;
+    let result = world.export_deployment().await?;
+    reply(req, result, true, StatusCode::Created).await
+}
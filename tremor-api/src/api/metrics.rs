@@ -0,0 +1,27 @@
+// Copyright 2020-2021, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::api::prelude::*;
+use http_types::headers;
+use tremor_runtime::metrics::prometheus;
+
+/// Content type of the prometheus text exposition format
+const CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+pub async fn get(_req: Request) -> Result<Response> {
+    Ok(Response::builder(StatusCode::Ok)
+        .header(headers::CONTENT_TYPE, CONTENT_TYPE)
+        .body(prometheus::render())
+        .build())
+}
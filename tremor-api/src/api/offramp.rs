@@ -33,16 +33,16 @@ pub async fn list_artefact(req: Request) -> Result<Response> {
 pub async fn publish_artefact(req: Request) -> Result<Response> {
     let (req, data): (_, tremor_runtime::config::OffRamp) = decode(req).await?;
     let url = build_url(&["offramp", &data.id])?;
-    let repo = &req.state().world.repo;
-    let result = repo.publish_offramp(&url, false, data).await?;
+    let world = &req.state().world;
+    let result = world.publish_offramp(&url, data).await?;
     reply(req, result, true, StatusCode::Created).await
 }
 
 pub async fn unpublish_artefact(req: Request) -> Result<Response> {
     let id = req.param("aid").unwrap_or_default();
     let url = build_url(&["offramp", id])?;
-    let repo = &req.state().world.repo;
-    let result = repo.unpublish_offramp(&url).await?;
+    let world = &req.state().world;
+    let result = world.unpublish_offramp(&url).await?;
     reply(req, result, true, StatusCode::Ok).await
 }
 
@@ -57,9 +57,9 @@ pub async fn publish_artefact(mut req: Request) -> Result<Response> {
             })?;
 
             let url = build_url(&["pipeline", &id])?;
-            let repo = &req.state().world.repo;
-            let result = repo
-                .publish_pipeline(&url, false, query)
+            let world = &req.state().world;
+            let result = world
+                .publish_pipeline(&url, query)
                 .await
                 .map(|result| result.source().to_string())?;
             reply_trickle_flat(req, result, true, StatusCode::Created).await
@@ -120,8 +120,8 @@ pub async fn reply_trickle_instanced(
 pub async fn unpublish_artefact(req: Request) -> Result<Response> {
     let id = req.param("aid").unwrap_or_default();
     let url = build_url(&["pipeline", id])?;
-    let repo = &req.state().world.repo;
-    let result = repo
+    let world = &req.state().world;
+    let result = world
         .unpublish_pipeline(&url)
         .await
         .map(|result| result.source().to_string())?;
@@ -150,3 +150,52 @@ pub async fn get_artefact(req: Request) -> Result<Response> {
     )
     .await
 }
+
+pub async fn pause_servant(req: Request) -> Result<Response> {
+    let url = servant_url(&req)?;
+    let result = req.state().world.pause_pipeline(&url).await?;
+    reply(req, result, false, StatusCode::Ok).await
+}
+
+pub async fn resume_servant(req: Request) -> Result<Response> {
+    let url = servant_url(&req)?;
+    let result = req.state().world.resume_pipeline(&url).await?;
+    reply(req, result, false, StatusCode::Ok).await
+}
+
+pub async fn step_servant(req: Request) -> Result<Response> {
+    let url = servant_url(&req)?;
+    let result = req.state().world.step_pipeline(&url).await?;
+    reply(req, result, false, StatusCode::Ok).await
+}
+
+pub async fn reload_servant(mut req: Request) -> Result<Response> {
+    let url = servant_url(&req)?;
+    match content_type(&req) {
+        Some(ResourceType::Trickle) => {
+            let body = req.body_string().await?;
+            let aggr_reg = tremor_script::registry::aggr();
+            let module_path = tremor_script::path::load();
+            let query = Query::parse(
+                &module_path,
+                &body,
+                "<API>",
+                vec![],
+                &*FN_REGISTRY.lock()?,
+                &aggr_reg,
+            )?;
+            let result = req.state().world.reload_pipeline(&url, query).await?;
+            reply(req, result, false, StatusCode::Ok).await
+        }
+        Some(_) | None => Err(Error::new(
+            StatusCode::UnsupportedMediaType,
+            "No content type provided".into(),
+        )),
+    }
+}
+
+fn servant_url(req: &Request) -> Result<TremorUrl> {
+    let a_id = req.param("aid").unwrap_or_default();
+    let s_id = req.param("sid").unwrap_or_default();
+    build_url(&["pipeline", a_id, s_id])
+}
@@ -0,0 +1,69 @@
+// Copyright 2020-2021, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::api::prelude::*;
+use serde::Deserialize;
+
+/// Name of the server sent events carrying tapped events
+const EVENT_NAME: &str = "event";
+
+#[derive(Deserialize)]
+struct Params {
+    /// fraction of the events on the port to stream, all by default
+    #[serde(default = "Params::default_rate")]
+    rate: f64,
+    /// operator of a pipeline whose output port is tapped
+    #[serde(default)]
+    node: Option<String>,
+}
+
+impl Params {
+    fn default_rate() -> f64 {
+        1.0
+    }
+}
+
+/// Streams events sampled from the port of a running onramp, pipeline or
+/// offramp as server sent events until the client disconnects
+pub async fn stream(req: Request) -> Result<Response> {
+    let kind = req.param("kind").unwrap_or_default();
+    let a_id = req.param("aid").unwrap_or_default();
+    let s_id = req.param("sid").unwrap_or_default();
+    let port = req.param("port").unwrap_or_default();
+    let url = build_url(&[kind, a_id, s_id, port])?;
+    let params: Params = req.query().map_err(|e| {
+        Error::new(
+            StatusCode::BadRequest,
+            format!("Invalid tap parameters: {}", e),
+        )
+    })?;
+
+    let rx = req
+        .state()
+        .world
+        .tap(&url, params.node.as_deref(), params.rate)
+        .await?;
+    // once the client is gone the stream ends and the receiver is dropped,
+    // which detaches the tap
+    Ok(tide::sse::upgrade(req, move |_req, sender| {
+        let rx = rx.clone();
+        async move {
+            while let Ok(event) = rx.recv().await {
+                let data = simd_json::to_string(&event)?;
+                sender.send(EVENT_NAME, data, None).await?;
+            }
+            Ok(())
+        }
+    }))
+}
@@ -128,6 +128,32 @@ impl From<TremorError> for Error {
                 StatusCode::Forbidden,
                 "System artefacts cannot be unpublished".into(),
             ),
+            ErrorKind::PipelineNotPaused(_) => {
+                Error::new(StatusCode::Conflict, "Pipeline is not paused".into())
+            }
+            ErrorKind::InvalidTap(url, reason) => Error::new(
+                StatusCode::BadRequest,
+                format!("Cannot tap {}: {}", url, reason),
+            ),
+            ErrorKind::PipelineReloadFailed(id, reason) => Error::new(
+                StatusCode::BadRequest,
+                format!("Failed to reload pipeline {}: {}", id, reason),
+            ),
+            ErrorKind::InvalidDeployment(reason) => Error::new(
+                StatusCode::BadRequest,
+                format!("Invalid deployment: {}", reason),
+            ),
+            ErrorKind::DeploymentConflict(url) => Error::new(
+                StatusCode::Conflict,
+                format!("The deployment of {} differs from the running one", url),
+            ),
+            ErrorKind::DeadLetterNotFound(_, _) => {
+                Error::new(StatusCode::NotFound, "Dead letter not found".into())
+            }
+            ErrorKind::InvalidDeadLetter(reason) => Error::new(
+                StatusCode::BadRequest,
+                format!("Invalid dead letter: {}", reason),
+            ),
             _e => Error::new(
                 StatusCode::InternalServerError,
                 "Internal server error".into(),
@@ -34,6 +34,10 @@ pub(crate) async fn run_cmd(mut app: TremorApp, cmd: &ArgMatches) -> Result<()>
         conductor_onramp_cmd(&app, &matches).await
     } else if let Some(matches) = cmd.subcommand_matches("offramp") {
         conductor_offramp_cmd(&app, &matches).await
+    } else if let Some(matches) = cmd.subcommand_matches("deployment") {
+        conductor_deployment_cmd(&app, &matches).await
+    } else if let Some(matches) = cmd.subcommand_matches("dead-letter") {
+        conductor_dead_letter_cmd(&app, &matches).await
     } else if let Some(matches) = cmd.subcommand_matches("target") {
         conductor_target_cmd(&mut app, &matches).await
     } else {
@@ -143,11 +147,65 @@ async fn conductor_pipeline_cmd(app: &TremorApp, cmd: &ArgMatches) -> Result<()>
         conductor_create_cmd_trickle(app, &matches, "pipeline").await
     } else if let Some(matches) = cmd.subcommand_matches("instance") {
         conductor_instance_cmd(app, &matches, "pipeline").await
+    } else if let Some(matches) = cmd.subcommand_matches("pause") {
+        conductor_pipeline_control_cmd(app, &matches, "pause").await
+    } else if let Some(matches) = cmd.subcommand_matches("resume") {
+        conductor_pipeline_control_cmd(app, &matches, "resume").await
+    } else if let Some(matches) = cmd.subcommand_matches("step") {
+        conductor_pipeline_control_cmd(app, &matches, "step").await
+    } else if let Some(matches) = cmd.subcommand_matches("reload") {
+        conductor_pipeline_reload_cmd(app, &matches).await
     } else {
         Err("Invalid command".into())
     }
 }
 
+#[allow(clippy::map_err_ignore)] // err is () here
+async fn conductor_pipeline_control_cmd(
+    app: &TremorApp,
+    cmd: &ArgMatches,
+    control: &str,
+) -> Result<()> {
+    let a_id = cmd
+        .value_of("ARTEFACT_ID")
+        .ok_or("ARTEFACT_ID not provided")?;
+    let s_id = cmd
+        .value_of("INSTANCE_ID")
+        .ok_or("INSTANCE_ID not provided")?;
+    let mut endpoint = app.endpoint_id_instance("pipeline", a_id, s_id)?;
+    endpoint
+        .path_segments_mut()
+        .map_err(|_| Error::from("Bad endpoint api"))?
+        .push(control);
+    let response = surf::post(&endpoint)
+        .header(headers::ACCEPT, accept(app))
+        .await?;
+    handle_response(response).await
+}
+
+#[allow(clippy::map_err_ignore)] // err is () here
+async fn conductor_pipeline_reload_cmd(app: &TremorApp, cmd: &ArgMatches) -> Result<()> {
+    let a_id = cmd
+        .value_of("ARTEFACT_ID")
+        .ok_or("ARTEFACT_ID not provided")?;
+    let s_id = cmd
+        .value_of("INSTANCE_ID")
+        .ok_or("INSTANCE_ID not provided")?;
+    let path_to_file = cmd.value_of("SOURCE").ok_or("SOURCE not provided")?;
+    let ser = load_trickle(path_to_file)?;
+    let mut endpoint = app.endpoint_id_instance("pipeline", a_id, s_id)?;
+    endpoint
+        .path_segments_mut()
+        .map_err(|_| Error::from("Bad endpoint api"))?
+        .push("reload");
+    let response = surf::post(&endpoint)
+        .header(http_types::headers::CONTENT_TYPE, "application/vnd.trickle")
+        .header(headers::ACCEPT, accept(app))
+        .body(ser)
+        .await?;
+    handle_response(response).await
+}
+
 /////////////////////////////
 // API Binding subcommands //
 /////////////////////////////
@@ -244,6 +302,63 @@ async fn conductor_onramp_cmd(app: &TremorApp, cmd: &ArgMatches) -> Result<()> {
     }
 }
 
+////////////////////////////////
+// API Deployment subcommands //
+////////////////////////////////
+
+async fn conductor_deployment_cmd(app: &TremorApp, cmd: &ArgMatches) -> Result<()> {
+    if cmd.subcommand_matches("export").is_some() {
+        conductor_list_cmd(app, "deployment").await
+    } else if let Some(matches) = cmd.subcommand_matches("import") {
+        conductor_create_cmd(app, &matches, "deployment").await
+    } else {
+        Err("Invalid command".into())
+    }
+}
+
+/////////////////////////////////
+// API Dead letter subcommands //
+/////////////////////////////////
+
+async fn conductor_dead_letter_cmd(app: &TremorApp, cmd: &ArgMatches) -> Result<()> {
+    if let Some(matches) = cmd.subcommand_matches("list") {
+        conductor_get_cmd(app, &matches, "dead-letter").await
+    } else if let Some(matches) = cmd.subcommand_matches("replay") {
+        conductor_dead_letter_replay_cmd(app, &matches).await
+    } else if let Some(matches) = cmd.subcommand_matches("delete") {
+        conductor_dead_letter_delete_cmd(app, &matches).await
+    } else {
+        Err("Invalid command".into())
+    }
+}
+
+#[allow(clippy::map_err_ignore)] // err is () here
+async fn conductor_dead_letter_replay_cmd(app: &TremorApp, cmd: &ArgMatches) -> Result<()> {
+    let a_id = cmd
+        .value_of("ARTEFACT_ID")
+        .ok_or("ARTEFACT_ID not provided")?;
+    let l_id = cmd.value_of("LETTER_ID").ok_or("LETTER_ID not provided")?;
+    let mut endpoint = app.endpoint_id_instance("dead-letter", a_id, l_id)?;
+    endpoint
+        .path_segments_mut()
+        .map_err(|_| Error::from("Bad endpoint api"))?
+        .push("replay");
+    let response = surf::post(&endpoint)
+        .header(headers::ACCEPT, accept(app))
+        .await?;
+    handle_response(response).await
+}
+
+async fn conductor_dead_letter_delete_cmd(app: &TremorApp, cmd: &ArgMatches) -> Result<()> {
+    let a_id = cmd
+        .value_of("ARTEFACT_ID")
+        .ok_or("ARTEFACT_ID not provided")?;
+    let l_id = cmd.value_of("LETTER_ID").ok_or("LETTER_ID not provided")?;
+    let endpoint = app.endpoint_id_instance("dead-letter", a_id, l_id)?;
+    let response = surf::delete(&endpoint).await?;
+    handle_response(response).await
+}
+
 /////////////////
 // Shared code //
 /////////////////
@@ -34,6 +34,14 @@ use tremor_script::{
     lexer::Tokenizer,
 };
 use tremor_script::{EventPayload, Value, ValueAndMeta};
+/// Resolves a codec given on the command line, either just by its name
+/// or as a `{name: ..., config: ...}` record
+fn codec_from_arg(arg: &str) -> Result<Box<dyn Codec>> {
+    let config: tremor_runtime::codec::Config =
+        serde_yaml::from_str(arg).unwrap_or_else(|_| arg.into());
+    Ok(tremor_runtime::codec::resolve(&config)?)
+}
+
 struct Ingress {
     is_interactive: bool,
     is_pretty: bool,
@@ -57,9 +65,9 @@ impl Ingress {
             Some(data) => Box::new(BufReader::new(crate::open_file(data, None)?)),
         };
 
-        let codec = tremor_runtime::codec::lookup(codec_decoder);
-        if let Err(_e) = codec {
-            eprintln!("Error Codec {} not found error.", codec_decoder);
+        let codec = codec_from_arg(codec_decoder);
+        if let Err(e) = codec {
+            eprintln!("Error Codec {}: {}", codec_decoder, e);
             // ALLOW: main.rs
             std::process::exit(1);
         }
@@ -148,9 +156,9 @@ impl Egress {
             Some(data) => Box::new(BufWriter::new(file::create(data)?)),
         };
 
-        let codec = tremor_runtime::codec::lookup(codec_encoder);
-        if let Err(_e) = codec {
-            eprintln!("Error Codec {} not found error.", codec_encoder);
+        let codec = codec_from_arg(codec_encoder);
+        if let Err(e) = codec {
+            eprintln!("Error Codec {}: {}", codec_encoder, e);
             // ALLOW: main.rs
             std::process::exit(1);
         }
@@ -48,6 +48,8 @@ fn api_server(world: &World) -> tide::Server<api::State> {
 
     app.at("/version")
         .get(|r| handle_api_request(r, api::version::get));
+    app.at("/metrics")
+        .get(|r| handle_api_request(r, api::metrics::get));
     app.at("/binding")
         .get(|r| handle_api_request(r, api::binding::list_artefact))
         .post(|r| handle_api_request(r, api::binding::publish_artefact));
@@ -64,6 +66,14 @@ fn api_server(world: &World) -> tide::Server<api::State> {
     app.at("/pipeline/:aid")
         .get(|r| handle_api_request(r, api::pipeline::get_artefact))
         .delete(|r| handle_api_request(r, api::pipeline::unpublish_artefact));
+    app.at("/pipeline/:aid/:sid/pause")
+        .post(|r| handle_api_request(r, api::pipeline::pause_servant));
+    app.at("/pipeline/:aid/:sid/resume")
+        .post(|r| handle_api_request(r, api::pipeline::resume_servant));
+    app.at("/pipeline/:aid/:sid/step")
+        .post(|r| handle_api_request(r, api::pipeline::step_servant));
+    app.at("/pipeline/:aid/:sid/reload")
+        .post(|r| handle_api_request(r, api::pipeline::reload_servant));
     app.at("/onramp")
         .get(|r| handle_api_request(r, api::onramp::list_artefact))
         .post(|r| handle_api_request(r, api::onramp::publish_artefact));
@@ -76,6 +86,17 @@ fn api_server(world: &World) -> tide::Server<api::State> {
     app.at("/offramp/:aid")
         .get(|r| handle_api_request(r, api::offramp::get_artefact))
         .delete(|r| handle_api_request(r, api::offramp::unpublish_artefact));
+    app.at("/deployment")
+        .get(|r| handle_api_request(r, api::deployment::export))
+        .post(|r| handle_api_request(r, api::deployment::import));
+    app.at("/dead-letter/:aid")
+        .get(|r| handle_api_request(r, api::dead_letter::list));
+    app.at("/dead-letter/:aid/:id")
+        .delete(|r| handle_api_request(r, api::dead_letter::remove));
+    app.at("/dead-letter/:aid/:id/replay")
+        .post(|r| handle_api_request(r, api::dead_letter::replay));
+    app.at("/tap/:kind/:aid/:sid/:port")
+        .get(|r| handle_api_request(r, api::tap::stream));
 
     app
 }
@@ -118,7 +139,7 @@ pub(crate) async fn run_dun(matches: &ArgMatches) -> Result<()> {
         .value_of("storage-directory")
         .map(std::string::ToString::to_string);
     // TODO: Allow configuring this for offramps and pipelines
-    let (world, handle) = World::start(64, storage_directory).await?;
+    let (mut world, handle) = World::start(64, storage_directory).await?;
 
     if let Some(config_files) = matches.values_of("artefacts") {
         let mut yaml_files = Vec::with_capacity(16);
@@ -151,6 +172,13 @@ pub(crate) async fn run_dun(matches: &ArgMatches) -> Result<()> {
         }
     }
 
+    // The stored deployment is restored after the files are loaded, artefacts
+    // and mappings loaded from the files take precedence over stored ones
+    if let Some(path) = matches.value_of("deployment-store") {
+        let count = world.open_store(path).await?;
+        info!("Restored {} artefacts and mappings from {}", count, path);
+    }
+
     if !matches.is_present("no-api") {
         let host = matches
             .value_of("api-host")
@@ -12,7 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use crate::{CbAction, EventId, OpMeta, SignalKind};
+use crate::{CbAction, EventId, OpMeta, SignalKind, DEFAULT_STREAM_ID};
 use std::mem::swap;
 use tremor_script::prelude::*;
 use tremor_script::{EventOriginUri, EventPayload, Value};
@@ -179,6 +179,29 @@ impl Event {
         }
     }
 
+    /// Creates a new watermark signal, announcing that all events with an event time
+    /// below `watermark` (in nanoseconds) have been seen by the operator with the uid `origin`
+    #[must_use]
+    pub fn watermark(origin: u64, ingest_ns: u64, watermark: u64) -> Self {
+        Event {
+            id: EventId::new(origin, DEFAULT_STREAM_ID, 0),
+            ingest_ns,
+            kind: Some(SignalKind::Watermark),
+            data: (Value::from(watermark), Value::object()).into(),
+            ..Event::default()
+        }
+    }
+
+    /// The watermark carried by a watermark signal, `None` for all other events
+    #[must_use]
+    pub fn watermark_ns(&self) -> Option<u64> {
+        if self.kind == Some(SignalKind::Watermark) {
+            self.data.suffix().value().as_u64()
+        } else {
+            None
+        }
+    }
+
     /// Creates a CB fail insight from the given `event` (the cause of this fail)
     #[must_use]
     pub fn to_fail(&self) -> Self {
@@ -22,11 +22,12 @@ use crate::{
     op::{prelude::IN, trickle::select::WindowImpl},
     ConfigMap, ExecPortIndexMap, NodeLookupFn,
 };
-use crate::{op::EventAndInsights, Event, NodeKind, Operator};
+use crate::{op::EventAndInsights, Event, EventId, NodeKind, Operator};
 use beef::Cow;
 use halfbrown::HashMap;
 use tremor_common::stry;
-use tremor_script::{srs, Value};
+use tremor_common::time::nanotime;
+use tremor_script::{prelude::*, srs, Value};
 
 /// Configuration for a node
 #[derive(Debug, Clone, PartialOrd, Eq, Default)]
@@ -130,6 +131,9 @@ pub struct OperatorNode {
     pub op: Box<dyn Operator>,
     /// Tremor unique identifyer
     pub uid: u64,
+    /// Fingerprint of the definition of the operator, operators with the same
+    /// id and fingerprint are interchangeable when a pipeline is reloaded
+    pub fingerprint: Option<u64>,
 }
 
 impl Operator for OperatorNode {
@@ -173,12 +177,22 @@ impl Operator for OperatorNode {
     fn skippable(&self) -> bool {
         self.op.skippable()
     }
+
+    fn checkpoint(&self, covered: &mut Option<EventId>) -> Result<Option<Value<'static>>> {
+        self.op.checkpoint(covered)
+    }
+
+    fn restore(&mut self, state: &Value) -> Result<()> {
+        self.op.restore(state)
+    }
 }
 
 #[derive(Debug, Default, Clone)]
 pub(crate) struct NodeMetrics {
     inputs: HashMap<Cow<'static, str>, u64>,
     outputs: HashMap<Cow<'static, str>, u64>,
+    /// nanoseconds the operator spent on each event since they were last drained
+    latencies_ns: Vec<u64>,
 }
 
 impl NodeMetrics {
@@ -250,6 +264,47 @@ impl NodeMetrics {
     }
 }
 
+/// Samples a fraction of the events it is offered
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sampler {
+    rate: f64,
+    seen: u64,
+}
+
+impl Sampler {
+    /// Creates a sampler passing on `rate` of all events
+    #[must_use]
+    pub fn new(rate: f64) -> Self {
+        Self { rate, seen: 0 }
+    }
+
+    /// The fraction of events sampled
+    #[must_use]
+    pub fn rate(&self) -> f64 {
+        self.rate
+    }
+
+    /// Decides if the next event is sampled
+    // an event is sampled whenever `seen * rate` crosses an integer,
+    // so a rate of 0.25 samples every fourth event
+    #[allow(clippy::cast_precision_loss)]
+    pub fn sample(&mut self) -> bool {
+        let before = (self.seen as f64 * self.rate).floor();
+        self.seen += 1;
+        (self.seen as f64 * self.rate).floor() > before
+    }
+}
+
+/// Where and how often the state of a graph is checkpointed, configured
+/// with `#!config checkpoint_dir` and `#!config checkpoint_interval_s`
+#[derive(Debug, Clone, PartialEq)]
+pub struct CheckpointSettings {
+    /// directory of the checkpoint store
+    pub dir: String,
+    /// nanoseconds between two checkpoints
+    pub interval: u64,
+}
+
 /// An executable graph, this is the executable
 /// form of a pipeline
 #[derive(Debug)]
@@ -269,10 +324,21 @@ pub struct ExecutableGraph {
     pub(crate) metric_interval: Option<u64>,
     /// snot
     pub insights: Vec<(usize, Event)>,
+    /// signals emitted by operators that reached an output of this graph, as `(output, signal)`,
+    /// they need to be forwarded to whatever is connected to that output
+    pub signals: Vec<(Cow<'static, str>, Event)>,
+    /// taps on operator output ports, as `(node, port, sampler)`, sampled events are collected in `tapped`
+    pub taps: Vec<(String, String, Sampler)>,
+    /// events sampled by taps, as `(tap, event)` with the index of the tap in `taps`
+    pub tapped: Vec<(usize, Event)>,
+    /// record the time operators spend on events, they have to be drained with `drain_latencies`
+    pub track_latencies: bool,
     /// source code of the pipeline
     pub source: Option<String>,
     /// the dot representation of the graph
     pub dot: String,
+    /// checkpointing of the state of the graph, if enabled
+    pub checkpoint: Option<CheckpointSettings>,
 }
 
 /// The return of a graph execution
@@ -456,6 +522,157 @@ impl ExecutableGraph {
         self.run(returns)
     }
 
+    /// Checks if the graph has an input with the given port
+    #[must_use]
+    pub fn has_input(&self, port: &str) -> bool {
+        self.inputs.contains_key(port)
+    }
+
+    /// Checks if the graph has an output with the given port
+    #[must_use]
+    pub fn has_output(&self, port: &str) -> bool {
+        self.graph
+            .iter()
+            .any(|n| matches!(&n.kind, NodeKind::Output(p) if p.as_ref() == port))
+    }
+
+    /// Takes over the state of `old`, the graph this graph replaces when a
+    /// pipeline is reloaded. Nodes are matched by id: the `state` of all nodes
+    /// present in both graphs is carried over, operators whose definition didn't
+    /// change are moved over with all their internal state, e.g. open windows or
+    /// the position in a WAL.
+    ///
+    /// Returns the ids of the nodes whose operators were moved over
+    pub fn migrate_state(&mut self, old: &mut Self) -> Vec<String> {
+        let mut migrated = Vec::new();
+        for (idx, node) in self.graph.iter_mut().enumerate() {
+            let old_idx = if let Some(i) = old.graph.iter().position(|n| n.id == node.id) {
+                i
+            } else {
+                continue;
+            };
+            if let (Some(state), Some(old_state)) =
+                (self.state.ops.get_mut(idx), old.state.ops.get_mut(old_idx))
+            {
+                std::mem::swap(state, old_state);
+            }
+            if let Some(old_node) = old.graph.get_mut(old_idx) {
+                if node.fingerprint.is_some()
+                    && node.fingerprint == old_node.fingerprint
+                    && node.kind == old_node.kind
+                {
+                    std::mem::swap(&mut node.op, &mut old_node.op);
+                    std::mem::swap(&mut node.uid, &mut old_node.uid);
+                    migrated.push(node.id.clone());
+                }
+            }
+        }
+        self.taps = std::mem::take(&mut old.taps);
+        migrated
+    }
+
+    /// Captures the `state` of all nodes and the internal state of their
+    /// operators, see `Operator::checkpoint`, so the graph can be restored
+    /// after a restart.
+    ///
+    /// Returns the checkpoint along with the ids of the events whose effect
+    /// is part of it
+    ///
+    /// # Errors
+    /// if an operator fails to capture its state
+    pub fn checkpoint(&self) -> Result<(Value<'static>, Option<EventId>)> {
+        let mut covered = None;
+        let mut nodes = Object::with_capacity(self.graph.len());
+        for (node, state) in self.graph.iter().zip(&self.state.ops) {
+            let op = node.checkpoint(&mut covered).map_err(|e| {
+                Error::from(format!("Failed to checkpoint node {}: {}", node.id, e))
+            })?;
+            if op.is_some() || !state.is_null() {
+                nodes.insert(
+                    node.id.clone().into(),
+                    literal!({
+                        "fingerprint": node.fingerprint,
+                        "state": state.clone(),
+                        "op": op,
+                    }),
+                );
+            }
+        }
+        Ok((Value::from(nodes), covered))
+    }
+
+    /// Restores a checkpoint taken with `checkpoint`, usually by a previous run
+    /// of this pipeline. Like in `migrate_state` nodes are matched by id: the
+    /// `state` of all nodes present in the checkpoint is restored, the internal
+    /// state of operators only if their definition didn't change.
+    ///
+    /// Returns the ids of the nodes whose operators were restored
+    ///
+    /// # Errors
+    /// if an operator fails to restore its state
+    pub fn restore(&mut self, checkpoint: &Value) -> Result<Vec<String>> {
+        let mut restored = Vec::new();
+        for (node, state) in self.graph.iter_mut().zip(self.state.ops.iter_mut()) {
+            let checkpoint = if let Some(checkpoint) = checkpoint.get(node.id.as_str()) {
+                checkpoint
+            } else {
+                continue;
+            };
+            if let Some(s) = checkpoint.get("state") {
+                *state = s.clone_static();
+            }
+            match checkpoint.get("op") {
+                Some(op)
+                    if !op.is_null()
+                        && node.fingerprint.is_some()
+                        && checkpoint.get_u64("fingerprint") == node.fingerprint =>
+                {
+                    node.restore(op).map_err(|e| {
+                        Error::from(format!("Failed to restore node {}: {}", node.id, e))
+                    })?;
+                    restored.push(node.id.clone());
+                }
+                _ => (),
+            }
+        }
+        Ok(restored)
+    }
+
+    /// Checks if the graph has an operator node with the given id
+    #[must_use]
+    pub fn has_node(&self, id: &str) -> bool {
+        self.graph.iter().any(|n| n.id == id)
+    }
+
+    /// Calls `f` with the node id, the direction (`input` or `output`), the port
+    /// and the number of events seen on that port for every port of every node
+    pub fn visit_port_metrics<F>(&self, mut f: F)
+    where
+        F: FnMut(&str, &'static str, &str, u64),
+    {
+        for (node, m) in self.graph.iter().zip(&self.metrics) {
+            for (port, count) in &m.inputs {
+                f(&node.id, "input", port, *count);
+            }
+            for (port, count) in &m.outputs {
+                f(&node.id, "output", port, *count);
+            }
+        }
+    }
+
+    /// Calls `f` with the node id and the nanoseconds its operator spent on an event,
+    /// for every event handled since the last call
+    pub fn drain_latencies<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&str, u64),
+    {
+        for (node, m) in self.graph.iter().zip(&mut self.metrics) {
+            for ns in m.latencies_ns.drain(..) {
+                f(&node.id, ns);
+            }
+        }
+    }
+
     #[inline]
     fn run(&mut self, returns: &mut Returns) -> Result<()> {
         while stry!(self.next(returns)) {}
@@ -466,10 +683,10 @@ impl ExecutableGraph {
     #[inline]
     fn next(&mut self, returns: &mut Returns) -> Result<bool> {
         if let Some((idx, port, event)) = self.stack.pop() {
-            // If we have emitted a signal event we got to handle it as a signal flow
-            // the signal flow will
+            // signals emitted by an operator only travel downstream of it,
+            // starting with the emitting operator itself
             if event.kind.is_some() {
-                stry!(self.signalflow(event));
+                stry!(self.downstream_signal(idx, event));
             } else {
                 // count ingres
                 let node = unsafe { self.graph.get_unchecked_mut(idx) };
@@ -478,11 +695,27 @@ impl ExecutableGraph {
                 } else {
                     // ALLOW: We know the state was initiated
                     let state = unsafe { self.state.ops.get_unchecked_mut(idx) };
-                    let EventAndInsights { events, insights } =
-                        stry!(node.on_event(0, &port, state, event));
+                    let start = if self.track_latencies { nanotime() } else { 0 };
+                    let EventAndInsights {
+                        mut events,
+                        insights,
+                    } = stry!(node.on_event(0, &port, state, event));
+                    let metrics = unsafe { self.metrics.get_unchecked_mut(idx) };
+                    if self.track_latencies {
+                        metrics.latencies_ns.push(nanotime().saturating_sub(start));
+                    }
 
-                    for (out_port, _) in &events {
-                        unsafe { self.metrics.get_unchecked_mut(idx) }.inc_output(out_port);
+                    for (out_port, event) in &mut events {
+                        metrics.inc_output(out_port);
+                        if out_port == "err" {
+                            tag_error(&self.id, &node.id, event);
+                        }
+                        // only sampled events are cloned
+                        for (tap, (n, p, sampler)) in self.taps.iter_mut().enumerate() {
+                            if n == &node.id && p == out_port && sampler.sample() {
+                                self.tapped.push((tap, event.clone()));
+                            }
+                        }
                     }
                     for insight in insights {
                         self.insights.push((idx, insight))
@@ -538,8 +771,46 @@ impl ExecutableGraph {
             }
         }
     }
+    /// Hands the `signal` to the operator at `idx` and passes it on to all operators
+    /// connected to any of its outputs. Signals reaching an output of the graph are
+    /// collected in `signals`.
+    fn downstream_signal(&mut self, idx: usize, mut signal: Event) -> Result<()> {
+        let node = unsafe { self.graph.get_unchecked_mut(idx) }; // We know this exists
+        if let NodeKind::Output(port) = &node.kind {
+            self.signals.push((port.clone(), signal));
+            return Ok(());
+        }
+        let state = unsafe { self.state.ops.get_unchecked(idx) }; // we know this has been initialized
+        let EventAndInsights { events, insights } =
+            stry!(node.on_signal(node.uid, state, &mut signal));
+        self.insights
+            .extend(insights.into_iter().map(|cf| (idx, cf)));
+        let mut next: Vec<(usize, Cow<'static, str>)> = self
+            .port_indexes
+            .iter()
+            .filter(|((from, _), _)| *from == idx)
+            .flat_map(|(_, to)| to.iter().cloned())
+            .collect();
+        next.sort_by_key(|(to, _)| *to);
+        next.dedup_by_key(|(to, _)| *to);
+        // the signal goes below the events it caused, so those are handled first
+        for (to, in_port) in next {
+            self.stack.push((to, in_port, signal.clone()));
+        }
+        self.enqueue_events(idx, events);
+        Ok(())
+    }
+
     #[inline]
     fn enqueue_events(&mut self, idx: usize, events: Vec<(Cow<'static, str>, Event)>) {
+        // signals emitted by an operator are handled after the events it emitted along with them,
+        // starting with the operator itself
+        let (signals, events): (Vec<_>, Vec<_>) = events
+            .into_iter()
+            .partition(|(_, event)| event.kind.is_some());
+        for (out_port, signal) in signals {
+            self.stack.push((idx, out_port, signal));
+        }
         for (out_port, event) in events {
             if let Some((last, rest)) = self
                 .port_indexes
@@ -600,6 +871,19 @@ impl ExecutableGraph {
     }
 }
 
+/// Names the pipeline and the operator in the error records operators emit on
+/// their `err` port, like the `{"error": ..., "event": ...}` records of scripts,
+/// so dead-letter queues can keep track of where the event failed
+fn tag_error(pipeline: &str, operator: &str, event: &mut Event) {
+    event.data.rent_mut(|data| {
+        let (v, _) = data.parts_mut();
+        if v.contains_key("error") && v.contains_key("event") && !v.contains_key("operator") {
+            v.try_insert("pipeline", pipeline.to_string());
+            v.try_insert("operator", operator.to_string());
+        }
+    });
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -637,6 +921,7 @@ mod test {
             op_type: id.into(),
             op: PassthroughFactory::new_boxed().from_node(uid, &c).unwrap(),
             uid: 0,
+            fingerprint: None,
         }
     }
     #[test]
@@ -657,6 +942,37 @@ mod test {
         assert!(n.metrics(&HashMap::default(), 0).unwrap().is_empty());
     }
 
+    #[test]
+    fn tag_error_records() {
+        let mut event = Event {
+            data: (
+                literal!({"error": "snot", "event": {"badger": 1}}),
+                Value::object(),
+            )
+                .into(),
+            ..Event::default()
+        };
+        tag_error("/pipeline/main/01", "script", &mut event);
+        let tagged = event.data.suffix().value();
+        assert_eq!(Some("/pipeline/main/01"), tagged.get_str("pipeline"));
+        assert_eq!(Some("script"), tagged.get_str("operator"));
+
+        // the operator that emitted it first is kept
+        tag_error("/pipeline/main/01", "other", &mut event);
+        assert_eq!(
+            Some("script"),
+            event.data.suffix().value().get_str("operator")
+        );
+
+        // other data is left alone
+        let mut event = Event {
+            data: (literal!({"error": "snot"}), Value::object()).into(),
+            ..Event::default()
+        };
+        tag_error("/pipeline/main/01", "script", &mut event);
+        assert!(!event.data.suffix().value().contains_key("operator"));
+    }
+
     fn test_metric<'value>(v: &Value<'value>, m: &str, c: u64) {
         assert_eq!(v.get("measurement").unwrap(), m);
         assert!(v.get("tags").unwrap().is_object());
@@ -737,6 +1053,7 @@ mod test {
             op_type: "test".into(),
             op: Box::new(AllOperator {}),
             uid: 0,
+            fingerprint: None,
         }
     }
 
@@ -840,8 +1157,13 @@ mod test {
             last_metrics: 0,
             metric_interval: Some(1),
             insights: vec![],
+            signals: vec![],
+            taps: vec![],
+            tapped: vec![],
+            track_latencies: false,
             source: None,
             dot: String::from(""),
+            checkpoint: None,
         };
 
         // Test with one event
@@ -875,6 +1197,36 @@ mod test {
         let (ports, metrics): (Vec<_>, Vec<_>) = returns.drain(..).unzip();
         assert!(ports.iter().all(|v| v == "metrics"));
         test_metrics(metrics, 3);
+
+        let mut visited = Vec::new();
+        g.visit_port_metrics(|node, direction, port, count| {
+            if node == "all-1" {
+                visited.push((direction, port.to_string(), count))
+            }
+        });
+        visited.sort();
+        assert_eq!(
+            vec![
+                ("input", "in".to_string(), 3),
+                ("output", "out".to_string(), 3)
+            ],
+            visited
+        );
+
+        assert!(g.has_node("all-2"));
+        assert!(!g.has_node("snot"));
+        g.taps
+            .push(("all-1".to_string(), "out".to_string(), Sampler::new(1.0)));
+        g.taps
+            .push(("all-2".to_string(), "out".to_string(), Sampler::new(0.5)));
+        let mut returns = Vec::new();
+        g.enqueue("in", Event::default(), &mut returns).unwrap();
+        assert_eq!(1, g.tapped.len());
+        assert_eq!(0, g.tapped[0].0);
+        g.tapped.clear();
+        g.enqueue("in", Event::default(), &mut returns).unwrap();
+        let tapped: Vec<usize> = g.tapped.iter().map(|(tap, _)| *tap).collect();
+        assert_eq!(vec![0, 1], tapped);
     }
 
     #[test]
@@ -942,8 +1294,13 @@ mod test {
             last_metrics: 0,
             metric_interval: Some(1),
             insights: vec![],
+            signals: vec![],
+            taps: vec![],
+            tapped: vec![],
+            track_latencies: false,
             source: None,
             dot: String::from(""),
+            checkpoint: None,
         };
         assert!(g.optimize().is_some());
         // Test with one event
@@ -967,4 +1324,107 @@ mod test {
             Some(&vec![(4usize, IN)])
         );
     }
+
+    #[derive(Debug)]
+    struct WatermarkOperator {
+        signals: std::sync::Arc<std::sync::atomic::AtomicUsize>,
+    }
+
+    impl Operator for WatermarkOperator {
+        fn on_event(
+            &mut self,
+            uid: u64,
+            _port: &str,
+            _state: &mut Value<'static>,
+            event: Event,
+        ) -> Result<EventAndInsights> {
+            let watermark = Event::watermark(uid, event.ingest_ns, event.ingest_ns);
+            Ok(vec![(OUT, event), (OUT, watermark)].into())
+        }
+        fn handles_signal(&self) -> bool {
+            true
+        }
+        fn on_signal(
+            &mut self,
+            _uid: u64,
+            _state: &Value<'static>,
+            _signal: &mut Event,
+        ) -> Result<EventAndInsights> {
+            self.signals
+                .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            Ok(EventAndInsights::default())
+        }
+    }
+
+    #[test]
+    fn eg_signals_only_flow_downstream() {
+        use std::sync::{atomic::AtomicUsize, atomic::Ordering, Arc};
+        let counter = |uid: u64, id: &'static str| {
+            let signals = Arc::new(AtomicUsize::new(0));
+            let node = OperatorNode {
+                id: id.into(),
+                kind: NodeKind::Operator,
+                op_type: "test".into(),
+                op: Box::new(WatermarkOperator {
+                    signals: signals.clone(),
+                }),
+                uid,
+                fingerprint: None,
+            };
+            (node, signals)
+        };
+        let mut in_n = pass(1, "in");
+        in_n.kind = NodeKind::Input;
+        let mut out_n = pass(2, "out");
+        out_n.kind = NodeKind::Output(OUT);
+        let mut metrics_n = pass(3, "metrics");
+        metrics_n.kind = NodeKind::Output(METRICS);
+        let (emitter, emitter_signals) = counter(4, "emitter");
+        let (down, down_signals) = counter(5, "down");
+        let (side, side_signals) = counter(6, "side");
+
+        // in -> emitter -> down -> out and in -> side
+        let graph = vec![in_n, emitter, down, side, out_n, metrics_n];
+        let mut inputs = HashMap::new();
+        inputs.insert("in".into(), 0);
+        let mut port_indexes = ExecPortIndexMap::new();
+        port_indexes.insert((0, "out".into()), vec![(1, "in".into()), (3, "in".into())]);
+        port_indexes.insert((1, "out".into()), vec![(2, "in".into())]);
+        port_indexes.insert((2, "out".into()), vec![(4, "in".into())]);
+        let mut g = ExecutableGraph {
+            id: "test".into(),
+            graph,
+            state: State {
+                ops: vec![Value::null(); 6],
+            },
+            inputs,
+            stack: vec![],
+            signalflow: vec![1, 2, 3],
+            contraflow: vec![3, 2, 1],
+            port_indexes,
+            metrics: vec![NodeMetrics::default(); 6],
+            metrics_idx: 5,
+            last_metrics: 0,
+            metric_interval: None,
+            insights: vec![],
+            signals: vec![],
+            taps: vec![],
+            tapped: vec![],
+            track_latencies: false,
+            source: None,
+            dot: String::from(""),
+            checkpoint: None,
+        };
+        let mut returns = Vec::new();
+        g.enqueue("in", Event::default(), &mut returns).unwrap();
+        // every operator sees its own watermark, the one downstream of the emitter
+        // its watermark as well, the unrelated one doesn't
+        assert_eq!(1, emitter_signals.load(Ordering::Relaxed));
+        assert_eq!(2, down_signals.load(Ordering::Relaxed));
+        assert_eq!(1, side_signals.load(Ordering::Relaxed));
+        // only the watermarks of the emitter and the operator downstream of it reach the output
+        assert_eq!(2, g.signals.len());
+        assert!(g.signals.iter().all(|(port, _)| port == &OUT));
+        assert_eq!(1, returns.len());
+    }
 }
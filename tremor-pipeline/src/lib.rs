@@ -68,7 +68,7 @@ const TIMESTAMP: Cow<'static, str> = Cow::const_str("timestamp");
 /// Tools to turn tremor query into pipelines
 pub mod query;
 pub use crate::event::{Event, ValueIter, ValueMetaIter};
-pub use crate::executable_graph::{ExecutableGraph, OperatorNode};
+pub use crate::executable_graph::{CheckpointSettings, ExecutableGraph, OperatorNode, Sampler};
 pub(crate) use crate::executable_graph::{NodeMetrics, State};
 pub use op::{ConfigImpl, InitializableOperator, Operator};
 pub use tremor_script::prelude::EventOriginUri;
@@ -488,6 +488,37 @@ impl EventId {
                 .map(|teid| (teid.stream_id, teid.max_event_id))
         }
     }
+
+    #[must_use]
+    /// get the event ids tracked by this one that come after the ones tracked by `other`
+    /// for each source and stream, `None` if there are none
+    ///
+    /// This also always checks the actual eventId, not only the tracked ones
+    pub fn without(&self, other: &EventId) -> Option<EventId> {
+        let own = TrackedEventIds::from_id(self.source_id, self.stream_id, self.event_id);
+        let tracked = if self.tracked_event_ids.is_empty() {
+            std::slice::from_ref(&own)
+        } else {
+            self.tracked_event_ids.as_slice()
+        };
+        let mut without: Option<EventId> = None;
+        for teid in tracked {
+            let min_event_id = match other.get_max_by_stream(teid.source_id, teid.stream_id) {
+                Some(max) if max >= teid.max_event_id => continue,
+                Some(max) => teid.min_event_id.max(max + 1),
+                None => teid.min_event_id,
+            };
+            without
+                .get_or_insert_with(|| EventId::new(teid.source_id, teid.stream_id, min_event_id))
+                .track_ids(
+                    teid.source_id,
+                    teid.stream_id,
+                    min_event_id,
+                    teid.max_event_id,
+                );
+        }
+        without
+    }
 }
 
 impl From<(u64, u64, u64)> for EventId {
@@ -679,13 +710,20 @@ pub enum SignalKind {
     Init,
     /// Shutdown Signal
     Shutdown,
-    // Pause, TODO debug trace
-    // Resume, TODO debug trace
-    // Step, TODO ( into, over, to next breakpoint )
+    // Debugging
+    /// Pause signal, the pipeline holds back events until it is resumed
+    Pause,
+    /// Resume signal, the pipeline processes all held back events
+    Resume,
+    /// Step signal, a paused pipeline processes a single held back event
+    Step,
     /// Control
     Control,
     /// Periodic Tick
     Tick,
+    /// Event time progress, all events with an event time
+    /// below the carried watermark are considered to have been seen
+    Watermark,
 }
 
 // We ignore this since it's a simple lookup table
@@ -694,10 +732,12 @@ fn factory(node: &NodeConfig) -> Result<Box<dyn InitializableOperator>> {
     #[cfg(feature = "bert")]
     use op::bert::{SequenceClassificationFactory, SummerizationFactory};
     use op::debug::EventHistoryFactory;
-    use op::generic::{BatchFactory, CounterFactory};
+    use op::generic::{BatchFactory, CounterFactory, DedupFactory};
     use op::grouper::BucketGrouperFactory;
     use op::identity::PassthroughFactory;
-    use op::qos::{BackpressureFactory, PercentileFactory, RoundRobinFactory, WalFactory};
+    use op::qos::{
+        BackpressureFactory, PercentileFactory, RateLimitFactory, RoundRobinFactory, WalFactory,
+    };
     let name_parts: Vec<&str> = node.op_type.split("::").collect();
     let factory = match name_parts.as_slice() {
         ["passthrough"] => PassthroughFactory::new_boxed(),
@@ -709,10 +749,12 @@ fn factory(node: &NodeConfig) -> Result<Box<dyn InitializableOperator>> {
             BackpressureFactory::new_boxed()
         }
         ["generic", "counter"] => CounterFactory::new_boxed(),
+        ["generic", "dedup"] => DedupFactory::new_boxed(),
         ["qos", "backpressure"] => BackpressureFactory::new_boxed(),
         ["qos", "roundrobin"] => RoundRobinFactory::new_boxed(),
         ["qos", "wal"] => WalFactory::new_boxed(),
         ["qos", "percentile"] => PercentileFactory::new_boxed(),
+        ["qos", "ratelimit"] => RateLimitFactory::new_boxed(),
         #[cfg(feature = "bert")]
         ["bert", "sequence_classification"] => SequenceClassificationFactory::new_boxed(),
         #[cfg(feature = "bert")]
@@ -873,6 +915,26 @@ mod test {
         assert_eq!(id.get_max_by_stream(5, DEFAULT_STREAM_ID), None);
     }
 
+    #[test]
+    fn event_ids_without() {
+        let mut ids = EventId::new(1, 1, 1);
+        ids.track_id(1, 1, 5);
+        ids.track_id(2, 1, 3);
+        assert_eq!(None, ids.without(&ids));
+
+        let mut other = EventId::new(1, 1, 3);
+        other.track_id(3, 1, 1);
+        let without = ids.without(&other).unwrap();
+        assert_eq!(Some(4), without.get_min_by_stream(1, 1));
+        assert_eq!(Some(5), without.get_max_by_stream(1, 1));
+        assert_eq!(Some(3), without.get_min_by_stream(2, 1));
+        assert_eq!(None, without.get_min_by_stream(3, 1));
+
+        let id = EventId::new(1, 1, 5);
+        assert_eq!(None, id.without(&ids));
+        assert_eq!(Some(5), id.without(&other).unwrap().get_max_by_stream(1, 1));
+    }
+
     #[test]
     fn tracked_event_ids() {
         let teid1 = TrackedEventIds::default();
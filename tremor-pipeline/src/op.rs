@@ -23,12 +23,12 @@ pub mod qos;
 pub mod trickle;
 
 use self::prelude::OUT;
-use super::{Event, NodeConfig};
-use crate::errors::Result;
+use super::{Event, EventId, NodeConfig};
+use crate::errors::{ErrorKind, Result};
 use beef::Cow;
 use halfbrown::HashMap;
 use regex::Regex;
-use tremor_script::Value;
+use tremor_script::prelude::*;
 
 lazy_static::lazy_static! {
     static ref LINE_REGEXP: Regex = {
@@ -140,6 +140,27 @@ pub trait Operator: std::fmt::Debug + Send {
     fn skippable(&self) -> bool {
         false
     }
+
+    /// Captures the internal state of the operator, e.g. open windows, so it
+    /// can be restored after a restart. Defaults to `None` for operators
+    /// without such state.
+    ///
+    /// The ids of the events incorporated into the captured state are tracked
+    /// in `covered`, those events don't need to be replayed once it is restored.
+    ///
+    /// # Errors
+    /// if the state can not be captured
+    fn checkpoint(&self, _covered: &mut Option<EventId>) -> Result<Option<Value<'static>>> {
+        Ok(None)
+    }
+
+    /// Restores the internal state captured by `checkpoint` - defaults to a noop
+    ///
+    /// # Errors
+    /// if the state can not be restored
+    fn restore(&mut self, _state: &Value) -> Result<()> {
+        Ok(())
+    }
 }
 
 /// Initialisable trait that can be turned from a `NodeConfig`
@@ -177,6 +198,127 @@ pub trait ConfigImpl {
     }
 }
 
+/// A path into an event, either into its value (`event.customer.id`) or its
+/// metadata (`$customer.id`), operators read keys of events from
+#[derive(Debug, Clone, PartialEq)]
+pub enum KeyPath {
+    /// path into the value of the event
+    Event(Vec<String>),
+    /// path into the metadata of the event
+    Meta(Vec<String>),
+}
+
+impl KeyPath {
+    /// Parses a path starting with `event` or `$`
+    ///
+    /// # Errors
+    /// if the path doesn't start with `event` or `$`
+    pub fn parse(path: &str) -> Result<Self> {
+        let segments = |path: &str| -> Vec<String> {
+            path.split('.')
+                .filter(|s| !s.is_empty())
+                .map(ToString::to_string)
+                .collect()
+        };
+        if path == "event" {
+            Ok(Self::Event(vec![]))
+        } else if let Some(path) = path.strip_prefix("event.") {
+            Ok(Self::Event(segments(path)))
+        } else if let Some(path) = path.strip_prefix('$') {
+            Ok(Self::Meta(segments(path)))
+        } else {
+            Err(ErrorKind::BadOpConfig(format!(
+                "key `{}` needs to start with `event` or `$`",
+                path
+            ))
+            .into())
+        }
+    }
+
+    /// The encoded value the path points to in `event`, `null` if there is
+    /// none
+    #[must_use]
+    pub fn key(&self, event: &Event) -> String {
+        let (value, meta) = event.data.parts();
+        let (mut v, path) = match self {
+            Self::Event(path) => (value, path),
+            Self::Meta(path) => (meta, path),
+        };
+        for segment in path {
+            if let Some(next) = v.get(segment.as_str()) {
+                v = next;
+            } else {
+                return NULL.encode();
+            }
+        }
+        v.encode()
+    }
+}
+
+/// A key operators read from events, either a path (see [`KeyPath`]) or any
+/// other tremor script expression, e.g. `[event.customer, $tenant]`
+#[derive(Debug)]
+pub enum KeyExpr {
+    /// a path, read without running a script
+    Path(KeyPath),
+    /// an expression, evaluated against the event
+    Script(Box<tremor_script::Script>),
+}
+
+impl KeyExpr {
+    /// Parses a path or compiles an expression
+    ///
+    /// # Errors
+    /// if the expression doesn't compile
+    pub fn parse(expr: &str) -> Result<Self> {
+        let is_path = expr.split('.').all(|s| {
+            s.chars()
+                .all(|c| c.is_alphanumeric() || c == '_' || c == '$')
+        });
+        if let (true, Ok(path)) = (is_path, KeyPath::parse(expr)) {
+            return Ok(Self::Path(path));
+        }
+        let reg = crate::FN_REGISTRY.lock()?;
+        let script = tremor_script::Script::parse(
+            &tremor_script::path::load(),
+            "key",
+            expr.to_string(),
+            &reg,
+        )
+        .map_err(tremor_script::errors::CompilerError::error)?;
+        Ok(Self::Script(Box::new(script)))
+    }
+
+    /// The encoded value the key evaluates to for `event`
+    ///
+    /// # Errors
+    /// if the expression fails to evaluate or mutates the event
+    pub fn key(&self, event: &mut Event) -> Result<String> {
+        match self {
+            Self::Path(path) => Ok(path.key(event)),
+            Self::Script(script) => {
+                let context = EventContext::new(event.ingest_ns, event.origin_uri.take());
+                let state = Value::null();
+                let key =
+                    event
+                        .data
+                        .apply_script(&script.script, |data, script| -> Result<String> {
+                            let (value, meta) = data.parts();
+                            if let Return::Emit { value, .. } =
+                                script.run_imut(&context, AggrType::Emit, value, &state, meta)?
+                            {
+                                Ok(value.encode())
+                            } else {
+                                Ok(NULL.encode())
+                            }
+                        });
+                event.origin_uri = context.origin_uri;
+                key
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -195,4 +337,53 @@ mod test {
 
         assert_eq!(e, "invalid type: integer `5`, expected struct C")
     }
+
+    #[test]
+    fn key_path() -> Result<()> {
+        let event = Event {
+            data: (
+                literal!({"customer": {"id": 42}}),
+                literal!({"tenant": "snot"}),
+            )
+                .into(),
+            ..Event::default()
+        };
+        assert_eq!("42", KeyPath::parse("event.customer.id")?.key(&event));
+        assert_eq!("\"snot\"", KeyPath::parse("$tenant")?.key(&event));
+        assert_eq!("null", KeyPath::parse("event.badger")?.key(&event));
+        assert_eq!(
+            r#"{"customer":{"id":42}}"#,
+            KeyPath::parse("event")?.key(&event)
+        );
+        assert!(KeyPath::parse("customer").is_err());
+        assert_eq!(
+            KeyPath::Meta(vec!["tenant".to_string(), "id".to_string()]),
+            KeyPath::parse("$tenant.id")?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn key_expr() -> Result<()> {
+        let mut event = Event {
+            data: (
+                literal!({"customer": {"id": 42}}),
+                literal!({"tenant": "snot"}),
+            )
+                .into(),
+            ..Event::default()
+        };
+        let path = KeyExpr::parse("event.customer.id")?;
+        assert!(matches!(path, KeyExpr::Path(_)));
+        assert_eq!("42", path.key(&mut event)?);
+        let expr = KeyExpr::parse("[event.customer.id + 1, $tenant]")?;
+        assert!(matches!(expr, KeyExpr::Script(_)));
+        assert_eq!(r#"[43,"snot"]"#, expr.key(&mut event)?);
+        assert!(KeyExpr::parse("event.customer +").is_err());
+        // mutating expressions don't evaluate
+        assert!(KeyExpr::parse("let event.customer = 1")?
+            .key(&mut event)
+            .is_err());
+        Ok(())
+    }
 }
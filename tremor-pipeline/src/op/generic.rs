@@ -14,6 +14,8 @@
 
 pub mod batch;
 pub mod counter;
+pub mod dedup;
 
 pub use batch::BatchFactory;
 pub use counter::CounterFactory;
+pub use dedup::DedupFactory;
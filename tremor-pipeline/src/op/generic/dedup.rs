@@ -0,0 +1,473 @@
+// Copyright 2020-2021, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! # Deduplication
+//!
+//! Filters events whose key was already seen within a horizon, the last
+//! `capacity` keys and, if set, the last `window_ms` milliseconds.
+//!
+//! Keys are remembered either exactly in an LRU set, or in a bloom filter that
+//! uses a fixed amount of memory but reports a small share (`fp_rate`) of
+//! unique events as duplicates. The bloom filter keeps two generations of
+//! keys and starts a new one every `capacity` keys or `window_ms`, so keys are
+//! remembered for one to two horizons.
+//!
+//! ## Configuration
+//!
+//! See [Config](struct.Config.html) for details.
+//!
+//! ## Outputs
+//!
+//! The 1st additional output `duplicate` is used to route duplicate events.
+//!
+//! # Example
+//!
+//! ```yaml
+//! - id: dedup
+//!   op: generic::dedup
+//!   config:
+//!     key: $kafka.key
+//!     capacity: 100000
+//!     window_ms: 60000
+//! ```
+
+use crate::errors::{ErrorKind, Result};
+use crate::influx_value;
+use crate::op::prelude::*;
+use beef::Cow;
+use lru::LruCache;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use tremor_script::prelude::*;
+
+const DUPLICATE: Cow<'static, str> = Cow::const_str("duplicate");
+const DEDUP: Cow<'static, str> = Cow::const_str("dedup");
+const RESULT: Cow<'static, str> = Cow::const_str("result");
+const HIT: Cow<'static, str> = Cow::const_str("hit");
+const MISS: Cow<'static, str> = Cow::const_str("miss");
+
+/// How seen keys are remembered
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Strategy {
+    /// every key in an LRU set
+    Exact,
+    /// in a bloom filter
+    Bloom,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Config {
+    /// Key events are deduplicated by, a path into the event (`event.id`) or
+    /// its metadata (`$kafka.key`), or any other expression on them
+    /// (`[event.customer, event.order_id]`)
+    ///
+    /// default: `event`
+    #[serde(default = "d_key")]
+    pub key: String,
+    /// `exact` or `bloom`
+    ///
+    /// default: `exact`
+    #[serde(default = "d_strategy")]
+    pub strategy: Strategy,
+    /// Number of keys remembered
+    ///
+    /// default: `10000`
+    #[serde(default = "d_capacity")]
+    pub capacity: usize,
+    /// Time in milliseconds keys are remembered for
+    #[serde(default)]
+    pub window_ms: Option<u64>,
+    /// Share of unique events the bloom filter may report as duplicates
+    ///
+    /// default: `0.01`
+    #[serde(default = "d_fp_rate")]
+    pub fp_rate: f64,
+}
+
+impl ConfigImpl for Config {}
+
+fn d_key() -> String {
+    "event".to_string()
+}
+
+fn d_strategy() -> Strategy {
+    Strategy::Exact
+}
+
+fn d_capacity() -> usize {
+    10_000
+}
+
+fn d_fp_rate() -> f64 {
+    0.01
+}
+
+/// A fixed size bloom filter
+#[derive(Debug, Clone)]
+struct Bloom {
+    bits: Vec<u64>,
+    hashes: u32,
+}
+
+impl Bloom {
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_precision_loss,
+        clippy::cast_sign_loss
+    )]
+    fn new(capacity: usize, fp_rate: f64) -> Self {
+        let ln2 = std::f64::consts::LN_2;
+        let bits = (-(capacity as f64) * fp_rate.ln() / (ln2 * ln2))
+            .ceil()
+            .max(64.0);
+        let hashes = (bits / capacity as f64 * ln2).round().max(1.0) as u32;
+        Self {
+            bits: vec![0; (bits as usize + 63) / 64],
+            hashes,
+        }
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn positions(&self, key: &str) -> impl Iterator<Item = usize> {
+        let hash = |seed: u64| {
+            let mut hasher = DefaultHasher::new();
+            seed.hash(&mut hasher);
+            key.hash(&mut hasher);
+            hasher.finish()
+        };
+        let (h1, h2) = (hash(0), hash(1));
+        let len = (self.bits.len() * 64) as u64;
+        (0..u64::from(self.hashes))
+            .map(move |i| (h1.wrapping_add(i.wrapping_mul(h2)) % len) as usize)
+    }
+
+    fn contains(&self, key: &str) -> bool {
+        self.positions(key)
+            .all(|p| self.bits[p / 64] & (1 << (p % 64)) != 0)
+    }
+
+    fn insert(&mut self, key: &str) {
+        let positions: Vec<usize> = self.positions(key).collect();
+        for p in positions {
+            self.bits[p / 64] |= 1 << (p % 64);
+        }
+    }
+
+    fn clear(&mut self) {
+        self.bits.iter_mut().for_each(|b| *b = 0);
+    }
+}
+
+/// The keys seen so far
+enum Seen {
+    Exact(LruCache<String, u64>),
+    Bloom {
+        current: Bloom,
+        previous: Bloom,
+        len: usize,
+        since_ns: u64,
+    },
+}
+
+pub struct Dedup {
+    pub config: Config,
+    key: KeyExpr,
+    window_ns: Option<u64>,
+    seen: Seen,
+    hits: u64,
+    misses: u64,
+}
+
+impl Dedup {
+    fn new(config: Config) -> Result<Self> {
+        if config.capacity == 0 {
+            return Err(ErrorKind::BadOpConfig(
+                "Dedup `capacity` needs to be at least 1.".to_string(),
+            )
+            .into());
+        }
+        let seen = match config.strategy {
+            Strategy::Exact => Seen::Exact(LruCache::new(config.capacity)),
+            Strategy::Bloom => {
+                if config.fp_rate.is_nan() || config.fp_rate <= 0.0 || config.fp_rate >= 1.0 {
+                    return Err(ErrorKind::BadOpConfig(
+                        "Dedup `fp_rate` needs to be between 0 and 1.".to_string(),
+                    )
+                    .into());
+                }
+                let bloom = Bloom::new(config.capacity, config.fp_rate);
+                Seen::Bloom {
+                    current: bloom.clone(),
+                    previous: bloom,
+                    len: 0,
+                    since_ns: 0,
+                }
+            }
+        };
+        Ok(Self {
+            key: KeyExpr::parse(&config.key)?,
+            window_ns: config.window_ms.map(|ms| ms.saturating_mul(1_000_000)),
+            config,
+            seen,
+            hits: 0,
+            misses: 0,
+        })
+    }
+
+    /// Checks if `key` was seen within the horizon and remembers it
+    fn check(&mut self, key: String, now_ns: u64) -> bool {
+        let window_ns = self.window_ns;
+        let expired =
+            |seen_ns: u64| window_ns.map_or(false, |w| now_ns >= seen_ns.saturating_add(w));
+        match &mut self.seen {
+            Seen::Exact(keys) => match keys.get_mut(&key) {
+                Some(seen_ns) if !expired(*seen_ns) => true,
+                Some(seen_ns) => {
+                    *seen_ns = now_ns;
+                    false
+                }
+                None => {
+                    keys.put(key, now_ns);
+                    false
+                }
+            },
+            Seen::Bloom {
+                current,
+                previous,
+                len,
+                since_ns,
+            } => {
+                if *len >= self.config.capacity || (*len > 0 && expired(*since_ns)) {
+                    std::mem::swap(current, previous);
+                    current.clear();
+                    *len = 0;
+                }
+                if current.contains(&key) || previous.contains(&key) {
+                    true
+                } else {
+                    if *len == 0 {
+                        *since_ns = now_ns;
+                    }
+                    current.insert(&key);
+                    *len += 1;
+                    false
+                }
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for Dedup {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Dedup")
+    }
+}
+
+op!(DedupFactory(_uid, node) {
+    if let Some(map) = &node.config {
+        let config: Config = Config::new(map)?;
+        Ok(Box::new(Dedup::new(config)?))
+    } else {
+        Err(ErrorKind::MissingOpConfig(node.id.to_string()).into())
+    }
+});
+
+impl Operator for Dedup {
+    fn on_event(
+        &mut self,
+        _uid: u64,
+        _port: &str,
+        _state: &mut Value<'static>,
+        mut event: Event,
+    ) -> Result<EventAndInsights> {
+        let key = match self.key.key(&mut event) {
+            Ok(key) => key,
+            Err(e) => {
+                error!("Failed to evaluate the dedup key: {}", e);
+                return Ok(vec![(ERR, event)].into());
+            }
+        };
+        if self.check(key, event.ingest_ns) {
+            self.hits += 1;
+            Ok(vec![(DUPLICATE, event)].into())
+        } else {
+            self.misses += 1;
+            Ok(event.into())
+        }
+    }
+
+    fn metrics(
+        &self,
+        tags: &HashMap<Cow<'static, str>, Value<'static>>,
+        timestamp: u64,
+    ) -> Result<Vec<Value<'static>>> {
+        let mut tags = tags.clone();
+        tags.insert(RESULT, HIT.into());
+        let hits = influx_value(DEDUP, tags.clone(), self.hits, timestamp);
+        tags.insert(RESULT, MISS.into());
+        let misses = influx_value(DEDUP, tags, self.misses, timestamp);
+        Ok(vec![hits, misses])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn config(strategy: Strategy) -> Config {
+        Config {
+            key: "event.id".to_string(),
+            strategy,
+            capacity: 2,
+            window_ms: None,
+            fp_rate: 0.01,
+        }
+    }
+
+    fn event(id: u64, ingest_ns: u64) -> Event {
+        Event {
+            id: (1, 1, id).into(),
+            ingest_ns,
+            data: (literal!({ "id": id }), Value::object()).into(),
+            ..Event::default()
+        }
+    }
+
+    fn port(op: &mut Dedup, id: u64, ingest_ns: u64) -> Result<String> {
+        let mut state = Value::null();
+        let mut r = op
+            .on_event(0, "in", &mut state, event(id, ingest_ns))?
+            .events;
+        assert_eq!(1, r.len());
+        Ok(r.pop()
+            .map(|(port, _)| port.to_string())
+            .unwrap_or_default())
+    }
+
+    #[test]
+    fn bad_config() {
+        assert!(Dedup::new(Config {
+            capacity: 0,
+            ..config(Strategy::Exact)
+        })
+        .is_err());
+        assert!(Dedup::new(Config {
+            fp_rate: 1.0,
+            ..config(Strategy::Bloom)
+        })
+        .is_err());
+        assert!(Dedup::new(Config {
+            key: "event.id +".to_string(),
+            ..config(Strategy::Exact)
+        })
+        .is_err());
+    }
+
+    #[test]
+    fn exact() -> Result<()> {
+        let mut op = Dedup::new(config(Strategy::Exact))?;
+        assert_eq!("out", port(&mut op, 1, 0)?);
+        assert_eq!("duplicate", port(&mut op, 1, 0)?);
+        assert_eq!("out", port(&mut op, 2, 0)?);
+        assert_eq!("duplicate", port(&mut op, 1, 0)?);
+        // 3 pushes out 2, the least recently seen key
+        assert_eq!("out", port(&mut op, 3, 0)?);
+        assert_eq!("out", port(&mut op, 2, 0)?);
+        assert_eq!(2, op.hits);
+        assert_eq!(4, op.misses);
+
+        let metrics = op.metrics(&HashMap::new(), 42)?;
+        assert_eq!(
+            Some(2),
+            metrics[0].get("fields").and_then(|f| f.get_u64("count"))
+        );
+        assert_eq!(
+            Some(4),
+            metrics[1].get("fields").and_then(|f| f.get_u64("count"))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn expression_key() -> Result<()> {
+        let mut op = Dedup::new(Config {
+            key: "event.id % 2".to_string(),
+            ..config(Strategy::Exact)
+        })?;
+        assert_eq!("out", port(&mut op, 1, 0)?);
+        assert_eq!("out", port(&mut op, 2, 0)?);
+        assert_eq!("duplicate", port(&mut op, 3, 0)?);
+        Ok(())
+    }
+
+    #[test]
+    fn exact_window() -> Result<()> {
+        let mut op = Dedup::new(Config {
+            window_ms: Some(1),
+            ..config(Strategy::Exact)
+        })?;
+        assert_eq!("out", port(&mut op, 1, 0)?);
+        assert_eq!("duplicate", port(&mut op, 1, 999_999)?);
+        assert_eq!("out", port(&mut op, 1, 1_000_000)?);
+        assert_eq!("duplicate", port(&mut op, 1, 1_000_001)?);
+        Ok(())
+    }
+
+    #[test]
+    fn bloom() -> Result<()> {
+        let mut op = Dedup::new(config(Strategy::Bloom))?;
+        assert_eq!("out", port(&mut op, 1, 0)?);
+        assert_eq!("duplicate", port(&mut op, 1, 0)?);
+        assert_eq!("out", port(&mut op, 2, 0)?);
+        // the 3rd key starts a new generation, 1 and 2 are still remembered
+        assert_eq!("out", port(&mut op, 3, 0)?);
+        assert_eq!("duplicate", port(&mut op, 1, 0)?);
+        assert_eq!("out", port(&mut op, 4, 0)?);
+        // the 5th key starts another generation, forgetting 1 and 2
+        assert_eq!("out", port(&mut op, 5, 0)?);
+        assert_eq!("out", port(&mut op, 1, 0)?);
+        Ok(())
+    }
+
+    #[test]
+    fn bloom_window() -> Result<()> {
+        let mut op = Dedup::new(Config {
+            capacity: 100,
+            window_ms: Some(1),
+            ..config(Strategy::Bloom)
+        })?;
+        assert_eq!("out", port(&mut op, 1, 0)?);
+        assert_eq!("duplicate", port(&mut op, 1, 999_999)?);
+        // a new generation, 1 is still in the previous one
+        assert_eq!("duplicate", port(&mut op, 1, 1_000_000)?);
+        assert_eq!("out", port(&mut op, 2, 1_000_000)?);
+        // another one, 1 is forgotten
+        assert_eq!("out", port(&mut op, 1, 2_000_000)?);
+        Ok(())
+    }
+
+    #[test]
+    fn bloom_fp_rate() {
+        let mut bloom = Bloom::new(1000, 0.01);
+        for i in 0..1000 {
+            bloom.insert(&i.to_string());
+        }
+        assert!((0..1000).all(|i| bloom.contains(&i.to_string())));
+        let fps = (1000..11000)
+            .filter(|i| bloom.contains(&i.to_string()))
+            .count();
+        assert!(fps < 300, "{} false positives", fps);
+    }
+}
@@ -25,3 +25,4 @@ pub const OUT: Cow<'static, str> = Cow::const_str("out");
 pub const IN: Cow<'static, str> = Cow::const_str("in");
 pub const ERR: Cow<'static, str> = Cow::const_str("err");
 pub const METRICS: Cow<'static, str> = Cow::const_str("metrics");
+pub const LATE: Cow<'static, str> = Cow::const_str("late");
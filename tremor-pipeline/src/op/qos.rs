@@ -14,10 +14,12 @@
 
 pub mod backpressure;
 pub mod percentile;
+pub mod ratelimit;
 pub mod rr;
 pub mod wal;
 
 pub use backpressure::BackpressureFactory;
 pub use percentile::PercentileFactory;
+pub use ratelimit::RateLimitFactory;
 pub use rr::RoundRobinFactory;
 pub use wal::WalFactory;
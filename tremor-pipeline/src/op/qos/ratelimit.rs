@@ -0,0 +1,560 @@
+// Copyright 2020-2021, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! # Token bucket rate limiter
+//!
+//! Enforces a hard budget of events or bytes per second. Every key gets its
+//! own token bucket that holds up to `burst` tokens and is refilled with
+//! `rate` tokens per second. An event passes if its bucket holds enough
+//! tokens for it, one per event or one per byte, everything else is excess.
+//!
+//! Excess events are either dropped, routed to the `overflow` port or delayed
+//! until their bucket has been refilled. Delayed events are released in order
+//! as further events or ticks arrive, if more than `max_delayed` events of a
+//! key are waiting, or an event exceeds `burst` on its own, it is routed to
+//! the `overflow` port.
+//!
+//! Buckets that are full and hold no delayed events are forgotten on ticks.
+//! At most `max_keys` buckets are kept, a new key evicts the least recently
+//! used bucket and routes its delayed events to the `overflow` port.
+//!
+//! ## Configuration
+//!
+//! See [Config](struct.Config.html) for details.
+//!
+//! ## Outputs
+//!
+//! The 1st additional output `overflow` is used to route excess events.
+//!
+//! # Example
+//!
+//! ```yaml
+//! - id: quota
+//!   op: qos::ratelimit
+//!   config:
+//!     rate: 100
+//!     burst: 200
+//!     key: event.customer
+//!     on_excess: delay
+//! ```
+
+use crate::errors::{ErrorKind, Result};
+use crate::op::prelude::*;
+use beef::Cow;
+use lru::LruCache;
+use std::collections::VecDeque;
+use tremor_script::prelude::*;
+
+const OVERFLOW: Cow<'static, str> = Cow::const_str("overflow");
+
+/// What the budget is measured in
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Unit {
+    /// every event costs one token
+    Events,
+    /// every byte of an event costs one token
+    Bytes,
+}
+
+/// What happens to events exceeding the budget
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OnExcess {
+    /// discard the event
+    Drop,
+    /// hold the event back until the budget allows it
+    Delay,
+    /// route the event to the `overflow` port
+    Overflow,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Config {
+    /// Tokens added to a bucket per second
+    pub rate: f64,
+    /// Maximum number of tokens a bucket holds
+    ///
+    /// default: `rate`
+    #[serde(default)]
+    pub burst: Option<f64>,
+    /// What a token stands for, `events` or `bytes`
+    ///
+    /// default: `events`
+    #[serde(default = "d_unit")]
+    pub unit: Unit,
+    /// Path of the key events are bucketed by, either into the event
+    /// (`event.customer.id`) or its metadata (`$customer.id`). Without a key
+    /// all events share a single bucket.
+    #[serde(default)]
+    pub key: Option<String>,
+    /// What to do with excess events, `drop`, `delay` or `overflow`
+    ///
+    /// default: `overflow`
+    #[serde(default = "d_on_excess")]
+    pub on_excess: OnExcess,
+    /// Maximum number of events delayed per key
+    ///
+    /// default: `1000`
+    #[serde(default = "d_max_delayed")]
+    pub max_delayed: usize,
+    /// Maximum number of keys a bucket is kept for
+    ///
+    /// default: `10000`
+    #[serde(default = "d_max_keys")]
+    pub max_keys: usize,
+}
+
+impl ConfigImpl for Config {}
+
+fn d_unit() -> Unit {
+    Unit::Events
+}
+
+fn d_on_excess() -> OnExcess {
+    OnExcess::Overflow
+}
+
+fn d_max_delayed() -> usize {
+    1000
+}
+
+fn d_max_keys() -> usize {
+    10_000
+}
+
+#[derive(Debug, Clone)]
+struct Bucket {
+    tokens: f64,
+    last_ns: u64,
+    delayed: VecDeque<(f64, Event)>,
+}
+
+impl Bucket {
+    fn new(burst: f64, now_ns: u64) -> Self {
+        Self {
+            tokens: burst,
+            last_ns: now_ns,
+            delayed: VecDeque::new(),
+        }
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn refill(&mut self, rate: f64, burst: f64, now_ns: u64) {
+        if now_ns > self.last_ns {
+            let elapsed = (now_ns - self.last_ns) as f64 / 1_000_000_000.0;
+            self.tokens = (self.tokens + elapsed * rate).min(burst);
+            self.last_ns = now_ns;
+        }
+    }
+
+    fn take(&mut self, cost: f64) -> bool {
+        if self.tokens >= cost {
+            self.tokens -= cost;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Releases the delayed events the bucket has tokens for, in order
+    fn release(&mut self, out: &mut Vec<(Cow<'static, str>, Event)>) {
+        while let Some((cost, _)) = self.delayed.front() {
+            if !self.take(*cost) {
+                break;
+            }
+            if let Some((_, event)) = self.delayed.pop_front() {
+                out.push((OUT, event));
+            }
+        }
+    }
+}
+
+pub struct RateLimit {
+    pub config: Config,
+    burst: f64,
+    key: Option<KeyPath>,
+    buckets: LruCache<String, Bucket>,
+}
+
+impl RateLimit {
+    fn new(config: Config) -> Result<Self> {
+        let burst = config.burst.unwrap_or(config.rate);
+        if config.rate.is_nan() || config.rate <= 0.0 {
+            return Err(ErrorKind::BadOpConfig(
+                "Rate limit `rate` needs to be greater than 0.".to_string(),
+            )
+            .into());
+        }
+        if burst.is_nan() || burst < 1.0 {
+            return Err(ErrorKind::BadOpConfig(
+                "Rate limit `burst` needs to be at least 1.".to_string(),
+            )
+            .into());
+        }
+        if config.max_keys == 0 {
+            return Err(ErrorKind::BadOpConfig(
+                "Rate limit `max_keys` needs to be at least 1.".to_string(),
+            )
+            .into());
+        }
+        let key = config.key.as_deref().map(KeyPath::parse).transpose()?;
+        Ok(Self {
+            buckets: LruCache::new(config.max_keys),
+            config,
+            burst,
+            key,
+        })
+    }
+
+    /// The tokens an event costs
+    #[allow(clippy::cast_precision_loss)]
+    fn cost(&self, event: &Event) -> f64 {
+        match self.config.unit {
+            Unit::Events => 1.0,
+            Unit::Bytes => event
+                .value_iter()
+                .map(|v| {
+                    v.as_bytes()
+                        .map(<[u8]>::len)
+                        .or_else(|| v.as_str().map(str::len))
+                        .unwrap_or_else(|| v.encode().len())
+                })
+                .sum::<usize>() as f64,
+        }
+    }
+
+    /// Passes, delays or routes an event to the `overflow` port
+    fn admit(
+        config: &Config,
+        burst: f64,
+        bucket: &mut Bucket,
+        cost: f64,
+        event: Event,
+        events: &mut Vec<(Cow<'static, str>, Event)>,
+    ) {
+        match config.on_excess {
+            OnExcess::Delay => {
+                bucket.release(events);
+                if cost > burst {
+                    events.push((OVERFLOW, event));
+                } else if bucket.delayed.is_empty() && bucket.take(cost) {
+                    events.push((OUT, event));
+                } else if bucket.delayed.len() < config.max_delayed {
+                    bucket.delayed.push_back((cost, event));
+                } else {
+                    events.push((OVERFLOW, event));
+                }
+            }
+            OnExcess::Drop => {
+                if bucket.take(cost) {
+                    events.push((OUT, event));
+                }
+            }
+            OnExcess::Overflow => {
+                if bucket.take(cost) {
+                    events.push((OUT, event));
+                } else {
+                    events.push((OVERFLOW, event));
+                }
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for RateLimit {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "RateLimit")
+    }
+}
+
+op!(RateLimitFactory(_uid, node) {
+    if let Some(map) = &node.config {
+        let config: Config = Config::new(map)?;
+        Ok(Box::new(RateLimit::new(config)?))
+    } else {
+        Err(ErrorKind::MissingOpConfig(node.id.to_string()).into())
+    }
+});
+
+impl Operator for RateLimit {
+    fn on_event(
+        &mut self,
+        _uid: u64,
+        _port: &str,
+        _state: &mut Value<'static>,
+        event: Event,
+    ) -> Result<EventAndInsights> {
+        let cost = self.cost(&event);
+        let key = self
+            .key
+            .as_ref()
+            .map_or_else(String::new, |path| path.key(&event));
+        let RateLimit {
+            config,
+            burst,
+            buckets,
+            ..
+        } = self;
+        let now_ns = event.ingest_ns;
+        let mut events = Vec::new();
+        if let Some(bucket) = buckets.get_mut(&key) {
+            bucket.refill(config.rate, *burst, now_ns);
+            Self::admit(config, *burst, bucket, cost, event, &mut events);
+        } else {
+            let mut bucket = Bucket::new(*burst, now_ns);
+            Self::admit(config, *burst, &mut bucket, cost, event, &mut events);
+            // the least recently used bucket makes room for the new key
+            if buckets.len() >= config.max_keys {
+                if let Some((_, evicted)) = buckets.pop_lru() {
+                    events.extend(evicted.delayed.into_iter().map(|(_, e)| (OVERFLOW, e)));
+                }
+            }
+            buckets.put(key, bucket);
+        }
+        Ok(events.into())
+    }
+
+    fn handles_signal(&self) -> bool {
+        true
+    }
+
+    fn on_signal(
+        &mut self,
+        _uid: u64,
+        _state: &Value<'static>,
+        signal: &mut Event,
+    ) -> Result<EventAndInsights> {
+        let RateLimit {
+            config,
+            burst,
+            buckets,
+            ..
+        } = self;
+        let mut events = Vec::new();
+        for (_, bucket) in buckets.iter_mut() {
+            bucket.refill(config.rate, *burst, signal.ingest_ns);
+            bucket.release(&mut events);
+        }
+        // full buckets without delayed events are the same as new ones
+        let full: Vec<String> = buckets
+            .iter()
+            .filter(|(_, b)| b.delayed.is_empty() && b.tokens >= *burst)
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in full {
+            buckets.pop(&key);
+        }
+        Ok(events.into())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn config(on_excess: OnExcess) -> Config {
+        Config {
+            rate: 2.0,
+            burst: None,
+            unit: Unit::Events,
+            key: None,
+            on_excess,
+            max_delayed: 2,
+            max_keys: 2,
+        }
+    }
+
+    fn event(id: u64, ingest_ns: u64, value: Value<'static>) -> Event {
+        Event {
+            id: (1, 1, id).into(),
+            ingest_ns,
+            data: (value, Value::object()).into(),
+            ..Event::default()
+        }
+    }
+
+    fn ports(r: EventAndInsights) -> Vec<(String, u64)> {
+        r.events
+            .into_iter()
+            .map(|(port, e)| (port.to_string(), e.id.get_max_by_stream(1, 1).unwrap_or(0)))
+            .collect()
+    }
+
+    fn send(op: &mut RateLimit, e: Event) -> Result<Vec<(String, u64)>> {
+        let mut state = Value::null();
+        Ok(ports(op.on_event(0, "in", &mut state, e)?))
+    }
+
+    #[test]
+    fn bad_config() {
+        assert!(RateLimit::new(Config {
+            rate: 0.0,
+            ..config(OnExcess::Drop)
+        })
+        .is_err());
+        assert!(RateLimit::new(Config {
+            burst: Some(0.5),
+            ..config(OnExcess::Drop)
+        })
+        .is_err());
+        assert!(RateLimit::new(Config {
+            max_keys: 0,
+            ..config(OnExcess::Drop)
+        })
+        .is_err());
+        assert!(RateLimit::new(Config {
+            key: Some("snot".to_string()),
+            ..config(OnExcess::Drop)
+        })
+        .is_err());
+    }
+
+    #[test]
+    fn overflow() -> Result<()> {
+        let mut op = RateLimit::new(config(OnExcess::Overflow))?;
+        assert_eq!(
+            vec![("out".to_string(), 1)],
+            send(&mut op, event(1, 0, Value::null()))?
+        );
+        assert_eq!(
+            vec![("out".to_string(), 2)],
+            send(&mut op, event(2, 0, Value::null()))?
+        );
+        assert_eq!(
+            vec![("overflow".to_string(), 3)],
+            send(&mut op, event(3, 0, Value::null()))?
+        );
+        // half a second later one token was refilled
+        assert_eq!(
+            vec![("out".to_string(), 4)],
+            send(&mut op, event(4, 500_000_000, Value::null()))?
+        );
+        assert_eq!(
+            vec![("overflow".to_string(), 5)],
+            send(&mut op, event(5, 500_000_000, Value::null()))?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn drop_excess() -> Result<()> {
+        let mut op = RateLimit::new(config(OnExcess::Drop))?;
+        assert_eq!(1, send(&mut op, event(1, 0, Value::null()))?.len());
+        assert_eq!(1, send(&mut op, event(2, 0, Value::null()))?.len());
+        assert!(send(&mut op, event(3, 0, Value::null()))?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn delay() -> Result<()> {
+        let mut op = RateLimit::new(config(OnExcess::Delay))?;
+        let mut state = Value::null();
+        assert_eq!(1, send(&mut op, event(1, 0, Value::null()))?.len());
+        assert_eq!(1, send(&mut op, event(2, 0, Value::null()))?.len());
+        // held back
+        assert!(send(&mut op, event(3, 0, Value::null()))?.is_empty());
+        assert!(send(&mut op, event(4, 0, Value::null()))?.is_empty());
+        // more than `max_delayed`
+        assert_eq!(
+            vec![("overflow".to_string(), 5)],
+            send(&mut op, event(5, 0, Value::null()))?
+        );
+        // a tick one second later releases the delayed events in order
+        let mut signal = Event {
+            ingest_ns: 1_000_000_000,
+            ..Event::default()
+        };
+        let r = op.on_signal(0, &state, &mut signal)?;
+        assert_eq!(
+            vec![("out".to_string(), 3), ("out".to_string(), 4)],
+            ports(r)
+        );
+        // all tokens are used up, so the next event is held back again
+        assert!(op
+            .on_event(0, "in", &mut state, event(6, 1_000_000_000, Value::null()))?
+            .events
+            .is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn keys_and_bytes() -> Result<()> {
+        let mut op = RateLimit::new(Config {
+            rate: 10.0,
+            unit: Unit::Bytes,
+            key: Some("event.customer".to_string()),
+            ..config(OnExcess::Overflow)
+        })?;
+        let snot = || literal!({"customer": "snot", "data": "0123456789"});
+        let badger = literal!({"customer": "badger"});
+        // the encoded event exceeds the burst of 10 bytes
+        assert_eq!(
+            vec![("overflow".to_string(), 1)],
+            send(&mut op, event(1, 0, snot()))?
+        );
+        // badger has a bucket of its own
+        assert_eq!(
+            vec![("overflow".to_string(), 2)],
+            send(&mut op, event(2, 0, badger))?
+        );
+        assert_eq!(2, op.buckets.len());
+        assert_eq!(
+            vec![("out".to_string(), 3)],
+            send(&mut op, event(3, 0, Value::from("0123456789")))?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn forget_full_buckets() -> Result<()> {
+        let mut op = RateLimit::new(Config {
+            key: Some("event".to_string()),
+            ..config(OnExcess::Drop)
+        })?;
+        send(&mut op, event(1, 0, Value::from(1)))?;
+        send(&mut op, event(2, 0, Value::from(2)))?;
+        assert_eq!(2, op.buckets.len());
+        let mut signal = Event {
+            ingest_ns: 1_000_000_000,
+            ..Event::default()
+        };
+        op.on_signal(0, &Value::null(), &mut signal)?;
+        assert!(op.buckets.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn evict_least_recently_used() -> Result<()> {
+        let mut op = RateLimit::new(Config {
+            burst: Some(1.0),
+            key: Some("event".to_string()),
+            ..config(OnExcess::Delay)
+        })?;
+        assert_eq!(1, send(&mut op, event(1, 0, Value::from(1)))?.len());
+        // delayed in the bucket of key 1
+        assert!(send(&mut op, event(2, 0, Value::from(1)))?.is_empty());
+        assert_eq!(1, send(&mut op, event(3, 0, Value::from(2)))?.len());
+        // key 2 was used more recently, so key 1 makes room for key 3
+        assert!(send(&mut op, event(4, 0, Value::from(2)))?.is_empty());
+        assert_eq!(
+            vec![("out".to_string(), 5), ("overflow".to_string(), 2)],
+            send(&mut op, event(5, 0, Value::from(3)))?
+        );
+        assert_eq!(2, op.buckets.len());
+        assert!(op.buckets.contains(&"2".to_string()));
+        Ok(())
+    }
+}
@@ -22,6 +22,8 @@ use crate::{op::prelude::*, EventIdGenerator};
 use crate::{Event, Operator};
 use halfbrown::{HashMap, RawEntryMut};
 use std::borrow::Cow as SCow;
+use std::collections::VecDeque;
+use std::convert::TryFrom;
 use std::mem;
 use tremor_common::stry;
 use tremor_script::{
@@ -42,6 +44,16 @@ pub struct GroupData {
     aggrs: Aggregates<'static>,
     id: EventId,
     transactional: bool,
+    /// closed panes of a sliding window that are still part of it, oldest first
+    panes: VecDeque<Pane>,
+}
+
+/// aggregation state of a slice of a sliding window
+#[derive(Debug, Clone)]
+struct Pane {
+    aggrs: Aggregates<'static>,
+    id: EventId,
+    transactional: bool,
 }
 
 impl GroupData {
@@ -50,11 +62,197 @@ impl GroupData {
     }
 
     fn reset(&mut self) {
-        for aggr in &mut self.aggrs {
-            aggr.invocable.init();
+        // event time windows keep their aggregates until they retire,
+        // so late events are emitted as part of the updated window result
+        if self.window.as_event_time().is_none() {
+            for aggr in &mut self.aggrs {
+                aggr.invocable.init();
+            }
         }
         self.transactional = false;
     }
+
+    /// merge the closed panes of a sliding window and the currently open pane, oldest first,
+    /// into a fresh aggregation state, so the emitted event covers the whole window.
+    /// Aggregate functions expect to be merged into the earlier state, see `TremorAggrFn::merge`.
+    ///
+    /// Returns the state of the open pane before merging, it needs to be handed to `close_pane`
+    /// after the window has been emitted. Returns `None` for all non-sliding windows.
+    fn merge_panes(&mut self, node_meta: &NodeMetas) -> Result<Option<Pane>> {
+        if !self.window.is_sliding() {
+            return Ok(None);
+        }
+        let open = Pane {
+            aggrs: self.aggrs.clone(),
+            id: self.id.clone(),
+            transactional: self.transactional,
+        };
+        let mut merged = self.aggrs.clone();
+        for aggr in &mut merged {
+            aggr.invocable.init();
+        }
+        let oldest_first = self
+            .panes
+            .iter()
+            .map(|pane| &pane.aggrs)
+            .chain(std::iter::once(&open.aggrs));
+        for aggrs in oldest_first {
+            for (this, later) in merged.iter_mut().zip(aggrs.iter()) {
+                this.invocable.merge(&later.invocable).map_err(|e| {
+                    let r: Option<&Registry> = None;
+                    e.into_err(later, later, r, node_meta)
+                })?;
+            }
+        }
+        self.aggrs = merged;
+        for pane in &self.panes {
+            self.id.track(&pane.id);
+            self.transactional = self.transactional || pane.transactional;
+        }
+        Ok(Some(open))
+    }
+
+    /// keep the pane that has just been emitted around for the next windows
+    /// and forget about the panes that slid out of the window
+    fn close_pane(&mut self, pane: Option<Pane>) {
+        if let Some(pane) = pane {
+            self.panes.push_back(pane);
+            let retained = self.window.retained_panes();
+            while self.panes.len() > retained {
+                self.panes.pop_front();
+            }
+        }
+    }
+
+    /// captures the state of this group for a checkpoint and tracks the ids
+    /// of all events incorporated into it in `covered`
+    fn snapshot(&self, covered: &mut Option<EventId>) -> Result<Value<'static>> {
+        track_covered(covered, &self.id);
+        let mut panes = Vec::with_capacity(self.panes.len());
+        for pane in &self.panes {
+            track_covered(covered, &pane.id);
+            panes.push(literal!({
+                "aggrs": snapshot_aggrs(&pane.aggrs)?,
+                "transactional": pane.transactional,
+            }));
+        }
+        Ok(literal!({
+            "group": self.group.clone(),
+            "window": self.window.snapshot(),
+            "aggrs": snapshot_aggrs(&self.aggrs)?,
+            "transactional": self.transactional,
+            "panes": panes,
+        }))
+    }
+
+    /// restores a group captured by `snapshot`. The ids of the events incorporated
+    /// into it belong to a previous run, so the group starts out with a fresh id.
+    fn restore(
+        window: &WindowImpl,
+        aggregates: &[InvokeAggrFn<'static>],
+        idgen: &mut EventIdGenerator,
+        state: &Value,
+    ) -> Result<Self> {
+        let mut window = window.clone();
+        window.restore(
+            state
+                .get("window")
+                .ok_or_else(|| invalid_checkpoint("window"))?,
+        )?;
+        let mut panes = VecDeque::new();
+        for pane in state
+            .get_array("panes")
+            .ok_or_else(|| invalid_checkpoint("panes"))?
+        {
+            panes.push_back(Pane {
+                aggrs: restore_aggrs(aggregates, pane.get("aggrs"))?,
+                id: idgen.next_id(),
+                transactional: pane.get_bool("transactional").unwrap_or_default(),
+            });
+        }
+        Ok(Self {
+            group: state
+                .get("group")
+                .ok_or_else(|| invalid_checkpoint("group"))?
+                .clone_static(),
+            window,
+            aggrs: restore_aggrs(aggregates, state.get("aggrs"))?,
+            id: idgen.next_id(),
+            transactional: state.get_bool("transactional").unwrap_or_default(),
+            panes,
+        })
+    }
+}
+
+fn invalid_checkpoint(what: &str) -> Error {
+    format!("Invalid checkpoint: {} is missing or invalid", what).into()
+}
+
+fn track_covered(covered: &mut Option<EventId>, id: &EventId) {
+    if let Some(covered) = covered {
+        covered.track(id);
+    } else {
+        *covered = Some(id.clone());
+    }
+}
+
+fn snapshot_aggrs(aggrs: &[InvokeAggrFn<'static>]) -> Result<Value<'static>> {
+    aggrs
+        .iter()
+        .map(|aggr| {
+            aggr.invocable.snapshot().ok_or_else(|| {
+                Error::from(format!("{:?} doesn't support checkpoints", aggr.invocable))
+            })
+        })
+        .collect()
+}
+
+fn restore_aggrs(
+    aggregates: &[InvokeAggrFn<'static>],
+    state: Option<&Value>,
+) -> Result<Aggregates<'static>> {
+    let states = state
+        .and_then(Value::as_array)
+        .filter(|states| states.len() == aggregates.len())
+        .ok_or_else(|| invalid_checkpoint("aggrs"))?;
+    let mut aggrs = aggregates.to_vec();
+    for (aggr, state) in aggrs.iter_mut().zip(states) {
+        aggr.invocable.init();
+        aggr.invocable.restore(state).map_err(|e| {
+            Error::from(format!(
+                "Invalid checkpoint for {:?}: {:?}",
+                aggr.invocable, e
+            ))
+        })?;
+    }
+    Ok(aggrs)
+}
+
+fn snapshot_groups(groups: &Groups, covered: &mut Option<EventId>) -> Result<Value<'static>> {
+    let mut snapshot = Object::with_capacity(groups.len());
+    for (group_str, group) in groups {
+        snapshot.insert(group_str.clone().into(), group.snapshot(covered)?);
+    }
+    Ok(Value::from(snapshot))
+}
+
+fn restore_groups(
+    window: &WindowImpl,
+    aggregates: &[InvokeAggrFn<'static>],
+    idgen: &mut EventIdGenerator,
+    state: Option<&Value>,
+) -> Result<Groups> {
+    let states = state
+        .and_then(Value::as_object)
+        .ok_or_else(|| invalid_checkpoint("groups"))?;
+    let mut groups = Groups::with_capacity(states.len());
+    for (group_str, state) in states {
+        groups.insert(
+            group_str.to_string(),
+            GroupData::restore(window, aggregates, idgen, state)?,
+        );
+    }
+    Ok(groups)
 }
 
 pub(crate) type Groups = HashMap<String, GroupData>;
@@ -79,12 +277,22 @@ pub trait WindowTrait: std::fmt::Debug {
     fn on_tick(&mut self, _ns: u64) -> Result<WindowEvent> {
         Ok(WindowEvent::all_false())
     }
+    /// handle the advancement of the watermark to `watermark`
+    fn on_watermark(&mut self, _watermark: u64) -> Result<WindowEvent> {
+        Ok(WindowEvent::all_false())
+    }
     fn eviction_ns(&self) -> Option<u64>;
     /// maximum number of groups to keep around simultaneously
     /// a value of `u64::MAX` allows as much simultaneous groups as possible
     /// decreasing this value will guard against runwaway memory growth
     /// when faced with unexpected huge cardinalities for grouping dimensions
     fn max_groups(&self) -> u64;
+    /// number of closed panes that are still part of the current window
+    /// and need to be merged into its aggregation state when emitting.
+    /// This is always `0` for tumbling windows.
+    fn retained_panes(&self) -> usize {
+        0
+    }
 }
 
 #[derive(Debug)]
@@ -95,6 +303,11 @@ pub struct Window {
     dims: Groups,
     last_dims: Groups,
     next_swap: u64,
+    /// event time progress, only tracked for event time windows
+    watermark: Option<Watermark>,
+    /// group value and start of the next interval to check for emptiness for every group,
+    /// only tracked for event time windows emitting empty windows
+    next_intervals: HashMap<String, (Value<'static>, u64)>,
 }
 
 impl Window {
@@ -107,6 +320,20 @@ impl Window {
     pub(crate) fn ident_name(fqwn: &str) -> &str {
         fqwn.split("::").last().map_or(fqwn, |last| last)
     }
+
+    /// forget about all event time windows that no longer accept late events at `watermark`
+    fn retire(&mut self, watermark: u64) {
+        for groups in vec![&mut self.dims, &mut self.last_dims] {
+            let retired: Vec<String> = groups
+                .iter()
+                .filter(|(_, group_data)| group_data.window.is_retired(watermark))
+                .map(|(group_str, _)| group_str.clone())
+                .collect();
+            for group_str in retired {
+                groups.remove(&group_str);
+            }
+        }
+    }
 }
 
 // We allow this since No is barely ever used.
@@ -114,6 +341,10 @@ impl Window {
 pub enum WindowImpl {
     TumblingCountBased(TumblingWindowOnNumber),
     TumblingTimeBased(TumblingWindowOnTime),
+    SlidingCountBased(SlidingWindowOnNumber),
+    SlidingTimeBased(SlidingWindowOnTime),
+    Session(SessionWindow),
+    TumblingEventTimeBased(TumblingWindowOnEventTime),
 }
 
 impl WindowImpl {
@@ -124,6 +355,106 @@ impl WindowImpl {
     // do not emit empty windows by default
     // this preserves backward compatibility
     pub const DEFAULT_EMIT_EMPTY_WINDOWS: bool = false;
+
+    /// sliding windows keep their aggregation state in panes
+    /// that are merged when the window is emitted
+    pub fn is_sliding(&self) -> bool {
+        matches!(self, Self::SlidingCountBased(_) | Self::SlidingTimeBased(_))
+    }
+
+    /// windows on event time are driven by watermarks
+    pub fn as_event_time(&self) -> Option<&TumblingWindowOnEventTime> {
+        match self {
+            Self::TumblingEventTimeBased(w) => Some(w),
+            _ => None,
+        }
+    }
+
+    /// opens the event time window for the interval starting at `start`, used for empty windows
+    fn open_at(&mut self, start: u64) {
+        if let Self::TumblingEventTimeBased(w) = self {
+            w.start = Some(start);
+        }
+    }
+
+    /// checks if this event time window no longer accepts events at `watermark`
+    fn is_retired(&self, watermark: u64) -> bool {
+        match self {
+            Self::TumblingEventTimeBased(w) => w
+                .start
+                .map_or(false, |start| w.is_retired_at(start, watermark)),
+            _ => false,
+        }
+    }
+
+    /// captures the progress of the window of a single group
+    fn snapshot(&self) -> Value<'static> {
+        match self {
+            Self::TumblingCountBased(w) => literal!({ "count": w.count }),
+            Self::TumblingTimeBased(w) => literal!({
+                "next_window": w.next_window,
+                "events": w.events,
+            }),
+            Self::SlidingCountBased(w) => literal!({
+                "count": w.count,
+                "closed": w.closed,
+            }),
+            Self::SlidingTimeBased(w) => literal!({
+                "next_window": w.next_window,
+                "events": w.events,
+                "pane_events": w.pane_events.iter().copied().collect::<Vec<_>>(),
+            }),
+            Self::Session(w) => literal!({
+                "session_start": w.session_start,
+                "last_event": w.last_event,
+            }),
+            Self::TumblingEventTimeBased(w) => literal!({
+                "start": w.start,
+                "fired": w.fired,
+            }),
+        }
+    }
+
+    /// restores the progress captured by `snapshot`
+    fn restore(&mut self, state: &Value) -> Result<()> {
+        let u64_of = |k: &str| state.get_u64(k).ok_or_else(|| invalid_checkpoint(k));
+        let optional_u64_of = |k: &str| match state.get(k) {
+            Some(v) if v.is_null() => Ok(None),
+            Some(v) => v.as_u64().map(Some).ok_or_else(|| invalid_checkpoint(k)),
+            None => Err(invalid_checkpoint(k)),
+        };
+        match self {
+            Self::TumblingCountBased(w) => w.count = u64_of("count")?,
+            Self::TumblingTimeBased(w) => {
+                w.next_window = optional_u64_of("next_window")?;
+                w.events = u64_of("events")?;
+            }
+            Self::SlidingCountBased(w) => {
+                w.count = u64_of("count")?;
+                w.closed = state
+                    .get("closed")
+                    .and_then(Value::as_usize)
+                    .ok_or_else(|| invalid_checkpoint("closed"))?;
+            }
+            Self::SlidingTimeBased(w) => {
+                w.next_window = optional_u64_of("next_window")?;
+                w.events = u64_of("events")?;
+                w.pane_events = state
+                    .get_array("pane_events")
+                    .and_then(|events| events.iter().map(Value::as_u64).collect())
+                    .ok_or_else(|| invalid_checkpoint("pane_events"))?;
+            }
+            Self::Session(w) => {
+                w.session_start = optional_u64_of("session_start")?;
+                w.last_event = u64_of("last_event")?;
+            }
+            Self::TumblingEventTimeBased(w) => {
+                w.start = optional_u64_of("start")?;
+                w.fired = state.get_bool("fired").unwrap_or_default();
+            }
+        }
+        Ok(())
+    }
 }
 
 impl WindowTrait for WindowImpl {
@@ -136,6 +467,10 @@ impl WindowTrait for WindowImpl {
         match self {
             Self::TumblingTimeBased(w) => w.on_event(data, ingest_ns, origin_uri),
             Self::TumblingCountBased(w) => w.on_event(data, ingest_ns, origin_uri),
+            Self::SlidingTimeBased(w) => w.on_event(data, ingest_ns, origin_uri),
+            Self::SlidingCountBased(w) => w.on_event(data, ingest_ns, origin_uri),
+            Self::Session(w) => w.on_event(data, ingest_ns, origin_uri),
+            Self::TumblingEventTimeBased(w) => w.on_event(data, ingest_ns, origin_uri),
         }
     }
 
@@ -143,6 +478,21 @@ impl WindowTrait for WindowImpl {
         match self {
             Self::TumblingTimeBased(w) => w.on_tick(ns),
             Self::TumblingCountBased(w) => w.on_tick(ns),
+            Self::SlidingTimeBased(w) => w.on_tick(ns),
+            Self::SlidingCountBased(w) => w.on_tick(ns),
+            Self::Session(w) => w.on_tick(ns),
+            Self::TumblingEventTimeBased(w) => w.on_tick(ns),
+        }
+    }
+
+    fn on_watermark(&mut self, watermark: u64) -> Result<WindowEvent> {
+        match self {
+            Self::TumblingTimeBased(w) => w.on_watermark(watermark),
+            Self::TumblingCountBased(w) => w.on_watermark(watermark),
+            Self::SlidingTimeBased(w) => w.on_watermark(watermark),
+            Self::SlidingCountBased(w) => w.on_watermark(watermark),
+            Self::Session(w) => w.on_watermark(watermark),
+            Self::TumblingEventTimeBased(w) => w.on_watermark(watermark),
         }
     }
 
@@ -150,12 +500,30 @@ impl WindowTrait for WindowImpl {
         match self {
             Self::TumblingTimeBased(w) => w.eviction_ns(),
             Self::TumblingCountBased(w) => w.eviction_ns(),
+            Self::SlidingTimeBased(w) => w.eviction_ns(),
+            Self::SlidingCountBased(w) => w.eviction_ns(),
+            Self::Session(w) => w.eviction_ns(),
+            Self::TumblingEventTimeBased(w) => w.eviction_ns(),
         }
     }
     fn max_groups(&self) -> u64 {
         match self {
             Self::TumblingTimeBased(w) => w.max_groups(),
             Self::TumblingCountBased(w) => w.max_groups(),
+            Self::SlidingTimeBased(w) => w.max_groups(),
+            Self::SlidingCountBased(w) => w.max_groups(),
+            Self::Session(w) => w.max_groups(),
+            Self::TumblingEventTimeBased(w) => w.max_groups(),
+        }
+    }
+    fn retained_panes(&self) -> usize {
+        match self {
+            Self::TumblingTimeBased(w) => w.retained_panes(),
+            Self::TumblingCountBased(w) => w.retained_panes(),
+            Self::SlidingTimeBased(w) => w.retained_panes(),
+            Self::SlidingCountBased(w) => w.retained_panes(),
+            Self::Session(w) => w.retained_panes(),
+            Self::TumblingEventTimeBased(w) => w.retained_panes(),
         }
     }
 }
@@ -170,6 +538,26 @@ impl From<TumblingWindowOnTime> for WindowImpl {
         Self::TumblingTimeBased(w)
     }
 }
+impl From<SlidingWindowOnNumber> for WindowImpl {
+    fn from(w: SlidingWindowOnNumber) -> Self {
+        Self::SlidingCountBased(w)
+    }
+}
+impl From<SlidingWindowOnTime> for WindowImpl {
+    fn from(w: SlidingWindowOnTime) -> Self {
+        Self::SlidingTimeBased(w)
+    }
+}
+impl From<SessionWindow> for WindowImpl {
+    fn from(w: SessionWindow) -> Self {
+        Self::Session(w)
+    }
+}
+impl From<TumblingWindowOnEventTime> for WindowImpl {
+    fn from(w: TumblingWindowOnEventTime) -> Self {
+        Self::TumblingEventTimeBased(w)
+    }
+}
 
 #[derive(Debug, PartialEq, Default)]
 pub struct WindowEvent {
@@ -217,6 +605,32 @@ impl WindowTrait for NoWindow {
     }
 }
 
+/// evaluate the script of a data based window against the given event
+/// returning the timestamp or count the window should use
+fn run_window_script(
+    script: &tremor_script::ast::Script<'static>,
+    data: &ValueAndMeta,
+    ingest_ns: u64,
+    origin_uri: &Option<EventOriginUri>,
+) -> Result<u64> {
+    // TODO avoid origin_uri clone here
+    let context = EventContext::new(ingest_ns, origin_uri.clone());
+    let (unwind_event, event_meta) = data.parts();
+    let value = script.run_imut(
+        &context,
+        AggrType::Emit,
+        &unwind_event,  // event
+        &Value::null(), // state for the window
+        &event_meta,    // $
+    )?;
+    let data = match value {
+        Return::Emit { value, .. } => value.as_u64(),
+        Return::EmitEvent { .. } => unwind_event.as_u64(),
+        Return::Drop { .. } => None,
+    };
+    data.ok_or_else(|| Error::from("Data based window didn't provide a valid value"))
+}
+
 #[derive(Default, Debug, Clone)]
 pub struct TumblingWindowOnTime {
     next_window: Option<u64>,
@@ -290,24 +704,7 @@ impl WindowTrait for TumblingWindowOnTime {
             .script
             .as_ref()
             .and_then(|script| script.script.as_ref())
-            .map(|script| {
-                // TODO avoid origin_uri clone here
-                let context = EventContext::new(ingest_ns, origin_uri.clone());
-                let (unwind_event, event_meta) = data.parts();
-                let value = script.run_imut(
-                    &context,
-                    AggrType::Emit,
-                    &unwind_event,  // event
-                    &Value::null(), // state for the window
-                    &event_meta,    // $
-                )?;
-                let data = match value {
-                    Return::Emit { value, .. } => value.as_u64(),
-                    Return::EmitEvent { .. } => unwind_event.as_u64(),
-                    Return::Drop { .. } => None,
-                };
-                data.ok_or_else(|| Error::from("Data based window didn't provide a valid value"))
-            })
+            .map(|script| run_window_script(script, data, ingest_ns, origin_uri))
             .unwrap_or(Ok(ingest_ns))?;
         Ok(self.get_window_event(time))
     }
@@ -367,22 +764,7 @@ impl WindowTrait for TumblingWindowOnNumber {
             .as_ref()
             .and_then(|script| script.script.as_ref())
             .map_or(Ok(1), |script| {
-                // TODO avoid origin_uri clone here
-                let context = EventContext::new(ingest_ns, origin_uri.clone());
-                let (unwind_event, event_meta) = data.parts();
-                let value = script.run_imut(
-                    &context,
-                    AggrType::Emit,
-                    &unwind_event,  // event
-                    &Value::null(), // state for the window
-                    &event_meta,    // $
-                )?;
-                let data = match value {
-                    Return::Emit { value, .. } => value.as_u64(),
-                    Return::EmitEvent { .. } => unwind_event.as_u64(),
-                    Return::Drop { .. } => None,
-                };
-                data.ok_or_else(|| Error::from("Data based window didn't provide a valid value"))
+                run_window_script(script, data, ingest_ns, origin_uri)
             })?;
 
         // If we're above count we emit and  set the new count to 1
@@ -399,558 +781,1713 @@ impl WindowTrait for TumblingWindowOnNumber {
     }
 }
 
-const NO_AGGRS: [InvokeAggrFn<'static>; 0] = [];
+/// A sliding window over time, covering the last `interval` nanoseconds and emitting every `hop` nanoseconds.
+///
+/// The window is split up into `interval / hop` panes, each pane is aggregated separately
+/// and all panes within the window are merged when it is emitted.
+#[derive(Default, Debug, Clone)]
+pub struct SlidingWindowOnTime {
+    next_window: Option<u64>,
+    emit_empty_windows: bool,
+    max_groups: u64,
+    /// number of events in the currently open pane
+    events: u64,
+    /// number of events in the closed panes that are still part of the window, oldest first
+    pane_events: VecDeque<u64>,
+    /// number of panes making up a full window
+    panes: usize,
+    hop: u64,
+    ttl: Option<u64>,
+    script: Option<WindowDecl<'static>>,
+}
 
-impl TrickleSelect {
-    pub fn with_stmt(
-        operator_uid: u64,
-        id: String,
-        dims: &Groups,
-        windows: Vec<(String, WindowImpl)>,
-        stmt: &srs::Stmt,
-    ) -> Result<Self> {
-        let windows = windows
-            .into_iter()
-            .map(|(fqwn, window_impl)| Window {
-                dims: dims.clone(),
-                last_dims: dims.clone(),
-                module: Window::module_path(&fqwn),
-                name: Window::ident_name(&fqwn).to_string(),
-                window_impl,
-                next_swap: 0,
-            })
-            .collect();
-        let select = srs::Select::try_new_from_stmt(stmt)?;
-        Ok(Self {
-            id,
-            windows,
-            select,
-            event_id_gen: EventIdGenerator::new(operator_uid),
-        })
+impl SlidingWindowOnTime {
+    pub fn from_stmt(
+        interval: u64,
+        hop: u64,
+        emit_empty_windows: bool,
+        max_groups: u64,
+        ttl: Option<u64>,
+        script: Option<&WindowDecl>,
+    ) -> Self {
+        let script = script.cloned().map(WindowDecl::into_static);
+        let panes = usize::try_from(interval / hop).unwrap_or(usize::MAX);
+        Self {
+            next_window: None,
+            emit_empty_windows,
+            max_groups,
+            events: 0,
+            pane_events: VecDeque::with_capacity(panes),
+            panes,
+            hop,
+            ttl,
+            script,
+        }
     }
-    fn opts() -> ExecOpts {
-        ExecOpts {
-            result_needed: true,
-            aggr: AggrType::Emit,
+
+    fn get_window_event(&mut self, time: u64) -> WindowEvent {
+        match self.next_window {
+            None => {
+                self.next_window = Some(time + self.hop);
+                WindowEvent {
+                    opened: true,
+                    include: false,
+                    emit: false,
+                }
+            }
+            Some(next_window) if next_window <= time => {
+                let emit = self.events > 0
+                    || self.pane_events.iter().any(|events| *events > 0)
+                    || self.emit_empty_windows;
+                // panes we skipped without any event or tick, they move older panes out of the window
+                let skipped =
+                    usize::try_from((time - next_window) / self.hop).unwrap_or(usize::MAX);
+                self.pane_events.push_back(self.events);
+                let retained = self.panes.saturating_sub(1).saturating_sub(skipped);
+                while self.pane_events.len() > retained {
+                    self.pane_events.pop_front();
+                }
+                self.next_window = Some(time + self.hop);
+                self.events = 0;
+                WindowEvent {
+                    opened: true,   // this event has been put into the newly opened pane
+                    include: false, // event is beyond the current window, put it into the next
+                    emit,           // only emit if we had any events in this window
+                }
+            }
+            Some(_) => WindowEvent::all_false(),
         }
     }
 }
 
-/// execute the select clause of the statement and filter results by having clause, if provided
-#[allow(clippy::too_many_arguments)]
-fn execute_select_and_having(
-    stmt: &Select,
-    node_meta: &NodeMetas,
-    opts: ExecOpts,
-    local_stack: &LocalStack,
-    state: &Value<'static>,
-    env: &Env,
-    event_id: EventId,
-    data: &ValueAndMeta,
-    ingest_ns: u64,
-    op_meta: &OpMeta,
-    origin_uri: Option<EventOriginUri>,
-    transactional: bool,
-) -> Result<Option<(Cow<'static, str>, Event)>> {
-    let (event_payload, event_meta) = data.parts();
-
-    let value = stmt
-        .target
-        .run(opts, env, event_payload, state, event_meta, local_stack)?;
+impl WindowTrait for SlidingWindowOnTime {
+    fn eviction_ns(&self) -> Option<u64> {
+        self.ttl
+    }
+    fn max_groups(&self) -> u64 {
+        self.max_groups
+    }
+    fn retained_panes(&self) -> usize {
+        self.pane_events.len()
+    }
+    fn on_event(
+        &mut self,
+        data: &ValueAndMeta,
+        ingest_ns: u64,
+        origin_uri: &Option<EventOriginUri>,
+    ) -> Result<WindowEvent> {
+        let time = self
+            .script
+            .as_ref()
+            .and_then(|script| script.script.as_ref())
+            .map(|script| run_window_script(script, data, ingest_ns, origin_uri))
+            .unwrap_or(Ok(ingest_ns))?;
+        let window_event = self.get_window_event(time);
+        // the event always ends up in the currently open pane
+        self.events += 1;
+        Ok(window_event)
+    }
 
-    // check having clause
-    let result = value.into_owned();
-    if let Some(guard) = &stmt.maybe_having {
-        let test = guard.run(opts, env, &result, state, &NULL, local_stack)?;
-        if let Some(test) = test.as_bool() {
-            if !test {
-                return Ok(None);
-            }
+    fn on_tick(&mut self, ns: u64) -> Result<WindowEvent> {
+        if self.script.is_none() {
+            Ok(self.get_window_event(ns))
         } else {
-            let s: &Select = &stmt;
-            return Err(tremor_script::errors::query_guard_not_bool_err(
-                s, guard, &test, node_meta,
-            )
-            .into());
+            // we basically ignore ticks when we have a script with a custom timestamp
+            Ok(WindowEvent::all_false())
         }
     }
-    Ok(Some((
-        OUT,
-        Event {
-            id: event_id,
-            ingest_ns,
-            origin_uri,
-            // TODO: this will ignore op_metas from all other events this one is based upon and might break operators requiring this
-            op_meta: op_meta.clone(),
-            is_batch: false,
-            data: (result.into_static(), event_meta.clone_static()).into(),
-            transactional,
-            ..Event::default()
-        },
-    )))
 }
 
-/// accumulate the given `event` into the current `group`s aggregates
-#[allow(clippy::too_many_arguments)] // this is the price for no transmutation
-fn accumulate(
-    opts: ExecOpts,
-    node_meta: &NodeMetas,
-    env: &Env,
-    local_stack: &LocalStack,
-    state: &mut Value<'static>,
-    group: &mut GroupData,
-    data: &ValueAndMeta,
-    id: &EventId,
-    transactional: bool,
-) -> Result<()> {
-    // we incorporate the event into this group below, so track it here
-    group.id.track(&id);
+/// A sliding window over the last `size` events, emitting every `hop` events.
+///
+/// The window is split up into `size / hop` panes, each pane is aggregated separately
+/// and all panes within the window are merged when it is emitted.
+#[derive(Default, Debug, Clone)]
+pub struct SlidingWindowOnNumber {
+    count: u64,
+    max_groups: u64,
+    /// number of closed panes that are part of the window
+    closed: usize,
+    /// number of panes making up a full window
+    panes: usize,
+    hop: u64,
+    ttl: Option<u64>,
+    script: Option<WindowDecl<'static>>,
+}
 
-    // track transactional state for the given event
-    group.transactional = group.transactional || transactional;
+impl SlidingWindowOnNumber {
+    pub fn from_stmt(
+        size: u64,
+        hop: u64,
+        max_groups: u64,
+        ttl: Option<u64>,
+        script: Option<&WindowDecl>,
+    ) -> Self {
+        let script = script.cloned().map(WindowDecl::into_static);
 
-    let (event_data, event_meta) = data.parts();
-    for aggr in &mut group.aggrs {
-        let invocable = &mut aggr.invocable;
-        let mut argv: Vec<SCow<Value>> = Vec::with_capacity(aggr.args.len());
-        let mut argv1: Vec<&Value> = Vec::with_capacity(aggr.args.len());
-        for arg in &aggr.args {
-            let result = arg.run(opts, env, event_data, state, event_meta, &local_stack)?;
-            argv.push(result);
-        }
-        for arg in &argv {
-            argv1.push(arg);
+        Self {
+            count: 0,
+            max_groups,
+            closed: 0,
+            panes: usize::try_from(size / hop).unwrap_or(usize::MAX),
+            hop,
+            ttl,
+            script,
         }
-
-        invocable.accumulate(argv1.as_slice()).map_err(|e| {
-            // TODO nice error
-            let r: Option<&Registry> = None;
-            e.into_err(aggr, aggr, r, node_meta)
-        })?;
     }
-    Ok(())
 }
 
-/// get a mutable reference to the group of this window identified by `group_str` and `group_value`
-fn get_or_create_group<'window>(
-    window: &'window mut Window,
-    idgen: &mut EventIdGenerator,
-    aggregates: &[InvokeAggrFn<'static>],
-    group_str: &str,
-    group_value: &Value,
-) -> Result<&'window mut GroupData> {
-    let this_groups = &mut window.dims;
-    let groups_len = this_groups.len() as u64;
-    let last_groups = &mut window.last_dims;
-    let window_impl = &window.window_impl;
-    let (_, this_group) = match this_groups.raw_entry_mut().from_key(group_str) {
-        // avoid double-clojure
-        RawEntryMut::Occupied(e) => e.into_key_value(),
-        RawEntryMut::Vacant(e) if groups_len < window.window_impl.max_groups() => {
-            let k = group_str.to_string();
-            let aggrs = aggregates.to_vec();
-            let v = last_groups.remove(group_str).unwrap_or_else(|| {
-                GroupData {
-                    window: window_impl.clone(),
-                    aggrs,
-                    group: group_value.clone_static(),
-                    id: idgen.next_id(), // after all this is a new event
-                    transactional: false,
-                }
-            });
-            e.insert(k, v)
+impl WindowTrait for SlidingWindowOnNumber {
+    fn eviction_ns(&self) -> Option<u64> {
+        self.ttl
+    }
+    fn max_groups(&self) -> u64 {
+        self.max_groups
+    }
+    fn retained_panes(&self) -> usize {
+        self.closed
+    }
+    fn on_event(
+        &mut self,
+        data: &ValueAndMeta,
+        ingest_ns: u64,
+        origin_uri: &Option<EventOriginUri>,
+    ) -> Result<WindowEvent> {
+        let count = self
+            .script
+            .as_ref()
+            .and_then(|script| script.script.as_ref())
+            .map_or(Ok(1), |script| {
+                run_window_script(script, data, ingest_ns, origin_uri)
+            })?;
+
+        // every `hop` events we close the current pane and emit the whole window
+        let new_count = self.count + count;
+        if new_count >= self.hop {
+            self.count = new_count - self.hop;
+            self.closed = (self.closed + 1).min(self.panes.saturating_sub(1));
+            // we can emit now, including this event
+            Ok(WindowEvent::all_true())
+        } else {
+            self.count = new_count;
+            Ok(WindowEvent::all_false())
         }
-        RawEntryMut::Vacant(_) => {
-            return Err(max_groups_reached(
-                window.window_impl.max_groups(),
-                group_str,
-            ))
+    }
+}
+
+/// A session window, closing after no event has been received for `gap` nanoseconds
+/// or, if given, after the session lasted for `max_duration` nanoseconds.
+///
+/// Each group tracks its own session.
+#[derive(Default, Debug, Clone)]
+pub struct SessionWindow {
+    /// start of the current session, `None` if there is no open session
+    session_start: Option<u64>,
+    /// timestamp of the last event in the current session
+    last_event: u64,
+    /// ingest time of the last event in the current session
+    last_ingest: u64,
+    max_groups: u64,
+    gap: u64,
+    max_duration: Option<u64>,
+    ttl: Option<u64>,
+    script: Option<WindowDecl<'static>>,
+}
+
+impl SessionWindow {
+    pub fn from_stmt(
+        gap: u64,
+        max_duration: Option<u64>,
+        max_groups: u64,
+        ttl: Option<u64>,
+        script: Option<&WindowDecl>,
+    ) -> Self {
+        let script = script.cloned().map(WindowDecl::into_static);
+        Self {
+            session_start: None,
+            last_event: 0,
+            last_ingest: 0,
+            max_groups,
+            gap,
+            max_duration,
+            ttl,
+            script,
         }
-    };
-    Ok(this_group)
+    }
+
+    /// checks if the current session, started at `session_start`, is over at `time`
+    fn is_expired(&self, session_start: u64, time: u64) -> bool {
+        time.saturating_sub(self.last_event) >= self.gap
+            || self.max_duration.map_or(false, |max_duration| {
+                time.saturating_sub(session_start) >= max_duration
+            })
+    }
 }
 
-impl Operator for TrickleSelect {
-    #[allow(clippy::too_many_lines)]
+impl WindowTrait for SessionWindow {
+    fn eviction_ns(&self) -> Option<u64> {
+        self.ttl
+    }
+    fn max_groups(&self) -> u64 {
+        self.max_groups
+    }
     fn on_event(
         &mut self,
-        _uid: u64,
-        _port: &str,
-        state: &mut Value<'static>,
-        mut event: Event,
-    ) -> Result<EventAndInsights> {
-        /// Simple enum to decide what we return
-        enum Res {
-            Event,
-            None,
-            Data(EventAndInsights),
+        data: &ValueAndMeta,
+        ingest_ns: u64,
+        origin_uri: &Option<EventOriginUri>,
+    ) -> Result<WindowEvent> {
+        let time = self
+            .script
+            .as_ref()
+            .and_then(|script| script.script.as_ref())
+            .map(|script| run_window_script(script, data, ingest_ns, origin_uri))
+            .unwrap_or(Ok(ingest_ns))?;
+        self.last_ingest = ingest_ns;
+        match self.session_start {
+            Some(session_start) if !self.is_expired(session_start, time) => {
+                // out of order events must not shorten the session
+                self.last_event = self.last_event.max(time);
+                Ok(WindowEvent::all_false())
+            }
+            session_start => {
+                self.session_start = Some(time);
+                self.last_event = time;
+                Ok(WindowEvent {
+                    opened: true,                  // this event starts a new session
+                    include: false,                // event is not part of the previous session
+                    emit: session_start.is_some(), // only emit if we had a session before
+                })
+            }
         }
+    }
 
-        let Self {
-            select,
-            windows,
-            event_id_gen,
-            ..
-        } = self;
+    fn on_tick(&mut self, ns: u64) -> Result<WindowEvent> {
+        let expired = match self.session_start {
+            Some(session_start) if self.script.is_none() => self.is_expired(session_start, ns),
+            // ticks are in ingest time, while a script keeps the session in its own time,
+            // so we close sessions that didn't see an event for `gap` nanoseconds of ingest time
+            Some(_) => ns.saturating_sub(self.last_ingest) >= self.gap,
+            None => false,
+        };
+        if expired {
+            self.session_start = None;
+            Ok(WindowEvent {
+                opened: false,
+                include: false,
+                emit: true,
+            })
+        } else {
+            Ok(WindowEvent::all_false())
+        }
+    }
+}
 
-        let Event {
-            ingest_ns,
-            ref mut data,
-            ref id,
-            ref origin_uri,
-            ref op_meta,
-            transactional,
-            ..
-        } = event;
+/// Watermark configuration of an event time window
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct WatermarkSettings {
+    /// bounded out-of-orderness, the watermark trails the latest event time
+    /// of each source by this many nanoseconds
+    pub delay: u64,
+    /// nanoseconds of event time a window is kept around after it has been emitted
+    /// to incorporate late events
+    pub allowed_lateness: u64,
+    /// sources that didn't send an event for this many nanoseconds
+    /// no longer hold back the watermark
+    pub idle_timeout: Option<u64>,
+}
 
-        // TODO avoid origin_uri clone here
-        let ctx = EventContext::new(ingest_ns, origin_uri.clone());
+/// Event time progress of a select with an event time window
+#[derive(Debug, Clone, Default)]
+pub struct Watermark {
+    settings: WatermarkSettings,
+    /// maximum event time and ingest time of the last event for each source stream
+    sources: HashMap<(u64, u64), (u64, u64)>,
+    /// latest watermark and its ingest time for each upstream operator sending us watermarks
+    upstream: HashMap<(u64, u64), (u64, u64)>,
+    /// the watermark derived from the event times of the events we saw ourselves
+    local: Option<u64>,
+    current: Option<u64>,
+}
 
-        let opts = Self::opts();
+impl Watermark {
+    fn new(settings: WatermarkSettings) -> Self {
+        Self {
+            settings,
+            sources: HashMap::new(),
+            upstream: HashMap::new(),
+            local: None,
+            current: None,
+        }
+    }
 
-        let res = data.apply_select(select, |data, stmt| -> Result<Res> {
+    /// the current watermark, `None` if we didn't see any event time yet
+    pub fn current(&self) -> Option<u64> {
+        self.current
+    }
 
-            let SelectStmt {
-                stmt: ref select,
-                ref mut aggregates,
-                ref mut aggregate_scratches,
-                ref mut consts,
-                ref locals,
-                ref node_meta,
-            } = stmt;
+    fn is_active(&self, last_seen: u64, ns: u64) -> bool {
+        self.settings.idle_timeout.map_or(true, |idle_timeout| {
+            ns.saturating_sub(last_seen) < idle_timeout
+        })
+    }
 
-            consts.window = Value::null();
-            consts.group = Value::null();
-            consts.args = Value::null();
+    /// track the event time of an event from the source stream `source`
+    /// returns the new local watermark if it advanced
+    fn on_event(&mut self, source: (u64, u64), event_time: u64, ingest_ns: u64) -> Option<u64> {
+        if let Some((max_event_time, last_seen)) = self.sources.get_mut(&source) {
+            *max_event_time = (*max_event_time).max(event_time);
+            *last_seen = ingest_ns;
+        } else {
+            self.sources.insert(source, (event_time, ingest_ns));
+        }
+        self.advance(ingest_ns)
+    }
 
-            let select: &Select = select;
+    /// sources might have become idle since the last event
+    /// returns the local watermark if the current watermark needs to advance
+    fn on_tick(&mut self, ns: u64) -> Option<u64> {
+        // upstream operators might have become idle as well
+        self.advance(ns)
+            .or_else(|| match (self.effective(ns), self.current) {
+                (Some(effective), Some(current)) if effective > current => self.local,
+                (Some(_), None) => self.local,
+                _ => None,
+            })
+    }
 
-            let Select {
-                target,
-                maybe_where,
-                maybe_having,
-                maybe_group_by,
-                ..
-            } = select;
+    /// incorporate a watermark signal, sent by the operator `origin`, `own` ones carry
+    /// our local watermark. Unrelated upstream operators might progress at different
+    /// speeds, so the slowest of them holds back the watermark.
+    ///
+    /// Returns the current watermark
+    fn observe(&mut self, origin: (u64, u64), own: bool, watermark: u64, ns: u64) -> u64 {
+        if !own {
+            let (upstream, last_seen) = self.upstream.entry(origin).or_insert((watermark, ns));
+            *upstream = (*upstream).max(watermark);
+            *last_seen = ns;
+        }
+        let effective = self.effective(ns).unwrap_or(watermark);
+        let current = self
+            .current
+            .map_or(effective, |current| current.max(effective));
+        self.current = Some(current);
+        current
+    }
 
-            let local_stack = tremor_script::interpreter::LocalStack::with_size(*locals);
+    /// the minimum of the local watermark and the watermarks of all active upstream operators
+    fn effective(&self, ns: u64) -> Option<u64> {
+        self.upstream
+            .values()
+            .filter(|(_, last_seen)| self.is_active(*last_seen, ns))
+            .map(|(watermark, _)| *watermark)
+            .chain(self.local)
+            .min()
+    }
 
-            //
-            // Before any select processing, we filter by where clause
-            //
-            if let Some(guard) = maybe_where {
-                let (unwind_event, event_meta) = data.parts();
-                let env = Env {
-                    context: &ctx,
-                    consts: consts.run(),
-                    aggrs: &NO_AGGRS,
-                    meta: &node_meta,
-                    recursion_limit: tremor_script::recursion_limit(),
-                };
-                let test = guard.run(opts, &env, unwind_event, state, event_meta, &local_stack)?;
-                if let Some(test) = test.as_bool() {
-                    if !test {
-                        return Ok(Res::None);
-                    };
-                } else {
-                    let s: &Select = &select;
-                    return Err(tremor_script::errors::query_guard_not_bool_err(
-                        s, guard, &test, &node_meta,
-                    )
-                    .into());
-                };
+    fn advance(&mut self, ns: u64) -> Option<u64> {
+        let slowest_active = self
+            .sources
+            .values()
+            .filter(|(_, last_seen)| self.is_active(*last_seen, ns))
+            .map(|(max_event_time, _)| *max_event_time)
+            .min();
+        // if all sources are idle, none of them holds back the watermark
+        let candidate = slowest_active
+            .or_else(|| {
+                self.sources
+                    .values()
+                    .map(|(max_event_time, _)| *max_event_time)
+                    .max()
+            })?
+            .saturating_sub(self.settings.delay);
+        match self.local {
+            Some(local) if local >= candidate => None,
+            _ => {
+                self.local = Some(candidate);
+                Some(candidate)
             }
+        }
+    }
+}
 
-            let mut events = vec![];
+/// A tumbling window on event time, emitted once the watermark passed its end
+/// instead of on the first event of the next window.
+///
+/// Each group keeps a window for every interval it received events for,
+/// so multiple windows of a group can be open at the same time.
+#[derive(Default, Debug, Clone)]
+pub struct TumblingWindowOnEventTime {
+    /// start of the interval this window covers, `None` until it received an event
+    start: Option<u64>,
+    /// the window has been emitted, further events are late
+    fired: bool,
+    max_groups: u64,
+    interval: u64,
+    ttl: Option<u64>,
+    settings: WatermarkSettings,
+    /// emit windows for intervals of a group the watermark passed without any events
+    emit_empty_windows: bool,
+    script: WindowDecl<'static>,
+}
 
-            let mut group_values =  if let Some(group_by) = &maybe_group_by {
-                group_by.generate_groups(&ctx, data.value(), state, &node_meta, data.meta())?
-            } else {
-                //
-                // select without group by or windows
-                // event stays the same, only the value might change based on select clause
-                // and we might drop it altogether based on having clause.
-                //
-                if windows.is_empty() {
-                    let group_value = Value::from(vec![()]);
-                    let group_str = sorted_serialize(&group_value)?;
+impl TumblingWindowOnEventTime {
+    pub fn from_stmt(
+        interval: u64,
+        settings: WatermarkSettings,
+        emit_empty_windows: bool,
+        max_groups: u64,
+        ttl: Option<u64>,
+        script: &WindowDecl,
+    ) -> Self {
+        Self {
+            start: None,
+            fired: false,
+            max_groups,
+            interval,
+            ttl,
+            settings,
+            emit_empty_windows,
+            script: script.clone().into_static(),
+        }
+    }
 
-                    consts.group = group_value.clone_static();
-                    consts.group.push(group_str)?;
+    /// evaluate the event time of the given event
+    pub fn event_time(
+        &self,
+        data: &ValueAndMeta,
+        ingest_ns: u64,
+        origin_uri: &Option<EventOriginUri>,
+    ) -> Result<u64> {
+        self.script
+            .script
+            .as_ref()
+            .map(|script| run_window_script(script, data, ingest_ns, origin_uri))
+            .unwrap_or(Ok(ingest_ns))
+    }
 
-                    let (unwind_event, event_meta): (&mut Value, &mut Value) = data.parts_mut();
+    /// start of the interval containing `event_time`
+    pub fn start_of(&self, event_time: u64) -> u64 {
+        event_time - event_time % self.interval
+    }
 
-                    let env = Env {
-                        context: &ctx,
-                        consts: consts.run(),
-                        aggrs: &NO_AGGRS,
-                        meta: &node_meta,
-                        recursion_limit: tremor_script::recursion_limit(),
-                    };
-                    let value =
-                        target
-                            .run(opts, &env, unwind_event, state, event_meta, &local_stack)?;
+    /// checks if the window starting at `start` no longer accepts events at `watermark`
+    pub fn is_retired_at(&self, start: u64, watermark: u64) -> bool {
+        start
+            .saturating_add(self.interval)
+            .saturating_add(self.settings.allowed_lateness)
+            <= watermark
+    }
+}
 
-                    let result = value.into_owned();
+impl WindowTrait for TumblingWindowOnEventTime {
+    fn eviction_ns(&self) -> Option<u64> {
+        self.ttl
+    }
+    fn max_groups(&self) -> u64 {
+        self.max_groups
+    }
+    fn on_event(
+        &mut self,
+        data: &ValueAndMeta,
+        ingest_ns: u64,
+        origin_uri: &Option<EventOriginUri>,
+    ) -> Result<WindowEvent> {
+        if self.start.is_none() {
+            self.start = Some(self.start_of(self.event_time(data, ingest_ns, origin_uri)?));
+        }
+        if self.fired {
+            // late event within the allowed lateness, emit it right away
+            Ok(WindowEvent {
+                opened: false,
+                include: true,
+                emit: true,
+            })
+        } else {
+            Ok(WindowEvent::all_false())
+        }
+    }
 
-                    // evaluate having clause, if one exists
-                    #[allow(clippy::option_if_let_else)] // The borrow checker prevents map_or
-                    return if let Some(guard) = maybe_having {
-                        let test = guard.run(opts, &env, &result, state, &NULL, &local_stack)?;
-                        #[allow(clippy::option_if_let_else)] // The borrow checker prevents map_or
-                        if let Some(test) = test.as_bool() {
-                            if test {
-                                *unwind_event = result;
-                                Ok(Res::Event)
-                            } else {
-                                Ok(Res::None)
-                            }
-                        } else {
-                            Err(tremor_script::errors::query_guard_not_bool_err(
-                                select, guard, &test, &node_meta,
-                            ).into())
-                        }
-                    } else {
-                        *unwind_event = result;
-                        Ok(Res::Event)
-                    }
-                };
-                vec![]
-            };
+    fn on_watermark(&mut self, watermark: u64) -> Result<WindowEvent> {
+        match self.start {
+            Some(start) if !self.fired && start.saturating_add(self.interval) <= watermark => {
+                self.fired = true;
+                Ok(WindowEvent {
+                    opened: false,
+                    include: false,
+                    emit: true,
+                })
+            }
+            _ => Ok(WindowEvent::all_false()),
+        }
+    }
+}
 
-            if group_values.is_empty() {
-                group_values.push(vec![Value::null()])
-            };
+const NO_AGGRS: [InvokeAggrFn<'static>; 0] = [];
 
-            // Handle eviction
-            // retire the group data that didnt receive an event in `eviction_ns()` nanoseconds
-            // if no event came after `2 * eviction_ns()` this group is finally cleared out
-            for window in windows.iter_mut() {
-                if let Some(eviction_ns) = window.window_impl.eviction_ns() {
-                    if window.next_swap < ingest_ns {
-                        window.next_swap = ingest_ns + eviction_ns;
-                        window.last_dims.clear();
-                        std::mem::swap(&mut window.dims, &mut window.last_dims);
-                    }
-                }
+impl TrickleSelect {
+    pub fn with_stmt(
+        operator_uid: u64,
+        id: String,
+        dims: &Groups,
+        windows: Vec<(String, WindowImpl)>,
+        stmt: &srs::Stmt,
+    ) -> Result<Self> {
+        // sliding windows can only be the last window in a tilt frame
+        // as their emitted aggregates overlap
+        if let Some((fqwn, _)) = windows
+            .iter()
+            .rev()
+            .skip(1)
+            .find(|(_, window_impl)| window_impl.is_sliding())
+        {
+            return Err(format!(
+                "Sliding window `{}` can only be used as the last window of a select",
+                fqwn
+            )
+            .into());
+        }
+        // event time windows have their own notion of time that can't be shared in a tilt frame
+        if windows.len() > 1 {
+            if let Some((fqwn, _)) = windows
+                .iter()
+                .find(|(_, window_impl)| window_impl.as_event_time().is_some())
+            {
+                return Err(format!(
+                    "Event time window `{}` can only be used as the only window of a select",
+                    fqwn
+                )
+                .into());
             }
+        }
+        let windows = windows
+            .into_iter()
+            .map(|(fqwn, window_impl)| Window {
+                dims: dims.clone(),
+                last_dims: dims.clone(),
+                module: Window::module_path(&fqwn),
+                name: Window::ident_name(&fqwn).to_string(),
+                watermark: window_impl
+                    .as_event_time()
+                    .map(|w| Watermark::new(w.settings)),
+                next_intervals: HashMap::new(),
+                window_impl,
+                next_swap: 0,
+            })
+            .collect();
+        let select = srs::Select::try_new_from_stmt(stmt)?;
+        Ok(Self {
+            id,
+            windows,
+            select,
+            event_id_gen: EventIdGenerator::new(operator_uid),
+        })
+    }
+    fn opts() -> ExecOpts {
+        ExecOpts {
+            result_needed: true,
+            aggr: AggrType::Emit,
+        }
+    }
+}
 
-            let group_values: Vec<Value> = group_values.into_iter().map(Value::Array).collect();
+/// execute the select clause of the statement and filter results by having clause, if provided
+#[allow(clippy::too_many_arguments)]
+fn execute_select_and_having(
+    stmt: &Select,
+    node_meta: &NodeMetas,
+    opts: ExecOpts,
+    local_stack: &LocalStack,
+    state: &Value<'static>,
+    env: &Env,
+    event_id: EventId,
+    data: &ValueAndMeta,
+    ingest_ns: u64,
+    op_meta: &OpMeta,
+    origin_uri: Option<EventOriginUri>,
+    transactional: bool,
+) -> Result<Option<(Cow<'static, str>, Event)>> {
+    let (event_payload, event_meta) = data.parts();
 
-            let scratches = aggregate_scratches.as_mut();
+    let value = stmt
+        .target
+        .run(opts, env, event_payload, state, event_meta, local_stack)?;
 
-            for group_value in group_values {
-                let group_str = sorted_serialize(&group_value)?;
+    // check having clause
+    let result = value.into_owned();
+    if let Some(guard) = &stmt.maybe_having {
+        let test = guard.run(opts, env, &result, state, &NULL, local_stack)?;
+        if let Some(test) = test.as_bool() {
+            if !test {
+                return Ok(None);
+            }
+        } else {
+            let s: &Select = &stmt;
+            return Err(tremor_script::errors::query_guard_not_bool_err(
+                s, guard, &test, node_meta,
+            )
+            .into());
+        }
+    }
+    Ok(Some((
+        OUT,
+        Event {
+            id: event_id,
+            ingest_ns,
+            origin_uri,
+            // TODO: this will ignore op_metas from all other events this one is based upon and might break operators requiring this
+            op_meta: op_meta.clone(),
+            is_batch: false,
+            data: (result.into_static(), event_meta.clone_static()).into(),
+            transactional,
+            ..Event::default()
+        },
+    )))
+}
 
-                consts.group = group_value.clone_static();
-                consts.group.push(group_str.clone())?;
+/// accumulate the given `event` into the current `group`s aggregates
+#[allow(clippy::too_many_arguments)] // this is the price for no transmutation
+fn accumulate(
+    opts: ExecOpts,
+    node_meta: &NodeMetas,
+    env: &Env,
+    local_stack: &LocalStack,
+    state: &mut Value<'static>,
+    group: &mut GroupData,
+    data: &ValueAndMeta,
+    id: &EventId,
+    transactional: bool,
+) -> Result<()> {
+    // we incorporate the event into this group below, so track it here
+    group.id.track(&id);
 
-                match windows.len() {
-                    0 => {
-                        // group by without windows
-                        // otherwise we just pass it through the select portion of the statement
+    // track transactional state for the given event
+    group.transactional = group.transactional || transactional;
 
-                        // evaluate select clause
-                        let env = Env {
-                            context: &ctx,
-                            consts: consts.run(),
-                            aggrs: &NO_AGGRS,
+    let (event_data, event_meta) = data.parts();
+    for aggr in &mut group.aggrs {
+        let invocable = &mut aggr.invocable;
+        let mut argv: Vec<SCow<Value>> = Vec::with_capacity(aggr.args.len());
+        let mut argv1: Vec<&Value> = Vec::with_capacity(aggr.args.len());
+        for arg in &aggr.args {
+            let result = arg.run(opts, env, event_data, state, event_meta, &local_stack)?;
+            argv.push(result);
+        }
+        for arg in &argv {
+            argv1.push(arg);
+        }
+
+        invocable.accumulate(argv1.as_slice()).map_err(|e| {
+            // TODO nice error
+            let r: Option<&Registry> = None;
+            e.into_err(aggr, aggr, r, node_meta)
+        })?;
+    }
+    Ok(())
+}
+
+/// get a mutable reference to the group of this window identified by `group_str` and `group_value`
+fn get_or_create_group<'window>(
+    window: &'window mut Window,
+    idgen: &mut EventIdGenerator,
+    aggregates: &[InvokeAggrFn<'static>],
+    group_str: &str,
+    group_value: &Value,
+) -> Result<&'window mut GroupData> {
+    let this_groups = &mut window.dims;
+    let groups_len = this_groups.len() as u64;
+    let last_groups = &mut window.last_dims;
+    let window_impl = &window.window_impl;
+    let (_, this_group) = match this_groups.raw_entry_mut().from_key(group_str) {
+        // avoid double-clojure
+        RawEntryMut::Occupied(e) => e.into_key_value(),
+        RawEntryMut::Vacant(e) if groups_len < window.window_impl.max_groups() => {
+            let k = group_str.to_string();
+            let aggrs = aggregates.to_vec();
+            let v = last_groups.remove(group_str).unwrap_or_else(|| {
+                GroupData {
+                    window: window_impl.clone(),
+                    aggrs,
+                    group: group_value.clone_static(),
+                    id: idgen.next_id(), // after all this is a new event
+                    transactional: false,
+                    panes: VecDeque::new(),
+                }
+            });
+            e.insert(k, v)
+        }
+        RawEntryMut::Vacant(_) => {
+            return Err(max_groups_reached(
+                window.window_impl.max_groups(),
+                group_str,
+            ))
+        }
+    };
+    Ok(this_group)
+}
+
+/// opens a window for every interval of a group of an event time window emitting empty windows
+/// that didn't receive any events before the `watermark` passed it
+fn open_empty_windows(
+    window: &mut Window,
+    idgen: &mut EventIdGenerator,
+    aggregates: &[InvokeAggrFn<'static>],
+    watermark: u64,
+) -> Result<()> {
+    let interval = match window.window_impl.as_event_time() {
+        Some(w) if w.emit_empty_windows => w.interval,
+        _ => return Ok(()),
+    };
+    let mut empty = Vec::new();
+    for (group_str, (group_value, next)) in &mut window.next_intervals {
+        while next.saturating_add(interval) <= watermark {
+            let group_key = format!("{}@{}", group_str, next);
+            if !window.dims.contains_key(&group_key) && !window.last_dims.contains_key(&group_key) {
+                empty.push((group_key, group_value.clone(), *next));
+            }
+            *next += interval;
+        }
+    }
+    for (group_key, group_value, start) in empty {
+        stry!(get_or_create_group(
+            window,
+            idgen,
+            aggregates,
+            &group_key,
+            &group_value
+        ))
+        .window
+        .open_at(start);
+    }
+    Ok(())
+}
+
+impl Operator for TrickleSelect {
+    #[allow(clippy::too_many_lines)]
+    fn on_event(
+        &mut self,
+        uid: u64,
+        _port: &str,
+        state: &mut Value<'static>,
+        mut event: Event,
+    ) -> Result<EventAndInsights> {
+        /// Simple enum to decide what we return
+        enum Res {
+            Event,
+            None,
+            Data(EventAndInsights),
+            Late,
+        }
+
+        let Self {
+            select,
+            windows,
+            event_id_gen,
+            ..
+        } = self;
+
+        let Event {
+            ingest_ns,
+            ref mut data,
+            ref id,
+            ref origin_uri,
+            ref op_meta,
+            transactional,
+            ..
+        } = event;
+
+        // TODO avoid origin_uri clone here
+        let ctx = EventContext::new(ingest_ns, origin_uri.clone());
+
+        let opts = Self::opts();
+
+        let res = data.apply_select(select, |data, stmt| -> Result<Res> {
+
+            let SelectStmt {
+                stmt: ref select,
+                ref mut aggregates,
+                ref mut aggregate_scratches,
+                ref mut consts,
+                ref locals,
+                ref node_meta,
+            } = stmt;
+
+            consts.window = Value::null();
+            consts.group = Value::null();
+            consts.args = Value::null();
+
+            let select: &Select = select;
+
+            let Select {
+                target,
+                maybe_where,
+                maybe_having,
+                maybe_group_by,
+                ..
+            } = select;
+
+            let local_stack = tremor_script::interpreter::LocalStack::with_size(*locals);
+
+            //
+            // Before any select processing, we filter by where clause
+            //
+            if let Some(guard) = maybe_where {
+                let (unwind_event, event_meta) = data.parts();
+                let env = Env {
+                    context: &ctx,
+                    consts: consts.run(),
+                    aggrs: &NO_AGGRS,
+                    meta: &node_meta,
+                    recursion_limit: tremor_script::recursion_limit(),
+                };
+                let test = guard.run(opts, &env, unwind_event, state, event_meta, &local_stack)?;
+                if let Some(test) = test.as_bool() {
+                    if !test {
+                        return Ok(Res::None);
+                    };
+                } else {
+                    let s: &Select = &select;
+                    return Err(tremor_script::errors::query_guard_not_bool_err(
+                        s, guard, &test, &node_meta,
+                    )
+                    .into());
+                };
+            }
+
+            let mut events = vec![];
+
+            let mut group_values =  if let Some(group_by) = &maybe_group_by {
+                group_by.generate_groups(&ctx, data.value(), state, &node_meta, data.meta())?
+            } else {
+                //
+                // select without group by or windows
+                // event stays the same, only the value might change based on select clause
+                // and we might drop it altogether based on having clause.
+                //
+                if windows.is_empty() {
+                    let group_value = Value::from(vec![()]);
+                    let group_str = sorted_serialize(&group_value)?;
+
+                    consts.group = group_value.clone_static();
+                    consts.group.push(group_str)?;
+
+                    let (unwind_event, event_meta): (&mut Value, &mut Value) = data.parts_mut();
+
+                    let env = Env {
+                        context: &ctx,
+                        consts: consts.run(),
+                        aggrs: &NO_AGGRS,
+                        meta: &node_meta,
+                        recursion_limit: tremor_script::recursion_limit(),
+                    };
+                    let value =
+                        target
+                            .run(opts, &env, unwind_event, state, event_meta, &local_stack)?;
+
+                    let result = value.into_owned();
+
+                    // evaluate having clause, if one exists
+                    #[allow(clippy::option_if_let_else)] // The borrow checker prevents map_or
+                    return if let Some(guard) = maybe_having {
+                        let test = guard.run(opts, &env, &result, state, &NULL, &local_stack)?;
+                        #[allow(clippy::option_if_let_else)] // The borrow checker prevents map_or
+                        if let Some(test) = test.as_bool() {
+                            if test {
+                                *unwind_event = result;
+                                Ok(Res::Event)
+                            } else {
+                                Ok(Res::None)
+                            }
+                        } else {
+                            Err(tremor_script::errors::query_guard_not_bool_err(
+                                select, guard, &test, &node_meta,
+                            ).into())
+                        }
+                    } else {
+                        *unwind_event = result;
+                        Ok(Res::Event)
+                    }
+                };
+                vec![]
+            };
+
+            if group_values.is_empty() {
+                group_values.push(vec![Value::null()])
+            };
+
+            // Handle eviction
+            // retire the group data that didnt receive an event in `eviction_ns()` nanoseconds
+            // if no event came after `2 * eviction_ns()` this group is finally cleared out
+            for window in windows.iter_mut() {
+                if let Some(eviction_ns) = window.window_impl.eviction_ns() {
+                    if window.next_swap < ingest_ns {
+                        window.next_swap = ingest_ns + eviction_ns;
+                        window.last_dims.clear();
+                        std::mem::swap(&mut window.dims, &mut window.last_dims);
+                    }
+                }
+            }
+
+            let group_values: Vec<Value> = group_values.into_iter().map(Value::Array).collect();
+
+            // event time windows put every event into the window of its interval,
+            // events for windows that no longer accept events are passed on via the `late` port
+            let mut event_time = None;
+            if let [window] = windows.as_mut_slice() {
+                if let (Some(watermark), Some(event_window)) =
+                    (&window.watermark, window.window_impl.as_event_time())
+                {
+                    let time = stry!(event_window.event_time(&data, ingest_ns, origin_uri));
+                    let start = event_window.start_of(time);
+                    if watermark
+                        .current()
+                        .map_or(false, |current| event_window.is_retired_at(start, current))
+                    {
+                        return Ok(Res::Late);
+                    }
+                    event_time = Some((time, start));
+                }
+            }
+
+            let scratches = aggregate_scratches.as_mut();
+
+            for group_value in group_values {
+                let group_str = sorted_serialize(&group_value)?;
+
+                consts.group = group_value.clone_static();
+                consts.group.push(group_str.clone())?;
+
+                match windows.len() {
+                    0 => {
+                        // group by without windows
+                        // otherwise we just pass it through the select portion of the statement
+
+                        // evaluate select clause
+                        let env = Env {
+                            context: &ctx,
+                            consts: consts.run(),
+                            aggrs: &NO_AGGRS,
+                            meta: &node_meta,
+                            recursion_limit: tremor_script::recursion_limit(),
+                        };
+                        // here we have a 1:1 between input and output event and thus can keep the origin uri
+                        if let Some(port_and_event) = stry!(execute_select_and_having(
+                            &select,
+                            &node_meta,
+                            opts,
+                            &local_stack,
+                            state,
+                            &env,
+                            id.clone(),
+                            &data,
+                            ingest_ns,
+                            op_meta,
+                            origin_uri.clone(),
+                            transactional // no windows, we can safely pass through the events field
+                        )) {
+                            events.push(port_and_event);
+                        };
+                    }
+                    1 => {
+                        // simple case of single window
+                        // window_event = on_event(event)
+                        // if emit && !include
+                        //   push
+                        //   init
+                        // accumulate
+                        // if emit && include
+                        //   push
+                        //   init
+
+                        // ALLOW: we verified that an element exists
+                        let window = &mut windows[0];
+                        consts.window = Value::from(window.name.to_string());
+
+                        // event time windows keep a group for every interval that is still open
+                        let group_key = event_time.map_or(SCow::Borrowed(group_str.as_str()), |(_, start)| {
+                            SCow::Owned(format!("{}@{}", group_str, start))
+                        });
+                        let emit_empty_windows = window.window_impl.as_event_time().map_or(false, |w| w.emit_empty_windows);
+                        if let (Some((_, start)), true) = (event_time, emit_empty_windows) {
+                            // empty windows are emitted from the first interval of a group on
+                            window
+                                .next_intervals
+                                .entry(group_str.clone())
+                                .or_insert_with(|| (group_value.clone_static(), start));
+                        }
+
+                        // get current window group
+                        let this_group = stry!(get_or_create_group(
+                            window,
+                            event_id_gen,
+                            aggregates,
+                            &group_key,
+                            &group_value,
+                        ));
+                        let window_event = stry!(this_group.window.on_event(&data, ingest_ns, origin_uri));
+                        if window_event.emit && !window_event.include {
+                            // push
+                            let pane = stry!(this_group.merge_panes(node_meta));
+                            let env = Env {
+                                context: &ctx,
+                                consts: consts.run(),
+                                aggrs: &this_group.aggrs,
+                                meta: &node_meta,
+                                recursion_limit: tremor_script::recursion_limit(),
+                            };
+                            let mut outgoing_event_id = event_id_gen.next_id();
+                            mem::swap(&mut outgoing_event_id, &mut this_group.id);
+                            if let Some(port_and_event) = stry!(execute_select_and_having(
+                                &select,
+                                &node_meta,
+                                opts,
+                                &local_stack,
+                                state,
+                                &env,
+                                outgoing_event_id,
+                                &data,
+                                ingest_ns,
+                                op_meta,
+                                    None,
+                                this_group.transactional
+                            )) {
+                                events.push(port_and_event);
+                            };
+                            // re-initialize aggr state for new window
+                            // reset transactional state for outgoing events
+                            this_group.reset();
+                            // retain the emitted pane for sliding windows
+                            this_group.close_pane(pane);
+                        }
+
+                        // accumulate
+
+                        // accumulate the current event
+                        let env = Env {
+                            context: &ctx,
+                            consts: consts.run(),
+                            aggrs: &NO_AGGRS,
                             meta: &node_meta,
                             recursion_limit: tremor_script::recursion_limit(),
                         };
-                        // here we have a 1:1 between input and output event and thus can keep the origin uri
-                        if let Some(port_and_event) = stry!(execute_select_and_having(
-                            &select,
-                            &node_meta,
+                        stry!(accumulate(
                             opts,
+                            &node_meta,
+                            &env,
                             &local_stack,
                             state,
-                            &env,
-                            id.clone(),
-                            &data,
-                            ingest_ns,
-                            op_meta,
-                            origin_uri.clone(),
-                            transactional // no windows, we can safely pass through the events field
-                        )) {
-                            events.push(port_and_event);
-                        };
+                            this_group,
+                            data,
+                            id,
+                            transactional
+                        ));
+
+                        if window_event.emit && window_event.include {
+                            // push
+                            let pane = stry!(this_group.merge_panes(node_meta));
+                            let env = Env {
+                                context: &ctx,
+                                consts: consts.run(),
+                                aggrs: &this_group.aggrs,
+                                meta: &node_meta,
+                                recursion_limit: tremor_script::recursion_limit(),
+                            };
+
+                            let mut outgoing_event_id = event_id_gen.next_id();
+                            mem::swap(&mut outgoing_event_id, &mut this_group.id);
+                            if let Some(port_and_event) = stry!(execute_select_and_having(
+                                &select,
+                                &node_meta,
+                                opts,
+                                &local_stack,
+                                state,
+                                &env,
+                                outgoing_event_id,
+                                &data,
+                                ingest_ns,
+                                op_meta,
+                                    None,
+                                this_group.transactional
+                            )) {
+                                events.push(port_and_event);
+                            };
+                            // re-initialize aggr state for new window
+                            // reset transactional state for outgoing events
+                            this_group.reset();
+                            // retain the emitted pane for sliding windows
+                            this_group.close_pane(pane);
+                        }
+                    }
+                    _ => {
+                        // multiple windows
+                        // emit_window_events = while emit { on_event() }
+                        // if !emit || include {
+                        //    accumulate
+                        // }
+                        // for window in emit_windows {
+                        //   if include
+                        //      this.aggrs.merge(scratch);
+                        //      push event
+                        //      swap(scratch, aggrs)
+                        //      aggrs.init() // init next window
+                        //   else
+                        //      push event
+                        //      if first {
+                        //          swap(scratch, aggrs)
+                        //          aggrs.init() // no merge needed
+                        //      } else {
+                        //          local_scratch = aggregates.clone()
+                        //          swap(scratch, local_scratch) // store old aggrs state from last window
+                        //          swap(aggrs, scratch) // store aggrs state from this before init
+                        //          aggrs.init()
+                        //          this.aggrs.merge(local_scratch);
+                        //      }
+                        // }
+                        // if emit && !include { // if the first window is !include we postpone accumulation until here
+                        //    accumulate
+                        // }
+                        // // if we have a window after the last emit one, we need to propagate the event/aggregation data to it
+                        // if let Some(non_emit_window) = self.windows.get(emit_window_events.len()) {
+                        //      this.aggrs.merge(scratch)
+                        // }
+                        //
+                        // The issue with the windows is the following:
+                        // We emit on the first event of the next windows, this works well for the initial frame
+                        // on a second frame, we have the coordinate with the first we have a problem as we
+                        // merge the higher resolution data into the lower resolution data before we get a chance
+                        // to emit the lower res frame - causing us to have an element too much
+                        // problems get worse as we now have windows that might emit before the current event
+                        // is accumulated into the aggregation state or after
+                        // we need to store the state before re-init in a scratch, so the next window can pull it whenever
+                        // it is ready to do so, before or after accumulating
+                        //
+                        // event | size 2 | tilt | size 3 |
+                        //     1 | [1]    |        |
+                        //     2 | [1, 2] |        |
+                        //     3 | emit   | [1, 2] | [1, 2]
+                        //       | [3]    |        | [1, 2]
+                        //     4 | [3, 4] |        | [1, 2]
+                        //     5 | emit   | [3, 4] | [1, 2, 3, 4]
+                        //       | [5]    |        | [1, 2, 3, 4]
+                        //     6 | [5, 6] |        | [1, 2, 3, 4]
+                        //     7 | emit   | [5, 6] | [1, 2, 3, 4, 5, 6]
+                        //       | [7]    |        | [1, 2, 3, 4, 5, 6]
+                        //     8 | [7, 8] |        | [1, 2, 3, 4, 5, 6]
+                        //     9 | emit   | [7, 8] | [1, 2, 3, 4, 5, 6, 7, 8] // this is where things break
+                        //       | [9]    |        | [1, 2, 3, 4, 5, 6, 7, 8] // since we tilt up before we check
+                        //       |        |        | emit                     // the next window we collect one too many elements
+
+                        // we need scratches for multiple window handling, to avoid allocating on the hot path here
+                        if let Some((scratch1, scratch2)) = scratches {
+                            // scratches for passing on the event id between windows to track all events contributing to an outgoing windowed event
+                            // cannot put them in AggregateScratch, as this is in tremor_script and doesnt know about EventId
+                            // cannot put AggregateScratch in TrickleSelect as it has lifetimes which we tried to avoid here
+                            let mut event_id_scratch1 = EventId::default();
+                            let mut event_id_scratch2 = EventId::default();
+
+                            // gather window events
+                            let mut emit_window_events = Vec::with_capacity(windows.len());
+                            let step1_iter = windows.iter_mut();
+                            for window in step1_iter {
+                                consts.window = Value::from(window.name.to_string());
+                                // get current window group
+                                let this_group = stry!(get_or_create_group(
+                                    window,
+                                    event_id_gen,
+                                    aggregates,
+                                    &group_str,
+                                    &group_value,
+                                ));
+                                let window_event = stry!(this_group.window.on_event(&data, ingest_ns, origin_uri));
+                                if window_event.emit {
+                                    emit_window_events.push(window_event);
+                                } else {
+                                    break;
+                                }
+                            }
+
+                            // accumulate if we do not emit anything or the first emitting window includes the current event into its state (window_event.include == true)
+                            let pending_accumulate = if let Some(WindowEvent {
+                                emit: true,
+                                include: false,
+                                ..
+                            }) = emit_window_events.first()
+                            {
+                                // postpone
+                                true
+                            } else if let Some(first_window) = windows.first_mut() {
+                                consts.window = Value::from(first_window.name.to_string());
+
+                                // get current window group
+                                let this_group = stry!(get_or_create_group(
+                                    first_window,
+                                    event_id_gen,
+                                    aggregates,
+                                    &group_str,
+                                    &group_value,
+                                ));
+
+                                // accumulate the current event
+                                let env = Env {
+                                    context: &ctx,
+                                    consts: consts.run(),
+                                    aggrs: &NO_AGGRS,
+                                    meta: &node_meta,
+                                    recursion_limit: tremor_script::recursion_limit(),
+                                };
+                                stry!(accumulate(
+                                    opts,
+                                    &node_meta,
+                                    &env,
+                                    &local_stack,
+                                    state,
+                                    this_group,
+                                    data,
+                                    id,
+                                    transactional
+                                        ));
+                                false
+                            } else {
+                                // should not happen
+                                false
+                            };
+
+                            // merge previous aggr state, push window event out, re-initialize for new window
+                            let mut first = true;
+                            let emit_window_iter =
+                                emit_window_events.iter().zip(windows.iter_mut());
+                            for (window_event, window) in emit_window_iter {
+                                consts.window = Value::from(window.name.to_string());
+
+                                // get current window group
+                                let this_group = stry!(get_or_create_group(
+                                    window,
+                                    event_id_gen,
+                                    aggregates,
+                                    &group_str,
+                                    &group_value,
+                                ));
+
+                                if window_event.include {
+                                    // add this event to the aggr state **BEFORE** emit and propagate to next windows
+                                    // merge with scratch
+                                    if !first {
+                                        for (this, prev) in this_group
+                                            .aggrs
+                                            .iter_mut()
+                                            .zip(scratch1.aggregates.iter())
+                                        {
+                                            this.invocable.merge(&prev.invocable).map_err(|e| {
+                                                let r: Option<&Registry> = None;
+                                                e.into_err(prev, prev, r, &node_meta)
+                                            })?;
+                                        }
+                                        this_group.track_transactional(scratch1.transactional);
+                                        this_group.id.track(&event_id_scratch1);
+                                    }
+                                    // push event
+                                    let pane = stry!(this_group.merge_panes(node_meta));
+                                    let env = Env {
+                                        context: &ctx,
+                                        consts: consts.run(),
+                                        aggrs: &this_group.aggrs,
+                                        meta: &node_meta,
+                                        recursion_limit: tremor_script::recursion_limit(),
+                                    };
+
+                                    let mut outgoing_event_id = event_id_gen.next_id();
+                                    event_id_scratch1 = this_group.id.clone(); // store the id for to be tracked by the next window
+                                    mem::swap(&mut outgoing_event_id, &mut this_group.id);
+                                    if let Some(port_and_event) = stry!(execute_select_and_having(
+                                        &select,
+                                        &node_meta,
+                                        opts,
+                                        &local_stack,
+                                        state,
+                                        &env,
+                                        outgoing_event_id,
+                                        &data,
+                                        ingest_ns,
+                                        op_meta,
+                                                    None,
+                                        this_group.transactional
+                                    )) {
+                                        events.push(port_and_event);
+                                    };
+
+                                    // swap(aggrs, scratch) - store state before init to scratch as we need it for the next window
+                                    std::mem::swap(&mut scratch1.aggregates, &mut this_group.aggrs);
+                                    // store transactional state for mixing into the next window
+                                    scratch1.transactional = this_group.transactional;
+
+                                    // aggrs.init() and reset transactional state
+                                    this_group.reset();
+                                    // retain the emitted pane for sliding windows
+                                    this_group.close_pane(pane);
+                                } else {
+                                    // add this event to the aggr state **AFTER** emit and propagate to next windows
+
+                                    // push event
+                                    let pane = stry!(this_group.merge_panes(node_meta));
+                                    let env = Env {
+                                        context: &ctx,
+                                        consts: consts.run(),
+                                        aggrs: &this_group.aggrs,
+                                        meta: &node_meta,
+                                        recursion_limit: tremor_script::recursion_limit(),
+                                    };
+
+                                    let mut outgoing_event_id = event_id_gen.next_id();
+                                    if !first {
+                                        // store old event_ids state from last window into scratch2
+                                        std::mem::swap(
+                                            &mut event_id_scratch1,
+                                            &mut event_id_scratch2,
+                                        );
+                                    }
+                                    event_id_scratch1 = this_group.id.clone(); // store the id for to be tracked by the next window
+                                    mem::swap(&mut outgoing_event_id, &mut this_group.id);
+                                    if let Some(port_and_event) = stry!(execute_select_and_having(
+                                        &select,
+                                        &node_meta,
+                                        opts,
+                                        &local_stack,
+                                        state,
+                                        &env,
+                                        outgoing_event_id,
+                                        &data,
+                                        ingest_ns,
+                                        op_meta,
+                                                    None,
+                                        this_group.transactional
+                                    )) {
+                                        events.push(port_and_event);
+                                    };
+
+                                    if first {
+                                        // first window
+                                        //          swap(scratch, aggrs)
+                                        std::mem::swap(
+                                            &mut scratch1.aggregates,
+                                            &mut this_group.aggrs,
+                                        ); // store current state before init
+                                        scratch1.transactional = this_group.transactional;
+                                        //          aggrs.init() and reset transactional state
+                                        this_group.reset();
+                                    } else {
+                                        // not first window
+                                        // store old aggrs state from last window into scratch2
+                                        std::mem::swap(scratch1, scratch2);
+                                        //          swap(aggrs, scratch) // store aggrs state before init() into scratch1 for next window
+                                        std::mem::swap(
+                                            &mut this_group.aggrs,
+                                            &mut scratch1.aggregates,
+                                        );
+                                        scratch1.transactional = this_group.transactional;
+                                        //          aggrs.init() and reset transactional state
+                                        this_group.reset();
+
+                                        //          merge state from last window into this one
+                                        for (this, prev) in this_group
+                                            .aggrs
+                                            .iter_mut()
+                                            .zip(scratch2.aggregates.iter())
+                                        {
+                                            this.invocable.merge(&prev.invocable).map_err(|e| {
+                                                let r: Option<&Registry> = None;
+                                                e.into_err(prev, prev, r, &node_meta)
+                                            })?;
+                                        }
+                                        this_group.track_transactional(scratch2.transactional);
+                                        this_group.id.track(&event_id_scratch2);
+                                    }
+                                    // retain the emitted pane for sliding windows
+                                    this_group.close_pane(pane);
+                                }
+
+                                first = false;
+                            }
+
+                            // execute the pending accumulate (if window_event.include == false of first window)
+                            if pending_accumulate {
+                                if let Some(first_window) = windows.first_mut() {
+                                    consts.window = Value::from(first_window.name.to_string());
+                                    // get current window group
+                                    let this_group = stry!(get_or_create_group(
+                                        first_window,
+                                        event_id_gen,
+                                        aggregates,
+                                        &group_str,
+                                        &group_value,
+                                    ));
+
+                                    // accumulate the current event
+                                    let env = Env {
+                                        context: &ctx,
+                                        consts: consts.run(),
+                                        aggrs: &NO_AGGRS,
+                                        meta: &node_meta,
+                                        recursion_limit: tremor_script::recursion_limit(),
+                                    };
+                                    stry!(accumulate(
+                                        opts,
+                                        &node_meta,
+                                        &env,
+                                        &local_stack,
+                                        state,
+                                        this_group,
+                                        data,
+                                        id,
+                                        transactional
+                                                ));
+                                }
+                            }
+
+                            // merge state from last emit window into next non-emit window if there is any
+                            if !emit_window_events.is_empty() {
+                                if let Some(non_emit_window) =
+                                   windows.get_mut(emit_window_events.len())
+                                {
+                                    consts.window = Value::from(non_emit_window.name.to_string());
+
+                                    // get current window group
+                                    let this_group = stry!(get_or_create_group(
+                                        non_emit_window,
+                                        event_id_gen,
+                                        aggregates,
+                                        &group_str,
+                                        &group_value,
+                                    ));
+                                    for (this, prev) in
+                                        this_group.aggrs.iter_mut().zip(scratch1.aggregates.iter())
+                                    {
+                                        this.invocable.merge(&prev.invocable).map_err(|e| {
+                                            let r: Option<&Registry> = None;
+                                            e.into_err(prev, prev, r, &node_meta)
+                                        })?;
+                                    }
+                                    this_group.track_transactional(scratch1.transactional);
+                                    this_group.id.track(&event_id_scratch1);
+                                }
+                            }
+                        } else {
+                            // if this happens, we fucked up internally.
+                            return Err("Internal Error. Cannot execute select with multiple windows, no scratches available. This is bad!".into());
+                        }
                     }
-                    1 => {
-                        // simple case of single window
-                        // window_event = on_event(event)
-                        // if emit && !include
-                        //   push
-                        //   init
-                        // accumulate
-                        // if emit && include
-                        //   push
-                        //   init
+                }
+            }
+            // advance the watermark, the signal passes through all operators including this one,
+            // emitting the windows it closed
+            if let (Some((time, _)), [window]) = (event_time, windows.as_mut_slice()) {
+                if let Some(watermark) = window
+                    .watermark
+                    .as_mut()
+                    .and_then(|watermark| watermark.on_event((id.source_id(), id.stream_id()), time, ingest_ns))
+                {
+                    events.push((OUT, Event::watermark(uid, ingest_ns, watermark)));
+                }
+            }
+            Ok(Res::Data(events.into()))
+        })?;
 
-                        // ALLOW: we verified that an element exists
-                        let window = &mut windows[0];
-                        consts.window = Value::from(window.name.to_string());
+        match res {
+            Res::Event => Ok(event.into()),
+            Res::None => Ok(EventAndInsights::default()),
+            Res::Data(data) => Ok(data),
+            Res::Late => Ok(EventAndInsights::from(vec![(LATE, event)])),
+        }
+    }
 
-                        // get current window group
-                        let this_group = stry!(get_or_create_group(
-                            window,
-                            event_id_gen,
-                            aggregates,
-                            &group_str,
-                            &group_value,
-                        ));
-                        let window_event = stry!(this_group.window.on_event(&data, ingest_ns, origin_uri));
-                        if window_event.emit && !window_event.include {
-                            // push
-                            let env = Env {
-                                context: &ctx,
-                                consts: consts.run(),
-                                aggrs: &this_group.aggrs,
-                                meta: &node_meta,
-                                recursion_limit: tremor_script::recursion_limit(),
-                            };
-                            let mut outgoing_event_id = event_id_gen.next_id();
-                            mem::swap(&mut outgoing_event_id, &mut this_group.id);
-                            if let Some(port_and_event) = stry!(execute_select_and_having(
-                                &select,
-                                &node_meta,
-                                opts,
-                                &local_stack,
-                                state,
-                                &env,
-                                outgoing_event_id,
-                                &data,
-                                ingest_ns,
-                                op_meta,
-                                    None,
-                                this_group.transactional
-                            )) {
-                                events.push(port_and_event);
-                            };
-                            // re-initialize aggr state for new window
-                            // reset transactional state for outgoing events
-                            this_group.reset();
+    #[allow(clippy::too_many_lines)]
+    fn on_signal(
+        &mut self,
+        uid: u64,
+        state: &Value<'static>, // we only reference state here immutably, no chance to change it here
+        signal: &mut Event,
+    ) -> Result<EventAndInsights> {
+        // we only react on ticks and when we have windows
+        let Self {
+            select,
+            windows,
+            event_id_gen,
+            ..
+        } = self;
+
+        // watermarks drive event time windows, we emit every window the watermark passed
+        let watermark = match (signal.kind, signal.watermark_ns(), windows.as_mut_slice()) {
+            (Some(SignalKind::Watermark), Some(watermark), [window]) => {
+                let origin = (signal.id.source_id(), signal.id.stream_id());
+                window.watermark.as_mut().map(|current| {
+                    current.observe(origin, origin.0 == uid, watermark, signal.ingest_ns)
+                })
+            }
+            _ => None,
+        };
+
+        if (signal.kind == Some(SignalKind::Tick) || watermark.is_some()) && !windows.is_empty() {
+            let mut idle_watermark = None;
+            if watermark.is_none() {
+                // Handle eviction
+                // retire the group data that didnt receive an event in `eviction_ns()` nanoseconds
+                // if no event came after `2 * eviction_ns()` this group is finally cleared out
+                for window in windows.iter_mut() {
+                    if let Some(eviction_ns) = window.window_impl.eviction_ns() {
+                        if window.next_swap < signal.ingest_ns {
+                            window.next_swap = signal.ingest_ns + eviction_ns;
+                            window.last_dims.clear();
+                            std::mem::swap(&mut window.dims, &mut window.last_dims);
                         }
+                    }
+                    // sources that became idle might no longer hold back the watermark
+                    idle_watermark = window
+                        .watermark
+                        .as_mut()
+                        .and_then(|watermark| watermark.on_tick(signal.ingest_ns))
+                        .or(idle_watermark);
+                }
+            }
 
-                        // accumulate
+            let ingest_ns = signal.ingest_ns;
+            let opts = Self::opts();
+            select.rent_mut(|stmt| {
+            let SelectStmt {
+                stmt,
+                aggregates,
+                aggregate_scratches,
+                consts,
+                locals,
+                node_meta,
+            }  = stmt;
+            let mut res = EventAndInsights::default();
+
+            let vm: ValueAndMeta = (Value::null(), Value::object()).into();
+            let op_meta = OpMeta::default();
+            let local_stack = tremor_script::interpreter::LocalStack::with_size(*locals);
+
+            consts.window = Value::null();
+            consts.group = Value::null();
+            consts.args = Value::null();
 
-                        // accumulate the current event
-                        let env = Env {
-                            context: &ctx,
-                            consts: consts.run(),
-                            aggrs: &NO_AGGRS,
-                            meta: &node_meta,
-                            recursion_limit: tremor_script::recursion_limit(),
-                        };
-                        stry!(accumulate(
-                            opts,
-                            &node_meta,
-                            &env,
-                            &local_stack,
-                            state,
-                            this_group,
-                            data,
-                            id,
-                            transactional
-                        ));
+            match windows.len() {
+                0 => {} // we shouldnt get here
+                1 => {
+                    let ctx = EventContext::new(signal.ingest_ns, None);
+                    for window in windows.iter_mut() {
+                        consts.window = Value::from(window.name.to_string());
+                        let is_event_time = window.watermark.is_some();
+                        if let Some(watermark) = watermark {
+                            stry!(open_empty_windows(window, event_id_gen, aggregates, watermark));
+                        }
+                        // iterate all groups, including the ones from last_dims
+                        let mut groups: Vec<(&String, &mut GroupData)> = window
+                            .dims
+                            .iter_mut()
+                            .chain(window.last_dims.iter_mut())
+                            .collect();
+                        if is_event_time {
+                            // emit event time windows in the order of their intervals
+                            groups.sort_by_key(|(_, group_data)| {
+                                group_data.window.as_event_time().and_then(|w| w.start)
+                            });
+                        }
+                        for (group_str, group_data) in groups {
+                            consts.group = group_data.group.clone_static();
+                            if is_event_time {
+                                // strip the interval from the group key
+                                consts.group.push(sorted_serialize(&group_data.group)?)?;
+                            } else {
+                                consts.group.push(group_str.clone())?;
+                            }
 
-                        if window_event.emit && window_event.include {
-                            // push
-                            let env = Env {
-                                context: &ctx,
-                                consts: consts.run(),
-                                aggrs: &this_group.aggrs,
-                                meta: &node_meta,
-                                recursion_limit: tremor_script::recursion_limit(),
+                            let window_event = if let Some(watermark) = watermark {
+                                stry!(group_data.window.on_watermark(watermark))
+                            } else {
+                                stry!(group_data.window.on_tick(ingest_ns))
                             };
-
-                            let mut outgoing_event_id = event_id_gen.next_id();
-                            mem::swap(&mut outgoing_event_id, &mut this_group.id);
-                            if let Some(port_and_event) = stry!(execute_select_and_having(
-                                &select,
-                                &node_meta,
-                                opts,
-                                &local_stack,
-                                state,
-                                &env,
-                                outgoing_event_id,
-                                &data,
-                                ingest_ns,
-                                op_meta,
+                            if window_event.emit {
+                                // evaluate the event and push
+                                let pane = stry!(group_data.merge_panes(node_meta));
+                                let env = Env {
+                                    context: &ctx,
+                                    consts: consts.run(),
+                                    aggrs: &group_data.aggrs,
+                                    meta: &node_meta,
+                                    recursion_limit: tremor_script::recursion_limit(),
+                                };
+                                let mut outgoing_event_id = event_id_gen.next_id();
+                                mem::swap(&mut group_data.id, &mut outgoing_event_id);
+                                let executed = stry!(execute_select_and_having(
+                                    &stmt,
+                                    node_meta,
+                                    opts,
+                                    &local_stack,
+                                    state,
+                                    &env,
+                                    outgoing_event_id,
+                                    &vm,
+                                    ingest_ns,
+                                    &op_meta,
                                     None,
-                                this_group.transactional
-                            )) {
-                                events.push(port_and_event);
-                            };
-                            // re-initialize aggr state for new window
-                            // reset transactional state for outgoing events
-                            this_group.reset();
+                                    group_data.transactional
+                                ));
+                                if let Some(port_and_event) = executed {
+                                    res.events.push(port_and_event);
+                                }
+
+                                // init aggregates and reset transactional status
+                                group_data.reset();
+                                // retain the emitted pane for sliding windows
+                                group_data.close_pane(pane);
+                            }
+                        }
+                        // forget about event time windows that no longer accept late events
+                        if let Some(watermark) = watermark {
+                            window.retire(watermark);
                         }
                     }
-                    _ => {
-                        // multiple windows
-                        // emit_window_events = while emit { on_event() }
-                        // if !emit || include {
-                        //    accumulate
-                        // }
-                        // for window in emit_windows {
-                        //   if include
-                        //      this.aggrs.merge(scratch);
-                        //      push event
-                        //      swap(scratch, aggrs)
-                        //      aggrs.init() // init next window
-                        //   else
-                        //      push event
-                        //      if first {
-                        //          swap(scratch, aggrs)
-                        //          aggrs.init() // no merge needed
-                        //      } else {
-                        //          local_scratch = aggregates.clone()
-                        //          swap(scratch, local_scratch) // store old aggrs state from last window
-                        //          swap(aggrs, scratch) // store aggrs state from this before init
-                        //          aggrs.init()
-                        //          this.aggrs.merge(local_scratch);
-                        //      }
-                        // }
-                        // if emit && !include { // if the first window is !include we postpone accumulation until here
-                        //    accumulate
-                        // }
-                        // // if we have a window after the last emit one, we need to propagate the event/aggregation data to it
-                        // if let Some(non_emit_window) = self.windows.get(emit_window_events.len()) {
-                        //      this.aggrs.merge(scratch)
-                        // }
-                        //
-                        // The issue with the windows is the following:
-                        // We emit on the first event of the next windows, this works well for the initial frame
-                        // on a second frame, we have the coordinate with the first we have a problem as we
-                        // merge the higher resolution data into the lower resolution data before we get a chance
-                        // to emit the lower res frame - causing us to have an element too much
-                        // problems get worse as we now have windows that might emit before the current event
-                        // is accumulated into the aggregation state or after
-                        // we need to store the state before re-init in a scratch, so the next window can pull it whenever
-                        // it is ready to do so, before or after accumulating
-                        //
-                        // event | size 2 | tilt | size 3 |
-                        //     1 | [1]    |        |
-                        //     2 | [1, 2] |        |
-                        //     3 | emit   | [1, 2] | [1, 2]
-                        //       | [3]    |        | [1, 2]
-                        //     4 | [3, 4] |        | [1, 2]
-                        //     5 | emit   | [3, 4] | [1, 2, 3, 4]
-                        //       | [5]    |        | [1, 2, 3, 4]
-                        //     6 | [5, 6] |        | [1, 2, 3, 4]
-                        //     7 | emit   | [5, 6] | [1, 2, 3, 4, 5, 6]
-                        //       | [7]    |        | [1, 2, 3, 4, 5, 6]
-                        //     8 | [7, 8] |        | [1, 2, 3, 4, 5, 6]
-                        //     9 | emit   | [7, 8] | [1, 2, 3, 4, 5, 6, 7, 8] // this is where things break
-                        //       | [9]    |        | [1, 2, 3, 4, 5, 6, 7, 8] // since we tilt up before we check
-                        //       |        |        | emit                     // the next window we collect one too many elements
+                }
+                _ => {
+                    // complicated logic
+                    // get a set of groups
+                    //   for each window
+                    //      get the corresponding group data
+                    //      on_event ... do the magic dance with scratches etc
+                    // we need scratches for multiple window handling
 
-                        // we need scratches for multiple window handling, to avoid allocating on the hot path here
-                        if let Some((scratch1, scratch2)) = scratches {
+                    let ctx = EventContext::new(ingest_ns, None);
+
+                    // we get away with just checking the first window as there can be no group in a further window
+                    // that is not also in the first.
+                    let distinct_groups: Vec<(&String, &Value)> = windows
+                        .first()
+                        .iter()
+                        .flat_map(|window| {
+                            (*window)
+                                .dims
+                                .iter()
+                                .chain(window.last_dims.iter())
+                        })
+                        .map(|(group_str, group_data)| {
+                            // trick the borrow checker, so we can reference self.windows mutably below
+                            // this is safe, as we only ever work on group_data.window and group_data.aggregates
+                            // we might move a group_data struct from one map to another, but there is no deletion
+                            // we never touch neither the group value nor the group_str during the fiddling below
+                            // ALLOW: https://github.com/tremor-rs/tremor-runtime/issues/1027
+                            let group_str: &'static String = unsafe { std::mem::transmute(group_str) };
+                            let group_value: &'static Value =
+                            // ALLOW: https://github.com/tremor-rs/tremor-runtime/issues/1027
+                                unsafe { std::mem::transmute(&group_data.group) }; // lose the reference to window
+                            (group_str, group_value)
+                        })
+                        .collect();
+
+                    for (group_str, group_value) in distinct_groups {
+                        consts.group = group_value.clone_static();
+                        consts.group.push(group_str.clone())?;
+
+                        if let Some((scratch1, scratch2)) = aggregate_scratches.as_mut() {
                             // scratches for passing on the event id between windows to track all events contributing to an outgoing windowed event
-                            // cannot put them in AggregateScratch, as this is in tremor_script and doesnt know about EventId
-                            // cannot put AggregateScratch in TrickleSelect as it has lifetimes which we tried to avoid here
                             let mut event_id_scratch1 = EventId::default();
                             let mut event_id_scratch2 = EventId::default();
 
@@ -962,64 +2499,18 @@ impl Operator for TrickleSelect {
                                 // get current window group
                                 let this_group = stry!(get_or_create_group(
                                     window,
-                                    event_id_gen,
-                                    aggregates,
-                                    &group_str,
-                                    &group_value,
-                                ));
-                                let window_event = stry!(this_group.window.on_event(&data, ingest_ns, origin_uri));
-                                if window_event.emit {
-                                    emit_window_events.push(window_event);
-                                } else {
-                                    break;
-                                }
-                            }
-
-                            // accumulate if we do not emit anything or the first emitting window includes the current event into its state (window_event.include == true)
-                            let pending_accumulate = if let Some(WindowEvent {
-                                emit: true,
-                                include: false,
-                                ..
-                            }) = emit_window_events.first()
-                            {
-                                // postpone
-                                true
-                            } else if let Some(first_window) = windows.first_mut() {
-                                consts.window = Value::from(first_window.name.to_string());
-
-                                // get current window group
-                                let this_group = stry!(get_or_create_group(
-                                    first_window,
-                                    event_id_gen,
-                                    aggregates,
-                                    &group_str,
-                                    &group_value,
-                                ));
-
-                                // accumulate the current event
-                                let env = Env {
-                                    context: &ctx,
-                                    consts: consts.run(),
-                                    aggrs: &NO_AGGRS,
-                                    meta: &node_meta,
-                                    recursion_limit: tremor_script::recursion_limit(),
-                                };
-                                stry!(accumulate(
-                                    opts,
-                                    &node_meta,
-                                    &env,
-                                    &local_stack,
-                                    state,
-                                    this_group,
-                                    data,
-                                    id,
-                                    transactional
-                                        ));
-                                false
-                            } else {
-                                // should not happen
-                                false
-                            };
+                                    event_id_gen,
+                                    aggregates,
+                                    group_str,
+                                    group_value,
+                                ));
+                                let window_event = stry!(this_group.window.on_tick(ingest_ns));
+                                if window_event.emit {
+                                    emit_window_events.push(window_event);
+                                } else {
+                                    break;
+                                }
+                            }
 
                             // merge previous aggr state, push window event out, re-initialize for new window
                             let mut first = true;
@@ -1033,8 +2524,8 @@ impl Operator for TrickleSelect {
                                     window,
                                     event_id_gen,
                                     aggregates,
-                                    &group_str,
-                                    &group_value,
+                                    group_str,
+                                    group_value,
                                 ));
 
                                 if window_event.include {
@@ -1055,6 +2546,7 @@ impl Operator for TrickleSelect {
                                         this_group.id.track(&event_id_scratch1);
                                     }
                                     // push event
+                                    let pane = stry!(this_group.merge_panes(node_meta));
                                     let env = Env {
                                         context: &ctx,
                                         consts: consts.run(),
@@ -1064,36 +2556,37 @@ impl Operator for TrickleSelect {
                                     };
 
                                     let mut outgoing_event_id = event_id_gen.next_id();
-                                    event_id_scratch1 = this_group.id.clone(); // store the id for to be tracked by the next window
-                                    mem::swap(&mut outgoing_event_id, &mut this_group.id);
+                                    event_id_scratch1 = this_group.id.clone(); // store event_id in a scratch for tracking it in the next window in the tilt-frame
+                                    mem::swap(&mut this_group.id, &mut outgoing_event_id);
                                     if let Some(port_and_event) = stry!(execute_select_and_having(
-                                        &select,
+                                        &stmt,
                                         &node_meta,
                                         opts,
                                         &local_stack,
                                         state,
                                         &env,
                                         outgoing_event_id,
-                                        &data,
+                                        &vm,
                                         ingest_ns,
-                                        op_meta,
-                                                    None,
+                                        &op_meta,
+                                            None,
                                         this_group.transactional
                                     )) {
-                                        events.push(port_and_event);
+                                        res.events.push(port_and_event);
                                     };
 
                                     // swap(aggrs, scratch) - store state before init to scratch as we need it for the next window
                                     std::mem::swap(&mut scratch1.aggregates, &mut this_group.aggrs);
-                                    // store transactional state for mixing into the next window
                                     scratch1.transactional = this_group.transactional;
-
                                     // aggrs.init() and reset transactional state
                                     this_group.reset();
+                                    // retain the emitted pane for sliding windows
+                                    this_group.close_pane(pane);
                                 } else {
                                     // add this event to the aggr state **AFTER** emit and propagate to next windows
 
                                     // push event
+                                    let pane = stry!(this_group.merge_panes(node_meta));
                                     let env = Env {
                                         context: &ctx,
                                         consts: consts.run(),
@@ -1104,29 +2597,29 @@ impl Operator for TrickleSelect {
 
                                     let mut outgoing_event_id = event_id_gen.next_id();
                                     if !first {
-                                        // store old event_ids state from last window into scratch2
+                                        // store previous window event_id in scratch2
                                         std::mem::swap(
                                             &mut event_id_scratch1,
                                             &mut event_id_scratch2,
                                         );
                                     }
-                                    event_id_scratch1 = this_group.id.clone(); // store the id for to be tracked by the next window
-                                    mem::swap(&mut outgoing_event_id, &mut this_group.id);
+                                    event_id_scratch1 = this_group.id.clone(); // store event id before swapping it out, for tracking in next window
+                                    mem::swap(&mut this_group.id, &mut outgoing_event_id);
                                     if let Some(port_and_event) = stry!(execute_select_and_having(
-                                        &select,
+                                        &stmt,
                                         &node_meta,
                                         opts,
                                         &local_stack,
                                         state,
                                         &env,
                                         outgoing_event_id,
-                                        &data,
+                                        &vm,
                                         ingest_ns,
-                                        op_meta,
-                                                    None,
+                                        &op_meta,
+                                            None,
                                         this_group.transactional
                                     )) {
-                                        events.push(port_and_event);
+                                        res.events.push(port_and_event);
                                     };
 
                                     if first {
@@ -1149,1308 +2642,1511 @@ impl Operator for TrickleSelect {
                                             &mut scratch1.aggregates,
                                         );
                                         scratch1.transactional = this_group.transactional;
+
                                         //          aggrs.init() and reset transactional state
                                         this_group.reset();
+                                        //          merge state from last window into this one
+                                        for (this, prev) in this_group
+                                            .aggrs
+                                            .iter_mut()
+                                            .zip(scratch2.aggregates.iter())
+                                        {
+                                            this.invocable.merge(&prev.invocable).map_err(|e| {
+                                                let r: Option<&Registry> = None;
+                                                e.into_err(prev, prev, r, &node_meta)
+                                            })?;
+                                        }
+                                        this_group.track_transactional(scratch2.transactional);
+                                        this_group.id.track(&event_id_scratch2);
+                                    }
+                                    // retain the emitted pane for sliding windows
+                                    this_group.close_pane(pane);
+                                }
+
+                                first = false;
+                            }
+
+                            // merge state from last emit window into next non-emit window if there is any
+                            if !emit_window_events.is_empty() {
+                                if let Some(non_emit_window) =
+                                    windows.get_mut(emit_window_events.len())
+                                {
+                                    consts.window = Value::from(non_emit_window.name.to_string());
+
+                                    // get current window group
+                                    let this_group = stry!(get_or_create_group(
+                                        non_emit_window,
+                                        event_id_gen,
+                                        aggregates,
+                                        group_str,
+                                        group_value,
+                                    ));
+                                    for (this, prev) in
+                                        this_group.aggrs.iter_mut().zip(scratch1.aggregates.iter())
+                                    {
+                                        this.invocable.merge(&prev.invocable).map_err(|e| {
+                                            let r: Option<&Registry> = None;
+                                            e.into_err(prev, prev, r, &node_meta)
+                                        })?;
+                                    }
+                                    this_group.track_transactional(scratch1.transactional);
+                                    this_group.id.track(&event_id_scratch1);
+                                }
+                            }
+                        } else {
+                            // if this happens, we fucked up internally.
+                            return Err("Internal Error. Cannot execute select with multiple windows, no scratches available. This is bad!".into());
+                        }
+                    }
+                }
+            }
+            // the advanced watermark passes through all operators including this one
+            if let Some(watermark) = idle_watermark {
+                res.events.push((OUT, Event::watermark(uid, ingest_ns, watermark)));
+            }
+            Ok(res)
+        })
+        } else {
+            Ok(EventAndInsights::default())
+        }
+    }
+
+    fn handles_signal(&self) -> bool {
+        true
+    }
+
+    fn checkpoint(&self, covered: &mut Option<EventId>) -> Result<Option<Value<'static>>> {
+        if self.windows.is_empty() {
+            return Ok(None);
+        }
+        let mut windows = Vec::with_capacity(self.windows.len());
+        for window in &self.windows {
+            windows.push(literal!({
+                "name": window.name.clone(),
+                "watermark": window.watermark.as_ref().and_then(Watermark::current),
+                "groups": snapshot_groups(&window.dims, covered)?,
+                "last_groups": snapshot_groups(&window.last_dims, covered)?,
+            }));
+        }
+        Ok(Some(Value::from(windows)))
+    }
+
+    fn restore(&mut self, state: &Value) -> Result<()> {
+        let aggregates = self.select.rent(|stmt| stmt.aggregates.clone());
+        let states = state
+            .as_array()
+            .ok_or_else(|| invalid_checkpoint("windows"))?;
+        for state in states {
+            let name = state
+                .get_str("name")
+                .ok_or_else(|| invalid_checkpoint("name"))?;
+            // windows that are no longer part of the select are dropped
+            if let Some(window) = self.windows.iter_mut().find(|w| w.name == name) {
+                let idgen = &mut self.event_id_gen;
+                window.dims =
+                    restore_groups(&window.window_impl, &aggregates, idgen, state.get("groups"))?;
+                window.last_dims = restore_groups(
+                    &window.window_impl,
+                    &aggregates,
+                    idgen,
+                    state.get("last_groups"),
+                )?;
+                if let (Some(watermark), Some(current)) =
+                    (window.watermark.as_mut(), state.get_u64("watermark"))
+                {
+                    watermark.current = Some(current);
+                    watermark.local = Some(current);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    #![allow(clippy::float_cmp)]
+    use crate::query::window_decl_to_impl;
+
+    use super::*;
+
+    use tremor_script::ast::Stmt;
+    use tremor_script::{
+        ast::{self, Ident, ImutExpr, Literal},
+        path::ModulePath,
+    };
+    use tremor_script::{
+        ast::{AggregateScratch, Consts},
+        Value,
+    };
+    use tremor_value::literal;
+
+    fn test_target<'test>() -> ast::ImutExpr<'test> {
+        let target: ast::ImutExpr<'test> = ImutExpr::from(ast::Literal {
+            mid: 0,
+            value: Value::from(42),
+        });
+        target
+    }
+
+    fn test_stmt(target: ast::ImutExpr) -> ast::Select {
+        tremor_script::ast::Select {
+            mid: 0,
+            from: (Ident::from("in"), Ident::from("out")),
+            into: (Ident::from("out"), Ident::from("in")),
+            target,
+            maybe_where: Some(ImutExpr::from(ast::Literal {
+                mid: 0,
+                value: Value::from(true),
+            })),
+            windows: vec![],
+            maybe_group_by: None,
+            maybe_having: None,
+        }
+    }
+
+    fn test_query(stmt: ast::Stmt) -> ast::Query {
+        ast::Query {
+            stmts: vec![stmt.clone()],
+            node_meta: ast::NodeMetas::new(Vec::new()),
+            windows: HashMap::new(),
+            scripts: HashMap::new(),
+            operators: HashMap::new(),
+            config: HashMap::new(),
+        }
+    }
+
+    fn ingest_ns(s: u64) -> u64 {
+        s * 1_000_000_000
+    }
+    fn test_event(s: u64) -> Event {
+        Event {
+            id: (0, 0, s).into(),
+            ingest_ns: ingest_ns(s),
+            data: literal!({
+               "h2g2" : 42,
+            })
+            .into(),
+            ..Event::default()
+        }
+    }
+
+    fn test_event_tx(s: u64, transactional: bool, group: u64) -> Event {
+        Event {
+            id: (0, 0, s).into(),
+            ingest_ns: s + 100,
+            data: literal!({ "group": group }).into(),
+            transactional,
+            ..Event::default()
+        }
+    }
+
+    fn test_select(uid: u64, stmt: srs::Stmt) -> Result<TrickleSelect> {
+        let groups = Groups::new();
+        let windows = vec![
+            (
+                "w15s".into(),
+                TumblingWindowOnTime {
+                    emit_empty_windows: false,
+                    ttl: None,
+                    max_groups: WindowImpl::DEFAULT_MAX_GROUPS,
+                    interval: 15_000_000_000,
+                    next_window: None,
+                    events: 0,
+                    script: None,
+                }
+                .into(),
+            ),
+            (
+                "w30s".into(),
+                TumblingWindowOnTime {
+                    emit_empty_windows: false,
+                    ttl: None,
+                    max_groups: WindowImpl::DEFAULT_MAX_GROUPS,
+                    interval: 30_000_000_000,
+                    next_window: None,
+                    events: 0,
+                    script: None,
+                }
+                .into(),
+            ),
+        ];
+        let id = "select".to_string();
+        TrickleSelect::with_stmt(uid, id, &groups, windows, &stmt)
+    }
+
+    fn try_enqueue(
+        op: &mut TrickleSelect,
+        event: Event,
+    ) -> Result<Option<(Cow<'static, str>, Event)>> {
+        let mut state = Value::null();
+        let mut action = op.on_event(0, "in", &mut state, event)?;
+        let first = action.events.pop();
+        if action.events.is_empty() {
+            Ok(first)
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn try_enqueue_two(
+        op: &mut TrickleSelect,
+        event: Event,
+    ) -> Result<Option<[(Cow<'static, str>, Event); 2]>> {
+        let mut state = Value::null();
+        let mut action = op.on_event(0, "in", &mut state, event)?;
+        let r = action
+            .events
+            .pop()
+            .and_then(|second| Some([action.events.pop()?, second]));
+        if action.events.is_empty() {
+            Ok(r)
+        } else {
+            Ok(None)
+        }
+    }
 
-                                        //          merge state from last window into this one
-                                        for (this, prev) in this_group
-                                            .aggrs
-                                            .iter_mut()
-                                            .zip(scratch2.aggregates.iter())
-                                        {
-                                            this.invocable.merge(&prev.invocable).map_err(|e| {
-                                                let r: Option<&Registry> = None;
-                                                e.into_err(prev, prev, r, &node_meta)
-                                            })?;
-                                        }
-                                        this_group.track_transactional(scratch2.transactional);
-                                        this_group.id.track(&event_id_scratch2);
-                                    }
-                                }
+    fn parse_query(
+        file_name: String,
+        query: &str,
+    ) -> Result<crate::op::trickle::select::TrickleSelect> {
+        let reg = tremor_script::registry();
+        let aggr_reg = tremor_script::aggr_registry();
+        let module_path = tremor_script::path::load();
+        let cus = vec![];
+        let query = tremor_script::query::Query::parse(
+            &module_path,
+            &file_name,
+            query,
+            cus,
+            &reg,
+            &aggr_reg,
+        )
+        .map_err(tremor_script::errors::CompilerError::error)?;
+        let stmt = srs::Stmt::try_new_from_query(&query.query, |q| {
+            q.stmts
+                .first()
+                .cloned()
+                .ok_or_else(|| Error::from("Invalid query"))
+        })?;
+        Ok(test_select(1, stmt)?)
+    }
 
-                                first = false;
-                            }
+    #[test]
+    fn test_sum() -> Result<()> {
+        let mut op = parse_query(
+            "test.trickle".to_string(),
+            "select aggr::stats::sum(event.h2g2) from in[w15s, w30s] into out;",
+        )?;
+        assert!(try_enqueue(&mut op, test_event(0))?.is_none());
+        assert!(try_enqueue(&mut op, test_event(1))?.is_none());
+        let (out, event) =
+            try_enqueue(&mut op, test_event(15))?.expect("no event emitted after aggregation");
+        assert_eq!("out", out);
 
-                            // execute the pending accumulate (if window_event.include == false of first window)
-                            if pending_accumulate {
-                                if let Some(first_window) = windows.first_mut() {
-                                    consts.window = Value::from(first_window.name.to_string());
-                                    // get current window group
-                                    let this_group = stry!(get_or_create_group(
-                                        first_window,
-                                        event_id_gen,
-                                        aggregates,
-                                        &group_str,
-                                        &group_value,
-                                    ));
+        assert_eq!(*event.data.suffix().value(), 84.0);
+        Ok(())
+    }
 
-                                    // accumulate the current event
-                                    let env = Env {
-                                        context: &ctx,
-                                        consts: consts.run(),
-                                        aggrs: &NO_AGGRS,
-                                        meta: &node_meta,
-                                        recursion_limit: tremor_script::recursion_limit(),
-                                    };
-                                    stry!(accumulate(
-                                        opts,
-                                        &node_meta,
-                                        &env,
-                                        &local_stack,
-                                        state,
-                                        this_group,
-                                        data,
-                                        id,
-                                        transactional
-                                                ));
-                                }
-                            }
+    #[test]
+    fn test_count() -> Result<()> {
+        let mut op = parse_query(
+            "test.trickle".to_string(),
+            "select aggr::stats::count() from in[w15s, w30s] into out;",
+        )?;
+        assert!(try_enqueue(&mut op, test_event(0))?.is_none());
+        assert!(try_enqueue(&mut op, test_event(1))?.is_none());
+        let (out, event) = try_enqueue(&mut op, test_event(15))?.expect("no event");
+        assert_eq!("out", out);
 
-                            // merge state from last emit window into next non-emit window if there is any
-                            if !emit_window_events.is_empty() {
-                                if let Some(non_emit_window) =
-                                   windows.get_mut(emit_window_events.len())
-                                {
-                                    consts.window = Value::from(non_emit_window.name.to_string());
+        assert_eq!(*event.data.suffix().value(), 2);
+        Ok(())
+    }
 
-                                    // get current window group
-                                    let this_group = stry!(get_or_create_group(
-                                        non_emit_window,
-                                        event_id_gen,
-                                        aggregates,
-                                        &group_str,
-                                        &group_value,
-                                    ));
-                                    for (this, prev) in
-                                        this_group.aggrs.iter_mut().zip(scratch1.aggregates.iter())
-                                    {
-                                        this.invocable.merge(&prev.invocable).map_err(|e| {
-                                            let r: Option<&Registry> = None;
-                                            e.into_err(prev, prev, r, &node_meta)
-                                        })?;
-                                    }
-                                    this_group.track_transactional(scratch1.transactional);
-                                    this_group.id.track(&event_id_scratch1);
-                                }
-                            }
-                        } else {
-                            // if this happens, we fucked up internally.
-                            return Err("Internal Error. Cannot execute select with multiple windows, no scratches available. This is bad!".into());
-                        }
-                    }
-                }
-            }
-            Ok(Res::Data(events.into()))
+    fn select_stmt_from_query(query_str: &str) -> Result<TrickleSelect> {
+        let reg = tremor_script::registry();
+        let aggr_reg = tremor_script::aggr_registry();
+        let module_path = tremor_script::path::load();
+        let cus = vec![];
+        let query = tremor_script::query::Query::parse(
+            &module_path,
+            "fake",
+            query_str,
+            cus,
+            &reg,
+            &aggr_reg,
+        )
+        .map_err(tremor_script::errors::CompilerError::error)?;
+        let window_decls: Vec<WindowDecl<'_>> = query
+            .suffix()
+            .stmts
+            .iter()
+            .filter_map(|stmt| match stmt {
+                Stmt::WindowDecl(wd) => Some(wd.as_ref().clone()),
+                _ => None,
+            })
+            .collect();
+        let stmt = srs::Stmt::try_new_from_query(&query.query, |q| {
+            q.stmts
+                .iter()
+                .find(|stmt| matches!(*stmt, Stmt::Select(_)))
+                .cloned()
+                .ok_or_else(|| Error::from("Invalid query, expected only 1 select statement"))
         })?;
+        let windows: Vec<(String, WindowImpl)> = window_decls
+            .iter()
+            .enumerate()
+            .map(|(i, window_decl)| {
+                (
+                    i.to_string(),
+                    window_decl_to_impl(window_decl).unwrap(), // yes, indeed!
+                )
+            })
+            .collect();
+        let groups = Groups::new();
 
-        match res {
-            Res::Event => Ok(event.into()),
-            Res::None => Ok(EventAndInsights::default()),
-            Res::Data(data) => Ok(data),
+        let id = "select".to_string();
+        Ok(TrickleSelect::with_stmt(42, id, &groups, windows, &stmt)?)
+    }
+
+    fn test_tick(ns: u64) -> Event {
+        Event {
+            id: EventId::new(1, 1, ns),
+            kind: Some(SignalKind::Tick),
+            ingest_ns: ns,
+            ..Event::default()
         }
     }
 
-    #[allow(clippy::too_many_lines)]
-    fn on_signal(
-        &mut self,
-        _uid: u64,
-        state: &Value<'static>, // we only reference state here immutably, no chance to change it here
-        signal: &mut Event,
-    ) -> Result<EventAndInsights> {
-        // we only react on ticks and when we have windows
-        let Self {
-            select,
-            windows,
-            event_id_gen,
-            ..
-        } = self;
+    #[test]
+    fn select_single_win_with_script_on_signal() -> Result<()> {
+        let mut select = select_stmt_from_query(
+            r#"
+        define tumbling window window1
+        with
+            interval = 5
+        script
+            event.time
+        end;
+        select aggr::stats::count() from in[window1] group by event.g into out having event > 0;
+        "#,
+        )?;
+        let uid = 42;
+        let mut state = Value::null();
+        let mut tick1 = test_tick(1);
+        let mut eis = select.on_signal(uid, &state, &mut tick1)?;
+        assert!(eis.insights.is_empty());
+        assert_eq!(0, eis.events.len());
 
-        if signal.kind == Some(SignalKind::Tick) && !windows.is_empty() {
-            // Handle eviction
-            // retire the group data that didnt receive an event in `eviction_ns()` nanoseconds
-            // if no event came after `2 * eviction_ns()` this group is finally cleared out
-            for window in windows.iter_mut() {
-                if let Some(eviction_ns) = window.window_impl.eviction_ns() {
-                    if window.next_swap < signal.ingest_ns {
-                        window.next_swap = signal.ingest_ns + eviction_ns;
-                        window.last_dims.clear();
-                        std::mem::swap(&mut window.dims, &mut window.last_dims);
-                    }
-                }
-            }
+        let event = Event {
+            id: (1, 1, 300).into(),
+            ingest_ns: 1_000,
+            data: literal!({
+               "time" : 4,
+               "g": "group"
+            })
+            .into(),
+            ..Event::default()
+        };
+        eis = select.on_event(uid, "IN", &mut state, event)?;
+        assert!(eis.insights.is_empty());
+        assert_eq!(0, eis.events.len());
 
-            let ingest_ns = signal.ingest_ns;
-            let opts = Self::opts();
-            select.rent_mut(|stmt| {
-            let SelectStmt {
-                stmt,
-                aggregates,
-                aggregate_scratches,
-                consts,
-                locals,
-                node_meta,
-            }  = stmt;
-            let mut res = EventAndInsights::default();
+        // no emit on signal, although window interval would be passed
+        let mut tick2 = test_tick(10);
+        eis = select.on_signal(uid, &state, &mut tick2)?;
+        assert!(eis.insights.is_empty());
+        assert_eq!(0, eis.events.len());
 
-            let vm: ValueAndMeta = (Value::null(), Value::object()).into();
-            let op_meta = OpMeta::default();
-            let local_stack = tremor_script::interpreter::LocalStack::with_size(*locals);
+        // only emit on event
+        let event = Event {
+            id: (1, 1, 300).into(),
+            ingest_ns: 3_000,
+            data: literal!({
+               "time" : 11,
+               "g": "group"
+            })
+            .into(),
+            ..Event::default()
+        };
+        eis = select.on_event(uid, "IN", &mut state, event)?;
+        assert!(eis.insights.is_empty());
+        assert_eq!(1, eis.events.len());
+        assert_eq!("1", sorted_serialize(eis.events[0].1.data.parts().0)?);
+        Ok(())
+    }
 
-            consts.window = Value::null();
-            consts.group = Value::null();
-            consts.args = Value::null();
+    #[test]
+    fn select_single_win_on_signal() -> Result<()> {
+        let mut select = select_stmt_from_query(
+            r#"
+        define tumbling window window1
+        with
+            interval = 2
+        end;
+        select aggr::win::collect_flattened(event) from in[window1] group by event.g into out;
+        "#,
+        )?;
+        let uid = 42;
+        let mut state = Value::null();
+        let mut tick1 = test_tick(1);
+        let mut eis = select.on_signal(uid, &state, &mut tick1)?;
+        assert!(eis.insights.is_empty());
+        assert_eq!(0, eis.events.len());
 
-            match windows.len() {
-                0 => {} // we shouldnt get here
-                1 => {
-                    let ctx = EventContext::new(signal.ingest_ns, None);
-                    for window in windows.iter_mut() {
-                        consts.window = Value::from(window.name.to_string());
-                        // iterate all groups, including the ones from last_dims
-                        for (group_str, group_data) in window
-                            .dims
-                            .iter_mut()
-                            .chain(window.last_dims.iter_mut())
-                        {
-                            consts.group = group_data.group.clone_static();
-                            consts.group.push(group_str.clone())?;
+        let event = Event {
+            id: (1, 1, 300).into(),
+            ingest_ns: 2,
+            data: literal!({
+               "g": "group"
+            })
+            .into(),
+            ..Event::default()
+        };
+        eis = select.on_event(uid, "IN", &mut state, event)?;
+        assert!(eis.insights.is_empty());
+        assert_eq!(0, eis.events.len());
 
-                            let window_event = stry!(group_data.window.on_tick(ingest_ns));
-                            if window_event.emit {
-                                // evaluate the event and push
-                                let env = Env {
-                                    context: &ctx,
-                                    consts: consts.run(),
-                                    aggrs: &group_data.aggrs,
-                                    meta: &node_meta,
-                                    recursion_limit: tremor_script::recursion_limit(),
-                                };
-                                let mut outgoing_event_id = event_id_gen.next_id();
-                                mem::swap(&mut group_data.id, &mut outgoing_event_id);
-                                let executed = stry!(execute_select_and_having(
-                                    &stmt,
-                                    node_meta,
-                                    opts,
-                                    &local_stack,
-                                    state,
-                                    &env,
-                                    outgoing_event_id,
-                                    &vm,
-                                    ingest_ns,
-                                    &op_meta,
-                                    None,
-                                    group_data.transactional
-                                ));
-                                if let Some(port_and_event) = executed {
-                                    res.events.push(port_and_event);
-                                }
+        // no emit yet
+        let mut tick2 = test_tick(3);
+        eis = select.on_signal(uid, &state, &mut tick2)?;
+        assert!(eis.insights.is_empty());
+        assert_eq!(0, eis.events.len());
 
-                                // init aggregates and reset transactional status
-                                group_data.reset();
-                            }
-                        }
-                    }
-                }
-                _ => {
-                    // complicated logic
-                    // get a set of groups
-                    //   for each window
-                    //      get the corresponding group data
-                    //      on_event ... do the magic dance with scratches etc
-                    // we need scratches for multiple window handling
+        // now emit
+        let mut tick3 = test_tick(4);
+        eis = select.on_signal(uid, &state, &mut tick3)?;
+        assert!(eis.insights.is_empty());
+        assert_eq!(1, eis.events.len());
+        assert_eq!(
+            r#"[{"g":"group"}]"#,
+            sorted_serialize(eis.events[0].1.data.parts().0)?
+        );
+        assert_eq!(false, eis.events[0].1.transactional);
+        Ok(())
+    }
 
-                    let ctx = EventContext::new(ingest_ns, None);
+    #[test]
+    fn select_multiple_wins_on_signal() -> Result<()> {
+        let mut select = select_stmt_from_query(
+            r#"
+        define tumbling window window1
+        with
+            interval = 100
+        end;
+        define tumbling window window2
+        with
+            size = 2
+        end;
+        select aggr::win::collect_flattened(event) from in[window1, window2] group by event.cat into out;
+        "#,
+        )?;
+        let uid = 42;
+        let mut state = Value::null();
+        let mut tick1 = test_tick(1);
+        let mut eis = select.on_signal(uid, &state, &mut tick1)?;
+        assert!(eis.insights.is_empty());
+        assert_eq!(0, eis.events.len());
 
-                    // we get away with just checking the first window as there can be no group in a further window
-                    // that is not also in the first.
-                    let distinct_groups: Vec<(&String, &Value)> = windows
-                        .first()
-                        .iter()
-                        .flat_map(|window| {
-                            (*window)
-                                .dims
-                                .iter()
-                                .chain(window.last_dims.iter())
-                        })
-                        .map(|(group_str, group_data)| {
-                            // trick the borrow checker, so we can reference self.windows mutably below
-                            // this is safe, as we only ever work on group_data.window and group_data.aggregates
-                            // we might move a group_data struct from one map to another, but there is no deletion
-                            // we never touch neither the group value nor the group_str during the fiddling below
-                            // ALLOW: https://github.com/tremor-rs/tremor-runtime/issues/1027
-                            let group_str: &'static String = unsafe { std::mem::transmute(group_str) };
-                            let group_value: &'static Value =
-                            // ALLOW: https://github.com/tremor-rs/tremor-runtime/issues/1027
-                                unsafe { std::mem::transmute(&group_data.group) }; // lose the reference to window
-                            (group_str, group_value)
-                        })
-                        .collect();
+        let mut tick2 = test_tick(100);
+        eis = select.on_signal(uid, &state, &mut tick2)?;
+        assert!(eis.insights.is_empty());
+        assert_eq!(0, eis.events.len());
 
-                    for (group_str, group_value) in distinct_groups {
-                        consts.group = group_value.clone_static();
-                        consts.group.push(group_str.clone())?;
+        // we have no groups, so no emit yet
+        let mut tick3 = test_tick(201);
+        eis = select.on_signal(uid, &state, &mut tick3)?;
+        assert!(eis.insights.is_empty());
+        assert_eq!(0, eis.events.len());
 
-                        if let Some((scratch1, scratch2)) = aggregate_scratches.as_mut() {
-                            // scratches for passing on the event id between windows to track all events contributing to an outgoing windowed event
-                            let mut event_id_scratch1 = EventId::default();
-                            let mut event_id_scratch2 = EventId::default();
+        // insert first event
+        let event = Event {
+            id: (1, 1, 300).into(),
+            ingest_ns: 300,
+            data: literal!({
+               "cat" : 42,
+            })
+            .into(),
+            transactional: true,
+            ..Event::default()
+        };
+        eis = select.on_event(uid, "IN", &mut state, event)?;
+        assert!(eis.insights.is_empty());
+        assert_eq!(0, eis.events.len());
 
-                            // gather window events
-                            let mut emit_window_events = Vec::with_capacity(windows.len());
-                            let step1_iter = windows.iter_mut();
-                            for window in step1_iter {
-                                consts.window = Value::from(window.name.to_string());
-                                // get current window group
-                                let this_group = stry!(get_or_create_group(
-                                    window,
-                                    event_id_gen,
-                                    aggregates,
-                                    group_str,
-                                    group_value,
-                                ));
-                                let window_event = stry!(this_group.window.on_tick(ingest_ns));
-                                if window_event.emit {
-                                    emit_window_events.push(window_event);
-                                } else {
-                                    break;
-                                }
-                            }
+        // finish the window with the next tick
+        let mut tick4 = test_tick(401);
+        eis = select.on_signal(uid, &state, &mut tick4)?;
+        assert!(eis.insights.is_empty());
+        assert_eq!(1, eis.events.len());
+        let (_port, event) = eis.events.remove(0);
+        assert_eq!(r#"[{"cat":42}]"#, sorted_serialize(event.data.parts().0)?);
+        assert_eq!(true, event.transactional);
 
-                            // merge previous aggr state, push window event out, re-initialize for new window
-                            let mut first = true;
-                            let emit_window_iter =
-                                emit_window_events.iter().zip(windows.iter_mut());
-                            for (window_event, window) in emit_window_iter {
-                                consts.window = Value::from(window.name.to_string());
+        let mut tick5 = test_tick(499);
+        eis = select.on_signal(uid, &state, &mut tick5)?;
+        assert!(eis.insights.is_empty());
+        assert_eq!(0, eis.events.len());
+
+        Ok(())
+    }
 
-                                // get current window group
-                                let this_group = stry!(get_or_create_group(
-                                    window,
-                                    event_id_gen,
-                                    aggregates,
-                                    group_str,
-                                    group_value,
-                                ));
+    #[test]
+    fn test_transactional_single_window() -> Result<()> {
+        let mut op = select_stmt_from_query(
+            r#"
+            define tumbling window w2
+            with
+              size = 2
+            end;
+            select aggr::stats::count() from in[w2] into out;
+        "#,
+        )?;
+        let mut state = Value::null();
+        let event1 = test_event_tx(0, false, 0);
+        let id1 = event1.id.clone();
+        let res = op.on_event(0, "in", &mut state, event1)?;
 
-                                if window_event.include {
-                                    // add this event to the aggr state **BEFORE** emit and propagate to next windows
-                                    // merge with scratch
-                                    if !first {
-                                        for (this, prev) in this_group
-                                            .aggrs
-                                            .iter_mut()
-                                            .zip(scratch1.aggregates.iter())
-                                        {
-                                            this.invocable.merge(&prev.invocable).map_err(|e| {
-                                                let r: Option<&Registry> = None;
-                                                e.into_err(prev, prev, r, &node_meta)
-                                            })?;
-                                        }
-                                        this_group.track_transactional(scratch1.transactional);
-                                        this_group.id.track(&event_id_scratch1);
-                                    }
-                                    // push event
-                                    let env = Env {
-                                        context: &ctx,
-                                        consts: consts.run(),
-                                        aggrs: &this_group.aggrs,
-                                        meta: &node_meta,
-                                        recursion_limit: tremor_script::recursion_limit(),
-                                    };
+        assert!(res.events.is_empty());
 
-                                    let mut outgoing_event_id = event_id_gen.next_id();
-                                    event_id_scratch1 = this_group.id.clone(); // store event_id in a scratch for tracking it in the next window in the tilt-frame
-                                    mem::swap(&mut this_group.id, &mut outgoing_event_id);
-                                    if let Some(port_and_event) = stry!(execute_select_and_having(
-                                        &stmt,
-                                        &node_meta,
-                                        opts,
-                                        &local_stack,
-                                        state,
-                                        &env,
-                                        outgoing_event_id,
-                                        &vm,
-                                        ingest_ns,
-                                        &op_meta,
-                                            None,
-                                        this_group.transactional
-                                    )) {
-                                        res.events.push(port_and_event);
-                                    };
+        let event2 = test_event_tx(1, true, 0);
+        let id2 = event2.id.clone();
+        let mut res = op.on_event(0, "in", &mut state, event2)?;
+        assert_eq!(1, res.events.len());
+        let (_, event) = res.events.pop().unwrap();
+        assert_eq!(true, event.transactional);
+        assert_eq!(true, event.id.is_tracking(&id1));
+        assert_eq!(true, event.id.is_tracking(&id2));
 
-                                    // swap(aggrs, scratch) - store state before init to scratch as we need it for the next window
-                                    std::mem::swap(&mut scratch1.aggregates, &mut this_group.aggrs);
-                                    scratch1.transactional = this_group.transactional;
-                                    // aggrs.init() and reset transactional state
-                                    this_group.reset();
-                                } else {
-                                    // add this event to the aggr state **AFTER** emit and propagate to next windows
+        let event3 = test_event_tx(2, false, 0);
+        let id3 = event3.id.clone();
+        let res = op.on_event(0, "in", &mut state, event3)?;
+        assert!(res.events.is_empty());
 
-                                    // push event
-                                    let env = Env {
-                                        context: &ctx,
-                                        consts: consts.run(),
-                                        aggrs: &this_group.aggrs,
-                                        meta: &node_meta,
-                                        recursion_limit: tremor_script::recursion_limit(),
-                                    };
+        let event4 = test_event_tx(3, false, 0);
+        let id4 = event4.id.clone();
+        let mut res = op.on_event(0, "in", &mut state, event4)?;
+        assert_eq!(1, res.events.len());
+        let (_, event) = res.events.pop().unwrap();
+        assert_eq!(false, event.transactional);
+        assert_eq!(true, event.id.is_tracking(&id3));
+        assert_eq!(true, event.id.is_tracking(&id4));
 
-                                    let mut outgoing_event_id = event_id_gen.next_id();
-                                    if !first {
-                                        // store previous window event_id in scratch2
-                                        std::mem::swap(
-                                            &mut event_id_scratch1,
-                                            &mut event_id_scratch2,
-                                        );
-                                    }
-                                    event_id_scratch1 = this_group.id.clone(); // store event id before swapping it out, for tracking in next window
-                                    mem::swap(&mut this_group.id, &mut outgoing_event_id);
-                                    if let Some(port_and_event) = stry!(execute_select_and_having(
-                                        &stmt,
-                                        &node_meta,
-                                        opts,
-                                        &local_stack,
-                                        state,
-                                        &env,
-                                        outgoing_event_id,
-                                        &vm,
-                                        ingest_ns,
-                                        &op_meta,
-                                            None,
-                                        this_group.transactional
-                                    )) {
-                                        res.events.push(port_and_event);
-                                    };
+        Ok(())
+    }
 
-                                    if first {
-                                        // first window
-                                        //          swap(scratch, aggrs)
-                                        std::mem::swap(
-                                            &mut scratch1.aggregates,
-                                            &mut this_group.aggrs,
-                                        ); // store current state before init
-                                        scratch1.transactional = this_group.transactional;
-                                        //          aggrs.init() and reset transactional state
-                                        this_group.reset();
-                                    } else {
-                                        // not first window
-                                        // store old aggrs state from last window into scratch2
-                                        std::mem::swap(scratch1, scratch2);
-                                        //          swap(aggrs, scratch) // store aggrs state before init() into scratch1 for next window
-                                        std::mem::swap(
-                                            &mut this_group.aggrs,
-                                            &mut scratch1.aggregates,
-                                        );
-                                        scratch1.transactional = this_group.transactional;
+    #[test]
+    fn test_transactional_multiple_windows() -> Result<()> {
+        let mut op = select_stmt_from_query(
+            r#"
+            define tumbling window w2_1
+            with
+              size = 2
+            end;
+            define tumbling window w2_2
+            with
+              size = 2
+            end;
+            select aggr::win::collect_flattened(event) from in[w2_1, w2_2] group by set(event["group"]) into out;
+        "#,
+        )?;
+        let mut state = Value::null();
+        let event1 = test_event_tx(0, true, 0);
+        let id1 = event1.id.clone();
+        let res = op.on_event(0, "in", &mut state, event1)?;
+        assert_eq!(0, res.len());
 
-                                        //          aggrs.init() and reset transactional state
-                                        this_group.reset();
-                                        //          merge state from last window into this one
-                                        for (this, prev) in this_group
-                                            .aggrs
-                                            .iter_mut()
-                                            .zip(scratch2.aggregates.iter())
-                                        {
-                                            this.invocable.merge(&prev.invocable).map_err(|e| {
-                                                let r: Option<&Registry> = None;
-                                                e.into_err(prev, prev, r, &node_meta)
-                                            })?;
-                                        }
-                                        this_group.track_transactional(scratch2.transactional);
-                                        this_group.id.track(&event_id_scratch2);
-                                    }
-                                }
+        let event2 = test_event_tx(1, false, 1);
+        let id2 = event2.id.clone();
+        let res = op.on_event(0, "in", &mut state, event2)?;
+        assert_eq!(0, res.len());
 
-                                first = false;
-                            }
+        let event3 = test_event_tx(2, false, 0);
+        let id3 = event3.id.clone();
+        let mut res = op.on_event(0, "in", &mut state, event3)?;
+        assert_eq!(1, res.len());
+        let (_, event) = res.events.pop().unwrap();
+        assert_eq!(true, event.transactional);
+        assert_eq!(true, event.id.is_tracking(&id1));
+        assert_eq!(true, event.id.is_tracking(&id3));
 
-                            // merge state from last emit window into next non-emit window if there is any
-                            if !emit_window_events.is_empty() {
-                                if let Some(non_emit_window) =
-                                    windows.get_mut(emit_window_events.len())
-                                {
-                                    consts.window = Value::from(non_emit_window.name.to_string());
+        let event4 = test_event_tx(3, false, 1);
+        let id4 = event4.id.clone();
+        let mut res = op.on_event(0, "in", &mut state, event4)?;
+        assert_eq!(1, res.len());
+        let (_, event) = res.events.remove(0);
+        assert_eq!(false, event.transactional);
+        assert_eq!(true, event.id.is_tracking(&id2));
+        assert_eq!(true, event.id.is_tracking(&id4));
 
-                                    // get current window group
-                                    let this_group = stry!(get_or_create_group(
-                                        non_emit_window,
-                                        event_id_gen,
-                                        aggregates,
-                                        group_str,
-                                        group_value,
-                                    ));
-                                    for (this, prev) in
-                                        this_group.aggrs.iter_mut().zip(scratch1.aggregates.iter())
-                                    {
-                                        this.invocable.merge(&prev.invocable).map_err(|e| {
-                                            let r: Option<&Registry> = None;
-                                            e.into_err(prev, prev, r, &node_meta)
-                                        })?;
-                                    }
-                                    this_group.track_transactional(scratch1.transactional);
-                                    this_group.id.track(&event_id_scratch1);
-                                }
-                            }
-                        } else {
-                            // if this happens, we fucked up internally.
-                            return Err("Internal Error. Cannot execute select with multiple windows, no scratches available. This is bad!".into());
-                        }
-                    }
-                }
-            }
-            Ok(res)
-        })
-        } else {
-            Ok(EventAndInsights::default())
-        }
-    }
+        let event5 = test_event_tx(4, false, 0);
+        let id5 = event5.id.clone();
+        let res = op.on_event(0, "in", &mut state, event5)?;
+        assert_eq!(0, res.len());
+
+        let event6 = test_event_tx(5, false, 0);
+        let id6 = event6.id.clone();
+        let mut res = op.on_event(0, "in", &mut state, event6)?;
+        assert_eq!(2, res.len());
 
-    fn handles_signal(&self) -> bool {
-        true
+        // first event from event5 and event6 - none of the source events are transactional
+        let (_, event_w1) = res.events.remove(0);
+        assert_eq!(false, event_w1.transactional);
+        assert_eq!(true, event_w1.id.is_tracking(&id5));
+        assert_eq!(true, event_w1.id.is_tracking(&id6));
+
+        let (_, event_w2) = res.events.remove(0);
+        assert_eq!(true, event_w2.transactional);
+        assert_eq!(true, event_w2.id.is_tracking(&id1));
+        assert_eq!(true, event_w2.id.is_tracking(&id3));
+        assert_eq!(true, event_w2.id.is_tracking(&id5));
+        assert_eq!(true, event_w2.id.is_tracking(&id6));
+
+        Ok(())
     }
-}
 
-#[cfg(test)]
-mod test {
+    #[test]
+    fn count_tilt() -> Result<()> {
+        // Windows are 15s and 30s
+        let mut op = select_stmt_from_query(
+            r#"
+        define tumbling window w15s
+        with
+          interval = 15 * 1000000000
+        end;
+        define tumbling window w30s
+        with
+          interval = 30 * 1000000000
+        end;
+        select aggr::stats::count() from in [w15s, w30s] into out;
+        "#,
+        )?;
 
-    #![allow(clippy::float_cmp)]
-    use crate::query::window_decl_to_impl;
+        // Insert two events prior to 15
+        assert!(try_enqueue(&mut op, test_event(0))?.is_none());
+        assert!(try_enqueue(&mut op, test_event(1))?.is_none());
+        // Add one event at 15, this flushes the prior two and set this to one.
+        // This is the first time the second frame sees an event so it's timer
+        // starts at 15.
+        let (out, event) = try_enqueue(&mut op, test_event(15))?.expect("no event 1");
+        assert_eq!("out", out);
+        assert_eq!(*event.data.suffix().value(), 2);
+        // Add another event prior to 30
+        assert!(try_enqueue(&mut op, test_event(16))?.is_none());
+        // This emits only the initial event since the rollup
+        // will only be emitted once it gets the first event
+        // of the next window
+        let (out, event) = try_enqueue(&mut op, test_event(30))?.expect("no event 2");
+        assert_eq!("out", out);
+        assert_eq!(*event.data.suffix().value(), 2);
+        // Add another event prior to 45 to the first window
+        assert!(try_enqueue(&mut op, test_event(31))?.is_none());
 
-    use super::*;
+        // At 45 the 15s window emits the rollup with the previous data
+        let [(out1, event1), (out2, event2)] =
+            try_enqueue_two(&mut op, test_event(45))?.expect("no event 3");
 
-    use tremor_script::ast::Stmt;
-    use tremor_script::{
-        ast::{self, Ident, ImutExpr, Literal},
-        path::ModulePath,
-    };
-    use tremor_script::{
-        ast::{AggregateScratch, Consts},
-        Value,
-    };
-    use tremor_value::literal;
+        assert_eq!("out", out1);
+        assert_eq!("out", out2);
+        assert_eq!(*event1.data.suffix().value(), 2);
+        assert_eq!(*event2.data.suffix().value(), 4);
+        assert!(try_enqueue(&mut op, test_event(46))?.is_none());
 
-    fn test_target<'test>() -> ast::ImutExpr<'test> {
-        let target: ast::ImutExpr<'test> = ImutExpr::from(ast::Literal {
-            mid: 0,
-            value: Value::from(42),
-        });
-        target
+        // Add 60 only the 15s window emits
+        let (out, event) = try_enqueue(&mut op, test_event(60))?.expect("no event 4");
+        assert_eq!("out", out);
+        assert_eq!(*event.data.suffix().value(), 2);
+        Ok(())
     }
 
-    fn test_stmt(target: ast::ImutExpr) -> ast::Select {
-        tremor_script::ast::Select {
-            mid: 0,
-            from: (Ident::from("in"), Ident::from("out")),
-            into: (Ident::from("out"), Ident::from("in")),
-            target,
-            maybe_where: Some(ImutExpr::from(ast::Literal {
-                mid: 0,
-                value: Value::from(true),
-            })),
-            windows: vec![],
-            maybe_group_by: None,
-            maybe_having: None,
-        }
-    }
+    #[test]
+    fn select_nowin_nogrp_nowhr_nohav() -> Result<()> {
+        let target = test_target();
+        let stmt_ast = test_stmt(target);
 
-    fn test_query(stmt: ast::Stmt) -> ast::Query {
-        ast::Query {
-            stmts: vec![stmt.clone()],
-            node_meta: ast::NodeMetas::new(Vec::new()),
-            windows: HashMap::new(),
-            scripts: HashMap::new(),
-            operators: HashMap::new(),
-            config: HashMap::new(),
-        }
-    }
+        let stmt_ast = test_select_stmt(stmt_ast);
+        let script = "fake".to_string();
+        let query = srs::Query::try_new::<Error, _>(script, |_| Ok(test_query(stmt_ast.clone())))?;
 
-    fn ingest_ns(s: u64) -> u64 {
-        s * 1_000_000_000
-    }
-    fn test_event(s: u64) -> Event {
-        Event {
-            id: (0, 0, s).into(),
-            ingest_ns: ingest_ns(s),
-            data: literal!({
-               "h2g2" : 42,
-            })
-            .into(),
-            ..Event::default()
-        }
-    }
+        let stmt = srs::Stmt::try_new_from_query::<Error, _>(&query, |_| Ok(stmt_ast))?;
 
-    fn test_event_tx(s: u64, transactional: bool, group: u64) -> Event {
-        Event {
-            id: (0, 0, s).into(),
-            ingest_ns: s + 100,
-            data: literal!({ "group": group }).into(),
-            transactional,
-            ..Event::default()
-        }
-    }
+        let mut op = test_select(1, stmt)?;
+        assert!(try_enqueue(&mut op, test_event(0))?.is_none());
+        assert!(try_enqueue(&mut op, test_event(1))?.is_none());
+        let (out, event) = try_enqueue(&mut op, test_event(15))?.expect("no event");
+        assert_eq!("out", out);
 
-    fn test_select(uid: u64, stmt: srs::Stmt) -> Result<TrickleSelect> {
-        let groups = Groups::new();
-        let windows = vec![
-            (
-                "w15s".into(),
-                TumblingWindowOnTime {
-                    emit_empty_windows: false,
-                    ttl: None,
-                    max_groups: WindowImpl::DEFAULT_MAX_GROUPS,
-                    interval: 15_000_000_000,
-                    next_window: None,
-                    events: 0,
-                    script: None,
-                }
-                .into(),
-            ),
-            (
-                "w30s".into(),
-                TumblingWindowOnTime {
-                    emit_empty_windows: false,
-                    ttl: None,
-                    max_groups: WindowImpl::DEFAULT_MAX_GROUPS,
-                    interval: 30_000_000_000,
-                    next_window: None,
-                    events: 0,
-                    script: None,
-                }
-                .into(),
-            ),
-        ];
-        let id = "select".to_string();
-        TrickleSelect::with_stmt(uid, id, &groups, windows, &stmt)
+        assert_eq!(*event.data.suffix().value(), 42);
+        Ok(())
     }
 
-    fn try_enqueue(
-        op: &mut TrickleSelect,
-        event: Event,
-    ) -> Result<Option<(Cow<'static, str>, Event)>> {
-        let mut state = Value::null();
-        let mut action = op.on_event(0, "in", &mut state, event)?;
-        let first = action.events.pop();
-        if action.events.is_empty() {
-            Ok(first)
-        } else {
-            Ok(None)
-        }
-    }
+    #[test]
+    fn select_nowin_nogrp_whrt_nohav() -> Result<()> {
+        let target = test_target();
+        let mut stmt_ast = test_stmt(target);
 
-    fn try_enqueue_two(
-        op: &mut TrickleSelect,
-        event: Event,
-    ) -> Result<Option<[(Cow<'static, str>, Event); 2]>> {
-        let mut state = Value::null();
-        let mut action = op.on_event(0, "in", &mut state, event)?;
-        let r = action
-            .events
-            .pop()
-            .and_then(|second| Some([action.events.pop()?, second]));
-        if action.events.is_empty() {
-            Ok(r)
-        } else {
-            Ok(None)
-        }
-    }
+        stmt_ast.maybe_where = Some(ImutExpr::from(ast::Literal {
+            mid: 0,
+            value: Value::from(true),
+        }));
 
-    fn parse_query(
-        file_name: String,
-        query: &str,
-    ) -> Result<crate::op::trickle::select::TrickleSelect> {
-        let reg = tremor_script::registry();
-        let aggr_reg = tremor_script::aggr_registry();
-        let module_path = tremor_script::path::load();
-        let cus = vec![];
-        let query = tremor_script::query::Query::parse(
-            &module_path,
-            &file_name,
-            query,
-            cus,
-            &reg,
-            &aggr_reg,
-        )
-        .map_err(tremor_script::errors::CompilerError::error)?;
-        let stmt = srs::Stmt::try_new_from_query(&query.query, |q| {
-            q.stmts
-                .first()
-                .cloned()
-                .ok_or_else(|| Error::from("Invalid query"))
-        })?;
-        Ok(test_select(1, stmt)?)
-    }
+        let stmt_ast = test_select_stmt(stmt_ast);
+        let script = "fake".to_string();
+        let query = srs::Query::try_new::<Error, _>(script, |_| Ok(test_query(stmt_ast.clone())))?;
+
+        let stmt = srs::Stmt::try_new_from_query::<Error, _>(&query, |_| Ok(stmt_ast))?;
+
+        let mut op = test_select(2, stmt)?;
 
-    #[test]
-    fn test_sum() -> Result<()> {
-        let mut op = parse_query(
-            "test.trickle".to_string(),
-            "select aggr::stats::sum(event.h2g2) from in[w15s, w30s] into out;",
-        )?;
         assert!(try_enqueue(&mut op, test_event(0))?.is_none());
-        assert!(try_enqueue(&mut op, test_event(1))?.is_none());
-        let (out, event) =
-            try_enqueue(&mut op, test_event(15))?.expect("no event emitted after aggregation");
+
+        let (out, event) = try_enqueue(&mut op, test_event(15))?.expect("no event");
         assert_eq!("out", out);
 
-        assert_eq!(*event.data.suffix().value(), 84.0);
+        assert_eq!(*event.data.suffix().value(), 42);
         Ok(())
     }
 
     #[test]
-    fn test_count() -> Result<()> {
-        let mut op = parse_query(
-            "test.trickle".to_string(),
-            "select aggr::stats::count() from in[w15s, w30s] into out;",
-        )?;
-        assert!(try_enqueue(&mut op, test_event(0))?.is_none());
-        assert!(try_enqueue(&mut op, test_event(1))?.is_none());
-        let (out, event) = try_enqueue(&mut op, test_event(15))?.expect("no event");
-        assert_eq!("out", out);
+    fn select_nowin_nogrp_whrf_nohav() -> Result<()> {
+        let target = test_target();
+        let mut stmt_ast = test_stmt(target);
 
-        assert_eq!(*event.data.suffix().value(), 2);
+        let script = "fake".to_string();
+        stmt_ast.maybe_where = Some(ImutExpr::from(ast::Literal {
+            mid: 0,
+            value: Value::from(false),
+        }));
+        let stmt_ast = test_select_stmt(stmt_ast);
+        let query = srs::Query::try_new::<Error, _>(script, |_| Ok(test_query(stmt_ast.clone())))?;
+
+        let stmt = srs::Stmt::try_new_from_query::<Error, _>(&query, |_| Ok(stmt_ast))?;
+
+        let mut op = test_select(3, stmt)?;
+        let next = try_enqueue(&mut op, test_event(0))?;
+        assert_eq!(None, next);
         Ok(())
     }
 
-    fn select_stmt_from_query(query_str: &str) -> Result<TrickleSelect> {
-        let reg = tremor_script::registry();
-        let aggr_reg = tremor_script::aggr_registry();
-        let module_path = tremor_script::path::load();
-        let cus = vec![];
-        let query = tremor_script::query::Query::parse(
-            &module_path,
-            "fake",
-            query_str,
-            cus,
-            &reg,
-            &aggr_reg,
-        )
-        .map_err(tremor_script::errors::CompilerError::error)?;
-        let window_decls: Vec<WindowDecl<'_>> = query
-            .suffix()
-            .stmts
-            .iter()
-            .filter_map(|stmt| match stmt {
-                Stmt::WindowDecl(wd) => Some(wd.as_ref().clone()),
-                _ => None,
-            })
-            .collect();
-        let stmt = srs::Stmt::try_new_from_query(&query.query, |q| {
-            q.stmts
-                .iter()
-                .find(|stmt| matches!(*stmt, Stmt::Select(_)))
-                .cloned()
-                .ok_or_else(|| Error::from("Invalid query, expected only 1 select statement"))
-        })?;
-        let windows: Vec<(String, WindowImpl)> = window_decls
-            .iter()
-            .enumerate()
-            .map(|(i, window_decl)| {
-                (
-                    i.to_string(),
-                    window_decl_to_impl(window_decl).unwrap(), // yes, indeed!
-                )
-            })
-            .collect();
-        let groups = Groups::new();
+    #[test]
+    fn select_nowin_nogrp_whrbad_nohav() -> Result<()> {
+        let target = test_target();
+        let mut stmt_ast = test_stmt(target);
+        stmt_ast.maybe_where = Some(ImutExpr::from(ast::Literal {
+            mid: 0,
+            value: Value::from("snot"),
+        }));
 
-        let id = "select".to_string();
-        Ok(TrickleSelect::with_stmt(42, id, &groups, windows, &stmt)?)
+        let stmt_ast = test_select_stmt(stmt_ast);
+        let script = "fake".to_string();
+        let query = srs::Query::try_new::<Error, _>(script, |_| Ok(test_query(stmt_ast.clone())))?;
+
+        let stmt = srs::Stmt::try_new_from_query::<Error, _>(&query, |_| Ok(stmt_ast))?;
+
+        let mut op = test_select(4, stmt)?;
+
+        assert!(try_enqueue(&mut op, test_event(0)).is_err());
+
+        Ok(())
     }
 
-    fn test_tick(ns: u64) -> Event {
-        Event {
-            id: EventId::new(1, 1, ns),
-            kind: Some(SignalKind::Tick),
-            ingest_ns: ns,
-            ..Event::default()
-        }
+    #[test]
+    fn select_nowin_nogrp_whrt_havt() -> Result<()> {
+        let target = test_target();
+        let mut stmt_ast = test_stmt(target);
+        stmt_ast.maybe_where = Some(ImutExpr::from(ast::Literal {
+            mid: 0,
+            value: Value::from(true),
+        }));
+        stmt_ast.maybe_having = Some(ImutExpr::from(ast::Literal {
+            mid: 0,
+            value: Value::from(true),
+        }));
+
+        let stmt_ast = test_select_stmt(stmt_ast);
+        let script = "fake".to_string();
+        let query = srs::Query::try_new::<Error, _>(script, |_| Ok(test_query(stmt_ast.clone())))?;
+
+        let stmt = srs::Stmt::try_new_from_query::<Error, _>(&query, |_| Ok(stmt_ast))?;
+
+        let mut op = test_select(5, stmt)?;
+
+        let event = test_event(0);
+        assert!(try_enqueue(&mut op, event)?.is_none());
+
+        let event = test_event(15);
+        let (out, event) = try_enqueue(&mut op, event)?.expect("no event");
+        assert_eq!("out", out);
+        assert_eq!(*event.data.suffix().value(), 42);
+        Ok(())
     }
 
     #[test]
-    fn select_single_win_with_script_on_signal() -> Result<()> {
-        let mut select = select_stmt_from_query(
-            r#"
-        define tumbling window window1
-        with
-            interval = 5
-        script
-            event.time
-        end;
-        select aggr::stats::count() from in[window1] group by event.g into out having event > 0;
-        "#,
-        )?;
-        let uid = 42;
-        let mut state = Value::null();
-        let mut tick1 = test_tick(1);
-        let mut eis = select.on_signal(uid, &state, &mut tick1)?;
-        assert!(eis.insights.is_empty());
-        assert_eq!(0, eis.events.len());
+    fn select_nowin_nogrp_whrt_havf() -> Result<()> {
+        let target = test_target();
+        let mut stmt_ast = test_stmt(target);
+        stmt_ast.maybe_where = Some(ImutExpr::from(ast::Literal {
+            mid: 0,
+            value: Value::from(true),
+        }));
+        stmt_ast.maybe_having = Some(ImutExpr::from(ast::Literal {
+            mid: 0,
+            value: Value::from(false),
+        }));
 
-        let event = Event {
-            id: (1, 1, 300).into(),
-            ingest_ns: 1_000,
-            data: literal!({
-               "time" : 4,
-               "g": "group"
-            })
-            .into(),
-            ..Event::default()
-        };
-        eis = select.on_event(uid, "IN", &mut state, event)?;
-        assert!(eis.insights.is_empty());
-        assert_eq!(0, eis.events.len());
+        let stmt_ast = test_select_stmt(stmt_ast);
+        let script = "fake".to_string();
+        let query = srs::Query::try_new::<Error, _>(script, |_| Ok(test_query(stmt_ast.clone())))?;
 
-        // no emit on signal, although window interval would be passed
-        let mut tick2 = test_tick(10);
-        eis = select.on_signal(uid, &state, &mut tick2)?;
-        assert!(eis.insights.is_empty());
-        assert_eq!(0, eis.events.len());
+        let stmt = srs::Stmt::try_new_from_query::<Error, _>(&query, |_| Ok(stmt_ast))?;
 
-        // only emit on event
-        let event = Event {
-            id: (1, 1, 300).into(),
-            ingest_ns: 3_000,
-            data: literal!({
-               "time" : 11,
-               "g": "group"
-            })
-            .into(),
-            ..Event::default()
-        };
-        eis = select.on_event(uid, "IN", &mut state, event)?;
-        assert!(eis.insights.is_empty());
-        assert_eq!(1, eis.events.len());
-        assert_eq!("1", sorted_serialize(eis.events[0].1.data.parts().0)?);
+        let mut op = test_select(6, stmt)?;
+        let event = test_event(0);
+
+        let next = try_enqueue(&mut op, event)?;
+
+        assert_eq!(None, next);
         Ok(())
     }
 
+    fn test_select_stmt(stmt: tremor_script::ast::Select) -> tremor_script::ast::Stmt {
+        let aggregates = vec![];
+        let aggregate_scratches = Some((
+            AggregateScratch::new(aggregates.clone()),
+            AggregateScratch::new(aggregates.clone()),
+        ));
+        ast::Stmt::Select(SelectStmt {
+            stmt: Box::new(stmt),
+            aggregates,
+            aggregate_scratches,
+            consts: Consts::default(),
+            locals: 0,
+            node_meta: ast::NodeMetas::new(vec![]),
+        })
+    }
     #[test]
-    fn select_single_win_on_signal() -> Result<()> {
-        let mut select = select_stmt_from_query(
-            r#"
-        define tumbling window window1
-        with
-            interval = 2
-        end;
-        select aggr::win::collect_flattened(event) from in[window1] group by event.g into out;
-        "#,
-        )?;
-        let uid = 42;
-        let mut state = Value::null();
-        let mut tick1 = test_tick(1);
-        let mut eis = select.on_signal(uid, &state, &mut tick1)?;
-        assert!(eis.insights.is_empty());
-        assert_eq!(0, eis.events.len());
+    fn select_nowin_nogrp_whrt_havbad() -> Result<()> {
+        use halfbrown::hashmap;
+        let target = test_target();
+        let mut stmt_ast = test_stmt(target);
+        stmt_ast.maybe_where = Some(ImutExpr::from(ast::Literal {
+            mid: 0,
+            value: Value::from(true),
+        }));
+        stmt_ast.maybe_having = Some(ImutExpr::from(Literal {
+            mid: 0,
+            value: Value::from(hashmap! {
+                "snot".into() => "badger".into(),
+            }),
+        }));
+
+        let stmt_ast = test_select_stmt(stmt_ast);
+        let script = "fake".to_string();
+        let query = srs::Query::try_new::<Error, _>(script, |_| Ok(test_query(stmt_ast.clone())))?;
+
+        let stmt = srs::Stmt::try_new_from_query::<Error, _>(&query, |_| Ok(stmt_ast))?;
 
-        let event = Event {
-            id: (1, 1, 300).into(),
-            ingest_ns: 2,
-            data: literal!({
-               "g": "group"
-            })
-            .into(),
-            ..Event::default()
-        };
-        eis = select.on_event(uid, "IN", &mut state, event)?;
-        assert!(eis.insights.is_empty());
-        assert_eq!(0, eis.events.len());
+        let mut op = test_select(7, stmt)?;
+        let event = test_event(0);
 
-        // no emit yet
-        let mut tick2 = test_tick(3);
-        eis = select.on_signal(uid, &state, &mut tick2)?;
-        assert!(eis.insights.is_empty());
-        assert_eq!(0, eis.events.len());
+        let next = try_enqueue(&mut op, event)?;
 
-        // now emit
-        let mut tick3 = test_tick(4);
-        eis = select.on_signal(uid, &state, &mut tick3)?;
-        assert!(eis.insights.is_empty());
-        assert_eq!(1, eis.events.len());
+        assert_eq!(None, next);
+        Ok(())
+    }
+
+    #[test]
+    fn tumbling_window_on_time_emit() -> Result<()> {
+        // interval = 10 seconds
+        let mut window = TumblingWindowOnTime::from_stmt(
+            10 * 1_000_000_000,
+            WindowImpl::DEFAULT_EMIT_EMPTY_WINDOWS,
+            WindowImpl::DEFAULT_MAX_GROUPS,
+            None,
+            None,
+        );
+        let vm = literal!({
+           "h2g2" : 42,
+        })
+        .into();
         assert_eq!(
-            r#"[{"g":"group"}]"#,
-            sorted_serialize(eis.events[0].1.data.parts().0)?
+            WindowEvent {
+                include: false,
+                opened: true,
+                emit: false
+            },
+            window.on_event(&vm, ingest_ns(5), &None)?
+        );
+        assert_eq!(
+            WindowEvent::all_false(),
+            window.on_event(&vm, ingest_ns(10), &None)?
+        );
+        assert_eq!(
+            WindowEvent {
+                include: false,
+                opened: true,
+                emit: true
+            },
+            window.on_event(&vm, ingest_ns(15), &None)? // exactly on time
+        );
+        assert_eq!(
+            WindowEvent {
+                include: false,
+                opened: true,
+                emit: true
+            },
+            window.on_event(&vm, ingest_ns(26), &None)? // exactly on time
         );
-        assert_eq!(false, eis.events[0].1.transactional);
         Ok(())
     }
 
     #[test]
-    fn select_multiple_wins_on_signal() -> Result<()> {
-        let mut select = select_stmt_from_query(
+    fn tumbling_window_on_time_from_script_emit() -> Result<()> {
+        // create a WindowDecl with a custom script
+        let reg = Registry::default();
+        let aggr_reg = AggrRegistry::default();
+        let module_path = ModulePath::load();
+        let q = tremor_script::query::Query::parse(
+            &module_path,
+            "bar",
             r#"
-        define tumbling window window1
-        with
-            interval = 100
-        end;
-        define tumbling window window2
-        with
-            size = 2
-        end;
-        select aggr::win::collect_flattened(event) from in[window1, window2] group by event.cat into out;
-        "#,
-        )?;
-        let uid = 42;
-        let mut state = Value::null();
-        let mut tick1 = test_tick(1);
-        let mut eis = select.on_signal(uid, &state, &mut tick1)?;
-        assert!(eis.insights.is_empty());
-        assert_eq!(0, eis.events.len());
-
-        let mut tick2 = test_tick(100);
-        eis = select.on_signal(uid, &state, &mut tick2)?;
-        assert!(eis.insights.is_empty());
-        assert_eq!(0, eis.events.len());
-
-        // we have no groups, so no emit yet
-        let mut tick3 = test_tick(201);
-        eis = select.on_signal(uid, &state, &mut tick3)?;
-        assert!(eis.insights.is_empty());
-        assert_eq!(0, eis.events.len());
-
-        // insert first event
-        let event = Event {
-            id: (1, 1, 300).into(),
-            ingest_ns: 300,
-            data: literal!({
-               "cat" : 42,
-            })
-            .into(),
-            transactional: true,
-            ..Event::default()
+            define tumbling window my_window
+            with
+                interval = 1000000000 # 1 second
+            script
+                event.timestamp
+            end;"#,
+            vec![],
+            &reg,
+            &aggr_reg,
+        )
+        .map_err(|ce| ce.error)?;
+        let window_decl = match q.query.suffix().stmts.first() {
+            Some(Stmt::WindowDecl(decl)) => decl.as_ref(),
+            other => return Err(format!("Didnt get a window decl, got: {:?}", other).into()),
         };
-        eis = select.on_event(uid, "IN", &mut state, event)?;
-        assert!(eis.insights.is_empty());
-        assert_eq!(0, eis.events.len());
+        let mut params = halfbrown::HashMap::with_capacity(1);
+        params.insert("size".to_string(), Value::from(3));
+        let interval = window_decl
+            .params
+            .get("interval")
+            .and_then(Value::as_u64)
+            .ok_or(Error::from("no interval found"))?;
+        let mut window = TumblingWindowOnTime::from_stmt(
+            interval,
+            WindowImpl::DEFAULT_EMIT_EMPTY_WINDOWS,
+            WindowImpl::DEFAULT_MAX_GROUPS,
+            None,
+            Some(&window_decl),
+        );
+        let json1 = literal!({
+            "timestamp": 1_000_000_000
+        })
+        .into();
+        assert_eq!(
+            WindowEvent {
+                opened: true,
+                include: false,
+                emit: false
+            },
+            window.on_event(&json1, 1, &None)?
+        );
+        let json2 = literal!({
+            "timestamp": 1_999_999_999
+        })
+        .into();
+        assert_eq!(WindowEvent::all_false(), window.on_event(&json2, 2, &None)?);
+        let json3 = literal!({
+            "timestamp": 2_000_000_000
+        })
+        .into();
+        // ignoring on_tick as we have a script
+        assert_eq!(WindowEvent::all_false(), window.on_tick(2_000_000_000)?);
+        assert_eq!(
+            WindowEvent {
+                opened: true,
+                include: false,
+                emit: true
+            },
+            window.on_event(&json3, 3, &None)?
+        );
+        Ok(())
+    }
 
-        // finish the window with the next tick
-        let mut tick4 = test_tick(401);
-        eis = select.on_signal(uid, &state, &mut tick4)?;
-        assert!(eis.insights.is_empty());
-        assert_eq!(1, eis.events.len());
-        let (_port, event) = eis.events.remove(0);
-        assert_eq!(r#"[{"cat":42}]"#, sorted_serialize(event.data.parts().0)?);
-        assert_eq!(true, event.transactional);
+    #[test]
+    fn tumbling_window_on_time_on_tick() -> Result<()> {
+        let mut window = TumblingWindowOnTime::from_stmt(
+            100,
+            WindowImpl::DEFAULT_EMIT_EMPTY_WINDOWS,
+            WindowImpl::DEFAULT_MAX_GROUPS,
+            None,
+            None,
+        );
+        assert_eq!(
+            WindowEvent {
+                opened: true,
+                include: false,
+                emit: false
+            },
+            window.on_tick(0)?
+        );
+        assert_eq!(WindowEvent::all_false(), window.on_tick(99)?);
+        assert_eq!(
+            WindowEvent {
+                opened: true,
+                include: false,
+                emit: false // we dont emit if we had no event
+            },
+            window.on_tick(100)?
+        );
+        assert_eq!(
+            WindowEvent::all_false(),
+            window.on_event(&ValueAndMeta::default(), 101, &None)?
+        );
+        assert_eq!(WindowEvent::all_false(), window.on_tick(102)?);
+        assert_eq!(
+            WindowEvent {
+                opened: true,
+                include: false,
+                emit: true // we had an event yeah
+            },
+            window.on_tick(200)?
+        );
+        Ok(())
+    }
 
-        let mut tick5 = test_tick(499);
-        eis = select.on_signal(uid, &state, &mut tick5)?;
-        assert!(eis.insights.is_empty());
-        assert_eq!(0, eis.events.len());
+    #[test]
+    fn tumbling_window_on_time_emit_empty_windows() -> Result<()> {
+        let mut window =
+            TumblingWindowOnTime::from_stmt(100, true, WindowImpl::DEFAULT_MAX_GROUPS, None, None);
+        assert_eq!(
+            WindowEvent {
+                opened: true,
+                include: false,
+                emit: false
+            },
+            window.on_tick(0)?
+        );
+        assert_eq!(WindowEvent::all_false(), window.on_tick(99)?);
+        assert_eq!(
+            WindowEvent {
+                opened: true,
+                include: false,
+                emit: true // we **DO** emit even if we had no event
+            },
+            window.on_tick(100)?
+        );
+        assert_eq!(
+            WindowEvent::all_false(),
+            window.on_event(&ValueAndMeta::default(), 101, &None)?
+        );
+        assert_eq!(WindowEvent::all_false(), window.on_tick(102)?);
+        assert_eq!(
+            WindowEvent {
+                opened: true,
+                include: false,
+                emit: true // we had an event yeah
+            },
+            window.on_tick(200)?
+        );
 
         Ok(())
     }
 
     #[test]
-    fn test_transactional_single_window() -> Result<()> {
-        let mut op = select_stmt_from_query(
-            r#"
-            define tumbling window w2
-            with
-              size = 2
-            end;
-            select aggr::stats::count() from in[w2] into out;
-        "#,
-        )?;
-        let mut state = Value::null();
-        let event1 = test_event_tx(0, false, 0);
-        let id1 = event1.id.clone();
-        let res = op.on_event(0, "in", &mut state, event1)?;
-
-        assert!(res.events.is_empty());
-
-        let event2 = test_event_tx(1, true, 0);
-        let id2 = event2.id.clone();
-        let mut res = op.on_event(0, "in", &mut state, event2)?;
-        assert_eq!(1, res.events.len());
-        let (_, event) = res.events.pop().unwrap();
-        assert_eq!(true, event.transactional);
-        assert_eq!(true, event.id.is_tracking(&id1));
-        assert_eq!(true, event.id.is_tracking(&id2));
-
-        let event3 = test_event_tx(2, false, 0);
-        let id3 = event3.id.clone();
-        let res = op.on_event(0, "in", &mut state, event3)?;
-        assert!(res.events.is_empty());
+    fn no_window_emit() -> Result<()> {
+        let mut window = NoWindow::default();
 
-        let event4 = test_event_tx(3, false, 0);
-        let id4 = event4.id.clone();
-        let mut res = op.on_event(0, "in", &mut state, event4)?;
-        assert_eq!(1, res.events.len());
-        let (_, event) = res.events.pop().unwrap();
-        assert_eq!(false, event.transactional);
-        assert_eq!(true, event.id.is_tracking(&id3));
-        assert_eq!(true, event.id.is_tracking(&id4));
+        let vm = literal!({
+           "h2g2" : 42,
+        })
+        .into();
 
+        assert_eq!(
+            WindowEvent::all_true(),
+            window.on_event(&vm, ingest_ns(0), &None)?
+        );
+        assert_eq!(WindowEvent::all_false(), window.on_tick(0)?);
+        assert_eq!(
+            WindowEvent::all_true(),
+            window.on_event(&vm, ingest_ns(1), &None)?
+        );
+        assert_eq!(WindowEvent::all_false(), window.on_tick(1)?);
         Ok(())
     }
 
     #[test]
-    fn test_transactional_multiple_windows() -> Result<()> {
-        let mut op = select_stmt_from_query(
-            r#"
-            define tumbling window w2_1
-            with
-              size = 2
-            end;
-            define tumbling window w2_2
-            with
-              size = 2
-            end;
-            select aggr::win::collect_flattened(event) from in[w2_1, w2_2] group by set(event["group"]) into out;
-        "#,
-        )?;
-        let mut state = Value::null();
-        let event1 = test_event_tx(0, true, 0);
-        let id1 = event1.id.clone();
-        let res = op.on_event(0, "in", &mut state, event1)?;
-        assert_eq!(0, res.len());
-
-        let event2 = test_event_tx(1, false, 1);
-        let id2 = event2.id.clone();
-        let res = op.on_event(0, "in", &mut state, event2)?;
-        assert_eq!(0, res.len());
-
-        let event3 = test_event_tx(2, false, 0);
-        let id3 = event3.id.clone();
-        let mut res = op.on_event(0, "in", &mut state, event3)?;
-        assert_eq!(1, res.len());
-        let (_, event) = res.events.pop().unwrap();
-        assert_eq!(true, event.transactional);
-        assert_eq!(true, event.id.is_tracking(&id1));
-        assert_eq!(true, event.id.is_tracking(&id3));
-
-        let event4 = test_event_tx(3, false, 1);
-        let id4 = event4.id.clone();
-        let mut res = op.on_event(0, "in", &mut state, event4)?;
-        assert_eq!(1, res.len());
-        let (_, event) = res.events.remove(0);
-        assert_eq!(false, event.transactional);
-        assert_eq!(true, event.id.is_tracking(&id2));
-        assert_eq!(true, event.id.is_tracking(&id4));
-
-        let event5 = test_event_tx(4, false, 0);
-        let id5 = event5.id.clone();
-        let res = op.on_event(0, "in", &mut state, event5)?;
-        assert_eq!(0, res.len());
-
-        let event6 = test_event_tx(5, false, 0);
-        let id6 = event6.id.clone();
-        let mut res = op.on_event(0, "in", &mut state, event6)?;
-        assert_eq!(2, res.len());
+    fn tumbling_window_on_number_emit() -> Result<()> {
+        let mut window =
+            TumblingWindowOnNumber::from_stmt(3, WindowImpl::DEFAULT_MAX_GROUPS, None, None);
 
-        // first event from event5 and event6 - none of the source events are transactional
-        let (_, event_w1) = res.events.remove(0);
-        assert_eq!(false, event_w1.transactional);
-        assert_eq!(true, event_w1.id.is_tracking(&id5));
-        assert_eq!(true, event_w1.id.is_tracking(&id6));
+        let vm = literal!({
+           "h2g2" : 42,
+        })
+        .into();
 
-        let (_, event_w2) = res.events.remove(0);
-        assert_eq!(true, event_w2.transactional);
-        assert_eq!(true, event_w2.id.is_tracking(&id1));
-        assert_eq!(true, event_w2.id.is_tracking(&id3));
-        assert_eq!(true, event_w2.id.is_tracking(&id5));
-        assert_eq!(true, event_w2.id.is_tracking(&id6));
+        // do not emit yet
+        assert_eq!(
+            WindowEvent::all_false(),
+            window.on_event(&vm, ingest_ns(0), &None)?
+        );
+        assert_eq!(WindowEvent::all_false(), window.on_tick(1_000_000_000)?);
+        // do not emit yet
+        assert_eq!(
+            WindowEvent::all_false(),
+            window.on_event(&vm, ingest_ns(1), &None)?
+        );
+        assert_eq!(WindowEvent::all_false(), window.on_tick(2_000_000_000)?);
+        // emit and open on the third event
+        assert_eq!(
+            WindowEvent::all_true(),
+            window.on_event(&vm, ingest_ns(2), &None)?
+        );
+        // no emit here, next window
+        assert_eq!(
+            WindowEvent::all_false(),
+            window.on_event(&vm, ingest_ns(3), &None)?
+        );
 
         Ok(())
     }
 
     #[test]
-    fn count_tilt() -> Result<()> {
-        // Windows are 15s and 30s
-        let mut op = select_stmt_from_query(
-            r#"
-        define tumbling window w15s
-        with
-          interval = 15 * 1000000000
-        end;
-        define tumbling window w30s
-        with
-          interval = 30 * 1000000000
-        end;
-        select aggr::stats::count() from in [w15s, w30s] into out;
-        "#,
-        )?;
-
-        // Insert two events prior to 15
-        assert!(try_enqueue(&mut op, test_event(0))?.is_none());
-        assert!(try_enqueue(&mut op, test_event(1))?.is_none());
-        // Add one event at 15, this flushes the prior two and set this to one.
-        // This is the first time the second frame sees an event so it's timer
-        // starts at 15.
-        let (out, event) = try_enqueue(&mut op, test_event(15))?.expect("no event 1");
-        assert_eq!("out", out);
-        assert_eq!(*event.data.suffix().value(), 2);
-        // Add another event prior to 30
-        assert!(try_enqueue(&mut op, test_event(16))?.is_none());
-        // This emits only the initial event since the rollup
-        // will only be emitted once it gets the first event
-        // of the next window
-        let (out, event) = try_enqueue(&mut op, test_event(30))?.expect("no event 2");
-        assert_eq!("out", out);
-        assert_eq!(*event.data.suffix().value(), 2);
-        // Add another event prior to 45 to the first window
-        assert!(try_enqueue(&mut op, test_event(31))?.is_none());
-
-        // At 45 the 15s window emits the rollup with the previous data
-        let [(out1, event1), (out2, event2)] =
-            try_enqueue_two(&mut op, test_event(45))?.expect("no event 3");
-
-        assert_eq!("out", out1);
-        assert_eq!("out", out2);
-        assert_eq!(*event1.data.suffix().value(), 2);
-        assert_eq!(*event2.data.suffix().value(), 4);
-        assert!(try_enqueue(&mut op, test_event(46))?.is_none());
+    fn sliding_window_on_number_emit() -> Result<()> {
+        // window of 4 events, advancing every 2 events
+        let mut window =
+            SlidingWindowOnNumber::from_stmt(4, 2, WindowImpl::DEFAULT_MAX_GROUPS, None, None);
+        let vm = literal!({
+           "h2g2" : 42,
+        })
+        .into();
 
-        // Add 60 only the 15s window emits
-        let (out, event) = try_enqueue(&mut op, test_event(60))?.expect("no event 4");
-        assert_eq!("out", out);
-        assert_eq!(*event.data.suffix().value(), 2);
+        assert_eq!(
+            WindowEvent::all_false(),
+            window.on_event(&vm, ingest_ns(0), &None)?
+        );
+        assert_eq!(0, window.retained_panes());
+        assert_eq!(
+            WindowEvent::all_true(),
+            window.on_event(&vm, ingest_ns(1), &None)?
+        );
+        assert_eq!(1, window.retained_panes());
+        assert_eq!(
+            WindowEvent::all_false(),
+            window.on_event(&vm, ingest_ns(2), &None)?
+        );
+        assert_eq!(WindowEvent::all_false(), window.on_tick(ingest_ns(3))?);
+        assert_eq!(
+            WindowEvent::all_true(),
+            window.on_event(&vm, ingest_ns(3), &None)?
+        );
+        // we never retain more than the panes making up a full window
+        assert_eq!(1, window.retained_panes());
         Ok(())
     }
 
     #[test]
-    fn select_nowin_nogrp_nowhr_nohav() -> Result<()> {
-        let target = test_target();
-        let stmt_ast = test_stmt(target);
-
-        let stmt_ast = test_select_stmt(stmt_ast);
-        let script = "fake".to_string();
-        let query = srs::Query::try_new::<Error, _>(script, |_| Ok(test_query(stmt_ast.clone())))?;
-
-        let stmt = srs::Stmt::try_new_from_query::<Error, _>(&query, |_| Ok(stmt_ast))?;
-
-        let mut op = test_select(1, stmt)?;
-        assert!(try_enqueue(&mut op, test_event(0))?.is_none());
-        assert!(try_enqueue(&mut op, test_event(1))?.is_none());
-        let (out, event) = try_enqueue(&mut op, test_event(15))?.expect("no event");
-        assert_eq!("out", out);
-
-        assert_eq!(*event.data.suffix().value(), 42);
+    fn sliding_window_on_time_on_tick() -> Result<()> {
+        // window of 300ns, advancing every 100ns
+        let mut window = SlidingWindowOnTime::from_stmt(
+            300,
+            100,
+            WindowImpl::DEFAULT_EMIT_EMPTY_WINDOWS,
+            WindowImpl::DEFAULT_MAX_GROUPS,
+            None,
+            None,
+        );
+        assert_eq!(
+            WindowEvent {
+                opened: true,
+                include: false,
+                emit: false
+            },
+            window.on_tick(0)?
+        );
+        assert_eq!(
+            WindowEvent::all_false(),
+            window.on_event(&ValueAndMeta::default(), 50, &None)?
+        );
+        let emit = WindowEvent {
+            opened: true,
+            include: false,
+            emit: true,
+        };
+        assert_eq!(emit, window.on_tick(100)?);
+        assert_eq!(1, window.retained_panes());
+        // the event from the first pane is still part of the window
+        assert_eq!(emit, window.on_tick(200)?);
+        assert_eq!(2, window.retained_panes());
+        assert_eq!(emit, window.on_tick(300)?);
+        assert_eq!(2, window.retained_panes());
+        // now the pane with our event slid out of the window
+        assert_eq!(
+            WindowEvent {
+                opened: true,
+                include: false,
+                emit: false
+            },
+            window.on_tick(400)?
+        );
         Ok(())
     }
 
     #[test]
-    fn select_nowin_nogrp_whrt_nohav() -> Result<()> {
-        let target = test_target();
-        let mut stmt_ast = test_stmt(target);
-
-        stmt_ast.maybe_where = Some(ImutExpr::from(ast::Literal {
-            mid: 0,
-            value: Value::from(true),
-        }));
-
-        let stmt_ast = test_select_stmt(stmt_ast);
-        let script = "fake".to_string();
-        let query = srs::Query::try_new::<Error, _>(script, |_| Ok(test_query(stmt_ast.clone())))?;
-
-        let stmt = srs::Stmt::try_new_from_query::<Error, _>(&query, |_| Ok(stmt_ast))?;
-
-        let mut op = test_select(2, stmt)?;
-
-        assert!(try_enqueue(&mut op, test_event(0))?.is_none());
-
-        let (out, event) = try_enqueue(&mut op, test_event(15))?.expect("no event");
-        assert_eq!("out", out);
-
-        assert_eq!(*event.data.suffix().value(), 42);
+    fn sliding_window_on_time_skipped_panes() -> Result<()> {
+        let mut window = SlidingWindowOnTime::from_stmt(
+            300,
+            100,
+            WindowImpl::DEFAULT_EMIT_EMPTY_WINDOWS,
+            WindowImpl::DEFAULT_MAX_GROUPS,
+            None,
+            None,
+        );
+        window.on_event(&ValueAndMeta::default(), 0, &None)?;
+        window.on_event(&ValueAndMeta::default(), 100, &None)?;
+        assert_eq!(1, window.retained_panes());
+        // we skip two whole panes, so everything before slid out of the window
+        assert_eq!(
+            WindowEvent {
+                opened: true,
+                include: false,
+                emit: true
+            },
+            window.on_event(&ValueAndMeta::default(), 400, &None)?
+        );
+        assert_eq!(0, window.retained_panes());
         Ok(())
     }
 
     #[test]
-    fn select_nowin_nogrp_whrf_nohav() -> Result<()> {
-        let target = test_target();
-        let mut stmt_ast = test_stmt(target);
-
-        let script = "fake".to_string();
-        stmt_ast.maybe_where = Some(ImutExpr::from(ast::Literal {
-            mid: 0,
-            value: Value::from(false),
-        }));
-        let stmt_ast = test_select_stmt(stmt_ast);
-        let query = srs::Query::try_new::<Error, _>(script, |_| Ok(test_query(stmt_ast.clone())))?;
-
-        let stmt = srs::Stmt::try_new_from_query::<Error, _>(&query, |_| Ok(stmt_ast))?;
-
-        let mut op = test_select(3, stmt)?;
-        let next = try_enqueue(&mut op, test_event(0))?;
-        assert_eq!(None, next);
+    fn select_sliding_window_on_number() -> Result<()> {
+        let mut select = select_stmt_from_query(
+            r#"
+        define sliding window window1
+        with
+            size = 3,
+            hop = 1
+        end;
+        select aggr::stats::sum(event.v) from in[window1] into out;
+        "#,
+        )?;
+        let uid = 42;
+        let mut state = Value::null();
+        let mut sums = Vec::new();
+        for v in 1..=5_u64 {
+            let event = Event {
+                id: (1, 1, v).into(),
+                ingest_ns: v,
+                data: literal!({ "v": v }).into(),
+                ..Event::default()
+            };
+            let eis = select.on_event(uid, "IN", &mut state, event)?;
+            assert_eq!(1, eis.events.len());
+            sums.push(sorted_serialize(eis.events[0].1.data.parts().0)?);
+        }
+        // moving sum over the last 3 events
+        assert_eq!(vec!["1.0", "3.0", "6.0", "9.0", "12.0"], sums);
         Ok(())
     }
 
     #[test]
-    fn select_nowin_nogrp_whrbad_nohav() -> Result<()> {
-        let target = test_target();
-        let mut stmt_ast = test_stmt(target);
-        stmt_ast.maybe_where = Some(ImutExpr::from(ast::Literal {
-            mid: 0,
-            value: Value::from("snot"),
-        }));
-
-        let stmt_ast = test_select_stmt(stmt_ast);
-        let script = "fake".to_string();
-        let query = srs::Query::try_new::<Error, _>(script, |_| Ok(test_query(stmt_ast.clone())))?;
-
-        let stmt = srs::Stmt::try_new_from_query::<Error, _>(&query, |_| Ok(stmt_ast))?;
-
-        let mut op = test_select(4, stmt)?;
-
-        assert!(try_enqueue(&mut op, test_event(0)).is_err());
-
+    fn select_sliding_window_first_and_last() -> Result<()> {
+        let mut select = select_stmt_from_query(
+            r#"
+        define sliding window window1
+        with
+            size = 4,
+            hop = 1
+        end;
+        select [aggr::win::first(event.v), aggr::win::last(event.v), aggr::win::collect_flattened(event.v)] from in[window1] into out;
+        "#,
+        )?;
+        let uid = 42;
+        let mut state = Value::null();
+        let mut emitted = Vec::new();
+        for v in 1..=6_u64 {
+            let event = Event {
+                id: (1, 1, v).into(),
+                ingest_ns: v,
+                data: literal!({ "v": v }).into(),
+                ..Event::default()
+            };
+            let eis = select.on_event(uid, "IN", &mut state, event)?;
+            assert_eq!(1, eis.events.len());
+            emitted.push(sorted_serialize(eis.events[0].1.data.parts().0)?);
+        }
+        // from the fourth event on the window spans three closed panes and the open one
+        assert_eq!(
+            vec![
+                "[1,1,[1]]",
+                "[1,2,[1,2]]",
+                "[1,3,[1,2,3]]",
+                "[1,4,[1,2,3,4]]",
+                "[2,5,[2,3,4,5]]",
+                "[3,6,[3,4,5,6]]"
+            ],
+            emitted
+        );
         Ok(())
     }
 
     #[test]
-    fn select_nowin_nogrp_whrt_havt() -> Result<()> {
-        let target = test_target();
-        let mut stmt_ast = test_stmt(target);
-        stmt_ast.maybe_where = Some(ImutExpr::from(ast::Literal {
-            mid: 0,
-            value: Value::from(true),
-        }));
-        stmt_ast.maybe_having = Some(ImutExpr::from(ast::Literal {
-            mid: 0,
-            value: Value::from(true),
-        }));
-
-        let stmt_ast = test_select_stmt(stmt_ast);
-        let script = "fake".to_string();
-        let query = srs::Query::try_new::<Error, _>(script, |_| Ok(test_query(stmt_ast.clone())))?;
-
-        let stmt = srs::Stmt::try_new_from_query::<Error, _>(&query, |_| Ok(stmt_ast))?;
-
-        let mut op = test_select(5, stmt)?;
-
-        let event = test_event(0);
-        assert!(try_enqueue(&mut op, event)?.is_none());
-
-        let event = test_event(15);
-        let (out, event) = try_enqueue(&mut op, event)?.expect("no event");
-        assert_eq!("out", out);
-        assert_eq!(*event.data.suffix().value(), 42);
+    fn select_sliding_window_checkpoint() -> Result<()> {
+        let query = r#"
+        define sliding window window1
+        with
+            size = 3,
+            hop = 1
+        end;
+        select aggr::stats::sum(event.v) from in[window1] into out;
+        "#;
+        let mut select = select_stmt_from_query(query)?;
+        let uid = 42;
+        let mut state = Value::null();
+        let event = |v: u64| Event {
+            id: (1, 1, v).into(),
+            ingest_ns: v,
+            data: literal!({ "v": v }).into(),
+            ..Event::default()
+        };
+        for v in 1..=3_u64 {
+            select.on_event(uid, "IN", &mut state, event(v))?;
+        }
+        let mut covered = None;
+        let checkpoint = select.checkpoint(&mut covered)?.unwrap_or_default();
+        let covered = covered.unwrap_or_default();
+        assert_eq!(Some(1), covered.get_min_by_stream(1, 1));
+        assert_eq!(Some(3), covered.get_max_by_stream(1, 1));
+
+        // a new select picks up where the old one left off
+        let mut select = select_stmt_from_query(query)?;
+        select.restore(&checkpoint)?;
+        let mut sums = Vec::new();
+        for v in 4..=5_u64 {
+            let eis = select.on_event(uid, "IN", &mut state, event(v))?;
+            assert_eq!(1, eis.events.len());
+            sums.push(sorted_serialize(eis.events[0].1.data.parts().0)?);
+        }
+        assert_eq!(vec!["9.0", "12.0"], sums);
         Ok(())
     }
 
     #[test]
-    fn select_nowin_nogrp_whrt_havf() -> Result<()> {
-        let target = test_target();
-        let mut stmt_ast = test_stmt(target);
-        stmt_ast.maybe_where = Some(ImutExpr::from(ast::Literal {
-            mid: 0,
-            value: Value::from(true),
-        }));
-        stmt_ast.maybe_having = Some(ImutExpr::from(ast::Literal {
-            mid: 0,
-            value: Value::from(false),
-        }));
-
-        let stmt_ast = test_select_stmt(stmt_ast);
-        let script = "fake".to_string();
-        let query = srs::Query::try_new::<Error, _>(script, |_| Ok(test_query(stmt_ast.clone())))?;
-
-        let stmt = srs::Stmt::try_new_from_query::<Error, _>(&query, |_| Ok(stmt_ast))?;
-
-        let mut op = test_select(6, stmt)?;
-        let event = test_event(0);
-
-        let next = try_enqueue(&mut op, event)?;
-
-        assert_eq!(None, next);
+    fn select_sliding_window_tracks_event_ids() -> Result<()> {
+        let mut select = select_stmt_from_query(
+            r#"
+        define sliding window window1
+        with
+            size = 4,
+            hop = 2
+        end;
+        select aggr::stats::count() from in[window1] into out;
+        "#,
+        )?;
+        let uid = 42;
+        let mut state = Value::null();
+        let mut emitted = Vec::new();
+        for i in 0..6_u64 {
+            let event = Event {
+                id: (1, 1, i).into(),
+                ingest_ns: i,
+                data: literal!({}).into(),
+                ..Event::default()
+            };
+            emitted.append(&mut select.on_event(uid, "IN", &mut state, event)?.events);
+        }
+        assert_eq!(3, emitted.len());
+        let (_, last) = emitted.remove(2);
+        assert_eq!("4", sorted_serialize(last.data.parts().0)?);
+        // the last window covers events 2 to 5
+        assert!(!last.id.is_tracking(&(1, 1, 1).into()));
+        for i in 2..6_u64 {
+            assert!(last.id.is_tracking(&(1, 1, i).into()));
+        }
         Ok(())
     }
 
-    fn test_select_stmt(stmt: tremor_script::ast::Select) -> tremor_script::ast::Stmt {
-        let aggregates = vec![];
-        let aggregate_scratches = Some((
-            AggregateScratch::new(aggregates.clone()),
-            AggregateScratch::new(aggregates.clone()),
-        ));
-        ast::Stmt::Select(SelectStmt {
-            stmt: Box::new(stmt),
-            aggregates,
-            aggregate_scratches,
-            consts: Consts::default(),
-            locals: 0,
-            node_meta: ast::NodeMetas::new(vec![]),
-        })
-    }
     #[test]
-    fn select_nowin_nogrp_whrt_havbad() -> Result<()> {
-        use halfbrown::hashmap;
-        let target = test_target();
-        let mut stmt_ast = test_stmt(target);
-        stmt_ast.maybe_where = Some(ImutExpr::from(ast::Literal {
-            mid: 0,
-            value: Value::from(true),
-        }));
-        stmt_ast.maybe_having = Some(ImutExpr::from(Literal {
-            mid: 0,
-            value: Value::from(hashmap! {
-                "snot".into() => "badger".into(),
-            }),
-        }));
-
-        let stmt_ast = test_select_stmt(stmt_ast);
-        let script = "fake".to_string();
-        let query = srs::Query::try_new::<Error, _>(script, |_| Ok(test_query(stmt_ast.clone())))?;
-
-        let stmt = srs::Stmt::try_new_from_query::<Error, _>(&query, |_| Ok(stmt_ast))?;
-
-        let mut op = test_select(7, stmt)?;
-        let event = test_event(0);
-
-        let next = try_enqueue(&mut op, event)?;
-
-        assert_eq!(None, next);
+    fn select_sliding_window_only_last() -> Result<()> {
+        let res = select_stmt_from_query(
+            r#"
+        define sliding window window1
+        with
+            size = 4,
+            hop = 2
+        end;
+        define tumbling window window2
+        with
+            size = 2
+        end;
+        select aggr::stats::count() from in[window1, window2] into out;
+        "#,
+        );
+        assert!(res.is_err());
         Ok(())
     }
 
     #[test]
-    fn tumbling_window_on_time_emit() -> Result<()> {
-        // interval = 10 seconds
-        let mut window = TumblingWindowOnTime::from_stmt(
-            10 * 1_000_000_000,
-            WindowImpl::DEFAULT_EMIT_EMPTY_WINDOWS,
-            WindowImpl::DEFAULT_MAX_GROUPS,
-            None,
-            None,
-        );
+    fn session_window_emit() -> Result<()> {
+        let mut window =
+            SessionWindow::from_stmt(10, None, WindowImpl::DEFAULT_MAX_GROUPS, None, None);
         let vm = literal!({
            "h2g2" : 42,
         })
         .into();
+        let open = WindowEvent {
+            opened: true,
+            include: false,
+            emit: false,
+        };
+        assert_eq!(open, window.on_event(&vm, 0, &None)?);
+        assert_eq!(WindowEvent::all_false(), window.on_event(&vm, 9, &None)?);
+        // the gap is relative to the last event
+        assert_eq!(WindowEvent::all_false(), window.on_event(&vm, 18, &None)?);
+        assert_eq!(WindowEvent::all_false(), window.on_tick(27)?);
         assert_eq!(
             WindowEvent {
-                include: false,
-                opened: true,
-                emit: false
-            },
-            window.on_event(&vm, ingest_ns(5), &None)?
-        );
-        assert_eq!(
-            WindowEvent::all_false(),
-            window.on_event(&vm, ingest_ns(10), &None)?
+                opened: true,
+                include: false,
+                emit: true
+            },
+            window.on_event(&vm, 28, &None)?
         );
+        Ok(())
+    }
+
+    #[test]
+    fn session_window_on_tick() -> Result<()> {
+        let mut window =
+            SessionWindow::from_stmt(10, None, WindowImpl::DEFAULT_MAX_GROUPS, None, None);
+        // no session, no emit
+        assert_eq!(WindowEvent::all_false(), window.on_tick(0)?);
+        window.on_event(&ValueAndMeta::default(), 5, &None)?;
+        assert_eq!(WindowEvent::all_false(), window.on_tick(14)?);
         assert_eq!(
             WindowEvent {
+                opened: false,
                 include: false,
-                opened: true,
                 emit: true
             },
-            window.on_event(&vm, ingest_ns(15), &None)? // exactly on time
+            window.on_tick(15)?
         );
+        // the session is closed, nothing more to emit
+        assert_eq!(WindowEvent::all_false(), window.on_tick(100)?);
         assert_eq!(
             WindowEvent {
-                include: false,
                 opened: true,
-                emit: true
+                include: false,
+                emit: false
             },
-            window.on_event(&vm, ingest_ns(26), &None)? // exactly on time
+            window.on_event(&ValueAndMeta::default(), 101, &None)?
         );
         Ok(())
     }
 
     #[test]
-    fn tumbling_window_on_time_from_script_emit() -> Result<()> {
-        // create a WindowDecl with a custom script
+    fn session_window_from_script_on_tick() -> Result<()> {
         let reg = Registry::default();
         let aggr_reg = AggrRegistry::default();
         let module_path = ModulePath::load();
@@ -2458,9 +4154,9 @@ mod test {
             &module_path,
             "bar",
             r#"
-            define tumbling window my_window
+            define session window my_window
             with
-                interval = 1000000000 # 1 second
+                gap = 10
             script
                 event.timestamp
             end;"#,
@@ -2473,189 +4169,315 @@ mod test {
             Some(Stmt::WindowDecl(decl)) => decl.as_ref(),
             other => return Err(format!("Didnt get a window decl, got: {:?}", other).into()),
         };
-        let mut params = halfbrown::HashMap::with_capacity(1);
-        params.insert("size".to_string(), Value::from(3));
-        let interval = window_decl
-            .params
-            .get("interval")
-            .and_then(Value::as_u64)
-            .ok_or(Error::from("no interval found"))?;
-        let mut window = TumblingWindowOnTime::from_stmt(
-            interval,
-            WindowImpl::DEFAULT_EMIT_EMPTY_WINDOWS,
+        let mut window = SessionWindow::from_stmt(
+            10,
+            None,
             WindowImpl::DEFAULT_MAX_GROUPS,
             None,
-            Some(&window_decl),
-        );
-        let json1 = literal!({
-            "timestamp": 1_000_000_000
-        })
-        .into();
-        assert_eq!(
-            WindowEvent {
-                opened: true,
-                include: false,
-                emit: false
-            },
-            window.on_event(&json1, 1, &None)?
+            Some(window_decl),
         );
-        let json2 = literal!({
-            "timestamp": 1_999_999_999
-        })
-        .into();
-        assert_eq!(WindowEvent::all_false(), window.on_event(&json2, 2, &None)?);
-        let json3 = literal!({
-            "timestamp": 2_000_000_000
-        })
-        .into();
-        // ignoring on_tick as we have a script
-        assert_eq!(WindowEvent::all_false(), window.on_tick(2_000_000_000)?);
+        let vm = literal!({ "timestamp": 1_000 }).into();
+        window.on_event(&vm, 5, &None)?;
+        // the event time doesn't matter for ticks, the ingest time of the last event does
+        assert_eq!(WindowEvent::all_false(), window.on_tick(14)?);
         assert_eq!(
             WindowEvent {
-                opened: true,
+                opened: false,
                 include: false,
                 emit: true
             },
-            window.on_event(&json3, 3, &None)?
+            window.on_tick(15)?
         );
+        assert_eq!(WindowEvent::all_false(), window.on_tick(100)?);
         Ok(())
     }
 
     #[test]
-    fn tumbling_window_on_time_on_tick() -> Result<()> {
-        let mut window = TumblingWindowOnTime::from_stmt(
-            100,
-            WindowImpl::DEFAULT_EMIT_EMPTY_WINDOWS,
-            WindowImpl::DEFAULT_MAX_GROUPS,
-            None,
-            None,
-        );
-        assert_eq!(
-            WindowEvent {
-                opened: true,
-                include: false,
-                emit: false
-            },
-            window.on_tick(0)?
-        );
-        assert_eq!(WindowEvent::all_false(), window.on_tick(99)?);
+    fn session_window_max_duration() -> Result<()> {
+        let mut window =
+            SessionWindow::from_stmt(10, Some(20), WindowImpl::DEFAULT_MAX_GROUPS, None, None);
+        let vm = ValueAndMeta::default();
+        window.on_event(&vm, 0, &None)?;
+        assert_eq!(WindowEvent::all_false(), window.on_event(&vm, 8, &None)?);
+        assert_eq!(WindowEvent::all_false(), window.on_event(&vm, 16, &None)?);
+        // the session is still active, but exceeded its maximum duration
         assert_eq!(
             WindowEvent {
                 opened: true,
                 include: false,
-                emit: false // we dont emit if we had no event
+                emit: true
             },
-            window.on_tick(100)?
+            window.on_event(&vm, 20, &None)?
         );
-        assert_eq!(
-            WindowEvent::all_false(),
-            window.on_event(&ValueAndMeta::default(), 101, &None)?
-        );
-        assert_eq!(WindowEvent::all_false(), window.on_tick(102)?);
+        assert_eq!(WindowEvent::all_false(), window.on_event(&vm, 28, &None)?);
+        assert_eq!(WindowEvent::all_false(), window.on_event(&vm, 36, &None)?);
+        assert_eq!(WindowEvent::all_false(), window.on_tick(39)?);
         assert_eq!(
             WindowEvent {
-                opened: true,
+                opened: false,
                 include: false,
-                emit: true // we had an event yeah
+                emit: true
             },
-            window.on_tick(200)?
+            window.on_tick(40)?
         );
         Ok(())
     }
 
     #[test]
-    fn tumbling_window_on_time_emit_empty_windows() -> Result<()> {
-        let mut window =
-            TumblingWindowOnTime::from_stmt(100, true, WindowImpl::DEFAULT_MAX_GROUPS, None, None);
-        assert_eq!(
-            WindowEvent {
-                opened: true,
-                include: false,
-                emit: false
-            },
-            window.on_tick(0)?
-        );
-        assert_eq!(WindowEvent::all_false(), window.on_tick(99)?);
-        assert_eq!(
-            WindowEvent {
-                opened: true,
-                include: false,
-                emit: true // we **DO** emit even if we had no event
-            },
-            window.on_tick(100)?
-        );
-        assert_eq!(
-            WindowEvent::all_false(),
-            window.on_event(&ValueAndMeta::default(), 101, &None)?
-        );
-        assert_eq!(WindowEvent::all_false(), window.on_tick(102)?);
-        assert_eq!(
-            WindowEvent {
-                opened: true,
-                include: false,
-                emit: true // we had an event yeah
-            },
-            window.on_tick(200)?
-        );
+    fn select_session_window_per_group() -> Result<()> {
+        let mut select = select_stmt_from_query(
+            r#"
+        define session window by_gap
+        with
+            gap = 10
+        end;
+        select aggr::win::collect_flattened(event.v) from in[by_gap] group by event.g into out;
+        "#,
+        )?;
+        let uid = 42;
+        let mut state = Value::null();
+        let events = vec![(0, "a", 1), (5, "b", 2), (9, "a", 3), (14, "b", 4)];
+        for (ingest_ns, g, v) in events {
+            let event = Event {
+                id: (1, 1, ingest_ns).into(),
+                ingest_ns,
+                data: literal!({ "g": g, "v": v }).into(),
+                ..Event::default()
+            };
+            let eis = select.on_event(uid, "IN", &mut state, event)?;
+            assert!(eis.events.is_empty());
+        }
+        // only the session of group `a` is over
+        let mut tick = test_tick(19);
+        let mut eis = select.on_signal(uid, &state, &mut tick)?;
+        assert_eq!(1, eis.events.len());
+        assert_eq!("[1,3]", sorted_serialize(eis.events[0].1.data.parts().0)?);
+
+        let mut tick = test_tick(24);
+        eis = select.on_signal(uid, &state, &mut tick)?;
+        assert_eq!(1, eis.events.len());
+        assert_eq!("[2,4]", sorted_serialize(eis.events[0].1.data.parts().0)?);
 
+        let mut tick = test_tick(100);
+        eis = select.on_signal(uid, &state, &mut tick)?;
+        assert!(eis.events.is_empty());
         Ok(())
     }
 
+    fn watermark_of(eis: &EventAndInsights) -> Option<u64> {
+        eis.events
+            .iter()
+            .find_map(|(_, event)| event.watermark_ns())
+    }
+
     #[test]
-    fn no_window_emit() -> Result<()> {
-        let mut window = NoWindow::default();
+    fn select_event_time_window_out_of_order() -> Result<()> {
+        let mut select = select_stmt_from_query(
+            r#"
+        define tumbling window ten
+        with
+            interval = 10,
+            watermark_delay = 5,
+            allowed_lateness = 10
+        script
+            event.t
+        end;
+        select aggr::win::collect_flattened(event.v) from in[ten] into out;
+        "#,
+        )?;
+        let uid = 42;
+        let mut state = Value::null();
+        let mut send = |select: &mut TrickleSelect, t: u64, v: u64| {
+            let event = Event {
+                id: (1, 1, v).into(),
+                ingest_ns: v,
+                data: literal!({ "t": t, "v": v }).into(),
+                ..Event::default()
+            };
+            select.on_event(uid, "IN", &mut state, event)
+        };
+        let eis = send(&mut select, 1, 1)?;
+        assert_eq!(Some(0), watermark_of(&eis));
+        let eis = send(&mut select, 12, 2)?;
+        assert_eq!(Some(7), watermark_of(&eis));
+        // out of order, but its window is still open
+        let eis = send(&mut select, 3, 3)?;
+        assert!(eis.events.is_empty());
+        let eis = send(&mut select, 16, 4)?;
+        assert_eq!(Some(11), watermark_of(&eis));
+
+        let mut watermark = Event::watermark(uid, 4, 11);
+        let eis = select.on_signal(uid, &Value::null(), &mut watermark)?;
+        assert_eq!(1, eis.events.len());
+        assert_eq!("[1,3]", sorted_serialize(eis.events[0].1.data.parts().0)?);
 
-        let vm = literal!({
-           "h2g2" : 42,
-        })
-        .into();
+        // late, but within the allowed lateness, the window is emitted again including it
+        let eis = send(&mut select, 5, 5)?;
+        assert_eq!(1, eis.events.len());
+        assert_eq!("[1,3,5]", sorted_serialize(eis.events[0].1.data.parts().0)?);
 
-        assert_eq!(
-            WindowEvent::all_true(),
-            window.on_event(&vm, ingest_ns(0), &None)?
-        );
-        assert_eq!(WindowEvent::all_false(), window.on_tick(0)?);
-        assert_eq!(
-            WindowEvent::all_true(),
-            window.on_event(&vm, ingest_ns(1), &None)?
-        );
-        assert_eq!(WindowEvent::all_false(), window.on_tick(1)?);
+        let eis = send(&mut select, 31, 6)?;
+        assert_eq!(Some(26), watermark_of(&eis));
+        let mut watermark = Event::watermark(uid, 6, 26);
+        let eis = select.on_signal(uid, &Value::null(), &mut watermark)?;
+        assert_eq!(1, eis.events.len());
+        assert_eq!("[2,4]", sorted_serialize(eis.events[0].1.data.parts().0)?);
+
+        // too late, passed on unchanged
+        let eis = send(&mut select, 7, 7)?;
+        assert_eq!(1, eis.events.len());
+        assert_eq!(LATE, eis.events[0].0);
+        assert_eq!(Some(7), eis.events[0].1.data.suffix().value().get_u64("v"));
         Ok(())
     }
 
     #[test]
-    fn tumbling_window_on_number_emit() -> Result<()> {
-        let mut window =
-            TumblingWindowOnNumber::from_stmt(3, WindowImpl::DEFAULT_MAX_GROUPS, None, None);
+    fn select_event_time_window_idle_source() -> Result<()> {
+        let mut select = select_stmt_from_query(
+            r#"
+        define tumbling window ten
+        with
+            interval = 10,
+            watermark_delay = 0,
+            idle_timeout = 100
+        script
+            event.t
+        end;
+        select aggr::win::collect_flattened(event.v) from in[ten] into out;
+        "#,
+        )?;
+        let uid = 42;
+        let mut state = Value::null();
+        let sources = vec![((1, 1), 5, 0, 1), ((2, 1), 15, 10, 2)];
+        for (source, t, ingest_ns, v) in sources {
+            let (source_id, stream_id) = source;
+            let event = Event {
+                id: (source_id, stream_id, v).into(),
+                ingest_ns,
+                data: literal!({ "t": t, "v": v }).into(),
+                ..Event::default()
+            };
+            select.on_event(uid, "IN", &mut state, event)?;
+        }
+        // the first source holds back the watermark
+        let mut tick = test_tick(50);
+        let eis = select.on_signal(uid, &state, &mut tick)?;
+        assert!(eis.events.is_empty());
+
+        // until it became idle
+        let mut tick = test_tick(105);
+        let eis = select.on_signal(uid, &state, &mut tick)?;
+        assert_eq!(Some(15), watermark_of(&eis));
+        let mut watermark = Event::watermark(uid, 105, 15);
+        let eis = select.on_signal(uid, &state, &mut watermark)?;
+        assert_eq!(1, eis.events.len());
+        assert_eq!("[1]", sorted_serialize(eis.events[0].1.data.parts().0)?);
+        Ok(())
+    }
 
-        let vm = literal!({
-           "h2g2" : 42,
-        })
-        .into();
+    #[test]
+    fn select_event_time_window_slowest_upstream() -> Result<()> {
+        let mut select = select_stmt_from_query(
+            r#"
+        define tumbling window ten
+        with
+            interval = 10,
+            watermark_delay = 0
+        script
+            event.t
+        end;
+        select aggr::win::collect_flattened(event.v) from in[ten] into out;
+        "#,
+        )?;
+        let uid = 42;
+        let mut state = Value::null();
+        let mut watermark = Event::watermark(8, 1, 2);
+        select.on_signal(uid, &state, &mut watermark)?;
+        for (t, v) in vec![(5, 1), (15, 2), (35, 3)] {
+            let event = Event {
+                id: (1, 1, v).into(),
+                ingest_ns: v,
+                data: literal!({ "t": t, "v": v }).into(),
+                ..Event::default()
+            };
+            select.on_event(uid, "IN", &mut state, event)?;
+        }
+        // a fast upstream operator doesn't close windows held back by a slow one
+        let mut watermark = Event::watermark(7, 4, 40);
+        let eis = select.on_signal(uid, &state, &mut watermark)?;
+        assert_eq!(0, eis.events.len());
+        let mut watermark = Event::watermark(8, 4, 12);
+        let eis = select.on_signal(uid, &state, &mut watermark)?;
+        assert_eq!(1, eis.events.len());
+        assert_eq!("[1]", sorted_serialize(eis.events[0].1.data.parts().0)?);
+        // our own watermark is held back by the slowest upstream as well
+        let mut watermark = Event::watermark(uid, 5, 35);
+        let eis = select.on_signal(uid, &state, &mut watermark)?;
+        assert!(eis.events.is_empty());
+        let mut watermark = Event::watermark(8, 6, 30);
+        let eis = select.on_signal(uid, &state, &mut watermark)?;
+        assert_eq!(1, eis.events.len());
+        assert_eq!("[2]", sorted_serialize(eis.events[0].1.data.parts().0)?);
+        Ok(())
+    }
 
-        // do not emit yet
-        assert_eq!(
-            WindowEvent::all_false(),
-            window.on_event(&vm, ingest_ns(0), &None)?
-        );
-        assert_eq!(WindowEvent::all_false(), window.on_tick(1_000_000_000)?);
-        // do not emit yet
-        assert_eq!(
-            WindowEvent::all_false(),
-            window.on_event(&vm, ingest_ns(1), &None)?
-        );
-        assert_eq!(WindowEvent::all_false(), window.on_tick(2_000_000_000)?);
-        // emit and open on the third event
-        assert_eq!(
-            WindowEvent::all_true(),
-            window.on_event(&vm, ingest_ns(2), &None)?
-        );
-        // no emit here, next window
-        assert_eq!(
-            WindowEvent::all_false(),
-            window.on_event(&vm, ingest_ns(3), &None)?
-        );
+    #[test]
+    fn select_event_time_window_emit_empty_windows() -> Result<()> {
+        let mut select = select_stmt_from_query(
+            r#"
+        define tumbling window ten
+        with
+            interval = 10,
+            watermark_delay = 0,
+            emit_empty_windows = true,
+            max_groups = 10
+        script
+            event.t
+        end;
+        select aggr::win::collect_flattened(event.v) from in[ten] into out;
+        "#,
+        )?;
+        let uid = 42;
+        let mut state = Value::null();
+        for (t, v) in vec![(5, 1), (35, 2)] {
+            let event = Event {
+                id: (1, 1, v).into(),
+                ingest_ns: v,
+                data: literal!({ "t": t, "v": v }).into(),
+                ..Event::default()
+            };
+            select.on_event(uid, "IN", &mut state, event)?;
+        }
+        let mut watermark = Event::watermark(uid, 2, 35);
+        let eis = select.on_signal(uid, &state, &mut watermark)?;
+        let emitted = eis
+            .events
+            .iter()
+            .map(|(_, event)| sorted_serialize(event.data.parts().0))
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(vec!["[1]", "[]", "[]"], emitted);
+        Ok(())
+    }
 
+    #[test]
+    fn select_event_time_window_only_window() -> Result<()> {
+        let res = select_stmt_from_query(
+            r#"
+        define tumbling window ten
+        with
+            interval = 10,
+            allowed_lateness = 10
+        script
+            event.t
+        end;
+        define tumbling window two
+        with
+            size = 2
+        end;
+        select aggr::stats::count() from in[ten, two] into out;
+        "#,
+        );
+        assert!(res.is_err());
         Ok(())
     }
 }
@@ -18,7 +18,7 @@ use crate::{
     op::{
         self,
         identity::PassthroughFactory,
-        prelude::{ERR, IN, METRICS, OUT},
+        prelude::{ERR, IN, LATE, METRICS, OUT},
         trickle::{
             operator::TrickleOperator,
             script::Script,
@@ -26,12 +26,14 @@ use crate::{
             simple_select::SimpleSelect,
         },
     },
-    ConfigGraph, Connection, NodeConfig, NodeKind, Operator, OperatorNode, PortIndexMap,
+    CheckpointSettings, ConfigGraph, Connection, NodeConfig, NodeKind, Operator, OperatorNode,
+    PortIndexMap,
 };
 use beef::Cow;
 use halfbrown::HashMap;
 use indexmap::IndexMap;
 use petgraph::algo::is_cyclic_directed;
+use sha2::{Digest, Sha256};
 use tremor_common::ids::OperatorIdGen;
 use tremor_script::{
     ast::{
@@ -48,6 +50,9 @@ use tremor_script::{
     srs, AggrRegistry, Registry, Value,
 };
 
+/// seconds between two checkpoints if `checkpoint_dir` is set without `checkpoint_interval_s`
+const DEFAULT_CHECKPOINT_INTERVAL_S: u64 = 10;
+
 const BUILTIN_NODES: [(Cow<'static, str>, NodeKind); 4] = [
     (IN, NodeKind::Input),
     (OUT, NodeKind::Output(OUT)),
@@ -89,40 +94,62 @@ fn resolve_output_port(port: &(Ident, Ident), meta: &NodeMetas) -> OutputPort {
 }
 
 pub(crate) fn window_decl_to_impl(d: &WindowDecl) -> Result<WindowImpl> {
-    use op::trickle::select::{TumblingWindowOnNumber, TumblingWindowOnTime};
+    use op::trickle::select::{
+        SessionWindow, SlidingWindowOnNumber, SlidingWindowOnTime, TumblingWindowOnEventTime,
+        TumblingWindowOnNumber, TumblingWindowOnTime, WatermarkSettings,
+    };
+    let script = if d.script.is_some() { Some(d) } else { None };
+    let ttl = d
+        .params
+        .get(WindowDecl::EVICTION_PERIOD)
+        .and_then(Value::as_u64);
+    let max_groups = d
+        .params
+        .get(WindowDecl::MAX_GROUPS)
+        .and_then(Value::as_u64)
+        .unwrap_or(WindowImpl::DEFAULT_MAX_GROUPS);
+    let emit_empty_windows = d
+        .params
+        .get(WindowDecl::EMIT_EMPTY_WINDOWS)
+        .and_then(Value::as_bool)
+        .unwrap_or(WindowImpl::DEFAULT_EMIT_EMPTY_WINDOWS);
+    let interval = d.params.get(WindowDecl::INTERVAL).and_then(Value::as_u64);
+    let size = d.params.get(WindowDecl::SIZE).and_then(Value::as_u64);
+    let watermark_delay = d
+        .params
+        .get(WindowDecl::WATERMARK_DELAY)
+        .and_then(Value::as_u64);
+    let allowed_lateness = d
+        .params
+        .get(WindowDecl::ALLOWED_LATENESS)
+        .and_then(Value::as_u64);
+
     match &d.kind {
-        WindowKind::Sliding => Err("Sliding windows are not yet implemented".into()),
-        WindowKind::Tumbling => {
-            let script = if d.script.is_some() { Some(d) } else { None };
-            let ttl = d
+        WindowKind::Sliding => {
+            let hop = d
                 .params
-                .get(WindowDecl::EVICTION_PERIOD)
-                .and_then(Value::as_u64);
-            let max_groups = d
-                .params
-                .get(WindowDecl::MAX_GROUPS)
+                .get(WindowDecl::HOP)
                 .and_then(Value::as_u64)
-                .unwrap_or(WindowImpl::DEFAULT_MAX_GROUPS);
-            let emit_empty_windows = d
-                .params
-                .get(WindowDecl::EMIT_EMPTY_WINDOWS)
-                .and_then(Value::as_bool)
-                .unwrap_or(WindowImpl::DEFAULT_EMIT_EMPTY_WINDOWS);
-
-            match (
-                d.params.get(WindowDecl::INTERVAL).and_then(Value::as_u64),
-                d.params.get(WindowDecl::SIZE).and_then(Value::as_u64),
-            ) {
-                (Some(interval), None) => Ok(WindowImpl::from(TumblingWindowOnTime::from_stmt(
-                    interval,
-                    emit_empty_windows,
-                    max_groups,
-                    ttl,
-                    script,
-                ))),
-                (None, Some(size)) => Ok(WindowImpl::from(TumblingWindowOnNumber::from_stmt(
-                    size, max_groups, ttl, script,
-                ))),
+                .ok_or_else(|| {
+                    Error::from("Bad window configuration, sliding windows require a `hop`.")
+                })?;
+            match (interval, size) {
+                (Some(interval), None) if hop > 0 && interval % hop == 0 => {
+                    Ok(WindowImpl::from(SlidingWindowOnTime::from_stmt(
+                        interval,
+                        hop,
+                        emit_empty_windows,
+                        max_groups,
+                        ttl,
+                        script,
+                    )))
+                }
+                (None, Some(size)) if hop > 0 && size % hop == 0 => Ok(WindowImpl::from(
+                    SlidingWindowOnNumber::from_stmt(size, hop, max_groups, ttl, script),
+                )),
+                (Some(_), None) | (None, Some(_)) => Err(Error::from(
+                    "Bad window configuration, `hop` must be a non-zero divisor of `size` or `interval`.",
+                )),
                 (Some(_), Some(_)) => Err(Error::from(
                     "Bad window configuration, only one of `size` or `interval` is allowed.",
                 )),
@@ -131,6 +158,72 @@ pub(crate) fn window_decl_to_impl(d: &WindowDecl) -> Result<WindowImpl> {
                 )),
             }
         }
+        WindowKind::Session => {
+            if interval.is_some() || size.is_some() {
+                return Err(Error::from(
+                    "Bad window configuration, session windows don't support `size` or `interval`.",
+                ));
+            }
+            let gap = d
+                .params
+                .get(WindowDecl::GAP)
+                .and_then(Value::as_u64)
+                .ok_or_else(|| {
+                    Error::from("Bad window configuration, session windows require a `gap`.")
+                })?;
+            let max_duration = d
+                .params
+                .get(WindowDecl::MAX_DURATION)
+                .and_then(Value::as_u64);
+            Ok(WindowImpl::from(SessionWindow::from_stmt(
+                gap,
+                max_duration,
+                max_groups,
+                ttl,
+                script,
+            )))
+        }
+        WindowKind::Tumbling => match (interval, size) {
+            (Some(interval), None) if watermark_delay.is_some() || allowed_lateness.is_some() => {
+                let script = script.ok_or_else(|| {
+                    Error::from(
+                        "Bad window configuration, event time windows require a `script` providing the event time.",
+                    )
+                })?;
+                let settings = WatermarkSettings {
+                    delay: watermark_delay.unwrap_or_default(),
+                    allowed_lateness: allowed_lateness.unwrap_or_default(),
+                    idle_timeout: d
+                        .params
+                        .get(WindowDecl::IDLE_TIMEOUT)
+                        .and_then(Value::as_u64),
+                };
+                Ok(WindowImpl::from(TumblingWindowOnEventTime::from_stmt(
+                    interval,
+                    settings,
+                    emit_empty_windows,
+                    max_groups,
+                    ttl,
+                    script,
+                )))
+            }
+            (Some(interval), None) => Ok(WindowImpl::from(TumblingWindowOnTime::from_stmt(
+                interval,
+                emit_empty_windows,
+                max_groups,
+                ttl,
+                script,
+            ))),
+            (None, Some(size)) => Ok(WindowImpl::from(TumblingWindowOnNumber::from_stmt(
+                size, max_groups, ttl, script,
+            ))),
+            (Some(_), Some(_)) => Err(Error::from(
+                "Bad window configuration, only one of `size` or `interval` is allowed.",
+            )),
+            (None, None) => Err(Error::from(
+                "Bad window configuration, either `size` or `interval` is required.",
+            )),
+        },
     }
 }
 /// A Tremor Query
@@ -198,6 +291,20 @@ impl Query {
             .and_then(Value::as_u64)
             .map(|i| i * 1_000_000_000);
 
+        let checkpoint = query
+            .config
+            .get("checkpoint_dir")
+            .and_then(Value::as_str)
+            .map(|dir| CheckpointSettings {
+                dir: dir.to_string(),
+                interval: query
+                    .config
+                    .get("checkpoint_interval_s")
+                    .and_then(Value::as_u64)
+                    .unwrap_or(DEFAULT_CHECKPOINT_INTERVAL_S)
+                    * 1_000_000_000,
+            });
+
         let pipeline_id = query
             .config
             .get("id")
@@ -256,6 +363,20 @@ impl Query {
                         )
                         .into());
                     }
+                    // checkpoints need to capture the state of every aggregate
+                    if checkpoint.is_some() {
+                        if let Some(aggr) = select
+                            .aggregates
+                            .iter()
+                            .find(|aggr| aggr.invocable.snapshot().is_none())
+                        {
+                            return Err(format!(
+                                "{:?} doesn't support the checkpoints set by `checkpoint_dir`",
+                                aggr.invocable
+                            )
+                            .into());
+                        }
+                    }
                     let e = select.stmt.extent(&select.node_meta);
                     let mut h = Dumb::new();
                     let label = h
@@ -353,13 +474,66 @@ impl Query {
                     for (name, decl) in &query.windows {
                         ww.insert(name.clone(), window_decl_to_impl(&decl)?);
                     }
-                    let op = node.to_op(
+                    // events arriving after their event time window has been emitted
+                    // leave the pipeline through its `late` port
+                    if s.windows.iter().any(|w| {
+                        ww.get(&w.fqwn())
+                            .map_or(false, |imp| imp.as_event_time().is_some())
+                    }) {
+                        let name: Cow<'static, str> = format!("out/{}", LATE).into();
+                        if !nodes.contains_key(&name) {
+                            let late_id = pipe_graph.add_node(NodeConfig {
+                                id: name.to_string(),
+                                label: Some(name.to_string()),
+                                kind: NodeKind::Output(LATE),
+                                op_type: "passthrough".to_string(),
+                                ..NodeConfig::default()
+                            });
+                            nodes.insert(name.clone(), late_id);
+                            let op = pipe_graph
+                                .raw_nodes()
+                                .get(late_id.index())
+                                .ok_or_else(|| Error::from("Error finding freshly added node."))
+                                .and_then(|node| {
+                                    node.weight.to_op(
+                                        idgen.next_id(),
+                                        supported_operators,
+                                        None,
+                                        None,
+                                        None,
+                                    )
+                                })?;
+                            pipe_ops.insert(late_id, op);
+                            outputs.push(late_id);
+                        }
+                        let select_late = OutputPort {
+                            id: select_in.id.clone(),
+                            port: LATE,
+                            had_port: false,
+                            location: s.extent(&query.node_meta),
+                        };
+                        links.entry(select_late).or_default().push(InputPort {
+                            id: name,
+                            port: IN,
+                            had_port: false,
+                            location: s.extent(&query.node_meta),
+                        });
+                    }
+                    let mut op = node.to_op(
                         idgen.next_id(),
                         supported_operators,
                         None,
                         Some(&stmt),
                         Some(ww),
                     )?;
+                    // the windows of a select are part of its definition
+                    let mut parts: Vec<_> = s
+                        .windows
+                        .iter()
+                        .map(|w| query.windows.get(&w.fqwn()).and_then(definition))
+                        .collect();
+                    parts.push(op.fingerprint.map(serde_yaml::Value::from));
+                    op.fingerprint = fingerprint(&node, &parts);
                     pipe_ops.insert(id, op);
                     nodes.insert(select_in.id.clone(), id);
                     outputs.push(id);
@@ -645,8 +819,13 @@ impl Query {
                 signalflow,
                 metric_interval,
                 insights: Vec::new(),
+                signals: Vec::new(),
+                taps: Vec::new(),
+                tapped: Vec::new(),
+                track_latencies: false,
                 source: Some(self.0.source.clone()),
                 dot: format!("{}", dot),
+                checkpoint,
             };
             exec.optimize();
 
@@ -743,6 +922,63 @@ fn script(
         node,
     )?))
 }
+/// Turns a part of the definition of a node into a comparable value. Metadata
+/// ids and source locations are left out, so unrelated changes elsewhere in the
+/// query don't affect it, and maps are ordered by key.
+fn definition<S: serde::Serialize>(part: &S) -> Option<serde_yaml::Value> {
+    fn normalize(value: &mut serde_yaml::Value) {
+        match value {
+            serde_yaml::Value::Mapping(m) => {
+                let mut entries: Vec<_> = std::mem::take(m)
+                    .into_iter()
+                    .filter(|(k, _)| !matches!(k.as_str(), Some("mid" | "node_meta")))
+                    .collect();
+                entries.sort_by_cached_key(|(k, _)| format!("{:?}", k));
+                for (k, mut v) in entries {
+                    normalize(&mut v);
+                    m.insert(k, v);
+                }
+            }
+            serde_yaml::Value::Sequence(s) => s.iter_mut().for_each(normalize),
+            _ => (),
+        }
+    }
+    let mut value = serde_yaml::to_value(part).ok()?;
+    normalize(&mut value);
+    Some(value)
+}
+
+/// Fingerprints the definition of a node, `None` if it can't be fingerprinted.
+///
+/// Fingerprints are persisted with checkpoints, so they are a digest of the
+/// canonical form of the definition, which is the same across builds.
+fn fingerprint(config: &NodeConfig, parts: &[Option<serde_yaml::Value>]) -> Option<u64> {
+    let kind = match &config.kind {
+        NodeKind::Input => "input".to_string(),
+        NodeKind::Output(port) => format!("output/{}", port),
+        NodeKind::Operator => "operator".to_string(),
+        NodeKind::Select => "select".to_string(),
+        NodeKind::Script => "script".to_string(),
+    };
+    let mut canonical = vec![
+        serde_yaml::Value::from(config.id.as_str()),
+        serde_yaml::Value::from(kind),
+        serde_yaml::Value::from(config.op_type.as_str()),
+        config
+            .config
+            .as_ref()
+            .map_or(Some(serde_yaml::Value::Null), definition)?,
+    ];
+    for part in parts {
+        canonical.push(part.clone()?);
+    }
+    let canonical = serde_yaml::to_string(&canonical).ok()?;
+    let digest = Sha256::digest(canonical.as_bytes());
+    let mut bytes = [0_u8; 8];
+    bytes.copy_from_slice(&digest[..8]);
+    Some(u64::from_be_bytes(bytes))
+}
+
 pub(crate) fn supported_operators(
     config: &NodeConfig,
     uid: u64,
@@ -758,12 +994,18 @@ pub(crate) fn supported_operators(
         ["trickle", "script"] => script(config, defn, node)?,
         _ => crate::operator(uid, &config)?,
     };
+    let parts: Vec<_> = defn
+        .iter()
+        .chain(node.iter())
+        .map(|stmt| definition(stmt.suffix()))
+        .collect();
     Ok(OperatorNode {
         uid,
         id: config.id.to_string(),
         kind: config.kind.clone(),
         op_type: config.op_type.clone(),
         op,
+        fingerprint: fingerprint(config, &parts),
     })
 }
 
@@ -777,6 +1019,8 @@ pub(crate) fn make_builtin_node_name_checker() -> impl Fn(&Cow<'static, str>) ->
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::Event;
+
     #[test]
     fn query() {
         let module_path = &tremor_script::path::ModulePath { mounts: Vec::new() };
@@ -810,6 +1054,61 @@ mod test {
         assert_eq!(query.source().trim_end(), src);
     }
 
+    #[test]
+    fn migrate_state() {
+        let module_path = &tremor_script::path::ModulePath { mounts: Vec::new() };
+        let aggr_reg = tremor_script::aggr_registry();
+        let mut idgen = OperatorIdGen::new();
+        let mut pipe = |src: &str| {
+            Query::parse(
+                &module_path,
+                src,
+                "<test>",
+                Vec::new(),
+                &*crate::FN_REGISTRY.lock().unwrap(),
+                &aggr_reg,
+            )
+            .unwrap()
+            .to_pipe(&mut idgen)
+            .unwrap()
+        };
+
+        let mut old = pipe(
+            r#"
+            define tumbling window two with size = 2 end;
+            select aggr::stats::count() from in[two] into out;
+            select event from in into err;
+            "#,
+        );
+        let mut returns = Vec::new();
+        old.enqueue("in", Event::default(), &mut returns).unwrap();
+        assert_eq!(
+            vec![ERR],
+            returns.drain(..).map(|(p, _)| p).collect::<Vec<_>>()
+        );
+
+        // moving statements around doesn't change the definition of the window
+        let mut new = pipe(
+            r#"
+            # reloaded
+            define tumbling window two with size = 2 end;
+
+            select aggr::stats::count() from in[two] into out;
+            select {"changed": event} from in into err;
+            "#,
+        );
+        let migrated = new.migrate_state(&mut old);
+        assert!(migrated.contains(&"select_0".to_string()));
+        assert!(!migrated.contains(&"select_1".to_string()));
+
+        // the second event closes the window opened before the reload
+        new.enqueue("in", Event::default(), &mut returns).unwrap();
+        returns.sort_by(|(p1, _), (p2, _)| str::cmp(p1, p2));
+        let (ports, events): (Vec<_>, Vec<_>) = returns.into_iter().unzip();
+        assert_eq!(vec![ERR, OUT], ports);
+        assert_eq!(Some(2), events[1].data.suffix().value().as_u64());
+    }
+
     #[test]
     fn custom_port() {
         let module_path = &tremor_script::path::ModulePath { mounts: Vec::new() };
@@ -836,6 +1135,34 @@ mod test {
         assert_eq!(out.kind, NodeKind::Output("test_out".into()));
     }
 
+    #[test]
+    fn checkpoint_unsupported_aggr() {
+        let module_path = &tremor_script::path::ModulePath { mounts: Vec::new() };
+        let aggr_reg = tremor_script::aggr_registry();
+        let mut idgen = OperatorIdGen::new();
+        let mut pipe = |src: &str| {
+            Query::parse(
+                &module_path,
+                src,
+                "<test>",
+                Vec::new(),
+                &*crate::FN_REGISTRY.lock().unwrap(),
+                &aggr_reg,
+            )
+            .unwrap()
+            .to_pipe(&mut idgen)
+        };
+        let select = r#"
+            define tumbling window two with size = 2 end;
+            select aggr::stats::dds(event) from in[two] into out;
+            "#;
+        assert!(pipe(select).is_ok());
+        let err = pipe(&format!("#!config checkpoint_dir = \"/tmp\"\n{}", select))
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("stats::dds"));
+    }
+
     #[test]
     fn builtin_nodes() {
         let has_builtin_node_name = make_builtin_node_name_checker();
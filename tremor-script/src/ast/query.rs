@@ -267,6 +267,8 @@ pub enum WindowKind {
     Sliding,
     /// we're forced to make this pub because of lalrpop
     Tumbling,
+    /// we're forced to make this pub because of lalrpop
+    Session,
 }
 
 /// A window declaration
@@ -298,6 +300,18 @@ impl<'script> WindowDecl<'script> {
     pub const INTERVAL: &'static str = "interval";
     /// `size` setting
     pub const SIZE: &'static str = "size";
+    /// `hop` setting
+    pub const HOP: &'static str = "hop";
+    /// `gap` setting
+    pub const GAP: &'static str = "gap";
+    /// `max_duration` setting
+    pub const MAX_DURATION: &'static str = "max_duration";
+    /// `watermark_delay` setting
+    pub const WATERMARK_DELAY: &'static str = "watermark_delay";
+    /// `allowed_lateness` setting
+    pub const ALLOWED_LATENESS: &'static str = "allowed_lateness";
+    /// `idle_timeout` setting
+    pub const IDLE_TIMEOUT: &'static str = "idle_timeout";
 
     /// Calculate the fully qualified window name
     #[must_use]
@@ -76,6 +76,20 @@ pub trait TremorAggrFn: DowncastSync + Sync + Send {
     fn warning(&self) -> Option<String> {
         None
     }
+    /// Captures the accumulated state of the function so it can be restored
+    /// later on, `None` if the function doesn't support this
+    fn snapshot(&self) -> Option<Value<'static>> {
+        None
+    }
+    /// Restores the accumulated state captured by `snapshot`
+    ///
+    /// # Errors
+    /// if the state is invalid or the function doesn't support snapshots
+    fn restore(&mut self, _state: &Value) -> FResult<()> {
+        Err(FunctionError::Error(Box::new(Error::from(
+            "aggregate function doesn't support snapshots",
+        ))))
+    }
 }
 impl_downcast!(sync TremorAggrFn);
 
@@ -757,6 +771,21 @@ impl TremorAggrFnWrapper {
         use std::borrow::Borrow;
         self.fun.merge(src.fun.borrow())
     }
+
+    /// Captures the accumulated state of the function,
+    /// `None` if the function doesn't support this
+    #[must_use]
+    pub fn snapshot(&self) -> Option<Value<'static>> {
+        self.fun.snapshot()
+    }
+
+    /// Restores the accumulated state captured by `snapshot`
+    ///
+    /// # Errors
+    /// if the state is invalid or the function doesn't support snapshots
+    pub fn restore(&mut self, state: &Value) -> FResult<()> {
+        self.fun.restore(state)
+    }
 }
 
 #[cfg(not(tarpaulin_include))]
@@ -44,6 +44,23 @@ pub fn ceil(value: f64, scale: i8) -> f64 {
     (value * multiplier).ceil() / multiplier
 }
 
+/// error for a snapshot that can't be restored into `stats::<name>`
+fn invalid_snapshot(name: &str, arity: usize) -> FunctionError {
+    FunctionError::RuntimeError {
+        mfa: mfa("stats", name, arity),
+        error: "invalid snapshot".to_string(),
+    }
+}
+
+/// restores a snapshot of an `Option<f64>`, where `null` stands for `None`
+fn restore_optional_f64(state: &Value) -> Option<Option<f64>> {
+    if state.is_null() {
+        Some(None)
+    } else {
+        state.cast_f64().map(Some)
+    }
+}
+
 #[derive(Clone, Debug, Default)]
 struct Count(i64);
 impl TremorAggrFn for Count {
@@ -76,6 +93,13 @@ impl TremorAggrFn for Count {
     fn arity(&self) -> RangeInclusive<usize> {
         0..=0
     }
+    fn snapshot(&self) -> Option<Value<'static>> {
+        Some(Value::from(self.0))
+    }
+    fn restore(&mut self, state: &Value) -> FResult<()> {
+        self.0 = state.as_i64().ok_or_else(|| invalid_snapshot("count", 0))?;
+        Ok(())
+    }
 }
 
 #[derive(Clone, Debug, Default)]
@@ -128,6 +152,13 @@ impl TremorAggrFn for Sum {
     fn arity(&self) -> RangeInclusive<usize> {
         1..=1
     }
+    fn snapshot(&self) -> Option<Value<'static>> {
+        Some(Value::from(self.0))
+    }
+    fn restore(&mut self, state: &Value) -> FResult<()> {
+        self.0 = state.cast_f64().ok_or_else(|| invalid_snapshot("sum", 1))?;
+        Ok(())
+    }
 }
 
 #[derive(Clone, Debug, Default)]
@@ -188,6 +219,19 @@ impl TremorAggrFn for Mean {
     fn arity(&self) -> RangeInclusive<usize> {
         1..=1
     }
+    fn snapshot(&self) -> Option<Value<'static>> {
+        Some(Value::from(vec![Value::from(self.0), Value::from(self.1)]))
+    }
+    fn restore(&mut self, state: &Value) -> FResult<()> {
+        match state.as_array().map(Vec::as_slice) {
+            Some([n, sum]) => {
+                self.0 = n.as_i64().ok_or_else(|| invalid_snapshot("mean", 1))?;
+                self.1 = sum.cast_f64().ok_or_else(|| invalid_snapshot("mean", 1))?;
+                Ok(())
+            }
+            _ => Err(invalid_snapshot("mean", 1)),
+        }
+    }
 }
 
 #[derive(Clone, Debug, Default)]
@@ -249,6 +293,13 @@ impl TremorAggrFn for Min {
     fn arity(&self) -> RangeInclusive<usize> {
         1..=1
     }
+    fn snapshot(&self) -> Option<Value<'static>> {
+        Some(Value::from(self.0))
+    }
+    fn restore(&mut self, state: &Value) -> FResult<()> {
+        self.0 = restore_optional_f64(state).ok_or_else(|| invalid_snapshot("min", 1))?;
+        Ok(())
+    }
 }
 
 #[derive(Clone, Debug, Default)]
@@ -296,6 +347,13 @@ impl TremorAggrFn for Max {
     fn arity(&self) -> RangeInclusive<usize> {
         1..=1
     }
+    fn snapshot(&self) -> Option<Value<'static>> {
+        Some(Value::from(self.0))
+    }
+    fn restore(&mut self, state: &Value) -> FResult<()> {
+        self.0 = restore_optional_f64(state).ok_or_else(|| invalid_snapshot("max", 1))?;
+        Ok(())
+    }
 }
 
 #[derive(Clone, Debug, Default)]
@@ -372,6 +430,27 @@ impl TremorAggrFn for Var {
     fn arity(&self) -> RangeInclusive<usize> {
         1..=1
     }
+    fn snapshot(&self) -> Option<Value<'static>> {
+        Some(Value::from(vec![
+            Value::from(self.n),
+            Value::from(self.k),
+            Value::from(self.ex),
+            Value::from(self.ex2),
+        ]))
+    }
+    fn restore(&mut self, state: &Value) -> FResult<()> {
+        match state.as_array().map(Vec::as_slice) {
+            Some([n, k, ex, ex2]) => {
+                let f = |v: &Value| v.cast_f64().ok_or_else(|| invalid_snapshot("var", 1));
+                self.n = n.as_u64().ok_or_else(|| invalid_snapshot("var", 1))?;
+                self.k = f(k)?;
+                self.ex = f(ex)?;
+                self.ex2 = f(ex2)?;
+                Ok(())
+            }
+            _ => Err(invalid_snapshot("var", 1)),
+        }
+    }
 }
 
 #[derive(Clone, Debug, Default)]
@@ -407,8 +486,16 @@ impl TremorAggrFn for Stdev {
     fn arity(&self) -> RangeInclusive<usize> {
         1..=1
     }
+    fn snapshot(&self) -> Option<Value<'static>> {
+        self.0.snapshot()
+    }
+    fn restore(&mut self, state: &Value) -> FResult<()> {
+        self.0.restore(state)
+    }
 }
 
+/// `DDSketch` doesn't expose its bins, so unlike the other aggregates `dds`
+/// can't capture its state for checkpoints
 struct Dds {
     histo: Option<DDSketch>,
     cache: Vec<f64>,
@@ -858,6 +945,68 @@ impl TremorAggrFn for Hdr {
     fn arity(&self) -> RangeInclusive<usize> {
         1..=2
     }
+    fn snapshot(&self) -> Option<Value<'static>> {
+        // the histogram is captured as the count of each recorded value
+        let histo = self.histo.as_ref().map(|histo| {
+            histo
+                .iter_recorded()
+                .map(|v| Value::from(vec![v.value_iterated_to(), v.count_at_value()]))
+                .collect::<Vec<_>>()
+        });
+        let percentiles: Vec<_> = self
+            .percentiles
+            .iter()
+            .map(|(pcn, _)| Value::from(pcn.clone()))
+            .collect();
+        Some(Value::from(hashmap! {
+            "histo".into() => Value::from(histo),
+            "cache".into() => Value::from(self.cache.clone()),
+            "percentiles".into() => Value::from(percentiles),
+            "percentiles_set".into() => Value::from(self.percentiles_set),
+            "high_bound".into() => Value::from(self.high_bound),
+        }))
+    }
+    fn restore(&mut self, state: &Value) -> FResult<()> {
+        let invalid = || invalid_snapshot("hdr", 2);
+        self.percentiles = state
+            .get_array("percentiles")
+            .ok_or_else(invalid)?
+            .iter()
+            .map(|pcn| {
+                let pcn = pcn.as_str()?;
+                Some((pcn.to_string(), pcn.parse().ok()?))
+            })
+            .collect::<Option<_>>()
+            .ok_or_else(invalid)?;
+        self.percentiles_set = state.get_bool("percentiles_set").ok_or_else(invalid)?;
+        self.high_bound = state.get_u64("high_bound").ok_or_else(invalid)?;
+        self.cache = state
+            .get_array("cache")
+            .ok_or_else(invalid)?
+            .iter()
+            .map(Value::as_u64)
+            .collect::<Option<_>>()
+            .ok_or_else(invalid)?;
+        self.histo = if let Some(counts) = state.get_array("histo") {
+            let mut histo: Histogram<u64> =
+                Histogram::new_with_bounds(1, self.high_bound(), 2).map_err(|_| invalid())?;
+            histo.auto(true);
+            for count in counts {
+                match count.as_array().map(Vec::as_slice) {
+                    Some([v, n]) => {
+                        let v = v.as_u64().ok_or_else(invalid)?;
+                        let n = n.as_u64().ok_or_else(invalid)?;
+                        histo.record_n(v, n).map_err(|_| invalid())?;
+                    }
+                    _ => return Err(invalid()),
+                }
+            }
+            Some(histo)
+        } else {
+            None
+        };
+        Ok(())
+    }
 }
 
 pub fn load_aggr(registry: &mut AggrRegistry) {
@@ -933,6 +1082,54 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn snapshot_and_restore() -> Result<()> {
+        let one = Value::from(1);
+        let four = Value::from(4);
+        let mut a = Var::default();
+        a.init();
+        a.accumulate(&[&one])?;
+        a.accumulate(&[&four])?;
+        let mut b = Var::default();
+        b.init();
+        b.restore(&a.snapshot().unwrap_or_default())?;
+        assert_eq!(a.emit()?, b.emit()?);
+
+        let mut a = Min::default();
+        a.init();
+        let mut b = Min::default();
+        b.restore(&a.snapshot().unwrap_or_default())?;
+        b.accumulate(&[&four])?;
+        assert_eq!(b.emit()?, 4.0);
+
+        let mut a = Mean::default();
+        a.init();
+        a.accumulate(&[&one])?;
+        a.accumulate(&[&four])?;
+        let mut b = Mean::default();
+        b.restore(&a.snapshot().unwrap_or_default())?;
+        assert_eq!(b.emit()?, 2.5);
+        assert!(b.restore(&Value::from("snot")).is_err());
+
+        let mut a = Hdr::default();
+        a.init();
+        for v in 1..=100_u64 {
+            a.accumulate(&[&Value::from(v)])?;
+        }
+        // turns the cache into a histogram
+        a.emit()?;
+        a.accumulate(&[&four])?;
+        let mut b = Hdr::default();
+        b.init();
+        b.restore(&a.snapshot().unwrap_or_default())?;
+        assert!(b.histo.is_some());
+        assert_eq!(a.emit()?, b.emit()?);
+        assert!(b.restore(&Value::null()).is_err());
+
+        assert!(Dds::default().snapshot().is_none());
+        Ok(())
+    }
+
     #[test]
     fn min() -> Result<()> {
         let mut a = Min::default();
@@ -12,12 +12,22 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use crate::registry::{Aggr as AggrRegistry, FResult, TremorAggrFn, TremorAggrFnWrapper};
+use crate::registry::{
+    mfa, Aggr as AggrRegistry, FResult, FunctionError, TremorAggrFn, TremorAggrFnWrapper,
+};
 
 use crate::prelude::*;
 
 use std::ops::RangeInclusive;
 
+/// error for a snapshot that can't be restored into `win::<name>`
+fn invalid_snapshot(name: &str) -> FunctionError {
+    FunctionError::RuntimeError {
+        mfa: mfa("win", name, 1),
+        error: "invalid snapshot".to_string(),
+    }
+}
+
 #[derive(Clone, Debug, Default)]
 struct First(Option<Value<'static>>);
 impl TremorAggrFn for First {
@@ -59,6 +69,18 @@ impl TremorAggrFn for First {
     fn arity(&self) -> RangeInclusive<usize> {
         1..=1
     }
+    fn snapshot(&self) -> Option<Value<'static>> {
+        // an empty array for no value, to tell it apart from `null`
+        Some(Value::from(self.0.iter().cloned().collect::<Vec<_>>()))
+    }
+    fn restore(&mut self, state: &Value) -> FResult<()> {
+        match state.as_array().map(Vec::as_slice) {
+            Some([]) => self.0 = None,
+            Some([v]) => self.0 = Some(v.clone_static()),
+            _ => return Err(invalid_snapshot("first")),
+        }
+        Ok(())
+    }
 }
 
 #[derive(Clone, Debug, Default)]
@@ -100,6 +122,18 @@ impl TremorAggrFn for Last {
     fn arity(&self) -> RangeInclusive<usize> {
         1..=1
     }
+    fn snapshot(&self) -> Option<Value<'static>> {
+        // an empty array for no value, to tell it apart from `null`
+        Some(Value::from(self.0.iter().cloned().collect::<Vec<_>>()))
+    }
+    fn restore(&mut self, state: &Value) -> FResult<()> {
+        match state.as_array().map(Vec::as_slice) {
+            Some([]) => self.0 = None,
+            Some([v]) => self.0 = Some(v.clone_static()),
+            _ => return Err(invalid_snapshot("last")),
+        }
+        Ok(())
+    }
 }
 
 #[derive(Clone, Debug, Default)]
@@ -142,6 +176,17 @@ impl TremorAggrFn for CollectFlattened {
     fn arity(&self) -> RangeInclusive<usize> {
         1..=1
     }
+    fn snapshot(&self) -> Option<Value<'static>> {
+        Some(Value::from(self.0.clone()))
+    }
+    fn restore(&mut self, state: &Value) -> FResult<()> {
+        if let Value::Array(values) = state.clone_static() {
+            self.0 = values;
+            Ok(())
+        } else {
+            Err(invalid_snapshot("collect_flattened"))
+        }
+    }
     fn warning(&self) -> Option<String> {
         Some(String::from(
             "Collect functions are very expensive memory wise, try avoiding them.",
@@ -189,6 +234,17 @@ impl TremorAggrFn for CollectNested {
     fn arity(&self) -> RangeInclusive<usize> {
         1..=1
     }
+    fn snapshot(&self) -> Option<Value<'static>> {
+        Some(Value::from(self.0.clone()))
+    }
+    fn restore(&mut self, state: &Value) -> FResult<()> {
+        if let Value::Array(values) = state.clone_static() {
+            self.0 = values;
+            Ok(())
+        } else {
+            Err(invalid_snapshot("collect_nested"))
+        }
+    }
     fn warning(&self) -> Option<String> {
         Some(String::from(
             "Collect functions are very expensive memory wise, try avoiding them.",
@@ -243,6 +299,32 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn snapshot_and_restore() -> Result<()> {
+        let one = Value::from(1);
+        let two = Value::from(2);
+        let mut a = First::default();
+        a.init();
+        let mut b = First::default();
+        b.restore(&a.snapshot().unwrap_or_default())?;
+        b.accumulate(&[&one])?;
+        assert_eq!(b.emit()?, 1);
+        let mut c = First::default();
+        c.restore(&b.snapshot().unwrap_or_default())?;
+        c.accumulate(&[&two])?;
+        assert_eq!(c.emit()?, 1);
+
+        let mut a = CollectNested::default();
+        a.init();
+        a.accumulate(&[&one])?;
+        let mut b = CollectNested::default();
+        b.restore(&a.snapshot().unwrap_or_default())?;
+        b.accumulate(&[&two])?;
+        assert_eq!(b.emit()?, Value::from(vec![1, 2]));
+        assert!(b.restore(&Value::null()).is_err());
+        Ok(())
+    }
+
     #[test]
     fn collect() -> Result<()> {
         let mut a = CollectFlattened::default();
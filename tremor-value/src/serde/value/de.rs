@@ -343,6 +343,22 @@ impl<'de> Visitor<'de> for ValueVisitor {
     {
         Ok(Value::Bytes(value.into()))
     }
+
+    #[cfg_attr(not(feature = "no-inline"), inline)]
+    fn visit_bytes<E>(self, value: &[u8]) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        Ok(Value::Bytes(Cow::owned(value.to_vec())))
+    }
+
+    #[cfg_attr(not(feature = "no-inline"), inline)]
+    fn visit_byte_buf<E>(self, value: Vec<u8>) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        Ok(Value::Bytes(Cow::owned(value)))
+    }
     /*
 
     #[cfg_attr(not(feature = "no-inline"), inline)]